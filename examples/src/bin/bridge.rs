@@ -15,27 +15,35 @@
  *    limitations under the License.
  */
 
-//! An example Matter Bridge that bridges two fictitious non-Matter devices as On-Off (Lamp) Matter devices.
+//! An example Matter Bridge that bridges fictitious non-Matter devices as On-Off (Lamp) Matter devices.
 //! The example operates over Ethernet for simplicity, but the concrete network protocol is orthogonal to
 //! the notion of a Matter bridge anyway.
+//!
+//! The bridged devices come and go at runtime, as is usually the case with e.g. Zigbee or BLE bridges.
+
+#![recursion_limit = "256"]
 
 use core::pin::pin;
 
 use std::net::UdpSocket;
 
-use embassy_futures::select::select4;
+use embassy_futures::select::{select, select4};
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::{Duration, Timer};
+
+use log::info;
 
 use rs_matter::core::{Matter, MATTER_PORT};
+use rs_matter::data_model::bridge::{Bridge, BridgedHandler, ClusterHandler as _};
 use rs_matter::data_model::device_types::{
     DEV_TYPE_AGGREGATOR, DEV_TYPE_BRIDGED_NODE, DEV_TYPE_ON_OFF_LIGHT,
 };
 use rs_matter::data_model::networks::unix::UnixNetifs;
 use rs_matter::data_model::objects::{
-    Async, AsyncHandler, AsyncMetadata, Cluster, Dataver, EmptyHandler, Endpoint, EpClMatcher,
-    Node, ReadContext,
+    Async, AsyncHandler, AsyncMetadata, Cluster, Dataver, DeviceType, EmptyHandler, Endpoint,
+    EndptId, EpClMatcher,
 };
-use rs_matter::data_model::on_off::{ClusterHandler as _, OnOffHandler};
+use rs_matter::data_model::on_off::{self, ClusterHandler as _, OnOffHandler};
 use rs_matter::data_model::root_endpoint;
 use rs_matter::data_model::sdm::net_comm::NetworkType;
 use rs_matter::data_model::subscriptions::Subscriptions;
@@ -48,17 +56,57 @@ use rs_matter::respond::DefaultResponder;
 use rs_matter::transport::core::MATTER_SOCKET_BIND_ADDR;
 use rs_matter::utils::select::Coalesce;
 use rs_matter::utils::storage::pooled::PooledBuffers;
-use rs_matter::{clusters, devices, test_device, with};
-
-pub use bridged_device_basic_information::HandlerAdaptor as BridgedHandlerAdaptor;
-
-use crate::bridged_device_basic_information::ClusterHandler as _;
-
-rs_matter::import!(BridgedDeviceBasicInformation);
+use rs_matter::{clusters, devices, test_device};
 
 #[path = "../common/mdns.rs"]
 mod mdns;
 
+/// The handler of the clusters of a bridged lamp, other than the Descriptor and the
+/// Bridged Device Basic Information ones, which are handled by the bridge itself.
+///
+/// Note that we are re-using here the ready-made `OnOffHandler` from `rs-matter` for demoing purposes.
+/// In production setups, user is expected to define their own handler for their bridge device cluster(s)
+/// which is likely to do remote calls over a proprietary protocol so as to e.g. retrieve the state of
+/// the lamp, or to switch it on/off.
+type LampHandler = Async<on_off::HandlerAdaptor<OnOffHandler<'static>>>;
+
+/// Our bridge, with up to 8 endpoints, 2 of which are the root and the aggregator endpoints.
+type LampBridge = Bridge<'static, 8, NoopRawMutex, LampHandler>;
+
+/// The ID of the aggregator endpoint.
+const AGGREGATOR_ENDPOINT_ID: EndptId = 1;
+
+/// The static endpoints of our Matter device.
+const ENDPOINTS: &[Endpoint<'static>] = &[
+    // The root (0) endpoint - as usual.
+    root_endpoint::root_endpoint(NetworkType::Ethernet),
+    // When the node contains one or more bridged endpoints, we need
+    // at least one endpoint that would serve as the aggregator endpoint and will thus
+    // enumerate all bridged endpoints which are bridged e.g. using the same technology.
+    //
+    // Optionally, the aggregator can declare and implement the `Actions` cluster
+    // (see below in the handler the meaning of this cluster).
+    // In any case, this endpoint must be of the Aggregator device type.
+    Endpoint::new(
+        AGGREGATOR_ENDPOINT_ID,
+        devices!(DEV_TYPE_AGGREGATOR),
+        clusters!(desc::DescHandler::CLUSTER),
+    ),
+];
+
+/// The device types of a bridged lamp.
+const LAMP_DEVICE_TYPES: &[DeviceType] = devices!(DEV_TYPE_ON_OFF_LIGHT, DEV_TYPE_BRIDGED_NODE);
+
+/// The clusters of a bridged lamp.
+///
+/// In addition to the usual clusters, every bridged endpoint needs to implement the
+/// "Bridged Device Basic Information" cluster as well.
+const LAMP_CLUSTERS: &[Cluster<'static>] = clusters!(
+    desc::DescHandler::CLUSTER,
+    BridgedHandler::CLUSTER,
+    OnOffHandler::CLUSTER
+);
+
 fn main() -> Result<(), Error> {
     env_logger::init_from_env(
         env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
@@ -82,8 +130,21 @@ fn main() -> Result<(), Error> {
     // Create the subscriptions
     let subscriptions = Subscriptions::<3>::new();
 
-    // Assemble our Data Model handler by composing the predefined Root Endpoint handler with our custom Speaker handler
-    let dm_handler = dm_handler(&matter);
+    // Create the bridge, which holds the static endpoints, as well as the bridged ones
+    let bridge = LampBridge::new(0, ENDPOINTS, AGGREGATOR_ENDPOINT_ID, matter.rand())?;
+
+    // Create and load the persister
+    // The endpoint IDs of the bridged devices are persisted, so that the devices keep their
+    // endpoint IDs across reboots
+    let mut psm: Psm<4096> = Psm::new();
+
+    let dir = std::env::temp_dir().join("rs-matter");
+
+    psm.load(&dir, &matter)?;
+    psm.load_bridge(&dir, &bridge)?;
+
+    // Assemble our Data Model handler by composing the predefined Root Endpoint handler with the bridge
+    let dm_handler = dm_handler(&matter, &bridge);
 
     // Create a default responder capable of handling up to 3 subscriptions
     // All other subscription requests will be turned down with "resource exhausted"
@@ -93,6 +154,66 @@ fn main() -> Result<(), Error> {
     // Clients trying to open more exchanges than the ones currently running will get "I'm busy, please try again later"
     let mut respond = pin!(responder.run::<4, 4>());
 
+    // This is a sample code that simulates bridged devices joining and leaving the non-Matter network,
+    // as well as becoming unreachable.
+    // Changes will be properly communicated to the Matter controllers, thanks to subscriptions
+    let mut devices = pin!(async {
+        let lamp = || Async(OnOffHandler::new(Dataver::new_rand(matter.rand())).adapt());
+
+        bridge
+            .add(
+                &subscriptions,
+                "lamp-1",
+                LAMP_DEVICE_TYPES,
+                LAMP_CLUSTERS,
+                lamp(),
+            )
+            .await?;
+
+        let lamp2 = bridge
+            .add(
+                &subscriptions,
+                "lamp-2",
+                LAMP_DEVICE_TYPES,
+                LAMP_CLUSTERS,
+                lamp(),
+            )
+            .await?;
+
+        let mut lamp3 = None;
+
+        loop {
+            Timer::after(Duration::from_secs(30)).await;
+
+            if let Some(endpoint_id) = lamp3.take() {
+                bridge.remove(&subscriptions, endpoint_id).await?;
+
+                info!("Lamp 3 removed from endpoint {endpoint_id}");
+            } else {
+                let endpoint_id = bridge
+                    .add(
+                        &subscriptions,
+                        "lamp-3",
+                        LAMP_DEVICE_TYPES,
+                        LAMP_CLUSTERS,
+                        lamp(),
+                    )
+                    .await?;
+
+                lamp3 = Some(endpoint_id);
+
+                info!("Lamp 3 added on endpoint {endpoint_id}");
+            }
+
+            let reachable = !bridge.is_reachable(lamp2).await?;
+            bridge
+                .set_reachable(&subscriptions, lamp2, reachable)
+                .await?;
+
+            info!("Lamp 2 reachable: {reachable}");
+        }
+    });
+
     // Create the Matter UDP socket
     let socket = async_io::Async::<UdpSocket>::bind(MATTER_SOCKET_BIND_ADDR)?;
 
@@ -100,177 +221,66 @@ fn main() -> Result<(), Error> {
     let mut mdns = pin!(mdns::run_mdns(&matter));
     let mut transport = pin!(matter.run(&socket, &socket, DiscoveryCapabilities::IP));
 
-    // Create, load and run the persister
-    let mut psm: Psm<4096> = Psm::new();
-
-    let dir = std::env::temp_dir().join("rs-matter");
-
-    psm.load(&dir, &matter)?;
-
-    let mut persist = pin!(psm.run(dir, &matter));
+    // Run the persister
+    let mut persist = pin!(psm.run_with_bridge(&dir, &matter, &bridge));
 
     // Combine all async tasks in a single one
-    let all = select4(&mut transport, &mut mdns, &mut persist, &mut respond);
+    let all = select4(
+        &mut transport,
+        &mut mdns,
+        &mut persist,
+        select(&mut respond, &mut devices).coalesce(),
+    );
 
     // Run with a simple `block_on`. Any local executor would do.
     futures_lite::future::block_on(all.coalesce())
 }
 
-/// The Node meta-data describing our Matter device.
-const NODE: Node<'static> = Node {
-    id: 0,
-    endpoints: &[
-        // The root (0) endpoint - as usual.
-        root_endpoint::root_endpoint(NetworkType::Ethernet),
-        // When the node contains one or more bridged endpoints, we need
-        // at least one endpoint that would serve as the aggregator endpoint and will thus
-        // enumerate all bridged endpoints which are bridged e.g. using the same technology.
-        //
-        // Optionally, the aggregator can declare and implement the `Actions` cluster
-        // (see below in the handler the meaning of this cluster).
-        // In any case, this endpoint must be of the Aggregator device type.
-        Endpoint {
-            id: 1,
-            device_types: devices!(DEV_TYPE_AGGREGATOR),
-            clusters: clusters!(desc::DescHandler::CLUSTER),
-        },
-        // This is the first bridged device. It could have any ID as long as it is not 0 or 1.
-        //
-        // The Matter Bridge needs to declare as many endpoints as there are bridged devices.
-        // If the bridged devices can vary over time, rather than using a static `Node`
-        // definition, one could use an `(Async)MetaData` implementation that returns a node
-        // which references the endpoints off from a `Vec` or a `heapless::Vec`.
-        Endpoint {
-            id: 2,
-            device_types: devices!(DEV_TYPE_ON_OFF_LIGHT, DEV_TYPE_BRIDGED_NODE),
-            clusters: clusters!(
-                desc::DescHandler::CLUSTER,
-                BridgedHandler::CLUSTER,
-                OnOffHandler::CLUSTER
-            ),
-        },
-        // This is the second bridged device.
-        //
-        // It could have a completely different device type, yet here - for simplicity - we
-        // just declare another lamp.
-        Endpoint {
-            id: 3,
-            device_types: devices!(DEV_TYPE_ON_OFF_LIGHT, DEV_TYPE_BRIDGED_NODE),
-            clusters: clusters!(
-                desc::DescHandler::CLUSTER,
-                BridgedHandler::CLUSTER,
-                OnOffHandler::CLUSTER
-            ),
-        },
-    ],
-};
-
 /// The Data Model handler + meta-data for our Matter Bridge.
-fn dm_handler(matter: &Matter<'_>) -> impl AsyncMetadata + AsyncHandler + 'static {
+fn dm_handler<'a>(
+    matter: &'a Matter<'_>,
+    bridge: &'a LampBridge,
+) -> impl AsyncMetadata + AsyncHandler + 'a {
     (
-        NODE,
+        // The bridge provides the meta-data of the node, as its endpoints change at runtime
+        bridge,
         root_endpoint::with_eth(
             &(),
             &UnixNetifs,
             matter.rand(),
-            root_endpoint::with_sys(
+            // The bridge is also the parts matcher of the root endpoint, so that the
+            // dataver of its parts changes when bridged devices are added or removed
+            root_endpoint::with_sys_matching(
                 &false,
+                bridge,
                 matter.rand(),
                 EmptyHandler
                     // The next chain is the handler for the "aggregator" endpoint 1.
                     //
                     // Note how the descriptor cluster is a bit different compared to the normal ones.
-                    // The `Aggregator` descriptor cluster takes care of declaring all bridged endpoints
-                    // as such.
+                    // The bridge takes care of declaring all bridged endpoints as parts of the aggregator.
                     //
                     // Implementing the "Actions" cluster (and declaring it in the ep1 meta-data)
                     // would allow one to designate locations/areas to the bridged devices. However, this is
                     // not yet supported by Google home and Apple, as per
                     // https://www.1home.io/docs/en/server/configure-devices#manage-rooms
                     .chain(
-                        EpClMatcher::new(Some(1), Some(desc::DescHandler::CLUSTER.id)),
+                        EpClMatcher::new(
+                            Some(AGGREGATOR_ENDPOINT_ID),
+                            Some(desc::DescHandler::CLUSTER.id),
+                        ),
                         Async(
-                            desc::DescHandler::new_aggregator(Dataver::new_rand(matter.rand()))
-                                .adapt(),
+                            desc::DescHandler::new_matching(
+                                Dataver::new_rand(matter.rand()),
+                                bridge,
+                            )
+                            .adapt(),
                         ),
                     )
-                    // The following chains are the handlers for the bridged devices corresponding to ep 2 and ep3.
-                    //
-                    // In addition to the usual clusters, every bridged endpoint needs to implement the
-                    // "Bridged" cluster as well.
-                    //
-                    // Note also that we are re-using here the ready-made `OnOffHandler` from `rs-matter` for demoing purposes.
-                    // In production setups, user is expected to define their own handler for their bridge device cluster(s)
-                    // which is likely to do remote calls over a proprietary protocol so as to e.g. retrieve the state of
-                    // the lamp, or to switch it on/off.
-                    .chain(
-                        EpClMatcher::new(Some(2), Some(desc::DescHandler::CLUSTER.id)),
-                        Async(desc::DescHandler::new(Dataver::new_rand(matter.rand())).adapt()),
-                    )
-                    .chain(
-                        EpClMatcher::new(Some(2), Some(OnOffHandler::CLUSTER.id)),
-                        Async(OnOffHandler::new(Dataver::new_rand(matter.rand())).adapt()),
-                    )
-                    .chain(
-                        EpClMatcher::new(Some(2), Some(BridgedHandler::CLUSTER.id)),
-                        Async(BridgedHandler::new(Dataver::new_rand(matter.rand())).adapt()),
-                    )
-                    .chain(
-                        EpClMatcher::new(Some(3), Some(desc::DescHandler::CLUSTER.id)),
-                        Async(desc::DescHandler::new(Dataver::new_rand(matter.rand())).adapt()),
-                    )
-                    .chain(
-                        EpClMatcher::new(Some(3), Some(OnOffHandler::CLUSTER.id)),
-                        Async(OnOffHandler::new(Dataver::new_rand(matter.rand())).adapt()),
-                    )
-                    .chain(
-                        EpClMatcher::new(Some(3), Some(BridgedHandler::CLUSTER.id)),
-                        Async(BridgedHandler::new(Dataver::new_rand(matter.rand())).adapt()),
-                    ),
+                    // The bridge handles all bridged endpoints, by delegating to the handlers
+                    // of the bridged devices
+                    .chain(bridge, bridge),
             ),
         ),
     )
 }
-
-#[derive(Clone, Debug)]
-pub struct BridgedHandler {
-    dataver: Dataver,
-}
-
-impl BridgedHandler {
-    pub const fn new(dataver: Dataver) -> Self {
-        Self { dataver }
-    }
-
-    pub const fn adapt(self) -> bridged_device_basic_information::HandlerAdaptor<Self> {
-        bridged_device_basic_information::HandlerAdaptor(self)
-    }
-}
-
-impl bridged_device_basic_information::ClusterHandler for BridgedHandler {
-    const CLUSTER: Cluster<'static> = bridged_device_basic_information::FULL_CLUSTER
-        .with_revision(1)
-        .with_features(0)
-        .with_attrs(with!(required))
-        .with_cmds(with!());
-
-    fn dataver(&self) -> u32 {
-        self.dataver.get()
-    }
-
-    fn dataver_changed(&self) {
-        self.dataver.changed();
-    }
-
-    fn reachable(&self, _ctx: &ReadContext<'_>) -> Result<bool, Error> {
-        // This is the only mandatory attribute.
-        //
-        // We always report that the bridged device is reachable,
-        // however - in production setup - the user might want to implement
-        // true reachability logic here.
-        Ok(true)
-    }
-
-    // Note that at least the `node_label` optional attribute is also good to implement,
-    // so that way users can name the bridged devices their own way, using the Matter controller.
-}
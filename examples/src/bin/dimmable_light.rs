@@ -0,0 +1,278 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! An example Matter device that implements a Dimmable Light (the On/Off and Level Control clusters) over Ethernet.
+
+use core::pin::pin;
+
+use embassy_futures::select::{select3, select4, Either3};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+use log::info;
+
+use rs_matter::core::{Matter, MATTER_PORT};
+use rs_matter::data_model::core::IMBuffer;
+use rs_matter::data_model::device_types::DEV_TYPE_DIMMABLE_LIGHT;
+use rs_matter::data_model::level_control::{self, ClusterHandler as _};
+use rs_matter::data_model::networks::unix::UnixNetifs;
+use rs_matter::data_model::objects::{
+    Async, AsyncHandler, AsyncMetadata, Dataver, EmptyHandler, Endpoint, EpClMatcher, Node,
+};
+use rs_matter::data_model::on_off::{self, ClusterHandler as _};
+use rs_matter::data_model::root_endpoint;
+use rs_matter::data_model::sdm::net_comm::NetworkType;
+use rs_matter::data_model::subscriptions::Subscriptions;
+use rs_matter::data_model::system_model::desc::{self, ClusterHandler as _};
+use rs_matter::error::Error;
+use rs_matter::mdns::MdnsService;
+use rs_matter::pairing::DiscoveryCapabilities;
+use rs_matter::persist::Psm;
+use rs_matter::respond::DefaultResponder;
+use rs_matter::transport::core::MATTER_SOCKET_BIND_ADDR;
+use rs_matter::transport::network::udp::PktInfoUdpSocket;
+use rs_matter::utils::init::InitMaybeUninit;
+use rs_matter::utils::select::Coalesce;
+use rs_matter::utils::storage::pooled::PooledBuffers;
+use rs_matter::{clusters, devices, test_device};
+
+use static_cell::StaticCell;
+
+#[path = "../common/mdns.rs"]
+mod mdns;
+
+// Statically allocate in BSS the bigger objects
+// `rs-matter` supports efficient initialization of BSS objects (with `init`)
+// as well as just allocating the objects on-stack or on the heap.
+static MATTER: StaticCell<Matter> = StaticCell::new();
+static BUFFERS: StaticCell<PooledBuffers<10, NoopRawMutex, IMBuffer>> = StaticCell::new();
+static SUBSCRIPTIONS: StaticCell<Subscriptions<3>> = StaticCell::new();
+static PSM: StaticCell<Psm<4096>> = StaticCell::new();
+
+fn main() -> Result<(), Error> {
+    let thread = std::thread::Builder::new()
+        // Increase the stack size until the example can work without stack blowups.
+        // Note that the used stack size increases exponentially by lowering the level of compiler optimizations,
+        // as lower optimization settings prevent the Rust compiler from inlining constructor functions
+        // which often results in (unnecessary) memory moves and increased stack utilization:
+        // e.g., an opt-level of "0" will require a several times' larger stack.
+        //
+        // Optimizing/lowering `rs-matter` memory consumption is an ongoing topic.
+        .stack_size(55 * 1024)
+        .spawn(run)
+        .unwrap();
+
+    thread.join().unwrap()
+}
+
+fn run() -> Result<(), Error> {
+    env_logger::init_from_env(
+        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "debug"),
+    );
+
+    // NOTE: chip-tool tests need the log to go to `stdout` instead
+    // env_logger::builder()
+    //     .format(|buf, record| {
+    //         use std::io::Write;
+    //         writeln!(buf, "{}: {}", record.level(), record.args())
+    //     })
+    //     .target(env_logger::Target::Stdout)
+    //     .filter_level(::log::LevelFilter::Info)
+    //     .init();
+
+    info!(
+        "Matter memory: Matter (BSS)={}B, IM Buffers (BSS)={}B, Subscriptions (BSS)={}B",
+        core::mem::size_of::<Matter>(),
+        core::mem::size_of::<PooledBuffers<10, NoopRawMutex, IMBuffer>>(),
+        core::mem::size_of::<Subscriptions<3>>()
+    );
+
+    let matter = MATTER.uninit().init_with(Matter::init(
+        &test_device::TEST_DEV_DET,
+        test_device::TEST_DEV_COMM,
+        &test_device::TEST_DEV_ATT,
+        MdnsService::Builtin,
+        rs_matter::utils::epoch::sys_epoch,
+        rs_matter::utils::rand::sys_rand,
+        MATTER_PORT,
+    ));
+
+    // Need to call this once
+    matter.initialize_transport_buffers()?;
+
+    // Create the transport buffers
+    let buffers = BUFFERS.uninit().init_with(PooledBuffers::init(0));
+
+    // Create the subscriptions
+    let subscriptions = SUBSCRIPTIONS.uninit().init_with(Subscriptions::init());
+
+    // Our on-off cluster
+    let mut on_off = on_off::OnOffHandler::new(Dataver::new_rand(matter.rand()));
+    on_off.set_hooks(&LampHooks);
+
+    // Our level control cluster, coupled with the on-off cluster
+    let mut level_control =
+        level_control::LevelControlHandler::new(Dataver::new_rand(matter.rand()));
+    level_control.set_hooks(&LampHooks);
+    level_control.set_on_off(&on_off);
+
+    on_off.set_level_control(&level_control);
+
+    // Assemble our Data Model handler by composing the predefined Root Endpoint handler with the On/Off and Level Control handlers
+    let dm_handler = dm_handler(matter, &on_off, &level_control);
+
+    // Create a default responder capable of handling up to 3 subscriptions
+    // All other subscription requests will be turned down with "resource exhausted"
+    let responder = DefaultResponder::new(matter, buffers, subscriptions, dm_handler);
+    info!(
+        "Responder memory: Responder (stack)={}B, Runner fut (stack)={}B",
+        core::mem::size_of_val(&responder),
+        core::mem::size_of_val(&responder.run::<4, 4>())
+    );
+
+    // Run the responder with up to 4 handlers (i.e. 4 exchanges can be handled simultaneously)
+    // Clients trying to open more exchanges than the ones currently running will get "I'm busy, please try again later"
+    let mut respond = pin!(responder.run::<4, 4>());
+
+    // Run the `OnTime` and `OffWaitTime` timers of the On/Off cluster, and the level transitions of the Level Control cluster
+    let mut timers = pin!(on_off.run(|| subscriptions.notify_changed()));
+    let mut transitions = pin!(level_control.run(|| subscriptions.notify_changed()));
+
+    // Create, load and run the persister
+    let socket = PktInfoUdpSocket::<16>::bind(MATTER_SOCKET_BIND_ADDR)?;
+
+    info!(
+        "Transport memory: Transport fut (stack)={}B, mDNS fut (stack)={}B",
+        core::mem::size_of_val(&matter.run(&socket, &socket, DiscoveryCapabilities::IP)),
+        core::mem::size_of_val(&mdns::run_mdns(matter))
+    );
+
+    // Run the Matter and mDNS transports
+    let mut mdns = pin!(mdns::run_mdns(matter));
+    let mut transport = pin!(matter.run(&socket, &socket, DiscoveryCapabilities::IP));
+
+    // Create, load and run the persister
+    let psm = PSM.uninit().init_with(Psm::init());
+
+    info!(
+        "Persist memory: Persist (BSS)={}B, Persist fut (stack)={}B",
+        core::mem::size_of::<Psm<4096>>(),
+        core::mem::size_of_val(&psm.run(std::env::temp_dir().join("rs-matter"), matter))
+    );
+
+    let dir = std::env::temp_dir().join("rs-matter");
+
+    psm.load(&dir, matter)?;
+    // Applies the `StartUpOnOff` and `StartUpCurrentLevel` behaviors too
+    psm.load_on_off(&dir, &on_off)?;
+    psm.load_level_control(&dir, &level_control)?;
+
+    let mut persist = pin!(async {
+        loop {
+            match select3(
+                matter.wait_persist(),
+                on_off.wait_persist(),
+                level_control.wait_persist(),
+            )
+            .await
+            {
+                Either3::First(_) => psm.store(&dir, matter)?,
+                Either3::Second(_) => psm.store_on_off(&dir, &on_off)?,
+                Either3::Third(_) => psm.store_level_control(&dir, &level_control)?,
+            }
+        }
+    });
+
+    // Combine all async tasks in a single one
+    let all = select4(
+        &mut transport,
+        &mut mdns,
+        &mut persist,
+        select3(&mut respond, &mut timers, &mut transitions).coalesce(),
+    );
+
+    // Run with a simple `block_on`. Any local executor would do.
+    futures_lite::future::block_on(all.coalesce())
+}
+
+/// The hardware of our lamp, which just logs the state changes.
+struct LampHooks;
+
+impl on_off::OnOffHooks for LampHooks {
+    fn on_off_changed(&self, on: bool) {
+        info!("Lamp is now {}", if on { "on" } else { "off" });
+    }
+}
+
+impl level_control::LevelControlHooks for LampHooks {
+    fn set_level(&self, level: u8) {
+        info!("Lamp level is now {level}");
+    }
+}
+
+/// The Node meta-data describing our Matter device.
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[
+        root_endpoint::root_endpoint(NetworkType::Ethernet),
+        Endpoint::new(
+            1,
+            devices!(DEV_TYPE_DIMMABLE_LIGHT),
+            clusters!(
+                desc::DescHandler::CLUSTER,
+                on_off::OnOffHandler::CLUSTER,
+                level_control::LevelControlHandler::CLUSTER
+            ),
+        ),
+    ],
+};
+
+/// The Data Model handler + meta-data for our Matter device.
+/// The handler is the root endpoint 0 handler plus the on-off and level control handlers and their descriptor.
+fn dm_handler<'a>(
+    matter: &'a Matter<'a>,
+    on_off: &'a on_off::OnOffHandler<'a>,
+    level_control: &'a level_control::LevelControlHandler<'a>,
+) -> impl AsyncMetadata + AsyncHandler + 'a {
+    (
+        NODE,
+        root_endpoint::with_eth(
+            &(),
+            &UnixNetifs,
+            matter.rand(),
+            root_endpoint::with_sys(
+                &false,
+                matter.rand(),
+                EmptyHandler
+                    .chain(
+                        EpClMatcher::new(Some(1), Some(desc::DescHandler::CLUSTER.id)),
+                        Async(desc::DescHandler::new(Dataver::new_rand(matter.rand())).adapt()),
+                    )
+                    .chain(
+                        EpClMatcher::new(Some(1), Some(on_off::OnOffHandler::CLUSTER.id)),
+                        Async(on_off::HandlerAdaptor(on_off)),
+                    )
+                    .chain(
+                        EpClMatcher::new(
+                            Some(1),
+                            Some(level_control::LevelControlHandler::CLUSTER.id),
+                        ),
+                        Async(level_control::HandlerAdaptor(level_control)),
+                    ),
+            ),
+        ),
+    )
+}
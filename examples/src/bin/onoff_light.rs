@@ -19,9 +19,7 @@
 
 use core::pin::pin;
 
-use std::net::UdpSocket;
-
-use embassy_futures::select::{select, select4};
+use embassy_futures::select::{select3, select4};
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_time::{Duration, Timer};
 
@@ -45,6 +43,7 @@ use rs_matter::pairing::DiscoveryCapabilities;
 use rs_matter::persist::Psm;
 use rs_matter::respond::DefaultResponder;
 use rs_matter::transport::core::MATTER_SOCKET_BIND_ADDR;
+use rs_matter::transport::network::udp::PktInfoUdpSocket;
 use rs_matter::utils::init::InitMaybeUninit;
 use rs_matter::utils::select::Coalesce;
 use rs_matter::utils::storage::pooled::PooledBuffers;
@@ -121,7 +120,8 @@ fn run() -> Result<(), Error> {
     let subscriptions = SUBSCRIPTIONS.uninit().init_with(Subscriptions::init());
 
     // Our on-off cluster
-    let on_off = on_off::OnOffHandler::new(Dataver::new_rand(matter.rand()));
+    let mut on_off = on_off::OnOffHandler::new(Dataver::new_rand(matter.rand()));
+    on_off.set_hooks(&LampHooks);
 
     // Assemble our Data Model handler by composing the predefined Root Endpoint handler with the On/Off handler
     let dm_handler = dm_handler(matter, &on_off);
@@ -152,8 +152,11 @@ fn run() -> Result<(), Error> {
         }
     });
 
+    // Run the `OnTime` and `OffWaitTime` timers of the On/Off cluster
+    let mut timers = pin!(on_off.run(|| subscriptions.notify_changed()));
+
     // Create, load and run the persister
-    let socket = async_io::Async::<UdpSocket>::bind(MATTER_SOCKET_BIND_ADDR)?;
+    let socket = PktInfoUdpSocket::<16>::bind(MATTER_SOCKET_BIND_ADDR)?;
 
     info!(
         "Transport memory: Transport fut (stack)={}B, mDNS fut (stack)={}B",
@@ -177,31 +180,42 @@ fn run() -> Result<(), Error> {
     let dir = std::env::temp_dir().join("rs-matter");
 
     psm.load(&dir, matter)?;
+    // Applies the `StartUpOnOff` behavior of the On/Off cluster too
+    psm.load_on_off(&dir, &on_off)?;
 
-    let mut persist = pin!(psm.run(dir, matter));
+    let mut persist = pin!(psm.run_with_on_off(dir, matter, &on_off));
 
     // Combine all async tasks in a single one
     let all = select4(
         &mut transport,
         &mut mdns,
         &mut persist,
-        select(&mut respond, &mut device).coalesce(),
+        select3(&mut respond, &mut device, &mut timers).coalesce(),
     );
 
     // Run with a simple `block_on`. Any local executor would do.
     futures_lite::future::block_on(all.coalesce())
 }
 
+/// The hardware of our lamp, which just logs the state changes.
+struct LampHooks;
+
+impl on_off::OnOffHooks for LampHooks {
+    fn on_off_changed(&self, on: bool) {
+        info!("Lamp is now {}", if on { "on" } else { "off" });
+    }
+}
+
 /// The Node meta-data describing our Matter device.
 const NODE: Node<'static> = Node {
     id: 0,
     endpoints: &[
         root_endpoint::root_endpoint(NetworkType::Ethernet),
-        Endpoint {
-            id: 1,
-            device_types: devices!(DEV_TYPE_ON_OFF_LIGHT),
-            clusters: clusters!(desc::DescHandler::CLUSTER, on_off::OnOffHandler::CLUSTER),
-        },
+        Endpoint::new(
+            1,
+            devices!(DEV_TYPE_ON_OFF_LIGHT),
+            clusters!(desc::DescHandler::CLUSTER, on_off::OnOffHandler::CLUSTER),
+        ),
     ],
 };
 
@@ -209,7 +223,7 @@ const NODE: Node<'static> = Node {
 /// The handler is the root endpoint 0 handler plus the on-off handler and its descriptor.
 fn dm_handler<'a>(
     matter: &'a Matter<'a>,
-    on_off: &'a on_off::OnOffHandler,
+    on_off: &'a on_off::OnOffHandler<'a>,
 ) -> impl AsyncMetadata + AsyncHandler + 'a {
     (
         NODE,
@@ -47,17 +47,17 @@ use rs_matter::data_model::objects::{
 };
 use rs_matter::data_model::on_off::{self, ClusterHandler as _};
 use rs_matter::data_model::root_endpoint;
+use rs_matter::data_model::sdm::gen_comm::CommPolicy;
 use rs_matter::data_model::sdm::net_comm::{NetCtl, NetCtlStatus, NetworkType, Networks};
 use rs_matter::data_model::sdm::wifi_diag::WifiDiag;
 use rs_matter::data_model::subscriptions::Subscriptions;
 use rs_matter::data_model::system_model::desc::{self, ClusterHandler as _};
 use rs_matter::error::Error;
 use rs_matter::mdns::MdnsService;
-use rs_matter::pairing::DiscoveryCapabilities;
 use rs_matter::persist::Psm;
 use rs_matter::respond::DefaultResponder;
 use rs_matter::transport::core::MATTER_SOCKET_BIND_ADDR;
-use rs_matter::transport::network::btp::{Btp, BtpContext};
+use rs_matter::transport::network::btp::{Btp, BtpCommissioning, BtpContext};
 use rs_matter::utils::select::Coalesce;
 use rs_matter::utils::storage::pooled::PooledBuffers;
 use rs_matter::utils::sync::blocking::raw::StdRawMutex;
@@ -105,8 +105,13 @@ fn main() -> Result<(), Error> {
     let net_ctl =
         NetCtlWithStatusImpl::new(&net_ctl_state, NoopWirelessNetCtl::new(NetworkType::Wifi));
 
+    // The commissioning policy of the device, shared by the General Commissioning cluster and the BTP commissioning.
+    // Concurrent connections are not supported, so BTP is shut down once the Wifi credentials are provided
+    // by the commissioner, and the commissioning is completed over the operational network.
+    let comm_policy = false;
+
     // Assemble our Data Model handler by composing the predefined Root Endpoint handler with the On/Off handler
-    let dm_handler = dm_handler(&matter, &on_off, &net_ctl, &networks);
+    let dm_handler = dm_handler(&matter, &comm_policy, &on_off, &net_ctl, &networks);
 
     // Create a default responder capable of handling up to 3 subscriptions
     // All other subscription requests will be turned down with "resource exhausted"
@@ -142,42 +147,15 @@ fn main() -> Result<(), Error> {
     // Create and run the mDNS responder
     let mut mdns = pin!(mdns::run_mdns(&matter));
 
-    if !matter.is_commissioned() {
-        // Not commissioned yet, start commissioning first
-
-        // The BTP transport impl
-        let btp = Btp::new_builtin(&BTP_CONTEXT);
-        let mut bluetooth = pin!(btp.run(
-            "MT",
-            &test_device::TEST_DEV_DET,
-            test_device::TEST_DEV_COMM.discriminator
-        ));
-
-        let mut transport = pin!(matter.run(&btp, &btp, DiscoveryCapabilities::BLE));
-        let mut wifi_prov_task = pin!(async {
-            NetCtlState::wait_prov_ready(&net_ctl_state, &btp).await;
-            Ok(())
-        });
-
-        // Combine all async tasks in a single one
-        let all = select4(
-            &mut transport,
-            &mut bluetooth,
-            select(&mut wifi_prov_task, &mut persist).coalesce(),
-            select(&mut respond, &mut device).coalesce(),
-        );
-
-        // Run with a simple `block_on`. Any local executor would do.
-        futures_lite::future::block_on(async_compat::Compat::new(all.coalesce()))?;
-
-        matter.reset_transport()?;
-    }
+    // The BTP transport impl
+    let btp = Btp::new_builtin(&BTP_CONTEXT);
 
     // Create the Matter UDP socket
     let udp = async_io::Async::<UdpSocket>::bind(MATTER_SOCKET_BIND_ADDR)?;
 
-    // Run the Matter transport
-    let mut transport = pin!(matter.run_transport(&udp, &udp));
+    // Run the Matter transport, commissioning over BTP first if the device is not commissioned yet
+    let commissioning = BtpCommissioning::new(&matter, &btp, &comm_policy);
+    let mut transport = pin!(commissioning.run(&udp, &udp, &net_ctl_state, "MT"));
 
     // Combine all async tasks in a single one
     let all = select4(
@@ -196,11 +174,11 @@ const NODE: Node<'static> = Node {
     id: 0,
     endpoints: &[
         root_endpoint::root_endpoint(NetworkType::Wifi),
-        Endpoint {
-            id: 1,
-            device_types: devices!(DEV_TYPE_ON_OFF_LIGHT),
-            clusters: clusters!(desc::DescHandler::CLUSTER, on_off::OnOffHandler::CLUSTER),
-        },
+        Endpoint::new(
+            1,
+            devices!(DEV_TYPE_ON_OFF_LIGHT),
+            clusters!(desc::DescHandler::CLUSTER, on_off::OnOffHandler::CLUSTER),
+        ),
     ],
 };
 
@@ -208,7 +186,8 @@ const NODE: Node<'static> = Node {
 /// The handler is the root endpoint 0 handler plus the on-off handler and its descriptor.
 fn dm_handler<'a, N>(
     matter: &'a Matter<'a>,
-    on_off: &'a on_off::OnOffHandler,
+    comm_policy: &'a dyn CommPolicy,
+    on_off: &'a on_off::OnOffHandler<'a>,
     net_ctl: &'a N,
     networks: &'a dyn Networks,
 ) -> impl AsyncMetadata + AsyncHandler + 'a
@@ -224,7 +203,7 @@ where
             networks,
             matter.rand(),
             root_endpoint::with_sys(
-                &false,
+                comm_policy,
                 matter.rand(),
                 EmptyHandler
                     .chain(
@@ -130,11 +130,11 @@ const NODE: Node<'static> = Node {
     id: 0,
     endpoints: &[
         root_endpoint::root_endpoint(NetworkType::Ethernet),
-        Endpoint {
-            id: 1,
-            device_types: devices!(DEV_TYPE_SMART_SPEAKER),
-            clusters: clusters!(desc::DescHandler::CLUSTER, SpeakerHandler::CLUSTER),
-        },
+        Endpoint::new(
+            1,
+            devices!(DEV_TYPE_SMART_SPEAKER),
+            clusters!(desc::DescHandler::CLUSTER, SpeakerHandler::CLUSTER),
+        ),
     ],
 };
 
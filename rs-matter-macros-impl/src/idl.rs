@@ -20038,7 +20038,8 @@ pub mod unit_testing {
                 rs_matter_crate::data_model::objects::Command::new(
                     CommandId::TimedInvokeRequest as _,
                     None,
-                    rs_matter_crate::data_model::objects::Access::WO,
+                    rs_matter_crate::data_model::objects::Access::WO
+                        .union(rs_matter_crate::data_model::objects::Access::TIMED_ONLY),
                 ),
                 rs_matter_crate::data_model::objects::Command::new(
                     CommandId::TestSimpleOptionalArgumentRequest as _,
@@ -20077,6 +20078,16 @@ pub mod unit_testing {
         const CLUSTER: rs_matter_crate::data_model::objects::Cluster<'static>;
         fn dataver(&self) -> u32;
         fn dataver_changed(&self);
+        #[doc = "Validate and apply the pending values of an atomic write which is being committed."]
+        #[doc = ""]
+        #[doc = "Only called for clusters supporting the `AtomicRequest` command."]
+        fn commit_atomic_write(
+            &self,
+            ctx: &rs_matter_crate::data_model::objects::InvokeContext<'_>,
+            write: &rs_matter_crate::data_model::objects::AtomicWrite<'_>,
+        ) -> Result<(), rs_matter_crate::error::Error> {
+            Err(rs_matter_crate::error::ErrorCode::InvalidAction.into())
+        }
         fn boolean(
             &self,
             ctx: &rs_matter_crate::data_model::objects::ReadContext<'_>,
@@ -21033,6 +21044,13 @@ pub mod unit_testing {
         fn dataver_changed(&self) {
             T::dataver_changed(self)
         }
+        fn commit_atomic_write(
+            &self,
+            ctx: &rs_matter_crate::data_model::objects::InvokeContext<'_>,
+            write: &rs_matter_crate::data_model::objects::AtomicWrite<'_>,
+        ) -> Result<(), rs_matter_crate::error::Error> {
+            T::commit_atomic_write(self, ctx, write)
+        }
         fn boolean(
             &self,
             ctx: &rs_matter_crate::data_model::objects::ReadContext<'_>,
@@ -27185,6 +27203,11 @@ pub mod unit_testing {
             ctx: &rs_matter_crate::data_model::objects::InvokeContext<'_>,
             encoder: rs_matter_crate::data_model::objects::CmdDataEncoder<'_, '_, '_>,
         ) -> Result<(), rs_matter_crate::error::Error> {
+            if let Some(write) = ctx.atomic_write() {
+                self.0.commit_atomic_write(ctx, write)?;
+                self.0.dataver_changed();
+                return Ok(());
+            }
             match CommandId::try_from(ctx.cmd().cmd_id)? {
                 CommandId::Test => {
                     let cmd_invoke_result = self.0.handle_test(ctx);
@@ -407,13 +407,17 @@ pub fn cluster(cluster: &Cluster, context: &IdlGenerateContext) -> TokenStream {
     let commands = cluster.commands.iter().map(|cmd| {
         let cmd_name = ident(&cmd.id);
 
-        let access = match cmd.access {
+        let mut access = match cmd.access {
             AccessPrivilege::View => panic!("Unsupported command access: {:?}", cmd.access),
             AccessPrivilege::Operate => quote!(#krate::data_model::objects::Access::WO),
             AccessPrivilege::Manage => quote!(#krate::data_model::objects::Access::WM),
             AccessPrivilege::Administer => quote!(#krate::data_model::objects::Access::WA),
         };
 
+        if cmd.is_timed {
+            access = quote!(#access.union(#krate::data_model::objects::Access::TIMED_ONLY));
+        }
+
         let resp_id = if cmd.output != NO_RESPONSE {
             let cmd_name = ident(&cmd.output);
 
@@ -76,6 +76,12 @@ pub fn handler(
         .iter()
         .map(|cmd| handler_command(cmd, asynch, delegate, cluster, &krate));
 
+    let (pasync, sawait) = if asynch {
+        (quote!(async), quote!(.await))
+    } else {
+        (quote!(), quote!())
+    };
+
     if delegate {
         quote!(
             impl<T> #handler_name for &T
@@ -87,6 +93,10 @@ pub fn handler(
                 fn dataver(&self) -> u32 { T::dataver(self) }
                 fn dataver_changed(&self) { T::dataver_changed(self) }
 
+                #pasync fn commit_atomic_write(&self, ctx: &#krate::data_model::objects::InvokeContext<'_>, write: &#krate::data_model::objects::AtomicWrite<'_>) -> Result<(), #krate::error::Error> {
+                    T::commit_atomic_write(self, ctx, write)#sawait
+                }
+
                 #(#handler_attribute_methods)*
 
                 #(#handler_attribute_write_methods)*
@@ -105,6 +115,13 @@ pub fn handler(
 
                 fn dataver_changed(&self);
 
+                #[doc = "Validate and apply the pending values of an atomic write which is being committed."]
+                #[doc = ""]
+                #[doc = "Only called for clusters supporting the `AtomicRequest` command."]
+                #pasync fn commit_atomic_write(&self, ctx: &#krate::data_model::objects::InvokeContext<'_>, write: &#krate::data_model::objects::AtomicWrite<'_>) -> Result<(), #krate::error::Error> {
+                    Err(#krate::error::ErrorCode::InvalidAction.into())
+                }
+
                 #(#handler_attribute_methods)*
 
                 #(#handler_attribute_write_methods)*
@@ -245,7 +262,11 @@ pub fn handler_adaptor(
         )
     };
 
-    let pasync = if asynch { quote!(async) } else { quote!() };
+    let (pasync, sawait) = if asynch {
+        (quote!(async), quote!(.await))
+    } else {
+        (quote!(), quote!())
+    };
 
     let stream = quote!(
         #[doc = "The handler adaptor for the cluster-specific handler. This adaptor implements the generic `rs-matter` handler trait."]
@@ -298,6 +319,13 @@ pub fn handler_adaptor(
                 ctx: &#krate::data_model::objects::InvokeContext<'_>,
                 encoder: #krate::data_model::objects::CmdDataEncoder<'_, '_, '_>,
             ) -> Result<(), #krate::error::Error> {
+                if let Some(write) = ctx.atomic_write() {
+                    self.0.commit_atomic_write(ctx, write)#sawait?;
+                    self.0.dataver_changed();
+
+                    return Ok(());
+                }
+
                 #invoke_stream
 
                 self.0.dataver_changed();
@@ -1140,6 +1168,16 @@ mod tests {
                     const CLUSTER: rs_matter_crate::data_model::objects::Cluster<'static>;
                     fn dataver(&self) -> u32;
                     fn dataver_changed(&self);
+                    #[doc = "Validate and apply the pending values of an atomic write which is being committed."]
+                    #[doc = ""]
+                    #[doc = "Only called for clusters supporting the `AtomicRequest` command."]
+                    fn commit_atomic_write(
+                        &self,
+                        ctx: &rs_matter_crate::data_model::objects::InvokeContext<'_>,
+                        write: &rs_matter_crate::data_model::objects::AtomicWrite<'_>,
+                    ) -> Result<(), rs_matter_crate::error::Error> {
+                        Err(rs_matter_crate::error::ErrorCode::InvalidAction.into())
+                    }
                     fn on_off(
                         &self,
                         ctx: &rs_matter_crate::data_model::objects::ReadContext<'_>,
@@ -1239,6 +1277,13 @@ mod tests {
                     fn dataver_changed(&self) {
                         T::dataver_changed(self)
                     }
+                    fn commit_atomic_write(
+                        &self,
+                        ctx: &rs_matter_crate::data_model::objects::InvokeContext<'_>,
+                        write: &rs_matter_crate::data_model::objects::AtomicWrite<'_>,
+                    ) -> Result<(), rs_matter_crate::error::Error> {
+                        T::commit_atomic_write(self, ctx, write)
+                    }
                     fn on_off(
                         &self,
                         ctx: &rs_matter_crate::data_model::objects::ReadContext<'_>,
@@ -1635,6 +1680,11 @@ mod tests {
                         ctx: &rs_matter_crate::data_model::objects::InvokeContext<'_>,
                         encoder: rs_matter_crate::data_model::objects::CmdDataEncoder<'_, '_, '_>,
                     ) -> Result<(), rs_matter_crate::error::Error> {
+                        if let Some(write) = ctx.atomic_write() {
+                            self.0.commit_atomic_write(ctx, write)?;
+                            self.0.dataver_changed();
+                            return Ok(());
+                        }
                         match CommandId::try_from(ctx.cmd().cmd_id)? {
                             CommandId::Off => {
                                 let cmd_invoke_result = self.0.handle_off(ctx);
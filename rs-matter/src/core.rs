@@ -15,6 +15,8 @@
  *    limitations under the License.
  */
 
+use core::num::NonZeroU8;
+
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 
 use crate::data_model::basic_info::{BasicInfoConfig, BasicInfoSettings};
@@ -25,8 +27,11 @@ use crate::failsafe::FailSafe;
 use crate::mdns::MdnsService;
 use crate::pairing::{print_pairing_code_and_qr, DiscoveryCapabilities};
 use crate::secure_channel::pake::PaseMgr;
+use crate::transport::capture::PacketCapture;
 use crate::transport::core::{PacketBufferExternalAccess, TransportMgr};
+use crate::transport::mrp::MrpParameters;
 use crate::transport::network::{NetworkReceive, NetworkSend};
+use crate::transport::session::{SessionEvictionPolicy, SessionRemoval, SessionRemovalReason};
 use crate::utils::cell::RefCell;
 use crate::utils::epoch::Epoch;
 use crate::utils::init::{init, Init};
@@ -56,6 +61,8 @@ pub struct Matter<'a> {
     pub(crate) basic_info_settings: RefCell<BasicInfoSettings>,
     pub transport_mgr: TransportMgr<'a>, // Public for tests
     persist_notification: Notification<NoopRawMutex>,
+    comm_window_notification: Notification<NoopRawMutex>,
+    failsafe_notification: Notification<NoopRawMutex>,
     epoch: Epoch,
     rand: Rand,
     dev_det: &'a BasicInfoConfig<'a>,
@@ -124,6 +131,8 @@ impl<'a> Matter<'a> {
             transport_mgr: TransportMgr::new(mdns, dev_det, port, epoch, rand),
             basic_info_settings: RefCell::new(BasicInfoSettings::new()),
             persist_notification: Notification::new(),
+            comm_window_notification: Notification::new(),
+            failsafe_notification: Notification::new(),
             epoch,
             rand,
             dev_det,
@@ -192,6 +201,8 @@ impl<'a> Matter<'a> {
                 transport_mgr <- TransportMgr::init(mdns, dev_det, port, epoch, rand),
                 basic_info_settings <- RefCell::init(BasicInfoSettings::init()),
                 persist_notification: Notification::new(),
+                comm_window_notification: Notification::new(),
+                failsafe_notification: Notification::new(),
                 epoch,
                 rand,
                 dev_det,
@@ -202,6 +213,99 @@ impl<'a> Matter<'a> {
         )
     }
 
+    /// Set the policy used for selecting the session to be evicted when the session table is full.
+    ///
+    /// By default, `DefaultSessionEvictionPolicy` is used.
+    /// Use i.e. the `Subscriptions` instance of the data model as a policy, so that sessions
+    /// with active subscriptions are not evicted.
+    pub fn set_session_eviction_policy(&mut self, policy: &'a dyn SessionEvictionPolicy) {
+        self.transport_mgr.set_eviction_policy(policy);
+    }
+
+    /// Set the maximum number of sessions which can be tracked at the same time,
+    /// and the maximum number of exchanges per session which can be active at the same time.
+    ///
+    /// By default, the full `MAX_SESSIONS` and `MAX_EXCHANGES` capacities are used.
+    /// Lowering the limits is useful i.e. on memory-constrained devices, where each session
+    /// or exchange might also need to reserve resources outside of `rs-matter`.
+    pub fn set_session_limits(
+        &self,
+        max_sessions: usize,
+        max_exchanges: usize,
+    ) -> Result<(), Error> {
+        self.transport_mgr
+            .session_mgr
+            .borrow_mut()
+            .set_limits(max_sessions, max_exchanges)
+    }
+
+    /// Set a hook which is called with every Matter message received (after decryption) and sent (before encryption),
+    /// along with the keys of its session.
+    ///
+    /// Use i.e. `PcapngCapture` to record the messages into a pcapng file for offline analysis.
+    /// As the captured messages are unencrypted and the session keys are exposed, this is meant for debugging only.
+    pub fn set_packet_capture(&mut self, capture: &'a dyn PacketCapture) {
+        self.transport_mgr.set_packet_capture(capture);
+    }
+
+    /// Remove the fabric with the provided local index, and close all of its sessions.
+    ///
+    /// The sessions are closed with a `CloseSession` status report as soon as their ongoing exchanges
+    /// are complete. Subscriptions reported over these sessions are cancelled once the sessions are removed.
+    pub fn remove_fabric(&self, fab_idx: NonZeroU8) -> Result<(), Error> {
+        self.fabric_mgr
+            .borrow_mut()
+            .remove(fab_idx, &self.transport_mgr.mdns)?;
+
+        self.transport_mgr
+            .group_peers
+            .borrow_mut()
+            .remove_for_fabric(fab_idx);
+
+        self.transport_mgr
+            .close_sessions_for_fabric(fab_idx, SessionRemovalReason::FabricRemoved);
+
+        self.notify_persist();
+
+        Ok(())
+    }
+
+    /// Close all sessions of the fabric with the provided local index.
+    ///
+    /// Return the number of sessions scheduled for closing.
+    pub fn close_sessions_for_fabric(&self, fab_idx: NonZeroU8) -> usize {
+        self.transport_mgr
+            .close_sessions_for_fabric(fab_idx, SessionRemovalReason::Closed)
+    }
+
+    /// Close all sessions with the provided peer on the fabric with the provided local index.
+    ///
+    /// Return the number of sessions scheduled for closing.
+    pub fn close_sessions_for_peer(&self, fab_idx: NonZeroU8, peer_node_id: u64) -> usize {
+        self.transport_mgr.close_sessions(
+            |sess| {
+                sess.get_local_fabric_idx() == fab_idx.get()
+                    && sess.get_peer_node_id() == Some(peer_node_id)
+            },
+            SessionRemovalReason::Closed,
+        )
+    }
+
+    /// Close the session with the provided internal ID.
+    ///
+    /// Return `true` if the session exists and was scheduled for closing.
+    pub fn close_session(&self, session_id: u32) -> bool {
+        self.transport_mgr
+            .close_sessions(|sess| sess.id() == session_id, SessionRemovalReason::Closed)
+            > 0
+    }
+
+    /// Wait until a session is removed, and return information about the removed session,
+    /// including the reason for its removal.
+    pub async fn wait_session_removed(&self) -> SessionRemoval {
+        self.transport_mgr.wait_session_removed().await
+    }
+
     pub fn initialize_transport_buffers(&self) -> Result<(), Error> {
         self.transport_mgr.initialize_buffers()
     }
@@ -288,6 +392,27 @@ impl<'a> Matter<'a> {
         self.basic_info_settings.borrow().changed
     }
 
+    pub fn load_counters(&self, data: &[u8]) -> Result<(), Error> {
+        self.transport_mgr.counters.borrow_mut().load(data)
+    }
+
+    /// Store the limits of the persistent message counters into the provided buffer
+    ///
+    /// Once the returned data is persisted, `Matter::counters_stored` must be called, as the
+    /// group message counters do not go past the limits before that.
+    pub fn store_counters<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        self.transport_mgr.counters.borrow_mut().store(buf)
+    }
+
+    /// Confirm that the data returned by the last `Matter::store_counters` call is persisted
+    pub fn counters_stored(&self) {
+        self.transport_mgr.counters.borrow_mut().stored()
+    }
+
+    pub fn counters_changed(&self) -> bool {
+        self.transport_mgr.counters.borrow().is_changed()
+    }
+
     /// Return `true` if there is at least one commissioned fabric
     //
     // TODO:
@@ -326,6 +451,8 @@ impl<'a> Matter<'a> {
             &self.transport_mgr.mdns,
         )?;
 
+        self.notify_commissioning_changed();
+
         print_pairing_code_and_qr(
             self.dev_det,
             &self.dev_comm,
@@ -340,9 +467,47 @@ impl<'a> Matter<'a> {
     ///
     /// The method will return Ok(false) if there is no active PASE session to disable.
     pub fn disable_commissioning(&self) -> Result<bool, Error> {
-        self.pase_mgr
+        let disabled = self
+            .pase_mgr
             .borrow_mut()
-            .disable_pase_session(&self.transport_mgr.mdns)
+            .disable_pase_session(&self.transport_mgr.mdns)?;
+
+        self.notify_commissioning_changed();
+
+        Ok(disabled)
+    }
+
+    /// Return `true` if a commissioning window (basic or enhanced) is currently open
+    pub fn is_commissioning_open(&self) -> bool {
+        self.pase_mgr.borrow().session_type().is_some()
+    }
+
+    /// Notify that a commissioning window _might_ have been opened or closed
+    pub(crate) fn notify_commissioning_changed(&self) {
+        self.comm_window_notification.notify();
+    }
+
+    /// Wait until a commissioning window _might_ have been opened or closed.
+    /// Once this future resolves, user code is supposed to check `is_commissioning_open`.
+    ///
+    /// Only one waiter is supported at a time.
+    pub async fn wait_commissioning_changed(&self) {
+        self.comm_window_notification.wait().await
+    }
+
+    /// Notify that the fail-safe timer _might_ have been armed, re-armed or disarmed
+    pub(crate) fn notify_failsafe_changed(&self) {
+        self.failsafe_notification.notify();
+    }
+
+    /// Wait until the fail-safe timer _might_ have been armed, re-armed or disarmed.
+    ///
+    /// Note that the expiry of the fail-safe timer is not signalled, as it is only detected when the
+    /// fail-safe is next accessed, so user code is supposed to wait for the expiry deadline as well.
+    ///
+    /// Only one waiter is supported at a time.
+    pub async fn wait_failsafe_changed(&self) {
+        self.failsafe_notification.wait().await
     }
 
     /// Run the transport layer
@@ -402,10 +567,51 @@ impl<'a> Matter<'a> {
         R: NetworkReceive,
     {
         self.transport_mgr
-            .run_builtin_mdns(send, recv, host, ipv4_interface, ipv6_interface)
+            .run_builtin_mdns(
+                send,
+                recv,
+                host,
+                ipv4_interface,
+                ipv6_interface,
+                |instance, params| {
+                    self.set_peer_mrp_params(instance, params);
+                },
+            )
             .await
     }
 
+    /// Set the MRP parameters of the operational peer advertised over DNS-SD with the provided
+    /// `<CompressedFabricId>-<NodeId>` instance name.
+    ///
+    /// The parameters are usually learned from the `SII`, `SAI` and `SAT` TXT records of the peer
+    /// (see `MrpParameters::from_txt_kvs`). The built-in mDNS responder calls this method for every
+    /// operational TXT record it sees; external mDNS implementations should call it when resolving peers.
+    ///
+    /// Return the number of updated sessions.
+    pub fn set_peer_mrp_params(&self, instance: &str, params: MrpParameters) -> usize {
+        let Some((fabric_id, node_id)) =
+            instance.split_once('-').and_then(|(fabric_id, node_id)| {
+                Some((
+                    u64::from_str_radix(fabric_id, 16).ok()?,
+                    u64::from_str_radix(node_id, 16).ok()?,
+                ))
+            })
+        else {
+            return 0;
+        };
+
+        let fabric_mgr = self.fabric_mgr.borrow();
+
+        fabric_mgr
+            .iter()
+            .filter(|fabric| fabric.compressed_fabric_id() == Some(fabric_id))
+            .map(|fabric| {
+                self.transport_mgr
+                    .set_peer_mrp_params(fabric.fab_idx(), node_id, &params)
+            })
+            .sum()
+    }
+
     /// Notify that the ACLs, Fabrics or Basic Info _might_ have changed
     /// This method is supposed to be called after processing SC and IM messages that might affect the ACLs, Fabrics or Basic Info.
     ///
@@ -413,7 +619,7 @@ impl<'a> Matter<'a> {
     ///
     /// TODO: Fix the method name as it is not clear enough. Potentially revamp the whole persistence notification logic
     pub fn notify_persist(&self) {
-        if self.fabrics_changed() || self.basic_info_changed() {
+        if self.fabrics_changed() || self.basic_info_changed() || self.counters_changed() {
             self.persist_notification.notify();
         }
     }
@@ -0,0 +1,123 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Air Quality cluster and its handler.
+
+use crate::error::Error;
+use crate::utils::cell::RefCell;
+use crate::with;
+
+use super::measurement::push_state;
+use super::objects::{Cluster, Dataver, ReadContext};
+
+pub use crate::data_model::clusters::air_quality::*;
+
+/// A handler for the Air Quality Matter cluster.
+///
+/// The air quality is classified by the sensor driver, and pushed with `AirQualityHandler::push_air_quality`.
+pub struct AirQualityHandler {
+    dataver: Dataver,
+    air_quality: RefCell<AirQualityEnum>,
+}
+
+impl AirQualityHandler {
+    /// Creates a new instance of `AirQualityHandler` with the given `Dataver`.
+    ///
+    /// The air quality is initially unknown.
+    pub const fn new(dataver: Dataver) -> Self {
+        Self {
+            dataver,
+            air_quality: RefCell::new(AirQualityEnum::Unknown),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Return the current air quality.
+    pub fn air_quality(&self) -> AirQualityEnum {
+        *self.air_quality.borrow()
+    }
+
+    /// Push the air quality.
+    ///
+    /// # Arguments
+    /// - `air_quality`: The new air quality
+    /// - `notify`: A callback which is called when the air quality changes, and which is expected
+    ///   to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub fn push_air_quality<F>(&self, air_quality: AirQualityEnum, notify: F)
+    where
+        F: FnOnce(),
+    {
+        push_state(&self.air_quality, air_quality, &self.dataver, notify);
+    }
+}
+
+impl ClusterHandler for AirQualityHandler {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(1)
+        .with_features(
+            Feature::FAIR.bits()
+                | Feature::MODERATE.bits()
+                | Feature::VERY_POOR.bits()
+                | Feature::EXTREMELY_POOR.bits(),
+        )
+        .with_attrs(with!(required))
+        .with_cmds(with!());
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn air_quality(&self, _ctx: &ReadContext) -> Result<AirQualityEnum, Error> {
+        Ok(AirQualityHandler::air_quality(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::measurement::check_pushes;
+    use crate::data_model::objects::Dataver;
+
+    use super::*;
+
+    #[test]
+    fn test_push_air_quality() {
+        let handler = AirQualityHandler::new(Dataver::new(0));
+
+        let push = |air_quality, notify: &dyn Fn()| handler.push_air_quality(air_quality, notify);
+        let dataver = || handler.dataver();
+
+        assert_eq!(handler.air_quality(), AirQualityEnum::Unknown);
+
+        check_pushes(
+            push,
+            dataver,
+            &[(AirQualityEnum::Good, true), (AirQualityEnum::Good, false)],
+        );
+        assert_eq!(handler.air_quality(), AirQualityEnum::Good);
+
+        check_pushes(push, dataver, &[(AirQualityEnum::VeryPoor, true)]);
+        assert_eq!(handler.air_quality(), AirQualityEnum::VeryPoor);
+    }
+}
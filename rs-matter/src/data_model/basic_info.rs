@@ -33,6 +33,9 @@ pub use crate::data_model::clusters::basic_information::*;
 
 const SUPPORTED_MATTER_SPEC_VERSION: u32 = 0x01000000;
 
+/// The default maximum number of paths in a single Invoke Request
+pub const DEFAULT_MAX_PATHS_PER_INVOKE: u16 = 1;
+
 /// Basic infomration which is immutable
 /// (i.e. valid for the lifetime of the device firmware)
 #[derive(Default, Clone, Eq, PartialEq, Hash)]
@@ -54,6 +57,17 @@ pub struct BasicInfoConfig<'a> {
     /// Session Idle Interval in ms
     /// If not specified, defaults to 5000
     pub sii: Option<u16>,
+    /// Session Active Threshold in ms
+    /// If not specified, defaults to 4000
+    pub sat: Option<u16>,
+    /// Maximum number of commands in a single Invoke Request
+    /// If not specified, defaults to `DEFAULT_MAX_PATHS_PER_INVOKE`
+    pub max_paths_per_invoke: Option<u16>,
+    /// If `true`, the headers of the messages sent over secure sessions and of group messages are privacy-obfuscated
+    /// (message privacy), so that the message counter and node IDs cannot be tracked by passive observers.
+    ///
+    /// Incoming messages using message privacy are always accepted.
+    pub privacy: bool,
 }
 
 /// Mutable basic information
@@ -297,8 +311,10 @@ impl ClusterHandler for BasicInfoHandler {
         Ok(SUPPORTED_MATTER_SPEC_VERSION)
     }
 
-    fn max_paths_per_invoke(&self, _ctx: &ReadContext) -> Result<u16, Error> {
-        Ok(1) // TODO: Report real value
+    fn max_paths_per_invoke(&self, ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(Self::config(ctx.exchange())
+            .max_paths_per_invoke
+            .unwrap_or(DEFAULT_MAX_PATHS_PER_INVOKE))
     }
 
     fn handle_mfg_specific_ping(&self, _ctx: &InvokeContext) -> Result<(), Error> {
@@ -0,0 +1,113 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Boolean State cluster and its handler.
+
+use crate::error::Error;
+use crate::utils::cell::RefCell;
+use crate::with;
+
+use super::measurement::push_state;
+use super::objects::{Cluster, Dataver, ReadContext};
+
+pub use crate::data_model::clusters::boolean_state::*;
+
+/// A handler for the Boolean State Matter cluster.
+///
+/// The state (i.e. of a contact sensor) is pushed by the sensor driver with `BooleanStateHandler::push_state`.
+///
+/// The `StateChange` event of the cluster is not generated (see [Events](crate::data_model#events)).
+pub struct BooleanStateHandler {
+    dataver: Dataver,
+    state: RefCell<bool>,
+}
+
+impl BooleanStateHandler {
+    /// Creates a new instance of `BooleanStateHandler` with the given `Dataver` and initial state.
+    pub const fn new(dataver: Dataver, state: bool) -> Self {
+        Self {
+            dataver,
+            state: RefCell::new(state),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Return the current state.
+    pub fn state(&self) -> bool {
+        *self.state.borrow()
+    }
+
+    /// Push the state.
+    ///
+    /// # Arguments
+    /// - `state`: The new state
+    /// - `notify`: A callback which is called when the state changes, and which is expected
+    ///   to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub fn push_state<F>(&self, state: bool, notify: F)
+    where
+        F: FnOnce(),
+    {
+        push_state(&self.state, state, &self.dataver, notify);
+    }
+}
+
+impl ClusterHandler for BooleanStateHandler {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(1)
+        .with_attrs(with!(required))
+        .with_cmds(with!());
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn state_value(&self, _ctx: &ReadContext) -> Result<bool, Error> {
+        Ok(self.state())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::measurement::check_pushes;
+    use crate::data_model::objects::Dataver;
+
+    use super::*;
+
+    #[test]
+    fn test_push_state() {
+        let handler = BooleanStateHandler::new(Dataver::new(0), true);
+
+        let push = |state, notify: &dyn Fn()| handler.push_state(state, notify);
+        let dataver = || handler.dataver();
+
+        assert!(handler.state());
+
+        check_pushes(push, dataver, &[(true, false), (false, true)]);
+        assert!(!handler.state());
+
+        check_pushes(push, dataver, &[(true, true)]);
+        assert!(handler.state());
+    }
+}
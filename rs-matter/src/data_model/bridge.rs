@@ -0,0 +1,1106 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Bridged Device Basic Information cluster and its handler,
+//! as well as a `Bridge` type for Matter bridges whose bridged endpoints are added and removed at runtime.
+
+use core::cell::Cell;
+use core::fmt;
+use core::num::Wrapping;
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::rwlock::{RwLock, RwLockReadGuard};
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{FromTLV, TLVBuilderParent, TLVElement, TLVTag, TLVWrite, ToTLV, Utf8StrBuilder};
+use crate::utils::cell::RefCell;
+use crate::utils::rand::Rand;
+use crate::utils::storage::{Vec, WriteBuf};
+use crate::utils::sync::blocking::Mutex;
+use crate::utils::sync::Notification;
+use crate::with;
+
+use super::objects::{
+    AsyncHandler, AsyncMetadata, AttrDataEncoder, Cluster, CmdDataEncoder, Dataver, DeviceType,
+    DynamicNode, Endpoint, EndptId, Handler, InvokeContext, MatchContext, Matcher, MetadataGuard,
+    Node, ReadContext, WriteContext,
+};
+use super::root_endpoint::ROOT_ENDPOINT_ID;
+use super::subscriptions::Subscriptions;
+use super::system_model::desc::{self, ClusterHandler as _, DescHandler, PartsMatcher};
+
+pub use crate::data_model::clusters::bridged_device_basic_information::*;
+
+/// The maximum length of the unique ID of a bridged device, as per the spec
+pub const MAX_UNIQUE_ID_LEN: usize = 32;
+
+/// The system implementation of a handler for the Bridged Device Basic Information Matter cluster.
+///
+/// Reports the reachability and the unique ID of a bridged device.
+#[derive(Debug)]
+pub struct BridgedHandler {
+    dataver: Dataver,
+    reachable: Cell<bool>,
+    unique_id: heapless::String<MAX_UNIQUE_ID_LEN>,
+}
+
+impl BridgedHandler {
+    /// Create a new instance of `BridgedHandler` with the given `Dataver`
+    /// and the unique ID of the bridged device.
+    ///
+    /// The bridged device is initially reported as reachable.
+    pub fn new(dataver: Dataver, unique_id: &str) -> Result<Self, Error> {
+        let mut id = heapless::String::new();
+        id.push_str(unique_id)
+            .map_err(|_| ErrorCode::ConstraintError)?;
+
+        Ok(Self {
+            dataver,
+            reachable: Cell::new(true),
+            unique_id: id,
+        })
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Return the unique ID of the bridged device
+    pub fn unique_id(&self) -> &str {
+        &self.unique_id
+    }
+
+    /// Return `true` if the bridged device is reachable
+    pub fn is_reachable(&self) -> bool {
+        self.reachable.get()
+    }
+
+    /// Set the reachability of the bridged device
+    ///
+    /// Returns `true` if the reachability had changed, in which case the dataver
+    /// of the cluster is changed as well.
+    pub fn set_reachable(&self, reachable: bool) -> bool {
+        if self.reachable.replace(reachable) != reachable {
+            self.dataver.changed();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl ClusterHandler for BridgedHandler {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(1)
+        .with_features(0)
+        .with_attrs(with!(required; AttributeId::UniqueID))
+        .with_cmds(with!());
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn reachable(&self, _ctx: &ReadContext<'_>) -> Result<bool, Error> {
+        Ok(self.reachable.get())
+    }
+
+    fn unique_id<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext<'_>,
+        out: Utf8StrBuilder<P>,
+    ) -> Result<P, Error> {
+        out.set(self.unique_id.as_str())
+    }
+}
+
+/// A trait for being notified of the events of the Bridged Device Basic Information clusters of the bridged devices.
+///
+/// The events are only delivered to the hooks for now (see [Events](crate::data_model#events)).
+pub trait BridgeHooks {
+    /// Called when the reachability of a bridged device changes (the `ReachableChanged` event).
+    fn reachable_changed(&self, endpoint_id: EndptId, reachable: bool) {
+        let _ = (endpoint_id, reachable);
+    }
+}
+
+impl BridgeHooks for () {}
+
+impl<T> BridgeHooks for &T
+where
+    T: BridgeHooks,
+{
+    fn reachable_changed(&self, endpoint_id: EndptId, reachable: bool) {
+        (*self).reachable_changed(endpoint_id, reachable)
+    }
+}
+
+/// The endpoint ID allocated to a bridged device with a certain unique ID
+#[derive(Debug, Clone, Eq, PartialEq, Hash, ToTLV, FromTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct BridgedEndpointId {
+    unique_id: heapless::String<MAX_UNIQUE_ID_LEN>,
+    endpoint_id: EndptId,
+}
+
+/// The persisted state of the bridge: the endpoint IDs allocated to the bridged devices
+struct BridgeStore<const N: usize> {
+    next_endpoint_id: EndptId,
+    endpoint_ids: Vec<BridgedEndpointId, N>,
+    version: Wrapping<u32>,
+    changed: bool,
+}
+
+impl<const N: usize> BridgeStore<N> {
+    const fn new(next_endpoint_id: EndptId) -> Self {
+        Self {
+            next_endpoint_id,
+            endpoint_ids: Vec::new(),
+            version: Wrapping(0),
+            changed: false,
+        }
+    }
+
+    fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVElement::new(data);
+        let seq = root.structure()?;
+
+        // Never go below the next ID computed from the static endpoints of the node
+        self.next_endpoint_id = self.next_endpoint_id.max(seq.ctx(0)?.u16()?);
+        self.endpoint_ids = FromTLV::from_tlv(&seq.ctx(1)?)?;
+
+        self.changed = false;
+
+        Ok(())
+    }
+
+    fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if !self.changed {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        wb.start_struct(&TLVTag::Anonymous)?;
+        wb.u16(&TLVTag::Context(0), self.next_endpoint_id)?;
+        self.endpoint_ids.to_tlv(&TLVTag::Context(1), &mut wb)?;
+        wb.end_container()?;
+
+        self.changed = false;
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    fn endpoint_id(&self, unique_id: &str) -> Option<EndptId> {
+        self.endpoint_ids
+            .iter()
+            .find(|id| id.unique_id == unique_id)
+            .map(|id| id.endpoint_id)
+    }
+
+    /// Return the endpoint ID already allocated to the bridged device with the given unique ID,
+    /// or allocate a new one, which had never been used before.
+    ///
+    /// Returns the endpoint ID and `true` if it was newly allocated.
+    fn allocate(&mut self, unique_id: &str) -> Result<(EndptId, bool), Error> {
+        if let Some(endpoint_id) = self.endpoint_id(unique_id) {
+            return Ok((endpoint_id, false));
+        }
+
+        if self.next_endpoint_id == EndptId::MAX {
+            return Err(ErrorCode::NoSpace.into());
+        }
+
+        let mut id = heapless::String::new();
+        id.push_str(unique_id)
+            .map_err(|_| ErrorCode::ConstraintError)?;
+
+        let endpoint_id = self.next_endpoint_id;
+
+        self.endpoint_ids
+            .push(BridgedEndpointId {
+                unique_id: id,
+                endpoint_id,
+            })
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        self.next_endpoint_id += 1;
+        self.changed = true;
+
+        Ok((endpoint_id, true))
+    }
+
+    /// Undo the allocation of a newly allocated endpoint ID (as returned by `allocate`),
+    /// so that the ID is not burned when the bridged device could not be added after all.
+    fn unallocate(&mut self, endpoint_id: EndptId, changed: bool) {
+        if self
+            .endpoint_ids
+            .last()
+            .map(|id| id.endpoint_id == endpoint_id)
+            .unwrap_or(false)
+            && self.next_endpoint_id == endpoint_id + 1
+        {
+            self.endpoint_ids.pop();
+            self.next_endpoint_id = endpoint_id;
+            self.changed = changed;
+        }
+    }
+
+    fn release(&mut self, endpoint_id: EndptId) {
+        self.endpoint_ids.retain(|id| id.endpoint_id != endpoint_id);
+        self.changed = true;
+    }
+}
+
+/// A bridged endpoint, together with the handlers serving it
+struct BridgedEndpoint<H> {
+    id: EndptId,
+    parent: EndptId,
+    desc_dataver: Dataver,
+    info: BridgedHandler,
+    handler: H,
+}
+
+impl<H> BridgedEndpoint<H> {
+    /// Return the Descriptor handler of the endpoint, whose parts are matched by the provided matcher
+    fn desc<'m>(&self, matcher: &'m dyn PartsMatcher) -> DescHandler<'m> {
+        // The Descriptor cluster has no writable attributes, so its dataver only changes with the parts,
+        // as reported by `PartsMatcher::version`
+        DescHandler::new_matching(Dataver::new(self.desc_dataver.get()), matcher)
+    }
+}
+
+/// The state of the bridge which is protected by the read-write lock
+struct BridgeNode<'a, const N: usize, H> {
+    node: DynamicNode<'a, N>,
+    endpoints: Vec<BridgedEndpoint<H>, N>,
+}
+
+impl<const N: usize, H> BridgeNode<'_, N, H> {
+    fn endpoint(&self, endpoint_id: EndptId) -> Result<&BridgedEndpoint<H>, Error> {
+        self.endpoints
+            .iter()
+            .find(|endpoint| endpoint.id == endpoint_id)
+            .ok_or_else(|| ErrorCode::EndpointNotFound.into())
+    }
+
+    /// Return `true` if the given endpoint is a bridged endpoint nested directly under `parent`
+    fn is_child(&self, endpoint_id: EndptId, parent: EndptId) -> bool {
+        self.endpoint(endpoint_id)
+            .map(|endpoint| endpoint.parent == parent)
+            .unwrap_or(false)
+    }
+}
+
+/// The metadata guard returned by `Bridge` when locked by the Data Model
+pub struct BridgeGuard<'g, 'a, const N: usize, M, H>(RwLockReadGuard<'g, M, BridgeNode<'a, N, H>>)
+where
+    M: RawMutex;
+
+impl<const N: usize, M, H> MetadataGuard for BridgeGuard<'_, '_, N, M, H>
+where
+    M: RawMutex,
+{
+    fn node(&self) -> Node<'_> {
+        self.0.node.node()
+    }
+}
+
+/// A Matter node whose bridged endpoints are added and removed at runtime.
+///
+/// The bridge contains the static endpoints of the node (the root endpoint and the aggregator endpoint),
+/// as well as up to `N` - (number of static endpoints) bridged endpoints.
+///
+/// The bridge serves as:
+/// - The metadata of the node (`AsyncMetadata`)
+/// - The handler of all bridged endpoints (`AsyncHandler`), as well as a `Matcher` matching these endpoints,
+///   so that it can be chained with the handlers of the static endpoints.
+///   The Descriptor and the Bridged Device Basic Information clusters of each bridged endpoint
+///   are served by the bridge itself; all other clusters are delegated to the `H` handler of the endpoint.
+/// - The `PartsMatcher` of the Descriptor clusters of the root and the aggregator endpoints, so that their
+///   `PartsList` attribute - and its dataver - follow the bridged endpoints being added and removed.
+///
+/// Bridged devices having more than one endpoint are modeled by nesting the additional endpoints under the
+/// endpoint of the bridged device (see `Bridge::add_child`). The aggregator, as well as each bridged endpoint,
+/// list their parts according to the tree pattern, i.e. only the bridged endpoints nested directly under them.
+///
+/// The endpoint IDs allocated to the bridged devices are tracked by their unique IDs and should be persisted,
+/// so that a bridged device keeps its endpoint ID across reboots, and endpoint IDs of removed devices
+/// are never re-used.
+pub struct Bridge<'a, const N: usize, M, H>
+where
+    M: RawMutex,
+{
+    aggregator_id: EndptId,
+    rand: Rand,
+    hooks: &'a dyn BridgeHooks,
+    node: RwLock<M, BridgeNode<'a, N, H>>,
+    store: Mutex<M, RefCell<BridgeStore<N>>>,
+    persist_state_changed: Notification<M>,
+}
+
+impl<'a, const N: usize, M, H> Bridge<'a, N, M, H>
+where
+    M: RawMutex,
+{
+    /// Create a new bridge.
+    ///
+    /// # Arguments
+    /// - `id`: The ID of the node
+    /// - `endpoints`: The static endpoints of the node, which must include the aggregator endpoint
+    /// - `aggregator_id`: The ID of the aggregator endpoint, whose parts are the bridged endpoints
+    /// - `rand`: A random number generator used for the datavers of the bridged endpoints
+    ///
+    /// No hooks are called until set with `Bridge::set_hooks`.
+    pub fn new(
+        id: u16,
+        endpoints: &[Endpoint<'a>],
+        aggregator_id: EndptId,
+        rand: Rand,
+    ) -> Result<Self, Error> {
+        let mut node = DynamicNode::new(id);
+
+        for endpoint in endpoints {
+            node.add(endpoint.clone())
+                .map_err(|_| ErrorCode::Duplicate)?;
+        }
+
+        if node.node().endpoint(aggregator_id).is_none() {
+            return Err(ErrorCode::EndpointNotFound.into());
+        }
+
+        let next_endpoint_id = endpoints.iter().map(|ep| ep.id + 1).max().unwrap_or(0);
+
+        Ok(Self {
+            aggregator_id,
+            rand,
+            hooks: &(),
+            node: RwLock::new(BridgeNode {
+                node,
+                endpoints: Vec::new(),
+            }),
+            store: Mutex::new(RefCell::new(BridgeStore::new(next_endpoint_id))),
+            persist_state_changed: Notification::new(),
+        })
+    }
+
+    /// Set the hooks notified of the events of the bridged devices.
+    pub fn set_hooks(&mut self, hooks: &'a dyn BridgeHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Load the endpoint IDs allocated to the bridged devices from a byte slice.
+    ///
+    /// Should be called before adding any bridged devices.
+    ///
+    /// # Arguments
+    /// - `data`: The byte slice to load the state from
+    pub fn load(&self, data: &[u8]) -> Result<(), Error> {
+        self.store.lock(|store| store.borrow_mut().load(data))
+    }
+
+    /// Store the endpoint IDs allocated to the bridged devices into a byte slice.
+    ///
+    /// # Arguments
+    /// - `buf`: The byte slice to store the state into
+    ///
+    /// Returns `Ok(None)` if the state has not changed, `Ok(Some(data))` if the state has changed
+    /// where `data` is the sub-slice of the buffer that contains the data to be persisted
+    pub fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        self.store.lock(|store| store.borrow_mut().store(buf))
+    }
+
+    /// Return `true` if the state has changed and needs to be persisted.
+    pub fn changed(&self) -> bool {
+        self.store.lock(|store| store.borrow().changed)
+    }
+
+    /// Wait for the state to be changed in a way that requires persisting.
+    pub async fn wait_persist(&self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+
+            self.persist_state_changed.wait().await;
+        }
+    }
+
+    /// Return the endpoint ID allocated to the bridged device with the given unique ID, if any.
+    ///
+    /// Note that the bridged device might not be currently added to the bridge,
+    /// as the endpoint IDs are kept until the device is removed.
+    pub fn endpoint_id(&self, unique_id: &str) -> Option<EndptId> {
+        self.store
+            .lock(|store| store.borrow().endpoint_id(unique_id))
+    }
+
+    /// Add a bridged device to the bridge.
+    ///
+    /// If the bridged device had been added before (possibly before a reboot), it gets the same endpoint ID as before.
+    /// Otherwise a new endpoint ID is allocated.
+    ///
+    /// # Arguments
+    /// - `subscriptions`: The subscriptions to notify of the change
+    /// - `unique_id`: The unique ID of the bridged device
+    /// - `device_types`: The device types of the bridged endpoint. Should include the Bridged Node device type
+    /// - `clusters`: The clusters of the bridged endpoint. Should include the Descriptor and the
+    ///   Bridged Device Basic Information clusters, which are served by the bridge itself
+    /// - `handler`: The handler serving all other clusters of the bridged endpoint
+    ///
+    /// Returns the ID of the bridged endpoint.
+    pub async fn add<const S: usize>(
+        &self,
+        subscriptions: &Subscriptions<S>,
+        unique_id: &str,
+        device_types: &'a [DeviceType],
+        clusters: &'a [Cluster<'a>],
+        handler: H,
+    ) -> Result<EndptId, Error> {
+        self.add_child(
+            subscriptions,
+            self.aggregator_id,
+            unique_id,
+            device_types,
+            clusters,
+            handler,
+        )
+        .await
+    }
+
+    /// Add a bridged endpoint nested under another bridged endpoint.
+    ///
+    /// Used for bridged devices having more than one endpoint (i.e. a power strip with
+    /// multiple outlets), where the endpoint of the bridged device is added with `Bridge::add`
+    /// and each of its additional endpoints is added as its child.
+    ///
+    /// # Arguments
+    /// - `subscriptions`: The subscriptions to notify of the change
+    /// - `parent`: The ID of the parent endpoint; either the aggregator endpoint or a bridged endpoint
+    /// - `unique_id`: The unique ID of the endpoint, used for keeping its endpoint ID stable
+    /// - `device_types`: The device types of the endpoint
+    /// - `clusters`: The clusters of the endpoint. Should include the Descriptor cluster, which is served by the bridge itself
+    /// - `handler`: The handler serving all other clusters of the endpoint
+    ///
+    /// Returns the ID of the bridged endpoint.
+    pub async fn add_child<const S: usize>(
+        &self,
+        subscriptions: &Subscriptions<S>,
+        parent: EndptId,
+        unique_id: &str,
+        device_types: &'a [DeviceType],
+        clusters: &'a [Cluster<'a>],
+        handler: H,
+    ) -> Result<EndptId, Error> {
+        let mut node = self.node.write().await;
+
+        if parent != self.aggregator_id {
+            node.endpoint(parent)?;
+        }
+
+        if node
+            .endpoints
+            .iter()
+            .any(|endpoint| endpoint.info.unique_id() == unique_id)
+        {
+            return Err(ErrorCode::Duplicate.into());
+        }
+
+        if node.endpoints.is_full() {
+            return Err(ErrorCode::NoSpace.into());
+        }
+
+        let info = BridgedHandler::new(Dataver::new_rand(self.rand), unique_id)?;
+
+        let (endpoint_id, changed) = self.store.lock(|store| {
+            let mut store = store.borrow_mut();
+            let changed = store.changed;
+
+            store
+                .allocate(unique_id)
+                .map(|(id, new)| (id, new.then_some(changed)))
+        })?;
+
+        if node
+            .node
+            .add(Endpoint::new(endpoint_id, device_types, clusters))
+            .is_err()
+        {
+            // Do not burn a newly allocated endpoint ID
+            if let Some(changed) = changed {
+                self.store
+                    .lock(|store| store.borrow_mut().unallocate(endpoint_id, changed));
+            }
+
+            return Err(ErrorCode::NoSpace.into());
+        }
+
+        self.persist_state_changed.notify();
+
+        unwrap!(node
+            .endpoints
+            .push(BridgedEndpoint {
+                id: endpoint_id,
+                parent,
+                desc_dataver: Dataver::new_rand(self.rand),
+                info,
+                handler,
+            })
+            .map_err(|_| ()));
+
+        self.parts_changed(subscriptions);
+
+        Ok(endpoint_id)
+    }
+
+    /// Remove a bridged device from the bridge.
+    ///
+    /// The endpoint ID of the bridged device is released and will not be re-used.
+    ///
+    /// Endpoints nested under the removed endpoint should be removed first.
+    ///
+    /// # Arguments
+    /// - `subscriptions`: The subscriptions to notify of the change
+    /// - `endpoint_id`: The ID of the bridged endpoint
+    ///
+    /// Returns the handler of the removed bridged endpoint.
+    pub async fn remove<const S: usize>(
+        &self,
+        subscriptions: &Subscriptions<S>,
+        endpoint_id: EndptId,
+    ) -> Result<H, Error> {
+        let mut node = self.node.write().await;
+
+        let index = node
+            .endpoints
+            .iter()
+            .position(|endpoint| endpoint.id == endpoint_id)
+            .ok_or(ErrorCode::EndpointNotFound)?;
+
+        if node
+            .endpoints
+            .iter()
+            .any(|endpoint| endpoint.parent == endpoint_id)
+        {
+            return Err(ErrorCode::InvalidAction.into());
+        }
+
+        let endpoint = node.endpoints.remove(index);
+        node.node.remove(endpoint_id);
+
+        self.store
+            .lock(|store| store.borrow_mut().release(endpoint_id));
+        self.persist_state_changed.notify();
+
+        self.parts_changed(subscriptions);
+
+        Ok(endpoint.handler)
+    }
+
+    /// Return `true` if the bridged device of the given endpoint is reachable.
+    pub async fn is_reachable(&self, endpoint_id: EndptId) -> Result<bool, Error> {
+        let node = self.node.read().await;
+
+        Ok(node.endpoint(endpoint_id)?.info.is_reachable())
+    }
+
+    /// Set the reachability of the bridged device of the given endpoint.
+    ///
+    /// Subscribers and the `BridgeHooks` (the `ReachableChanged` event) are notified if the reachability had changed.
+    ///
+    /// # Arguments
+    /// - `subscriptions`: The subscriptions to notify of the change
+    /// - `endpoint_id`: The ID of the bridged endpoint
+    /// - `reachable`: The new reachability of the bridged device
+    pub async fn set_reachable<const S: usize>(
+        &self,
+        subscriptions: &Subscriptions<S>,
+        endpoint_id: EndptId,
+        reachable: bool,
+    ) -> Result<(), Error> {
+        let node = self.node.read().await;
+
+        if node.endpoint(endpoint_id)?.info.set_reachable(reachable) {
+            subscriptions.notify_changed();
+            self.hooks.reachable_changed(endpoint_id, reachable);
+        }
+
+        Ok(())
+    }
+
+    /// Call the provided closure with the handler of the given bridged endpoint.
+    ///
+    /// Useful for updating the state of the bridged device as served by its handler.
+    pub async fn handler<F, R>(&self, endpoint_id: EndptId, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&H) -> R,
+    {
+        let node = self.node.read().await;
+
+        Ok(f(&node.endpoint(endpoint_id)?.handler))
+    }
+
+    fn parts_changed<const S: usize>(&self, subscriptions: &Subscriptions<S>) {
+        self.store.lock(|store| store.borrow_mut().version += 1);
+
+        subscriptions.notify_changed();
+    }
+
+    fn is_bridged(&self, endpoint_id: EndptId) -> bool {
+        // The Data Model holds the read lock while processing an operation,
+        // so the lock is never held for writing here
+        self.node
+            .try_read()
+            .map(|node| node.endpoint(endpoint_id).is_ok())
+            .unwrap_or(false)
+    }
+
+    fn is_part(&self, our_endpoint: EndptId, endpoint_id: EndptId) -> bool {
+        // See `is_bridged` for why `try_read` is sufficient
+        self.node
+            .try_read()
+            .map(|node| node.is_child(endpoint_id, our_endpoint))
+            .unwrap_or(false)
+    }
+
+    fn is_system_cluster(cluster_id: u32) -> bool {
+        cluster_id == DescHandler::CLUSTER.id || cluster_id == BridgedHandler::CLUSTER.id
+    }
+}
+
+impl<const N: usize, M, H> fmt::Debug for Bridge<'_, N, M, H>
+where
+    M: RawMutex,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bridge")
+            .field("aggregator_id", &self.aggregator_id)
+            .finish()
+    }
+}
+
+impl<const N: usize, M, H> PartsMatcher for Bridge<'_, N, M, H>
+where
+    M: RawMutex,
+{
+    fn matches(&self, our_endpoint: EndptId, endpoint: EndptId) -> bool {
+        if our_endpoint == ROOT_ENDPOINT_ID {
+            endpoint != our_endpoint
+        } else {
+            self.is_part(our_endpoint, endpoint)
+        }
+    }
+
+    fn version(&self) -> u32 {
+        self.store.lock(|store| store.borrow().version.0)
+    }
+}
+
+impl<const N: usize, M, H> Matcher for Bridge<'_, N, M, H>
+where
+    M: RawMutex,
+{
+    fn matches(&self, ctx: &MatchContext<'_>) -> bool {
+        let endpoint_id = match ctx {
+            MatchContext::Read(ctx) => ctx.attr().endpoint_id,
+            MatchContext::Write(ctx) => ctx.attr().endpoint_id,
+            MatchContext::Invoke(ctx) => ctx.cmd().endpoint_id,
+        };
+
+        self.is_bridged(endpoint_id)
+    }
+}
+
+impl<'a, const N: usize, M, H> AsyncMetadata for Bridge<'a, N, M, H>
+where
+    M: RawMutex,
+{
+    type MetadataGuard<'g>
+        = BridgeGuard<'g, 'a, N, M, H>
+    where
+        Self: 'g;
+
+    async fn lock(&self) -> Self::MetadataGuard<'_> {
+        BridgeGuard(self.node.read().await)
+    }
+}
+
+impl<const N: usize, M, H> AsyncHandler for Bridge<'_, N, M, H>
+where
+    M: RawMutex,
+    H: AsyncHandler,
+{
+    fn read_awaits(&self, ctx: &ReadContext<'_>) -> bool {
+        if Self::is_system_cluster(ctx.attr().cluster_id) {
+            return false;
+        }
+
+        self.node
+            .try_read()
+            .ok()
+            .and_then(|node| {
+                node.endpoint(ctx.attr().endpoint_id)
+                    .ok()
+                    .map(|endpoint| endpoint.handler.read_awaits(ctx))
+            })
+            .unwrap_or(true)
+    }
+
+    fn write_awaits(&self, ctx: &WriteContext<'_>) -> bool {
+        if Self::is_system_cluster(ctx.attr().cluster_id) {
+            return false;
+        }
+
+        self.node
+            .try_read()
+            .ok()
+            .and_then(|node| {
+                node.endpoint(ctx.attr().endpoint_id)
+                    .ok()
+                    .map(|endpoint| endpoint.handler.write_awaits(ctx))
+            })
+            .unwrap_or(true)
+    }
+
+    fn invoke_awaits(&self, ctx: &InvokeContext<'_>) -> bool {
+        if Self::is_system_cluster(ctx.cmd().cluster_id) {
+            return false;
+        }
+
+        self.node
+            .try_read()
+            .ok()
+            .and_then(|node| {
+                node.endpoint(ctx.cmd().endpoint_id)
+                    .ok()
+                    .map(|endpoint| endpoint.handler.invoke_awaits(ctx))
+            })
+            .unwrap_or(true)
+    }
+
+    async fn read(
+        &self,
+        ctx: &ReadContext<'_>,
+        encoder: AttrDataEncoder<'_, '_, '_>,
+    ) -> Result<(), Error> {
+        let node = self.node.read().await;
+        let endpoint = node.endpoint(ctx.attr().endpoint_id)?;
+
+        if ctx.attr().cluster_id == DescHandler::CLUSTER.id {
+            desc::HandlerAdaptor(endpoint.desc(self)).read(ctx, encoder)
+        } else if ctx.attr().cluster_id == BridgedHandler::CLUSTER.id {
+            HandlerAdaptor(&endpoint.info).read(ctx, encoder)
+        } else {
+            endpoint.handler.read(ctx, encoder).await
+        }
+    }
+
+    async fn write(&self, ctx: &WriteContext<'_>) -> Result<(), Error> {
+        let node = self.node.read().await;
+        let endpoint = node.endpoint(ctx.attr().endpoint_id)?;
+
+        if ctx.attr().cluster_id == DescHandler::CLUSTER.id {
+            desc::HandlerAdaptor(endpoint.desc(self)).write(ctx)
+        } else if ctx.attr().cluster_id == BridgedHandler::CLUSTER.id {
+            HandlerAdaptor(&endpoint.info).write(ctx)
+        } else {
+            endpoint.handler.write(ctx).await
+        }
+    }
+
+    async fn invoke(
+        &self,
+        ctx: &InvokeContext<'_>,
+        encoder: CmdDataEncoder<'_, '_, '_>,
+    ) -> Result<(), Error> {
+        let node = self.node.read().await;
+        let endpoint = node.endpoint(ctx.cmd().endpoint_id)?;
+
+        if ctx.cmd().cluster_id == DescHandler::CLUSTER.id {
+            desc::HandlerAdaptor(endpoint.desc(self)).invoke(ctx, encoder)
+        } else if ctx.cmd().cluster_id == BridgedHandler::CLUSTER.id {
+            HandlerAdaptor(&endpoint.info).invoke(ctx, encoder)
+        } else {
+            endpoint.handler.invoke(ctx, encoder).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use crate::data_model::device_types::{DEV_TYPE_AGGREGATOR, DEV_TYPE_BRIDGED_NODE};
+    use crate::data_model::objects::{Cluster, DeviceType, Endpoint, EndptId};
+    use crate::data_model::root_endpoint;
+    use crate::data_model::sdm::net_comm::NetworkType;
+    use crate::data_model::subscriptions::Subscriptions;
+    use crate::data_model::system_model::desc::{ClusterHandler as _, DescHandler, PartsMatcher};
+    use crate::error::ErrorCode;
+    use crate::utils::rand::dummy_rand;
+    use crate::{clusters, devices};
+
+    use core::cell::Cell;
+
+    use super::{Bridge, BridgeHooks, BridgedHandler, ClusterHandler as _};
+
+    const AGGREGATOR: EndptId = 1;
+
+    const ENDPOINTS: &[Endpoint<'static>] = &[
+        root_endpoint::root_endpoint(NetworkType::Ethernet),
+        Endpoint::new(
+            AGGREGATOR,
+            devices!(DEV_TYPE_AGGREGATOR),
+            clusters!(DescHandler::CLUSTER),
+        ),
+    ];
+
+    const DEVICE_TYPES: &[DeviceType] = devices!(DEV_TYPE_BRIDGED_NODE);
+
+    const CLUSTERS: &[Cluster<'static>] = clusters!(DescHandler::CLUSTER, BridgedHandler::CLUSTER);
+
+    type TestBridge = Bridge<'static, 6, NoopRawMutex, ()>;
+
+    fn add(bridge: &TestBridge, subscriptions: &Subscriptions<1>, unique_id: &str) -> EndptId {
+        unwrap!(block_on(bridge.add(
+            subscriptions,
+            unique_id,
+            DEVICE_TYPES,
+            CLUSTERS,
+            ()
+        )))
+    }
+
+    #[test]
+    fn test_endpoint_ids() {
+        let subscriptions = Subscriptions::<1>::new();
+        let bridge = unwrap!(TestBridge::new(0, ENDPOINTS, AGGREGATOR, dummy_rand));
+
+        assert_eq!(add(&bridge, &subscriptions, "a"), 2);
+        assert_eq!(add(&bridge, &subscriptions, "b"), 3);
+
+        // The same device cannot be added twice
+        assert_eq!(
+            block_on(bridge.add(&subscriptions, "a", DEVICE_TYPES, CLUSTERS, ()))
+                .map_err(|e| e.code()),
+            Err(ErrorCode::Duplicate)
+        );
+
+        // Endpoint IDs of removed devices are not re-used
+        unwrap!(block_on(bridge.remove(&subscriptions, 2)));
+        assert_eq!(bridge.endpoint_id("a"), None);
+        assert_eq!(add(&bridge, &subscriptions, "c"), 4);
+        assert_eq!(add(&bridge, &subscriptions, "a"), 5);
+
+        assert_eq!(
+            block_on(bridge.remove(&subscriptions, 2)).map_err(|e| e.code()),
+            Err(ErrorCode::EndpointNotFound)
+        );
+    }
+
+    #[test]
+    fn test_no_space() {
+        let subscriptions = Subscriptions::<1>::new();
+        let bridge = unwrap!(Bridge::<'static, 3, NoopRawMutex, ()>::new(
+            0, ENDPOINTS, AGGREGATOR, dummy_rand
+        ));
+
+        let add = |unique_id| {
+            block_on(bridge.add(&subscriptions, unique_id, DEVICE_TYPES, CLUSTERS, ()))
+                .map_err(|e| e.code())
+        };
+
+        assert_eq!(add("a"), Ok(2));
+        let mut buf = [0; 256];
+        unwrap!(bridge.store(&mut buf));
+
+        // A device which cannot be added does not burn an endpoint ID
+        assert_eq!(add("b"), Err(ErrorCode::NoSpace));
+        assert_eq!(bridge.endpoint_id("b"), None);
+        assert!(!bridge.changed());
+
+        unwrap!(block_on(bridge.remove(&subscriptions, 2)));
+        assert_eq!(add("b"), Ok(3));
+    }
+
+    #[test]
+    fn test_persist() {
+        let subscriptions = Subscriptions::<1>::new();
+        let bridge = unwrap!(TestBridge::new(0, ENDPOINTS, AGGREGATOR, dummy_rand));
+
+        add(&bridge, &subscriptions, "a");
+        add(&bridge, &subscriptions, "b");
+        unwrap!(block_on(bridge.remove(&subscriptions, 2)));
+
+        assert!(bridge.changed());
+
+        let mut buf = [0; 256];
+        let data = unwrap!(unwrap!(bridge.store(&mut buf)));
+
+        assert!(!bridge.changed());
+
+        // After a reboot, a known device keeps its endpoint ID,
+        // and new devices do not get the IDs of removed ones
+        let bridge = unwrap!(TestBridge::new(0, ENDPOINTS, AGGREGATOR, dummy_rand));
+        unwrap!(bridge.load(data));
+
+        assert_eq!(bridge.endpoint_id("b"), Some(3));
+        assert_eq!(add(&bridge, &subscriptions, "c"), 4);
+        assert_eq!(add(&bridge, &subscriptions, "b"), 3);
+    }
+
+    #[test]
+    fn test_parts() {
+        let subscriptions = Subscriptions::<1>::new();
+        let bridge = unwrap!(TestBridge::new(0, ENDPOINTS, AGGREGATOR, dummy_rand));
+
+        let version = bridge.version();
+
+        let a = add(&bridge, &subscriptions, "a");
+        assert_ne!(bridge.version(), version);
+
+        // The root endpoint has all other endpoints as parts
+        assert!(PartsMatcher::matches(&bridge, 0, AGGREGATOR));
+        assert!(PartsMatcher::matches(&bridge, 0, a));
+
+        // The aggregator has only the bridged endpoints as parts
+        assert!(!PartsMatcher::matches(&bridge, AGGREGATOR, 0));
+        assert!(PartsMatcher::matches(&bridge, AGGREGATOR, a));
+
+        // Bridged endpoints without nested endpoints have no parts
+        assert!(!PartsMatcher::matches(&bridge, a, AGGREGATOR));
+
+        let version = bridge.version();
+
+        unwrap!(block_on(bridge.remove(&subscriptions, a)));
+        assert_ne!(bridge.version(), version);
+        assert!(!PartsMatcher::matches(&bridge, AGGREGATOR, a));
+    }
+
+    #[test]
+    fn test_nested_parts() {
+        let subscriptions = Subscriptions::<1>::new();
+        let bridge = unwrap!(TestBridge::new(0, ENDPOINTS, AGGREGATOR, dummy_rand));
+
+        let strip = add(&bridge, &subscriptions, "strip");
+        let outlet = unwrap!(block_on(bridge.add_child(
+            &subscriptions,
+            strip,
+            "outlet",
+            DEVICE_TYPES,
+            CLUSTERS,
+            ()
+        )));
+        let plug = unwrap!(block_on(bridge.add_child(
+            &subscriptions,
+            outlet,
+            "plug",
+            DEVICE_TYPES,
+            CLUSTERS,
+            ()
+        )));
+
+        // Children can only be added to existing endpoints
+        assert_eq!(
+            block_on(bridge.add_child(&subscriptions, 42, "x", DEVICE_TYPES, CLUSTERS, ()))
+                .map_err(|e| e.code()),
+            Err(ErrorCode::EndpointNotFound)
+        );
+
+        // The root endpoint lists all endpoints, while the aggregator and the bridged endpoints
+        // list only their direct children
+        for ep in [strip, outlet, plug] {
+            assert!(PartsMatcher::matches(&bridge, 0, ep));
+        }
+
+        assert!(PartsMatcher::matches(&bridge, AGGREGATOR, strip));
+        assert!(!PartsMatcher::matches(&bridge, AGGREGATOR, outlet));
+        assert!(!PartsMatcher::matches(&bridge, AGGREGATOR, plug));
+
+        assert!(PartsMatcher::matches(&bridge, strip, outlet));
+        assert!(!PartsMatcher::matches(&bridge, strip, plug));
+        assert!(PartsMatcher::matches(&bridge, outlet, plug));
+        assert!(!PartsMatcher::matches(&bridge, outlet, strip));
+        assert!(!PartsMatcher::matches(&bridge, plug, outlet));
+
+        // Endpoints having children cannot be removed
+        assert_eq!(
+            block_on(bridge.remove(&subscriptions, outlet)).map_err(|e| e.code()),
+            Err(ErrorCode::InvalidAction)
+        );
+
+        unwrap!(block_on(bridge.remove(&subscriptions, plug)));
+        unwrap!(block_on(bridge.remove(&subscriptions, outlet)));
+        assert!(!PartsMatcher::matches(&bridge, strip, outlet));
+    }
+
+    #[test]
+    fn test_reachable() {
+        let subscriptions = Subscriptions::<1>::new();
+        let bridge = unwrap!(TestBridge::new(0, ENDPOINTS, AGGREGATOR, dummy_rand));
+
+        let a = add(&bridge, &subscriptions, "a");
+        assert!(unwrap!(block_on(bridge.is_reachable(a))));
+
+        unwrap!(block_on(bridge.set_reachable(&subscriptions, a, false)));
+        assert!(!unwrap!(block_on(bridge.is_reachable(a))));
+    }
+
+    #[test]
+    fn test_reachable_changed() {
+        struct Hooks(Cell<Option<(EndptId, bool)>>);
+
+        impl BridgeHooks for Hooks {
+            fn reachable_changed(&self, endpoint_id: EndptId, reachable: bool) {
+                self.0.set(Some((endpoint_id, reachable)));
+            }
+        }
+
+        let hooks = Hooks(Cell::new(None));
+
+        let subscriptions = Subscriptions::<1>::new();
+        let mut bridge = unwrap!(Bridge::<'_, 6, NoopRawMutex, ()>::new(
+            0, ENDPOINTS, AGGREGATOR, dummy_rand
+        ));
+        bridge.set_hooks(&hooks);
+
+        let a = unwrap!(block_on(bridge.add(
+            &subscriptions,
+            "a",
+            DEVICE_TYPES,
+            CLUSTERS,
+            ()
+        )));
+
+        // No event if the reachability does not change
+        unwrap!(block_on(bridge.set_reachable(&subscriptions, a, true)));
+        assert_eq!(hooks.0.take(), None);
+
+        unwrap!(block_on(bridge.set_reachable(&subscriptions, a, false)));
+        assert_eq!(hooks.0.take(), Some((a, false)));
+
+        unwrap!(block_on(bridge.set_reachable(&subscriptions, a, true)));
+        assert_eq!(hooks.0.take(), Some((a, true)));
+    }
+}
@@ -18,22 +18,60 @@
 //! This module imports all system clusters that are used by the `rs-matter` itself.
 //!
 //! Additionally, it imports the following extra ones:
-//! - OnOff - for demoing purposes
+//! - BridgedDeviceBasicInformation - for Matter bridges
+//! - Identify - for application endpoints
+//! - DoorLock - for smart locks
+//! - Thermostat, ThermostatUserInterfaceConfiguration - for HVAC controllers
+//! - WindowCovering - for blinds and shades
+//! - TemperatureMeasurement, RelativeHumidityMeasurement, PressureMeasurement, FlowMeasurement,
+//!   IlluminanceMeasurement, OccupancySensing, BooleanState, AirQuality and the Concentration Measurement
+//!   clusters - for sensors
+//! - PowerSource, PowerSourceConfiguration - for battery-powered and wired devices
+//! - OnOff, LevelControl, ColorControl, Scenes - for lighting devices
 //! - UnitTesting - for testing purposes
 
 crate::import!(
     AdministratorCommissioning,
     AccessControl,
+    AirQuality,
     BasicInformation,
+    BooleanState,
+    BridgedDeviceBasicInformation,
+    CarbonDioxideConcentrationMeasurement,
+    CarbonMonoxideConcentrationMeasurement,
+    ColorControl,
     Descriptor,
+    DoorLock,
     EthernetNetworkDiagnostics,
+    FlowMeasurement,
+    FormaldehydeConcentrationMeasurement,
     GeneralDiagnostics,
     GeneralCommissioning,
     GroupKeyManagement,
+    Identify,
+    IlluminanceMeasurement,
+    LevelControl,
     NetworkCommissioning,
+    NitrogenDioxideConcentrationMeasurement,
+    OccupancySensing,
     OnOff,
     OperationalCredentials,
+    OzoneConcentrationMeasurement,
+    Pm1ConcentrationMeasurement,
+    Pm10ConcentrationMeasurement,
+    Pm25ConcentrationMeasurement,
+    PowerSource,
+    PowerSourceConfiguration,
+    PressureMeasurement,
+    RadonConcentrationMeasurement,
+    RelativeHumidityMeasurement,
+    Scenes,
+    TemperatureMeasurement,
+    Thermostat,
+    ThermostatUserInterfaceConfiguration,
     ThreadNetworkDiagnostics,
+    TotalVolatileOrganicCompoundsConcentrationMeasurement,
     UnitTesting,
     WiFiNetworkDiagnostics,
+    WindowCovering,
 );
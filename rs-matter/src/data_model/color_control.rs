@@ -0,0 +1,2116 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Color Control cluster and its handler.
+//!
+//! The handler implements all features of the cluster (Hue/Saturation, Enhanced Hue, Color Loop, XY and
+//! Color Temperature), i.e. it is suitable for Extended Color Lights.
+
+use embassy_futures::select::select;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{FromTLV, Nullable, TLVElement, TLVTag, TLVWrite, ToTLV};
+use crate::utils::cell::RefCell;
+use crate::utils::storage::WriteBuf;
+use crate::utils::sync::Notification;
+use crate::with;
+
+use super::level_control::{ColorTempCoupling, MAX_LEVEL, MIN_LEVEL};
+use super::objects::{Cluster, Dataver, InvokeContext, ReadContext, WriteContext};
+use super::on_off::OnOffHandler;
+use super::scenes::{SceneAttributeValue, SceneAttributeValues, SceneExtension};
+
+pub use crate::data_model::clusters::color_control::*;
+
+/// The maximum value of the `CurrentSaturation` attribute.
+pub const MAX_SATURATION: u8 = 254;
+/// The maximum value of the `CurrentX` and `CurrentY` attributes.
+pub const MAX_XY: u16 = 0xfeff;
+
+/// The default physical minimum color temperature (6500K) of the device.
+pub const DEFAULT_MIN_MIREDS: u16 = 153;
+/// The default physical maximum color temperature (2000K) of the device.
+pub const DEFAULT_MAX_MIREDS: u16 = 500;
+
+/// The `EnhancedColorMode` attribute value when the `EnhancedCurrentHue` attribute is in use.
+const ENHANCED_COLOR_MODE_ENHANCED_HUE: u8 = 3;
+
+/// The `ExecuteIfOff` bit of the `Options` attribute.
+const OPTIONS_EXECUTE_IF_OFF: u8 = 0x01;
+
+/// How often the transitions are advanced.
+const TRANSITION_TICK_MS: u64 = 100;
+
+/// The full circle of the `EnhancedCurrentHue` attribute.
+const HUE_CIRCLE: i32 = 0x10000;
+
+/// A color, as applied to the hardware of the device.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Color {
+    /// Hue (`EnhancedCurrentHue` units, i.e. 1/65536 of the full circle) and saturation (0 - 254)
+    HueSaturation { hue: u16, saturation: u8 },
+    /// CIE 1931 chromaticity coordinates, scaled to 0 - 65536
+    Xy { x: u16, y: u16 },
+    /// Color temperature in mireds
+    Temperature { mireds: u16 },
+}
+
+impl Color {
+    /// Return the CIE 1931 chromaticity coordinates of the color, converting if necessary.
+    ///
+    /// Useful for RGB(W) drivers which need to convert all color modes to the color space of their LEDs.
+    pub fn xy(&self) -> (u16, u16) {
+        match *self {
+            Self::HueSaturation { hue, saturation } => hs_to_xy(hue, saturation),
+            Self::Xy { x, y } => (x, y),
+            Self::Temperature { mireds } => mireds_to_xy(mireds),
+        }
+    }
+}
+
+/// Convert hue (`EnhancedCurrentHue` units) and saturation (0 - 254) to CIE 1931 chromaticity coordinates.
+///
+/// The conversion assumes the sRGB primaries and full brightness, and ignores the sRGB gamma.
+pub fn hs_to_xy(hue: u16, saturation: u8) -> (u16, u16) {
+    let h = hue as f32 * 6.0 / HUE_CIRCLE as f32;
+    let s = saturation as f32 / MAX_SATURATION as f32;
+
+    let sector = h as u32;
+    let f = h - sector as f32;
+
+    let p = 1.0 - s;
+    let q = 1.0 - s * f;
+    let t = 1.0 - s * (1.0 - f);
+
+    let (r, g, b) = match sector {
+        0 => (1.0, t, p),
+        1 => (q, 1.0, p),
+        2 => (p, 1.0, t),
+        3 => (p, q, 1.0),
+        4 => (t, p, 1.0),
+        _ => (1.0, p, q),
+    };
+
+    rgb_to_xy(r, g, b)
+}
+
+/// Convert CIE 1931 chromaticity coordinates to hue (`EnhancedCurrentHue` units) and saturation (0 - 254).
+///
+/// The conversion assumes the sRGB primaries and full brightness, and ignores the sRGB gamma.
+/// Colors outside of the sRGB gamut are clipped.
+pub fn xy_to_hs(x: u16, y: u16) -> (u16, u8) {
+    let x = x as f32 / 65536.0;
+    let y = (y as f32 / 65536.0).max(f32::EPSILON);
+
+    let cx = x / y;
+    let cz = (1.0 - x - y) / y;
+
+    let r = (3.2406 * cx - 1.5372 - 0.4986 * cz).max(0.0);
+    let g = (-0.9689 * cx + 1.8758 + 0.0415 * cz).max(0.0);
+    let b = (0.0557 * cx - 0.2040 + 1.0570 * cz).max(0.0);
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+
+    if max <= 0.0 || max - min <= 0.0 {
+        return (0, 0);
+    }
+
+    let delta = max - min;
+
+    let h = if max == r {
+        ((g - b) / delta + 6.0) % 6.0
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+
+    let hue = (h / 6.0 * HUE_CIRCLE as f32 + 0.5) as u32 % HUE_CIRCLE as u32;
+    let saturation = (delta / max * MAX_SATURATION as f32 + 0.5) as u8;
+
+    (hue as u16, saturation.min(MAX_SATURATION))
+}
+
+/// Convert a color temperature in mireds to CIE 1931 chromaticity coordinates,
+/// using the approximation of the Planckian locus by Kim et al.
+pub fn mireds_to_xy(mireds: u16) -> (u16, u16) {
+    let t = (1_000_000.0 / mireds.max(1) as f32).clamp(1667.0, 25000.0);
+
+    let (t1, t2, t3) = (
+        1000.0 / t,
+        1_000_000.0 / (t * t),
+        1_000_000_000.0 / (t * t * t),
+    );
+
+    let x = if t <= 4000.0 {
+        -0.2661239 * t3 - 0.2343589 * t2 + 0.8776956 * t1 + 0.179910
+    } else {
+        -3.025_847 * t3 + 2.1070379 * t2 + 0.2226347 * t1 + 0.240390
+    };
+
+    let (x2, x3) = (x * x, x * x * x);
+
+    let y = if t <= 2222.0 {
+        -1.1063814 * x3 - 1.348_110_2 * x2 + 2.185_558_3 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x3 - 1.374_185_9 * x2 + 2.091_37 * x - 0.16748867
+    } else {
+        3.081_758 * x3 - 5.873_387 * x2 + 3.751_129_9 * x - 0.37001483
+    };
+
+    (scale_xy(x), scale_xy(y))
+}
+
+/// Convert CIE 1931 chromaticity coordinates to the correlated color temperature in mireds,
+/// using McCamy's approximation.
+pub fn xy_to_mireds(x: u16, y: u16) -> u16 {
+    let x = x as f32 / 65536.0;
+    let y = y as f32 / 65536.0;
+
+    let denominator = 0.1858 - y;
+    if denominator.abs() <= f32::EPSILON {
+        return DEFAULT_MIN_MIREDS;
+    }
+
+    let n = (x - 0.3320) / denominator;
+    let cct = 449.0 * n * n * n + 3525.0 * n * n + 6823.3 * n + 5520.33;
+
+    (1_000_000.0 / cct.clamp(1000.0, 1_000_000.0) + 0.5) as u16
+}
+
+fn rgb_to_xy(r: f32, g: f32, b: f32) -> (u16, u16) {
+    let cx = 0.4124 * r + 0.3576 * g + 0.1805 * b;
+    let cy = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let cz = 0.0193 * r + 0.1192 * g + 0.9505 * b;
+
+    let sum = cx + cy + cz;
+    if sum <= 0.0 {
+        return (0, 0);
+    }
+
+    (scale_xy(cx / sum), scale_xy(cy / sum))
+}
+
+fn scale_xy(value: f32) -> u16 {
+    ((value * 65536.0 + 0.5).max(0.0) as u32).min(MAX_XY as u32) as u16
+}
+
+/// A trait for the hardware (or the driver) of a device with a controllable color.
+///
+/// Note that the color is reported regardless of the state of the On/Off cluster.
+pub trait ColorControlHooks {
+    /// Apply the given color to the device.
+    fn set_color(&self, color: Color);
+}
+
+impl<T> ColorControlHooks for &T
+where
+    T: ColorControlHooks,
+{
+    fn set_color(&self, color: Color) {
+        (*self).set_color(color)
+    }
+}
+
+/// A no-op implementation for devices that only need to observe the color with `ColorControlHandler::get`.
+impl ColorControlHooks for () {
+    fn set_color(&self, _color: Color) {}
+}
+
+/// A linear change of a color component over time.
+#[derive(Copy, Clone)]
+struct Ramp {
+    from: i32,
+    delta: i32,
+}
+
+impl Ramp {
+    const fn new(from: i32, delta: i32) -> Self {
+        Self { from, delta }
+    }
+
+    fn at(&self, elapsed: Duration, period: Duration) -> i64 {
+        let period = period.as_micros();
+
+        if period == 0 {
+            (self.from + self.delta) as _
+        } else {
+            self.from as i64 + self.delta as i64 * elapsed.as_micros() as i64 / period as i64
+        }
+    }
+}
+
+/// A transition of the color which is in progress.
+#[derive(Clone)]
+struct Transition {
+    start: Instant,
+    /// The time over which the components change by the deltas of their ramps
+    period: Duration,
+    /// When the transition completes; `None` for moves which complete only when they reach the limits
+    /// of their components, or when stopped
+    duration: Option<Duration>,
+    hue: Option<Ramp>,
+    saturation: Option<Ramp>,
+    x: Option<Ramp>,
+    y: Option<Ramp>,
+    mireds: Option<Ramp>,
+    /// The limits of the color temperature during the transition
+    mireds_range: (u16, u16),
+    color_loop: bool,
+}
+
+impl Transition {
+    /// A transition which completes after the given time.
+    fn timed(duration: Duration, mireds_range: (u16, u16)) -> Self {
+        Self {
+            start: Instant::now(),
+            period: duration,
+            duration: Some(duration),
+            hue: None,
+            saturation: None,
+            x: None,
+            y: None,
+            mireds: None,
+            mireds_range,
+            color_loop: false,
+        }
+    }
+
+    /// A move at a rate of the deltas of the ramps per second.
+    fn rate(mireds_range: (u16, u16)) -> Self {
+        Self {
+            period: Duration::from_secs(1),
+            duration: None,
+            ..Self::timed(Duration::from_secs(1), mireds_range)
+        }
+    }
+}
+
+/// The state of the Color Control cluster.
+struct ColorState {
+    color_mode: ColorMode,
+    enhanced_hue_mode: bool,
+    enhanced_hue: u16,
+    saturation: u8,
+    x: u16,
+    y: u16,
+    mireds: u16,
+    options: u8,
+    remaining_time: u16,
+    loop_active: bool,
+    loop_direction: ColorLoopDirection,
+    loop_time: u16,
+    loop_start_hue: u16,
+    loop_stored_hue: u16,
+    start_up_mireds: Option<u16>,
+    transition: Option<Transition>,
+    changed: bool,
+}
+
+impl ColorState {
+    const fn new() -> Self {
+        Self {
+            color_mode: ColorMode::ColorTemperature,
+            enhanced_hue_mode: false,
+            enhanced_hue: 0,
+            saturation: 0,
+            x: 0x616b,
+            y: 0x607d,
+            mireds: 250,
+            options: 0,
+            remaining_time: 0,
+            loop_active: false,
+            loop_direction: ColorLoopDirection::IncrementHue,
+            loop_time: 25,
+            loop_start_hue: 0x2300,
+            loop_stored_hue: 0,
+            start_up_mireds: None,
+            transition: None,
+            changed: false,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self.color_mode {
+            ColorMode::CurrentHueAndCurrentSaturation => Color::HueSaturation {
+                hue: self.enhanced_hue,
+                saturation: self.saturation,
+            },
+            ColorMode::CurrentXAndCurrentY => Color::Xy {
+                x: self.x,
+                y: self.y,
+            },
+            ColorMode::ColorTemperature => Color::Temperature {
+                mireds: self.mireds,
+            },
+        }
+    }
+
+    /// Switch to the given color mode, converting the current color to the attributes of the new mode,
+    /// so that transitions in the new mode start from the current color.
+    fn switch_mode(&mut self, color_mode: ColorMode, enhanced_hue_mode: bool) {
+        if self.color_mode != color_mode {
+            let color = self.color();
+
+            match color_mode {
+                ColorMode::CurrentHueAndCurrentSaturation => {
+                    let (x, y) = color.xy();
+                    (self.enhanced_hue, self.saturation) = xy_to_hs(x, y);
+                }
+                ColorMode::CurrentXAndCurrentY => {
+                    (self.x, self.y) = color.xy();
+                }
+                ColorMode::ColorTemperature => {
+                    let (x, y) = color.xy();
+                    self.mireds = xy_to_mireds(x, y);
+                }
+            }
+
+            self.color_mode = color_mode;
+        }
+
+        self.enhanced_hue_mode =
+            color_mode == ColorMode::CurrentHueAndCurrentSaturation && enhanced_hue_mode;
+    }
+}
+
+/// A handler for the Color Control Matter cluster, implementing all of its features.
+///
+/// The handler drives the device via the `ColorControlHooks` trait. The color transitions and the color loop are
+/// only running while the `ColorControlHandler::run` future is polled.
+///
+/// When the handler is used together with an `OnOffHandler` (see `ColorControlHandler::set_on_off`), the color
+/// commands are only executed while the device is on, unless the `ExecuteIfOff` option says otherwise.
+///
+/// When the handler is used together with a `LevelControlHandler`, the latter should be coupled with the former
+/// via `LevelControlHandler::set_color_control`, so that the color temperature follows the level when the
+/// `CoupleColorTempToLevel` option of the Level Control cluster is set.
+///
+/// The color, as well as the `Options` and `StartUpColorTemperatureMireds` attributes should be persisted
+/// (see `ColorControlHandler::load` and `ColorControlHandler::store`), so that the `StartUpColorTemperatureMireds`
+/// behavior can be applied when the device boots.
+pub struct ColorControlHandler<'a> {
+    dataver: Dataver,
+    state: RefCell<ColorState>,
+    min_mireds: u16,
+    max_mireds: u16,
+    hooks: &'a dyn ColorControlHooks,
+    on_off: Option<&'a OnOffHandler<'a>>,
+    transition_changed: Notification<NoopRawMutex>,
+    persist_state_changed: Notification<NoopRawMutex>,
+}
+
+impl<'a> ColorControlHandler<'a> {
+    /// Creates a new instance of `ColorControlHandler` with the given `Dataver`.
+    ///
+    /// The physical color temperature range of the device is `DEFAULT_MIN_MIREDS..=DEFAULT_MAX_MIREDS`.
+    pub const fn new(dataver: Dataver) -> Self {
+        Self::new_with_mireds(dataver, DEFAULT_MIN_MIREDS, DEFAULT_MAX_MIREDS)
+    }
+
+    /// Creates a new instance of `ColorControlHandler` with the given `Dataver`
+    /// and the given physical color temperature range of the device.
+    pub const fn new_with_mireds(dataver: Dataver, min_mireds: u16, max_mireds: u16) -> Self {
+        Self {
+            dataver,
+            state: RefCell::new(ColorState::new()),
+            min_mireds,
+            max_mireds,
+            hooks: &(),
+            on_off: None,
+            transition_changed: Notification::new(),
+            persist_state_changed: Notification::new(),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Set the hooks which drive the hardware of the device.
+    ///
+    /// Should be called before `ColorControlHandler::load`, so that the persisted color is applied to the hardware.
+    pub fn set_hooks(&mut self, hooks: &'a dyn ColorControlHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Set the On/Off cluster handler of the same endpoint.
+    pub fn set_on_off(&mut self, on_off: &'a OnOffHandler<'a>) {
+        self.on_off = Some(on_off);
+    }
+
+    /// Return the current color.
+    pub fn get(&self) -> Color {
+        self.state.borrow().color()
+    }
+
+    /// Set the current color, stopping the transition in progress (including the color loop), if any.
+    ///
+    /// Suitable for reflecting color changes triggered locally (i.e. by the HAL).
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn set(&self, color: Color) {
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.transition = None;
+            state.loop_active = false;
+            state.remaining_time = 0;
+
+            match color {
+                Color::HueSaturation { hue, saturation } => {
+                    state.color_mode = ColorMode::CurrentHueAndCurrentSaturation;
+                    state.enhanced_hue = hue;
+                    state.saturation = saturation.min(MAX_SATURATION);
+                }
+                Color::Xy { x, y } => {
+                    state.color_mode = ColorMode::CurrentXAndCurrentY;
+                    state.x = x.min(MAX_XY);
+                    state.y = y.min(MAX_XY);
+                }
+                Color::Temperature { mireds } => {
+                    state.color_mode = ColorMode::ColorTemperature;
+                    state.mireds = mireds.clamp(self.min_mireds, self.max_mireds);
+                }
+            }
+        }
+
+        self.apply();
+        self.settled();
+    }
+
+    /// Run the color transitions and the color loop.
+    ///
+    /// # Arguments
+    /// - `notify`: A callback which is called whenever the transitions change the state of the cluster,
+    ///   and which is expected to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub async fn run<F>(&self, notify: F) -> Result<(), Error>
+    where
+        F: Fn(),
+    {
+        loop {
+            if self.state.borrow().transition.is_some() {
+                let next = self.step(Instant::now());
+
+                notify();
+
+                if let Some(next) = next {
+                    select(Timer::at(next), self.transition_changed.wait()).await;
+                    continue;
+                }
+            }
+
+            self.transition_changed.wait().await;
+        }
+    }
+
+    /// Load the persisted color, `Options` and `StartUpColorTemperatureMireds` attributes from a byte slice,
+    /// and apply the `StartUpColorTemperatureMireds` behavior.
+    ///
+    /// Should be called once, when the device boots.
+    ///
+    /// # Arguments
+    /// - `data`: The byte slice to load the state from
+    pub fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVElement::new(data);
+        let seq = root.structure()?;
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.color_mode = match seq.ctx(0)?.u8()? {
+                0 => ColorMode::CurrentHueAndCurrentSaturation,
+                1 => ColorMode::CurrentXAndCurrentY,
+                _ => ColorMode::ColorTemperature,
+            };
+            state.enhanced_hue_mode = seq.ctx(1)?.bool()?;
+            state.enhanced_hue = seq.ctx(2)?.u16()?;
+            state.saturation = seq.ctx(3)?.u8()?.min(MAX_SATURATION);
+            state.x = seq.ctx(4)?.u16()?.min(MAX_XY);
+            state.y = seq.ctx(5)?.u16()?.min(MAX_XY);
+            state.mireds = seq.ctx(6)?.u16()?.clamp(self.min_mireds, self.max_mireds);
+            state.options = seq.ctx(7)?.u8()?;
+            state.start_up_mireds = Nullable::<u16>::from_tlv(&seq.ctx(8)?)?.into_option();
+            state.transition = None;
+            state.loop_active = false;
+            state.changed = false;
+
+            if let Some(mireds) = state.start_up_mireds {
+                state.color_mode = ColorMode::ColorTemperature;
+                state.enhanced_hue_mode = false;
+                state.mireds = mireds.clamp(self.min_mireds, self.max_mireds);
+                state.changed = true;
+            }
+        }
+
+        self.apply();
+
+        Ok(())
+    }
+
+    /// Store the color, `Options` and `StartUpColorTemperatureMireds` attributes into a byte slice.
+    ///
+    /// # Arguments
+    /// - `buf`: The byte slice to store the state into
+    ///
+    /// Returns `Ok(None)` if the state has not changed, `Ok(Some(data))` if the state has changed
+    /// where `data` is the sub-slice of the buffer that contains the data to be persisted
+    pub fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        let mut state = self.state.borrow_mut();
+
+        if !state.changed {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        wb.start_struct(&TLVTag::Anonymous)?;
+        wb.u8(&TLVTag::Context(0), state.color_mode as _)?;
+        wb.bool(&TLVTag::Context(1), state.enhanced_hue_mode)?;
+        wb.u16(&TLVTag::Context(2), state.enhanced_hue)?;
+        wb.u8(&TLVTag::Context(3), state.saturation)?;
+        wb.u16(&TLVTag::Context(4), state.x)?;
+        wb.u16(&TLVTag::Context(5), state.y)?;
+        wb.u16(&TLVTag::Context(6), state.mireds)?;
+        wb.u8(&TLVTag::Context(7), state.options)?;
+        Nullable::new(state.start_up_mireds).to_tlv(&TLVTag::Context(8), &mut wb)?;
+        wb.end_container()?;
+
+        state.changed = false;
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    /// Return `true` if the state has changed and needs to be persisted.
+    pub fn changed(&self) -> bool {
+        self.state.borrow().changed
+    }
+
+    /// Wait for the state to be changed in a way that requires persisting.
+    pub async fn wait_persist(&self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+
+            self.persist_state_changed.wait().await;
+        }
+    }
+
+    /// Apply the current color to the hardware.
+    fn apply(&self) {
+        let color = self.get();
+
+        self.hooks.set_color(color);
+
+        self.dataver.changed();
+    }
+
+    /// Mark the state as changed (and therefore to be persisted), once the color had settled.
+    fn settled(&self) {
+        self.state.borrow_mut().changed = true;
+        self.persist_state_changed.notify();
+    }
+
+    /// Return `true` if a command should be executed, as per the `Options` attribute and
+    /// the `OptionsMask` and `OptionsOverride` fields of the command.
+    fn should_execute(&self, mask: u8, overrides: u8) -> bool {
+        let Some(on_off) = self.on_off else {
+            return true;
+        };
+
+        if on_off.get() {
+            return true;
+        }
+
+        let options = (self.state.borrow().options & !mask) | (overrides & mask);
+
+        options & OPTIONS_EXECUTE_IF_OFF != 0
+    }
+
+    /// Return the default limits of the color temperature transitions.
+    fn mireds_range(&self) -> (u16, u16) {
+        (self.min_mireds, self.max_mireds)
+    }
+
+    /// Start a new transition, replacing the one in progress (including the color loop), if any.
+    ///
+    /// The closure is given the state - after the switch to the color mode of the transition -
+    /// and is expected to set the ramps of the transition.
+    fn start<F>(
+        &self,
+        color_mode: ColorMode,
+        enhanced_hue_mode: bool,
+        mut transition: Transition,
+        f: F,
+    ) where
+        F: FnOnce(&mut ColorState, &mut Transition),
+    {
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.switch_mode(color_mode, enhanced_hue_mode);
+
+            if !transition.color_loop {
+                state.loop_active = false;
+            }
+
+            f(&mut state, &mut transition);
+
+            transition.start = Instant::now();
+            state.transition = Some(transition);
+        }
+
+        self.step(Instant::now());
+
+        self.transition_changed.notify();
+    }
+
+    /// Advance the transition in progress to the given instant.
+    ///
+    /// Return the instant when the transition should be advanced next, or `None` if the transition had completed.
+    fn step(&self, now: Instant) -> Option<Instant> {
+        let completed = {
+            let mut state = self.state.borrow_mut();
+
+            let transition = state.transition.clone()?;
+            let elapsed = now.saturating_duration_since(transition.start);
+
+            let (elapsed, mut completed) = match transition.duration {
+                Some(duration) if elapsed >= duration => (duration, true),
+                _ => (elapsed, false),
+            };
+
+            // Hue wraps around, so moves of the hue never reach a limit
+            let mut at_limit = transition.hue.is_none();
+            let mut limit = |value: i64, min: i64, max: i64| {
+                let clamped = value.clamp(min, max);
+                at_limit &= clamped != value;
+                clamped
+            };
+
+            if let Some(ramp) = transition.hue {
+                state.enhanced_hue = ramp
+                    .at(elapsed, transition.period)
+                    .rem_euclid(HUE_CIRCLE as _) as _;
+            }
+
+            if let Some(ramp) = transition.saturation {
+                state.saturation =
+                    limit(ramp.at(elapsed, transition.period), 0, MAX_SATURATION as _) as _;
+            }
+
+            if let Some(ramp) = transition.x {
+                state.x = limit(ramp.at(elapsed, transition.period), 0, MAX_XY as _) as _;
+            }
+
+            if let Some(ramp) = transition.y {
+                state.y = limit(ramp.at(elapsed, transition.period), 0, MAX_XY as _) as _;
+            }
+
+            if let Some(ramp) = transition.mireds {
+                let (min, max) = transition.mireds_range;
+                state.mireds = limit(ramp.at(elapsed, transition.period), min as _, max as _) as _;
+            }
+
+            // Moves complete once all of their components reach their limits
+            completed |= transition.duration.is_none() && at_limit;
+
+            state.remaining_time = match transition.duration {
+                Some(duration) if !completed => (duration - elapsed)
+                    .as_millis()
+                    .div_ceil(100)
+                    .min(u16::MAX as _) as _,
+                _ => 0,
+            };
+
+            if completed {
+                state.transition = None;
+            }
+
+            completed
+        };
+
+        self.apply();
+
+        if completed {
+            self.settled();
+
+            None
+        } else {
+            Some(now + Duration::from_millis(TRANSITION_TICK_MS))
+        }
+    }
+
+    /// Stop the transition in progress, if any - except the color loop.
+    fn stop(&self) {
+        let stopped = {
+            let mut state = self.state.borrow_mut();
+
+            let stopped = state
+                .transition
+                .as_ref()
+                .map(|transition| !transition.color_loop)
+                .unwrap_or(false);
+
+            if stopped {
+                state.transition = None;
+                state.remaining_time = 0;
+            }
+
+            stopped
+        };
+
+        if stopped {
+            self.dataver.changed();
+            self.settled();
+        }
+    }
+
+    fn move_to_hue(
+        &self,
+        hue: u16,
+        saturation: Option<u8>,
+        direction: HueDirection,
+        transition_time: u16,
+        enhanced: bool,
+    ) -> Result<(), Error> {
+        if saturation.is_some_and(|saturation| saturation > MAX_SATURATION) {
+            return Err(ErrorCode::ConstraintError.into());
+        }
+
+        self.start(
+            ColorMode::CurrentHueAndCurrentSaturation,
+            enhanced,
+            Transition::timed(tenths(transition_time), self.mireds_range()),
+            |state, transition| {
+                let from = state.enhanced_hue as i32;
+                let up = (hue as i32 - from).rem_euclid(HUE_CIRCLE);
+                let down = if up == 0 { 0 } else { up - HUE_CIRCLE };
+
+                let delta = match direction {
+                    HueDirection::ShortestDistance => {
+                        if up <= HUE_CIRCLE / 2 {
+                            up
+                        } else {
+                            down
+                        }
+                    }
+                    HueDirection::LongestDistance => {
+                        if up > HUE_CIRCLE / 2 {
+                            up
+                        } else {
+                            down
+                        }
+                    }
+                    HueDirection::Up => up,
+                    HueDirection::Down => down,
+                };
+
+                transition.hue = Some(Ramp::new(from, delta));
+                transition.saturation = saturation.map(|saturation| {
+                    Ramp::new(
+                        state.saturation as _,
+                        saturation as i32 - state.saturation as i32,
+                    )
+                });
+            },
+        );
+
+        Ok(())
+    }
+
+    fn move_hue(&self, mode: HueMoveMode, rate: u16, enhanced: bool) -> Result<(), Error> {
+        let delta = match mode {
+            HueMoveMode::Stop => {
+                self.stop();
+                return Ok(());
+            }
+            _ if rate == 0 => return Err(ErrorCode::InvalidCommand.into()),
+            HueMoveMode::Up => rate as i32,
+            HueMoveMode::Down => -(rate as i32),
+        };
+
+        self.start(
+            ColorMode::CurrentHueAndCurrentSaturation,
+            enhanced,
+            Transition::rate(self.mireds_range()),
+            |state, transition| {
+                transition.hue = Some(Ramp::new(state.enhanced_hue as _, delta));
+            },
+        );
+
+        Ok(())
+    }
+
+    fn step_hue(
+        &self,
+        mode: HueStepMode,
+        size: u16,
+        transition_time: u16,
+        enhanced: bool,
+    ) -> Result<(), Error> {
+        if size == 0 {
+            return Err(ErrorCode::InvalidCommand.into());
+        }
+
+        let delta = match mode {
+            HueStepMode::Up => size as i32,
+            HueStepMode::Down => -(size as i32),
+        };
+
+        self.start(
+            ColorMode::CurrentHueAndCurrentSaturation,
+            enhanced,
+            Transition::timed(tenths(transition_time), self.mireds_range()),
+            |state, transition| {
+                transition.hue = Some(Ramp::new(state.enhanced_hue as _, delta));
+            },
+        );
+
+        Ok(())
+    }
+
+    fn move_to_saturation(&self, saturation: u8, transition_time: u16) -> Result<(), Error> {
+        if saturation > MAX_SATURATION {
+            return Err(ErrorCode::ConstraintError.into());
+        }
+
+        let enhanced = self.state.borrow().enhanced_hue_mode;
+
+        self.start(
+            ColorMode::CurrentHueAndCurrentSaturation,
+            enhanced,
+            Transition::timed(tenths(transition_time), self.mireds_range()),
+            |state, transition| {
+                transition.saturation = Some(Ramp::new(
+                    state.saturation as _,
+                    saturation as i32 - state.saturation as i32,
+                ));
+            },
+        );
+
+        Ok(())
+    }
+
+    fn move_saturation(&self, mode: SaturationMoveMode, rate: u8) -> Result<(), Error> {
+        let delta = match mode {
+            SaturationMoveMode::Stop => {
+                self.stop();
+                return Ok(());
+            }
+            _ if rate == 0 => return Err(ErrorCode::InvalidCommand.into()),
+            SaturationMoveMode::Up => rate as i32,
+            SaturationMoveMode::Down => -(rate as i32),
+        };
+
+        let enhanced = self.state.borrow().enhanced_hue_mode;
+
+        self.start(
+            ColorMode::CurrentHueAndCurrentSaturation,
+            enhanced,
+            Transition::rate(self.mireds_range()),
+            |state, transition| {
+                transition.saturation = Some(Ramp::new(state.saturation as _, delta));
+            },
+        );
+
+        Ok(())
+    }
+
+    fn step_saturation(
+        &self,
+        mode: SaturationStepMode,
+        size: u8,
+        transition_time: u16,
+    ) -> Result<(), Error> {
+        if size == 0 {
+            return Err(ErrorCode::InvalidCommand.into());
+        }
+
+        let delta = match mode {
+            SaturationStepMode::Up => size as i32,
+            SaturationStepMode::Down => -(size as i32),
+        };
+
+        let enhanced = self.state.borrow().enhanced_hue_mode;
+
+        self.start(
+            ColorMode::CurrentHueAndCurrentSaturation,
+            enhanced,
+            Transition::timed(tenths(transition_time), self.mireds_range()),
+            |state, transition| {
+                transition.saturation = Some(Ramp::new(state.saturation as _, delta));
+            },
+        );
+
+        Ok(())
+    }
+
+    fn move_to_color(&self, x: u16, y: u16, transition_time: u16) -> Result<(), Error> {
+        if x > MAX_XY || y > MAX_XY {
+            return Err(ErrorCode::ConstraintError.into());
+        }
+
+        self.start(
+            ColorMode::CurrentXAndCurrentY,
+            false,
+            Transition::timed(tenths(transition_time), self.mireds_range()),
+            |state, transition| {
+                transition.x = Some(Ramp::new(state.x as _, x as i32 - state.x as i32));
+                transition.y = Some(Ramp::new(state.y as _, y as i32 - state.y as i32));
+            },
+        );
+
+        Ok(())
+    }
+
+    fn move_color(&self, rate_x: i16, rate_y: i16) {
+        if rate_x == 0 && rate_y == 0 {
+            self.stop();
+            return;
+        }
+
+        self.start(
+            ColorMode::CurrentXAndCurrentY,
+            false,
+            Transition::rate(self.mireds_range()),
+            |state, transition| {
+                transition.x = (rate_x != 0).then(|| Ramp::new(state.x as _, rate_x as _));
+                transition.y = (rate_y != 0).then(|| Ramp::new(state.y as _, rate_y as _));
+            },
+        );
+    }
+
+    fn step_color(&self, step_x: i16, step_y: i16, transition_time: u16) {
+        self.start(
+            ColorMode::CurrentXAndCurrentY,
+            false,
+            Transition::timed(tenths(transition_time), self.mireds_range()),
+            |state, transition| {
+                transition.x = Some(Ramp::new(state.x as _, step_x as _));
+                transition.y = Some(Ramp::new(state.y as _, step_y as _));
+            },
+        );
+    }
+
+    fn move_to_color_temperature(&self, mireds: u16, transition_time: u16) {
+        let mireds = mireds.clamp(self.min_mireds, self.max_mireds);
+
+        self.start(
+            ColorMode::ColorTemperature,
+            false,
+            Transition::timed(tenths(transition_time), self.mireds_range()),
+            |state, transition| {
+                transition.mireds = Some(Ramp::new(
+                    state.mireds as _,
+                    mireds as i32 - state.mireds as i32,
+                ));
+            },
+        );
+    }
+
+    /// Return the limits of a color temperature move or step, as requested by the command.
+    fn command_mireds_range(&self, min: u16, max: u16) -> (u16, u16) {
+        let min = if min == 0 {
+            self.min_mireds
+        } else {
+            min.max(self.min_mireds)
+        };
+
+        let max = if max == 0 {
+            self.max_mireds
+        } else {
+            max.min(self.max_mireds)
+        };
+
+        (min, max.max(min))
+    }
+
+    fn move_color_temperature(
+        &self,
+        mode: HueMoveMode,
+        rate: u16,
+        min: u16,
+        max: u16,
+    ) -> Result<(), Error> {
+        let delta = match mode {
+            HueMoveMode::Stop => {
+                self.stop();
+                return Ok(());
+            }
+            _ if rate == 0 => return Err(ErrorCode::InvalidCommand.into()),
+            HueMoveMode::Up => rate as i32,
+            HueMoveMode::Down => -(rate as i32),
+        };
+
+        self.start(
+            ColorMode::ColorTemperature,
+            false,
+            Transition::rate(self.command_mireds_range(min, max)),
+            |state, transition| {
+                transition.mireds = Some(Ramp::new(state.mireds as _, delta));
+            },
+        );
+
+        Ok(())
+    }
+
+    fn step_color_temperature(
+        &self,
+        mode: HueStepMode,
+        size: u16,
+        transition_time: u16,
+        min: u16,
+        max: u16,
+    ) -> Result<(), Error> {
+        if size == 0 {
+            return Err(ErrorCode::InvalidCommand.into());
+        }
+
+        let delta = match mode {
+            HueStepMode::Up => size as i32,
+            HueStepMode::Down => -(size as i32),
+        };
+
+        self.start(
+            ColorMode::ColorTemperature,
+            false,
+            Transition::timed(tenths(transition_time), self.command_mireds_range(min, max)),
+            |state, transition| {
+                transition.mireds = Some(Ramp::new(state.mireds as _, delta));
+            },
+        );
+
+        Ok(())
+    }
+
+    fn color_loop_set(
+        &self,
+        flags: ColorLoopUpdateFlags,
+        action: ColorLoopAction,
+        direction: ColorLoopDirection,
+        time: u16,
+        start_hue: u16,
+    ) {
+        let (action, active) = {
+            let mut state = self.state.borrow_mut();
+
+            if flags.contains(ColorLoopUpdateFlags::UPDATE_DIRECTION) {
+                state.loop_direction = direction;
+            }
+
+            if flags.contains(ColorLoopUpdateFlags::UPDATE_TIME) {
+                state.loop_time = time;
+            }
+
+            if flags.contains(ColorLoopUpdateFlags::UPDATE_START_HUE) {
+                state.loop_start_hue = start_hue;
+            }
+
+            (
+                flags
+                    .contains(ColorLoopUpdateFlags::UPDATE_ACTION)
+                    .then_some(action),
+                state.loop_active,
+            )
+        };
+
+        match action {
+            Some(ColorLoopAction::Deactivate) if active => {
+                {
+                    let mut state = self.state.borrow_mut();
+
+                    state.loop_active = false;
+                    state.transition = None;
+                    state.enhanced_hue = state.loop_stored_hue;
+                }
+
+                self.apply();
+                self.settled();
+            }
+            Some(ColorLoopAction::Deactivate) => (),
+            Some(ColorLoopAction::ActivateFromColorLoopStartEnhancedHue) => {
+                self.start_color_loop(true)
+            }
+            Some(ColorLoopAction::ActivateFromEnhancedCurrentHue) => self.start_color_loop(false),
+            // Restart the active loop with the updated direction and time
+            None if active => self.start_color_loop(false),
+            None => (),
+        }
+    }
+
+    fn start_color_loop(&self, from_start_hue: bool) {
+        let period = Duration::from_secs(self.state.borrow().loop_time.max(1) as _);
+
+        self.start(
+            ColorMode::CurrentHueAndCurrentSaturation,
+            true,
+            Transition {
+                period,
+                duration: None,
+                color_loop: true,
+                ..Transition::rate(self.mireds_range())
+            },
+            |state, transition| {
+                if !state.loop_active {
+                    state.loop_stored_hue = state.enhanced_hue;
+                }
+
+                let from = if from_start_hue {
+                    state.loop_start_hue
+                } else {
+                    state.enhanced_hue
+                };
+
+                let delta = match state.loop_direction {
+                    ColorLoopDirection::IncrementHue => HUE_CIRCLE,
+                    ColorLoopDirection::DecrementHue => -HUE_CIRCLE,
+                };
+
+                transition.hue = Some(Ramp::new(from as _, delta));
+            },
+        );
+
+        self.state.borrow_mut().loop_active = true;
+    }
+
+    fn set_attr<F>(&self, ctx: &WriteContext, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ColorState),
+    {
+        f(&mut self.state.borrow_mut());
+
+        self.settled();
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+}
+
+impl ColorTempCoupling for ColorControlHandler<'_> {
+    fn level_changed(&self, level: u8) {
+        {
+            let mut state = self.state.borrow_mut();
+
+            if state.color_mode != ColorMode::ColorTemperature
+                || state
+                    .transition
+                    .as_ref()
+                    .is_some_and(|transition| transition.mireds.is_some())
+            {
+                return;
+            }
+
+            // The maximum level maps to the coolest color temperature (the minimum mireds)
+            let range = (self.max_mireds - self.min_mireds) as u32;
+            let level = level.clamp(MIN_LEVEL, MAX_LEVEL);
+            let steps = (MAX_LEVEL - level) as u32;
+
+            state.mireds =
+                self.min_mireds + (range * steps / (MAX_LEVEL - MIN_LEVEL) as u32) as u16;
+        }
+
+        self.apply();
+        self.settled();
+    }
+}
+
+impl SceneExtension for ColorControlHandler<'_> {
+    fn cluster_id(&self) -> u32 {
+        Self::CLUSTER.id
+    }
+
+    fn store_scene(&self, values: &mut SceneAttributeValues) -> Result<(), Error> {
+        let state = self.state.borrow();
+
+        let enhanced_color_mode = if state.enhanced_hue_mode {
+            ENHANCED_COLOR_MODE_ENHANCED_HUE
+        } else {
+            state.color_mode as _
+        };
+
+        for (attr, value) in [
+            (AttributeId::CurrentX, state.x as u32),
+            (AttributeId::CurrentY, state.y as _),
+            (AttributeId::EnhancedCurrentHue, state.enhanced_hue as _),
+            (AttributeId::CurrentSaturation, state.saturation as _),
+            (AttributeId::ColorLoopActive, state.loop_active as _),
+            (AttributeId::ColorLoopDirection, state.loop_direction as _),
+            (AttributeId::ColorLoopTime, state.loop_time as _),
+            (AttributeId::ColorTemperatureMireds, state.mireds as _),
+            (AttributeId::EnhancedColorMode, enhanced_color_mode as _),
+        ] {
+            values
+                .push(SceneAttributeValue::new(attr as _, value))
+                .map_err(|_| ErrorCode::NoSpace)?;
+        }
+
+        Ok(())
+    }
+
+    fn recall_scene(
+        &self,
+        values: &[SceneAttributeValue],
+        transition_time: Duration,
+    ) -> Result<(), Error> {
+        let (mode, x, y, hue, saturation, mireds, loop_time) = {
+            let state = self.state.borrow();
+
+            let value = |attr: AttributeId, current: u32| {
+                SceneAttributeValue::find(values, attr as _).unwrap_or(current)
+            };
+
+            let mode = if state.enhanced_hue_mode {
+                ENHANCED_COLOR_MODE_ENHANCED_HUE
+            } else {
+                state.color_mode as _
+            };
+
+            (
+                value(AttributeId::EnhancedColorMode, mode as _),
+                value(AttributeId::CurrentX, state.x as _).min(MAX_XY as _) as u16,
+                value(AttributeId::CurrentY, state.y as _).min(MAX_XY as _) as u16,
+                value(AttributeId::EnhancedCurrentHue, state.enhanced_hue as _) as u16,
+                value(AttributeId::CurrentSaturation, state.saturation as _)
+                    .min(MAX_SATURATION as _) as u8,
+                value(AttributeId::ColorTemperatureMireds, state.mireds as _).min(u16::MAX as _)
+                    as u16,
+                value(AttributeId::ColorLoopTime, state.loop_time as _).min(u16::MAX as _) as u16,
+            )
+        };
+
+        let tenths = (transition_time.as_millis() / 100).min(u16::MAX as _) as u16;
+
+        match mode {
+            0 => self.move_to_hue(
+                hue,
+                Some(saturation),
+                HueDirection::ShortestDistance,
+                tenths,
+                false,
+            )?,
+            1 => self.move_to_color(x, y, tenths)?,
+            2 => self.move_to_color_temperature(mireds, tenths),
+            _ => self.move_to_hue(
+                hue,
+                Some(saturation),
+                HueDirection::ShortestDistance,
+                tenths,
+                true,
+            )?,
+        }
+
+        if SceneAttributeValue::find(values, AttributeId::ColorLoopActive as _) == Some(1) {
+            let direction =
+                match SceneAttributeValue::find(values, AttributeId::ColorLoopDirection as _) {
+                    Some(0) => ColorLoopDirection::DecrementHue,
+                    _ => ColorLoopDirection::IncrementHue,
+                };
+
+            self.color_loop_set(
+                ColorLoopUpdateFlags::UPDATE_ACTION
+                    | ColorLoopUpdateFlags::UPDATE_DIRECTION
+                    | ColorLoopUpdateFlags::UPDATE_TIME,
+                ColorLoopAction::ActivateFromEnhancedCurrentHue,
+                direction,
+                loop_time,
+                0,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl ClusterHandler for ColorControlHandler<'_> {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(6)
+        .with_features(
+            Feature::HUE_AND_SATURATION.bits()
+                | Feature::ENHANCED_HUE.bits()
+                | Feature::COLOR_LOOP.bits()
+                | Feature::XY.bits()
+                | Feature::COLOR_TEMPERATURE.bits(),
+        )
+        .with_attrs(with!(
+            required;
+            AttributeId::CurrentHue
+                | AttributeId::CurrentSaturation
+                | AttributeId::RemainingTime
+                | AttributeId::CurrentX
+                | AttributeId::CurrentY
+                | AttributeId::ColorTemperatureMireds
+                | AttributeId::EnhancedCurrentHue
+                | AttributeId::ColorLoopActive
+                | AttributeId::ColorLoopDirection
+                | AttributeId::ColorLoopTime
+                | AttributeId::ColorLoopStartEnhancedHue
+                | AttributeId::ColorLoopStoredEnhancedHue
+                | AttributeId::ColorTempPhysicalMinMireds
+                | AttributeId::ColorTempPhysicalMaxMireds
+                | AttributeId::CoupleColorTempToLevelMinMireds
+                | AttributeId::StartUpColorTemperatureMireds
+        ))
+        .with_cmds(with!(all));
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn current_hue(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok((self.state.borrow().enhanced_hue >> 8) as _)
+    }
+
+    fn current_saturation(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.state.borrow().saturation)
+    }
+
+    fn remaining_time(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().remaining_time)
+    }
+
+    fn current_x(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().x)
+    }
+
+    fn current_y(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().y)
+    }
+
+    fn color_temperature_mireds(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().mireds)
+    }
+
+    fn color_mode(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.state.borrow().color_mode as _)
+    }
+
+    fn options(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.state.borrow().options)
+    }
+
+    fn number_of_primaries(&self, _ctx: &ReadContext) -> Result<Nullable<u8>, Error> {
+        Ok(Nullable::none())
+    }
+
+    fn enhanced_current_hue(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().enhanced_hue)
+    }
+
+    fn enhanced_color_mode(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        let state = self.state.borrow();
+
+        Ok(if state.enhanced_hue_mode {
+            ENHANCED_COLOR_MODE_ENHANCED_HUE
+        } else {
+            state.color_mode as _
+        })
+    }
+
+    fn color_loop_active(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.state.borrow().loop_active as _)
+    }
+
+    fn color_loop_direction(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.state.borrow().loop_direction as _)
+    }
+
+    fn color_loop_time(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().loop_time)
+    }
+
+    fn color_loop_start_enhanced_hue(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().loop_start_hue)
+    }
+
+    fn color_loop_stored_enhanced_hue(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().loop_stored_hue)
+    }
+
+    fn color_capabilities(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok((ColorCapabilities::HUE_SATURATION_SUPPORTED
+            | ColorCapabilities::ENHANCED_HUE_SUPPORTED
+            | ColorCapabilities::COLOR_LOOP_SUPPORTED
+            | ColorCapabilities::XY_ATTRIBUTES_SUPPORTED
+            | ColorCapabilities::COLOR_TEMPERATURE_SUPPORTED)
+            .bits())
+    }
+
+    fn color_temp_physical_min_mireds(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.min_mireds)
+    }
+
+    fn color_temp_physical_max_mireds(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.max_mireds)
+    }
+
+    fn couple_color_temp_to_level_min_mireds(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.min_mireds)
+    }
+
+    fn start_up_color_temperature_mireds(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::new(self.state.borrow().start_up_mireds))
+    }
+
+    fn set_options(&self, ctx: &WriteContext, value: u8) -> Result<(), Error> {
+        self.set_attr(ctx, |state| state.options = value)
+    }
+
+    fn set_start_up_color_temperature_mireds(
+        &self,
+        ctx: &WriteContext,
+        value: Nullable<u16>,
+    ) -> Result<(), Error> {
+        let value = value.into_option();
+
+        if value.is_some_and(|mireds| !(self.min_mireds..=self.max_mireds).contains(&mireds)) {
+            return Err(ErrorCode::ConstraintError.into());
+        }
+
+        self.set_attr(ctx, |state| state.start_up_mireds = value)
+    }
+
+    fn handle_move_to_hue(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveToHueRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            let hue = request.hue()?;
+            if hue > MAX_SATURATION {
+                return Err(ErrorCode::ConstraintError.into());
+            }
+
+            self.move_to_hue(
+                (hue as u16) << 8,
+                None,
+                request.direction()?,
+                request.transition_time()?,
+                false,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move_hue(&self, ctx: &InvokeContext, request: MoveHueRequest) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_hue(request.move_mode()?, (request.rate()? as u16) << 8, false)?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_step_hue(&self, ctx: &InvokeContext, request: StepHueRequest) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.step_hue(
+                request.step_mode()?,
+                (request.step_size()? as u16) << 8,
+                request.transition_time()? as _,
+                false,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move_to_saturation(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveToSaturationRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_to_saturation(request.saturation()?, request.transition_time()?)?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move_saturation(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveSaturationRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_saturation(request.move_mode()?, request.rate()?)?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_step_saturation(
+        &self,
+        ctx: &InvokeContext,
+        request: StepSaturationRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.step_saturation(
+                request.step_mode()?,
+                request.step_size()?,
+                request.transition_time()? as _,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move_to_hue_and_saturation(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveToHueAndSaturationRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            let hue = request.hue()?;
+            if hue > MAX_SATURATION {
+                return Err(ErrorCode::ConstraintError.into());
+            }
+
+            self.move_to_hue(
+                (hue as u16) << 8,
+                Some(request.saturation()?),
+                HueDirection::ShortestDistance,
+                request.transition_time()?,
+                false,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move_to_color(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveToColorRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_to_color(
+                request.color_x()?,
+                request.color_y()?,
+                request.transition_time()?,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move_color(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveColorRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_color(request.rate_x()?, request.rate_y()?);
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_step_color(
+        &self,
+        ctx: &InvokeContext,
+        request: StepColorRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.step_color(
+                request.step_x()?,
+                request.step_y()?,
+                request.transition_time()?,
+            );
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move_to_color_temperature(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveToColorTemperatureRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_to_color_temperature(
+                request.color_temperature_mireds()?,
+                request.transition_time()?,
+            );
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_enhanced_move_to_hue(
+        &self,
+        ctx: &InvokeContext,
+        request: EnhancedMoveToHueRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_to_hue(
+                request.enhanced_hue()?,
+                None,
+                request.direction()?,
+                request.transition_time()?,
+                true,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_enhanced_move_hue(
+        &self,
+        ctx: &InvokeContext,
+        request: EnhancedMoveHueRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_hue(request.move_mode()?, request.rate()?, true)?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_enhanced_step_hue(
+        &self,
+        ctx: &InvokeContext,
+        request: EnhancedStepHueRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.step_hue(
+                request.step_mode()?,
+                request.step_size()?,
+                request.transition_time()?,
+                true,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_enhanced_move_to_hue_and_saturation(
+        &self,
+        ctx: &InvokeContext,
+        request: EnhancedMoveToHueAndSaturationRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_to_hue(
+                request.enhanced_hue()?,
+                Some(request.saturation()?),
+                HueDirection::ShortestDistance,
+                request.transition_time()?,
+                true,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_color_loop_set(
+        &self,
+        ctx: &InvokeContext,
+        request: ColorLoopSetRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.color_loop_set(
+                request.update_flags()?,
+                request.action()?,
+                request.direction()?,
+                request.time()?,
+                request.start_hue()?,
+            );
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_stop_move_step(
+        &self,
+        ctx: &InvokeContext,
+        request: StopMoveStepRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.stop();
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move_color_temperature(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveColorTemperatureRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_color_temperature(
+                request.move_mode()?,
+                request.rate()?,
+                request.color_temperature_minimum_mireds()?,
+                request.color_temperature_maximum_mireds()?,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_step_color_temperature(
+        &self,
+        ctx: &InvokeContext,
+        request: StepColorTemperatureRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.step_color_temperature(
+                request.step_mode()?,
+                request.step_size()?,
+                request.transition_time()?,
+                request.color_temperature_minimum_mireds()?,
+                request.color_temperature_maximum_mireds()?,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+}
+
+/// Convert a time in tenths of a second to a `Duration`.
+fn tenths(time: u16) -> Duration {
+    Duration::from_millis(time as u64 * 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use embassy_time::Duration;
+
+    use crate::data_model::objects::Dataver;
+    use crate::data_model::on_off::OnOffHandler;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Hooks {
+        color: Cell<Option<Color>>,
+    }
+
+    impl ColorControlHooks for Hooks {
+        fn set_color(&self, color: Color) {
+            self.color.set(Some(color));
+        }
+    }
+
+    /// Advance the transition in progress to the given time since its start, without waiting.
+    fn step(handler: &ColorControlHandler, elapsed: Duration) -> Option<Instant> {
+        let start = unwrap!(handler.state.borrow().transition.as_ref()).start;
+        handler.step(start + elapsed)
+    }
+
+    #[test]
+    fn test_conversions() {
+        fn assert_near(xy: (u16, u16), expected: (f32, f32)) {
+            let expected = (expected.0 * 65536.0, expected.1 * 65536.0);
+
+            assert!((xy.0 as f32 - expected.0).abs() < 100.0);
+            assert!((xy.1 as f32 - expected.1).abs() < 100.0);
+        }
+
+        // Red, green and blue map to the sRGB primaries
+        assert_near(hs_to_xy(0, MAX_SATURATION), (0.64, 0.33));
+        assert_near(hs_to_xy(0x5555, MAX_SATURATION), (0.30, 0.60));
+        assert_near(hs_to_xy(0xaaaa, MAX_SATURATION), (0.15, 0.06));
+        // White maps to D65
+        assert_near(hs_to_xy(0, 0), (0.3127, 0.3290));
+
+        for (hue, saturation) in [(0, 254), (0x2000, 200), (0x8000, 100), (0xc000, 254)] {
+            let (x, y) = hs_to_xy(hue, saturation);
+            let (hue2, saturation2) = xy_to_hs(x, y);
+
+            assert!((hue as i32 - hue2 as i32).abs() < 0x100);
+            assert!((saturation as i32 - saturation2 as i32).abs() <= 2);
+        }
+
+        // McCamy's approximation is accurate to ~1% for warm white
+        for mireds in [153, 250, 370, 500] {
+            let (x, y) = mireds_to_xy(mireds);
+            assert!((xy_to_mireds(x, y) as i32 - mireds as i32).abs() <= mireds as i32 / 100 + 1);
+        }
+    }
+
+    #[test]
+    fn test_move_to_hue() {
+        let hooks = Hooks::default();
+
+        let mut handler = ColorControlHandler::new(Dataver::new(0));
+        handler.set_hooks(&hooks);
+
+        handler
+            .move_to_hue(0xf000, Some(254), HueDirection::ShortestDistance, 0, true)
+            .unwrap();
+        assert_eq!(
+            handler.get(),
+            Color::HueSaturation {
+                hue: 0xf000,
+                saturation: 254
+            }
+        );
+        assert_eq!(hooks.color.get(), Some(handler.get()));
+        assert!(handler.state.borrow().enhanced_hue_mode);
+        assert!(handler.changed());
+
+        // The shortest distance wraps through 0
+        handler
+            .move_to_hue(0x1000, None, HueDirection::ShortestDistance, 10, true)
+            .unwrap();
+
+        let next = step(&handler, Duration::from_millis(500));
+        assert_eq!(handler.state.borrow().enhanced_hue, 0);
+        assert_eq!(handler.state.borrow().remaining_time, 5);
+        assert!(next.is_some());
+
+        assert!(step(&handler, Duration::from_secs(1)).is_none());
+        assert_eq!(handler.state.borrow().enhanced_hue, 0x1000);
+
+        // The longest distance goes the other way
+        handler
+            .move_to_hue(0xf000, None, HueDirection::LongestDistance, 10, true)
+            .unwrap();
+
+        step(&handler, Duration::from_millis(500));
+        assert_eq!(handler.state.borrow().enhanced_hue, 0x8000);
+
+        assert!(handler
+            .move_to_hue(0, Some(255), HueDirection::Up, 0, false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_move_and_stop() {
+        let handler = ColorControlHandler::new(Dataver::new(0));
+
+        handler.move_to_saturation(100, 0).unwrap();
+        handler
+            .move_saturation(SaturationMoveMode::Up, 100)
+            .unwrap();
+
+        step(&handler, Duration::from_millis(500));
+        assert_eq!(handler.state.borrow().saturation, 150);
+
+        // The move completes once the saturation reaches its limit
+        assert!(step(&handler, Duration::from_secs(2)).is_none());
+        assert_eq!(handler.state.borrow().saturation, MAX_SATURATION);
+
+        assert!(handler
+            .move_saturation(SaturationMoveMode::Down, 0)
+            .is_err());
+
+        handler.move_color(1000, -1000);
+        step(&handler, Duration::from_millis(500));
+
+        handler.stop();
+        assert!(handler.state.borrow().transition.is_none());
+        assert_eq!(
+            handler.state.borrow().color_mode,
+            ColorMode::CurrentXAndCurrentY
+        );
+    }
+
+    #[test]
+    fn test_color_temperature() {
+        let handler = ColorControlHandler::new_with_mireds(Dataver::new(0), 200, 400);
+
+        // Clamped to the physical range
+        handler.move_to_color_temperature(100, 0);
+        assert_eq!(handler.get(), Color::Temperature { mireds: 200 });
+
+        handler
+            .move_color_temperature(HueMoveMode::Up, 100, 0, 300)
+            .unwrap();
+        assert!(step(&handler, Duration::from_secs(2)).is_none());
+        assert_eq!(handler.get(), Color::Temperature { mireds: 300 });
+
+        handler
+            .step_color_temperature(HueStepMode::Down, 50, 0, 0, 0)
+            .unwrap();
+        assert_eq!(handler.get(), Color::Temperature { mireds: 250 });
+
+        // Coupling to the level
+        handler.level_changed(MAX_LEVEL);
+        assert_eq!(handler.get(), Color::Temperature { mireds: 200 });
+
+        handler.level_changed(MIN_LEVEL);
+        assert_eq!(handler.get(), Color::Temperature { mireds: 400 });
+
+        // Switching the color mode converts the current color
+        handler.move_to_saturation(0, 0).unwrap();
+        assert_eq!(
+            handler.state.borrow().color_mode,
+            ColorMode::CurrentHueAndCurrentSaturation
+        );
+
+        handler.level_changed(MAX_LEVEL);
+        assert_eq!(handler.state.borrow().mireds, 400);
+    }
+
+    #[test]
+    fn test_color_loop() {
+        let handler = ColorControlHandler::new(Dataver::new(0));
+
+        handler
+            .move_to_hue(0x1234, Some(254), HueDirection::Up, 0, true)
+            .unwrap();
+
+        handler.color_loop_set(
+            ColorLoopUpdateFlags::UPDATE_ACTION
+                | ColorLoopUpdateFlags::UPDATE_DIRECTION
+                | ColorLoopUpdateFlags::UPDATE_TIME
+                | ColorLoopUpdateFlags::UPDATE_START_HUE,
+            ColorLoopAction::ActivateFromColorLoopStartEnhancedHue,
+            ColorLoopDirection::IncrementHue,
+            4,
+            0x4000,
+        );
+
+        assert!(handler.state.borrow().loop_active);
+        assert_eq!(handler.state.borrow().loop_stored_hue, 0x1234);
+
+        // A quarter of the loop
+        assert!(step(&handler, Duration::from_secs(1)).is_some());
+        assert_eq!(handler.state.borrow().enhanced_hue, 0x8000);
+
+        // StopMoveStep does not stop the loop
+        handler.stop();
+        assert!(handler.state.borrow().loop_active);
+
+        handler.color_loop_set(
+            ColorLoopUpdateFlags::UPDATE_ACTION,
+            ColorLoopAction::Deactivate,
+            ColorLoopDirection::IncrementHue,
+            0,
+            0,
+        );
+
+        assert!(!handler.state.borrow().loop_active);
+        assert!(handler.state.borrow().transition.is_none());
+        assert_eq!(handler.state.borrow().enhanced_hue, 0x1234);
+    }
+
+    #[test]
+    fn test_execute_if_off() {
+        let on_off = OnOffHandler::new(Dataver::new(0));
+
+        let mut handler = ColorControlHandler::new(Dataver::new(0));
+        handler.set_on_off(&on_off);
+
+        on_off.set(false);
+        assert!(!handler.should_execute(0, 0));
+        assert!(handler.should_execute(OPTIONS_EXECUTE_IF_OFF, OPTIONS_EXECUTE_IF_OFF));
+
+        handler.state.borrow_mut().options = OPTIONS_EXECUTE_IF_OFF;
+        assert!(handler.should_execute(0, 0));
+        assert!(!handler.should_execute(OPTIONS_EXECUTE_IF_OFF, 0));
+
+        on_off.set(true);
+        assert!(handler.should_execute(OPTIONS_EXECUTE_IF_OFF, 0));
+    }
+
+    #[test]
+    fn test_persist() {
+        let mut buf = [0; 64];
+
+        let handler = ColorControlHandler::new(Dataver::new(0));
+        handler.move_to_color(0x4000, 0x5000, 0).unwrap();
+        handler.state.borrow_mut().options = OPTIONS_EXECUTE_IF_OFF;
+
+        let data = unwrap!(handler.store(&mut buf).unwrap());
+
+        let hooks = Hooks::default();
+
+        let mut handler2 = ColorControlHandler::new(Dataver::new(0));
+        handler2.set_hooks(&hooks);
+        handler2.load(data).unwrap();
+
+        assert_eq!(
+            handler2.get(),
+            Color::Xy {
+                x: 0x4000,
+                y: 0x5000
+            }
+        );
+        assert_eq!(hooks.color.get(), Some(handler2.get()));
+        assert_eq!(handler2.state.borrow().options, OPTIONS_EXECUTE_IF_OFF);
+        assert!(!handler2.changed());
+
+        // StartUpColorTemperatureMireds overrides the persisted color
+        handler.state.borrow_mut().start_up_mireds = Some(300);
+        handler.settled();
+
+        let data = unwrap!(handler.store(&mut buf).unwrap());
+
+        handler2.load(data).unwrap();
+        assert_eq!(handler2.get(), Color::Temperature { mireds: 300 });
+        assert!(handler2.changed());
+    }
+}
@@ -0,0 +1,334 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Concentration Measurement clusters
+//! (Carbon Monoxide, Carbon Dioxide, Nitrogen Dioxide, Ozone, PM2.5, Formaldehyde, PM1, PM10,
+//! Total Volatile Organic Compounds and Radon) and their common handler.
+//!
+//! The handler implements the NumericMeasurement, LevelIndication, MediumLevel and CriticalLevel
+//! features of the clusters.
+
+use crate::error::Error;
+use crate::tlv::Nullable;
+use crate::utils::cell::RefCell;
+use crate::with;
+
+use super::measurement::Measurement;
+use super::objects::{Cluster, Dataver, ReadContext};
+
+pub use crate::data_model::clusters::{
+    carbon_dioxide_concentration_measurement, carbon_monoxide_concentration_measurement,
+    formaldehyde_concentration_measurement, nitrogen_dioxide_concentration_measurement,
+    ozone_concentration_measurement, pm_10_concentration_measurement,
+    pm_1_concentration_measurement, pm_25_concentration_measurement,
+    radon_concentration_measurement, total_volatile_organic_compounds_concentration_measurement,
+};
+
+/// A trait for the marker types of the Concentration Measurement clusters.
+///
+/// The clusters share the same structure, but each of them has its own (identical) enumerations.
+pub trait ConcentrationCluster {
+    /// The unit of the measured values
+    type Unit: Copy;
+    /// The medium in which the concentration is measured
+    type Medium: Copy;
+    /// The level of the concentration
+    type Level: Copy;
+
+    /// The levels of the concentration, in the order Unknown, Low, Medium, High and Critical.
+    const LEVELS: [Self::Level; 5];
+}
+
+/// A handler for the Concentration Measurement Matter clusters.
+///
+/// The concentration is pushed by the sensor driver with `ConcentrationMeasurementHandler::push_measurement`.
+/// The level of the concentration is derived from the reported value, using the thresholds set with
+/// `ConcentrationMeasurementHandler::with_level_thresholds`.
+///
+/// The handler is adapted to the generic `rs-matter` `Handler` trait with the `HandlerAdaptor` of the
+/// cluster module corresponding to the marker type, i.e.
+/// `pm_25_concentration_measurement::HandlerAdaptor(ConcentrationMeasurementHandler::<Pm25>::new(...))`.
+pub struct ConcentrationMeasurementHandler<C>
+where
+    C: ConcentrationCluster,
+{
+    dataver: Dataver,
+    unit: C::Unit,
+    medium: C::Medium,
+    uncertainty: f32,
+    level_thresholds: Option<[f32; 3]>,
+    measurement: RefCell<Measurement<f32>>,
+}
+
+impl<C> ConcentrationMeasurementHandler<C>
+where
+    C: ConcentrationCluster,
+{
+    /// Creates a new instance of `ConcentrationMeasurementHandler` with the given `Dataver`, measurement,
+    /// unit of the measured values and measured medium.
+    pub const fn new(
+        dataver: Dataver,
+        measurement: Measurement<f32>,
+        unit: C::Unit,
+        medium: C::Medium,
+    ) -> Self {
+        Self {
+            dataver,
+            unit,
+            medium,
+            uncertainty: 0.0,
+            level_thresholds: None,
+            measurement: RefCell::new(measurement),
+        }
+    }
+
+    /// Return the handler with the given uncertainty of the sensor, in the unit of the measured values.
+    pub const fn with_uncertainty(mut self, uncertainty: f32) -> Self {
+        self.uncertainty = uncertainty;
+
+        self
+    }
+
+    /// Return the handler with the given thresholds of the Medium, High and Critical levels of the concentration,
+    /// in the unit of the measured values.
+    ///
+    /// Without thresholds, the level is always reported as Unknown.
+    pub const fn with_level_thresholds(mut self, medium: f32, high: f32, critical: f32) -> Self {
+        self.level_thresholds = Some([medium, high, critical]);
+
+        self
+    }
+
+    /// Return the reported concentration, or `None` if it is unknown.
+    pub fn measured_value(&self) -> Option<f32> {
+        self.measurement.borrow().value()
+    }
+
+    /// Return the level of the reported concentration.
+    pub fn level(&self) -> C::Level {
+        let index = match (self.level_thresholds, self.measured_value()) {
+            (Some(thresholds), Some(value)) => {
+                1 + thresholds
+                    .iter()
+                    .filter(|threshold| value >= **threshold)
+                    .count()
+            }
+            _ => 0,
+        };
+
+        C::LEVELS[index]
+    }
+
+    /// Push a new concentration (`None` if it cannot be measured).
+    ///
+    /// # Arguments
+    /// - `value`: The measured concentration
+    /// - `notify`: A callback which is called when the reported concentration changes, and which is expected
+    ///   to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub fn push_measurement<F>(&self, value: Option<f32>, notify: F)
+    where
+        F: FnOnce(),
+    {
+        if self.measurement.borrow_mut().push(value) {
+            self.dataver.changed();
+            notify();
+        }
+    }
+}
+
+macro_rules! concentration_cluster {
+    ($(#[$meta:meta])* $marker:ident, $module:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub struct $marker;
+
+        impl ConcentrationCluster for $marker {
+            type Unit = $module::MeasurementUnitEnum;
+            type Medium = $module::MeasurementMediumEnum;
+            type Level = $module::LevelValueEnum;
+
+            const LEVELS: [Self::Level; 5] = [
+                $module::LevelValueEnum::Unknown,
+                $module::LevelValueEnum::Low,
+                $module::LevelValueEnum::Medium,
+                $module::LevelValueEnum::High,
+                $module::LevelValueEnum::Critical,
+            ];
+        }
+
+        impl $module::ClusterHandler for ConcentrationMeasurementHandler<$marker> {
+            const CLUSTER: Cluster<'static> = $module::FULL_CLUSTER
+                .with_revision(3)
+                .with_features(
+                    $module::Feature::NUMERIC_MEASUREMENT.bits()
+                        | $module::Feature::LEVEL_INDICATION.bits()
+                        | $module::Feature::MEDIUM_LEVEL.bits()
+                        | $module::Feature::CRITICAL_LEVEL.bits(),
+                )
+                .with_attrs(with!(
+                    required;
+                    $module::AttributeId::MeasuredValue
+                        | $module::AttributeId::MinMeasuredValue
+                        | $module::AttributeId::MaxMeasuredValue
+                        | $module::AttributeId::Uncertainty
+                        | $module::AttributeId::MeasurementUnit
+                        | $module::AttributeId::MeasurementMedium
+                        | $module::AttributeId::LevelValue
+                ))
+                .with_cmds(with!());
+
+            fn dataver(&self) -> u32 {
+                self.dataver.get()
+            }
+
+            fn dataver_changed(&self) {
+                self.dataver.changed();
+            }
+
+            fn measured_value(&self, _ctx: &ReadContext) -> Result<Nullable<f32>, Error> {
+                Ok(Nullable::new(self.measurement.borrow().value()))
+            }
+
+            fn min_measured_value(&self, _ctx: &ReadContext) -> Result<Nullable<f32>, Error> {
+                Ok(Nullable::new(self.measurement.borrow().min()))
+            }
+
+            fn max_measured_value(&self, _ctx: &ReadContext) -> Result<Nullable<f32>, Error> {
+                Ok(Nullable::new(self.measurement.borrow().max()))
+            }
+
+            fn uncertainty(&self, _ctx: &ReadContext) -> Result<f32, Error> {
+                Ok(self.uncertainty)
+            }
+
+            fn measurement_unit(
+                &self,
+                _ctx: &ReadContext,
+            ) -> Result<$module::MeasurementUnitEnum, Error> {
+                Ok(self.unit)
+            }
+
+            fn measurement_medium(
+                &self,
+                _ctx: &ReadContext,
+            ) -> Result<$module::MeasurementMediumEnum, Error> {
+                Ok(self.medium)
+            }
+
+            fn level_value(&self, _ctx: &ReadContext) -> Result<$module::LevelValueEnum, Error> {
+                Ok(self.level())
+            }
+        }
+    };
+}
+
+concentration_cluster!(
+    /// The marker type of the Carbon Monoxide Concentration Measurement cluster.
+    CarbonMonoxide,
+    carbon_monoxide_concentration_measurement
+);
+concentration_cluster!(
+    /// The marker type of the Carbon Dioxide Concentration Measurement cluster.
+    CarbonDioxide,
+    carbon_dioxide_concentration_measurement
+);
+concentration_cluster!(
+    /// The marker type of the Nitrogen Dioxide Concentration Measurement cluster.
+    NitrogenDioxide,
+    nitrogen_dioxide_concentration_measurement
+);
+concentration_cluster!(
+    /// The marker type of the Ozone Concentration Measurement cluster.
+    Ozone,
+    ozone_concentration_measurement
+);
+concentration_cluster!(
+    /// The marker type of the PM2.5 Concentration Measurement cluster.
+    Pm25,
+    pm_25_concentration_measurement
+);
+concentration_cluster!(
+    /// The marker type of the Formaldehyde Concentration Measurement cluster.
+    Formaldehyde,
+    formaldehyde_concentration_measurement
+);
+concentration_cluster!(
+    /// The marker type of the PM1 Concentration Measurement cluster.
+    Pm1,
+    pm_1_concentration_measurement
+);
+concentration_cluster!(
+    /// The marker type of the PM10 Concentration Measurement cluster.
+    Pm10,
+    pm_10_concentration_measurement
+);
+concentration_cluster!(
+    /// The marker type of the Total Volatile Organic Compounds Concentration Measurement cluster.
+    TotalVolatileOrganicCompounds,
+    total_volatile_organic_compounds_concentration_measurement
+);
+concentration_cluster!(
+    /// The marker type of the Radon Concentration Measurement cluster.
+    Radon,
+    radon_concentration_measurement
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::measurement::check_pushes;
+    use crate::data_model::objects::Dataver;
+
+    use super::carbon_dioxide_concentration_measurement::{
+        LevelValueEnum, MeasurementMediumEnum, MeasurementUnitEnum,
+    };
+    use super::*;
+
+    #[test]
+    fn test_level() {
+        let handler = ConcentrationMeasurementHandler::<CarbonDioxide>::new(
+            Dataver::new(0),
+            Measurement::new(Some(0.0), Some(5000.0)).with_hysteresis(10.0),
+            MeasurementUnitEnum::PPM,
+            MeasurementMediumEnum::Air,
+        )
+        .with_level_thresholds(1000.0, 2000.0, 4000.0);
+
+        let push = |value, notify: &dyn Fn()| handler.push_measurement(value, notify);
+        let dataver = || handler.dataver.get();
+
+        assert_eq!(handler.level(), LevelValueEnum::Unknown);
+
+        // The second value is within the hysteresis
+        check_pushes(push, dataver, &[(Some(400.0), true), (Some(405.0), false)]);
+        assert_eq!(handler.measured_value(), Some(400.0));
+        assert_eq!(handler.level(), LevelValueEnum::Low);
+
+        for (value, level) in [
+            (1500.0, LevelValueEnum::Medium),
+            (2000.0, LevelValueEnum::High),
+            (9000.0, LevelValueEnum::Critical),
+        ] {
+            check_pushes(push, dataver, &[(Some(value), true)]);
+            assert_eq!(handler.level(), level);
+        }
+        assert_eq!(handler.measured_value(), Some(5000.0));
+
+        check_pushes(push, dataver, &[(None, true)]);
+        assert_eq!(handler.level(), LevelValueEnum::Unknown);
+    }
+}
@@ -18,13 +18,14 @@
 use core::cell::{Cell, RefCell};
 use core::iter::Peekable;
 use core::num::NonZeroU8;
+use core::ops::DerefMut;
 use core::pin::pin;
 use core::time::Duration;
 
 use embassy_futures::select::select3;
 use embassy_time::{Instant, Timer};
 
-use crate::interaction_model::messages::ib::AttrStatus;
+use crate::interaction_model::messages::ib::{AttrResp, AttrStatus, CmdStatus};
 use crate::utils::storage::pooled::BufferAccess;
 use crate::{error::*, Matter};
 
@@ -36,17 +37,19 @@ use crate::interaction_model::messages::msg::{
     TimedReq, WriteReqRef, WriteRespTag,
 };
 use crate::respond::ExchangeHandler;
-use crate::tlv::{get_root_node_struct, FromTLV, TLVElement, TLVTag, TLVWrite, TLVWriter};
+use crate::tlv::{get_root_node_struct, FromTLV, TLVElement, TLVTag, TLVWrite, TLVWriter, ToTLV};
 use crate::transport::exchange::{Exchange, MAX_EXCHANGE_RX_BUF_SIZE, MAX_EXCHANGE_TX_BUF_SIZE};
 use crate::utils::storage::WriteBuf;
 
+use super::basic_info::DEFAULT_MAX_PATHS_PER_INVOKE;
 use super::objects::*;
 use super::subscriptions::Subscriptions;
 
-/// The Maximum number of expanded writer request per transaction
+/// The Maximum number of expanded writer request per batch
 ///
 /// The write requests are first wildcard-expanded, and these many number of
-/// write requests per-transaction will be supported.
+/// write requests are access-checked together, before any of them is applied.
+/// Write requests beyond that number are processed in subsequent batches.
 const MAX_WRITE_ATTRS_IN_ONE_TRANS: usize = 7;
 
 pub type IMBuffer = heapless::Vec<u8, MAX_EXCHANGE_RX_BUF_SIZE>;
@@ -67,6 +70,7 @@ where
     handler: T,
     subscriptions: &'a Subscriptions<N>,
     subscriptions_buffers: RefCell<heapless::Vec<SubscriptionBuffer<B::Buffer<'a>>, N>>,
+    atomic_writes: Option<&'a AtomicWrites>,
     buffers: &'a B,
 }
 
@@ -84,18 +88,70 @@ where
     /// * `handler` - an instance of type `T` which implements the `DataModelHandler` trait. This instance is used for interacting with the underlying
     ///   clusters of the data model. Note that the expectations is for the user to provide a handler that handles the Matter system clusters
     ///   as well (Endpoint 0), possibly by decorating her own clusters with the `rs_matter::data_model::root_endpoint::with_` methods
+    ///
+    /// The data model created with this constructor does not support atomic writes, i.e. all `AtomicRequest` commands
+    /// are rejected with a `ResourceExhausted` status. Use `DataModel::new_with_atomic_writes` if any of the clusters
+    /// of the data model supports atomic writes.
     #[inline(always)]
     pub const fn new(buffers: &'a B, subscriptions: &'a Subscriptions<N>, handler: T) -> Self {
         Self {
             handler,
             subscriptions,
             subscriptions_buffers: RefCell::new(heapless::Vec::new()),
+            atomic_writes: None,
+            buffers,
+        }
+    }
+
+    /// Create the data model, with support for atomic writes.
+    ///
+    /// Same as `DataModel::new`, except that the pending values of the atomic writes in progress are stored
+    /// in the provided `atomic_writes` storage.
+    #[inline(always)]
+    pub const fn new_with_atomic_writes(
+        buffers: &'a B,
+        subscriptions: &'a Subscriptions<N>,
+        atomic_writes: &'a AtomicWrites,
+        handler: T,
+    ) -> Self {
+        Self {
+            handler,
+            subscriptions,
+            subscriptions_buffers: RefCell::new(heapless::Vec::new()),
+            atomic_writes: Some(atomic_writes),
             buffers,
         }
     }
 
     /// Answer a responding exchange using the `DataModelHandler` instance wrapped by this exchange handler.
     pub async fn handle(&self, exchange: &mut Exchange<'_>) -> Result<(), Error> {
+        let mut list_write = None;
+
+        let result = self.handle_requests(exchange, &mut list_write).await;
+
+        if let Some(list_write) = list_write {
+            // The exchange was interrupted in the middle of a (chunked) list write,
+            // hence restore the list to its value before the list write had started
+            let metadata = self.handler.lock().await;
+
+            if let Err(err) = list_write
+                .end(&self.handler, exchange, &metadata.node(), self, false)
+                .await
+            {
+                error!("Restoring an interrupted list write failed: {}", err);
+            }
+
+            exchange.matter().notify_persist();
+        }
+
+        result
+    }
+
+    async fn handle_requests(
+        &self,
+        exchange: &mut Exchange<'_>,
+        list_write: &mut Option<ListWrite<B::Buffer<'a>>>,
+    ) -> Result<(), Error> {
         let mut timeout_instant = None;
 
         loop {
@@ -113,7 +169,9 @@ where
             match meta.opcode::<OpCode>()? {
                 OpCode::ReadRequest => self.read(exchange).await?,
                 OpCode::WriteRequest => {
-                    repeat = self.write(exchange, timeout_instant.take()).await?;
+                    repeat = self
+                        .write(exchange, timeout_instant.take(), list_write)
+                        .await?;
                 }
                 OpCode::InvokeRequest => self.invoke(exchange, timeout_instant.take()).await?,
                 OpCode::SubscribeRequest => self.subscribe(exchange).await?,
@@ -237,6 +295,7 @@ where
         &self,
         exchange: &mut Exchange<'_>,
         timeout_instant: Option<Duration>,
+        list_write: &mut Option<ListWrite<B::Buffer<'a>>>,
     ) -> Result<bool, Error> {
         let req = WriteReqRef::new(TLVElement::new(exchange.rx()?.payload()));
         debug!("IM: Write request: {:?}", req);
@@ -257,10 +316,13 @@ where
 
         let req = WriteReqRef::new(TLVElement::new(exchange.rx()?.payload()));
 
+        // A list write which had started in a previous chunk is already granted access
+        let grant = WriteGrant::new(list_write.as_ref().map(ListWrite::path));
+
         // Will the clusters that are to be invoked await?
         let mut awaits = false;
 
-        for item in metadata.node().write(&req, &exchange.accessor()?)? {
+        for item in metadata.node().write(&req, &exchange.accessor()?, &grant)? {
             if item?
                 .map(|(attr, _)| {
                     self.handler.write_awaits(&WriteContext::new(
@@ -289,14 +351,34 @@ where
 
             let req = WriteReqRef::new(TLVElement::new(&rx));
 
-            req.respond(&self.handler, exchange, &metadata.node(), self, &mut wb)
-                .await?
+            req.respond(
+                &self.handler,
+                exchange,
+                &metadata.node(),
+                self,
+                self.buffers,
+                self.atomic_writes,
+                list_write,
+                &grant,
+                &mut wb,
+            )
+            .await?
         } else {
             // No, they won't. Answer the request by directly using the RX packet
             // of the transport layer, as the operation won't await.
 
-            req.respond(&self.handler, exchange, &metadata.node(), self, &mut wb)
-                .await?
+            req.respond(
+                &self.handler,
+                exchange,
+                &metadata.node(),
+                self,
+                self.buffers,
+                self.atomic_writes,
+                list_write,
+                &grant,
+                &mut wb,
+            )
+            .await?
         };
 
         exchange.send(OpCode::WriteResponse, wb.as_slice()).await?;
@@ -313,11 +395,26 @@ where
         debug!("IM: Invoke request: {:?}", req);
 
         let timed = req.timed_request()?;
+        let suppress_resp = req.suppress_response()?;
+
+        let max_paths = exchange
+            .matter()
+            .dev_det()
+            .max_paths_per_invoke
+            .unwrap_or(DEFAULT_MAX_PATHS_PER_INVOKE);
+
+        let batched = req.batched(max_paths)?;
 
         if self.timed_out(exchange, timeout_instant, timed).await? {
             return Ok(());
         }
 
+        let Some(batched) = batched else {
+            Self::send_status(exchange, IMStatusCode::InvalidAction).await?;
+
+            return Ok(());
+        };
+
         let Some(mut tx) = self.tx_buffer(exchange).await? else {
             return Ok(());
         };
@@ -348,43 +445,98 @@ where
             }
         }
 
-        if awaits {
-            // Yes, they will
-            // Allocate a separate RX buffer then and copy the RX packet into this buffer,
-            // so as not to hold on to the transport layer (single) RX packet for too long
-            // and block send / receive for everybody
-
-            let Some(rx) = self.rx_buffer(exchange).await? else {
-                return Ok(());
-            };
+        if !awaits && !batched {
+            // No, they won't, and the responses are not chunked.
+            // Answer the request by directly using the RX packet
+            // of the transport layer, as the operation won't await.
 
-            let req = InvReqRef::new(TLVElement::new(&rx));
+            let node = metadata.node();
+            let accessor = exchange.accessor()?;
+            let mut cmds = node.invoke(&req, &accessor)?.peekable();
 
             req.respond(
                 &self.handler,
                 exchange,
-                &metadata.node(),
                 self,
+                self.atomic_writes,
+                &mut cmds,
+                None,
                 &mut wb,
-                false,
             )
             .await?;
-        } else {
-            // No, they won't. Answer the request by directly using the RX packet
-            // of the transport layer, as the operation won't await.
 
-            req.respond(
-                &self.handler,
-                exchange,
-                &metadata.node(),
-                self,
-                &mut wb,
-                false,
-            )
-            .await?;
+            drop(cmds);
+
+            if suppress_resp {
+                exchange.acknowledge().await?;
+            } else {
+                exchange.send(OpCode::InvokeResponse, wb.as_slice()).await?;
+            }
+
+            return Ok(());
         }
 
-        exchange.send(OpCode::InvokeResponse, wb.as_slice()).await?;
+        // Yes, they will, or the responses might need to be chunked.
+        // Allocate a separate RX buffer then and copy the RX packet into this buffer,
+        // so as not to hold on to the transport layer (single) RX packet for too long
+        // and block send / receive for everybody
+
+        let Some(rx) = self.rx_buffer(exchange).await? else {
+            return Ok(());
+        };
+
+        // Commands of batched requests are invoked one by one, with each response being
+        // staged first, so that it can be moved to the next chunk if it does not fit in the current one
+        let mut staged = if batched {
+            let Some(mut staged) = self.buffer(exchange).await? else {
+                return Ok(());
+            };
+
+            // Always safe as `IMBuffer` is defined to be `MAX_EXCHANGE_RX_BUF_SIZE`, which is bigger than `MAX_EXCHANGE_TX_BUF_SIZE`
+            unwrap!(staged.resize_default(InvReqRef::MAX_STAGED_RESP_SIZE));
+
+            Some(staged)
+        } else {
+            None
+        };
+
+        let mut staged_wb = staged.as_mut().map(|staged| WriteBuf::new(staged));
+
+        let req = InvReqRef::new(TLVElement::new(&rx));
+
+        let node = metadata.node();
+        let accessor = exchange.accessor()?;
+        let mut cmds = node.invoke(&req, &accessor)?.peekable();
+
+        loop {
+            let more_chunks = req
+                .respond(
+                    &self.handler,
+                    exchange,
+                    self,
+                    self.atomic_writes,
+                    &mut cmds,
+                    staged_wb.as_mut(),
+                    &mut wb,
+                )
+                .await?;
+
+            if suppress_resp {
+                if more_chunks {
+                    continue;
+                }
+
+                exchange.acknowledge().await?;
+
+                break;
+            }
+
+            exchange.send(OpCode::InvokeResponse, wb.as_slice()).await?;
+
+            if !more_chunks || !Self::recv_status_success(exchange).await? {
+                break;
+            }
+        }
 
         Ok(())
     }
@@ -924,18 +1076,25 @@ impl<'a> ReportDataReq<'a> {
 }
 
 impl WriteReqRef<'_> {
-    async fn respond<T>(
+    #[allow(clippy::too_many_arguments)]
+    async fn respond<'a, T, A>(
         &self,
         handler: T,
         exchange: &Exchange<'_>,
         node: &Node<'_>,
         notify: &dyn ChangeNotify,
+        buffers: &'a A,
+        atomic_writes: Option<&AtomicWrites>,
+        list_write: &mut Option<ListWrite<A::Buffer<'a>>>,
+        grant: &WriteGrant,
         wb: &mut WriteBuf<'_>,
     ) -> Result<bool, Error>
     where
         T: DataModelHandler,
+        A: BufferAccess<IMBuffer>,
     {
         let accessor = exchange.accessor()?;
+        let owner = atomic_write_owner(exchange)?;
 
         wb.reset();
 
@@ -954,63 +1113,478 @@ impl WriteReqRef<'_> {
         // As with the C++ SDK, here we do all the ACLs checks first, before any write begins.
         // Thus we support the Case1 by doing this. It does come at the cost of maintaining an
         // additional list of expanded write requests as we start processing those.
-        let write_attrs: heapless::Vec<_, MAX_WRITE_ATTRS_IN_ONE_TRANS> =
-            node.write(self, &accessor)?.collect();
+        //
+        // Since that list is bounded, the write requests are processed in batches, so the ACL checks
+        // of a subsequent batch do see the effects of the previous one. This is not a problem for
+        // Case 1 though, as the access to a list being written is checked only once - at the beginning
+        // of the list write - and is granted to all subsequent writes to the same list (see `WriteGrant`).
+        let mut write_attrs = node.write(self, &accessor, grant)?;
+
+        loop {
+            let batch: heapless::Vec<_, MAX_WRITE_ATTRS_IN_ONE_TRANS> = write_attrs
+                .by_ref()
+                .take(MAX_WRITE_ATTRS_IN_ONE_TRANS)
+                .collect();
+
+            if batch.is_empty() {
+                break;
+            }
+
+            for item in batch {
+                let (attr, data) = match item? {
+                    Ok(item) => item,
+                    Err(status) => {
+                        status.to_tlv(&TLVTag::Anonymous, &mut tw)?;
+                        continue;
+                    }
+                };
+
+                if let Some(status) =
+                    atomic_writes.and_then(|atomic_writes| atomic_writes.write(owner, &attr, &data))
+                {
+                    // The attribute is part of an atomic write, so the write is either stored
+                    // as a pending value, or rejected if the atomic write is owned by another client
+                    if let Some(attr_status) = attr.status(status)? {
+                        attr_status.to_tlv(&TLVTag::Anonymous, &mut tw)?;
+                    }
+
+                    continue;
+                }
+
+                if list_write
+                    .as_ref()
+                    .map(|list_write| !list_write.matches(&attr))
+                    .unwrap_or(false)
+                {
+                    // A write to another attribute ends the list write in progress
+                    grant.set(None);
+                    unwrap!(list_write.take())
+                        .end(&handler, exchange, node, notify, true)
+                        .await?;
+                }
+
+                if list_write.is_none() && ListWrite::<A::Buffer<'a>>::is_list_write(&attr, &data) {
+                    let new_list_write =
+                        ListWrite::begin(&attr, buffers.get().await, &handler, exchange).await;
 
-        for item in write_attrs {
-            AttrDataEncoder::handle_write(exchange, &item?, &handler, &mut tw, notify).await?;
+                    grant.set(Some(new_list_write.path()));
+                    *list_write = Some(new_list_write);
+                }
+
+                if let Some(status) = list_write.as_ref().and_then(|list_write| list_write.failed) {
+                    // A previous write to the same list had failed, so the list is going to be restored anyway.
+                    // Do not apply the write and report the status of the failed one instead
+                    if let Some(attr_status) = attr.status(status)? {
+                        attr_status.to_tlv(&TLVTag::Anonymous, &mut tw)?;
+                    }
+                } else {
+                    let status = AttrDataEncoder::handle_write(
+                        exchange, &attr, &data, &handler, &mut tw, notify,
+                    )
+                    .await?;
+
+                    if status != IMStatusCode::Success {
+                        if let Some(list_write) = list_write.as_mut() {
+                            list_write.failed = Some(status);
+                        }
+                    }
+                }
+            }
         }
 
         tw.end_container()?;
         tw.end_container()?;
 
-        self.more_chunked()
+        let more_chunked = self.more_chunked()?;
+
+        if !more_chunked {
+            // The last chunk of the write request ends the list write in progress, if any
+            if let Some(list_write) = list_write.take() {
+                grant.set(None);
+                list_write
+                    .end(&handler, exchange, node, notify, true)
+                    .await?;
+            }
+        }
+
+        Ok(more_chunked)
+    }
+}
+
+/// A list attribute write which is in progress as part of a - potentially chunked - write request.
+///
+/// A list attribute might be written with a sequence of `AttributeDataIB`s for the same path,
+/// which might also span multiple chunks of a chunked write request. Typically, the sequence starts
+/// with a replace of the whole list - which in the case of a chunked write is a replace with an empty list -
+/// followed by appends (`ListIndex` = null) of the individual list items.
+///
+/// To make the sequence atomic, the value of the list is snapshotted before its first write is applied.
+/// If any of the writes in the sequence fails, the remaining ones are not applied (but reported with the status
+/// of the failed write), and the list is restored to its snapshotted value once the sequence is over.
+struct ListWrite<B> {
+    endpoint_id: EndptId,
+    cluster_id: ClusterId,
+    attr_id: AttrId,
+    /// The value of the list before the first write of the sequence, encoded as an `AttrResp`.
+    /// `None` if the value could not be snapshotted.
+    snapshot: Option<B>,
+    /// The status of the first failed write of the sequence, if any.
+    failed: Option<IMStatusCode>,
+}
+
+impl<B> ListWrite<B>
+where
+    B: DerefMut<Target = IMBuffer>,
+{
+    /// Return `true` if the write is a list write, i.e. a write of a whole list
+    /// or an operation on an individual list item.
+    fn is_list_write(attr: &AttrDetails, data: &TLVElement) -> bool {
+        attr.list_index.is_some() || data.array().is_ok()
+    }
+
+    /// Begin a new list write by snapshotting the current value of the list
+    /// into the provided buffer.
+    async fn begin<T>(
+        attr: &AttrDetails<'_>,
+        buffer: Option<B>,
+        handler: T,
+        exchange: &Exchange<'_>,
+    ) -> Self
+    where
+        T: DataModelHandler,
+    {
+        let snapshot = if let Some(mut buffer) = buffer {
+            let read_attr = AttrDetails {
+                node: attr.node,
+                endpoint_id: attr.endpoint_id,
+                cluster_id: attr.cluster_id,
+                attr_id: attr.attr_id,
+                wildcard: false,
+                list_index: None,
+                fab_idx: attr.fab_idx,
+                fab_filter: true,
+                dataver: None,
+            };
+
+            buffer.clear();
+            // Always safe as the `IMBuffer` capacity is `MAX_EXCHANGE_RX_BUF_SIZE`
+            unwrap!(buffer.resize_default(MAX_EXCHANGE_RX_BUF_SIZE));
+
+            let mut wb = WriteBuf::new(&mut buffer);
+            let mut tw = TLVWriter::new(&mut wb);
+
+            let result = handler
+                .read(
+                    &ReadContext::new(exchange, &read_attr),
+                    AttrDataEncoder::new(&read_attr, &mut tw),
+                )
+                .await;
+
+            let len = wb.get_tail();
+
+            match result {
+                Ok(()) if len > 0 => {
+                    buffer.truncate(len);
+                    Some(buffer)
+                }
+                Ok(()) => None,
+                Err(err) => {
+                    warn!("Snapshotting the list for a list write failed: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if snapshot.is_none() {
+            warn!(
+                "List {}/{}/{} cannot be restored should its write fail",
+                attr.endpoint_id, attr.cluster_id, attr.attr_id
+            );
+        }
+
+        Self {
+            endpoint_id: attr.endpoint_id,
+            cluster_id: attr.cluster_id,
+            attr_id: attr.attr_id,
+            snapshot,
+            failed: None,
+        }
+    }
+
+    /// Return the concrete path of the list being written.
+    fn path(&self) -> (EndptId, ClusterId, AttrId) {
+        (self.endpoint_id, self.cluster_id, self.attr_id)
+    }
+
+    /// Return `true` if the write is to the list being written.
+    fn matches(&self, attr: &AttrDetails) -> bool {
+        self.path() == (attr.endpoint_id, attr.cluster_id, attr.attr_id)
+    }
+
+    /// End the list write.
+    ///
+    /// If the list write did not complete, or if any of its writes failed,
+    /// the list is restored to its value before the list write had started.
+    async fn end<T>(
+        self,
+        handler: T,
+        exchange: &Exchange<'_>,
+        node: &Node<'_>,
+        notify: &dyn ChangeNotify,
+        complete: bool,
+    ) -> Result<(), Error>
+    where
+        T: DataModelHandler,
+    {
+        if complete && self.failed.is_none() {
+            return Ok(());
+        }
+
+        let Some(snapshot) = self.snapshot.as_ref() else {
+            warn!(
+                "List {}/{}/{} write failed, but the list cannot be restored",
+                self.endpoint_id, self.cluster_id, self.attr_id
+            );
+
+            return Ok(());
+        };
+
+        let AttrResp::Data(data) = AttrResp::from_tlv(&TLVElement::new(snapshot))? else {
+            return Ok(());
+        };
+
+        let attr = AttrDetails {
+            node,
+            endpoint_id: self.endpoint_id,
+            cluster_id: self.cluster_id,
+            attr_id: self.attr_id,
+            wildcard: false,
+            list_index: None,
+            fab_idx: exchange.accessor()?.fab_idx,
+            fab_filter: false,
+            dataver: None,
+        };
+
+        debug!(
+            "List {}/{}/{} write failed, restoring the list",
+            self.endpoint_id, self.cluster_id, self.attr_id
+        );
+
+        if let Err(err) = handler
+            .write(&WriteContext::new(exchange, &attr, &data.data, notify))
+            .await
+        {
+            error!("Restoring the list failed: {}", err);
+        }
+
+        Ok(())
     }
 }
 
 impl InvReqRef<'_> {
-    async fn respond<T>(
+    // This is the amount of space we reserve for other things to be attached towards
+    // the end of chunked invoke responses.
+    const LONG_INVOKES_TLV_RESERVE_SIZE: usize = 8;
+
+    // The maximum size of a single staged command response of a batched invoke request.
+    // This is the space left in an invoke response chunk after its header and the reserve above.
+    const MAX_STAGED_RESP_SIZE: usize =
+        MAX_EXCHANGE_TX_BUF_SIZE - Self::LONG_INVOKES_TLV_RESERVE_SIZE - 8;
+
+    /// Check the invoke request against the maximum number of paths per invoke
+    /// and - for requests with multiple commands - that all commands have unique
+    /// concrete paths and unique command references, as required by the spec.
+    ///
+    /// Returns `None` if the request is invalid, or `Some(true)` if it contains more than one command.
+    fn batched(&self, max_paths: u16) -> Result<Option<bool>, Error> {
+        let Some(reqs) = self.inv_requests()? else {
+            return Ok(Some(false));
+        };
+
+        let mut count = 0;
+
+        for (index, req) in reqs.iter().enumerate() {
+            let req = req?;
+
+            count += 1;
+
+            if count > max_paths as usize {
+                warn!(
+                    "Invoke request exceeds the maximum number of paths ({})",
+                    max_paths
+                );
+                return Ok(None);
+            }
+
+            if index == 0 {
+                continue;
+            }
+
+            if req.command_ref.is_none() || req.path.path.is_wildcard() {
+                warn!("Batched invoke request with a missing command reference or a wildcard path");
+                return Ok(None);
+            }
+
+            for other in reqs.iter().take(index) {
+                let other = other?;
+
+                if other.command_ref.is_none() || other.path.path.is_wildcard() {
+                    warn!("Batched invoke request with a missing command reference or a wildcard path");
+                    return Ok(None);
+                }
+
+                if other.command_ref == req.command_ref || other.path == req.path {
+                    warn!("Batched invoke request with duplicate paths or command references");
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(Some(count > 1))
+    }
+
+    /// Respond to the invoke request by invoking the commands and encoding their responses
+    /// in an invoke response chunk.
+    ///
+    /// If `staged` is provided, each command response is encoded in it first and then moved
+    /// into the chunk, or kept for the next chunk if the current one is full.
+    ///
+    /// Returns `true` if there are more responses to be sent in subsequent chunks.
+    #[allow(clippy::too_many_arguments)]
+    async fn respond<'m, T, I>(
         &self,
         handler: T,
         exchange: &Exchange<'_>,
-        node: &Node<'_>,
         notify: &dyn ChangeNotify,
+        atomic_writes: Option<&AtomicWrites>,
+        cmds: &mut Peekable<I>,
+        mut staged: Option<&mut WriteBuf<'_>>,
         wb: &mut WriteBuf<'_>,
-        suppress_resp: bool,
-    ) -> Result<(), Error>
+    ) -> Result<bool, Error>
     where
         T: DataModelHandler,
+        I: Iterator<Item = Result<Result<(CmdDetails<'m>, TLVElement<'m>), CmdStatus>, Error>>,
     {
         wb.reset();
+        wb.shrink(Self::LONG_INVOKES_TLV_RESERVE_SIZE)?;
 
         let mut tw = TLVWriter::new(wb);
 
         tw.start_struct(&TLVTag::Anonymous)?;
 
-        // Suppress Response -> TODO: Need to revisit this for cases where we send a command back
-        tw.bool(
-            &TLVTag::Context(InvRespTag::SupressResponse as u8),
-            suppress_resp,
-        )?;
+        // Suppress Response is reserved in invoke responses and should always be false.
+        // Whether the client asked for the response to be suppressed is handled by not sending it at all
+        tw.bool(&TLVTag::Context(InvRespTag::SupressResponse as u8), false)?;
 
         let has_requests = self.inv_requests()?.is_some();
+        let timed = self.timed_request()?;
 
         if has_requests {
             tw.start_array(&TLVTag::Context(InvRespTag::InvokeResponses as u8))?;
         }
 
-        let accessor = exchange.accessor()?;
+        if let Some(staged) = staged.as_mut() {
+            loop {
+                if staged.get_tail() == 0 {
+                    let Some(item) = cmds.next() else {
+                        break;
+                    };
+
+                    Self::handle(
+                        item?,
+                        timed,
+                        &handler,
+                        exchange,
+                        notify,
+                        atomic_writes,
+                        &mut TLVWriter::new(staged),
+                    )
+                    .await?;
+                }
+
+                if staged.get_tail() > 0 {
+                    let tail = tw.get_tail();
 
-        for item in node.invoke(self, &accessor)? {
-            CmdDataEncoder::handle(&item?, &handler, &mut tw, exchange, notify).await?;
+                    if tw
+                        .write_raw_data(staged.as_slice().iter().copied())
+                        .is_err()
+                    {
+                        // No space left in this chunk, the staged response goes to the next one
+                        tw.rewind_to(tail);
+                        break;
+                    }
+
+                    staged.reset();
+                }
+            }
+        } else {
+            for item in cmds.by_ref() {
+                Self::handle(
+                    item?,
+                    timed,
+                    &handler,
+                    exchange,
+                    notify,
+                    atomic_writes,
+                    &mut tw,
+                )
+                .await?;
+            }
         }
 
+        let more_chunks =
+            staged.map(|staged| staged.get_tail() > 0).unwrap_or(false) || cmds.peek().is_some();
+
+        wb.expand(Self::LONG_INVOKES_TLV_RESERVE_SIZE)?;
+        let tw = wb;
+
         if has_requests {
             tw.end_container()?;
         }
 
+        if more_chunks {
+            tw.bool(&TLVTag::Context(InvRespTag::MoreChunkedMsgs as u8), true)?;
+        }
+
         tw.end_container()?;
 
-        Ok(())
+        Ok(more_chunks)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn handle<T>(
+        item: Result<(CmdDetails<'_>, TLVElement<'_>), CmdStatus>,
+        timed: bool,
+        handler: T,
+        exchange: &Exchange<'_>,
+        notify: &dyn ChangeNotify,
+        atomic_writes: Option<&AtomicWrites>,
+        tw: &mut TLVWriter<'_, '_>,
+    ) -> Result<(), Error>
+    where
+        T: DataModelHandler,
+    {
+        match item {
+            Ok((cmd, _)) if !timed && cmd.is_timed() => {
+                // Commands which require a timed interaction are rejected when invoked outside of one
+                let item = Err(unwrap!(cmd.status(IMStatusCode::NeedsTimedInteraction)));
+
+                CmdDataEncoder::handle(&item, &handler, tw, exchange, notify).await
+            }
+            Ok((cmd, data)) if cmd.cmd_id == ATOMIC_REQUEST_CMD_ID => {
+                // Atomic requests are processed by the Data Model itself,
+                // and only delegated to the cluster handler when committing
+                if let Some(atomic_writes) = atomic_writes {
+                    atomic_writes
+                        .handle(&handler, exchange, &cmd, &data, tw, notify)
+                        .await
+                } else {
+                    AtomicWrites::reject(&cmd, tw)
+                }
+            }
+            item => CmdDataEncoder::handle(&item, &handler, tw, exchange, notify).await,
+        }
     }
 }
@@ -43,6 +43,104 @@ pub const DEV_TYPE_ON_OFF_LIGHT: DeviceType = DeviceType {
     drev: 2,
 };
 
+/// A constant representing the device type for
+/// the Dimmable Light cluster in Matter.
+pub const DEV_TYPE_DIMMABLE_LIGHT: DeviceType = DeviceType {
+    dtype: 0x0101,
+    drev: 3,
+};
+
+/// A constant representing the device type for
+/// the Extended Color Light cluster in Matter.
+pub const DEV_TYPE_EXTENDED_COLOR_LIGHT: DeviceType = DeviceType {
+    dtype: 0x010d,
+    drev: 4,
+};
+
+/// A constant representing the device type for
+/// the Door Lock cluster in Matter.
+pub const DEV_TYPE_DOOR_LOCK: DeviceType = DeviceType {
+    dtype: 0x000a,
+    drev: 3,
+};
+
+/// A constant representing the device type for
+/// the Thermostat cluster in Matter.
+pub const DEV_TYPE_THERMOSTAT: DeviceType = DeviceType {
+    dtype: 0x0301,
+    drev: 4,
+};
+
+/// A constant representing the device type for
+/// the Window Covering cluster in Matter.
+pub const DEV_TYPE_WINDOW_COVERING: DeviceType = DeviceType {
+    dtype: 0x0202,
+    drev: 4,
+};
+
+/// A constant representing the device type for
+/// the Temperature Sensor cluster in Matter.
+pub const DEV_TYPE_TEMPERATURE_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0302,
+    drev: 2,
+};
+
+/// A constant representing the device type for
+/// the Humidity Sensor cluster in Matter.
+pub const DEV_TYPE_HUMIDITY_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0307,
+    drev: 2,
+};
+
+/// A constant representing the device type for
+/// the Pressure Sensor cluster in Matter.
+pub const DEV_TYPE_PRESSURE_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0305,
+    drev: 2,
+};
+
+/// A constant representing the device type for
+/// the Flow Sensor cluster in Matter.
+pub const DEV_TYPE_FLOW_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0306,
+    drev: 2,
+};
+
+/// A constant representing the device type for
+/// the Light Sensor cluster in Matter.
+pub const DEV_TYPE_LIGHT_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0106,
+    drev: 3,
+};
+
+/// A constant representing the device type for
+/// the Occupancy Sensor cluster in Matter.
+pub const DEV_TYPE_OCCUPANCY_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0107,
+    drev: 4,
+};
+
+/// A constant representing the device type for
+/// the Contact Sensor cluster in Matter.
+pub const DEV_TYPE_CONTACT_SENSOR: DeviceType = DeviceType {
+    dtype: 0x0015,
+    drev: 2,
+};
+
+/// A constant representing the device type for
+/// the Air Quality Sensor cluster in Matter.
+pub const DEV_TYPE_AIR_QUALITY_SENSOR: DeviceType = DeviceType {
+    dtype: 0x002c,
+    drev: 1,
+};
+
+/// A constant representing the device type for
+/// the Power Source cluster in Matter.
+pub const DEV_TYPE_POWER_SOURCE: DeviceType = DeviceType {
+    dtype: 0x0011,
+    drev: 1,
+};
+
 /// A constant representing the device type for
 /// the Smart Speaker cluster in Matter.
 pub const DEV_TYPE_SMART_SPEAKER: DeviceType = DeviceType {
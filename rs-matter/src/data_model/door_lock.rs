@@ -0,0 +1,2018 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Door Lock cluster and its handler.
+//!
+//! The handler implements the User, PIN Credential, RFID Credential, Finger Credentials and
+//! Credentials Over-the-Air Access features of the cluster, i.e. a user and credential database
+//! managed with the `SetUser`, `GetUser`, `ClearUser`, `SetCredential`, `GetCredentialStatus` and
+//! `ClearCredential` commands, as well as the `LockDoor`, `UnlockDoor` and `UnlockWithTimeout` commands
+//! with PIN validation, wrong code entry lockout and auto-relocking.
+//!
+//! Fingerprint credentials are stored as opaque templates: matching a finger against the stored templates
+//! is up to the device, which reports the matched credential with `DoorLockHandler::lock` / `DoorLockHandler::unlock`.
+
+use core::num::NonZeroU8;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::{Duration, Instant, Timer};
+
+use subtle::ConstantTimeEq;
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{
+    FromTLV, Nullable, OctetsOwned, TLVBuilderParent, TLVElement, TLVTag, ToTLV, Utf8Str,
+};
+use crate::utils::cell::RefCell;
+use crate::utils::storage::{Vec, WriteBuf};
+use crate::utils::sync::Notification;
+use crate::with;
+
+use super::objects::{Cluster, Dataver, InvokeContext, ReadContext, WriteContext};
+
+pub use crate::data_model::clusters::door_lock::*;
+
+/// The maximum number of users in the user database.
+pub const MAX_USERS: usize = 10;
+/// The maximum number of credentials of each type (PIN, RFID and fingerprint).
+pub const MAX_CREDENTIALS_PER_TYPE: usize = MAX_USERS;
+/// The maximum number of credentials in the credential database, across all credential types.
+pub const MAX_CREDENTIALS: usize = 2 * MAX_USERS;
+/// The maximum number of credentials a single user can have.
+pub const MAX_CREDENTIALS_PER_USER: usize = 5;
+/// The maximum length of a user name.
+pub const MAX_USER_NAME_LEN: usize = 10;
+
+/// The minimum and maximum length of a PIN code.
+pub const MIN_PIN_LEN: usize = 4;
+pub const MAX_PIN_LEN: usize = 8;
+/// The minimum and maximum length of an RFID code.
+pub const MIN_RFID_LEN: usize = 4;
+pub const MAX_RFID_LEN: usize = 20;
+
+/// The maximum length of the data of a credential (i.e. a PIN, an RFID code or a fingerprint template ID).
+const MAX_CREDENTIAL_DATA_LEN: usize = MAX_RFID_LEN;
+
+/// The user or credential index designating all users or all credentials of a type.
+const ALL_INDEXES: u16 = 0xfffe;
+
+/// The operating modes supported by the handler.
+const SUPPORTED_OPERATING_MODES: DlSupportedOperatingModes = DlSupportedOperatingModes::all();
+
+/// The details of a lock operation - successful or failed - as reported to the `DoorLockHooks`.
+///
+/// These correspond to the fields of the `LockOperation` and `LockOperationError` events of the cluster.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LockOperationInfo {
+    /// The type of the operation
+    pub operation: LockOperationTypeEnum,
+    /// The source of the operation
+    pub source: OperationSourceEnum,
+    /// The index of the user who performed the operation, if known
+    pub user_index: Option<u16>,
+    /// The index of the fabric which performed the operation, for remote operations
+    pub fab_idx: Option<NonZeroU8>,
+    /// The node ID of the node which performed the operation, for remote operations
+    pub source_node: Option<u64>,
+    /// The type and the index of the credential used for the operation, if any
+    pub credential: Option<(CredentialTypeEnum, u16)>,
+}
+
+/// A trait for the hardware (or the driver) of a Door Lock device.
+///
+/// The `DoorLockHandler` calls into it whenever the lock needs to be actuated, and whenever
+/// one of the `LockOperation`, `LockOperationError` and `DoorLockAlarm` events of the cluster happens.
+///
+/// The events are only delivered to the hooks for now (see [Events](crate::data_model#events)).
+pub trait DoorLockHooks {
+    /// Lock or unlock the bolt of the lock.
+    ///
+    /// Return `false` if the bolt could not be moved (i.e. the lock is jammed).
+    fn actuate(&self, lock: bool) -> bool;
+
+    /// Called after a successful lock operation (the `LockOperation` event).
+    fn lock_operation(&self, info: &LockOperationInfo) {
+        let _ = info;
+    }
+
+    /// Called after a failed lock operation (the `LockOperationError` event).
+    fn lock_operation_error(&self, info: &LockOperationInfo, error: OperationErrorEnum) {
+        let _ = (info, error);
+    }
+
+    /// Called when an alarm condition occurs (the `DoorLockAlarm` event).
+    fn alarm(&self, code: AlarmCodeEnum) {
+        let _ = code;
+    }
+}
+
+impl<T> DoorLockHooks for &T
+where
+    T: DoorLockHooks,
+{
+    fn actuate(&self, lock: bool) -> bool {
+        (*self).actuate(lock)
+    }
+
+    fn lock_operation(&self, info: &LockOperationInfo) {
+        (*self).lock_operation(info)
+    }
+
+    fn lock_operation_error(&self, info: &LockOperationInfo, error: OperationErrorEnum) {
+        (*self).lock_operation_error(info, error)
+    }
+
+    fn alarm(&self, code: AlarmCodeEnum) {
+        (*self).alarm(code)
+    }
+}
+
+/// A no-op implementation for devices that only need to observe the state of the cluster
+/// with `DoorLockHandler::lock_state`.
+impl DoorLockHooks for () {
+    fn actuate(&self, _lock: bool) -> bool {
+        true
+    }
+}
+
+/// A user of the user database.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct User {
+    index: u16,
+    name: heapless::String<MAX_USER_NAME_LEN>,
+    unique_id: Option<u32>,
+    status: UserStatusEnum,
+    user_type: UserTypeEnum,
+    credential_rule: CredentialRuleEnum,
+    creator_fab_idx: u8,
+    modifier_fab_idx: u8,
+}
+
+/// A credential of the credential database.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Credential {
+    cred_type: CredentialTypeEnum,
+    index: u16,
+    user_index: u16,
+    data: OctetsOwned<MAX_CREDENTIAL_DATA_LEN>,
+    creator_fab_idx: u8,
+    modifier_fab_idx: u8,
+}
+
+impl Credential {
+    fn is(&self, cred_type: CredentialTypeEnum, index: u16) -> bool {
+        self.cred_type == cred_type && self.index == index
+    }
+}
+
+/// The persisted part of the state of the Door Lock cluster:
+/// the lock settings, and the user and credential databases.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct DoorLockData {
+    auto_relock_time: u32,
+    operating_mode: OperatingModeEnum,
+    wrong_code_entry_limit: u8,
+    user_code_temporary_disable_time: u8,
+    require_pin_for_remote_operation: bool,
+    users: Vec<User, MAX_USERS>,
+    credentials: Vec<Credential, MAX_CREDENTIALS>,
+}
+
+impl DoorLockData {
+    const fn new() -> Self {
+        Self {
+            auto_relock_time: 0,
+            operating_mode: OperatingModeEnum::Normal,
+            wrong_code_entry_limit: 3,
+            user_code_temporary_disable_time: 10,
+            require_pin_for_remote_operation: false,
+            users: Vec::new(),
+            credentials: Vec::new(),
+        }
+    }
+
+    fn user(&self, index: u16) -> Option<&User> {
+        self.users.iter().find(|user| user.index == index)
+    }
+
+    fn user_mut(&mut self, index: u16) -> Option<&mut User> {
+        self.users.iter_mut().find(|user| user.index == index)
+    }
+
+    fn credential(&self, cred_type: CredentialTypeEnum, index: u16) -> Option<&Credential> {
+        self.credentials
+            .iter()
+            .find(|cred| cred.is(cred_type, index))
+    }
+
+    /// Find the credential of the given type with the given data.
+    ///
+    /// The credential data is compared in constant time, so as not to leak it via timing.
+    fn find_credential(&self, cred_type: CredentialTypeEnum, data: &[u8]) -> Option<&Credential> {
+        self.credentials
+            .iter()
+            .find(|cred| cred.cred_type == cred_type && bool::from(cred.data.vec.ct_eq(data)))
+    }
+
+    /// Return an iterator over the credentials of the given user.
+    fn user_credentials(&self, user_index: u16) -> impl Iterator<Item = &Credential> {
+        self.credentials
+            .iter()
+            .filter(move |cred| cred.user_index == user_index)
+    }
+
+    /// Return the first user index after `index` (exclusive) which is occupied (or free, if `occupied` is `false`).
+    fn next_user_index(&self, index: u16, occupied: bool) -> Option<u16> {
+        (index + 1..=MAX_USERS as u16).find(|index| self.user(*index).is_some() == occupied)
+    }
+
+    /// Return the first credential index of the given type after `index` (exclusive)
+    /// which is occupied (or free, if `occupied` is `false`).
+    fn next_credential_index(
+        &self,
+        cred_type: CredentialTypeEnum,
+        index: u16,
+        occupied: bool,
+    ) -> Option<u16> {
+        (index + 1..=MAX_CREDENTIALS_PER_TYPE as u16)
+            .find(|index| self.credential(cred_type, *index).is_some() == occupied)
+    }
+
+    /// Remove the credentials matching the given predicate, as well as the users left without credentials.
+    fn remove_credentials<F>(&mut self, f: F)
+    where
+        F: Fn(&Credential) -> bool,
+    {
+        let mut orphans = Vec::<u16, MAX_CREDENTIALS>::new();
+
+        for cred in self.credentials.iter().filter(|cred| f(cred)) {
+            if !orphans.contains(&cred.user_index) {
+                unwrap!(orphans.push(cred.user_index));
+            }
+        }
+
+        self.credentials.retain(|cred| !f(cred));
+
+        for user_index in orphans {
+            if self.user_credentials(user_index).next().is_none() {
+                self.users.retain(|user| user.index != user_index);
+            }
+        }
+    }
+}
+
+/// The state of the Door Lock cluster.
+struct DoorLockState {
+    lock_state: Option<DlLockState>,
+    data: DoorLockData,
+    wrong_code_entries: u8,
+    lockout_until: Option<Instant>,
+    relock_at: Option<Instant>,
+    changed: bool,
+}
+
+impl DoorLockState {
+    const fn new() -> Self {
+        Self {
+            lock_state: None,
+            data: DoorLockData::new(),
+            wrong_code_entries: 0,
+            lockout_until: None,
+            relock_at: None,
+            changed: false,
+        }
+    }
+
+    /// Return `true` if the user interface is temporarily disabled after too many wrong code entries.
+    fn locked_out(&self) -> bool {
+        self.lockout_until
+            .map(|until| Instant::now() < until)
+            .unwrap_or(false)
+    }
+
+    /// Record a wrong code entry, returning `true` if the wrong code entry limit was reached.
+    fn wrong_code_entry(&mut self) -> bool {
+        self.wrong_code_entries = self.wrong_code_entries.saturating_add(1);
+
+        let limit = self.data.wrong_code_entry_limit;
+
+        if limit > 0 && self.wrong_code_entries >= limit {
+            self.wrong_code_entries = 0;
+            self.lockout_until = Some(
+                Instant::now()
+                    + Duration::from_secs(self.data.user_code_temporary_disable_time as _),
+            );
+
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A handler for the Door Lock Matter cluster.
+///
+/// The handler drives the lock via the `DoorLockHooks` trait.
+///
+/// The lock settings and the user and credential databases should be persisted
+/// (see `DoorLockHandler::load` and `DoorLockHandler::store`).
+///
+/// The auto-relock timer is only running while the `DoorLockHandler::run` future is polled.
+pub struct DoorLockHandler<'a> {
+    dataver: Dataver,
+    lock_type: DlLockType,
+    state: RefCell<DoorLockState>,
+    hooks: &'a dyn DoorLockHooks,
+    timer_changed: Notification<NoopRawMutex>,
+    persist_state_changed: Notification<NoopRawMutex>,
+}
+
+impl<'a> DoorLockHandler<'a> {
+    /// Creates a new instance of `DoorLockHandler` with the given `Dataver` and lock type.
+    ///
+    /// The state of the lock is initially unknown (i.e. `null`), until the first lock operation,
+    /// or until it is reported with `DoorLockHandler::set_lock_state`.
+    pub const fn new(dataver: Dataver, lock_type: DlLockType) -> Self {
+        Self {
+            dataver,
+            lock_type,
+            state: RefCell::new(DoorLockState::new()),
+            hooks: &(),
+            timer_changed: Notification::new(),
+            persist_state_changed: Notification::new(),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Set the hooks which drive the hardware of the device.
+    pub fn set_hooks(&mut self, hooks: &'a dyn DoorLockHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Return the current state of the lock, or `None` if it is not known.
+    pub fn lock_state(&self) -> Option<DlLockState> {
+        self.state.borrow().lock_state
+    }
+
+    /// Set the state of the lock, as reported by the hardware.
+    ///
+    /// Unlike `DoorLockHandler::lock` and `DoorLockHandler::unlock`, this method does not actuate the lock.
+    /// As such, it is suitable for reflecting state changes triggered locally (i.e. by a key or a thumbturn).
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn set_lock_state(&self, lock_state: Option<DlLockState>) {
+        self.state.borrow_mut().lock_state = lock_state;
+        self.dataver.changed();
+    }
+
+    /// Lock the door as a result of a local operation (i.e. a keypad or an RFID reader).
+    ///
+    /// # Arguments
+    /// - `source`: The source of the operation
+    /// - `credential`: The type and the data of the credential used for the operation, if any;
+    ///   for fingerprint credentials, the data is the template matched by the device
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn lock(
+        &self,
+        source: OperationSourceEnum,
+        credential: Option<(CredentialTypeEnum, &[u8])>,
+    ) -> Result<(), OperationErrorEnum> {
+        self.operate(
+            LockOperationTypeEnum::Lock,
+            source,
+            credential,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Unlock the door as a result of a local operation (i.e. a keypad or an RFID reader).
+    ///
+    /// # Arguments
+    /// - `source`: The source of the operation
+    /// - `credential`: The type and the data of the credential used for the operation, if any;
+    ///   for fingerprint credentials, the data is the template matched by the device
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn unlock(
+        &self,
+        source: OperationSourceEnum,
+        credential: Option<(CredentialTypeEnum, &[u8])>,
+    ) -> Result<(), OperationErrorEnum> {
+        self.operate(
+            LockOperationTypeEnum::Unlock,
+            source,
+            credential,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Run the auto-relock timer.
+    ///
+    /// # Arguments
+    /// - `notify`: A callback which is called whenever the timer changes the state of the cluster,
+    ///   and which is expected to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub async fn run<F>(&self, notify: F) -> Result<(), Error>
+    where
+        F: Fn(),
+    {
+        loop {
+            let relock_at = self.state.borrow().relock_at;
+
+            if let Some(relock_at) = relock_at {
+                if let Either::First(_) =
+                    select(Timer::at(relock_at), self.timer_changed.wait()).await
+                {
+                    self.relock();
+                    notify();
+                }
+            } else {
+                self.timer_changed.wait().await;
+            }
+        }
+    }
+
+    /// Load the persisted lock settings and user and credential databases from a byte slice.
+    ///
+    /// # Arguments
+    /// - `data`: The byte slice to load the state from
+    pub fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let data = DoorLockData::from_tlv(&TLVElement::new(data))?;
+
+        let mut state = self.state.borrow_mut();
+
+        state.data = data;
+        state.changed = false;
+
+        self.dataver.changed();
+
+        Ok(())
+    }
+
+    /// Store the lock settings and the user and credential databases into a byte slice.
+    ///
+    /// # Arguments
+    /// - `buf`: The byte slice to store the state into
+    ///
+    /// Returns `Ok(None)` if the state has not changed, `Ok(Some(data))` if the state has changed
+    /// where `data` is the sub-slice of the buffer that contains the data to be persisted
+    pub fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        let mut state = self.state.borrow_mut();
+
+        if !state.changed {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        state.data.to_tlv(&TLVTag::Anonymous, &mut wb)?;
+
+        state.changed = false;
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    /// Return `true` if the state has changed and needs to be persisted.
+    pub fn changed(&self) -> bool {
+        self.state.borrow().changed
+    }
+
+    /// Wait for the state to be changed in a way that requires persisting.
+    pub async fn wait_persist(&self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+
+            self.persist_state_changed.wait().await;
+        }
+    }
+
+    /// Perform a lock operation, validating the credential (if any) against the credential database.
+    #[allow(clippy::too_many_arguments)]
+    fn operate(
+        &self,
+        operation: LockOperationTypeEnum,
+        source: OperationSourceEnum,
+        credential: Option<(CredentialTypeEnum, &[u8])>,
+        fab_idx: Option<NonZeroU8>,
+        source_node: Option<u64>,
+        relock_after: Option<u16>,
+    ) -> Result<(), OperationErrorEnum> {
+        let mut info = LockOperationInfo {
+            operation,
+            source,
+            user_index: None,
+            fab_idx,
+            source_node,
+            credential: None,
+        };
+
+        let result = self.validate(&mut info, credential);
+
+        let result = result.and_then(|_| {
+            let lock = matches!(operation, LockOperationTypeEnum::Lock);
+
+            self.actuate(lock, relock_after)
+                .then_some(())
+                .ok_or(OperationErrorEnum::Unspecified)
+        });
+
+        match result {
+            Ok(()) => self.hooks.lock_operation(&info),
+            Err(error) => self.hooks.lock_operation_error(&info, error),
+        }
+
+        result
+    }
+
+    /// Check whether the operation is allowed in the current operating mode, and with the provided credential.
+    fn validate(
+        &self,
+        info: &mut LockOperationInfo,
+        credential: Option<(CredentialTypeEnum, &[u8])>,
+    ) -> Result<(), OperationErrorEnum> {
+        let remote = matches!(info.source, OperationSourceEnum::Remote);
+
+        let limit_reached = {
+            let mut state = self.state.borrow_mut();
+
+            let restricted = match state.data.operating_mode {
+                OperatingModeEnum::NoRemoteLockUnlock => remote,
+                OperatingModeEnum::Vacation | OperatingModeEnum::Privacy => matches!(
+                    info.source,
+                    OperationSourceEnum::Keypad
+                        | OperationSourceEnum::RFID
+                        | OperationSourceEnum::Biometric
+                ),
+                _ => false,
+            };
+
+            if restricted {
+                return Err(OperationErrorEnum::Restricted);
+            }
+
+            let Some((cred_type, data)) = credential else {
+                return if remote && state.data.require_pin_for_remote_operation {
+                    Err(OperationErrorEnum::InvalidCredential)
+                } else {
+                    Ok(())
+                };
+            };
+
+            if state.locked_out() {
+                return Err(OperationErrorEnum::Restricted);
+            }
+
+            if let Some(cred) = state.data.find_credential(cred_type, data) {
+                info.user_index = Some(cred.user_index);
+                info.credential = Some((cred.cred_type, cred.index));
+
+                let user = state.data.user(cred.user_index);
+
+                if user
+                    .map(|user| matches!(user.status, UserStatusEnum::OccupiedDisabled))
+                    .unwrap_or(true)
+                {
+                    return Err(OperationErrorEnum::DisabledUserDenied);
+                }
+
+                if user
+                    .map(|user| matches!(user.user_type, UserTypeEnum::NonAccessUser))
+                    .unwrap_or(false)
+                {
+                    return Err(OperationErrorEnum::Restricted);
+                }
+
+                state.wrong_code_entries = 0;
+
+                return Ok(());
+            }
+
+            state.wrong_code_entry()
+        };
+
+        if limit_reached {
+            self.hooks.alarm(AlarmCodeEnum::WrongCodeEntryLimit);
+        }
+
+        Err(OperationErrorEnum::InvalidCredential)
+    }
+
+    /// Actuate the lock and update the lock state and the auto-relock timer.
+    ///
+    /// Return `false` if the lock could not be actuated.
+    fn actuate(&self, lock: bool, relock_after: Option<u16>) -> bool {
+        let actuated = self.hooks.actuate(lock);
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            if actuated {
+                state.lock_state = Some(if lock {
+                    DlLockState::Locked
+                } else {
+                    DlLockState::Unlocked
+                });
+
+                let relock_after = relock_after.map(|secs| secs as u32).or_else(|| {
+                    (state.data.auto_relock_time > 0
+                        && !matches!(state.data.operating_mode, OperatingModeEnum::Passage))
+                    .then_some(state.data.auto_relock_time)
+                });
+
+                state.relock_at = relock_after
+                    .filter(|_| !lock)
+                    .map(|secs| Instant::now() + Duration::from_secs(secs as _));
+            } else {
+                state.lock_state = Some(DlLockState::NotFullyLocked);
+                state.relock_at = None;
+            }
+        }
+
+        if !actuated {
+            self.hooks.alarm(AlarmCodeEnum::LockJammed);
+        }
+
+        self.dataver.changed();
+        self.timer_changed.notify();
+
+        actuated
+    }
+
+    /// Relock the door once the auto-relock timer had expired.
+    fn relock(&self) {
+        self.state.borrow_mut().relock_at = None;
+
+        let _ = self.operate(
+            LockOperationTypeEnum::Lock,
+            OperationSourceEnum::Auto,
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    /// Perform a remote lock operation, as requested by a `LockDoor`, `UnlockDoor` or `UnlockWithTimeout` command.
+    fn operate_remote(
+        &self,
+        ctx: &InvokeContext,
+        operation: LockOperationTypeEnum,
+        pin_code: Option<&[u8]>,
+        relock_after: Option<u16>,
+    ) -> Result<(), Error> {
+        let (fab_idx, source_node) = accessor(ctx)?;
+
+        let result = self.operate(
+            operation,
+            OperationSourceEnum::Remote,
+            pin_code.map(|pin_code| (CredentialTypeEnum::PIN, pin_code)),
+            Some(fab_idx),
+            source_node,
+            relock_after,
+        );
+
+        ctx.notify_changed();
+
+        // The Door Lock cluster reports all failed lock operations with a FAILURE status
+        result.map_err(|_| ErrorCode::Failure.into())
+    }
+
+    /// Add or modify a user, as per the `SetUser` command.
+    #[allow(clippy::too_many_arguments)]
+    fn set_user(
+        &self,
+        fab_idx: NonZeroU8,
+        operation: DataOperationTypeEnum,
+        index: u16,
+        name: Option<&str>,
+        unique_id: Option<u32>,
+        status: Option<UserStatusEnum>,
+        user_type: Option<UserTypeEnum>,
+        credential_rule: Option<CredentialRuleEnum>,
+    ) -> DlStatus {
+        if !(1..=MAX_USERS as u16).contains(&index)
+            || name
+                .map(|name| name.len() > MAX_USER_NAME_LEN)
+                .unwrap_or(false)
+            || matches!(status, Some(UserStatusEnum::Available))
+            || matches!(user_type, Some(UserTypeEnum::ProgrammingUser))
+            || !matches!(credential_rule, None | Some(CredentialRuleEnum::Single))
+        {
+            return DlStatus::InvalidField;
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        match operation {
+            DataOperationTypeEnum::Add => {
+                if state.data.user(index).is_some() {
+                    return DlStatus::Occupied;
+                }
+
+                let user = User {
+                    index,
+                    name: name
+                        .and_then(|name| name.try_into().ok())
+                        .unwrap_or_default(),
+                    unique_id,
+                    status: status.unwrap_or(UserStatusEnum::OccupiedEnabled),
+                    user_type: user_type.unwrap_or(UserTypeEnum::UnrestrictedUser),
+                    credential_rule: credential_rule.unwrap_or(CredentialRuleEnum::Single),
+                    creator_fab_idx: fab_idx.get(),
+                    modifier_fab_idx: fab_idx.get(),
+                };
+
+                if state.data.users.push(user).is_err() {
+                    return DlStatus::ResourceExhausted;
+                }
+            }
+            DataOperationTypeEnum::Modify => {
+                let Some(user) = state.data.user_mut(index) else {
+                    return DlStatus::InvalidField;
+                };
+
+                // Only the fabric which created the user can change its name and unique ID
+                if user.creator_fab_idx != fab_idx.get() && (name.is_some() || unique_id.is_some())
+                {
+                    return DlStatus::InvalidField;
+                }
+
+                if let Some(name) = name {
+                    user.name = unwrap!(name.try_into());
+                }
+
+                if unique_id.is_some() {
+                    user.unique_id = unique_id;
+                }
+
+                if let Some(status) = status {
+                    user.status = status;
+                }
+
+                if let Some(user_type) = user_type {
+                    user.user_type = user_type;
+                }
+
+                if let Some(credential_rule) = credential_rule {
+                    user.credential_rule = credential_rule;
+                }
+
+                user.modifier_fab_idx = fab_idx.get();
+            }
+            DataOperationTypeEnum::Clear => return DlStatus::InvalidField,
+        }
+
+        self.db_changed(&mut state);
+
+        DlStatus::Success
+    }
+
+    /// Remove a user (or all users if `index` is `0xfffe`) and their credentials, as per the `ClearUser` command.
+    fn clear_user(&self, index: u16) -> DlStatus {
+        if index != ALL_INDEXES && !(1..=MAX_USERS as u16).contains(&index) {
+            return DlStatus::InvalidField;
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        let all = index == ALL_INDEXES;
+
+        state.data.users.retain(|user| !all && user.index != index);
+        state
+            .data
+            .credentials
+            .retain(|cred| !all && cred.user_index != index);
+
+        self.db_changed(&mut state);
+
+        DlStatus::Success
+    }
+
+    /// Add or modify a credential, as per the `SetCredential` command.
+    ///
+    /// Return the status of the operation and the index of the user the credential belongs to.
+    #[allow(clippy::too_many_arguments)]
+    fn set_credential(
+        &self,
+        fab_idx: NonZeroU8,
+        operation: DataOperationTypeEnum,
+        cred_type: CredentialTypeEnum,
+        index: u16,
+        data: &[u8],
+        user_index: Option<u16>,
+        user_status: Option<UserStatusEnum>,
+        user_type: Option<UserTypeEnum>,
+    ) -> (DlStatus, Option<u16>) {
+        let data_len_valid = match cred_type {
+            CredentialTypeEnum::PIN => {
+                (MIN_PIN_LEN..=MAX_PIN_LEN).contains(&data.len())
+                    && data.iter().all(u8::is_ascii_digit)
+            }
+            CredentialTypeEnum::RFID => (MIN_RFID_LEN..=MAX_RFID_LEN).contains(&data.len()),
+            CredentialTypeEnum::Fingerprint => {
+                !data.is_empty() && data.len() <= MAX_CREDENTIAL_DATA_LEN
+            }
+            _ => false,
+        };
+
+        if !data_len_valid
+            || !(1..=MAX_CREDENTIALS_PER_TYPE as u16).contains(&index)
+            || user_index
+                .map(|user_index| !(1..=MAX_USERS as u16).contains(&user_index))
+                .unwrap_or(false)
+            || matches!(user_status, Some(UserStatusEnum::Available))
+            || matches!(user_type, Some(UserTypeEnum::ProgrammingUser))
+        {
+            return (DlStatus::InvalidField, None);
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        if state
+            .data
+            .find_credential(cred_type, data)
+            .map(|cred| cred.index != index)
+            .unwrap_or(false)
+        {
+            return (DlStatus::Duplicate, None);
+        }
+
+        match operation {
+            DataOperationTypeEnum::Add => {
+                if state.data.credential(cred_type, index).is_some() {
+                    return (DlStatus::Occupied, None);
+                }
+
+                if state.data.credentials.is_full() {
+                    return (DlStatus::ResourceExhausted, None);
+                }
+
+                let user_index = if let Some(user_index) =
+                    user_index.filter(|user_index| state.data.user(*user_index).is_some())
+                {
+                    // Adding a credential to an existing user
+                    if user_status.is_some() || user_type.is_some() {
+                        return (DlStatus::InvalidField, None);
+                    }
+
+                    if state.data.user_credentials(user_index).count() >= MAX_CREDENTIALS_PER_USER {
+                        return (DlStatus::ResourceExhausted, None);
+                    }
+
+                    user_index
+                } else {
+                    // Adding a credential to a new user
+                    let Some(user_index) =
+                        user_index.or_else(|| state.data.next_user_index(0, false))
+                    else {
+                        return (DlStatus::ResourceExhausted, None);
+                    };
+
+                    let user = User {
+                        index: user_index,
+                        name: heapless::String::new(),
+                        unique_id: None,
+                        status: user_status.unwrap_or(UserStatusEnum::OccupiedEnabled),
+                        user_type: user_type.unwrap_or(UserTypeEnum::UnrestrictedUser),
+                        credential_rule: CredentialRuleEnum::Single,
+                        creator_fab_idx: fab_idx.get(),
+                        modifier_fab_idx: fab_idx.get(),
+                    };
+
+                    if state.data.users.push(user).is_err() {
+                        return (DlStatus::ResourceExhausted, None);
+                    }
+
+                    user_index
+                };
+
+                unwrap!(state
+                    .data
+                    .credentials
+                    .push(Credential {
+                        cred_type,
+                        index,
+                        user_index,
+                        data: OctetsOwned {
+                            vec: unwrap!(data.try_into()),
+                        },
+                        creator_fab_idx: fab_idx.get(),
+                        modifier_fab_idx: fab_idx.get(),
+                    })
+                    .map_err(|_| ()));
+
+                self.db_changed(&mut state);
+
+                (DlStatus::Success, Some(user_index))
+            }
+            DataOperationTypeEnum::Modify => {
+                let Some(cred) = state
+                    .data
+                    .credentials
+                    .iter_mut()
+                    .find(|cred| cred.is(cred_type, index))
+                else {
+                    return (DlStatus::InvalidField, None);
+                };
+
+                if user_index != Some(cred.user_index) {
+                    return (DlStatus::InvalidField, None);
+                }
+
+                cred.data.vec = unwrap!(data.try_into());
+                cred.modifier_fab_idx = fab_idx.get();
+
+                self.db_changed(&mut state);
+
+                (DlStatus::Success, user_index)
+            }
+            DataOperationTypeEnum::Clear => (DlStatus::InvalidField, None),
+        }
+    }
+
+    /// Remove a credential (or all credentials of a type if `index` is `0xfffe`, or all credentials
+    /// if `credential` is `None`), as per the `ClearCredential` command.
+    ///
+    /// Users left without credentials are removed as well.
+    fn clear_credential(&self, credential: Option<(CredentialTypeEnum, u16)>) -> DlStatus {
+        if let Some((_, index)) = credential {
+            if index != ALL_INDEXES && !(1..=MAX_CREDENTIALS_PER_TYPE as u16).contains(&index) {
+                return DlStatus::InvalidField;
+            }
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        state.data.remove_credentials(|cred| match credential {
+            None => true,
+            Some((cred_type, ALL_INDEXES)) => cred.cred_type == cred_type,
+            Some((cred_type, index)) => cred.is(cred_type, index),
+        });
+
+        self.db_changed(&mut state);
+
+        DlStatus::Success
+    }
+
+    /// Mark the persisted state as changed.
+    fn db_changed(&self, state: &mut DoorLockState) {
+        state.changed = true;
+
+        self.persist_state_changed.notify();
+        self.dataver.changed();
+    }
+
+    /// Update the persisted lock settings.
+    fn update<F>(&self, ctx: &WriteContext, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut DoorLockData) -> Result<(), Error>,
+    {
+        {
+            let mut state = self.state.borrow_mut();
+
+            f(&mut state.data)?;
+
+            self.db_changed(&mut state);
+        }
+
+        self.timer_changed.notify();
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+}
+
+impl ClusterHandler for DoorLockHandler<'_> {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(7)
+        .with_features(
+            Feature::PIN_CREDENTIAL.bits()
+                | Feature::RFID_CREDENTIAL.bits()
+                | Feature::FINGER_CREDENTIALS.bits()
+                | Feature::CREDENTIALS_OVER_THE_AIR_ACCESS.bits()
+                | Feature::USER.bits(),
+        )
+        .with_attrs(with!(
+            required;
+            AttributeId::NumberOfTotalUsersSupported
+                | AttributeId::NumberOfPINUsersSupported
+                | AttributeId::NumberOfRFIDUsersSupported
+                | AttributeId::MaxPINCodeLength
+                | AttributeId::MinPINCodeLength
+                | AttributeId::MaxRFIDCodeLength
+                | AttributeId::MinRFIDCodeLength
+                | AttributeId::CredentialRulesSupport
+                | AttributeId::NumberOfCredentialsSupportedPerUser
+                | AttributeId::WrongCodeEntryLimit
+                | AttributeId::UserCodeTemporaryDisableTime
+                | AttributeId::RequirePINforRemoteOperation
+        ))
+        .with_cmds(with!(
+            CommandId::LockDoor
+                | CommandId::UnlockDoor
+                | CommandId::UnlockWithTimeout
+                | CommandId::SetUser
+                | CommandId::GetUser
+                | CommandId::ClearUser
+                | CommandId::SetCredential
+                | CommandId::GetCredentialStatus
+                | CommandId::ClearCredential
+        ));
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn lock_state(&self, _ctx: &ReadContext) -> Result<Nullable<DlLockState>, Error> {
+        Ok(Nullable::new(DoorLockHandler::lock_state(self)))
+    }
+
+    fn lock_type(&self, _ctx: &ReadContext) -> Result<DlLockType, Error> {
+        Ok(self.lock_type)
+    }
+
+    fn actuator_enabled(&self, _ctx: &ReadContext) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn number_of_total_users_supported(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(MAX_USERS as _)
+    }
+
+    fn number_of_pin_users_supported(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(MAX_CREDENTIALS_PER_TYPE as _)
+    }
+
+    fn number_of_rfid_users_supported(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(MAX_CREDENTIALS_PER_TYPE as _)
+    }
+
+    fn max_pin_code_length(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(MAX_PIN_LEN as _)
+    }
+
+    fn min_pin_code_length(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(MIN_PIN_LEN as _)
+    }
+
+    fn max_rfid_code_length(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(MAX_RFID_LEN as _)
+    }
+
+    fn min_rfid_code_length(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(MIN_RFID_LEN as _)
+    }
+
+    fn credential_rules_support(&self, _ctx: &ReadContext) -> Result<DlCredentialRuleMask, Error> {
+        Ok(DlCredentialRuleMask::SINGLE)
+    }
+
+    fn number_of_credentials_supported_per_user(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(MAX_CREDENTIALS_PER_USER as _)
+    }
+
+    fn auto_relock_time(&self, _ctx: &ReadContext) -> Result<u32, Error> {
+        Ok(self.state.borrow().data.auto_relock_time)
+    }
+
+    fn operating_mode(&self, _ctx: &ReadContext) -> Result<OperatingModeEnum, Error> {
+        Ok(self.state.borrow().data.operating_mode)
+    }
+
+    fn supported_operating_modes(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<DlSupportedOperatingModes, Error> {
+        // The bits of this attribute are inverted, i.e. a cleared bit means that the mode is supported
+        Ok(DlSupportedOperatingModes::from_bits_truncate(
+            !SUPPORTED_OPERATING_MODES.bits(),
+        ))
+    }
+
+    fn wrong_code_entry_limit(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.state.borrow().data.wrong_code_entry_limit)
+    }
+
+    fn user_code_temporary_disable_time(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.state.borrow().data.user_code_temporary_disable_time)
+    }
+
+    fn require_pi_nfor_remote_operation(&self, _ctx: &ReadContext) -> Result<bool, Error> {
+        Ok(self.state.borrow().data.require_pin_for_remote_operation)
+    }
+
+    fn set_auto_relock_time(&self, ctx: &WriteContext, value: u32) -> Result<(), Error> {
+        self.update(ctx, |data| {
+            data.auto_relock_time = value;
+            Ok(())
+        })
+    }
+
+    fn set_operating_mode(
+        &self,
+        ctx: &WriteContext,
+        value: OperatingModeEnum,
+    ) -> Result<(), Error> {
+        self.update(ctx, |data| {
+            data.operating_mode = value;
+            Ok(())
+        })
+    }
+
+    fn set_wrong_code_entry_limit(&self, ctx: &WriteContext, value: u8) -> Result<(), Error> {
+        if value == 0 {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        self.update(ctx, |data| {
+            data.wrong_code_entry_limit = value;
+            Ok(())
+        })
+    }
+
+    fn set_user_code_temporary_disable_time(
+        &self,
+        ctx: &WriteContext,
+        value: u8,
+    ) -> Result<(), Error> {
+        if value == 0 {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        self.update(ctx, |data| {
+            data.user_code_temporary_disable_time = value;
+            Ok(())
+        })
+    }
+
+    fn set_require_pi_nfor_remote_operation(
+        &self,
+        ctx: &WriteContext,
+        value: bool,
+    ) -> Result<(), Error> {
+        self.update(ctx, |data| {
+            data.require_pin_for_remote_operation = value;
+            Ok(())
+        })
+    }
+
+    fn handle_lock_door(&self, ctx: &InvokeContext, request: LockDoorRequest) -> Result<(), Error> {
+        self.operate_remote(
+            ctx,
+            LockOperationTypeEnum::Lock,
+            request.pin_code()?.map(|pin_code| pin_code.0),
+            None,
+        )
+    }
+
+    fn handle_unlock_door(
+        &self,
+        ctx: &InvokeContext,
+        request: UnlockDoorRequest,
+    ) -> Result<(), Error> {
+        self.operate_remote(
+            ctx,
+            LockOperationTypeEnum::Unlock,
+            request.pin_code()?.map(|pin_code| pin_code.0),
+            None,
+        )
+    }
+
+    fn handle_unlock_with_timeout(
+        &self,
+        ctx: &InvokeContext,
+        request: UnlockWithTimeoutRequest,
+    ) -> Result<(), Error> {
+        self.operate_remote(
+            ctx,
+            LockOperationTypeEnum::Unlock,
+            request.pin_code()?.map(|pin_code| pin_code.0),
+            Some(request.timeout()?),
+        )
+    }
+
+    fn handle_set_week_day_schedule(
+        &self,
+        _ctx: &InvokeContext,
+        _request: SetWeekDayScheduleRequest,
+    ) -> Result<(), Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_get_week_day_schedule<P: TLVBuilderParent>(
+        &self,
+        _ctx: &InvokeContext,
+        _request: GetWeekDayScheduleRequest,
+        _response: GetWeekDayScheduleResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_clear_week_day_schedule(
+        &self,
+        _ctx: &InvokeContext,
+        _request: ClearWeekDayScheduleRequest,
+    ) -> Result<(), Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_set_year_day_schedule(
+        &self,
+        _ctx: &InvokeContext,
+        _request: SetYearDayScheduleRequest,
+    ) -> Result<(), Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_get_year_day_schedule<P: TLVBuilderParent>(
+        &self,
+        _ctx: &InvokeContext,
+        _request: GetYearDayScheduleRequest,
+        _response: GetYearDayScheduleResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_clear_year_day_schedule(
+        &self,
+        _ctx: &InvokeContext,
+        _request: ClearYearDayScheduleRequest,
+    ) -> Result<(), Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_set_holiday_schedule(
+        &self,
+        _ctx: &InvokeContext,
+        _request: SetHolidayScheduleRequest,
+    ) -> Result<(), Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_get_holiday_schedule<P: TLVBuilderParent>(
+        &self,
+        _ctx: &InvokeContext,
+        _request: GetHolidayScheduleRequest,
+        _response: GetHolidayScheduleResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_clear_holiday_schedule(
+        &self,
+        _ctx: &InvokeContext,
+        _request: ClearHolidayScheduleRequest,
+    ) -> Result<(), Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_set_user(&self, ctx: &InvokeContext, request: SetUserRequest) -> Result<(), Error> {
+        let (fab_idx, _) = accessor(ctx)?;
+
+        let user_name: Nullable<Utf8Str> = request.user_name()?;
+
+        let status = self.set_user(
+            fab_idx,
+            request.operation_type()?,
+            request.user_index()?,
+            user_name.into_option(),
+            request.user_unique_id()?.into_option(),
+            request.user_status()?.into_option(),
+            request.user_type()?.into_option(),
+            request.credential_rule()?.into_option(),
+        );
+
+        ctx.notify_changed();
+
+        dl_result(status)
+    }
+
+    fn handle_get_user<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: GetUserRequest,
+        response: GetUserResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let _ = accessor(ctx)?;
+
+        let index = request.user_index()?;
+
+        if !(1..=MAX_USERS as u16).contains(&index) {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        let state = self.state.borrow();
+
+        let response = response.user_index(index)?;
+
+        let next_user_index = Nullable::new(state.data.next_user_index(index, true));
+
+        let Some(user) = state.data.user(index) else {
+            return response
+                .user_name(Nullable::none())?
+                .user_unique_id(Nullable::none())?
+                .user_status(Nullable::none())?
+                .user_type(Nullable::none())?
+                .credential_rule(Nullable::none())?
+                .credentials()?
+                .null()?
+                .creator_fabric_index(Nullable::none())?
+                .last_modified_fabric_index(Nullable::none())?
+                .next_user_index(next_user_index)?
+                .end();
+        };
+
+        let response = response
+            .user_name(Nullable::some(user.name.as_str()))?
+            .user_unique_id(Nullable::new(user.unique_id))?
+            .user_status(Nullable::some(user.status))?
+            .user_type(Nullable::some(user.user_type))?
+            .credential_rule(Nullable::some(user.credential_rule))?;
+
+        let response = if state.data.user_credentials(index).next().is_some() {
+            let mut credentials = response.credentials()?.non_null()?;
+
+            for cred in state.data.user_credentials(index) {
+                credentials = credentials
+                    .push()?
+                    .credential_type(cred.cred_type)?
+                    .credential_index(cred.index)?
+                    .end()?;
+            }
+
+            credentials.end()?
+        } else {
+            response.credentials()?.null()?
+        };
+
+        response
+            .creator_fabric_index(Nullable::some(user.creator_fab_idx))?
+            .last_modified_fabric_index(Nullable::some(user.modifier_fab_idx))?
+            .next_user_index(next_user_index)?
+            .end()
+    }
+
+    fn handle_clear_user(
+        &self,
+        ctx: &InvokeContext,
+        request: ClearUserRequest,
+    ) -> Result<(), Error> {
+        let _ = accessor(ctx)?;
+
+        let status = self.clear_user(request.user_index()?);
+
+        ctx.notify_changed();
+
+        dl_result(status)
+    }
+
+    fn handle_set_credential<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: SetCredentialRequest,
+        response: SetCredentialResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, _) = accessor(ctx)?;
+
+        let credential = request.credential()?;
+        let (cred_type, index) = (
+            credential.credential_type()?,
+            credential.credential_index()?,
+        );
+
+        let (status, user_index) = self.set_credential(
+            fab_idx,
+            request.operation_type()?,
+            cred_type,
+            index,
+            request.credential_data()?.0,
+            request.user_index()?.into_option(),
+            request.user_status()?.into_option(),
+            request.user_type()?.into_option(),
+        );
+
+        ctx.notify_changed();
+
+        let next_credential_index = self
+            .state
+            .borrow()
+            .data
+            .next_credential_index(cred_type, index, false);
+
+        response
+            .status(status)?
+            .user_index(Nullable::new(user_index))?
+            .next_credential_index(Nullable::new(next_credential_index))?
+            .end()
+    }
+
+    fn handle_get_credential_status<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: GetCredentialStatusRequest,
+        response: GetCredentialStatusResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let _ = accessor(ctx)?;
+
+        let credential = request.credential()?;
+        let (cred_type, index) = (
+            credential.credential_type()?,
+            credential.credential_index()?,
+        );
+
+        if !(1..=MAX_CREDENTIALS_PER_TYPE as u16).contains(&index) {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        let state = self.state.borrow();
+
+        let cred = state.data.credential(cred_type, index);
+
+        response
+            .credential_exists(cred.is_some())?
+            .user_index(Nullable::new(cred.map(|cred| cred.user_index)))?
+            .creator_fabric_index(Nullable::new(cred.map(|cred| cred.creator_fab_idx)))?
+            .last_modified_fabric_index(Nullable::new(cred.map(|cred| cred.modifier_fab_idx)))?
+            .next_credential_index(Nullable::new(
+                state.data.next_credential_index(cred_type, index, true),
+            ))?
+            .end()
+    }
+
+    fn handle_clear_credential(
+        &self,
+        ctx: &InvokeContext,
+        request: ClearCredentialRequest,
+    ) -> Result<(), Error> {
+        let _ = accessor(ctx)?;
+
+        let credential = match request.credential()?.into_option() {
+            Some(credential) => Some((
+                credential.credential_type()?,
+                credential.credential_index()?,
+            )),
+            None => None,
+        };
+
+        let status = self.clear_credential(credential);
+
+        ctx.notify_changed();
+
+        dl_result(status)
+    }
+
+    fn handle_unbolt_door(
+        &self,
+        _ctx: &InvokeContext,
+        _request: UnboltDoorRequest,
+    ) -> Result<(), Error> {
+        Err(ErrorCode::CommandNotFound.into())
+    }
+}
+
+/// Return the fabric index and the node ID of the accessing client.
+fn accessor(ctx: &InvokeContext) -> Result<(NonZeroU8, Option<u64>), Error> {
+    ctx.exchange().with_session(|sess| {
+        Ok((
+            NonZeroU8::new(sess.get_local_fabric_idx()).ok_or(ErrorCode::UnsupportedAccess)?,
+            sess.get_peer_node_id(),
+        ))
+    })
+}
+
+/// Map the status of a user or credential database operation to the result of a command.
+///
+/// Since `rs-matter` cannot report cluster-specific status codes yet, the statuses which have
+/// no Interaction Model equivalent (i.e. `Occupied` and `Duplicate`) are reported as a FAILURE.
+fn dl_result(status: DlStatus) -> Result<(), Error> {
+    match status {
+        DlStatus::Success => Ok(()),
+        DlStatus::InvalidField => Err(ErrorCode::InvalidCommand.into()),
+        DlStatus::ResourceExhausted => Err(ErrorCode::ResourceExhausted.into()),
+        DlStatus::NotFound => Err(ErrorCode::NotFound.into()),
+        DlStatus::Failure => Err(ErrorCode::Failure.into()),
+        _ => Err(ErrorCode::Duplicate.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::num::NonZeroU8;
+
+    use crate::data_model::objects::Dataver;
+    use crate::interaction_model::core::IMStatusCode;
+
+    use super::*;
+
+    const FAB1: NonZeroU8 = NonZeroU8::MIN;
+    const FAB2: NonZeroU8 = FAB1.saturating_add(1);
+
+    #[derive(Default)]
+    struct Hooks {
+        jammed: Cell<bool>,
+        actuated: Cell<Option<bool>>,
+        operation: Cell<Option<(LockOperationTypeEnum, Option<u16>)>>,
+        error: Cell<Option<OperationErrorEnum>>,
+        alarm: Cell<Option<AlarmCodeEnum>>,
+    }
+
+    impl DoorLockHooks for Hooks {
+        fn actuate(&self, lock: bool) -> bool {
+            self.actuated.set(Some(lock));
+            !self.jammed.get()
+        }
+
+        fn lock_operation(&self, info: &LockOperationInfo) {
+            self.operation.set(Some((info.operation, info.user_index)));
+        }
+
+        fn lock_operation_error(&self, _info: &LockOperationInfo, error: OperationErrorEnum) {
+            self.error.set(Some(error));
+        }
+
+        fn alarm(&self, code: AlarmCodeEnum) {
+            self.alarm.set(Some(code));
+        }
+    }
+
+    fn set_user(
+        handler: &DoorLockHandler,
+        fab_idx: NonZeroU8,
+        operation: DataOperationTypeEnum,
+        index: u16,
+        name: Option<&str>,
+    ) -> DlStatus {
+        handler.set_user(fab_idx, operation, index, name, None, None, None, None)
+    }
+
+    fn set_pin(
+        handler: &DoorLockHandler,
+        operation: DataOperationTypeEnum,
+        index: u16,
+        pin: &[u8],
+        user_index: Option<u16>,
+    ) -> (DlStatus, Option<u16>) {
+        handler.set_credential(
+            FAB1,
+            operation,
+            CredentialTypeEnum::PIN,
+            index,
+            pin,
+            user_index,
+            None,
+            None,
+        )
+    }
+
+    fn unlock_remote(
+        handler: &DoorLockHandler,
+        pin: Option<&[u8]>,
+    ) -> Result<(), OperationErrorEnum> {
+        handler.operate(
+            LockOperationTypeEnum::Unlock,
+            OperationSourceEnum::Remote,
+            pin.map(|pin| (CredentialTypeEnum::PIN, pin)),
+            Some(FAB1),
+            Some(1),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_users() {
+        let handler = DoorLockHandler::new(Dataver::new(0), DlLockType::DeadBolt);
+
+        let add = DataOperationTypeEnum::Add;
+        let modify = DataOperationTypeEnum::Modify;
+
+        assert_eq!(
+            set_user(&handler, FAB1, add, 1, Some("Alice")),
+            DlStatus::Success
+        );
+        assert_eq!(
+            set_user(&handler, FAB1, add, 1, Some("Bob")),
+            DlStatus::Occupied
+        );
+        assert_eq!(
+            set_user(&handler, FAB1, add, 0, None),
+            DlStatus::InvalidField
+        );
+        assert_eq!(
+            set_user(&handler, FAB1, add, 2, Some("A very long name")),
+            DlStatus::InvalidField
+        );
+        assert!(handler.changed());
+
+        // Only the creating fabric can rename the user
+        assert_eq!(
+            set_user(&handler, FAB2, modify, 1, Some("Bob")),
+            DlStatus::InvalidField
+        );
+        assert_eq!(
+            set_user(&handler, FAB1, modify, 1, Some("Bob")),
+            DlStatus::Success
+        );
+        assert_eq!(
+            set_user(&handler, FAB1, modify, 3, None),
+            DlStatus::InvalidField
+        );
+
+        {
+            let state = handler.state.borrow();
+            let user = unwrap!(state.data.user(1));
+            assert_eq!(user.name.as_str(), "Bob");
+            assert_eq!(user.status, UserStatusEnum::OccupiedEnabled);
+            assert_eq!(state.data.next_user_index(0, false), Some(2));
+            assert_eq!(state.data.next_user_index(0, true), Some(1));
+            assert_eq!(state.data.next_user_index(1, true), None);
+        }
+
+        assert_eq!(
+            set_pin(&handler, add, 1, b"1234", Some(1)),
+            (DlStatus::Success, Some(1))
+        );
+
+        // Clearing a user clears its credentials too
+        assert_eq!(handler.clear_user(1), DlStatus::Success);
+        assert!(handler.state.borrow().data.user(1).is_none());
+        assert!(handler.state.borrow().data.credentials.is_empty());
+
+        for index in 1..=MAX_USERS as u16 {
+            assert_eq!(
+                set_user(&handler, FAB1, add, index, None),
+                DlStatus::Success
+            );
+        }
+
+        assert_eq!(handler.clear_user(ALL_INDEXES), DlStatus::Success);
+        assert!(handler.state.borrow().data.users.is_empty());
+    }
+
+    #[test]
+    fn test_credentials() {
+        let handler = DoorLockHandler::new(Dataver::new(0), DlLockType::DeadBolt);
+
+        let add = DataOperationTypeEnum::Add;
+        let modify = DataOperationTypeEnum::Modify;
+
+        // Invalid PINs
+        assert_eq!(
+            set_pin(&handler, add, 1, b"123", None).0,
+            DlStatus::InvalidField
+        );
+        assert_eq!(
+            set_pin(&handler, add, 1, b"123456789", None).0,
+            DlStatus::InvalidField
+        );
+        assert_eq!(
+            set_pin(&handler, add, 1, b"12a4", None).0,
+            DlStatus::InvalidField
+        );
+
+        // Adding a credential without a user creates one
+        assert_eq!(
+            set_pin(&handler, add, 1, b"1234", None),
+            (DlStatus::Success, Some(1))
+        );
+        assert_eq!(
+            set_pin(&handler, add, 1, b"5678", None).0,
+            DlStatus::Occupied
+        );
+        assert_eq!(
+            set_pin(&handler, add, 2, b"1234", None).0,
+            DlStatus::Duplicate
+        );
+        assert_eq!(
+            set_pin(&handler, add, 2, b"5678", Some(1)),
+            (DlStatus::Success, Some(1))
+        );
+
+        // RFID credentials have their own index space
+        assert_eq!(
+            handler.set_credential(
+                FAB1,
+                add,
+                CredentialTypeEnum::RFID,
+                1,
+                b"\x01\x02\x03\x04\x05",
+                Some(1),
+                None,
+                None
+            ),
+            (DlStatus::Success, Some(1))
+        );
+
+        // Modifying requires the owning user
+        assert_eq!(
+            set_pin(&handler, modify, 2, b"4321", Some(2)).0,
+            DlStatus::InvalidField
+        );
+        assert_eq!(
+            set_pin(&handler, modify, 2, b"4321", Some(1)),
+            (DlStatus::Success, Some(1))
+        );
+
+        {
+            let state = handler.state.borrow();
+            assert_eq!(state.data.user_credentials(1).count(), 3);
+            assert_eq!(
+                state
+                    .data
+                    .next_credential_index(CredentialTypeEnum::PIN, 0, false),
+                Some(3)
+            );
+            assert!(state
+                .data
+                .find_credential(CredentialTypeEnum::PIN, b"4321")
+                .is_some());
+        }
+
+        // The user already has two PINs and one RFID credential
+        for index in 3..MAX_CREDENTIALS_PER_USER as u16 {
+            assert_eq!(
+                set_pin(
+                    &handler,
+                    add,
+                    index,
+                    &[b'0', b'0', b'0', b'0' + index as u8],
+                    Some(1)
+                )
+                .0,
+                DlStatus::Success
+            );
+        }
+        assert_eq!(
+            set_pin(&handler, add, 9, b"9999", Some(1)).0,
+            DlStatus::ResourceExhausted
+        );
+
+        // Clearing the credentials of a type keeps the user while it has other credentials
+        assert_eq!(
+            handler.clear_credential(Some((CredentialTypeEnum::PIN, ALL_INDEXES))),
+            DlStatus::Success
+        );
+        assert!(handler.state.borrow().data.user(1).is_some());
+
+        // ... and removes it with its last credential
+        assert_eq!(
+            handler.clear_credential(Some((CredentialTypeEnum::RFID, 1))),
+            DlStatus::Success
+        );
+        assert!(handler.state.borrow().data.users.is_empty());
+
+        assert_eq!(
+            set_pin(&handler, add, 1, b"1234", None).0,
+            DlStatus::Success
+        );
+        assert_eq!(handler.clear_credential(None), DlStatus::Success);
+        assert!(handler.state.borrow().data.credentials.is_empty());
+        assert!(handler.state.borrow().data.users.is_empty());
+    }
+
+    #[test]
+    fn test_operate() {
+        let hooks = Hooks::default();
+
+        let mut handler = DoorLockHandler::new(Dataver::new(0), DlLockType::DeadBolt);
+        handler.set_hooks(&hooks);
+
+        assert_eq!(handler.lock_state(), None);
+
+        assert_eq!(
+            set_pin(&handler, DataOperationTypeEnum::Add, 1, b"1234", None).0,
+            DlStatus::Success
+        );
+
+        // No PIN required by default for remote operations
+        assert_eq!(unlock_remote(&handler, None), Ok(()));
+        assert_eq!(hooks.actuated.take(), Some(false));
+        assert_eq!(
+            hooks.operation.take(),
+            Some((LockOperationTypeEnum::Unlock, None))
+        );
+        assert_eq!(handler.lock_state(), Some(DlLockState::Unlocked));
+
+        handler
+            .state
+            .borrow_mut()
+            .data
+            .require_pin_for_remote_operation = true;
+
+        assert_eq!(
+            unlock_remote(&handler, None),
+            Err(OperationErrorEnum::InvalidCredential)
+        );
+        assert_eq!(hooks.actuated.take(), None);
+
+        assert_eq!(unlock_remote(&handler, Some(b"1234")), Ok(()));
+        assert_eq!(
+            hooks.operation.take(),
+            Some((LockOperationTypeEnum::Unlock, Some(1)))
+        );
+
+        // Disabled users are denied
+        assert_eq!(
+            handler.set_user(
+                FAB1,
+                DataOperationTypeEnum::Modify,
+                1,
+                None,
+                None,
+                Some(UserStatusEnum::OccupiedDisabled),
+                None,
+                None
+            ),
+            DlStatus::Success
+        );
+        assert_eq!(
+            unlock_remote(&handler, Some(b"1234")),
+            Err(OperationErrorEnum::DisabledUserDenied)
+        );
+        assert_eq!(
+            hooks.error.take(),
+            Some(OperationErrorEnum::DisabledUserDenied)
+        );
+
+        // Operating mode restrictions
+        handler.state.borrow_mut().data.operating_mode = OperatingModeEnum::NoRemoteLockUnlock;
+        assert_eq!(
+            unlock_remote(&handler, Some(b"1234")),
+            Err(OperationErrorEnum::Restricted)
+        );
+        assert_eq!(handler.lock(OperationSourceEnum::Manual, None), Ok(()));
+        assert_eq!(handler.lock_state(), Some(DlLockState::Locked));
+
+        // Jammed lock
+        hooks.jammed.set(true);
+        assert_eq!(
+            handler.unlock(OperationSourceEnum::Manual, None),
+            Err(OperationErrorEnum::Unspecified)
+        );
+        assert_eq!(hooks.alarm.take(), Some(AlarmCodeEnum::LockJammed));
+        assert_eq!(handler.lock_state(), Some(DlLockState::NotFullyLocked));
+    }
+
+    #[test]
+    fn test_wrong_code_lockout() {
+        let hooks = Hooks::default();
+
+        let mut handler = DoorLockHandler::new(Dataver::new(0), DlLockType::DeadBolt);
+        handler.set_hooks(&hooks);
+
+        assert_eq!(
+            set_pin(&handler, DataOperationTypeEnum::Add, 1, b"1234", None).0,
+            DlStatus::Success
+        );
+
+        for _ in 0..2 {
+            assert_eq!(
+                unlock_remote(&handler, Some(b"0000")),
+                Err(OperationErrorEnum::InvalidCredential)
+            );
+            assert_eq!(hooks.alarm.take(), None);
+        }
+
+        // A correct PIN resets the counter
+        assert_eq!(unlock_remote(&handler, Some(b"1234")), Ok(()));
+
+        for _ in 0..3 {
+            assert_eq!(
+                unlock_remote(&handler, Some(b"0000")),
+                Err(OperationErrorEnum::InvalidCredential)
+            );
+        }
+        assert_eq!(hooks.alarm.take(), Some(AlarmCodeEnum::WrongCodeEntryLimit));
+
+        // The keypad is now temporarily disabled, even for the correct PIN
+        assert_eq!(
+            unlock_remote(&handler, Some(b"1234")),
+            Err(OperationErrorEnum::Restricted)
+        );
+
+        handler.state.borrow_mut().lockout_until = None;
+        assert_eq!(unlock_remote(&handler, Some(b"1234")), Ok(()));
+    }
+
+    #[test]
+    fn test_auto_relock() {
+        let hooks = Hooks::default();
+
+        let mut handler = DoorLockHandler::new(Dataver::new(0), DlLockType::DeadBolt);
+        handler.set_hooks(&hooks);
+
+        assert_eq!(handler.unlock(OperationSourceEnum::Manual, None), Ok(()));
+        assert!(handler.state.borrow().relock_at.is_none());
+
+        handler.state.borrow_mut().data.auto_relock_time = 5;
+
+        assert_eq!(handler.unlock(OperationSourceEnum::Manual, None), Ok(()));
+        assert!(handler.state.borrow().relock_at.is_some());
+
+        handler.relock();
+        assert_eq!(handler.lock_state(), Some(DlLockState::Locked));
+        assert_eq!(hooks.actuated.take(), Some(true));
+        assert!(handler.state.borrow().relock_at.is_none());
+
+        // No auto-relocking in Passage mode, unless requested with `UnlockWithTimeout`
+        handler.state.borrow_mut().data.operating_mode = OperatingModeEnum::Passage;
+
+        assert_eq!(handler.unlock(OperationSourceEnum::Manual, None), Ok(()));
+        assert!(handler.state.borrow().relock_at.is_none());
+
+        assert_eq!(
+            handler.operate(
+                LockOperationTypeEnum::Unlock,
+                OperationSourceEnum::Remote,
+                None,
+                Some(FAB1),
+                Some(1),
+                Some(10)
+            ),
+            Ok(())
+        );
+        assert!(handler.state.borrow().relock_at.is_some());
+
+        // Locking cancels the timer
+        assert_eq!(handler.lock(OperationSourceEnum::Manual, None), Ok(()));
+        assert!(handler.state.borrow().relock_at.is_none());
+    }
+
+    #[test]
+    fn test_dl_result() {
+        let status = |dl_status| dl_result(dl_status).map_err(IMStatusCode::from);
+
+        assert_eq!(status(DlStatus::Success), Ok(()));
+        assert_eq!(status(DlStatus::NotFound), Err(IMStatusCode::NotFound));
+        assert_eq!(
+            status(DlStatus::InvalidField),
+            Err(IMStatusCode::InvalidCommand)
+        );
+        assert_eq!(
+            status(DlStatus::ResourceExhausted),
+            Err(IMStatusCode::ResourceExhausted)
+        );
+        assert_eq!(status(DlStatus::Failure), Err(IMStatusCode::Failure));
+    }
+
+    #[test]
+    fn test_persist() {
+        let handler = DoorLockHandler::new(Dataver::new(0), DlLockType::DeadBolt);
+
+        assert_eq!(
+            set_user(&handler, FAB1, DataOperationTypeEnum::Add, 2, Some("Alice")),
+            DlStatus::Success
+        );
+        assert_eq!(
+            set_pin(&handler, DataOperationTypeEnum::Add, 1, b"1234", Some(2)).0,
+            DlStatus::Success
+        );
+        handler.state.borrow_mut().data.auto_relock_time = 30;
+
+        let mut buf = [0; 1024];
+        let data = unwrap!(unwrap!(handler.store(&mut buf)));
+        assert!(!handler.changed());
+
+        let handler2 = DoorLockHandler::new(Dataver::new(0), DlLockType::DeadBolt);
+        unwrap!(handler2.load(data));
+
+        assert_eq!(handler.state.borrow().data, handler2.state.borrow().data);
+        assert_eq!(handler2.state.borrow().data.auto_relock_time, 30);
+    }
+}
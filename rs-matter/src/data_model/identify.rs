@@ -0,0 +1,322 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Identify cluster and its handler.
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::{Duration, Timer};
+
+use crate::error::Error;
+use crate::utils::cell::RefCell;
+use crate::utils::sync::Notification;
+use crate::with;
+
+use super::objects::{Cluster, Dataver, InvokeContext, ReadContext, WriteContext};
+
+pub use crate::data_model::clusters::identify::*;
+
+/// The resolution of the `IdentifyTime` attribute, as per the Matter spec.
+const TIMER_TICK_MS: u64 = 1000;
+
+/// A trait for the hardware (or the driver) of a device which can identify itself,
+/// i.e. by blinking a LED or by emitting a sound.
+pub trait IdentifyHooks {
+    /// Start or stop identification.
+    ///
+    /// Called with `true` when the `IdentifyTime` attribute becomes non-zero,
+    /// and with `false` when it goes back to zero (either on expiry, or because it was set to zero).
+    fn identify(&self, active: bool);
+
+    /// Run the effect requested with a `TriggerEffect` command.
+    ///
+    /// `EffectIdentifierEnum::FinishEffect` asks the device to complete the current effect sequence before terminating it,
+    /// while `EffectIdentifierEnum::StopEffect` asks the device to terminate the current effect as soon as possible.
+    ///
+    /// The default implementation does nothing.
+    fn trigger_effect(&self, effect: EffectIdentifierEnum, variant: EffectVariantEnum) {
+        let _ = (effect, variant);
+    }
+}
+
+impl<T> IdentifyHooks for &T
+where
+    T: IdentifyHooks,
+{
+    fn identify(&self, active: bool) {
+        (*self).identify(active)
+    }
+
+    fn trigger_effect(&self, effect: EffectIdentifierEnum, variant: EffectVariantEnum) {
+        (*self).trigger_effect(effect, variant)
+    }
+}
+
+/// A no-op implementation for devices that only need to observe the state of the cluster
+/// with `IdentifyHandler::identify_time`.
+impl IdentifyHooks for () {
+    fn identify(&self, _active: bool) {}
+}
+
+/// A handler for the Identify Matter cluster.
+///
+/// The handler drives the device via the `IdentifyHooks` trait.
+///
+/// The `IdentifyTime` countdown is only running while the `IdentifyHandler::run` future is polled.
+pub struct IdentifyHandler<'a> {
+    dataver: Dataver,
+    identify_type: IdentifyTypeEnum,
+    identify_time: RefCell<u16>,
+    hooks: &'a dyn IdentifyHooks,
+    timer_changed: Notification<NoopRawMutex>,
+}
+
+impl<'a> IdentifyHandler<'a> {
+    /// Creates a new instance of `IdentifyHandler` with the given `Dataver` and identification type.
+    ///
+    /// No hooks are called until `IdentifyHandler::set_hooks` is used.
+    pub const fn new(dataver: Dataver, identify_type: IdentifyTypeEnum) -> Self {
+        Self {
+            dataver,
+            identify_type,
+            identify_time: RefCell::new(0),
+            hooks: &(),
+            timer_changed: Notification::new(),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Set the hooks which drive the hardware of the device.
+    pub fn set_hooks(&mut self, hooks: &'a dyn IdentifyHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Return the remaining identification time, in seconds.
+    pub fn identify_time(&self) -> u16 {
+        *self.identify_time.borrow()
+    }
+
+    /// Return `true` if the device is currently identifying itself.
+    pub fn is_identifying(&self) -> bool {
+        self.identify_time() > 0
+    }
+
+    /// Start identification for the given number of seconds, or stop it if `secs` is zero.
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn identify(&self, secs: u16) {
+        let prev = self.identify_time.replace(secs);
+
+        self.dataver.changed();
+        self.timer_changed.notify();
+
+        if (prev > 0) != (secs > 0) {
+            self.hooks.identify(secs > 0);
+        }
+    }
+
+    /// Run the given effect.
+    ///
+    /// If the device is currently identifying itself, `EffectIdentifierEnum::StopEffect` also stops identification,
+    /// while `EffectIdentifierEnum::FinishEffect` lets the current second of the identification complete.
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn trigger_effect(&self, effect: EffectIdentifierEnum, variant: EffectVariantEnum) {
+        self.hooks.trigger_effect(effect, variant);
+
+        if self.is_identifying() {
+            match effect {
+                EffectIdentifierEnum::StopEffect => self.identify(0),
+                EffectIdentifierEnum::FinishEffect => self.identify(1),
+                _ => (),
+            }
+        }
+    }
+
+    /// Run the `IdentifyTime` countdown.
+    ///
+    /// # Arguments
+    /// - `notify`: A callback which is called whenever the countdown changes the state of the cluster,
+    ///   and which is expected to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub async fn run<F>(&self, notify: F) -> Result<(), Error>
+    where
+        F: Fn(),
+    {
+        loop {
+            if self.is_identifying() {
+                let tick = Timer::after(Duration::from_millis(TIMER_TICK_MS));
+
+                if let Either::First(_) = select(tick, self.timer_changed.wait()).await {
+                    self.tick();
+                    notify();
+                }
+            } else {
+                self.timer_changed.wait().await;
+            }
+        }
+    }
+
+    /// Count down the `IdentifyTime` attribute by one second.
+    fn tick(&self) {
+        let secs = self.identify_time();
+
+        if secs > 0 {
+            self.identify(secs - 1);
+        }
+    }
+}
+
+impl ClusterHandler for IdentifyHandler<'_> {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(4)
+        .with_attrs(with!(required))
+        .with_cmds(with!(all));
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn identify_time(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(IdentifyHandler::identify_time(self))
+    }
+
+    fn identify_type(&self, _ctx: &ReadContext) -> Result<IdentifyTypeEnum, Error> {
+        Ok(self.identify_type)
+    }
+
+    fn set_identify_time(&self, ctx: &WriteContext, value: u16) -> Result<(), Error> {
+        self.identify(value);
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_identify(&self, ctx: &InvokeContext, request: IdentifyRequest) -> Result<(), Error> {
+        self.identify(request.identify_time()?);
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_trigger_effect(
+        &self,
+        ctx: &InvokeContext,
+        request: TriggerEffectRequest,
+    ) -> Result<(), Error> {
+        self.trigger_effect(request.effect_identifier()?, request.effect_variant()?);
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::data_model::objects::Dataver;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Hooks {
+        active: Cell<Option<bool>>,
+        effect: Cell<Option<EffectIdentifierEnum>>,
+    }
+
+    impl IdentifyHooks for Hooks {
+        fn identify(&self, active: bool) {
+            self.active.set(Some(active));
+        }
+
+        fn trigger_effect(&self, effect: EffectIdentifierEnum, _variant: EffectVariantEnum) {
+            self.effect.set(Some(effect));
+        }
+    }
+
+    #[test]
+    fn test_countdown() {
+        let hooks = Hooks::default();
+
+        let mut handler = IdentifyHandler::new(Dataver::new(0), IdentifyTypeEnum::LightOutput);
+        handler.set_hooks(&hooks);
+
+        handler.identify(2);
+        assert_eq!(hooks.active.take(), Some(true));
+        assert!(handler.is_identifying());
+
+        handler.tick();
+        assert_eq!(handler.identify_time(), 1);
+        assert_eq!(hooks.active.take(), None);
+
+        handler.tick();
+        assert_eq!(handler.identify_time(), 0);
+        assert_eq!(hooks.active.take(), Some(false));
+
+        handler.tick();
+        assert_eq!(handler.identify_time(), 0);
+        assert_eq!(hooks.active.take(), None);
+
+        // Restarting while identifying does not call the hooks again
+        handler.identify(5);
+        handler.identify(10);
+        assert_eq!(hooks.active.take(), Some(true));
+        assert_eq!(handler.identify_time(), 10);
+
+        handler.identify(0);
+        assert_eq!(hooks.active.take(), Some(false));
+    }
+
+    #[test]
+    fn test_trigger_effect() {
+        let hooks = Hooks::default();
+
+        let mut handler = IdentifyHandler::new(Dataver::new(0), IdentifyTypeEnum::LightOutput);
+        handler.set_hooks(&hooks);
+
+        handler.trigger_effect(EffectIdentifierEnum::Blink, EffectVariantEnum::Default);
+        assert_eq!(hooks.effect.take(), Some(EffectIdentifierEnum::Blink));
+        assert!(!handler.is_identifying());
+
+        handler.identify(10);
+        handler.trigger_effect(
+            EffectIdentifierEnum::FinishEffect,
+            EffectVariantEnum::Default,
+        );
+        assert_eq!(
+            hooks.effect.take(),
+            Some(EffectIdentifierEnum::FinishEffect)
+        );
+        assert_eq!(handler.identify_time(), 1);
+
+        handler.trigger_effect(EffectIdentifierEnum::StopEffect, EffectVariantEnum::Default);
+        assert_eq!(hooks.effect.take(), Some(EffectIdentifierEnum::StopEffect));
+        assert_eq!(handler.identify_time(), 0);
+        assert_eq!(hooks.active.take(), Some(false));
+    }
+}
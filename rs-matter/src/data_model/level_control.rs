@@ -0,0 +1,1243 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Level Control cluster and its handler.
+//!
+//! The handler implements the OnOff and Lighting features of the cluster, i.e. it is suitable
+//! for dimmable lights, where it is coupled with the On/Off cluster of the same endpoint.
+
+use core::cell::Cell;
+
+use embassy_futures::select::select;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{FromTLV, Nullable, TLVElement, TLVTag, TLVWrite, ToTLV};
+use crate::utils::cell::RefCell;
+use crate::utils::storage::WriteBuf;
+use crate::utils::sync::Notification;
+use crate::with;
+
+use super::objects::{Cluster, Dataver, InvokeContext, ReadContext, WriteContext};
+use super::on_off::{LevelControlCoupling, OnOffHandler};
+use super::scenes::{SceneAttributeValue, SceneAttributeValues, SceneExtension};
+
+pub use crate::data_model::clusters::level_control::*;
+
+/// The lowest minimum level of a device supporting the Lighting feature, as per the Matter spec.
+pub const MIN_LEVEL: u8 = 1;
+/// The highest maximum level of a device supporting the Lighting feature, as per the Matter spec.
+pub const MAX_LEVEL: u8 = 254;
+
+/// A trait for the hardware (or the driver - i.e. PWM) of a device with a controllable level.
+///
+/// Note that the level is reported regardless of the state of the On/Off cluster,
+/// i.e. the device is expected to be dark while the On/Off cluster (see `OnOffHooks`) reports it as off.
+pub trait LevelControlHooks {
+    /// Set the output level of the device, in the range of the minimum and maximum levels of the handler
+    /// (see `LevelControlHandler::new_with_levels`).
+    fn set_level(&self, level: u8);
+}
+
+impl<T> LevelControlHooks for &T
+where
+    T: LevelControlHooks,
+{
+    fn set_level(&self, level: u8) {
+        (*self).set_level(level)
+    }
+}
+
+/// A no-op implementation for devices that only need to observe the level with `LevelControlHandler::get`.
+impl LevelControlHooks for () {
+    fn set_level(&self, _level: u8) {}
+}
+
+/// The coupling of the Level Control cluster with a Color Control cluster on the same endpoint,
+/// used when the `CoupleColorTempToLevel` bit of the `Options` attribute is set.
+pub trait ColorTempCoupling {
+    /// Called whenever the current level changes, so that the color temperature can follow it.
+    fn level_changed(&self, level: u8);
+}
+
+/// A transition of the level which is in progress.
+#[derive(Clone)]
+struct Transition {
+    from: u8,
+    to: u8,
+    start: Instant,
+    duration: Duration,
+    /// Switch the device off once the transition completes
+    off_at_end: bool,
+    /// Restore the `CurrentLevel` attribute to this level once the transition completes
+    restore_level: Option<u8>,
+}
+
+/// The state of the Level Control cluster.
+struct LevelState {
+    current_level: Option<u8>,
+    remaining_time: u16,
+    options: OptionsBitmap,
+    on_off_transition_time: u16,
+    on_level: Option<u8>,
+    on_transition_time: Option<u16>,
+    off_transition_time: Option<u16>,
+    default_move_rate: Option<u8>,
+    start_up_current_level: Option<u8>,
+    transition: Option<Transition>,
+    changed: bool,
+}
+
+impl LevelState {
+    const fn new(max_level: u8) -> Self {
+        Self {
+            current_level: Some(max_level),
+            remaining_time: 0,
+            options: OptionsBitmap::empty(),
+            on_off_transition_time: 0,
+            on_level: None,
+            on_transition_time: None,
+            off_transition_time: None,
+            default_move_rate: None,
+            start_up_current_level: None,
+            transition: None,
+            changed: false,
+        }
+    }
+
+    fn current_level(&self, min_level: u8) -> u8 {
+        self.current_level.unwrap_or(min_level)
+    }
+
+    /// Return `true` if the device is being switched off by a transition to the minimum level.
+    fn switching_off(&self) -> bool {
+        self.transition
+            .as_ref()
+            .map(|transition| transition.off_at_end)
+            .unwrap_or(false)
+    }
+}
+
+/// A handler for the Level Control Matter cluster, implementing the OnOff and Lighting features.
+///
+/// The handler drives the device via the `LevelControlHooks` trait. The level transitions are only running
+/// while the `LevelControlHandler::run` future is polled.
+///
+/// When the handler is used together with an `OnOffHandler` (see `LevelControlHandler::set_on_off`), the latter
+/// should be coupled with the former via `OnOffHandler::set_level_control`, so that switching the device on and off
+/// follows the "OnOff-with-level" dependency rules of the Matter spec.
+///
+/// The `CurrentLevel` attribute, as well as all writable attributes should be persisted
+/// (see `LevelControlHandler::load` and `LevelControlHandler::store`), so that the `StartUpCurrentLevel`
+/// behavior can be applied when the device boots.
+pub struct LevelControlHandler<'a> {
+    dataver: Dataver,
+    state: RefCell<LevelState>,
+    min_level: u8,
+    max_level: u8,
+    hooks: &'a dyn LevelControlHooks,
+    on_off: Option<&'a OnOffHandler<'a>>,
+    color_control: Cell<Option<&'a dyn ColorTempCoupling>>,
+    transition_changed: Notification<NoopRawMutex>,
+    persist_state_changed: Notification<NoopRawMutex>,
+}
+
+impl<'a> LevelControlHandler<'a> {
+    /// Creates a new instance of `LevelControlHandler` with the given `Dataver`.
+    ///
+    /// The level range of the device is `MIN_LEVEL..=MAX_LEVEL`.
+    /// The device is initially at its maximum level, and no hooks are called.
+    pub const fn new(dataver: Dataver) -> Self {
+        Self::new_with_levels(dataver, MIN_LEVEL, MAX_LEVEL)
+    }
+
+    /// Creates a new instance of `LevelControlHandler` with the given `Dataver`
+    /// and the given level range of the device (the `MinLevel` and `MaxLevel` attributes).
+    ///
+    /// The device is initially at its maximum level, and no hooks are called.
+    ///
+    /// # Panics
+    /// If the range is empty or not within `MIN_LEVEL..=MAX_LEVEL`.
+    pub const fn new_with_levels(dataver: Dataver, min_level: u8, max_level: u8) -> Self {
+        // `core::assert!`, as the `defmt` one cannot be used in `const` contexts
+        core::assert!(MIN_LEVEL <= min_level && min_level < max_level && max_level <= MAX_LEVEL);
+
+        Self {
+            dataver,
+            state: RefCell::new(LevelState::new(max_level)),
+            min_level,
+            max_level,
+            hooks: &(),
+            on_off: None,
+            color_control: Cell::new(None),
+            transition_changed: Notification::new(),
+            persist_state_changed: Notification::new(),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Set the hooks which drive the hardware of the device.
+    ///
+    /// Should be called before `LevelControlHandler::load`, so that the `StartUpCurrentLevel` behavior
+    /// is applied to the hardware.
+    pub fn set_hooks(&mut self, hooks: &'a dyn LevelControlHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Set the On/Off cluster handler of the same endpoint.
+    pub fn set_on_off(&mut self, on_off: &'a OnOffHandler<'a>) {
+        self.on_off = Some(on_off);
+    }
+
+    /// Couple the handler with the Color Control cluster handler of the same endpoint.
+    pub fn set_color_control(&self, color_control: &'a dyn ColorTempCoupling) {
+        self.color_control.set(Some(color_control));
+    }
+
+    /// Return the current level.
+    pub fn get(&self) -> Option<u8> {
+        self.state.borrow().current_level
+    }
+
+    /// Set the current level, stopping the transition in progress, if any.
+    ///
+    /// Suitable for reflecting level changes triggered locally (i.e. by the HAL).
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn set(&self, level: u8) {
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.transition = None;
+            state.remaining_time = 0;
+        }
+
+        self.apply_level(level.clamp(self.min_level, self.max_level));
+        self.level_settled();
+    }
+
+    /// Run the level transitions.
+    ///
+    /// # Arguments
+    /// - `notify`: A callback which is called whenever the transitions change the state of the cluster,
+    ///   and which is expected to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub async fn run<F>(&self, notify: F) -> Result<(), Error>
+    where
+        F: Fn(),
+    {
+        loop {
+            if self.state.borrow().transition.is_some() {
+                let next = self.step(Instant::now());
+
+                notify();
+
+                if let Some(next) = next {
+                    select(Timer::at(next), self.transition_changed.wait()).await;
+                    continue;
+                }
+            }
+
+            self.transition_changed.wait().await;
+        }
+    }
+
+    /// Load the persisted `CurrentLevel` and writable attributes from a byte slice,
+    /// and apply the `StartUpCurrentLevel` behavior.
+    ///
+    /// Should be called once, when the device boots.
+    ///
+    /// # Arguments
+    /// - `data`: The byte slice to load the state from
+    pub fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVElement::new(data);
+        let seq = root.structure()?;
+
+        let level = {
+            let mut state = self.state.borrow_mut();
+
+            state.current_level = Nullable::<u8>::from_tlv(&seq.ctx(0)?)?.into_option();
+            state.start_up_current_level = Nullable::<u8>::from_tlv(&seq.ctx(1)?)?.into_option();
+            state.on_level = Nullable::<u8>::from_tlv(&seq.ctx(2)?)?.into_option();
+            state.options = OptionsBitmap::from_bits_truncate(seq.ctx(3)?.u8()?);
+            state.on_off_transition_time = seq.ctx(4)?.u16()?;
+            state.on_transition_time = Nullable::<u16>::from_tlv(&seq.ctx(5)?)?.into_option();
+            state.off_transition_time = Nullable::<u16>::from_tlv(&seq.ctx(6)?)?.into_option();
+            state.default_move_rate = Nullable::<u8>::from_tlv(&seq.ctx(7)?)?.into_option();
+
+            let level = match state.start_up_current_level {
+                None => state.current_level(self.min_level),
+                Some(0) => self.min_level,
+                Some(level) => level,
+            }
+            .clamp(self.min_level, self.max_level);
+
+            state.changed = state.current_level != Some(level);
+            state.current_level = Some(level);
+            state.transition = None;
+
+            level
+        };
+
+        self.hooks.set_level(level);
+
+        self.dataver.changed();
+
+        Ok(())
+    }
+
+    /// Store the `CurrentLevel` and writable attributes into a byte slice.
+    ///
+    /// # Arguments
+    /// - `buf`: The byte slice to store the state into
+    ///
+    /// Returns `Ok(None)` if the state has not changed, `Ok(Some(data))` if the state has changed
+    /// where `data` is the sub-slice of the buffer that contains the data to be persisted
+    pub fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        let mut state = self.state.borrow_mut();
+
+        if !state.changed {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        wb.start_struct(&TLVTag::Anonymous)?;
+        Nullable::new(state.current_level).to_tlv(&TLVTag::Context(0), &mut wb)?;
+        Nullable::new(state.start_up_current_level).to_tlv(&TLVTag::Context(1), &mut wb)?;
+        Nullable::new(state.on_level).to_tlv(&TLVTag::Context(2), &mut wb)?;
+        wb.u8(&TLVTag::Context(3), state.options.bits())?;
+        wb.u16(&TLVTag::Context(4), state.on_off_transition_time)?;
+        Nullable::new(state.on_transition_time).to_tlv(&TLVTag::Context(5), &mut wb)?;
+        Nullable::new(state.off_transition_time).to_tlv(&TLVTag::Context(6), &mut wb)?;
+        Nullable::new(state.default_move_rate).to_tlv(&TLVTag::Context(7), &mut wb)?;
+        wb.end_container()?;
+
+        state.changed = false;
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    /// Return `true` if the state has changed and needs to be persisted.
+    pub fn changed(&self) -> bool {
+        self.state.borrow().changed
+    }
+
+    /// Wait for the state to be changed in a way that requires persisting.
+    pub async fn wait_persist(&self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+
+            self.persist_state_changed.wait().await;
+        }
+    }
+
+    /// Set the current level and call the hooks, if the level had changed.
+    fn apply_level(&self, level: u8) {
+        let couple_color_temp = {
+            let mut state = self.state.borrow_mut();
+
+            if state.current_level == Some(level) {
+                return;
+            }
+
+            state.current_level = Some(level);
+
+            state
+                .options
+                .contains(OptionsBitmap::COUPLE_COLOR_TEMP_TO_LEVEL)
+        };
+
+        self.hooks.set_level(level);
+
+        if couple_color_temp {
+            if let Some(color_control) = self.color_control.get() {
+                color_control.level_changed(level);
+            }
+        }
+
+        self.dataver.changed();
+    }
+
+    /// Mark the state as changed (and therefore to be persisted), once the level had settled.
+    fn level_settled(&self) {
+        self.state.borrow_mut().changed = true;
+        self.persist_state_changed.notify();
+    }
+
+    /// Return `true` if a command without the `WithOnOff` suffix should be executed,
+    /// as per the `Options` attribute and the `OptionsMask` and `OptionsOverride` fields of the command.
+    fn should_execute(&self, mask: OptionsBitmap, overrides: OptionsBitmap) -> bool {
+        let Some(on_off) = self.on_off else {
+            return true;
+        };
+
+        if on_off.get() {
+            return true;
+        }
+
+        let options = (self.state.borrow().options & !mask) | (overrides & mask);
+
+        options.contains(OptionsBitmap::EXECUTE_IF_OFF)
+    }
+
+    /// Prepare a transition of a `WithOnOff` command, by switching the device on, if the level is about to go up.
+    ///
+    /// Return the level the transition should start from.
+    fn prepare(&self, up: bool, with_on_off: bool) -> u8 {
+        if with_on_off && up {
+            if let Some(on_off) = self.on_off {
+                if !on_off.get() {
+                    self.apply_level(self.min_level);
+                }
+
+                on_off.set(true);
+            }
+        }
+
+        self.state.borrow().current_level(self.min_level)
+    }
+
+    /// Start a new transition, replacing the one in progress, if any.
+    fn start(
+        &self,
+        from: u8,
+        to: u8,
+        duration: Duration,
+        off_at_end: bool,
+        restore_level: Option<u8>,
+    ) {
+        let now = Instant::now();
+
+        self.state.borrow_mut().transition = Some(Transition {
+            from,
+            to,
+            start: now,
+            duration,
+            off_at_end,
+            restore_level,
+        });
+
+        self.step(now);
+
+        self.transition_changed.notify();
+    }
+
+    /// Advance the transition in progress to the given instant.
+    ///
+    /// Return the instant when the level should be advanced next, or `None` if the transition had completed.
+    fn step(&self, now: Instant) -> Option<Instant> {
+        let (level, next, completed) = {
+            let mut state = self.state.borrow_mut();
+
+            let transition = state.transition.clone()?;
+            let elapsed = now.saturating_duration_since(transition.start);
+            let delta = transition.to as i32 - transition.from as i32;
+
+            if elapsed >= transition.duration || delta == 0 {
+                state.transition = None;
+                state.remaining_time = 0;
+
+                (transition.to, None, Some(transition))
+            } else {
+                let total = transition.duration.as_micros();
+                let steps = (delta.unsigned_abs() as u64 * elapsed.as_micros() / total) as i32;
+
+                let level = (transition.from as i32 + steps * delta.signum()) as u8;
+                let next = transition.start
+                    + Duration::from_micros(
+                        total * (steps as u64 + 1) / delta.unsigned_abs() as u64,
+                    );
+
+                let remaining = (transition.duration - elapsed).as_millis().div_ceil(100);
+                state.remaining_time = remaining.min(u16::MAX as _) as _;
+
+                (level, Some(next), None)
+            }
+        };
+
+        self.apply_level(level);
+        self.dataver.changed();
+
+        if let Some(transition) = completed {
+            if transition.off_at_end {
+                if let Some(on_off) = self.on_off {
+                    on_off.set(false);
+                }
+            }
+
+            if let Some(level) = transition.restore_level {
+                // Not applied to the hardware, as the device is off by now
+                self.state.borrow_mut().current_level = Some(level);
+            }
+
+            self.level_settled();
+        }
+
+        next
+    }
+
+    fn move_to_level(
+        &self,
+        level: u8,
+        transition_time: Option<u16>,
+        with_on_off: bool,
+    ) -> Result<(), Error> {
+        if level > MAX_LEVEL {
+            return Err(ErrorCode::ConstraintError.into());
+        }
+
+        let to = level.clamp(self.min_level, self.max_level);
+        let duration = transition_time
+            .map(tenths)
+            .unwrap_or_else(|| tenths(self.state.borrow().on_off_transition_time));
+
+        let from = self.prepare(to > self.min_level, with_on_off);
+
+        self.start(
+            from,
+            to,
+            duration,
+            with_on_off && to == self.min_level,
+            None,
+        );
+
+        Ok(())
+    }
+
+    fn move_(&self, mode: MoveModeEnum, rate: Option<u8>, with_on_off: bool) -> Result<(), Error> {
+        if rate == Some(0) {
+            return Err(ErrorCode::ConstraintError.into());
+        }
+
+        let rate = rate
+            .or(self.state.borrow().default_move_rate)
+            .filter(|rate| *rate > 0);
+
+        let (up, to) = match mode {
+            MoveModeEnum::Up => (true, self.max_level),
+            MoveModeEnum::Down => (false, self.min_level),
+        };
+
+        let from = self.prepare(up, with_on_off);
+
+        // Without a rate, move as fast as possible
+        let duration = rate
+            .map(|rate| Duration::from_micros(from.abs_diff(to) as u64 * 1_000_000 / rate as u64))
+            .unwrap_or_default();
+
+        self.start(
+            from,
+            to,
+            duration,
+            with_on_off && to == self.min_level,
+            None,
+        );
+
+        Ok(())
+    }
+
+    fn step_(
+        &self,
+        mode: StepModeEnum,
+        size: u8,
+        transition_time: Option<u16>,
+        with_on_off: bool,
+    ) -> Result<(), Error> {
+        if size == 0 {
+            return Err(ErrorCode::ConstraintError.into());
+        }
+
+        let up = matches!(mode, StepModeEnum::Up);
+
+        let from = self.prepare(up, with_on_off);
+
+        let to = if up {
+            from.saturating_add(size).min(self.max_level)
+        } else {
+            from.saturating_sub(size).max(self.min_level)
+        };
+
+        // Without a transition time, move as fast as possible
+        let duration = transition_time.map(tenths).unwrap_or_default();
+
+        self.start(
+            from,
+            to,
+            duration,
+            with_on_off && to == self.min_level,
+            None,
+        );
+
+        Ok(())
+    }
+
+    fn stop(&self) {
+        let stopped = {
+            let mut state = self.state.borrow_mut();
+
+            state.remaining_time = 0;
+            state.transition.take().is_some()
+        };
+
+        if stopped {
+            self.dataver.changed();
+            self.level_settled();
+        }
+    }
+
+    fn set_attr<F>(&self, ctx: &WriteContext, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut LevelState),
+    {
+        f(&mut self.state.borrow_mut());
+
+        self.level_settled();
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+}
+
+impl LevelControlCoupling for LevelControlHandler<'_> {
+    fn on_off_command(&self, on: bool) -> bool {
+        let Some(on_off) = self.on_off else {
+            return false;
+        };
+
+        let (switching_off, current_level, restore_level, on_level, on_duration, off_duration) = {
+            let state = self.state.borrow();
+
+            (
+                state.switching_off(),
+                state.current_level(self.min_level),
+                state
+                    .transition
+                    .as_ref()
+                    .and_then(|transition| transition.restore_level),
+                state.on_level,
+                tenths(
+                    state
+                        .on_transition_time
+                        .unwrap_or(state.on_off_transition_time),
+                ),
+                tenths(
+                    state
+                        .off_transition_time
+                        .unwrap_or(state.on_off_transition_time),
+                ),
+            )
+        };
+
+        if on {
+            if on_off.get() && !switching_off {
+                return false;
+            }
+
+            let to = on_level
+                .or(restore_level)
+                .unwrap_or(current_level)
+                .clamp(self.min_level, self.max_level);
+
+            // When interrupting a switch-off, continue from the current level
+            if !switching_off {
+                self.apply_level(self.min_level);
+            }
+
+            on_off.set(true);
+
+            let from = self.state.borrow().current_level(self.min_level);
+
+            self.start(from, to, on_duration, false, None);
+        } else {
+            if !on_off.get() {
+                return false;
+            }
+
+            if !switching_off {
+                self.start(
+                    current_level,
+                    self.min_level,
+                    off_duration,
+                    true,
+                    Some(current_level),
+                );
+            }
+        }
+
+        true
+    }
+}
+
+impl SceneExtension for LevelControlHandler<'_> {
+    fn cluster_id(&self) -> u32 {
+        Self::CLUSTER.id
+    }
+
+    fn store_scene(&self, values: &mut SceneAttributeValues) -> Result<(), Error> {
+        if let Some(level) = self.get() {
+            values
+                .push(SceneAttributeValue::new(
+                    AttributeId::CurrentLevel as _,
+                    level as _,
+                ))
+                .map_err(|_| ErrorCode::NoSpace)?;
+        }
+
+        Ok(())
+    }
+
+    fn recall_scene(
+        &self,
+        values: &[SceneAttributeValue],
+        transition_time: Duration,
+    ) -> Result<(), Error> {
+        if let Some(level) = SceneAttributeValue::find(values, AttributeId::CurrentLevel as _) {
+            let to = level.clamp(self.min_level as _, self.max_level as _) as u8;
+            let from = self.state.borrow().current_level(self.min_level);
+
+            self.start(from, to, transition_time, false, None);
+        }
+
+        Ok(())
+    }
+}
+
+impl ClusterHandler for LevelControlHandler<'_> {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(5)
+        .with_features(Feature::ON_OFF.bits() | Feature::LIGHTING.bits())
+        .with_attrs(with!(
+            required;
+            AttributeId::RemainingTime
+                | AttributeId::MinLevel
+                | AttributeId::MaxLevel
+                | AttributeId::OnOffTransitionTime
+                | AttributeId::OnTransitionTime
+                | AttributeId::OffTransitionTime
+                | AttributeId::DefaultMoveRate
+                | AttributeId::StartUpCurrentLevel
+        ))
+        .with_cmds(with!(
+            CommandId::MoveToLevel
+                | CommandId::Move
+                | CommandId::Step
+                | CommandId::Stop
+                | CommandId::MoveToLevelWithOnOff
+                | CommandId::MoveWithOnOff
+                | CommandId::StepWithOnOff
+                | CommandId::StopWithOnOff
+        ));
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn current_level(&self, _ctx: &ReadContext) -> Result<Nullable<u8>, Error> {
+        Ok(Nullable::new(self.state.borrow().current_level))
+    }
+
+    fn remaining_time(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().remaining_time)
+    }
+
+    fn min_level(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.min_level)
+    }
+
+    fn max_level(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.max_level)
+    }
+
+    fn options(&self, _ctx: &ReadContext) -> Result<OptionsBitmap, Error> {
+        Ok(self.state.borrow().options)
+    }
+
+    fn on_off_transition_time(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().on_off_transition_time)
+    }
+
+    fn on_level(&self, _ctx: &ReadContext) -> Result<Nullable<u8>, Error> {
+        Ok(Nullable::new(self.state.borrow().on_level))
+    }
+
+    fn on_transition_time(&self, _ctx: &ReadContext) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::new(self.state.borrow().on_transition_time))
+    }
+
+    fn off_transition_time(&self, _ctx: &ReadContext) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::new(self.state.borrow().off_transition_time))
+    }
+
+    fn default_move_rate(&self, _ctx: &ReadContext) -> Result<Nullable<u8>, Error> {
+        Ok(Nullable::new(self.state.borrow().default_move_rate))
+    }
+
+    fn start_up_current_level(&self, _ctx: &ReadContext) -> Result<Nullable<u8>, Error> {
+        Ok(Nullable::new(self.state.borrow().start_up_current_level))
+    }
+
+    fn set_options(&self, ctx: &WriteContext, value: OptionsBitmap) -> Result<(), Error> {
+        self.set_attr(ctx, |state| state.options = value)
+    }
+
+    fn set_on_off_transition_time(&self, ctx: &WriteContext, value: u16) -> Result<(), Error> {
+        self.set_attr(ctx, |state| state.on_off_transition_time = value)
+    }
+
+    fn set_on_level(&self, ctx: &WriteContext, value: Nullable<u8>) -> Result<(), Error> {
+        let value = value.into_option();
+
+        if value.is_some_and(|level| !(self.min_level..=self.max_level).contains(&level)) {
+            return Err(ErrorCode::ConstraintError.into());
+        }
+
+        self.set_attr(ctx, |state| state.on_level = value)
+    }
+
+    fn set_on_transition_time(
+        &self,
+        ctx: &WriteContext,
+        value: Nullable<u16>,
+    ) -> Result<(), Error> {
+        self.set_attr(ctx, |state| state.on_transition_time = value.into_option())
+    }
+
+    fn set_off_transition_time(
+        &self,
+        ctx: &WriteContext,
+        value: Nullable<u16>,
+    ) -> Result<(), Error> {
+        self.set_attr(ctx, |state| state.off_transition_time = value.into_option())
+    }
+
+    fn set_default_move_rate(&self, ctx: &WriteContext, value: Nullable<u8>) -> Result<(), Error> {
+        self.set_attr(ctx, |state| state.default_move_rate = value.into_option())
+    }
+
+    fn set_start_up_current_level(
+        &self,
+        ctx: &WriteContext,
+        value: Nullable<u8>,
+    ) -> Result<(), Error> {
+        self.set_attr(ctx, |state| {
+            state.start_up_current_level = value.into_option()
+        })
+    }
+
+    fn handle_move_to_level(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveToLevelRequest,
+    ) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_to_level(
+                request.level()?,
+                request.transition_time()?.into_option(),
+                false,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move(&self, ctx: &InvokeContext, request: MoveRequest) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.move_(request.move_mode()?, request.rate()?.into_option(), false)?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_step(&self, ctx: &InvokeContext, request: StepRequest) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.step_(
+                request.step_mode()?,
+                request.step_size()?,
+                request.transition_time()?.into_option(),
+                false,
+            )?;
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_stop(&self, ctx: &InvokeContext, request: StopRequest) -> Result<(), Error> {
+        if self.should_execute(request.options_mask()?, request.options_override()?) {
+            self.stop();
+
+            ctx.notify_changed();
+        }
+
+        Ok(())
+    }
+
+    fn handle_move_to_level_with_on_off(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveToLevelWithOnOffRequest,
+    ) -> Result<(), Error> {
+        self.move_to_level(
+            request.level()?,
+            request.transition_time()?.into_option(),
+            true,
+        )?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_move_with_on_off(
+        &self,
+        ctx: &InvokeContext,
+        request: MoveWithOnOffRequest,
+    ) -> Result<(), Error> {
+        self.move_(request.move_mode()?, request.rate()?.into_option(), true)?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_step_with_on_off(
+        &self,
+        ctx: &InvokeContext,
+        request: StepWithOnOffRequest,
+    ) -> Result<(), Error> {
+        self.step_(
+            request.step_mode()?,
+            request.step_size()?,
+            request.transition_time()?.into_option(),
+            true,
+        )?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_stop_with_on_off(
+        &self,
+        ctx: &InvokeContext,
+        _request: StopWithOnOffRequest,
+    ) -> Result<(), Error> {
+        self.stop();
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_move_to_closest_frequency(
+        &self,
+        _ctx: &InvokeContext,
+        _request: MoveToClosestFrequencyRequest,
+    ) -> Result<(), Error> {
+        Err(ErrorCode::InvalidCommand.into())
+    }
+}
+
+/// Convert a time in tenths of a second to a `Duration`.
+fn tenths(time: u16) -> Duration {
+    Duration::from_millis(time as u64 * 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use embassy_time::Duration;
+
+    use crate::data_model::objects::Dataver;
+    use crate::data_model::on_off::OnOffHandler;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Hooks {
+        level: Cell<Option<u8>>,
+    }
+
+    impl LevelControlHooks for Hooks {
+        fn set_level(&self, level: u8) {
+            self.level.set(Some(level));
+        }
+    }
+
+    /// Run the transition in progress to completion, without waiting.
+    fn complete(handler: &LevelControlHandler) {
+        let start = unwrap!(handler.state.borrow().transition.as_ref()).start;
+        handler.step(start + Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_move_to_level() {
+        let hooks = Hooks::default();
+
+        let mut handler = LevelControlHandler::new(Dataver::new(0));
+        handler.set_hooks(&hooks);
+
+        // Instant
+        handler.move_to_level(100, Some(0), false).unwrap();
+        assert_eq!(handler.get(), Some(100));
+        assert_eq!(hooks.level.get(), Some(100));
+        assert!(handler.changed());
+
+        assert!(handler.move_to_level(255, None, false).is_err());
+
+        // 1 second for 100 levels up
+        handler.move_to_level(200, Some(10), false).unwrap();
+
+        let start = unwrap!(handler.state.borrow().transition.as_ref()).start;
+
+        let next = handler.step(start + Duration::from_millis(505));
+        assert_eq!(handler.get(), Some(150));
+        assert_eq!(handler.state.borrow().remaining_time, 5);
+        assert_eq!(next, Some(start + Duration::from_millis(510)));
+
+        handler.step(start + Duration::from_secs(1));
+        assert_eq!(handler.get(), Some(200));
+        assert_eq!(handler.state.borrow().remaining_time, 0);
+        assert!(handler.state.borrow().transition.is_none());
+    }
+
+    #[test]
+    fn test_move_step_stop() {
+        let handler = LevelControlHandler::new(Dataver::new(0));
+        handler.set(100);
+
+        assert!(handler.move_(MoveModeEnum::Up, Some(0), false).is_err());
+
+        // Without a rate, as fast as possible
+        handler.move_(MoveModeEnum::Down, None, false).unwrap();
+        assert_eq!(handler.get(), Some(MIN_LEVEL));
+
+        // 10 levels per second
+        handler.move_(MoveModeEnum::Up, Some(10), false).unwrap();
+        let start = unwrap!(handler.state.borrow().transition.as_ref()).start;
+        handler.step(start + Duration::from_millis(2050));
+        assert_eq!(handler.get(), Some(MIN_LEVEL + 20));
+
+        handler.stop();
+        assert!(handler.state.borrow().transition.is_none());
+        assert_eq!(handler.get(), Some(MIN_LEVEL + 20));
+
+        handler.step_(StepModeEnum::Up, 250, None, false).unwrap();
+        assert_eq!(handler.get(), Some(MAX_LEVEL));
+
+        handler
+            .step_(StepModeEnum::Down, 4, Some(1), false)
+            .unwrap();
+        complete(&handler);
+        assert_eq!(handler.get(), Some(MAX_LEVEL - 4));
+    }
+
+    #[test]
+    fn test_level_range() {
+        let on_off = OnOffHandler::new(Dataver::new(0));
+
+        let mut handler = LevelControlHandler::new_with_levels(Dataver::new(0), 10, 200);
+        handler.set_on_off(&on_off);
+        on_off.set_level_control(&handler);
+
+        // The device starts at its maximum level
+        assert_eq!(handler.get(), Some(200));
+
+        handler.set(5);
+        assert_eq!(handler.get(), Some(10));
+
+        handler.move_to_level(250, None, false).unwrap();
+        assert_eq!(handler.get(), Some(200));
+
+        handler.move_to_level(0, None, false).unwrap();
+        assert_eq!(handler.get(), Some(10));
+
+        handler.move_(MoveModeEnum::Up, None, false).unwrap();
+        assert_eq!(handler.get(), Some(200));
+
+        handler.step_(StepModeEnum::Down, 250, None, false).unwrap();
+        assert_eq!(handler.get(), Some(10));
+
+        // Switching off goes down to the minimum level, and switching on restores the previous level
+        handler.set(100);
+        handler.state.borrow_mut().on_off_transition_time = 10;
+        on_off.set(true);
+
+        assert!(handler.on_off_command(false));
+        complete(&handler);
+        assert!(!on_off.get());
+        assert_eq!(handler.get(), Some(100));
+
+        assert!(handler.on_off_command(true));
+        assert_eq!(handler.get(), Some(10));
+        complete(&handler);
+        assert_eq!(handler.get(), Some(100));
+
+        // The start-up level is clamped too
+        handler.state.borrow_mut().start_up_current_level = Some(250);
+        handler.state.borrow_mut().changed = true;
+
+        let mut buf = [0; 64];
+        let data = handler.store(&mut buf).unwrap().unwrap().to_vec();
+
+        let handler = LevelControlHandler::new_with_levels(Dataver::new(0), 10, 200);
+        handler.load(&data).unwrap();
+        assert_eq!(handler.get(), Some(200));
+    }
+
+    #[test]
+    fn test_options() {
+        let on_off = OnOffHandler::new(Dataver::new(0));
+
+        let mut handler = LevelControlHandler::new(Dataver::new(0));
+        handler.set_on_off(&on_off);
+
+        assert!(!handler.should_execute(OptionsBitmap::empty(), OptionsBitmap::empty()));
+        assert!(
+            handler.should_execute(OptionsBitmap::EXECUTE_IF_OFF, OptionsBitmap::EXECUTE_IF_OFF)
+        );
+
+        handler.state.borrow_mut().options = OptionsBitmap::EXECUTE_IF_OFF;
+        assert!(handler.should_execute(OptionsBitmap::empty(), OptionsBitmap::empty()));
+        assert!(!handler.should_execute(OptionsBitmap::EXECUTE_IF_OFF, OptionsBitmap::empty()));
+
+        on_off.set(true);
+        assert!(handler.should_execute(OptionsBitmap::EXECUTE_IF_OFF, OptionsBitmap::empty()));
+    }
+
+    #[test]
+    fn test_with_on_off() {
+        let on_off = OnOffHandler::new(Dataver::new(0));
+
+        let mut handler = LevelControlHandler::new(Dataver::new(0));
+        handler.set_on_off(&on_off);
+
+        handler.move_to_level(100, Some(0), true).unwrap();
+        assert!(on_off.get());
+        assert_eq!(handler.get(), Some(100));
+
+        handler.move_to_level(MIN_LEVEL, Some(10), true).unwrap();
+        assert!(on_off.get());
+        complete(&handler);
+        assert!(!on_off.get());
+        assert_eq!(handler.get(), Some(MIN_LEVEL));
+
+        // Moving up from off starts at the minimum level
+        handler.move_(MoveModeEnum::Up, Some(1), true).unwrap();
+        assert!(on_off.get());
+        assert_eq!(handler.get(), Some(MIN_LEVEL));
+    }
+
+    #[test]
+    fn test_on_off_coupling() {
+        let hooks = Hooks::default();
+
+        let on_off = OnOffHandler::new(Dataver::new(0));
+
+        let mut handler = LevelControlHandler::new(Dataver::new(0));
+        handler.set_hooks(&hooks);
+        handler.set_on_off(&on_off);
+        handler.set(150);
+
+        on_off.set_level_control(&handler);
+
+        // Switching on moves from the minimum level to the previous level
+        handler.state.borrow_mut().on_off_transition_time = 10;
+        assert!(handler.on_off_command(true));
+        assert!(on_off.get());
+        assert_eq!(handler.get(), Some(MIN_LEVEL));
+        complete(&handler);
+        assert_eq!(handler.get(), Some(150));
+
+        // Already on
+        assert!(!handler.on_off_command(true));
+
+        // Switching off moves to the minimum level, switches off, and restores the level
+        assert!(handler.on_off_command(false));
+        assert!(on_off.get());
+        complete(&handler);
+        assert!(!on_off.get());
+        assert_eq!(hooks.level.get(), Some(MIN_LEVEL));
+        assert_eq!(handler.get(), Some(150));
+
+        // OnLevel takes precedence
+        handler.state.borrow_mut().on_level = Some(42);
+        handler.state.borrow_mut().on_transition_time = Some(0);
+        assert!(handler.on_off_command(true));
+        assert_eq!(handler.get(), Some(42));
+
+        // Switching on while switching off continues from the current level
+        assert!(handler.on_off_command(false));
+        let start = unwrap!(handler.state.borrow().transition.as_ref()).start;
+        handler.step(start + Duration::from_millis(500));
+        assert!(handler.on_off_command(true));
+        assert!(on_off.get());
+        assert!(!handler.state.borrow().switching_off());
+        assert_eq!(handler.get(), Some(42));
+    }
+
+    #[test]
+    fn test_persist() {
+        let mut buf = [0; 64];
+
+        let handler = LevelControlHandler::new(Dataver::new(0));
+        handler.set(100);
+        handler.state.borrow_mut().on_level = Some(50);
+
+        let data = handler.store(&mut buf).unwrap().unwrap().to_vec();
+        assert!(!handler.changed());
+
+        for (start_up_current_level, level) in [(None, 100), (Some(0), MIN_LEVEL), (Some(7), 7)] {
+            let handler = LevelControlHandler::new(Dataver::new(0));
+            handler.load(&data).unwrap();
+            handler.state.borrow_mut().start_up_current_level = start_up_current_level;
+            handler.state.borrow_mut().changed = true;
+
+            let data = handler.store(&mut buf).unwrap().unwrap().to_vec();
+
+            let hooks = Hooks::default();
+
+            let mut handler = LevelControlHandler::new(Dataver::new(0));
+            handler.set_hooks(&hooks);
+            handler.load(&data).unwrap();
+
+            assert_eq!(handler.get(), Some(level));
+            assert_eq!(hooks.level.get(), Some(level));
+            assert_eq!(handler.state.borrow().on_level, Some(50));
+        }
+    }
+}
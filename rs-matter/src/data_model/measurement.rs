@@ -0,0 +1,596 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the state shared by the handlers of the measurement and sensing clusters
+//! (Temperature, Relative Humidity, Pressure, Flow, Illuminance, the Concentration Measurement clusters,
+//! Occupancy Sensing, Boolean State and Air Quality), as well as the common handler of the
+//! Temperature, Relative Humidity, Pressure, Flow and Illuminance Measurement clusters.
+
+use core::marker::PhantomData;
+
+use crate::error::Error;
+use crate::tlv::Nullable;
+use crate::utils::cell::RefCell;
+use crate::with;
+
+use super::objects::{Cluster, Dataver, ReadContext};
+
+pub use crate::data_model::clusters::{
+    flow_measurement, illuminance_measurement, pressure_measurement, relative_humidity_measurement,
+    temperature_measurement,
+};
+
+/// A trait for the types of the values of the measurement clusters.
+pub trait MeasurementValue: Copy + PartialOrd {
+    /// Return `true` if `self` differs from `other` by at least `threshold`.
+    fn differs(self, other: Self, threshold: Self) -> bool;
+}
+
+impl MeasurementValue for i16 {
+    fn differs(self, other: Self, threshold: Self) -> bool {
+        self.abs_diff(other) >= threshold.unsigned_abs()
+    }
+}
+
+impl MeasurementValue for u16 {
+    fn differs(self, other: Self, threshold: Self) -> bool {
+        self.abs_diff(other) >= threshold
+    }
+}
+
+impl MeasurementValue for f32 {
+    fn differs(self, other: Self, threshold: Self) -> bool {
+        let diff = if self > other {
+            self - other
+        } else {
+            other - self
+        };
+
+        diff >= threshold
+    }
+}
+
+/// A measured value, together with the range of the sensor and the hysteresis of its reporting.
+///
+/// The reported value (i.e. the `MeasuredValue` attribute) only changes when the measured value
+/// differs from it by at least the hysteresis, so that noisy sensors do not flood the subscribers
+/// with reports. Transitions from and to an unknown value are always reported.
+#[derive(Debug, Clone)]
+pub struct Measurement<T> {
+    value: Option<T>,
+    min: Option<T>,
+    max: Option<T>,
+    hysteresis: Option<T>,
+}
+
+impl<T> Measurement<T>
+where
+    T: MeasurementValue,
+{
+    /// Create a new, initially unknown measurement with the given range of the sensor
+    /// (`None` if the respective limit is unknown) and without hysteresis.
+    pub const fn new(min: Option<T>, max: Option<T>) -> Self {
+        Self {
+            value: None,
+            min,
+            max,
+            hysteresis: None,
+        }
+    }
+
+    /// Return the measurement with the given hysteresis.
+    pub const fn with_hysteresis(self, hysteresis: T) -> Self {
+        Self {
+            value: self.value,
+            min: self.min,
+            max: self.max,
+            hysteresis: Some(hysteresis),
+        }
+    }
+
+    /// Return the reported value, or `None` if it is unknown.
+    pub fn value(&self) -> Option<T> {
+        self.value
+    }
+
+    /// Return the minimum value which can be measured, or `None` if it is unknown.
+    pub fn min(&self) -> Option<T> {
+        self.min
+    }
+
+    /// Return the maximum value which can be measured, or `None` if it is unknown.
+    pub fn max(&self) -> Option<T> {
+        self.max
+    }
+
+    /// Update the measurement with a new value (`None` if the value cannot be measured).
+    ///
+    /// The value is clamped to the range of the sensor.
+    ///
+    /// Return `true` if the reported value has changed.
+    pub fn push(&mut self, value: Option<T>) -> bool {
+        let value = value.map(|mut value| {
+            if let Some(min) = self.min {
+                if value < min {
+                    value = min;
+                }
+            }
+
+            if let Some(max) = self.max {
+                if value > max {
+                    value = max;
+                }
+            }
+
+            value
+        });
+
+        let changed = match (self.value, value) {
+            (Some(old), Some(new)) => {
+                old != new
+                    && self
+                        .hysteresis
+                        .map(|hysteresis| new.differs(old, hysteresis))
+                        .unwrap_or(true)
+            }
+            (None, None) => false,
+            _ => true,
+        };
+
+        if changed {
+            self.value = value;
+        }
+
+        changed
+    }
+}
+
+/// Replace the state pushed by the driver of a sensor which reports every change of its state
+/// (i.e. an occupancy or a contact sensor), and if the state has changed - bump the dataver of the cluster
+/// and call `notify`.
+pub(crate) fn push_state<T, F>(state: &RefCell<T>, value: T, dataver: &Dataver, notify: F)
+where
+    T: PartialEq,
+    F: FnOnce(),
+{
+    if state.replace(value) != *state.borrow() {
+        dataver.changed();
+        notify();
+    }
+}
+
+/// Push the provided values to the handler of a sensor, checking after each push whether the change
+/// was reported as expected, i.e. whether the dataver of the cluster was bumped and `notify` was called.
+///
+/// Each of the `pushes` is a pushed value, along with whether it is expected to be reported.
+#[cfg(test)]
+pub(crate) fn check_pushes<T, P, D>(push: P, dataver: D, pushes: &[(T, bool)])
+where
+    T: Clone,
+    P: Fn(T, &dyn Fn()),
+    D: Fn() -> u32,
+{
+    for (value, reported) in pushes {
+        let notified = core::cell::Cell::new(false);
+        let prev = dataver();
+
+        push(value.clone(), &|| notified.set(true));
+
+        assert_eq!(notified.get(), *reported);
+        assert_eq!(dataver() != prev, *reported);
+    }
+}
+
+/// A trait for the marker types of the measurement clusters handled by `MeasurementHandler`.
+///
+/// The clusters share the same structure, but each of them has its own unit of the measured values.
+pub trait MeasurementCluster {
+    /// The type of the measured values
+    type Value: MeasurementValue;
+    /// The type of the sensor, for the clusters which report it
+    /// (i.e. the light sensor type of the Illuminance Measurement cluster), or `()`
+    type SensorType: Copy;
+}
+
+/// A handler for the Temperature, Relative Humidity, Pressure, Flow and Illuminance Measurement Matter clusters.
+///
+/// The measured value is pushed by the sensor driver with `MeasurementHandler::push_measurement`.
+///
+/// The handler is adapted to the generic `rs-matter` `Handler` trait with `MeasurementHandler::adapt`,
+/// i.e. `MeasurementHandler::<Temperature>::new(...).adapt()`.
+pub struct MeasurementHandler<C>
+where
+    C: MeasurementCluster,
+{
+    dataver: Dataver,
+    tolerance: u16,
+    sensor_type: Option<C::SensorType>,
+    measurement: RefCell<Measurement<C::Value>>,
+    _cluster: PhantomData<C>,
+}
+
+impl<C> MeasurementHandler<C>
+where
+    C: MeasurementCluster,
+{
+    /// Creates a new instance of `MeasurementHandler` with the given `Dataver` and measurement.
+    pub const fn new(dataver: Dataver, measurement: Measurement<C::Value>) -> Self {
+        Self {
+            dataver,
+            tolerance: 0,
+            sensor_type: None,
+            measurement: RefCell::new(measurement),
+            _cluster: PhantomData,
+        }
+    }
+
+    /// Return the handler with the given tolerance of the sensor, in the unit of the measured values.
+    pub const fn with_tolerance(mut self, tolerance: u16) -> Self {
+        self.tolerance = tolerance;
+
+        self
+    }
+
+    /// Return the reported value, or `None` if it is unknown.
+    pub fn measured_value(&self) -> Option<C::Value> {
+        self.measurement.borrow().value()
+    }
+
+    /// Return the minimum value which can be measured, or `None` if it is unknown.
+    pub fn min_measured_value(&self) -> Option<C::Value> {
+        self.measurement.borrow().min()
+    }
+
+    /// Return the maximum value which can be measured, or `None` if it is unknown.
+    pub fn max_measured_value(&self) -> Option<C::Value> {
+        self.measurement.borrow().max()
+    }
+
+    /// Return the tolerance of the sensor.
+    pub fn tolerance(&self) -> u16 {
+        self.tolerance
+    }
+
+    /// Push a new value (`None` if it cannot be measured).
+    ///
+    /// # Arguments
+    /// - `value`: The measured value
+    /// - `notify`: A callback which is called when the reported value changes, and which is expected
+    ///   to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub fn push_measurement<F>(&self, value: Option<C::Value>, notify: F)
+    where
+        F: FnOnce(),
+    {
+        if self.measurement.borrow_mut().push(value) {
+            self.dataver.changed();
+            notify();
+        }
+    }
+}
+
+impl MeasurementHandler<Illuminance> {
+    /// Return the handler with the given type of the light sensor.
+    pub const fn with_light_sensor_type(
+        mut self,
+        light_sensor_type: illuminance_measurement::LightSensorTypeEnum,
+    ) -> Self {
+        self.sensor_type = Some(light_sensor_type);
+
+        self
+    }
+
+    /// Return the type of the light sensor, or `None` if it is unknown.
+    pub fn light_sensor_type(&self) -> Option<illuminance_measurement::LightSensorTypeEnum> {
+        self.sensor_type
+    }
+}
+
+macro_rules! measurement_cluster {
+    ($(#[$meta:meta])* $marker:ident, $module:ident, $value:ty, $revision:expr) => {
+        measurement_cluster!($(#[$meta])* $marker, $module, $value, (), $revision, Tolerance, {});
+    };
+    ($(#[$meta:meta])* $marker:ident, $module:ident, $value:ty, $sensor_type:ty, $revision:expr, $($attr:ident)|+, { $($item:item)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub struct $marker;
+
+        impl MeasurementCluster for $marker {
+            type Value = $value;
+            type SensorType = $sensor_type;
+        }
+
+        impl MeasurementHandler<$marker> {
+            /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+            pub const fn adapt(self) -> $module::HandlerAdaptor<Self> {
+                $module::HandlerAdaptor(self)
+            }
+        }
+
+        impl $module::ClusterHandler for MeasurementHandler<$marker> {
+            const CLUSTER: Cluster<'static> = $module::FULL_CLUSTER
+                .with_revision($revision)
+                .with_attrs(with!(required; $($module::AttributeId::$attr)|+))
+                .with_cmds(with!());
+
+            fn dataver(&self) -> u32 {
+                self.dataver.get()
+            }
+
+            fn dataver_changed(&self) {
+                self.dataver.changed();
+            }
+
+            fn measured_value(&self, _ctx: &ReadContext) -> Result<Nullable<$value>, Error> {
+                Ok(Nullable::new(MeasurementHandler::measured_value(self)))
+            }
+
+            fn min_measured_value(&self, _ctx: &ReadContext) -> Result<Nullable<$value>, Error> {
+                Ok(Nullable::new(MeasurementHandler::min_measured_value(self)))
+            }
+
+            fn max_measured_value(&self, _ctx: &ReadContext) -> Result<Nullable<$value>, Error> {
+                Ok(Nullable::new(MeasurementHandler::max_measured_value(self)))
+            }
+
+            fn tolerance(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+                Ok(MeasurementHandler::tolerance(self))
+            }
+
+            $($item)*
+        }
+    };
+}
+
+measurement_cluster!(
+    /// The marker type of the Temperature Measurement cluster.
+    ///
+    /// The temperature is in units of 0.01 degrees Celsius.
+    Temperature,
+    temperature_measurement,
+    i16,
+    4
+);
+measurement_cluster!(
+    /// The marker type of the Pressure Measurement cluster.
+    ///
+    /// The pressure is in units of 0.1 kPa.
+    Pressure,
+    pressure_measurement,
+    i16,
+    3
+);
+measurement_cluster!(
+    /// The marker type of the Relative Humidity Measurement cluster.
+    ///
+    /// The relative humidity is in units of 0.01 %.
+    RelativeHumidity,
+    relative_humidity_measurement,
+    u16,
+    3
+);
+measurement_cluster!(
+    /// The marker type of the Flow Measurement cluster.
+    ///
+    /// The flow is in units of 0.1 m³/h.
+    Flow,
+    flow_measurement,
+    u16,
+    3
+);
+measurement_cluster!(
+    /// The marker type of the Illuminance Measurement cluster.
+    ///
+    /// The illuminance is encoded as `10000 x log10(lux) + 1` (with `0` meaning too low to be measured).
+    Illuminance,
+    illuminance_measurement,
+    u16,
+    illuminance_measurement::LightSensorTypeEnum,
+    3,
+    Tolerance | LightSensorType,
+    {
+        fn light_sensor_type(
+            &self,
+            _ctx: &ReadContext,
+        ) -> Result<Nullable<illuminance_measurement::LightSensorTypeEnum>, Error> {
+            Ok(Nullable::new(MeasurementHandler::light_sensor_type(self)))
+        }
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::tlv::{TLVTag, ToTLV};
+    use crate::utils::storage::WriteBuf;
+
+    use super::illuminance_measurement::LightSensorTypeEnum;
+    use super::*;
+
+    /// Encode a read attribute value the same way the generated handler adaptor does
+    fn read<T: ToTLV>(value: T, buf: &mut [u8]) -> &[u8] {
+        let mut wb = WriteBuf::new(buf);
+        unwrap!(value.to_tlv(&TLVTag::Anonymous, &mut wb));
+
+        let len = wb.as_slice().len();
+        &buf[..len]
+    }
+
+    #[test]
+    fn test_push() {
+        let mut measurement = Measurement::new(Some(-100), Some(100));
+
+        assert_eq!(measurement.value(), None);
+        assert!(!measurement.push(None));
+
+        assert!(measurement.push(Some(10)));
+        assert!(!measurement.push(Some(10)));
+        assert!(measurement.push(Some(11)));
+        assert_eq!(measurement.value(), Some(11));
+
+        // Clamped to the range of the sensor
+        assert!(measurement.push(Some(200)));
+        assert_eq!(measurement.value(), Some(100));
+        assert!(measurement.push(Some(i16::MIN)));
+        assert_eq!(measurement.value(), Some(-100));
+
+        assert!(measurement.push(None));
+        assert_eq!(measurement.value(), None);
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut measurement = Measurement::new(None, None).with_hysteresis(50_u16);
+
+        assert!(measurement.push(Some(1000)));
+
+        // Below the hysteresis; the reported value is kept
+        assert!(!measurement.push(Some(1049)));
+        assert!(!measurement.push(Some(951)));
+        assert_eq!(measurement.value(), Some(1000));
+
+        assert!(measurement.push(Some(1050)));
+        assert_eq!(measurement.value(), Some(1050));
+
+        // Changes in the known-ness of the value are always reported
+        assert!(measurement.push(None));
+        assert!(measurement.push(Some(1060)));
+
+        let mut measurement = Measurement::new(Some(0.0), None).with_hysteresis(0.5_f32);
+
+        assert!(measurement.push(Some(400.0)));
+        assert!(!measurement.push(Some(399.6)));
+        assert!(measurement.push(Some(400.5)));
+        assert!(measurement.push(Some(-1.0)));
+        assert_eq!(measurement.value(), Some(0.0));
+    }
+
+    #[test]
+    fn test_handler_nullable_i16() {
+        let handler = MeasurementHandler::<Temperature>::new(
+            Dataver::new(0),
+            Measurement::new(Some(-4000), Some(12500)).with_hysteresis(10),
+        )
+        .with_tolerance(50);
+
+        let push = |value, notify: &dyn Fn()| handler.push_measurement(value, notify);
+        let dataver = || handler.dataver.get();
+
+        let mut buf = [0; 8];
+
+        // Unknown temperature is reported as null
+        assert_eq!(
+            read(Nullable::new(handler.measured_value()), &mut buf),
+            &[0x14]
+        );
+        assert_eq!(
+            read(Nullable::new(handler.min_measured_value()), &mut buf),
+            &[0x01, 0x60, 0xf0]
+        );
+        assert_eq!(
+            read(Nullable::new(handler.max_measured_value()), &mut buf),
+            &[0x01, 0xd4, 0x30]
+        );
+        assert_eq!(handler.tolerance(), 50);
+
+        // The second value is within the hysteresis
+        check_pushes(push, dataver, &[(Some(2150), true), (Some(2155), false)]);
+        assert_eq!(
+            read(Nullable::new(handler.measured_value()), &mut buf),
+            &[0x01, 0x66, 0x08]
+        );
+
+        check_pushes(push, dataver, &[(Some(-5000), true)]);
+        assert_eq!(handler.measured_value(), Some(-4000));
+
+        check_pushes(push, dataver, &[(None, true)]);
+        assert_eq!(
+            read(Nullable::new(handler.measured_value()), &mut buf),
+            &[0x14]
+        );
+    }
+
+    #[test]
+    fn test_handler_u16() {
+        let handler = MeasurementHandler::<Illuminance>::new(
+            Dataver::new(0),
+            Measurement::new(Some(1), None),
+        )
+        .with_light_sensor_type(LightSensorTypeEnum::Photodiode);
+
+        let push = |value, notify: &dyn Fn()| handler.push_measurement(value, notify);
+        let dataver = || handler.dataver.get();
+
+        let mut buf = [0; 8];
+
+        assert_eq!(
+            read(Nullable::new(handler.min_measured_value()), &mut buf),
+            &[0x04, 0x01]
+        );
+        assert_eq!(
+            read(Nullable::new(handler.max_measured_value()), &mut buf),
+            &[0x14]
+        );
+        assert_eq!(
+            read(Nullable::new(handler.light_sensor_type()), &mut buf),
+            &[0x04, 0x00]
+        );
+
+        check_pushes(push, dataver, &[(Some(40001), true)]);
+        assert_eq!(
+            read(Nullable::new(handler.measured_value()), &mut buf),
+            &[0x05, 0x41, 0x9c]
+        );
+
+        // Without hysteresis, every change is reported
+        check_pushes(push, dataver, &[(Some(40002), true), (Some(40002), false)]);
+
+        check_pushes(push, dataver, &[(Some(0), true)]);
+        assert_eq!(handler.measured_value(), Some(1));
+
+        let without_type = MeasurementHandler::<RelativeHumidity>::new(
+            Dataver::new(0),
+            Measurement::new(Some(0), Some(10000)),
+        );
+        assert_eq!(without_type.tolerance(), 0);
+        assert_eq!(without_type.measured_value(), None);
+    }
+
+    #[test]
+    fn test_cluster_attrs() {
+        fn has_attr(cluster: &Cluster, attr: u32) -> bool {
+            cluster.attributes().any(|a| a.id == attr)
+        }
+
+        let temperature =
+            <MeasurementHandler<Temperature> as temperature_measurement::ClusterHandler>::CLUSTER;
+        assert!(has_attr(
+            &temperature,
+            temperature_measurement::AttributeId::Tolerance as _
+        ));
+
+        let illuminance =
+            <MeasurementHandler<Illuminance> as illuminance_measurement::ClusterHandler>::CLUSTER;
+        assert!(has_attr(
+            &illuminance,
+            illuminance_measurement::AttributeId::Tolerance as _
+        ));
+        assert!(has_attr(
+            &illuminance,
+            illuminance_measurement::AttributeId::LightSensorType as _
+        ));
+    }
+}
@@ -15,15 +15,45 @@
  *    limitations under the License.
  */
 
+//! The Matter Data Model: the handlers of the Matter clusters, and the machinery for combining them into a node.
+//!
+//! # Events
+//!
+//! `rs-matter` does not report cluster events via the Interaction Model yet, i.e. the event paths of Read and
+//! Subscribe requests are not served. Until it does, the cluster handlers which emit events deliver them to their
+//! hooks instead (e.g. `DoorLockHooks`, `BatteryMonitor` and `BridgeHooks`), while those without hooks
+//! (e.g. `BooleanStateHandler`) do not generate them at all.
+
+pub mod air_quality;
 pub mod basic_info;
+pub mod boolean_state;
+pub mod bridge;
 mod clusters;
+pub mod color_control;
+pub mod concentration_measurement;
 pub mod core;
 pub mod device_types;
+pub mod door_lock;
+pub mod flow_measurement;
+pub mod identify;
+pub mod illuminance_measurement;
+pub mod level_control;
+pub mod measurement;
 pub mod networks;
 pub mod objects;
+pub mod occupancy_sensing;
 pub mod on_off;
+pub mod power_source;
+pub mod power_source_config;
+pub mod pressure_measurement;
+pub mod relative_humidity_measurement;
 pub mod root_endpoint;
+pub mod scenes;
 pub mod sdm;
 pub mod subscriptions;
 pub mod system_model;
+pub mod temperature_measurement;
+pub mod thermostat;
+pub mod thermostat_ui_config;
 pub mod unit_testing;
+pub mod window_covering;
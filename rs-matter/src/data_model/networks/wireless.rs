@@ -812,10 +812,12 @@ impl NetCtlState {
     ///
     /// This method is only useful for non-concurrent commisioning using wireless networks and BLE,
     /// and is likely to be used together with `NoopWirelessNetCtl`.
-    pub async fn wait_prov_ready<M, C, M2, G>(state: &NetCtlStateMutex<M>, btp: &Btp<C, M2, G>)
-    where
+    pub async fn wait_prov_ready<M, C, M2, G, const N: usize>(
+        state: &NetCtlStateMutex<M>,
+        btp: &Btp<C, M2, G, N>,
+    ) where
         M: RawMutex,
-        C: Borrow<BtpContext<M2>> + Clone + Send + Sync + 'static,
+        C: Borrow<BtpContext<M2, N>> + Clone + Send + Sync + 'static,
         M2: RawMutex + Send + Sync,
         G: GattPeripheral,
     {
@@ -0,0 +1,987 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Support for Atomic Writes, i.e. writing a set of attributes of a cluster as a single unit.
+//!
+//! A client starts an atomic write by invoking the `AtomicRequest` command of the cluster with
+//! a `BeginWrite` request type and the list of attributes to be written. From then on and until
+//! the atomic write is committed, rolled back or times out:
+//! - Writes of these attributes by the client are not applied, but are rather stored as pending
+//! - Writes of these attributes by other clients are rejected with a `Busy` status
+//!
+//! Once the client invokes the `AtomicRequest` command with a `CommitWrite` request type, the
+//! pending values of all attributes are handed over to the cluster handler (see `InvokeContext::atomic_write`)
+//! which should validate and then apply them as a whole.
+//!
+//! The pending values are kept in storage dedicated to atomic writes (`AtomicWrites`) rather than in buffers
+//! borrowed from the IM buffer pool, so that atomic writes in progress cannot starve the regular IM exchanges
+//! of buffers. As that storage is sizeable (see `MAX_ATOMIC_WRITE_BUF_SIZE`), it is provided by the application
+//! - and only if any of its clusters supports atomic writes - with `DataModel::new_with_atomic_writes`.
+
+use core::cell::RefCell;
+
+use embassy_time::{Duration, Instant};
+
+use crate::error::{Error, ErrorCode};
+use crate::interaction_model::core::IMStatusCode;
+use crate::interaction_model::messages::ib::CmdResp;
+use crate::tlv::{FromTLV, TLVArray, TLVElement, TLVTag, TLVWrite, TLVWriter, ToTLV};
+use crate::transport::exchange::Exchange;
+use crate::utils::storage::WriteBuf;
+
+use super::{
+    Access, AttrDetails, AttrId, ChangeNotify, ClusterId, CmdDataEncoder, CmdDataTracker,
+    CmdDataWriter, CmdDetails, CmdId, DataModelHandler, EndptId, InvokeContext,
+};
+
+/// The ID of the `AtomicRequest` command, in clusters supporting atomic writes
+pub const ATOMIC_REQUEST_CMD_ID: CmdId = 0xfe;
+/// The ID of the `AtomicResponse` command, in clusters supporting atomic writes
+pub const ATOMIC_RESPONSE_CMD_ID: CmdId = 0xfd;
+
+/// The maximum number of attributes which can be written as part of a single atomic write
+pub const MAX_ATOMIC_WRITE_ATTRS: usize = 8;
+/// The maximum size (in bytes) of the TLV-encoded pending values of all attributes of a single atomic write
+pub const MAX_ATOMIC_WRITE_BUF_SIZE: usize = 1024;
+/// The maximum timeout (in milliseconds) of an atomic write
+///
+/// Timeouts requested by the clients are capped to this value.
+pub const MAX_ATOMIC_WRITE_TIMEOUT_MS: u16 = 9000;
+
+/// The maximum number of atomic writes which can be in progress at the same time
+pub(crate) const MAX_ATOMIC_WRITES: usize = 2;
+
+/// The type of an `AtomicRequest` command
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum AtomicRequestType {
+    #[enumval(0)]
+    BeginWrite = 0,
+    #[enumval(1)]
+    CommitWrite = 1,
+    #[enumval(2)]
+    RollbackWrite = 2,
+}
+
+/// The `AtomicRequest` command payload
+#[derive(Debug, Clone, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[tlvargs(lifetime = "'a")]
+pub struct AtomicRequest<'a> {
+    pub request_type: AtomicRequestType,
+    pub attribute_requests: TLVArray<'a, AttrId>,
+    pub timeout: Option<u16>,
+}
+
+/// Tags of the `AtomicResponse` command payload
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum AtomicResponseTag {
+    StatusCode = 0,
+    AttributeStatus = 1,
+    Timeout = 2,
+}
+
+/// Tags of the `AtomicAttributeStatusStruct` struct
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum AtomicAttributeStatusTag {
+    AttributeId = 0,
+    StatusCode = 1,
+}
+
+/// The pending values of an atomic write which is being committed.
+///
+/// Passed to the cluster handler via `InvokeContext::atomic_write`.
+#[derive(Debug)]
+pub struct AtomicWrite<'a> {
+    attrs: &'a [PendingAttr],
+    data: &'a [u8],
+}
+
+impl<'a> AtomicWrite<'a> {
+    /// Return the IDs of all attributes which are part of the atomic write.
+    pub fn attrs(&self) -> impl Iterator<Item = AttrId> + 'a {
+        self.attrs.iter().map(|attr| attr.id)
+    }
+
+    /// Return the pending value of the attribute with the provided ID.
+    ///
+    /// Returns `None` if the attribute is not part of the atomic write,
+    /// or if it is, but the client did not write it.
+    pub fn value(&self, attr_id: AttrId) -> Option<TLVElement<'a>> {
+        self.attrs
+            .iter()
+            .find(|attr| attr.id == attr_id)
+            .and_then(|attr| attr.value)
+            .map(|(start, end)| TLVElement::new(&self.data[start..end]))
+    }
+}
+
+/// An attribute which is part of an atomic write, with the location
+/// of its pending value (if any) in the atomic write buffer.
+#[derive(Debug, Clone)]
+struct PendingAttr {
+    id: AttrId,
+    value: Option<(usize, usize)>,
+}
+
+/// Return the fabric index and the node ID of the peer of the exchange,
+/// which are used for identifying the owner of an atomic write.
+pub(crate) fn atomic_write_owner(exchange: &Exchange<'_>) -> Result<(u8, u64), Error> {
+    exchange.with_session(|sess| {
+        Ok((
+            sess.get_local_fabric_idx(),
+            sess.get_peer_node_id().unwrap_or(0),
+        ))
+    })
+}
+
+/// The attributes of an atomic write in progress, together with their pending values.
+struct PendingValues {
+    attrs: heapless::Vec<PendingAttr, MAX_ATOMIC_WRITE_ATTRS>,
+    buf: [u8; MAX_ATOMIC_WRITE_BUF_SIZE],
+    len: usize,
+}
+
+impl PendingValues {
+    const fn new() -> Self {
+        Self {
+            attrs: heapless::Vec::new(),
+            buf: [0; MAX_ATOMIC_WRITE_BUF_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Reset to the provided attributes, none of which has a pending value yet.
+    fn reset(&mut self, attrs: impl Iterator<Item = AttrId>) {
+        self.attrs = attrs
+            .map(|attr_id| PendingAttr {
+                id: attr_id,
+                value: None,
+            })
+            .collect();
+        self.len = 0;
+    }
+
+    fn contains(&self, attr_id: AttrId) -> bool {
+        self.attrs.iter().any(|attr| attr.id == attr_id)
+    }
+
+    /// Store the written value as the pending value of the attribute.
+    ///
+    /// A write without a list index replaces the pending value, while an append
+    /// (null list index) appends to the pending value, which must be an array.
+    /// Updates and removals of individual list items are not supported.
+    fn write(&mut self, attr: &AttrDetails, data: &TLVElement) -> Result<(), Error> {
+        let index = unwrap!(self
+            .attrs
+            .iter()
+            .position(|pending| pending.id == attr.attr_id));
+
+        let append = match attr.list_index.as_ref().map(|index| index.as_opt_ref()) {
+            None => false,
+            Some(None) => true,
+            Some(Some(_)) => Err(ErrorCode::InvalidAction)?,
+        };
+
+        // Move the current pending value (if any) to the end of the buffer,
+        // so that it can be easily replaced or appended to
+        let start = self.move_to_end(index);
+
+        if append {
+            if self.attrs[index].value.is_none() {
+                // Can't append to a value which had not been written
+                Err(ErrorCode::InvalidAction)?;
+            }
+
+            // Remove the end-of-container of the pending array
+            self.len -= 1;
+        } else {
+            self.len = start;
+        }
+
+        let mut wb = WriteBuf::new(&mut self.buf[self.len..]);
+        let mut tw = TLVWriter::new(&mut wb);
+
+        let result = data
+            .to_tlv(&TLVTag::Anonymous, &mut tw)
+            .and_then(|_| if append { tw.end_container() } else { Ok(()) })
+            .map_err(|_| ErrorCode::ResourceExhausted);
+
+        match result {
+            Ok(()) => {
+                self.len += wb.get_tail();
+                self.attrs[index].value = Some((start, self.len));
+
+                Ok(())
+            }
+            Err(err) => {
+                // The pending value is now broken, so discard it
+                self.len = start;
+                self.attrs[index].value = None;
+
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Move the pending value of the attribute with the provided index to the end of the buffer,
+    /// returning its new start position.
+    fn move_to_end(&mut self, index: usize) -> usize {
+        let Some((start, end)) = self.attrs[index].value else {
+            return self.len;
+        };
+
+        let size = end - start;
+
+        self.buf[start..self.len].rotate_left(size);
+
+        for attr in &mut self.attrs {
+            if let Some((other_start, other_end)) = attr.value.as_mut() {
+                if *other_start > start {
+                    *other_start -= size;
+                    *other_end -= size;
+                }
+            }
+        }
+
+        let new_start = self.len - size;
+        self.attrs[index].value = Some((new_start, self.len));
+
+        new_start
+    }
+
+    fn atomic_write(&self) -> AtomicWrite<'_> {
+        AtomicWrite {
+            attrs: &self.attrs,
+            data: &self.buf[..self.len],
+        }
+    }
+}
+
+/// The state of an atomic write in progress.
+struct AtomicWriteState {
+    fab_idx: u8,
+    peer_node_id: u64,
+    endpoint_id: EndptId,
+    cluster_id: ClusterId,
+    expires: Instant,
+    /// The index of the storage slot holding the attributes and the pending values of the atomic write
+    slot: usize,
+    /// `true` while the pending values are being committed by the cluster handler
+    committing: bool,
+}
+
+impl AtomicWriteState {
+    fn is_for(&self, endpoint_id: EndptId, cluster_id: ClusterId) -> bool {
+        self.endpoint_id == endpoint_id && self.cluster_id == cluster_id
+    }
+
+    fn is_owned_by(&self, owner: (u8, u64)) -> bool {
+        (self.fab_idx, self.peer_node_id) == owner
+    }
+}
+
+/// The storage for the atomic writes in progress, to be provided to `DataModel::new_with_atomic_writes`.
+///
+/// Atomic writes are keyed by the fabric and node ID of the client which started them,
+/// and by the endpoint and cluster of the attributes being written. The pending values
+/// of each atomic write are stored in one of `MAX_ATOMIC_WRITES` storage slots.
+///
+/// None of the operations awaits while the atomic writes are inspected or modified, so all
+/// conflict checks are always done against the current state. The only await - the cluster
+/// handler committing the pending values - happens while the atomic write is still registered
+/// (and marked as committing), so its attributes cannot be claimed by another atomic write meanwhile.
+pub struct AtomicWrites {
+    writes: RefCell<heapless::Vec<AtomicWriteState, MAX_ATOMIC_WRITES>>,
+    slots: [RefCell<PendingValues>; MAX_ATOMIC_WRITES],
+}
+
+impl AtomicWrites {
+    /// Create the storage, with no atomic writes in progress.
+    #[inline(always)]
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: RefCell<PendingValues> = RefCell::new(PendingValues::new());
+
+        Self {
+            writes: RefCell::new(heapless::Vec::new()),
+            slots: [EMPTY; MAX_ATOMIC_WRITES],
+        }
+    }
+
+    /// Reject an `AtomicRequest` command, as the Data Model does not support atomic writes.
+    pub(crate) fn reject(cmd: &CmdDetails<'_>, tw: &mut TLVWriter<'_, '_>) -> Result<(), Error> {
+        Self::encode_status(cmd, IMStatusCode::ResourceExhausted, tw)
+    }
+
+    /// Handle a write to an attribute.
+    ///
+    /// Returns `None` if the attribute is not part of any atomic write, in which case
+    /// the write should be processed as usual. Otherwise, returns the status of the write,
+    /// which is `Busy` if the attribute is part of an atomic write owned by another client,
+    /// or part of an atomic write which is being committed.
+    pub(crate) fn write(
+        &self,
+        owner: (u8, u64),
+        attr: &AttrDetails,
+        data: &TLVElement,
+    ) -> Option<IMStatusCode> {
+        let mut writes = self.writes.borrow_mut();
+
+        Self::purge(&mut writes);
+
+        let write = writes.iter().find(|write| {
+            write.is_for(attr.endpoint_id, attr.cluster_id) && self.contains(write, attr.attr_id)
+        })?;
+
+        if !write.is_owned_by(owner) || write.committing {
+            return Some(IMStatusCode::Busy);
+        }
+
+        match self.slots[write.slot].borrow_mut().write(attr, data) {
+            Ok(()) => Some(IMStatusCode::Success),
+            Err(err) => {
+                error!("Error storing a pending attribute value: {}", err);
+                Some(err.into())
+            }
+        }
+    }
+
+    /// Handle an `AtomicRequest` command.
+    // The slot of an atomic write being committed stays borrowed while the handler commits it,
+    // which is fine, as slots of atomic writes being committed are never borrowed mutably
+    #[allow(clippy::await_holding_refcell_ref)]
+    pub(crate) async fn handle<T>(
+        &self,
+        handler: T,
+        exchange: &Exchange<'_>,
+        cmd: &CmdDetails<'_>,
+        data: &TLVElement<'_>,
+        tw: &mut TLVWriter<'_, '_>,
+        notify: &dyn ChangeNotify,
+    ) -> Result<(), Error>
+    where
+        T: DataModelHandler,
+    {
+        let owner = atomic_write_owner(exchange)?;
+
+        let Ok(req) = AtomicRequest::from_tlv(data) else {
+            return Self::encode_status(cmd, IMStatusCode::InvalidCommand, tw);
+        };
+
+        let mut attrs = heapless::Vec::<_, MAX_ATOMIC_WRITE_ATTRS>::new();
+
+        for attr_id in &req.attribute_requests {
+            let Ok(attr_id) = attr_id else {
+                return Self::encode_status(cmd, IMStatusCode::InvalidCommand, tw);
+            };
+
+            if attrs.contains(&attr_id) {
+                return Self::encode_status(cmd, IMStatusCode::InvalidCommand, tw);
+            }
+
+            if attrs.push(attr_id).is_err() {
+                return Self::encode_status(cmd, IMStatusCode::ResourceExhausted, tw);
+            }
+        }
+
+        if attrs.is_empty() {
+            return Self::encode_status(cmd, IMStatusCode::InvalidCommand, tw);
+        }
+
+        match req.request_type {
+            AtomicRequestType::BeginWrite => {
+                let Some(timeout) = req.timeout else {
+                    return Self::encode_status(cmd, IMStatusCode::InvalidCommand, tw);
+                };
+
+                self.begin(owner, cmd, &attrs, timeout, tw)
+            }
+            AtomicRequestType::CommitWrite => {
+                let Some(slot) = self.start_commit(owner, cmd.endpoint_id, cmd.cluster_id, &attrs)
+                else {
+                    return Self::encode_status(cmd, IMStatusCode::InvalidInState, tw);
+                };
+
+                // Ends the commit even if we are dropped while the handler is committing
+                let _commit = Commit { writes: self, slot };
+
+                let pending = self.slots[slot].borrow();
+                let atomic_write = pending.atomic_write();
+
+                let mut tracker = CmdDataTracker::new();
+
+                let result = handler
+                    .invoke(
+                        &InvokeContext::new(exchange, cmd, data, notify)
+                            .with_atomic_write(&atomic_write),
+                        CmdDataEncoder::new(cmd, &mut tracker, tw),
+                    )
+                    .await;
+
+                if !tracker.needs_status() {
+                    // The handler had already responded
+                    return Ok(());
+                }
+
+                let (status, attrs_status) = match result {
+                    Ok(()) => (IMStatusCode::Success, IMStatusCode::Success),
+                    Err(err) => {
+                        error!("Committing an atomic write failed: {}", err);
+                        (IMStatusCode::Failure, err.into())
+                    }
+                };
+
+                Self::encode_response(
+                    cmd,
+                    status,
+                    atomic_write.attrs().map(|attr_id| (attr_id, attrs_status)),
+                    None,
+                    tw,
+                )
+            }
+            AtomicRequestType::RollbackWrite => {
+                let Some(attrs) = self.rollback(owner, cmd.endpoint_id, cmd.cluster_id, &attrs)
+                else {
+                    return Self::encode_status(cmd, IMStatusCode::InvalidInState, tw);
+                };
+
+                Self::encode_response(
+                    cmd,
+                    IMStatusCode::Success,
+                    attrs
+                        .iter()
+                        .map(|attr_id| (*attr_id, IMStatusCode::Success)),
+                    None,
+                    tw,
+                )
+            }
+        }
+    }
+
+    fn begin(
+        &self,
+        owner: (u8, u64),
+        cmd: &CmdDetails<'_>,
+        attrs: &[AttrId],
+        timeout: u16,
+        tw: &mut TLVWriter<'_, '_>,
+    ) -> Result<(), Error> {
+        let timeout = timeout.min(MAX_ATOMIC_WRITE_TIMEOUT_MS);
+
+        let cluster = cmd
+            .node
+            .endpoint(cmd.endpoint_id)
+            .and_then(|endpoint| endpoint.cluster(cmd.cluster_id))
+            .ok_or(ErrorCode::ClusterNotFound)?;
+
+        let mut statuses = heapless::Vec::<_, MAX_ATOMIC_WRITE_ATTRS>::new();
+
+        for attr_id in attrs {
+            let status = match cluster.attributes().find(|attr| attr.id == *attr_id) {
+                None => IMStatusCode::UnsupportedAttribute,
+                Some(attr) if !attr.access.contains(Access::WRITE) => {
+                    IMStatusCode::UnsupportedWrite
+                }
+                Some(_) => IMStatusCode::Success,
+            };
+
+            unwrap!(statuses.push((*attr_id, status)));
+        }
+
+        match self.try_begin(
+            owner,
+            cmd.endpoint_id,
+            cmd.cluster_id,
+            &mut statuses,
+            timeout,
+        ) {
+            IMStatusCode::Success => Self::encode_response(
+                cmd,
+                IMStatusCode::Success,
+                statuses.iter().copied(),
+                Some(timeout),
+                tw,
+            ),
+            IMStatusCode::Failure => Self::encode_response(
+                cmd,
+                IMStatusCode::Failure,
+                statuses.iter().copied(),
+                None,
+                tw,
+            ),
+            status => Self::encode_status(cmd, status, tw),
+        }
+    }
+
+    /// Begin an atomic write of the attributes in `statuses` for the provided owner.
+    ///
+    /// Attributes which are already part of another atomic write get a `Busy` status.
+    /// Returns `Failure` if any of the attributes does not have a `Success` status (in which case
+    /// the atomic write is not started), `InvalidInState` if the owner already has an atomic write
+    /// in progress for the cluster, and `ResourceExhausted` if there are too many atomic writes in progress.
+    fn try_begin(
+        &self,
+        owner: (u8, u64),
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        statuses: &mut [(AttrId, IMStatusCode)],
+        timeout_ms: u16,
+    ) -> IMStatusCode {
+        let mut writes = self.writes.borrow_mut();
+
+        Self::purge(&mut writes);
+
+        if writes
+            .iter()
+            .any(|write| write.is_for(endpoint_id, cluster_id) && write.is_owned_by(owner))
+        {
+            return IMStatusCode::InvalidInState;
+        }
+
+        for (attr_id, status) in statuses.iter_mut() {
+            if *status == IMStatusCode::Success
+                && writes.iter().any(|write| {
+                    write.is_for(endpoint_id, cluster_id) && self.contains(write, *attr_id)
+                })
+            {
+                *status = IMStatusCode::Busy;
+            }
+        }
+
+        if statuses
+            .iter()
+            .any(|(_, status)| *status != IMStatusCode::Success)
+        {
+            return IMStatusCode::Failure;
+        }
+
+        let Some(slot) =
+            (0..MAX_ATOMIC_WRITES).find(|slot| writes.iter().all(|write| write.slot != *slot))
+        else {
+            return IMStatusCode::ResourceExhausted;
+        };
+
+        self.slots[slot]
+            .borrow_mut()
+            .reset(statuses.iter().map(|(attr_id, _)| *attr_id));
+
+        // Can't fail, as there is one write per occupied slot, and we just found a free slot
+        unwrap!(writes
+            .push(AtomicWriteState {
+                fab_idx: owner.0,
+                peer_node_id: owner.1,
+                endpoint_id,
+                cluster_id,
+                expires: Instant::now() + Duration::from_millis(timeout_ms as _),
+                slot,
+                committing: false,
+            })
+            .map_err(|_| ()));
+
+        IMStatusCode::Success
+    }
+
+    /// Mark the atomic write owned by the provided owner for the provided cluster as being committed,
+    /// provided that it contains all of the provided attributes, and return its storage slot.
+    ///
+    /// The atomic write stays registered until `end_commit` is called.
+    fn start_commit(
+        &self,
+        owner: (u8, u64),
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        attrs: &[AttrId],
+    ) -> Option<usize> {
+        let mut writes = self.writes.borrow_mut();
+
+        Self::purge(&mut writes);
+
+        let write = writes
+            .iter_mut()
+            .find(|write| self.is_owned_match(write, owner, endpoint_id, cluster_id, attrs))?;
+
+        write.committing = true;
+
+        Some(write.slot)
+    }
+
+    /// Remove the atomic write stored in the provided slot, once it is committed.
+    fn end_commit(&self, slot: usize) {
+        self.writes.borrow_mut().retain(|write| write.slot != slot);
+    }
+
+    /// Remove the atomic write owned by the provided owner for the provided cluster, provided that
+    /// it contains all of the provided attributes, and return the IDs of all of its attributes.
+    fn rollback(
+        &self,
+        owner: (u8, u64),
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        attrs: &[AttrId],
+    ) -> Option<heapless::Vec<AttrId, MAX_ATOMIC_WRITE_ATTRS>> {
+        let mut writes = self.writes.borrow_mut();
+
+        Self::purge(&mut writes);
+
+        let index = writes
+            .iter()
+            .position(|write| self.is_owned_match(write, owner, endpoint_id, cluster_id, attrs))?;
+
+        let write = writes.swap_remove(index);
+
+        let attrs = self.slots[write.slot]
+            .borrow()
+            .attrs
+            .iter()
+            .map(|attr| attr.id)
+            .collect();
+
+        Some(attrs)
+    }
+
+    /// Return `true` if the atomic write is not being committed, is owned by the provided owner,
+    /// is for the provided cluster and contains all of the provided attributes.
+    fn is_owned_match(
+        &self,
+        write: &AtomicWriteState,
+        owner: (u8, u64),
+        endpoint_id: EndptId,
+        cluster_id: ClusterId,
+        attrs: &[AttrId],
+    ) -> bool {
+        !write.committing
+            && write.is_for(endpoint_id, cluster_id)
+            && write.is_owned_by(owner)
+            && attrs.iter().all(|attr_id| self.contains(write, *attr_id))
+    }
+
+    fn contains(&self, write: &AtomicWriteState, attr_id: AttrId) -> bool {
+        self.slots[write.slot].borrow().contains(attr_id)
+    }
+
+    /// Remove all atomic writes which had timed out.
+    ///
+    /// Atomic writes which are being committed do not time out.
+    fn purge(writes: &mut heapless::Vec<AtomicWriteState, MAX_ATOMIC_WRITES>) {
+        let now = Instant::now();
+
+        writes.retain(|write| {
+            let expired = !write.committing && write.expires <= now;
+
+            if expired {
+                warn!(
+                    "Atomic write to {}/{} timed out, discarding its pending values",
+                    write.endpoint_id, write.cluster_id
+                );
+            }
+
+            !expired
+        });
+    }
+
+    fn encode_status(
+        cmd: &CmdDetails,
+        status: IMStatusCode,
+        tw: &mut TLVWriter<'_, '_>,
+    ) -> Result<(), Error> {
+        if let Some(status) = cmd.status(status) {
+            CmdResp::Status(status).to_tlv(&TLVTag::Anonymous, tw)?;
+        }
+
+        Ok(())
+    }
+
+    fn encode_response(
+        cmd: &CmdDetails,
+        status: IMStatusCode,
+        attrs: impl Iterator<Item = (AttrId, IMStatusCode)>,
+        timeout: Option<u16>,
+        tw: &mut TLVWriter<'_, '_>,
+    ) -> Result<(), Error> {
+        let mut tracker = CmdDataTracker::new();
+        let mut writer =
+            CmdDataEncoder::new(cmd, &mut tracker, tw).with_command(ATOMIC_RESPONSE_CMD_ID)?;
+
+        writer.start_struct(&CmdDataWriter::TAG)?;
+        writer.u8(
+            &TLVTag::Context(AtomicResponseTag::StatusCode as _),
+            status as _,
+        )?;
+
+        writer.start_array(&TLVTag::Context(AtomicResponseTag::AttributeStatus as _))?;
+        for (attr_id, status) in attrs {
+            writer.start_struct(&TLVTag::Anonymous)?;
+            writer.u32(
+                &TLVTag::Context(AtomicAttributeStatusTag::AttributeId as _),
+                attr_id,
+            )?;
+            writer.u8(
+                &TLVTag::Context(AtomicAttributeStatusTag::StatusCode as _),
+                status as _,
+            )?;
+            writer.end_container()?;
+        }
+        writer.end_container()?;
+
+        if let Some(timeout) = timeout {
+            writer.u16(&TLVTag::Context(AtomicResponseTag::Timeout as _), timeout)?;
+        }
+
+        writer.end_container()?;
+
+        writer.complete()
+    }
+}
+
+impl Default for AtomicWrites {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Ends the commit of an atomic write (and thus removes it) once dropped.
+struct Commit<'a> {
+    writes: &'a AtomicWrites,
+    slot: usize,
+}
+
+impl Drop for Commit<'_> {
+    fn drop(&mut self) {
+        self.writes.end_commit(self.slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::objects::{AttrDetails, Node};
+    use crate::interaction_model::core::IMStatusCode;
+    use crate::tlv::{Nullable, TLVElement};
+
+    use super::{AtomicWrites, MAX_ATOMIC_WRITES};
+
+    const OWNER1: (u8, u64) = (1, 100);
+    const OWNER2: (u8, u64) = (1, 200);
+
+    const NODE: Node<'static> = Node {
+        id: 0,
+        endpoints: &[],
+    };
+
+    fn attr(attr_id: u32, list_index: Option<Nullable<u16>>) -> AttrDetails<'static> {
+        AttrDetails {
+            node: &NODE,
+            endpoint_id: 1,
+            cluster_id: 0x201,
+            attr_id,
+            list_index,
+            fab_idx: 1,
+            fab_filter: false,
+            dataver: None,
+            wildcard: false,
+        }
+    }
+
+    fn begin(
+        writes: &AtomicWrites,
+        owner: (u8, u64),
+        attrs: &[u32],
+        timeout_ms: u16,
+    ) -> (IMStatusCode, heapless::Vec<(u32, IMStatusCode), 8>) {
+        let mut statuses: heapless::Vec<_, 8> = attrs
+            .iter()
+            .map(|attr_id| (*attr_id, IMStatusCode::Success))
+            .collect();
+
+        let status = writes.try_begin(owner, 1, 0x201, &mut statuses, timeout_ms);
+
+        (status, statuses)
+    }
+
+    // A TLV-encoded unsigned integer 5
+    const VALUE: &[u8] = &[0x04, 0x05];
+    // A TLV-encoded array with one unsigned integer 5
+    const ARRAY: &[u8] = &[0x16, 0x04, 0x05, 0x18];
+    // A TLV-encoded array with two unsigned integers 5
+    const ARRAY2: &[u8] = &[0x16, 0x04, 0x05, 0x04, 0x05, 0x18];
+
+    #[test]
+    fn test_conflict() {
+        let writes = AtomicWrites::new();
+
+        assert_eq!(
+            begin(&writes, OWNER1, &[1, 2], 9000).0,
+            IMStatusCode::Success
+        );
+
+        // The owner can't begin a second atomic write for the same cluster
+        assert_eq!(
+            begin(&writes, OWNER1, &[3], 9000).0,
+            IMStatusCode::InvalidInState
+        );
+
+        // Another client can't begin an atomic write including any of the attributes...
+        let (status, statuses) = begin(&writes, OWNER2, &[2, 3], 9000);
+        assert_eq!(status, IMStatusCode::Failure);
+        assert_eq!(
+            statuses.as_slice(),
+            &[(2, IMStatusCode::Busy), (3, IMStatusCode::Success)]
+        );
+
+        // ... nor write them
+        let value = TLVElement::new(VALUE);
+        assert_eq!(
+            writes.write(OWNER2, &attr(1, None), &value),
+            Some(IMStatusCode::Busy)
+        );
+
+        // Attributes which are not part of any atomic write are written as usual
+        assert_eq!(writes.write(OWNER2, &attr(3, None), &value), None);
+
+        // The owner's writes are stored as pending values
+        assert_eq!(
+            writes.write(OWNER1, &attr(1, None), &value),
+            Some(IMStatusCode::Success)
+        );
+
+        // Another client can begin an atomic write of other attributes
+        assert_eq!(begin(&writes, OWNER2, &[3], 9000).0, IMStatusCode::Success);
+
+        // ... but there is no room for a third one
+        assert_eq!(MAX_ATOMIC_WRITES, 2);
+        assert_eq!(
+            begin(&writes, (2, 100), &[4], 9000).0,
+            IMStatusCode::ResourceExhausted
+        );
+    }
+
+    #[test]
+    fn test_commit() {
+        let writes = AtomicWrites::new();
+
+        assert_eq!(
+            begin(&writes, OWNER1, &[1, 2], 9000).0,
+            IMStatusCode::Success
+        );
+
+        let value = TLVElement::new(VALUE);
+        let array = TLVElement::new(ARRAY);
+
+        assert_eq!(
+            writes.write(OWNER1, &attr(1, None), &value),
+            Some(IMStatusCode::Success)
+        );
+        assert_eq!(
+            writes.write(OWNER1, &attr(2, None), &array),
+            Some(IMStatusCode::Success)
+        );
+        // Replace the pending value of attribute 1 and append to the pending value of attribute 2
+        assert_eq!(
+            writes.write(OWNER1, &attr(1, None), &value),
+            Some(IMStatusCode::Success)
+        );
+        assert_eq!(
+            writes.write(OWNER1, &attr(2, Some(Nullable::none())), &value),
+            Some(IMStatusCode::Success)
+        );
+
+        // Only the owner can commit, and only with attributes of the atomic write
+        assert_eq!(writes.start_commit(OWNER2, 1, 0x201, &[1]), None);
+        assert_eq!(writes.start_commit(OWNER1, 1, 0x201, &[3]), None);
+
+        let slot = writes.start_commit(OWNER1, 1, 0x201, &[1, 2]).unwrap();
+
+        {
+            let pending = writes.slots[slot].borrow();
+            let atomic_write = pending.atomic_write();
+
+            assert_eq!(atomic_write.value(1).unwrap().raw_data(), VALUE);
+            assert_eq!(atomic_write.value(2).unwrap().raw_data(), ARRAY2);
+            assert!(atomic_write.value(3).is_none());
+
+            // While being committed, the attributes are still part of the atomic write
+            assert_eq!(
+                writes.write(OWNER1, &attr(1, None), &value),
+                Some(IMStatusCode::Busy)
+            );
+            assert_eq!(begin(&writes, OWNER2, &[1], 9000).0, IMStatusCode::Failure);
+            assert_eq!(writes.rollback(OWNER1, 1, 0x201, &[1]), None);
+        }
+
+        writes.end_commit(slot);
+
+        assert_eq!(writes.write(OWNER1, &attr(1, None), &value), None);
+        assert_eq!(begin(&writes, OWNER2, &[1], 9000).0, IMStatusCode::Success);
+    }
+
+    #[test]
+    fn test_rollback() {
+        let writes = AtomicWrites::new();
+
+        assert_eq!(
+            begin(&writes, OWNER1, &[1, 2], 9000).0,
+            IMStatusCode::Success
+        );
+
+        let value = TLVElement::new(VALUE);
+        assert_eq!(
+            writes.write(OWNER1, &attr(1, None), &value),
+            Some(IMStatusCode::Success)
+        );
+
+        // Only the owner can roll back
+        assert_eq!(writes.rollback(OWNER2, 1, 0x201, &[1]), None);
+
+        assert_eq!(
+            writes.rollback(OWNER1, 1, 0x201, &[1]).unwrap().as_slice(),
+            &[1, 2]
+        );
+
+        // The attributes are no longer part of an atomic write
+        assert_eq!(writes.write(OWNER1, &attr(1, None), &value), None);
+        assert_eq!(writes.rollback(OWNER1, 1, 0x201, &[1]), None);
+        assert_eq!(
+            begin(&writes, OWNER2, &[1, 2], 9000).0,
+            IMStatusCode::Success
+        );
+    }
+
+    #[test]
+    fn test_timeout() {
+        let writes = AtomicWrites::new();
+
+        assert_eq!(begin(&writes, OWNER1, &[1, 2], 10).0, IMStatusCode::Success);
+
+        let value = TLVElement::new(VALUE);
+        assert_eq!(
+            writes.write(OWNER2, &attr(1, None), &value),
+            Some(IMStatusCode::Busy)
+        );
+
+        embassy_time::block_for(embassy_time::Duration::from_millis(50));
+
+        // The atomic write had timed out, so its attributes are free again
+        assert_eq!(writes.write(OWNER2, &attr(1, None), &value), None);
+        assert_eq!(writes.start_commit(OWNER1, 1, 0x201, &[1]), None);
+        assert_eq!(begin(&writes, OWNER2, &[1, 2], 10).0, IMStatusCode::Success);
+    }
+}
@@ -190,6 +190,11 @@ impl<T, E> ArrayAttributeRead<T, E> {
 }
 
 /// An enum for modeling writes to attributes whose type is an array.
+///
+/// Note that a single write of a list might be delivered to the handler as a sequence of writes:
+/// a `Replace` (with an empty array, in the case of a chunked write request) followed by one or more `Add`s.
+/// The Data Model treats such a sequence as a single list write and - should any of the writes fail -
+/// restores the list by issuing one more `Replace` with the value the list had before the sequence had started.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ArrayAttributeWrite<T, E> {
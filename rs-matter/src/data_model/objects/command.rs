@@ -26,6 +26,11 @@ impl Command {
             access,
         }
     }
+
+    /// Return `true` if the command can only be invoked as part of a timed interaction.
+    pub const fn is_timed(&self) -> bool {
+        self.access.contains(Access::TIMED_ONLY)
+    }
 }
 
 impl core::fmt::Display for Command {
@@ -53,6 +58,10 @@ pub struct CmdDetails<'a> {
     pub cluster_id: ClusterId,
     pub cmd_id: CmdId,
     pub wildcard: bool,
+    /// The reference of the command request, as provided by the client
+    ///
+    /// Only present in Invoke Requests with multiple commands.
+    pub command_ref: Option<u16>,
 }
 
 impl CmdDetails<'_> {
@@ -64,6 +73,16 @@ impl CmdDetails<'_> {
         )
     }
 
+    /// Return `true` if the command can only be invoked as part of a timed interaction.
+    pub fn is_timed(&self) -> bool {
+        self.node
+            .endpoint(self.endpoint_id)
+            .and_then(|endpoint| endpoint.cluster(self.cluster_id))
+            .and_then(|cluster| cluster.commands().find(|cmd| cmd.id == self.cmd_id))
+            .map(Command::is_timed)
+            .unwrap_or(false)
+    }
+
     pub fn success(&self, tracker: &CmdDataTracker) -> Option<CmdStatus> {
         if tracker.needs_status() {
             self.status(IMStatusCode::Success)
@@ -74,15 +93,18 @@ impl CmdDetails<'_> {
 
     pub fn status(&self, status: IMStatusCode) -> Option<CmdStatus> {
         if self.should_report(status) {
-            Some(CmdStatus::new(
-                CmdPath::new(
-                    Some(self.endpoint_id),
-                    Some(self.cluster_id),
-                    Some(self.cmd_id),
-                ),
-                status,
-                0,
-            ))
+            Some(
+                CmdStatus::new(
+                    CmdPath::new(
+                        Some(self.endpoint_id),
+                        Some(self.cluster_id),
+                        Some(self.cmd_id),
+                    ),
+                    status,
+                    0,
+                )
+                .with_command_ref(self.command_ref),
+            )
         } else {
             None
         }
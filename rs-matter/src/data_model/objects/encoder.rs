@@ -78,34 +78,35 @@ impl<'a, 'b, 'c> AttrDataEncoder<'a, 'b, 'c> {
         Ok(true)
     }
 
+    /// Write the provided data into the attribute and encode the status of the write operation.
+    ///
+    /// Returns the status of the write operation, even if it was not encoded
+    /// (i.e. a successful write to a wildcard path).
     pub(crate) async fn handle_write<T: DataModelHandler>(
         exchange: &Exchange<'_>,
-        item: &Result<(AttrDetails<'_>, TLVElement<'_>), AttrStatus>,
+        attr: &AttrDetails<'_>,
+        data: &TLVElement<'_>,
         handler: &T,
         tw: &mut TLVWriter<'_, '_>,
         notify: &dyn ChangeNotify,
-    ) -> Result<(), Error> {
-        let status = match item {
-            Ok((attr, data)) => {
-                let result = handler
-                    .write(&WriteContext::new(exchange, attr, data, notify))
-                    .await;
-                match result {
-                    Ok(()) => attr.status(IMStatusCode::Success)?,
-                    Err(error) => {
-                        error!("Error writing attribute: {}", error);
-                        attr.status(error.into())?
-                    }
-                }
+    ) -> Result<IMStatusCode, Error> {
+        let result = handler
+            .write(&WriteContext::new(exchange, attr, data, notify))
+            .await;
+
+        let status = match result {
+            Ok(()) => IMStatusCode::Success,
+            Err(error) => {
+                error!("Error writing attribute: {}", error);
+                error.into()
             }
-            Err(status) => Some(status.clone()),
         };
 
-        if let Some(status) = status {
-            status.to_tlv(&TagType::Anonymous, tw)?;
+        if let Some(attr_status) = attr.status(status)? {
+            attr_status.to_tlv(&TagType::Anonymous, tw)?;
         }
 
-        Ok(())
+        Ok(status)
     }
 
     pub fn new(attr: &AttrDetails, tw: &'a mut TLVWriter<'b, 'c>) -> Self {
@@ -223,6 +224,7 @@ impl CmdDataTracker {
 pub struct CmdDataEncoder<'a, 'b, 'c> {
     tracker: &'a mut CmdDataTracker,
     path: CmdPath,
+    command_ref: Option<u16>,
     tw: &'a mut TLVWriter<'b, 'c>,
 }
 
@@ -271,12 +273,13 @@ impl<'a, 'b, 'c> CmdDataEncoder<'a, 'b, 'c> {
         Self {
             tracker,
             path: cmd.path(),
+            command_ref: cmd.command_ref,
             tw,
         }
     }
 
     pub fn with_command(mut self, cmd: u32) -> Result<CmdDataWriter<'a, 'b, 'c>, Error> {
-        let mut writer = CmdDataWriter::new(self.tracker, self.tw);
+        let mut writer = CmdDataWriter::new(self.tracker, self.command_ref, self.tw);
 
         writer.start_struct(&TLVTag::Anonymous)?;
         writer.start_struct(&TLVTag::Context(CmdRespTag::Cmd as _))?;
@@ -291,6 +294,7 @@ impl<'a, 'b, 'c> CmdDataEncoder<'a, 'b, 'c> {
 
 pub struct CmdDataWriter<'a, 'b, 'c> {
     tracker: &'a mut CmdDataTracker,
+    command_ref: Option<u16>,
     tw: &'a mut TLVWriter<'b, 'c>,
     anchor: usize,
     completed: bool,
@@ -299,11 +303,16 @@ pub struct CmdDataWriter<'a, 'b, 'c> {
 impl<'a, 'b, 'c> CmdDataWriter<'a, 'b, 'c> {
     pub const TAG: TagType = TagType::Context(CmdDataTag::Data as _);
 
-    fn new(tracker: &'a mut CmdDataTracker, tw: &'a mut TLVWriter<'b, 'c>) -> Self {
+    fn new(
+        tracker: &'a mut CmdDataTracker,
+        command_ref: Option<u16>,
+        tw: &'a mut TLVWriter<'b, 'c>,
+    ) -> Self {
         let anchor = tw.get_tail();
 
         Self {
             tracker,
+            command_ref,
             tw,
             anchor,
             completed: false,
@@ -316,6 +325,11 @@ impl<'a, 'b, 'c> CmdDataWriter<'a, 'b, 'c> {
     }
 
     pub fn complete(mut self) -> Result<(), Error> {
+        if let Some(command_ref) = self.command_ref {
+            self.tw
+                .u16(&TLVTag::Context(CmdDataTag::CommandRef as _), command_ref)?;
+        }
+
         self.tw.end_container()?;
         self.tw.end_container()?;
 
@@ -29,6 +29,8 @@ pub struct Endpoint<'a> {
     pub device_types: &'a [DeviceType],
     /// The list of clusters associated with this endpoint.
     pub clusters: &'a [Cluster<'a>],
+    /// The list of semantic tags associated with this endpoint (see `Endpoint::with_tags`).
+    tags: &'a [SemanticTag<'a>],
 }
 
 impl<'a> Endpoint<'a> {
@@ -42,15 +44,72 @@ impl<'a> Endpoint<'a> {
             id,
             device_types,
             clusters,
+            tags: &[],
         }
     }
 
+    /// Return a copy of the endpoint with the provided semantic tags.
+    ///
+    /// The tags are reported by the `TagList` attribute of the Descriptor cluster, and are used for
+    /// disambiguating endpoints having the same device type (i.e. the buttons of a
+    /// multi-button switch or the gangs of a multi-gang device).
+    pub const fn with_tags(self, tags: &'a [SemanticTag<'a>]) -> Self {
+        Self { tags, ..self }
+    }
+
+    /// Return the semantic tags associated with this endpoint.
+    pub const fn tags(&self) -> &'a [SemanticTag<'a>] {
+        self.tags
+    }
+
     /// Return a reference to the cluster with the given ID, if it exists.
     pub fn cluster(&self, id: ClusterId) -> Option<&Cluster<'a>> {
         self.clusters.iter().find(|cluster| cluster.id == id)
     }
 }
 
+/// A type modeling a semantic tag of an endpoint in the Matter data model.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SemanticTag<'a> {
+    /// The manufacturer code for manufacturer-specific namespaces, or `None` for standard namespaces.
+    pub mfg_code: Option<u16>,
+    /// The ID of the namespace the tag belongs to.
+    pub namespace_id: u8,
+    /// The ID of the tag within the namespace.
+    pub tag: u8,
+    /// An optional human-readable label of the tag.
+    pub label: Option<&'a str>,
+}
+
+impl<'a> SemanticTag<'a> {
+    /// Create a new `SemanticTag` instance from a standard namespace.
+    pub const fn new(namespace_id: u8, tag: u8) -> Self {
+        Self {
+            mfg_code: None,
+            namespace_id,
+            tag,
+            label: None,
+        }
+    }
+
+    /// Return a copy of the tag with the provided manufacturer code.
+    pub const fn with_mfg_code(self, mfg_code: u16) -> Self {
+        Self {
+            mfg_code: Some(mfg_code),
+            ..self
+        }
+    }
+
+    /// Return a copy of the tag with the provided label.
+    pub const fn with_label(self, label: &'a str) -> Self {
+        Self {
+            label: Some(label),
+            ..self
+        }
+    }
+}
+
 impl core::fmt::Display for Endpoint<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "clusters:[")?;
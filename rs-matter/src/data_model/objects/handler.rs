@@ -21,7 +21,9 @@ use crate::{
     transport::exchange::Exchange,
 };
 
-use super::{AttrDataEncoder, AttrDetails, ClusterId, CmdDataEncoder, CmdDetails, EndptId};
+use super::{
+    AtomicWrite, AttrDataEncoder, AttrDetails, ClusterId, CmdDataEncoder, CmdDetails, EndptId,
+};
 
 pub use asynch::*;
 
@@ -127,6 +129,7 @@ pub struct InvokeContext<'a> {
     cmd: &'a CmdDetails<'a>,
     data: &'a TLVElement<'a>,
     notify: &'a dyn ChangeNotify,
+    atomic_write: Option<&'a AtomicWrite<'a>>,
 }
 
 impl<'a> InvokeContext<'a> {
@@ -143,9 +146,17 @@ impl<'a> InvokeContext<'a> {
             cmd,
             data,
             notify,
+            atomic_write: None,
         }
     }
 
+    /// Attach the pending values of an atomic write which is being committed.
+    #[inline(always)]
+    pub(crate) const fn with_atomic_write(mut self, atomic_write: &'a AtomicWrite<'a>) -> Self {
+        self.atomic_write = Some(atomic_write);
+        self
+    }
+
     /// Return the exchange object that is associated with this invoke operation.
     #[inline(always)]
     pub fn exchange(&self) -> &Exchange<'_> {
@@ -164,6 +175,16 @@ impl<'a> InvokeContext<'a> {
         self.data
     }
 
+    /// Return the pending values of the atomic write which is being committed.
+    ///
+    /// Only available when the invoked command is an `AtomicRequest` command
+    /// with a `CommitWrite` request type, which is processed by the Data Model itself
+    /// up until the point where the pending values need to be validated and committed.
+    #[inline(always)]
+    pub fn atomic_write(&self) -> Option<&AtomicWrite<'_>> {
+        self.atomic_write
+    }
+
     /// Notify that the cluster has changed.
     #[inline(always)]
     pub fn notify_changed(&self) {
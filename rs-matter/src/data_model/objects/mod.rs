@@ -16,6 +16,9 @@
  */
 use crate::tlv::ToTLV;
 
+mod atomic;
+pub use atomic::*;
+
 mod attribute;
 pub use attribute::*;
 
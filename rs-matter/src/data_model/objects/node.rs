@@ -15,6 +15,7 @@
  *    limitations under the License.
  */
 
+use core::cell::Cell;
 use core::fmt;
 
 use crate::acl::Accessor;
@@ -28,7 +29,14 @@ use crate::interaction_model::messages::msg::{InvReqRef, WriteReqRef};
 use crate::interaction_model::messages::GenericPath;
 use crate::tlv::{TLVArray, TLVElement};
 
-use super::{AttrDetails, ClusterId, CmdDetails, EndptId};
+use super::{AttrDetails, AttrId, ClusterId, CmdDetails, EndptId};
+
+/// The concrete path of a list attribute for which write access had already been granted
+/// at the beginning of a list write spanning multiple `AttributeDataIB`s, and potentially
+/// multiple chunks of a chunked write request.
+///
+/// See `Node::write` for details.
+pub type WriteGrant = Cell<Option<(EndptId, ClusterId, AttrId)>>;
 
 /// The main Matter metadata type describing a Matter Node.
 #[derive(Debug, Clone)]
@@ -88,11 +96,17 @@ impl<'a> Node<'a> {
     /// As part of the expansion, the method will check whether the attributes are
     /// accessible by the accessor and filter out the inaccessible ones (wildcard writes)
     /// or report an error status for the non-wildcard ones.
+    ///
+    /// The access check is skipped for the concrete path recorded in `grant` (if any).
+    /// This is necessary for list writes which start with replacing the list with an
+    /// empty one and then continue with appending the list items, as the replace might
+    /// revoke the access rights of the accessor when the list being written is the ACL list itself.
     #[allow(clippy::type_complexity)]
     pub fn write<'m>(
         &'m self,
         req: &'m WriteReqRef,
         accessor: &'m Accessor<'m>,
+        grant: &'m WriteGrant,
     ) -> Result<
         impl Iterator<Item = Result<Result<(AttrDetails<'m>, TLVElement<'m>), AttrStatus>, Error>> + 'm,
         Error,
@@ -100,7 +114,11 @@ impl<'a> Node<'a> {
         Ok(PathExpander::new(
             self,
             accessor,
-            Some(req.write_requests()?.into_iter()),
+            Some(
+                req.write_requests()?
+                    .into_iter()
+                    .map(move |data| data.map(|data| AttrWriteData { data, grant })),
+            ),
         ))
     }
 
@@ -173,6 +191,8 @@ impl<'a, const N: usize> DynamicNode<'a, N> {
     }
 
     /// Remove an endpoint from the dynamic node.
+    ///
+    /// The order of the remaining endpoints is preserved.
     pub fn remove(&mut self, endpoint_id: u16) -> Option<Endpoint<'a>> {
         let index = self
             .endpoints
@@ -181,7 +201,7 @@ impl<'a, const N: usize> DynamicNode<'a, N> {
             .find_map(|(index, ep)| (ep.id == endpoint_id).then_some(index));
 
         if let Some(index) = index {
-            Some(self.endpoints.swap_remove(index))
+            Some(self.endpoints.remove(index))
         } else {
             None
         }
@@ -206,6 +226,14 @@ struct AttrReadPath<'a> {
     fabric_filtered: bool,
 }
 
+/// A helper type for `AttrData` that enriches it with the write-scope information
+/// of which concrete path (if any) had its write access already granted.
+#[derive(Debug)]
+struct AttrWriteData<'a> {
+    data: AttrData<'a>,
+    grant: &'a WriteGrant,
+}
+
 /// A helper type for `PathExpander` that captures what type of expansion is being done:
 /// Read requests, write requests, or invoke requests.
 #[derive(Debug)]
@@ -245,6 +273,12 @@ trait PathExpansionItem<'a> {
         leaf_id: u32,
     ) -> Result<Self::Expanded<'a>, Error>;
 
+    /// Return `true` if the access rights for the provided concrete path had already been
+    /// granted, and therefore should not be checked again.
+    fn granted(&self, _endpoint_id: EndptId, _cluster_id: ClusterId, _leaf_id: u32) -> bool {
+        false
+    }
+
     /// Convert the item into an error status if the expansion failed.
     fn into_status(self, status: IMStatusCode) -> Self::Status;
 }
@@ -286,15 +320,15 @@ impl<'a> PathExpansionItem<'a> for AttrReadPath<'a> {
     }
 }
 
-/// `PathExpansionItem` implementation for `AttrData` (attr write requests expansion).
-impl<'a> PathExpansionItem<'a> for AttrData<'a> {
+/// `PathExpansionItem` implementation for `AttrWriteData` (attr write requests expansion).
+impl<'a> PathExpansionItem<'a> for AttrWriteData<'a> {
     const OPERATION: Operation = Operation::Write;
 
     type Expanded<'n> = (AttrDetails<'n>, TLVElement<'n>);
     type Status = AttrStatus;
 
     fn path(&self) -> GenericPath {
-        self.path.to_gp()
+        self.data.path.to_gp()
     }
 
     fn expand(
@@ -311,20 +345,24 @@ impl<'a> PathExpansionItem<'a> for AttrData<'a> {
                 endpoint_id,
                 cluster_id,
                 attr_id: leaf_id as _,
-                wildcard: self.path.to_gp().is_wildcard(),
-                list_index: self.path.list_index.clone(),
+                wildcard: self.data.path.to_gp().is_wildcard(),
+                list_index: self.data.path.list_index.clone(),
                 fab_idx: accessor.fab_idx,
                 fab_filter: false,
-                dataver: self.data_ver,
+                dataver: self.data.data_ver,
             },
-            self.data.clone(),
+            self.data.data.clone(),
         );
 
         Ok(expanded)
     }
 
+    fn granted(&self, endpoint_id: EndptId, cluster_id: ClusterId, leaf_id: u32) -> bool {
+        self.grant.get() == Some((endpoint_id, cluster_id, leaf_id as _))
+    }
+
     fn into_status(self, status: IMStatusCode) -> Self::Status {
-        AttrStatus::new(&self.path.to_gp(), status, 0)
+        AttrStatus::new(&self.data.path.to_gp(), status, 0)
     }
 }
 
@@ -354,6 +392,7 @@ impl<'a> PathExpansionItem<'a> for CmdData<'a> {
                 cluster_id,
                 cmd_id: leaf_id,
                 wildcard: false,
+                command_ref: self.command_ref,
             },
             self.data.clone(),
         );
@@ -362,7 +401,7 @@ impl<'a> PathExpansionItem<'a> for CmdData<'a> {
     }
 
     fn into_status(self, status: IMStatusCode) -> Self::Status {
-        CmdStatus::new(self.path, status, 0)
+        CmdStatus::new(self.path, status, 0).with_command_ref(self.command_ref)
     }
 }
 
@@ -462,7 +501,15 @@ where
                             if path.leaf.is_none() || path.leaf == Some(leaf_id as _) {
                                 // Leaf found, check its access rights
 
-                                let check = if matches!(T::OPERATION, Operation::Invoke) {
+                                let granted = unwrap!(self.item.as_ref()).granted(
+                                    endpoint.id,
+                                    cluster.id,
+                                    leaf_id,
+                                );
+
+                                let check = if granted {
+                                    Ok(())
+                                } else if matches!(T::OPERATION, Operation::Invoke) {
                                     cluster.check_cmd_access(
                                         self.accessor,
                                         GenericPath::new(
@@ -0,0 +1,167 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Occupancy Sensing cluster and its handler.
+
+use crate::error::Error;
+use crate::utils::cell::RefCell;
+use crate::with;
+
+use super::measurement::push_state;
+use super::objects::{Cluster, Dataver, ReadContext};
+
+pub use crate::data_model::clusters::occupancy_sensing::*;
+
+/// A handler for the Occupancy Sensing Matter cluster.
+///
+/// The occupancy is pushed by the sensor driver with `OccupancySensingHandler::push_occupancy`.
+/// Any debouncing of the sensor (i.e. the occupied-to-unoccupied delay) is the responsibility of the driver.
+pub struct OccupancySensingHandler {
+    dataver: Dataver,
+    sensor_type: OccupancySensorTypeEnum,
+    occupied: RefCell<bool>,
+}
+
+impl OccupancySensingHandler {
+    /// Creates a new instance of `OccupancySensingHandler` with the given `Dataver` and type of the sensor.
+    ///
+    /// The sensor is initially unoccupied.
+    pub const fn new(dataver: Dataver, sensor_type: OccupancySensorTypeEnum) -> Self {
+        Self {
+            dataver,
+            sensor_type,
+            occupied: RefCell::new(false),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Return `true` if the sensed area is occupied.
+    pub fn occupied(&self) -> bool {
+        *self.occupied.borrow()
+    }
+
+    /// Push the occupancy of the sensed area.
+    ///
+    /// # Arguments
+    /// - `occupied`: `true` if the sensed area is occupied
+    /// - `notify`: A callback which is called when the occupancy changes, and which is expected
+    ///   to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub fn push_occupancy<F>(&self, occupied: bool, notify: F)
+    where
+        F: FnOnce(),
+    {
+        push_state(&self.occupied, occupied, &self.dataver, notify);
+    }
+
+    /// Return the value of the `Occupancy` attribute.
+    pub fn occupancy_bitmap(&self) -> OccupancyBitmap {
+        let mut occupancy = OccupancyBitmap::empty();
+        occupancy.set(OccupancyBitmap::OCCUPIED, self.occupied());
+
+        occupancy
+    }
+
+    /// Return the value of the `OccupancySensorTypeBitmap` attribute, as derived from the type of the sensor.
+    pub fn sensor_type_bitmap(&self) -> OccupancySensorTypeBitmap {
+        match self.sensor_type {
+            OccupancySensorTypeEnum::PIR => OccupancySensorTypeBitmap::PIR,
+            OccupancySensorTypeEnum::Ultrasonic => OccupancySensorTypeBitmap::ULTRASONIC,
+            OccupancySensorTypeEnum::PIRAndUltrasonic => {
+                OccupancySensorTypeBitmap::PIR | OccupancySensorTypeBitmap::ULTRASONIC
+            }
+            OccupancySensorTypeEnum::PhysicalContact => OccupancySensorTypeBitmap::PHYSICAL_CONTACT,
+        }
+    }
+}
+
+impl ClusterHandler for OccupancySensingHandler {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(3)
+        .with_attrs(with!(required))
+        .with_cmds(with!());
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn occupancy(&self, _ctx: &ReadContext) -> Result<OccupancyBitmap, Error> {
+        Ok(self.occupancy_bitmap())
+    }
+
+    fn occupancy_sensor_type(&self, _ctx: &ReadContext) -> Result<OccupancySensorTypeEnum, Error> {
+        Ok(self.sensor_type)
+    }
+
+    fn occupancy_sensor_type_bitmap(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<OccupancySensorTypeBitmap, Error> {
+        Ok(self.sensor_type_bitmap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::measurement::check_pushes;
+    use crate::data_model::objects::Dataver;
+
+    use super::*;
+
+    #[test]
+    fn test_push_occupancy() {
+        let handler = OccupancySensingHandler::new(Dataver::new(0), OccupancySensorTypeEnum::PIR);
+
+        let push = |occupied, notify: &dyn Fn()| handler.push_occupancy(occupied, notify);
+        let dataver = || handler.dataver();
+
+        assert_eq!(handler.occupancy_bitmap(), OccupancyBitmap::empty());
+
+        check_pushes(push, dataver, &[(true, true), (true, false)]);
+        assert_eq!(handler.occupancy_bitmap(), OccupancyBitmap::OCCUPIED);
+
+        check_pushes(push, dataver, &[(false, true)]);
+        assert_eq!(handler.occupancy_bitmap(), OccupancyBitmap::empty());
+    }
+
+    #[test]
+    fn test_sensor_type_bitmap() {
+        let bitmap = |sensor_type| {
+            OccupancySensingHandler::new(Dataver::new(0), sensor_type).sensor_type_bitmap()
+        };
+
+        assert_eq!(
+            bitmap(OccupancySensorTypeEnum::PIR),
+            OccupancySensorTypeBitmap::PIR
+        );
+        assert_eq!(
+            bitmap(OccupancySensorTypeEnum::PIRAndUltrasonic),
+            OccupancySensorTypeBitmap::PIR | OccupancySensorTypeBitmap::ULTRASONIC
+        );
+        assert_eq!(
+            bitmap(OccupancySensorTypeEnum::PhysicalContact),
+            OccupancySensorTypeBitmap::PHYSICAL_CONTACT
+        );
+    }
+}
@@ -17,31 +17,159 @@
 
 //! This module contains the implementation of the On/Off cluster and its handler.
 //!
-//! While this cluster is not necessary for the operation of `rs-matter`, this
-//! implementation is useful in examples and tests.
+//! The handler implements the Lighting feature of the cluster, i.e. the `GlobalSceneControl`,
+//! `OnTime`, `OffWaitTime` and `StartUpOnOff` attributes, as well as the `OffWithEffect`,
+//! `OnWithRecallGlobalScene` and `OnWithTimedOff` commands.
 
 use core::cell::Cell;
+use core::num::NonZeroU8;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::{Duration, Timer};
 
 use crate::error::{Error, ErrorCode};
+use crate::fabric::FabricMgr;
+use crate::tlv::{FromTLV, Nullable, TLVElement, TLVTag, TLVWrite, ToTLV};
+use crate::utils::cell::RefCell;
+use crate::utils::storage::WriteBuf;
+use crate::utils::sync::Notification;
 use crate::with;
 
-use super::objects::{Cluster, Dataver, InvokeContext, ReadContext};
+use super::objects::{Cluster, Dataver, InvokeContext, ReadContext, WriteContext};
+use super::scenes::{SceneAttributeValue, SceneAttributeValues, SceneExtension};
 
 pub use crate::data_model::clusters::on_off::*;
 
-/// A sample implementation of a handler for the On/Off Matter cluster.
-#[derive(Clone, Debug)]
-pub struct OnOffHandler {
+/// The resolution of the `OnTime` and `OffWaitTime` attributes, as per the Matter spec.
+const TIMER_TICK_MS: u64 = 100;
+
+/// How often - in timer ticks, i.e. once per second - the countdown of the `OnTime` and `OffWaitTime` timers
+/// is reported to the subscribers. Timers reaching zero are reported right away.
+const TIMER_REPORT_TICKS: u32 = 10;
+
+/// A trait for the hardware (or the driver) of an On/Off device.
+///
+/// The `OnOffHandler` calls into it whenever the state of the device needs to change.
+pub trait OnOffHooks {
+    /// Switch the device on or off.
+    fn on_off_changed(&self, on: bool);
+
+    /// Run the effect requested with an `OffWithEffect` command.
+    ///
+    /// Called right before the device is switched off with `on_off_changed(false)`.
+    /// The default implementation does nothing, i.e. the device is switched off without an effect.
+    fn off_with_effect(&self, effect: EffectIdentifierEnum, variant: u8) {
+        let _ = (effect, variant);
+    }
+}
+
+impl<T> OnOffHooks for &T
+where
+    T: OnOffHooks,
+{
+    fn on_off_changed(&self, on: bool) {
+        (*self).on_off_changed(on)
+    }
+
+    fn off_with_effect(&self, effect: EffectIdentifierEnum, variant: u8) {
+        (*self).off_with_effect(effect, variant)
+    }
+}
+
+/// A no-op implementation for devices that only need to observe the state of the cluster with `OnOffHandler::get`.
+impl OnOffHooks for () {
+    fn on_off_changed(&self, _on: bool) {}
+}
+
+/// The coupling of the On/Off cluster with a Level Control cluster on the same endpoint,
+/// as per the "OnOff-with-level" dependency rules of the Matter spec.
+pub trait LevelControlCoupling {
+    /// Called when an On/Off cluster command (or the expiry of the `OnTime` timer) switches the device on or off.
+    ///
+    /// Return `true` if the Level Control cluster takes over the switching, in which case it should set
+    /// the `OnOff` attribute itself with `OnOffHandler::set` - immediately when switching on (before moving from the
+    /// minimum level to the on level), or once the level had reached its minimum when switching off.
+    ///
+    /// Return `false` to have the `OnOff` attribute set by the On/Off cluster right away.
+    fn on_off_command(&self, on: bool) -> bool;
+}
+
+/// The coupling of the On/Off cluster with a Scenes cluster on the same endpoint, used by the
+/// `OffWithEffect` and `OnWithRecallGlobalScene` commands.
+///
+/// The fabric manager is provided so that the scenes of removed fabrics can be pruned first.
+pub trait GlobalSceneCoupling {
+    /// Store the current state of the endpoint in the global scene of the given fabric.
+    fn store_global_scene(&self, fabric_mgr: &FabricMgr, fab_idx: NonZeroU8);
+
+    /// Recall the global scene of the given fabric.
+    ///
+    /// Return `false` if the fabric has no global scene.
+    fn recall_global_scene(&self, fabric_mgr: &FabricMgr, fab_idx: NonZeroU8) -> bool;
+}
+
+/// The state of the On/Off cluster.
+struct OnOffState {
+    on: bool,
+    global_scene_control: bool,
+    on_time: u16,
+    off_wait_time: u16,
+    start_up_on_off: Option<StartUpOnOffEnum>,
+    changed: bool,
+}
+
+impl OnOffState {
+    const fn new() -> Self {
+        Self {
+            on: false,
+            global_scene_control: true,
+            on_time: 0,
+            off_wait_time: 0,
+            start_up_on_off: None,
+            changed: false,
+        }
+    }
+
+    /// Return `true` if the `OnTime` or the `OffWaitTime` timer is running.
+    fn timer_running(&self) -> bool {
+        self.on && self.on_time > 0 || !self.on && self.off_wait_time > 0
+    }
+}
+
+/// A handler for the On/Off Matter cluster, implementing the Lighting feature.
+///
+/// The handler drives the device via the `OnOffHooks` trait and - for dimmable devices - coordinates with the
+/// Level Control cluster via the `LevelControlCoupling` trait. The global scene used by the `OffWithEffect` and
+/// `OnWithRecallGlobalScene` commands is stored and recalled via the `GlobalSceneCoupling` trait.
+///
+/// The `OnTime` and `OffWaitTime` timers are only running while the `OnOffHandler::run` future is polled.
+///
+/// The `OnOff` and `StartUpOnOff` attributes should be persisted (see `OnOffHandler::load` and `OnOffHandler::store`),
+/// so that the `StartUpOnOff` behavior can be applied when the device boots.
+pub struct OnOffHandler<'a> {
     dataver: Dataver,
-    on: Cell<bool>,
+    state: RefCell<OnOffState>,
+    hooks: &'a dyn OnOffHooks,
+    level_control: Cell<Option<&'a dyn LevelControlCoupling>>,
+    scenes: Cell<Option<&'a dyn GlobalSceneCoupling>>,
+    timer_changed: Notification<NoopRawMutex>,
+    persist_state_changed: Notification<NoopRawMutex>,
 }
 
-impl OnOffHandler {
+impl<'a> OnOffHandler<'a> {
     /// Creates a new instance of `OnOffHandler` with the given `Dataver`.
+    ///
+    /// The device is initially off, and no hooks are called.
     pub const fn new(dataver: Dataver) -> Self {
         Self {
             dataver,
-            on: Cell::new(false),
+            state: RefCell::new(OnOffState::new()),
+            hooks: &(),
+            level_control: Cell::new(None),
+            scenes: Cell::new(None),
+            timer_changed: Notification::new(),
+            persist_state_changed: Notification::new(),
         }
     }
 
@@ -50,25 +178,375 @@ impl OnOffHandler {
         HandlerAdaptor(self)
     }
 
+    /// Set the hooks which drive the hardware of the device.
+    ///
+    /// Should be called before `OnOffHandler::load`, so that the `StartUpOnOff` behavior is applied to the hardware.
+    pub fn set_hooks(&mut self, hooks: &'a dyn OnOffHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Couple the handler with the Level Control cluster handler of the same endpoint.
+    pub fn set_level_control(&self, level_control: &'a dyn LevelControlCoupling) {
+        self.level_control.set(Some(level_control));
+    }
+
+    /// Couple the handler with the Scenes cluster handler of the same endpoint.
+    pub fn set_scenes(&self, scenes: &'a dyn GlobalSceneCoupling) {
+        self.scenes.set(Some(scenes));
+    }
+
     /// Return the current state of the On/Off attribute.
     pub fn get(&self) -> bool {
-        self.on.get()
+        self.state.borrow().on
     }
 
-    /// Set the On/Off attribute to the given value and notify potential subscribers.
+    /// Set the On/Off attribute to the given value and call the hooks.
+    ///
+    /// Unlike the On/Off cluster commands, this method neither touches the `OnTime` / `OffWaitTime` timers,
+    /// nor does it notify the Level Control cluster. As such, it is suitable for reflecting state changes triggered
+    /// locally (i.e. by the HAL) and for use by the Level Control cluster itself.
+    ///
+    /// The caller is expected to notify the subscriptions.
     pub fn set(&self, on: bool) {
-        if self.on.get() != on {
-            self.on.set(on);
-            self.dataver.changed();
+        {
+            let mut state = self.state.borrow_mut();
+
+            if state.on == on {
+                return;
+            }
+
+            state.on = on;
+            state.changed = true;
+        }
+
+        self.hooks.on_off_changed(on);
+
+        self.dataver.changed();
+        self.timer_changed.notify();
+        self.persist_state_changed.notify();
+    }
+
+    /// Run the `OnTime` and `OffWaitTime` timers.
+    ///
+    /// # Arguments
+    /// - `notify`: A callback which is called whenever the timers change the state of the cluster,
+    ///   and which is expected to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`).
+    ///   While a timer is counting down, the callback is called at most once per second, and then when the timer
+    ///   reaches zero.
+    pub async fn run<F>(&self, notify: F) -> Result<(), Error>
+    where
+        F: Fn(),
+    {
+        let mut ticks = 0;
+
+        loop {
+            if self.state.borrow().timer_running() {
+                let tick = Timer::after(Duration::from_millis(TIMER_TICK_MS));
+
+                if let Either::First(_) = select(tick, self.timer_changed.wait()).await {
+                    ticks += 1;
+
+                    if self.tick() || ticks >= TIMER_REPORT_TICKS {
+                        ticks = 0;
+                        notify();
+                    }
+                }
+            } else {
+                ticks = 0;
+
+                self.timer_changed.wait().await;
+            }
+        }
+    }
+
+    /// Load the persisted `OnOff` and `StartUpOnOff` attributes from a byte slice,
+    /// and apply the `StartUpOnOff` behavior.
+    ///
+    /// Should be called once, when the device boots.
+    ///
+    /// # Arguments
+    /// - `data`: The byte slice to load the state from
+    pub fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let root = TLVElement::new(data);
+        let seq = root.structure()?;
+
+        let persisted_on = seq.ctx(0)?.bool()?;
+        let start_up_on_off = Nullable::<StartUpOnOffEnum>::from_tlv(&seq.ctx(1)?)?.into_option();
+
+        let on = match start_up_on_off {
+            None => persisted_on,
+            Some(StartUpOnOffEnum::Off) => false,
+            Some(StartUpOnOffEnum::On) => true,
+            Some(StartUpOnOffEnum::Toggle) => !persisted_on,
+        };
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.on = on;
+            state.start_up_on_off = start_up_on_off;
+            state.changed = on != persisted_on;
+        }
+
+        // Always call the hooks, as the hardware might not be in the restored state yet,
+        // even if the latter is the initial (off) state of the handler
+        self.hooks.on_off_changed(on);
+
+        self.dataver.changed();
+        self.timer_changed.notify();
+        self.persist_state_changed.notify();
+
+        Ok(())
+    }
+
+    /// Store the `OnOff` and `StartUpOnOff` attributes into a byte slice.
+    ///
+    /// # Arguments
+    /// - `buf`: The byte slice to store the state into
+    ///
+    /// Returns `Ok(None)` if the state has not changed, `Ok(Some(data))` if the state has changed
+    /// where `data` is the sub-slice of the buffer that contains the data to be persisted
+    pub fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        let mut state = self.state.borrow_mut();
+
+        if !state.changed {
+            return Ok(None);
         }
+
+        let mut wb = WriteBuf::new(buf);
+
+        wb.start_struct(&TLVTag::Anonymous)?;
+        wb.bool(&TLVTag::Context(0), state.on)?;
+        Nullable::new(state.start_up_on_off).to_tlv(&TLVTag::Context(1), &mut wb)?;
+        wb.end_container()?;
+
+        state.changed = false;
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    /// Return `true` if the state has changed and needs to be persisted.
+    pub fn changed(&self) -> bool {
+        self.state.borrow().changed
+    }
+
+    /// Wait for the state to be changed in a way that requires persisting.
+    pub async fn wait_persist(&self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+
+            self.persist_state_changed.wait().await;
+        }
+    }
+
+    /// Switch the device on or off as a result of an On/Off cluster command,
+    /// letting the Level Control cluster take over, if it is coupled.
+    fn switch(&self, on: bool) {
+        let handled = self
+            .level_control
+            .get()
+            .map(|level_control| level_control.on_off_command(on))
+            .unwrap_or(false);
+
+        if !handled {
+            self.set(on);
+        }
+    }
+
+    fn on(&self) {
+        {
+            let mut state = self.state.borrow_mut();
+
+            if state.on_time == 0 {
+                state.off_wait_time = 0;
+            }
+
+            state.global_scene_control = true;
+        }
+
+        self.switch(true);
+    }
+
+    fn off(&self) {
+        self.state.borrow_mut().on_time = 0;
+
+        self.switch(false);
+    }
+
+    fn toggle(&self) {
+        if self.get() {
+            self.off();
+        } else {
+            self.on();
+        }
+    }
+
+    fn off_with_effect(
+        &self,
+        fabric: Option<(&FabricMgr, NonZeroU8)>,
+        effect: EffectIdentifierEnum,
+        variant: u8,
+    ) {
+        let store_global_scene = {
+            let mut state = self.state.borrow_mut();
+
+            let global_scene_control = state.global_scene_control;
+
+            state.global_scene_control = false;
+            state.on_time = 0;
+
+            global_scene_control
+        };
+
+        if store_global_scene {
+            if let (Some(scenes), Some((fabric_mgr, fab_idx))) = (self.scenes.get(), fabric) {
+                scenes.store_global_scene(fabric_mgr, fab_idx);
+            }
+        }
+
+        if self.get() {
+            self.hooks.off_with_effect(effect, variant);
+        }
+
+        self.set(false);
     }
+
+    fn on_with_recall_global_scene(&self, fabric: Option<(&FabricMgr, NonZeroU8)>) {
+        {
+            let mut state = self.state.borrow_mut();
+
+            if state.global_scene_control {
+                return;
+            }
+
+            state.global_scene_control = true;
+
+            if state.on_time == 0 {
+                state.off_wait_time = 0;
+            }
+        }
+
+        let recalled = match (self.scenes.get(), fabric) {
+            (Some(scenes), Some((fabric_mgr, fab_idx))) => {
+                scenes.recall_global_scene(fabric_mgr, fab_idx)
+            }
+            _ => false,
+        };
+
+        // Without a global scene, just switch on
+        if !recalled {
+            self.switch(true);
+        }
+    }
+
+    fn on_with_timed_off(&self, control: OnOffControlBitmap, on_time: u16, off_wait_time: u16) {
+        let on = {
+            let mut state = self.state.borrow_mut();
+
+            if control.contains(OnOffControlBitmap::ACCEPT_ONLY_WHEN_ON) && !state.on {
+                return;
+            }
+
+            if state.off_wait_time > 0 && !state.on {
+                state.off_wait_time = state.off_wait_time.min(off_wait_time);
+                false
+            } else {
+                state.on_time = state.on_time.max(on_time);
+                state.off_wait_time = off_wait_time;
+                true
+            }
+        };
+
+        if on {
+            self.switch(true);
+        }
+
+        self.timer_changed.notify();
+    }
+
+    /// Process one tick (1/10th of a second) of the `OnTime` and `OffWaitTime` timers.
+    ///
+    /// Return `true` if a running timer had reached zero.
+    fn tick(&self) -> bool {
+        let (expired, reached_zero) = {
+            let mut state = self.state.borrow_mut();
+
+            if state.on && state.on_time > 0 {
+                state.on_time -= 1;
+
+                if state.on_time == 0 {
+                    state.off_wait_time = 0;
+                    (true, true)
+                } else {
+                    (false, false)
+                }
+            } else if !state.on && state.off_wait_time > 0 {
+                state.off_wait_time -= 1;
+                (false, state.off_wait_time == 0)
+            } else {
+                return false;
+            }
+        };
+
+        self.dataver.changed();
+
+        if expired {
+            self.switch(false);
+        }
+
+        reached_zero
+    }
+}
+
+impl SceneExtension for OnOffHandler<'_> {
+    fn cluster_id(&self) -> u32 {
+        Self::CLUSTER.id
+    }
+
+    fn store_scene(&self, values: &mut SceneAttributeValues) -> Result<(), Error> {
+        values
+            .push(SceneAttributeValue::new(
+                AttributeId::OnOff as _,
+                self.get() as _,
+            ))
+            .map_err(|_| ErrorCode::NoSpace.into())
+    }
+
+    fn recall_scene(
+        &self,
+        values: &[SceneAttributeValue],
+        _transition_time: Duration,
+    ) -> Result<(), Error> {
+        if let Some(on) = SceneAttributeValue::find(values, AttributeId::OnOff as _) {
+            self.set(on != 0);
+        }
+
+        Ok(())
+    }
+}
+
+/// Return the index of the fabric on which the command was invoked, if any.
+fn accessing_fabric(ctx: &InvokeContext) -> Result<Option<NonZeroU8>, Error> {
+    ctx.exchange()
+        .with_session(|sess| Ok(NonZeroU8::new(sess.get_local_fabric_idx())))
 }
 
-impl ClusterHandler for OnOffHandler {
+impl ClusterHandler for OnOffHandler<'_> {
     const CLUSTER: Cluster<'static> = FULL_CLUSTER
-        .with_revision(1)
-        .with_attrs(with!(required))
-        .with_cmds(with!(CommandId::On | CommandId::Off | CommandId::Toggle));
+        .with_revision(6)
+        .with_features(Feature::LIGHTING.bits())
+        .with_attrs(with!(
+            required;
+            AttributeId::GlobalSceneControl
+                | AttributeId::OnTime
+                | AttributeId::OffWaitTime
+                | AttributeId::StartUpOnOff
+        ))
+        .with_cmds(with!(all));
 
     fn dataver(&self) -> u32 {
         self.dataver.get()
@@ -79,41 +557,326 @@ impl ClusterHandler for OnOffHandler {
     }
 
     fn on_off(&self, _ctx: &ReadContext) -> Result<bool, Error> {
-        Ok(self.on.get())
+        Ok(self.get())
     }
 
-    fn handle_off(&self, _ctx: &InvokeContext) -> Result<(), Error> {
-        self.set(false);
+    fn global_scene_control(&self, _ctx: &ReadContext) -> Result<bool, Error> {
+        Ok(self.state.borrow().global_scene_control)
+    }
+
+    fn on_time(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().on_time)
+    }
+
+    fn off_wait_time(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.state.borrow().off_wait_time)
+    }
+
+    fn start_up_on_off(&self, _ctx: &ReadContext) -> Result<Nullable<StartUpOnOffEnum>, Error> {
+        Ok(Nullable::new(self.state.borrow().start_up_on_off))
+    }
+
+    fn set_on_time(&self, ctx: &WriteContext, value: u16) -> Result<(), Error> {
+        self.state.borrow_mut().on_time = value;
+        self.timer_changed.notify();
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn set_off_wait_time(&self, ctx: &WriteContext, value: u16) -> Result<(), Error> {
+        self.state.borrow_mut().off_wait_time = value;
+        self.timer_changed.notify();
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn set_start_up_on_off(
+        &self,
+        ctx: &WriteContext,
+        value: Nullable<StartUpOnOffEnum>,
+    ) -> Result<(), Error> {
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.start_up_on_off = value.into_option();
+            state.changed = true;
+        }
+
+        self.persist_state_changed.notify();
+
+        ctx.notify_changed();
+
         Ok(())
     }
 
-    fn handle_on(&self, _ctx: &InvokeContext) -> Result<(), Error> {
-        self.set(true);
+    fn handle_off(&self, ctx: &InvokeContext) -> Result<(), Error> {
+        self.off();
+
+        ctx.notify_changed();
+
         Ok(())
     }
 
-    fn handle_toggle(&self, _ctx: &InvokeContext) -> Result<(), Error> {
-        self.set(!self.on.get());
+    fn handle_on(&self, ctx: &InvokeContext) -> Result<(), Error> {
+        self.on();
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_toggle(&self, ctx: &InvokeContext) -> Result<(), Error> {
+        self.toggle();
+
+        ctx.notify_changed();
+
         Ok(())
     }
 
     fn handle_off_with_effect(
         &self,
-        _ctx: &InvokeContext,
-        _request: OffWithEffectRequest,
+        ctx: &InvokeContext,
+        request: OffWithEffectRequest,
     ) -> Result<(), Error> {
-        Err(ErrorCode::InvalidCommand.into())
+        let fabric_mgr = ctx.exchange().matter().fabric_mgr.borrow();
+
+        self.off_with_effect(
+            accessing_fabric(ctx)?.map(|fab_idx| (&*fabric_mgr, fab_idx)),
+            request.effect_identifier()?,
+            request.effect_variant()?,
+        );
+
+        ctx.notify_changed();
+
+        Ok(())
     }
 
-    fn handle_on_with_recall_global_scene(&self, _ctx: &InvokeContext) -> Result<(), Error> {
-        Err(ErrorCode::InvalidCommand.into())
+    fn handle_on_with_recall_global_scene(&self, ctx: &InvokeContext) -> Result<(), Error> {
+        let fabric_mgr = ctx.exchange().matter().fabric_mgr.borrow();
+
+        self.on_with_recall_global_scene(
+            accessing_fabric(ctx)?.map(|fab_idx| (&*fabric_mgr, fab_idx)),
+        );
+
+        ctx.notify_changed();
+
+        Ok(())
     }
 
     fn handle_on_with_timed_off(
         &self,
-        _ctx: &InvokeContext,
-        _request: OnWithTimedOffRequest,
+        ctx: &InvokeContext,
+        request: OnWithTimedOffRequest,
     ) -> Result<(), Error> {
-        Err(ErrorCode::InvalidCommand.into())
+        self.on_with_timed_off(
+            request.on_off_control()?,
+            request.on_time()?,
+            request.off_wait_time()?,
+        );
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::data_model::objects::Dataver;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Hooks {
+        on: Cell<Option<bool>>,
+        effect: Cell<Option<EffectIdentifierEnum>>,
+    }
+
+    impl OnOffHooks for Hooks {
+        fn on_off_changed(&self, on: bool) {
+            self.on.set(Some(on));
+        }
+
+        fn off_with_effect(&self, effect: EffectIdentifierEnum, _variant: u8) {
+            self.effect.set(Some(effect));
+        }
+    }
+
+    /// A level control which defers switching off to the end of its (instantaneous) transition.
+    struct Level<'a> {
+        on_off: &'a OnOffHandler<'a>,
+        commands: Cell<usize>,
+    }
+
+    impl LevelControlCoupling for Level<'_> {
+        fn on_off_command(&self, on: bool) -> bool {
+            self.commands.set(self.commands.get() + 1);
+            self.on_off.set(on);
+
+            true
+        }
+    }
+
+    fn state(handler: &OnOffHandler) -> (bool, bool, u16, u16) {
+        let state = handler.state.borrow();
+
+        (
+            state.on,
+            state.global_scene_control,
+            state.on_time,
+            state.off_wait_time,
+        )
+    }
+
+    #[test]
+    fn test_on_off_toggle() {
+        let hooks = Hooks::default();
+
+        let mut handler = OnOffHandler::new(Dataver::new(0));
+        handler.set_hooks(&hooks);
+
+        handler.on();
+        assert!(handler.get());
+        assert_eq!(hooks.on.get(), Some(true));
+
+        handler.toggle();
+        assert!(!handler.get());
+        assert_eq!(hooks.on.get(), Some(false));
+
+        handler.toggle();
+        assert!(handler.get());
+    }
+
+    #[test]
+    fn test_on_with_timed_off() {
+        let handler = OnOffHandler::new(Dataver::new(0));
+
+        // Accept only when on
+        handler.on_with_timed_off(OnOffControlBitmap::ACCEPT_ONLY_WHEN_ON, 10, 20);
+        assert_eq!(state(&handler), (false, true, 0, 0));
+
+        handler.on_with_timed_off(OnOffControlBitmap::empty(), 2, 3);
+        assert_eq!(state(&handler), (true, true, 2, 3));
+
+        // OnTime is never shortened
+        handler.on_with_timed_off(OnOffControlBitmap::empty(), 1, 3);
+        assert_eq!(state(&handler), (true, true, 2, 3));
+
+        // The countdown itself is not reported...
+        assert!(!handler.tick());
+        assert_eq!(state(&handler), (true, true, 1, 3));
+
+        // ... unlike the expiry of OnTime
+        assert!(handler.tick());
+        assert_eq!(state(&handler), (false, true, 0, 0));
+
+        handler.on_with_timed_off(OnOffControlBitmap::empty(), 1, 5);
+        assert!(handler.tick());
+        assert_eq!(state(&handler), (false, true, 0, 0));
+
+        // OffWaitTime is running while off
+        handler.state.borrow_mut().off_wait_time = 5;
+        handler.on_with_timed_off(OnOffControlBitmap::empty(), 1, 2);
+        assert_eq!(state(&handler), (false, true, 0, 2));
+
+        assert!(!handler.tick());
+        assert!(handler.tick());
+        assert!(!handler.tick());
+        assert_eq!(state(&handler), (false, true, 0, 0));
+    }
+
+    #[test]
+    fn test_global_scene_control() {
+        let hooks = Hooks::default();
+
+        let mut handler = OnOffHandler::new(Dataver::new(0));
+        handler.set_hooks(&hooks);
+
+        handler.on_with_timed_off(OnOffControlBitmap::empty(), 10, 10);
+
+        handler.off_with_effect(None, EffectIdentifierEnum::DyingLight, 0);
+        assert_eq!(state(&handler), (false, false, 0, 10));
+        assert_eq!(hooks.effect.get(), Some(EffectIdentifierEnum::DyingLight));
+
+        handler.on_with_recall_global_scene(None);
+        assert_eq!(state(&handler), (true, true, 0, 0));
+
+        // Discarded, because the global scene is already recalled
+        handler.set(false);
+        handler.on_with_recall_global_scene(None);
+        assert!(!handler.get());
+    }
+
+    #[test]
+    fn test_level_control_coupling() {
+        let handler = OnOffHandler::new(Dataver::new(0));
+        let level = Level {
+            on_off: &handler,
+            commands: Cell::new(0),
+        };
+
+        handler.set_level_control(&level);
+
+        handler.on();
+        handler.off();
+        handler.toggle();
+        assert!(handler.get());
+        assert_eq!(level.commands.get(), 3);
+
+        // Local changes are not propagated to the level control
+        handler.set(false);
+        assert_eq!(level.commands.get(), 3);
+    }
+
+    #[test]
+    fn test_persist() {
+        let mut buf = [0; 32];
+
+        let handler = OnOffHandler::new(Dataver::new(0));
+        assert!(!handler.changed());
+        assert!(handler.store(&mut buf).unwrap().is_none());
+
+        handler.on();
+        assert!(handler.changed());
+
+        let data = handler.store(&mut buf).unwrap().unwrap().to_vec();
+        assert!(!handler.changed());
+
+        for (start_up_on_off, on) in [
+            (None, true),
+            (Some(StartUpOnOffEnum::Off), false),
+            (Some(StartUpOnOffEnum::On), true),
+            (Some(StartUpOnOffEnum::Toggle), false),
+        ] {
+            let hooks = Hooks::default();
+
+            let mut handler = OnOffHandler::new(Dataver::new(0));
+            handler.set_hooks(&hooks);
+
+            handler.load(&data).unwrap();
+            handler.state.borrow_mut().start_up_on_off = start_up_on_off;
+            handler.state.borrow_mut().changed = true;
+
+            let data = handler.store(&mut buf).unwrap().unwrap().to_vec();
+
+            let hooks = Hooks::default();
+
+            let mut handler = OnOffHandler::new(Dataver::new(0));
+            handler.set_hooks(&hooks);
+
+            handler.load(&data).unwrap();
+            assert_eq!(handler.get(), on);
+            // The hardware is switched to the restored state, even when it is off
+            assert_eq!(hooks.on.get(), Some(on));
+            assert_eq!(handler.state.borrow().start_up_on_off, start_up_on_off);
+            // The state needs to be persisted again, if it differs from the persisted (on) one
+            assert_eq!(handler.changed(), !on);
+        }
     }
 }
@@ -0,0 +1,755 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Power Source cluster and its handler.
+//!
+//! The handler implements the Wired, Battery, Rechargeable and Replaceable features of the cluster.
+
+use embassy_time::{Duration, Timer};
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{Nullable, TLVBuilderParent, ToTLVArrayBuilder, ToTLVBuilder, Utf8StrBuilder};
+use crate::utils::cell::RefCell;
+use crate::utils::storage::Vec;
+use crate::with;
+
+use super::objects::{
+    ArrayAttributeRead, Attribute, Cluster, Dataver, EndptId, Quality, ReadContext,
+};
+
+pub use crate::data_model::clusters::power_source::*;
+
+/// The maximum number of active battery faults.
+pub const MAX_BAT_FAULTS: usize = 3;
+
+/// The default `BatPercentRemaining` (in half-percent units) below which the charge level is Warning.
+pub const DEFAULT_WARNING_LEVEL: u8 = 40;
+/// The default `BatPercentRemaining` (in half-percent units) below which the charge level is Critical.
+pub const DEFAULT_CRITICAL_LEVEL: u8 = 10;
+
+/// The interval at which the battery monitor is polled by `PowerSourceHandler::run`.
+const BATTERY_POLL_INTERVAL_SECS: u64 = 60;
+
+/// The minimum change of the battery voltage (in mV) which is reported by `PowerSourceHandler::update`,
+/// so that a slowly discharging battery does not flood the subscribers with reports.
+const BAT_VOLTAGE_HYSTERESIS_MV: u32 = 50;
+
+/// The type of a power source.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerSourceType {
+    /// A wired (i.e. mains or USB) power source with the given current type.
+    Wired(WiredCurrentTypeEnum),
+    /// A battery power source.
+    Battery {
+        /// Whether the battery is rechargeable.
+        rechargeable: bool,
+        /// Whether the battery can be replaced by the user.
+        replaceable: bool,
+    },
+}
+
+impl PowerSourceType {
+    /// Return the metadata of the Power Source cluster for this type of power source.
+    ///
+    /// Should be used instead of `PowerSourceHandler::CLUSTER` on endpoints whose power source is
+    /// not a user-replaceable, non-rechargeable battery.
+    pub const fn cluster(&self) -> Cluster<'static> {
+        let features = match self {
+            Self::Wired(_) => Feature::WIRED.bits(),
+            Self::Battery {
+                rechargeable,
+                replaceable,
+            } => {
+                let mut features = Feature::BATTERY.bits();
+
+                if *rechargeable {
+                    features |= Feature::RECHARGEABLE.bits();
+                }
+
+                if *replaceable {
+                    features |= Feature::REPLACEABLE.bits();
+                }
+
+                features
+            }
+        };
+
+        FULL_CLUSTER
+            .with_revision(2)
+            .with_features(features)
+            .with_attrs(Self::with_attrs)
+            .with_cmds(with!())
+    }
+
+    /// Match the mandatory attributes, and the optional attributes implemented by the handler,
+    /// according to the features of the cluster.
+    fn with_attrs(attr: &Attribute, _revision: u16, features: u32) -> bool {
+        if !attr.quality.contains(Quality::OPTIONAL) {
+            return true;
+        }
+
+        let Ok(attr_id) = AttributeId::try_from(attr.id) else {
+            return false;
+        };
+
+        let feature = match attr_id {
+            AttributeId::WiredCurrentType
+            | AttributeId::WiredPresent
+            | AttributeId::ActiveWiredFaults => Feature::WIRED,
+            AttributeId::BatVoltage
+            | AttributeId::BatPercentRemaining
+            | AttributeId::BatChargeLevel
+            | AttributeId::BatReplacementNeeded
+            | AttributeId::BatReplaceability
+            | AttributeId::BatPresent
+            | AttributeId::ActiveBatFaults => Feature::BATTERY,
+            AttributeId::BatReplacementDescription | AttributeId::BatQuantity => {
+                Feature::REPLACEABLE
+            }
+            AttributeId::BatChargeState | AttributeId::BatFunctionalWhileCharging => {
+                Feature::RECHARGEABLE
+            }
+            _ => return false,
+        };
+
+        Feature::from_bits_truncate(features).contains(feature)
+    }
+}
+
+/// A trait for monitoring the battery of a power source.
+///
+/// The `PowerSourceHandler` polls it for the state of the battery, and calls into it whenever
+/// the `BatFaultChange` event of the cluster happens.
+///
+/// The events are only delivered to the monitor for now (see [Events](crate::data_model#events)).
+pub trait BatteryMonitor {
+    /// Return the voltage of the battery in mV, or `None` if it is unknown.
+    fn voltage(&self) -> Option<u32>;
+
+    /// Return the remaining charge of the battery in half-percent units (0 - 200),
+    /// or `None` if it is unknown.
+    fn percent_remaining(&self) -> Option<u8>;
+
+    /// Return `true` if the battery is present.
+    ///
+    /// The default implementation returns `true`.
+    fn present(&self) -> bool {
+        true
+    }
+
+    /// Return the charge state of a rechargeable battery.
+    ///
+    /// The default implementation returns `BatChargeStateEnum::Unknown`.
+    fn charge_state(&self) -> BatChargeStateEnum {
+        BatChargeStateEnum::Unknown
+    }
+
+    /// Return the active faults of the battery.
+    ///
+    /// The default implementation returns no faults.
+    fn active_faults(&self) -> Vec<BatFaultEnum, MAX_BAT_FAULTS> {
+        Vec::new()
+    }
+
+    /// Called when the active faults of the battery change (the `BatFaultChange` event).
+    fn bat_fault_change(&self, current: &[BatFaultEnum], previous: &[BatFaultEnum]) {
+        let _ = (current, previous);
+    }
+}
+
+impl<T> BatteryMonitor for &T
+where
+    T: BatteryMonitor,
+{
+    fn voltage(&self) -> Option<u32> {
+        (*self).voltage()
+    }
+
+    fn percent_remaining(&self) -> Option<u8> {
+        (*self).percent_remaining()
+    }
+
+    fn present(&self) -> bool {
+        (*self).present()
+    }
+
+    fn charge_state(&self) -> BatChargeStateEnum {
+        (*self).charge_state()
+    }
+
+    fn active_faults(&self) -> Vec<BatFaultEnum, MAX_BAT_FAULTS> {
+        (*self).active_faults()
+    }
+
+    fn bat_fault_change(&self, current: &[BatFaultEnum], previous: &[BatFaultEnum]) {
+        (*self).bat_fault_change(current, previous)
+    }
+}
+
+/// A no-op implementation for wired power sources, or for batteries whose state is unknown.
+impl BatteryMonitor for () {
+    fn voltage(&self) -> Option<u32> {
+        None
+    }
+
+    fn percent_remaining(&self) -> Option<u8> {
+        None
+    }
+}
+
+/// The state of the battery, as last read from the battery monitor.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct BatteryState {
+    voltage: Option<u32>,
+    percent_remaining: Option<u8>,
+    present: bool,
+    charge_state: BatChargeStateEnum,
+    active_faults: Vec<BatFaultEnum, MAX_BAT_FAULTS>,
+}
+
+impl BatteryState {
+    const fn new() -> Self {
+        Self {
+            voltage: None,
+            percent_remaining: None,
+            present: true,
+            charge_state: BatChargeStateEnum::Unknown,
+            active_faults: Vec::new(),
+        }
+    }
+
+    fn read(monitor: &dyn BatteryMonitor) -> Self {
+        Self {
+            voltage: monitor.voltage(),
+            percent_remaining: monitor.percent_remaining().map(|percent| percent.min(200)),
+            present: monitor.present(),
+            charge_state: monitor.charge_state(),
+            active_faults: monitor.active_faults(),
+        }
+    }
+
+    /// Return `true` if `self` differs from `other`, ignoring voltage changes
+    /// smaller than `BAT_VOLTAGE_HYSTERESIS_MV`.
+    fn differs(&self, other: &Self) -> bool {
+        let voltage_differs = match (self.voltage, other.voltage) {
+            (Some(voltage), Some(other_voltage)) => {
+                voltage.abs_diff(other_voltage) >= BAT_VOLTAGE_HYSTERESIS_MV
+            }
+            (voltage, other_voltage) => voltage != other_voltage,
+        };
+
+        voltage_differs
+            || self.percent_remaining != other.percent_remaining
+            || self.present != other.present
+            || self.charge_state != other.charge_state
+            || self.active_faults != other.active_faults
+    }
+}
+
+/// The state of the Power Source cluster.
+struct PowerSourceState {
+    status: PowerSourceStatusEnum,
+    battery: BatteryState,
+}
+
+/// A handler for the Power Source Matter cluster.
+///
+/// The state of a battery power source is read from a `BatteryMonitor` with `PowerSourceHandler::update`,
+/// which is called periodically by `PowerSourceHandler::run`.
+///
+/// The handler (and the endpoint hosting the cluster) should also be provided to the `PowerSourceConfigHandler`
+/// of the root endpoint, so that the endpoint is listed in its `Sources` attribute.
+pub struct PowerSourceHandler<'a> {
+    dataver: Dataver,
+    source_type: PowerSourceType,
+    order: u8,
+    description: &'a str,
+    endpoints: &'a [EndptId],
+    replacement_description: &'a str,
+    quantity: u8,
+    warning_level: u8,
+    critical_level: u8,
+    monitor: &'a dyn BatteryMonitor,
+    state: RefCell<PowerSourceState>,
+}
+
+impl<'a> PowerSourceHandler<'a> {
+    /// Creates a new instance of `PowerSourceHandler`.
+    ///
+    /// # Arguments
+    /// - `dataver`: The data version of the cluster
+    /// - `source_type`: The type of the power source; the metadata of the cluster should be `source_type.cluster()`
+    /// - `order`: The priority of the power source, where lower values mean higher priority
+    /// - `description`: A user-facing description of the power source (i.e. "Primary Battery")
+    /// - `endpoints`: The endpoints powered by the power source
+    pub const fn new(
+        dataver: Dataver,
+        source_type: PowerSourceType,
+        order: u8,
+        description: &'a str,
+        endpoints: &'a [EndptId],
+    ) -> Self {
+        Self {
+            dataver,
+            source_type,
+            order,
+            description,
+            endpoints,
+            replacement_description: "",
+            quantity: 1,
+            warning_level: DEFAULT_WARNING_LEVEL,
+            critical_level: DEFAULT_CRITICAL_LEVEL,
+            monitor: &(),
+            state: RefCell::new(PowerSourceState {
+                status: PowerSourceStatusEnum::Active,
+                battery: BatteryState::new(),
+            }),
+        }
+    }
+
+    /// Return the handler with the given description of the replacement battery (i.e. "CR2032")
+    /// and number of batteries.
+    pub const fn with_replacement(mut self, description: &'a str, quantity: u8) -> Self {
+        self.replacement_description = description;
+        self.quantity = quantity;
+
+        self
+    }
+
+    /// Return the handler with the given thresholds of the Warning and Critical charge levels,
+    /// in half-percent units of the remaining charge.
+    pub const fn with_charge_level_thresholds(mut self, warning: u8, critical: u8) -> Self {
+        self.warning_level = warning;
+        self.critical_level = critical;
+
+        self
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Set the monitor of the battery.
+    pub fn set_monitor(&mut self, monitor: &'a dyn BatteryMonitor) {
+        self.monitor = monitor;
+    }
+
+    /// Return the priority of the power source, where lower values mean higher priority.
+    pub fn order(&self) -> u8 {
+        self.order
+    }
+
+    /// Return the status of the power source.
+    pub fn status(&self) -> PowerSourceStatusEnum {
+        self.state.borrow().status
+    }
+
+    /// Set the status of the power source (i.e. when a backup power source becomes active).
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn set_status(&self, status: PowerSourceStatusEnum) {
+        let mut state = self.state.borrow_mut();
+
+        if state.status != status {
+            state.status = status;
+            self.dataver.changed();
+        }
+    }
+
+    /// Return the charge level of the battery, according to its remaining charge.
+    pub fn charge_level(&self) -> BatChargeLevelEnum {
+        match self.state.borrow().battery.percent_remaining {
+            Some(percent) if percent < self.critical_level => BatChargeLevelEnum::Critical,
+            Some(percent) if percent < self.warning_level => BatChargeLevelEnum::Warning,
+            _ => BatChargeLevelEnum::OK,
+        }
+    }
+
+    /// Return `true` if the battery needs to be replaced, i.e. it is critically low and cannot be recharged,
+    /// or it has faults.
+    pub fn replacement_needed(&self) -> bool {
+        let rechargeable = matches!(
+            self.source_type,
+            PowerSourceType::Battery {
+                rechargeable: true,
+                ..
+            }
+        );
+
+        (!rechargeable && self.charge_level() == BatChargeLevelEnum::Critical)
+            || !self.state.borrow().battery.active_faults.is_empty()
+    }
+
+    /// Read the state of the battery from the battery monitor.
+    ///
+    /// # Arguments
+    /// - `notify`: A callback which is called when the state of the battery changes, and which is expected
+    ///   to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub fn update<F>(&self, notify: F)
+    where
+        F: FnOnce(),
+    {
+        if matches!(self.source_type, PowerSourceType::Wired(_)) {
+            return;
+        }
+
+        let battery = BatteryState::read(self.monitor);
+
+        let previous = {
+            let mut state = self.state.borrow_mut();
+
+            if !state.battery.differs(&battery) {
+                return;
+            }
+
+            core::mem::replace(&mut state.battery, battery)
+        };
+
+        self.dataver.changed();
+        notify();
+
+        let state = self.state.borrow();
+
+        if state.battery.active_faults != previous.active_faults {
+            self.monitor
+                .bat_fault_change(&state.battery.active_faults, &previous.active_faults);
+        }
+    }
+
+    /// Periodically read the state of the battery from the battery monitor.
+    ///
+    /// # Arguments
+    /// - `notify`: A callback which is called when the state of the battery changes, and which is expected
+    ///   to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub async fn run<F>(&self, notify: F) -> Result<(), Error>
+    where
+        F: Fn(),
+    {
+        loop {
+            self.update(&notify);
+
+            Timer::after(Duration::from_secs(BATTERY_POLL_INTERVAL_SECS)).await;
+        }
+    }
+}
+
+impl ClusterHandler for PowerSourceHandler<'_> {
+    const CLUSTER: Cluster<'static> = PowerSourceType::Battery {
+        rechargeable: false,
+        replaceable: true,
+    }
+    .cluster();
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn status(&self, _ctx: &ReadContext) -> Result<PowerSourceStatusEnum, Error> {
+        Ok(PowerSourceHandler::status(self))
+    }
+
+    fn order(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(PowerSourceHandler::order(self))
+    }
+
+    fn description<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext,
+        out: Utf8StrBuilder<P>,
+    ) -> Result<P, Error> {
+        out.set(self.description)
+    }
+
+    fn wired_current_type(&self, _ctx: &ReadContext) -> Result<WiredCurrentTypeEnum, Error> {
+        match self.source_type {
+            PowerSourceType::Wired(current_type) => Ok(current_type),
+            _ => Err(ErrorCode::InvalidAction.into()),
+        }
+    }
+
+    fn wired_present(&self, _ctx: &ReadContext) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn active_wired_faults<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext,
+        builder: ArrayAttributeRead<
+            ToTLVArrayBuilder<P, WiredFaultEnum>,
+            ToTLVBuilder<P, WiredFaultEnum>,
+        >,
+    ) -> Result<P, Error> {
+        // Wired faults are not monitored
+        match builder {
+            ArrayAttributeRead::ReadAll(builder) => builder.end(),
+            ArrayAttributeRead::ReadOne(_, _) => Err(ErrorCode::ConstraintError.into()),
+        }
+    }
+
+    fn bat_voltage(&self, _ctx: &ReadContext) -> Result<Nullable<u32>, Error> {
+        Ok(Nullable::new(self.state.borrow().battery.voltage))
+    }
+
+    fn bat_percent_remaining(&self, _ctx: &ReadContext) -> Result<Nullable<u8>, Error> {
+        Ok(Nullable::new(self.state.borrow().battery.percent_remaining))
+    }
+
+    fn bat_charge_level(&self, _ctx: &ReadContext) -> Result<BatChargeLevelEnum, Error> {
+        Ok(self.charge_level())
+    }
+
+    fn bat_replacement_needed(&self, _ctx: &ReadContext) -> Result<bool, Error> {
+        Ok(self.replacement_needed())
+    }
+
+    fn bat_replaceability(&self, _ctx: &ReadContext) -> Result<BatReplaceabilityEnum, Error> {
+        Ok(match self.source_type {
+            PowerSourceType::Battery {
+                replaceable: true, ..
+            } => BatReplaceabilityEnum::UserReplaceable,
+            _ => BatReplaceabilityEnum::NotReplaceable,
+        })
+    }
+
+    fn bat_present(&self, _ctx: &ReadContext) -> Result<bool, Error> {
+        Ok(self.state.borrow().battery.present)
+    }
+
+    fn active_bat_faults<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext,
+        builder: ArrayAttributeRead<
+            ToTLVArrayBuilder<P, BatFaultEnum>,
+            ToTLVBuilder<P, BatFaultEnum>,
+        >,
+    ) -> Result<P, Error> {
+        let state = self.state.borrow();
+
+        match builder {
+            ArrayAttributeRead::ReadAll(mut builder) => {
+                for fault in &state.battery.active_faults {
+                    builder = builder.push(fault)?;
+                }
+
+                builder.end()
+            }
+            ArrayAttributeRead::ReadOne(index, builder) => {
+                let Some(fault) = state.battery.active_faults.get(index as usize) else {
+                    return Err(ErrorCode::ConstraintError.into());
+                };
+
+                builder.set(fault)
+            }
+        }
+    }
+
+    fn bat_replacement_description<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext,
+        out: Utf8StrBuilder<P>,
+    ) -> Result<P, Error> {
+        out.set(self.replacement_description)
+    }
+
+    fn bat_quantity(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(self.quantity)
+    }
+
+    fn bat_charge_state(&self, _ctx: &ReadContext) -> Result<BatChargeStateEnum, Error> {
+        Ok(self.state.borrow().battery.charge_state)
+    }
+
+    fn bat_functional_while_charging(&self, _ctx: &ReadContext) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn endpoint_list<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext,
+        builder: ArrayAttributeRead<ToTLVArrayBuilder<P, EndptId>, ToTLVBuilder<P, EndptId>>,
+    ) -> Result<P, Error> {
+        match builder {
+            ArrayAttributeRead::ReadAll(mut builder) => {
+                for ep_id in self.endpoints {
+                    builder = builder.push(ep_id)?;
+                }
+
+                builder.end()
+            }
+            ArrayAttributeRead::ReadOne(index, builder) => {
+                let Some(ep_id) = self.endpoints.get(index as usize) else {
+                    return Err(ErrorCode::ConstraintError.into());
+                };
+
+                builder.set(ep_id)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::data_model::objects::Dataver;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Monitor {
+        voltage: Cell<u32>,
+        percent: Cell<Option<u8>>,
+        fault: Cell<Option<BatFaultEnum>>,
+        fault_changes: Cell<usize>,
+    }
+
+    impl BatteryMonitor for Monitor {
+        fn voltage(&self) -> Option<u32> {
+            Some(self.voltage.get())
+        }
+
+        fn percent_remaining(&self) -> Option<u8> {
+            self.percent.get()
+        }
+
+        fn active_faults(&self) -> Vec<BatFaultEnum, MAX_BAT_FAULTS> {
+            self.fault.get().into_iter().collect()
+        }
+
+        fn bat_fault_change(&self, _current: &[BatFaultEnum], _previous: &[BatFaultEnum]) {
+            self.fault_changes.set(self.fault_changes.get() + 1);
+        }
+    }
+
+    fn has_attr(cluster: &Cluster, attr: AttributeId) -> bool {
+        cluster.attributes().any(|a| a.id == attr as u32)
+    }
+
+    #[test]
+    fn test_cluster() {
+        let wired = PowerSourceType::Wired(WiredCurrentTypeEnum::DC).cluster();
+        assert!(has_attr(&wired, AttributeId::WiredCurrentType));
+        assert!(!has_attr(&wired, AttributeId::BatChargeLevel));
+        assert!(has_attr(&wired, AttributeId::EndpointList));
+
+        let battery = PowerSourceHandler::CLUSTER;
+        assert!(has_attr(&battery, AttributeId::BatChargeLevel));
+        assert!(has_attr(&battery, AttributeId::BatQuantity));
+        assert!(!has_attr(&battery, AttributeId::BatChargeState));
+        assert!(!has_attr(&battery, AttributeId::WiredCurrentType));
+
+        let rechargeable = PowerSourceType::Battery {
+            rechargeable: true,
+            replaceable: false,
+        }
+        .cluster();
+        assert!(has_attr(&rechargeable, AttributeId::BatChargeState));
+        assert!(!has_attr(&rechargeable, AttributeId::BatQuantity));
+    }
+
+    #[test]
+    fn test_battery() {
+        let monitor = Monitor::default();
+
+        let mut handler = PowerSourceHandler::new(
+            Dataver::new(0),
+            PowerSourceType::Battery {
+                rechargeable: false,
+                replaceable: true,
+            },
+            0,
+            "Battery",
+            &[1],
+        )
+        .with_replacement("CR2032", 1);
+        handler.set_monitor(&monitor);
+
+        let notified = Cell::new(0);
+        let notify = || notified.set(notified.get() + 1);
+
+        handler.update(notify);
+        assert_eq!(notified.get(), 1);
+        assert_eq!(handler.charge_level(), BatChargeLevelEnum::OK);
+
+        // No changes
+        handler.update(notify);
+        assert_eq!(notified.get(), 1);
+
+        // Small voltage changes are not reported
+        monitor.voltage.set(BAT_VOLTAGE_HYSTERESIS_MV - 1);
+        handler.update(notify);
+        assert_eq!(notified.get(), 1);
+
+        monitor.voltage.set(BAT_VOLTAGE_HYSTERESIS_MV);
+        handler.update(notify);
+        assert_eq!(notified.get(), 2);
+
+        monitor.percent.set(Some(DEFAULT_WARNING_LEVEL - 1));
+        handler.update(notify);
+        assert_eq!(notified.get(), 3);
+        assert_eq!(handler.charge_level(), BatChargeLevelEnum::Warning);
+        assert!(!handler.replacement_needed());
+
+        monitor.percent.set(Some(DEFAULT_CRITICAL_LEVEL - 1));
+        handler.update(notify);
+        assert_eq!(handler.charge_level(), BatChargeLevelEnum::Critical);
+        assert!(handler.replacement_needed());
+
+        monitor.percent.set(Some(200));
+        monitor.fault.set(Some(BatFaultEnum::OverTemp));
+        handler.update(notify);
+        assert_eq!(handler.charge_level(), BatChargeLevelEnum::OK);
+        assert!(handler.replacement_needed());
+        assert_eq!(monitor.fault_changes.get(), 1);
+
+        monitor.fault.set(None);
+        handler.update(notify);
+        assert!(!handler.replacement_needed());
+        assert_eq!(monitor.fault_changes.get(), 2);
+        assert_eq!(notified.get(), 6);
+    }
+
+    #[test]
+    fn test_rechargeable() {
+        let monitor = Monitor::default();
+        monitor.percent.set(Some(0));
+
+        let mut handler = PowerSourceHandler::new(
+            Dataver::new(0),
+            PowerSourceType::Battery {
+                rechargeable: true,
+                replaceable: false,
+            },
+            0,
+            "Battery",
+            &[],
+        );
+        handler.set_monitor(&monitor);
+
+        handler.update(|| ());
+
+        // A depleted rechargeable battery needs to be charged, not replaced
+        assert_eq!(handler.charge_level(), BatChargeLevelEnum::Critical);
+        assert!(!handler.replacement_needed());
+    }
+}
@@ -0,0 +1,155 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Power Source Configuration cluster and its handler.
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{TLVBuilderParent, ToTLVArrayBuilder, ToTLVBuilder};
+use crate::with;
+
+use super::objects::{ArrayAttributeRead, Cluster, Dataver, EndptId, ReadContext};
+use super::power_source::PowerSourceHandler;
+
+pub use crate::data_model::clusters::power_source_configuration::*;
+
+/// A handler for the Power Source Configuration Matter cluster.
+///
+/// The `Sources` attribute lists the endpoints of the power sources of the node, sorted by the `Order`
+/// attribute of their Power Source clusters (i.e. highest priority first). Power sources with the same
+/// order are listed in the order in which they are provided to the handler.
+///
+/// The cluster should be installed on the root endpoint only (see `root_endpoint::with_power_source_config`).
+pub struct PowerSourceConfigHandler<'a> {
+    dataver: Dataver,
+    sources: &'a [(EndptId, &'a PowerSourceHandler<'a>)],
+}
+
+impl<'a> PowerSourceConfigHandler<'a> {
+    /// Creates a new instance of `PowerSourceConfigHandler`.
+    ///
+    /// # Arguments
+    /// - `dataver`: The data version of the cluster
+    /// - `sources`: The power sources of the node, as the endpoints hosting their Power Source clusters
+    ///   and the handlers of these clusters (which are then adapted with `power_source::HandlerAdaptor(&handler)`)
+    pub const fn new(
+        dataver: Dataver,
+        sources: &'a [(EndptId, &'a PowerSourceHandler<'a>)],
+    ) -> Self {
+        Self { dataver, sources }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Return the endpoints of the power sources, sorted by the order of the power sources.
+    pub fn sources(&self) -> impl Iterator<Item = EndptId> + '_ {
+        // Ties are broken by the index of the power source, so that the sorting is stable
+        let key = |index: usize| (self.sources[index].1.order(), index);
+
+        // Selection sort, as the power sources are few and this does not need a buffer
+        let mut last = None;
+
+        core::iter::from_fn(move || {
+            let next = (0..self.sources.len())
+                .filter(|index| last.map(|last| key(*index) > last).unwrap_or(true))
+                .min_by_key(|index| key(*index))?;
+
+            last = Some(key(next));
+
+            Some(self.sources[next].0)
+        })
+    }
+}
+
+impl ClusterHandler for PowerSourceConfigHandler<'_> {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(1)
+        .with_attrs(with!(required))
+        .with_cmds(with!());
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn sources<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext,
+        builder: ArrayAttributeRead<ToTLVArrayBuilder<P, EndptId>, ToTLVBuilder<P, EndptId>>,
+    ) -> Result<P, Error> {
+        let mut ep_ids = PowerSourceConfigHandler::sources(self);
+
+        match builder {
+            ArrayAttributeRead::ReadAll(mut builder) => {
+                for id in ep_ids {
+                    builder = builder.push(&id)?;
+                }
+
+                builder.end()
+            }
+            ArrayAttributeRead::ReadOne(index, builder) => {
+                let Some(ep_id) = ep_ids.nth(index as usize) else {
+                    return Err(ErrorCode::ConstraintError.into());
+                };
+
+                builder.set(&ep_id)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::objects::Dataver;
+    use crate::data_model::power_source::{PowerSourceType, WiredCurrentTypeEnum};
+
+    use super::*;
+
+    const BATTERY: PowerSourceType = PowerSourceType::Battery {
+        rechargeable: false,
+        replaceable: true,
+    };
+
+    #[test]
+    fn test_sources_order() {
+        let mains = PowerSourceHandler::new(
+            Dataver::new(0),
+            PowerSourceType::Wired(WiredCurrentTypeEnum::AC),
+            0,
+            "Mains",
+            &[],
+        );
+        let primary = PowerSourceHandler::new(Dataver::new(0), BATTERY, 1, "Primary Battery", &[]);
+        let backup = PowerSourceHandler::new(Dataver::new(0), BATTERY, 2, "Backup Battery", &[]);
+        let other = PowerSourceHandler::new(Dataver::new(0), BATTERY, 1, "Other Battery", &[]);
+
+        let sources = [(3, &backup), (2, &primary), (1, &mains), (4, &other)];
+        let handler = PowerSourceConfigHandler::new(Dataver::new(0), &sources);
+
+        // Sorted by order, with the ties kept in the order of the provided power sources
+        assert!(handler.sources().eq([1, 2, 4, 3]));
+        assert_eq!(handler.sources().nth(2), Some(4));
+
+        let handler = PowerSourceConfigHandler::new(Dataver::new(0), &[]);
+        assert_eq!(handler.sources().next(), None);
+    }
+}
@@ -21,6 +21,8 @@ use crate::{devices, handler_chain_type};
 use super::basic_info::{self, BasicInfoHandler, ClusterHandler as _};
 use super::networks::eth::{EthNetCtl, EthNetwork};
 use super::objects::{Async, ChainedHandler, Dataver, Endpoint, EndptId, EpClMatcher};
+use super::power_source::PowerSourceHandler;
+use super::power_source_config::{self, ClusterHandler as _, PowerSourceConfigHandler};
 use super::sdm::adm_comm::{self, AdminCommHandler, ClusterHandler as _};
 use super::sdm::eth_diag::{self, ClusterHandler as _, EthDiagHandler};
 use super::sdm::gen_comm::{self, ClusterHandler as _, CommPolicy, GenCommHandler};
@@ -33,15 +35,15 @@ use super::sdm::noc::{self, ClusterHandler as _, NocHandler};
 use super::sdm::thread_diag::{self, ClusterHandler as _, ThreadDiag, ThreadDiagHandler};
 use super::sdm::wifi_diag::{self, ClusterHandler as _, WifiDiag, WifiDiagHandler};
 use super::system_model::acl::{self, AclHandler, ClusterHandler as _};
-use super::system_model::desc::{self, ClusterHandler as _, DescHandler};
+use super::system_model::desc::{self, ClusterHandler as _, DescHandler, PartsMatcher};
 
 /// A utility function to create a root (Endpoint 0) object using the requested operational network type.
 pub const fn root_endpoint(net_type: NetworkType) -> Endpoint<'static> {
-    Endpoint {
-        id: ROOT_ENDPOINT_ID,
-        device_types: devices!(super::device_types::DEV_TYPE_ROOT_NODE),
-        clusters: net_type.root_clusters(),
-    }
+    Endpoint::new(
+        ROOT_ENDPOINT_ID,
+        devices!(super::device_types::DEV_TYPE_ROOT_NODE),
+        net_type.root_clusters(),
+    )
 }
 
 /// A type alias for the handler chain returned by `with_eth()`.
@@ -87,6 +89,12 @@ pub type SysHandler<'a, H> = handler_chain_type!(
     | H
 );
 
+/// A type alias for the handler chain returned by `with_power_source_config()`.
+pub type PowerSourceConfigChain<'a, H> = handler_chain_type!(
+    EpClMatcher => Async<power_source_config::HandlerAdaptor<PowerSourceConfigHandler<'a>>>
+    | H
+);
+
 /// The ID of the root endpoint (Endpoint 0)
 pub const ROOT_ENDPOINT_ID: EndptId = 0;
 
@@ -230,6 +238,71 @@ pub fn with_sys<'a, H>(
     comm_policy: &'a dyn CommPolicy,
     rand: Rand,
     handler: H,
+) -> SysHandler<'a, H> {
+    with_sys_desc(
+        comm_policy,
+        DescHandler::new(Dataver::new_rand(rand)),
+        rand,
+        handler,
+    )
+}
+
+/// Decorates the provided `handler` with the system model handlers installed on the root endpoint (0),
+/// using a custom `PartsMatcher` for the Descriptor cluster of the root endpoint.
+///
+/// Useful for nodes whose endpoints change at runtime (i.e. bridges), so that the parts
+/// of the root endpoint are reported with a dataver reflecting the changes.
+///
+/// # Arguments:
+/// - `comm_policy`: The commissioning policy to be used for the `GenCommHandler`.
+/// - `matcher`: The parts matcher to be used for the `DescHandler`.
+/// - `rand`: A random number generator.
+/// - `handler`: The handler to be decorated.
+pub fn with_sys_matching<'a, H>(
+    comm_policy: &'a dyn CommPolicy,
+    matcher: &'a dyn PartsMatcher,
+    rand: Rand,
+    handler: H,
+) -> SysHandler<'a, H> {
+    with_sys_desc(
+        comm_policy,
+        DescHandler::new_matching(Dataver::new_rand(rand), matcher),
+        rand,
+        handler,
+    )
+}
+
+/// Decorates the provided `handler` with the `PowerSourceConfigHandler`, installed on the root endpoint (0).
+///
+/// Nodes having Power Source clusters should use this decorator, and should also add
+/// `PowerSourceConfigHandler::CLUSTER` to the clusters of the root endpoint, i.e.
+/// `clusters!(eth; PowerSourceConfigHandler::CLUSTER)`.
+///
+/// # Arguments:
+/// - `rand`: A random number generator.
+/// - `sources`: The power sources of the node, as the endpoints hosting their Power Source clusters
+///   and the handlers of these clusters.
+/// - `handler`: The handler to be decorated.
+pub fn with_power_source_config<'a, H>(
+    rand: Rand,
+    sources: &'a [(EndptId, &'a PowerSourceHandler<'a>)],
+    handler: H,
+) -> PowerSourceConfigChain<'a, H> {
+    ChainedHandler::new(
+        EpClMatcher::new(
+            Some(ROOT_ENDPOINT_ID),
+            Some(PowerSourceConfigHandler::CLUSTER.id),
+        ),
+        Async(PowerSourceConfigHandler::new(Dataver::new_rand(rand), sources).adapt()),
+        handler,
+    )
+}
+
+fn with_sys_desc<'a, H>(
+    comm_policy: &'a dyn CommPolicy,
+    desc: DescHandler<'a>,
+    rand: Rand,
+    handler: H,
 ) -> SysHandler<'a, H> {
     ChainedHandler::new(
         EpClMatcher::new(Some(ROOT_ENDPOINT_ID), Some(GrpKeyMgmtHandler::CLUSTER.id)),
@@ -258,6 +331,6 @@ pub fn with_sys<'a, H>(
     )
     .chain(
         EpClMatcher::new(Some(ROOT_ENDPOINT_ID), Some(DescHandler::CLUSTER.id)),
-        Async(DescHandler::new(Dataver::new_rand(rand)).adapt()),
+        Async(desc.adapt()),
     )
 }
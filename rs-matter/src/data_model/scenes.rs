@@ -0,0 +1,1428 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Scenes cluster and its handler.
+//!
+//! The handler keeps a fabric-scoped scene table, where each scene is identified by its fabric, group ID and scene ID,
+//! and contains the "extension field sets" - i.e. the values of the scene-able attributes - of the other clusters
+//! on the same endpoint. These clusters take part in the scenes by implementing the `SceneExtension` trait.
+
+use core::num::NonZeroU8;
+
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::Duration;
+
+use crate::error::{Error, ErrorCode};
+use crate::fabric::{FabricMgr, MAX_SUPPORTED_FABRICS};
+use crate::interaction_model::core::IMStatusCode;
+use crate::tlv::{
+    FromTLV, Nullable, TLVArray, TLVBuilderParent, TLVElement, TLVTag, ToTLV, Utf8Str,
+};
+use crate::utils::cell::RefCell;
+use crate::utils::storage::{Vec, WriteBuf};
+use crate::utils::sync::Notification;
+use crate::with;
+
+use super::objects::{
+    ArrayAttributeRead, AttrId, Cluster, ClusterId, Dataver, InvokeContext, ReadContext,
+};
+use super::on_off::GlobalSceneCoupling;
+
+pub use crate::data_model::clusters::scenes::*;
+
+/// The maximum number of scenes in the scene table of an endpoint.
+pub const MAX_SCENES: usize = 16;
+/// The maximum number of scenes of a single fabric, so that each fabric is guaranteed
+/// to be able to use at least that many scenes.
+pub const MAX_SCENES_PER_FABRIC: usize = MAX_SCENES / 2;
+/// The maximum length of a scene name.
+pub const MAX_SCENE_NAME_LEN: usize = 16;
+/// The maximum number of extension field sets (i.e. clusters) in a scene.
+pub const MAX_SCENE_FIELD_SETS: usize = 4;
+/// The maximum number of attribute values in an extension field set of a scene.
+pub const MAX_SCENE_ATTRS: usize = 10;
+
+/// The ID of the global scene, which - together with group ID 0 - is used by the
+/// `OffWithEffect` and `OnWithRecallGlobalScene` commands of the On/Off cluster.
+const GLOBAL_SCENE_ID: u8 = 0;
+/// The group ID of the scenes which are not associated with a group.
+const NO_GROUP: u16 = 0;
+
+/// The value of a scene-able attribute of a cluster, as stored in a scene.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SceneAttributeValue {
+    pub attr_id: AttrId,
+    pub value: u32,
+}
+
+impl SceneAttributeValue {
+    /// Create a new attribute value.
+    pub const fn new(attr_id: AttrId, value: u32) -> Self {
+        Self { attr_id, value }
+    }
+
+    /// Find the value of the attribute with the given ID in a list of attribute values.
+    pub fn find(values: &[Self], attr_id: AttrId) -> Option<u32> {
+        values
+            .iter()
+            .find(|value| value.attr_id == attr_id)
+            .map(|value| value.value)
+    }
+}
+
+/// The attribute values of a cluster, as stored in a scene.
+pub type SceneAttributeValues = Vec<SceneAttributeValue, MAX_SCENE_ATTRS>;
+
+/// A trait for the cluster handlers whose state can be stored in and recalled from scenes.
+///
+/// The trait is implemented by the On/Off, Level Control and Color Control cluster handlers.
+pub trait SceneExtension {
+    /// The ID of the cluster.
+    fn cluster_id(&self) -> ClusterId;
+
+    /// Capture the current values of the scene-able attributes of the cluster.
+    fn store_scene(&self, values: &mut SceneAttributeValues) -> Result<(), Error>;
+
+    /// Apply the given attribute values (which might be a subset of the scene-able attributes of the cluster)
+    /// by transitioning to them over the given time.
+    ///
+    /// Unknown attributes should be ignored.
+    fn recall_scene(
+        &self,
+        values: &[SceneAttributeValue],
+        transition_time: Duration,
+    ) -> Result<(), Error>;
+}
+
+impl<T> SceneExtension for &T
+where
+    T: SceneExtension,
+{
+    fn cluster_id(&self) -> ClusterId {
+        (*self).cluster_id()
+    }
+
+    fn store_scene(&self, values: &mut SceneAttributeValues) -> Result<(), Error> {
+        (*self).store_scene(values)
+    }
+
+    fn recall_scene(
+        &self,
+        values: &[SceneAttributeValue],
+        transition_time: Duration,
+    ) -> Result<(), Error> {
+        (*self).recall_scene(values, transition_time)
+    }
+}
+
+/// A trait for checking the membership of the endpoint in groups, as the scenes of a group
+/// can only be used when the endpoint is a member of the group.
+pub trait SceneGroups {
+    /// Return `true` if the endpoint is a member of the given group of the given fabric.
+    fn is_member(&self, fab_idx: NonZeroU8, group_id: u16) -> bool;
+}
+
+/// Accepts all groups, which is what the handler uses by default, as `rs-matter` does not have
+/// a Groups cluster (yet).
+impl SceneGroups for () {
+    fn is_member(&self, _fab_idx: NonZeroU8, _group_id: u16) -> bool {
+        true
+    }
+}
+
+/// The attribute values of a cluster in a scene.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct SceneFieldSet {
+    cluster_id: ClusterId,
+    values: SceneAttributeValues,
+}
+
+/// An entry of the scene table.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct SceneEntry {
+    fab_idx: u8,
+    group_id: u16,
+    scene_id: u8,
+    name: heapless::String<MAX_SCENE_NAME_LEN>,
+    /// The transition time in milliseconds
+    transition_time: u32,
+    field_sets: Vec<SceneFieldSet, MAX_SCENE_FIELD_SETS>,
+}
+
+impl SceneEntry {
+    const fn new(fab_idx: NonZeroU8, group_id: u16, scene_id: u8) -> Self {
+        Self {
+            fab_idx: fab_idx.get(),
+            group_id,
+            scene_id,
+            name: heapless::String::new(),
+            transition_time: 0,
+            field_sets: Vec::new(),
+        }
+    }
+
+    fn is(&self, fab_idx: NonZeroU8, group_id: u16, scene_id: u8) -> bool {
+        self.is_in(fab_idx, group_id) && self.scene_id == scene_id
+    }
+
+    fn is_in(&self, fab_idx: NonZeroU8, group_id: u16) -> bool {
+        self.fab_idx == fab_idx.get() && self.group_id == group_id
+    }
+}
+
+/// The scene which was last stored or recalled by a fabric.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct CurrentScene {
+    fab_idx: NonZeroU8,
+    group_id: u16,
+    scene_id: u8,
+    valid: bool,
+}
+
+/// The state of the Scenes cluster.
+struct ScenesState {
+    scenes: Vec<SceneEntry, MAX_SCENES>,
+    current: Vec<CurrentScene, MAX_SUPPORTED_FABRICS>,
+    last_configured_by: Option<u64>,
+    changed: bool,
+}
+
+impl ScenesState {
+    const fn new() -> Self {
+        Self {
+            scenes: Vec::new(),
+            current: Vec::new(),
+            last_configured_by: None,
+            changed: false,
+        }
+    }
+
+    fn scene_count(&self, fab_idx: NonZeroU8) -> usize {
+        self.scenes
+            .iter()
+            .filter(|scene| scene.fab_idx == fab_idx.get())
+            .count()
+    }
+
+    /// Return how many more scenes the fabric can add.
+    fn remaining_capacity(&self, fab_idx: NonZeroU8) -> usize {
+        (MAX_SCENES_PER_FABRIC - self.scene_count(fab_idx).min(MAX_SCENES_PER_FABRIC))
+            .min(MAX_SCENES - self.scenes.len())
+    }
+
+    fn find(&self, fab_idx: NonZeroU8, group_id: u16, scene_id: u8) -> Option<&SceneEntry> {
+        self.scenes
+            .iter()
+            .find(|scene| scene.is(fab_idx, group_id, scene_id))
+    }
+
+    fn current(&self, fab_idx: NonZeroU8) -> Option<&CurrentScene> {
+        self.current
+            .iter()
+            .find(|current| current.fab_idx == fab_idx)
+    }
+
+    fn set_current(&mut self, fab_idx: NonZeroU8, group_id: u16, scene_id: u8) {
+        let current = CurrentScene {
+            fab_idx,
+            group_id,
+            scene_id,
+            valid: true,
+        };
+
+        if let Some(existing) = self.current.iter_mut().find(|c| c.fab_idx == fab_idx) {
+            *existing = current;
+        } else {
+            // Cannot fail, as there is at most one entry per fabric
+            let _ = self.current.push(current);
+        }
+    }
+
+    /// Mark the current scene of the fabric as invalid, if the scene matches the given predicate.
+    fn invalidate<F>(&mut self, fab_idx: NonZeroU8, f: F)
+    where
+        F: Fn(&CurrentScene) -> bool,
+    {
+        for current in self.current.iter_mut() {
+            if current.fab_idx == fab_idx && f(current) {
+                current.valid = false;
+            }
+        }
+    }
+
+    /// Read the scene information of the fabric into the provided builder.
+    fn read_info<P: TLVBuilderParent>(
+        &self,
+        fab_idx: NonZeroU8,
+        builder: SceneInfoStructBuilder<P>,
+    ) -> Result<P, Error> {
+        let current = self.current(fab_idx);
+
+        builder
+            .scene_count(self.scene_count(fab_idx) as _)?
+            .current_scene(current.map(|current| current.scene_id).unwrap_or(0))?
+            .current_group(current.map(|current| current.group_id).unwrap_or(NO_GROUP))?
+            .scene_valid(current.map(|current| current.valid).unwrap_or(false))?
+            .remaining_capacity(self.remaining_capacity(fab_idx) as _)?
+            .fabric_index(fab_idx.get())?
+            .end()
+    }
+
+    /// Insert the scene into the table, replacing the scene with the same identifiers, if any.
+    fn insert(&mut self, scene: SceneEntry) -> IMStatusCode {
+        let fab_idx = unwrap!(NonZeroU8::new(scene.fab_idx));
+
+        if let Some(existing) = self
+            .scenes
+            .iter_mut()
+            .find(|existing| existing.is(fab_idx, scene.group_id, scene.scene_id))
+        {
+            *existing = scene;
+        } else if self.remaining_capacity(fab_idx) == 0 || self.scenes.push(scene).is_err() {
+            return IMStatusCode::ResourceExhausted;
+        }
+
+        self.changed = true;
+
+        IMStatusCode::Success
+    }
+}
+
+/// A handler for the Scenes Matter cluster, with a fabric-scoped scene table.
+///
+/// The scenes capture and apply the state of the other clusters on the same endpoint, which implement the
+/// `SceneExtension` trait and are registered with `ScenesHandler::set_extensions`. The handler should also be coupled
+/// with the On/Off cluster handler of the endpoint (with `OnOffHandler::set_scenes`), so that the global scene is
+/// stored and recalled by the `OffWithEffect` and `OnWithRecallGlobalScene` commands.
+///
+/// The scene table should be persisted (see `ScenesHandler::load` and `ScenesHandler::store`).
+///
+/// Since the handler is not notified when a fabric is removed, the scenes of removed fabrics are pruned lazily -
+/// whenever the scene table is read or a scene is stored or recalled (including the global scene) - or explicitly,
+/// with `ScenesHandler::remove_fabric`. This relies on `FabricMgr` not re-using the index of a removed fabric
+/// for the next fabric.
+pub struct ScenesHandler<'a> {
+    dataver: Dataver,
+    state: RefCell<ScenesState>,
+    extensions: &'a [&'a dyn SceneExtension],
+    groups: &'a dyn SceneGroups,
+    persist_state_changed: Notification<NoopRawMutex>,
+}
+
+impl<'a> ScenesHandler<'a> {
+    /// Creates a new instance of `ScenesHandler` with the given `Dataver`.
+    pub const fn new(dataver: Dataver) -> Self {
+        Self {
+            dataver,
+            state: RefCell::new(ScenesState::new()),
+            extensions: &[],
+            groups: &(),
+            persist_state_changed: Notification::new(),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Set the cluster handlers of the same endpoint, whose state is stored in the scenes.
+    pub fn set_extensions(&mut self, extensions: &'a [&'a dyn SceneExtension]) {
+        self.extensions = extensions;
+    }
+
+    /// Set the group membership of the endpoint.
+    pub fn set_groups(&mut self, groups: &'a dyn SceneGroups) {
+        self.groups = groups;
+    }
+
+    /// Remove all scenes of the given fabric.
+    pub fn remove_fabric(&self, fab_idx: NonZeroU8) {
+        let mut state = self.state.borrow_mut();
+
+        let len = state.scenes.len();
+        state.scenes.retain(|scene| scene.fab_idx != fab_idx.get());
+        state.current.retain(|current| current.fab_idx != fab_idx);
+
+        if state.scenes.len() != len {
+            state.changed = true;
+            self.persist_state_changed.notify();
+        }
+
+        self.dataver.changed();
+    }
+
+    /// Load the scene table from a byte slice.
+    ///
+    /// # Arguments
+    /// - `data`: The byte slice to load the scene table from
+    pub fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let scenes = Vec::<SceneEntry, MAX_SCENES>::from_tlv(&TLVElement::new(data))?;
+
+        let mut state = self.state.borrow_mut();
+
+        state.scenes = scenes;
+        state.current.clear();
+        state.changed = false;
+
+        Ok(())
+    }
+
+    /// Store the scene table into a byte slice.
+    ///
+    /// # Arguments
+    /// - `buf`: The byte slice to store the scene table into
+    ///
+    /// Returns `Ok(None)` if the scene table has not changed, `Ok(Some(data))` if the scene table has changed
+    /// where `data` is the sub-slice of the buffer that contains the data to be persisted
+    pub fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        let mut state = self.state.borrow_mut();
+
+        if !state.changed {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        state.scenes.to_tlv(&TLVTag::Anonymous, &mut wb)?;
+
+        state.changed = false;
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    /// Return `true` if the scene table has changed and needs to be persisted.
+    pub fn changed(&self) -> bool {
+        self.state.borrow().changed
+    }
+
+    /// Wait for the scene table to be changed in a way that requires persisting.
+    pub async fn wait_persist(&self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+
+            self.persist_state_changed.wait().await;
+        }
+    }
+
+    /// Remove the scenes of the fabrics which no longer exist.
+    fn prune(&self, fabric_mgr: &FabricMgr) {
+        let mut state = self.state.borrow_mut();
+
+        let len = state.scenes.len();
+        state.scenes.retain(|scene| {
+            NonZeroU8::new(scene.fab_idx)
+                .and_then(|fab_idx| fabric_mgr.get(fab_idx))
+                .is_some()
+        });
+        state
+            .current
+            .retain(|current| fabric_mgr.get(current.fab_idx).is_some());
+
+        if state.scenes.len() != len {
+            state.changed = true;
+            self.persist_state_changed.notify();
+        }
+    }
+
+    /// Record the changes done to the scene table by a client.
+    fn configured(&self, node_id: Option<u64>) {
+        self.state.borrow_mut().last_configured_by = node_id;
+        self.persist_state_changed.notify();
+        self.dataver.changed();
+    }
+
+    /// Return `true` if the scenes of the given group can be used by the fabric.
+    fn is_valid_group(&self, fab_idx: NonZeroU8, group_id: u16) -> bool {
+        group_id == NO_GROUP || self.groups.is_member(fab_idx, group_id)
+    }
+
+    fn extension(&self, cluster_id: ClusterId) -> Option<&'a dyn SceneExtension> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.cluster_id() == cluster_id)
+            .copied()
+    }
+
+    fn add_scene(&self, scene: SceneEntry) -> IMStatusCode {
+        let fab_idx = unwrap!(NonZeroU8::new(scene.fab_idx));
+
+        if !self.is_valid_group(fab_idx, scene.group_id) {
+            return IMStatusCode::InvalidCommand;
+        }
+
+        let (group_id, scene_id) = (scene.group_id, scene.scene_id);
+
+        let mut state = self.state.borrow_mut();
+
+        let status = state.insert(scene);
+
+        if status == IMStatusCode::Success {
+            state.invalidate(fab_idx, |current| {
+                current.group_id == group_id && current.scene_id == scene_id
+            });
+        }
+
+        status
+    }
+
+    fn remove_scene(&self, fab_idx: NonZeroU8, group_id: u16, scene_id: u8) -> IMStatusCode {
+        if !self.is_valid_group(fab_idx, group_id) {
+            return IMStatusCode::InvalidCommand;
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        let len = state.scenes.len();
+        state
+            .scenes
+            .retain(|scene| !scene.is(fab_idx, group_id, scene_id));
+
+        if state.scenes.len() == len {
+            return IMStatusCode::NotFound;
+        }
+
+        state.invalidate(fab_idx, |current| {
+            current.group_id == group_id && current.scene_id == scene_id
+        });
+        state.changed = true;
+
+        IMStatusCode::Success
+    }
+
+    fn remove_all_scenes(&self, fab_idx: NonZeroU8, group_id: u16) -> IMStatusCode {
+        if !self.is_valid_group(fab_idx, group_id) {
+            return IMStatusCode::InvalidCommand;
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        state.scenes.retain(|scene| !scene.is_in(fab_idx, group_id));
+        state.invalidate(fab_idx, |current| current.group_id == group_id);
+        state.changed = true;
+
+        IMStatusCode::Success
+    }
+
+    /// Store the current state of the extensions in the scene, creating the scene if it does not exist.
+    fn store_scene(&self, fab_idx: NonZeroU8, group_id: u16, scene_id: u8) -> IMStatusCode {
+        if !self.is_valid_group(fab_idx, group_id) {
+            return IMStatusCode::InvalidCommand;
+        }
+
+        let mut scene = self
+            .state
+            .borrow()
+            .find(fab_idx, group_id, scene_id)
+            .cloned()
+            .unwrap_or_else(|| SceneEntry::new(fab_idx, group_id, scene_id));
+
+        scene.field_sets.clear();
+
+        for extension in self.extensions {
+            let mut values = SceneAttributeValues::new();
+
+            if extension.store_scene(&mut values).is_err()
+                || scene
+                    .field_sets
+                    .push(SceneFieldSet {
+                        cluster_id: extension.cluster_id(),
+                        values,
+                    })
+                    .is_err()
+            {
+                return IMStatusCode::ResourceExhausted;
+            }
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        let status = state.insert(scene);
+
+        if status == IMStatusCode::Success {
+            state.set_current(fab_idx, group_id, scene_id);
+        }
+
+        status
+    }
+
+    /// Recall the scene, transitioning over the given time (in milliseconds),
+    /// or over the transition time of the scene, if the time is not provided.
+    fn recall_scene(
+        &self,
+        fab_idx: NonZeroU8,
+        group_id: u16,
+        scene_id: u8,
+        transition_time: Option<u32>,
+    ) -> IMStatusCode {
+        if !self.is_valid_group(fab_idx, group_id) {
+            return IMStatusCode::InvalidCommand;
+        }
+
+        let Some(scene) = self
+            .state
+            .borrow()
+            .find(fab_idx, group_id, scene_id)
+            .cloned()
+        else {
+            return IMStatusCode::NotFound;
+        };
+
+        let transition_time =
+            Duration::from_millis(transition_time.unwrap_or(scene.transition_time) as _);
+
+        for field_set in &scene.field_sets {
+            if let Some(extension) = self.extension(field_set.cluster_id) {
+                if let Err(e) = extension.recall_scene(&field_set.values, transition_time) {
+                    warn!(
+                        "Failed to recall scene {} for cluster {}: {:?}",
+                        scene_id, field_set.cluster_id, e
+                    );
+                }
+            }
+        }
+
+        self.state
+            .borrow_mut()
+            .set_current(fab_idx, group_id, scene_id);
+
+        IMStatusCode::Success
+    }
+
+    fn copy_scene(
+        &self,
+        fab_idx: NonZeroU8,
+        mode: CopyModeBitmap,
+        (group_from, scene_from): (u16, u8),
+        (group_to, scene_to): (u16, u8),
+    ) -> IMStatusCode {
+        if !self.is_valid_group(fab_idx, group_from) || !self.is_valid_group(fab_idx, group_to) {
+            return IMStatusCode::InvalidCommand;
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        if mode.contains(CopyModeBitmap::COPY_ALL_SCENES) {
+            let count = state
+                .scenes
+                .iter()
+                .filter(|scene| scene.is_in(fab_idx, group_from))
+                .count();
+
+            if count == 0 {
+                return IMStatusCode::NotFound;
+            }
+
+            for index in 0..state.scenes.len() {
+                if state.scenes[index].is_in(fab_idx, group_from) {
+                    let mut scene = state.scenes[index].clone();
+                    scene.group_id = group_to;
+
+                    let status = state.insert(scene);
+                    if status != IMStatusCode::Success {
+                        return status;
+                    }
+                }
+            }
+        } else {
+            let Some(mut scene) = state.find(fab_idx, group_from, scene_from).cloned() else {
+                return IMStatusCode::NotFound;
+            };
+
+            scene.group_id = group_to;
+            scene.scene_id = scene_to;
+
+            let status = state.insert(scene);
+            if status != IMStatusCode::Success {
+                return status;
+            }
+        }
+
+        state.invalidate(fab_idx, |current| current.group_id == group_to);
+
+        IMStatusCode::Success
+    }
+
+    /// Parse the scene to be added from the fields of an `AddScene` or `EnhancedAddScene` request.
+    fn parse_scene(
+        &self,
+        fab_idx: NonZeroU8,
+        (group_id, scene_id): (u16, u8),
+        transition_time: u32,
+        name: Utf8Str<'_>,
+        field_sets: TLVArray<'_, ExtensionFieldSet<'_>>,
+    ) -> Result<Result<SceneEntry, IMStatusCode>, Error> {
+        let mut scene = SceneEntry::new(fab_idx, group_id, scene_id);
+
+        scene.transition_time = transition_time;
+
+        if scene.name.push_str(name).is_err() {
+            return Ok(Err(IMStatusCode::ConstraintError));
+        }
+
+        for field_set in field_sets {
+            let field_set = field_set?;
+            let cluster_id = field_set.cluster_id()?;
+
+            // Field sets of clusters which are not scene-able on this endpoint are ignored
+            if self.extension(cluster_id).is_none() {
+                continue;
+            }
+
+            let mut values = SceneAttributeValues::new();
+
+            for value in field_set.attribute_value_list()? {
+                let value = value?;
+
+                if values
+                    .push(SceneAttributeValue::new(
+                        value.attribute_id()?,
+                        value.attribute_value()?,
+                    ))
+                    .is_err()
+                {
+                    return Ok(Err(IMStatusCode::ResourceExhausted));
+                }
+            }
+
+            // A later field set of the same cluster replaces the earlier one
+            scene
+                .field_sets
+                .retain(|field_set| field_set.cluster_id != cluster_id);
+
+            if scene
+                .field_sets
+                .push(SceneFieldSet { cluster_id, values })
+                .is_err()
+            {
+                return Ok(Err(IMStatusCode::ResourceExhausted));
+            }
+        }
+
+        Ok(Ok(scene))
+    }
+}
+
+/// Return the fabric index and the node ID of the peer of the invoked command.
+fn accessor(ctx: &InvokeContext) -> Result<(NonZeroU8, Option<u64>), Error> {
+    ctx.exchange().with_session(|sess| {
+        Ok((
+            NonZeroU8::new(sess.get_local_fabric_idx()).ok_or(ErrorCode::UnsupportedAccess)?,
+            sess.get_peer_node_id(),
+        ))
+    })
+}
+
+impl GlobalSceneCoupling for ScenesHandler<'_> {
+    fn store_global_scene(&self, fabric_mgr: &FabricMgr, fab_idx: NonZeroU8) {
+        self.prune(fabric_mgr);
+
+        if self.store_scene(fab_idx, NO_GROUP, GLOBAL_SCENE_ID) == IMStatusCode::Success {
+            self.configured(None);
+        }
+    }
+
+    fn recall_global_scene(&self, fabric_mgr: &FabricMgr, fab_idx: NonZeroU8) -> bool {
+        self.prune(fabric_mgr);
+
+        let recalled =
+            self.recall_scene(fab_idx, NO_GROUP, GLOBAL_SCENE_ID, None) == IMStatusCode::Success;
+
+        if recalled {
+            self.dataver.changed();
+        }
+
+        recalled
+    }
+}
+
+impl ClusterHandler for ScenesHandler<'_> {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(5)
+        .with_features(
+            Feature::SCENE_NAMES.bits()
+                | Feature::TABLE_SIZE.bits()
+                | Feature::FABRIC_SCENES.bits(),
+        )
+        .with_attrs(with!(required; AttributeId::LastConfiguredBy))
+        .with_cmds(with!(all));
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn name_support(&self, _ctx: &ReadContext) -> Result<NameSupportBitmap, Error> {
+        Ok(NameSupportBitmap::SCENE_NAMES)
+    }
+
+    fn last_configured_by(&self, _ctx: &ReadContext) -> Result<Nullable<u64>, Error> {
+        Ok(Nullable::new(self.state.borrow().last_configured_by))
+    }
+
+    fn scene_table_size(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(MAX_SCENES as _)
+    }
+
+    fn fabric_scene_info<P: TLVBuilderParent>(
+        &self,
+        ctx: &ReadContext,
+        builder: ArrayAttributeRead<SceneInfoStructArrayBuilder<P>, SceneInfoStructBuilder<P>>,
+    ) -> Result<P, Error> {
+        let fabric_mgr = ctx.exchange().matter().fabric_mgr.borrow();
+        let attr = ctx.attr();
+
+        self.prune(&fabric_mgr);
+
+        let state = self.state.borrow();
+
+        let mut fabrics = fabric_mgr
+            .iter()
+            .map(|fabric| fabric.fab_idx())
+            .filter(|fab_idx| !attr.fab_filter || fab_idx.get() == attr.fab_idx);
+
+        match builder {
+            ArrayAttributeRead::ReadAll(mut builder) => {
+                for fab_idx in fabrics {
+                    builder = state.read_info(fab_idx, builder.push()?)?;
+                }
+
+                builder.end()
+            }
+            ArrayAttributeRead::ReadOne(index, builder) => {
+                let Some(fab_idx) = fabrics.nth(index as _) else {
+                    return Err(ErrorCode::ConstraintError.into());
+                };
+
+                state.read_info(fab_idx, builder)
+            }
+        }
+    }
+
+    fn handle_add_scene<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: AddSceneRequest,
+        response: AddSceneResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, node_id) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let ids = (request.group_id()?, request.scene_id()?);
+
+        let status = match self.parse_scene(
+            fab_idx,
+            ids,
+            request.transition_time()? as u32 * 1000,
+            request.scene_name()?,
+            request.extension_field_sets()?,
+        )? {
+            Ok(scene) => self.add_scene(scene),
+            Err(status) => status,
+        };
+
+        if status == IMStatusCode::Success {
+            self.configured(node_id);
+            ctx.notify_changed();
+        }
+
+        response
+            .status(status as _)?
+            .group_id(ids.0)?
+            .scene_id(ids.1)?
+            .end()
+    }
+
+    fn handle_view_scene<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: ViewSceneRequest,
+        response: ViewSceneResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, _) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let (group_id, scene_id) = (request.group_id()?, request.scene_id()?);
+
+        let state = self.state.borrow();
+
+        let (status, scene) = if !self.is_valid_group(fab_idx, group_id) {
+            (IMStatusCode::InvalidCommand, None)
+        } else if let Some(scene) = state.find(fab_idx, group_id, scene_id) {
+            (IMStatusCode::Success, Some(scene))
+        } else {
+            (IMStatusCode::NotFound, None)
+        };
+
+        let response = response
+            .status(status as _)?
+            .group_id(group_id)?
+            .scene_id(scene_id)?;
+
+        let Some(scene) = scene else {
+            return response
+                .transition_time(None)?
+                .scene_name(None)?
+                .extension_field_sets()?
+                .none()
+                .end();
+        };
+
+        let mut field_sets = response
+            .transition_time(Some((scene.transition_time / 1000).min(u16::MAX as _) as _))?
+            .scene_name(Some(scene.name.as_str()))?
+            .extension_field_sets()?
+            .some()?;
+
+        for field_set in &scene.field_sets {
+            let mut values = field_sets
+                .push()?
+                .cluster_id(field_set.cluster_id)?
+                .attribute_value_list()?;
+
+            for value in &field_set.values {
+                values = values
+                    .push()?
+                    .attribute_id(value.attr_id)?
+                    .attribute_value(value.value)?
+                    .end()?;
+            }
+
+            field_sets = values.end()?.end()?;
+        }
+
+        field_sets.end()?.end()
+    }
+
+    fn handle_remove_scene<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: RemoveSceneRequest,
+        response: RemoveSceneResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, node_id) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let (group_id, scene_id) = (request.group_id()?, request.scene_id()?);
+
+        let status = self.remove_scene(fab_idx, group_id, scene_id);
+
+        if status == IMStatusCode::Success {
+            self.configured(node_id);
+            ctx.notify_changed();
+        }
+
+        response
+            .status(status as _)?
+            .group_id(group_id)?
+            .scene_id(scene_id)?
+            .end()
+    }
+
+    fn handle_remove_all_scenes<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: RemoveAllScenesRequest,
+        response: RemoveAllScenesResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, node_id) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let group_id = request.group_id()?;
+
+        let status = self.remove_all_scenes(fab_idx, group_id);
+
+        if status == IMStatusCode::Success {
+            self.configured(node_id);
+            ctx.notify_changed();
+        }
+
+        response.status(status as _)?.group_id(group_id)?.end()
+    }
+
+    fn handle_store_scene<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: StoreSceneRequest,
+        response: StoreSceneResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, node_id) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let (group_id, scene_id) = (request.group_id()?, request.scene_id()?);
+
+        let status = self.store_scene(fab_idx, group_id, scene_id);
+
+        if status == IMStatusCode::Success {
+            self.configured(node_id);
+            ctx.notify_changed();
+        }
+
+        response
+            .status(status as _)?
+            .group_id(group_id)?
+            .scene_id(scene_id)?
+            .end()
+    }
+
+    fn handle_recall_scene(
+        &self,
+        ctx: &InvokeContext,
+        request: RecallSceneRequest,
+    ) -> Result<(), Error> {
+        let (fab_idx, _) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let transition_time = request
+            .transition_time()?
+            .and_then(Nullable::into_option)
+            .map(|tenths| tenths as u32 * 100);
+
+        match self.recall_scene(
+            fab_idx,
+            request.group_id()?,
+            request.scene_id()?,
+            transition_time,
+        ) {
+            IMStatusCode::Success => {
+                ctx.notify_changed();
+
+                Ok(())
+            }
+            IMStatusCode::NotFound => Err(ErrorCode::NotFound.into()),
+            _ => Err(ErrorCode::InvalidCommand.into()),
+        }
+    }
+
+    fn handle_get_scene_membership<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: GetSceneMembershipRequest,
+        response: GetSceneMembershipResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, _) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let group_id = request.group_id()?;
+
+        let state = self.state.borrow();
+
+        let valid = self.is_valid_group(fab_idx, group_id);
+
+        let response = response
+            .status(if valid {
+                IMStatusCode::Success
+            } else {
+                IMStatusCode::InvalidCommand
+            } as _)?
+            .capacity(Nullable::some(
+                state.remaining_capacity(fab_idx).min(u8::MAX as _) as _,
+            ))?
+            .group_id(group_id)?
+            .scene_list()?;
+
+        if !valid {
+            return response.none().end();
+        }
+
+        let mut scenes = response.some()?;
+
+        for scene in state
+            .scenes
+            .iter()
+            .filter(|scene| scene.is_in(fab_idx, group_id))
+        {
+            scenes = scenes.push(&scene.scene_id)?;
+        }
+
+        scenes.end()?.end()
+    }
+
+    fn handle_enhanced_add_scene<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: EnhancedAddSceneRequest,
+        response: EnhancedAddSceneResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, node_id) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let ids = (request.group_id()?, request.scene_id()?);
+
+        let status = match self.parse_scene(
+            fab_idx,
+            ids,
+            request.transition_time()? as u32 * 100,
+            request.scene_name()?,
+            request.extension_field_sets()?,
+        )? {
+            Ok(scene) => self.add_scene(scene),
+            Err(status) => status,
+        };
+
+        if status == IMStatusCode::Success {
+            self.configured(node_id);
+            ctx.notify_changed();
+        }
+
+        response
+            .status(status as _)?
+            .group_id(ids.0)?
+            .scene_id(ids.1)?
+            .end()
+    }
+
+    fn handle_enhanced_view_scene<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: EnhancedViewSceneRequest,
+        response: EnhancedViewSceneResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, _) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let (group_id, scene_id) = (request.group_id()?, request.scene_id()?);
+
+        let state = self.state.borrow();
+
+        let (status, scene) = if !self.is_valid_group(fab_idx, group_id) {
+            (IMStatusCode::InvalidCommand, None)
+        } else if let Some(scene) = state.find(fab_idx, group_id, scene_id) {
+            (IMStatusCode::Success, Some(scene))
+        } else {
+            (IMStatusCode::NotFound, None)
+        };
+
+        let response = response
+            .status(status as _)?
+            .group_id(group_id)?
+            .scene_id(scene_id)?;
+
+        let Some(scene) = scene else {
+            return response
+                .transition_time(None)?
+                .scene_name(None)?
+                .extension_field_sets()?
+                .none()
+                .end();
+        };
+
+        let mut field_sets = response
+            .transition_time(Some((scene.transition_time / 100).min(u16::MAX as _) as _))?
+            .scene_name(Some(scene.name.as_str()))?
+            .extension_field_sets()?
+            .some()?;
+
+        for field_set in &scene.field_sets {
+            let mut values = field_sets
+                .push()?
+                .cluster_id(field_set.cluster_id)?
+                .attribute_value_list()?;
+
+            for value in &field_set.values {
+                values = values
+                    .push()?
+                    .attribute_id(value.attr_id)?
+                    .attribute_value(value.value)?
+                    .end()?;
+            }
+
+            field_sets = values.end()?.end()?;
+        }
+
+        field_sets.end()?.end()
+    }
+
+    fn handle_copy_scene<P: TLVBuilderParent>(
+        &self,
+        ctx: &InvokeContext,
+        request: CopySceneRequest,
+        response: CopySceneResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let (fab_idx, node_id) = accessor(ctx)?;
+        self.prune(&ctx.exchange().matter().fabric_mgr.borrow());
+
+        let from = (
+            request.group_identifier_from()?,
+            request.scene_identifier_from()?,
+        );
+        let to = (
+            request.group_identifier_to()?,
+            request.scene_identifier_to()?,
+        );
+
+        let status = self.copy_scene(fab_idx, request.mode()?, from, to);
+
+        if status == IMStatusCode::Success {
+            self.configured(node_id);
+            ctx.notify_changed();
+        }
+
+        response
+            .status(status as _)?
+            .group_identifier_from(from.0)?
+            .scene_identifier_from(from.1)?
+            .end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU8;
+
+    use crate::crypto::KeyPair;
+    use crate::data_model::level_control::LevelControlHandler;
+    use crate::data_model::objects::Dataver;
+    use crate::data_model::on_off::{GlobalSceneCoupling, OnOffHandler};
+    use crate::utils::rand::dummy_rand;
+
+    use super::*;
+
+    const FAB1: NonZeroU8 = NonZeroU8::MIN;
+    const FAB2: NonZeroU8 = FAB1.saturating_add(1);
+
+    struct Groups;
+
+    impl SceneGroups for Groups {
+        fn is_member(&self, _fab_idx: NonZeroU8, group_id: u16) -> bool {
+            group_id < 10
+        }
+    }
+
+    /// A fabric manager with fabrics 1 to `count`
+    fn fabric_mgr(count: usize) -> FabricMgr {
+        let mut fabric_mgr = FabricMgr::new();
+
+        for _ in 0..count {
+            unwrap!(fabric_mgr.add_with_post_init(unwrap!(KeyPair::new(dummy_rand)), |_| Ok(())));
+        }
+
+        fabric_mgr
+    }
+
+    fn scene(fab_idx: NonZeroU8, group_id: u16, scene_id: u8) -> SceneEntry {
+        SceneEntry::new(fab_idx, group_id, scene_id)
+    }
+
+    #[test]
+    fn test_store_recall() {
+        let on_off = OnOffHandler::new(Dataver::new(0));
+        let level = LevelControlHandler::new(Dataver::new(0));
+        let extensions: [&dyn SceneExtension; 2] = [&on_off, &level];
+
+        let mut scenes = ScenesHandler::new(Dataver::new(0));
+        scenes.set_extensions(&extensions);
+
+        on_off.set(true);
+        level.set(100);
+
+        assert_eq!(scenes.store_scene(FAB1, 1, 1), IMStatusCode::Success);
+        assert!(scenes.changed());
+
+        {
+            let state = scenes.state.borrow();
+            let stored = unwrap!(state.find(FAB1, 1, 1));
+
+            assert_eq!(stored.field_sets.len(), 2);
+            assert_eq!(
+                SceneAttributeValue::find(&stored.field_sets[1].values, 0),
+                Some(100)
+            );
+            assert_eq!(state.current(FAB1).map(|current| current.valid), Some(true));
+        }
+
+        on_off.set(false);
+        level.set(10);
+
+        assert_eq!(
+            scenes.recall_scene(FAB1, 1, 1, Some(0)),
+            IMStatusCode::Success
+        );
+        assert!(on_off.get());
+        assert_eq!(level.get(), Some(100));
+
+        assert_eq!(
+            scenes.recall_scene(FAB1, 1, 2, None),
+            IMStatusCode::NotFound
+        );
+        assert_eq!(
+            scenes.recall_scene(FAB2, 1, 1, None),
+            IMStatusCode::NotFound
+        );
+    }
+
+    #[test]
+    fn test_add_remove() {
+        let mut scenes = ScenesHandler::new(Dataver::new(0));
+        scenes.set_groups(&Groups);
+
+        assert_eq!(scenes.add_scene(scene(FAB1, 1, 1)), IMStatusCode::Success);
+        assert_eq!(scenes.add_scene(scene(FAB1, 1, 2)), IMStatusCode::Success);
+        assert_eq!(scenes.add_scene(scene(FAB1, 2, 1)), IMStatusCode::Success);
+        assert_eq!(scenes.add_scene(scene(FAB2, 1, 1)), IMStatusCode::Success);
+
+        // Not a member of the group
+        assert_eq!(
+            scenes.add_scene(scene(FAB1, 10, 1)),
+            IMStatusCode::InvalidCommand
+        );
+
+        // Replaces the existing scene
+        assert_eq!(scenes.add_scene(scene(FAB1, 1, 1)), IMStatusCode::Success);
+        assert_eq!(scenes.state.borrow().scenes.len(), 4);
+
+        assert_eq!(scenes.remove_scene(FAB1, 1, 1), IMStatusCode::Success);
+        assert_eq!(scenes.remove_scene(FAB1, 1, 1), IMStatusCode::NotFound);
+
+        assert_eq!(scenes.remove_all_scenes(FAB1, 1), IMStatusCode::Success);
+        assert_eq!(scenes.state.borrow().scene_count(FAB1), 1);
+        assert_eq!(scenes.state.borrow().scene_count(FAB2), 1);
+
+        scenes.remove_fabric(FAB2);
+        assert_eq!(scenes.state.borrow().scenes.len(), 1);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let scenes = ScenesHandler::new(Dataver::new(0));
+
+        for scene_id in 0..MAX_SCENES_PER_FABRIC as u8 {
+            assert_eq!(
+                scenes.add_scene(scene(FAB1, 0, scene_id)),
+                IMStatusCode::Success
+            );
+        }
+
+        assert_eq!(scenes.state.borrow().remaining_capacity(FAB1), 0);
+        assert_eq!(
+            scenes.add_scene(scene(FAB1, 0, 100)),
+            IMStatusCode::ResourceExhausted
+        );
+
+        // Other fabrics still have their share of the table
+        assert_eq!(
+            scenes.state.borrow().remaining_capacity(FAB2),
+            MAX_SCENES_PER_FABRIC
+        );
+        assert_eq!(scenes.add_scene(scene(FAB2, 0, 1)), IMStatusCode::Success);
+    }
+
+    #[test]
+    fn test_copy() {
+        let scenes = ScenesHandler::new(Dataver::new(0));
+
+        let mut named = scene(FAB1, 1, 1);
+        unwrap!(named.name.push_str("Evening"));
+
+        assert_eq!(scenes.add_scene(named), IMStatusCode::Success);
+        assert_eq!(scenes.add_scene(scene(FAB1, 1, 2)), IMStatusCode::Success);
+
+        assert_eq!(
+            scenes.copy_scene(FAB1, CopyModeBitmap::empty(), (1, 1), (2, 5)),
+            IMStatusCode::Success
+        );
+        assert_eq!(
+            unwrap!(scenes.state.borrow().find(FAB1, 2, 5))
+                .name
+                .as_str(),
+            "Evening"
+        );
+
+        assert_eq!(
+            scenes.copy_scene(FAB1, CopyModeBitmap::COPY_ALL_SCENES, (1, 0), (3, 0)),
+            IMStatusCode::Success
+        );
+        assert!(scenes.state.borrow().find(FAB1, 3, 1).is_some());
+        assert!(scenes.state.borrow().find(FAB1, 3, 2).is_some());
+
+        assert_eq!(
+            scenes.copy_scene(FAB1, CopyModeBitmap::empty(), (4, 1), (5, 1)),
+            IMStatusCode::NotFound
+        );
+    }
+
+    #[test]
+    fn test_global_scene() {
+        let on_off = OnOffHandler::new(Dataver::new(0));
+        let level = LevelControlHandler::new(Dataver::new(0));
+        let extensions: [&dyn SceneExtension; 2] = [&on_off, &level];
+
+        let mut scenes = ScenesHandler::new(Dataver::new(0));
+        scenes.set_extensions(&extensions);
+
+        let fabric_mgr = fabric_mgr(2);
+
+        assert!(!scenes.recall_global_scene(&fabric_mgr, FAB1));
+
+        on_off.set(true);
+        level.set(50);
+
+        scenes.store_global_scene(&fabric_mgr, FAB1);
+        scenes.store_global_scene(&fabric_mgr, FAB2);
+
+        on_off.set(false);
+        level.set(200);
+
+        assert!(scenes.recall_global_scene(&fabric_mgr, FAB1));
+        assert!(on_off.get());
+        assert_eq!(level.get(), Some(50));
+
+        // The scenes of a removed fabric are pruned
+        let fabric_mgr = self::fabric_mgr(1);
+        assert!(!scenes.recall_global_scene(&fabric_mgr, FAB2));
+        assert_eq!(scenes.state.borrow().scene_count(FAB2), 0);
+        assert_eq!(scenes.state.borrow().scene_count(FAB1), 1);
+    }
+
+    #[test]
+    fn test_persist() {
+        let mut buf = [0; 512];
+
+        let on_off = OnOffHandler::new(Dataver::new(0));
+        let extensions: [&dyn SceneExtension; 1] = [&on_off];
+
+        let mut scenes = ScenesHandler::new(Dataver::new(0));
+        scenes.set_extensions(&extensions);
+
+        let mut named = scene(FAB1, 1, 1);
+        unwrap!(named.name.push_str("Morning"));
+        named.transition_time = 1500;
+
+        assert_eq!(scenes.add_scene(named), IMStatusCode::Success);
+        assert_eq!(scenes.store_scene(FAB2, 0, 3), IMStatusCode::Success);
+
+        let data = unwrap!(scenes.store(&mut buf).unwrap());
+
+        let scenes2 = ScenesHandler::new(Dataver::new(0));
+        scenes2.load(data).unwrap();
+
+        assert_eq!(scenes2.state.borrow().scenes, scenes.state.borrow().scenes);
+        assert!(!scenes2.changed());
+        assert!(scenes.store(&mut buf).unwrap().is_none());
+    }
+}
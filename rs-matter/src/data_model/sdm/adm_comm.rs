@@ -115,7 +115,11 @@ impl ClusterHandler for AdminCommHandler {
             request.discriminator()?,
             request.commissioning_timeout()?,
             &matter.transport_mgr.mdns,
-        )
+        )?;
+
+        matter.notify_commissioning_changed();
+
+        Ok(())
     }
 
     fn handle_open_basic_commissioning_window(
@@ -130,16 +134,17 @@ impl ClusterHandler for AdminCommHandler {
             matter.dev_comm().discriminator,
             request.commissioning_timeout()?,
             &matter.transport_mgr.mdns,
-        )
+        )?;
+
+        matter.notify_commissioning_changed();
+
+        Ok(())
     }
 
     fn handle_revoke_commissioning(&self, ctx: &InvokeContext<'_>) -> Result<(), Error> {
         let matter = ctx.exchange().matter();
 
-        matter
-            .pase_mgr
-            .borrow_mut()
-            .disable_pase_session(&matter.transport_mgr.mdns)?;
+        matter.disable_commissioning()?;
 
         // TODO: Send status code if no commissioning window is open?
 
@@ -184,6 +184,8 @@ impl ClusterHandler for GenCommHandler<'_> {
                 .arm(request.expiry_length_seconds()?, sess.get_session_mode())
         }))?;
 
+        ctx.exchange().matter().notify_failsafe_changed();
+
         response.error_code(status)?.debug_text("")?.end()
     }
 
@@ -213,14 +215,12 @@ impl ClusterHandler for GenCommHandler<'_> {
                 .disarm(sess.get_session_mode())
         }))?;
 
+        ctx.exchange().matter().notify_failsafe_changed();
+
         if matches!(status, CommissioningErrorEnum::OK) {
             // As per section 5.5 of the Matter Core Spec V1.3 we have to teriminate the PASE session
             // upon completion of commissioning
-            ctx.exchange()
-                .matter()
-                .pase_mgr
-                .borrow_mut()
-                .disable_pase_session(&ctx.exchange().matter().transport_mgr.mdns)?;
+            ctx.exchange().matter().disable_commissioning()?;
         }
 
         response.error_code(status)?.debug_text("")?.end()
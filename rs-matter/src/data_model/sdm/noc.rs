@@ -526,46 +526,14 @@ impl ClusterHandler for NocHandler {
 
         let fab_idx = NonZeroU8::new(request.fabric_index()?).ok_or(ErrorCode::InvalidAction)?;
 
-        let status = if ctx
-            .exchange()
-            .matter()
-            .fabric_mgr
-            .borrow_mut()
-            .remove(fab_idx, &ctx.exchange().matter().transport_mgr.mdns)
-            .is_ok()
-        {
-            // If our own session is running on the fabric being removed,
-            // we need to expire it rather than immediately remove it, so that
-            // the response can be sent back properly
-            let expire_sess_id = ctx.exchange().with_session(|sess| {
-                Ok((sess.get_local_fabric_idx() == fab_idx.get()).then_some(sess.id()))
-            })?;
-
-            // Remove all sessions related to the fabric being removed
-            // If `expire_sess_id` is Some, the session will be expired instead of removed.
-            ctx.exchange()
-                .matter()
-                .transport_mgr
-                .session_mgr
-                .borrow_mut()
-                .remove_for_fabric(fab_idx, expire_sess_id);
-
-            // Notify that the fabrics need to be persisted
-            // We need to explicitly do this because if the fabric being removed
-            // is the one on which the session is running, the session will be removed
-            // and the response will fail
-            ctx.exchange().matter().notify_persist();
-
-            // Notify that a session was removed
-            ctx.exchange()
-                .matter()
-                .transport_mgr
-                .session_removed
-                .notify();
-
-            // Note that since we might have removed our own session, the exchange
-            // will terminate with a "NoSession" error, but that's OK and handled properly
-
+        // All sessions of the fabric being removed - including our own session, if it is running on that fabric -
+        // are expired rather than immediately removed, so that the response can be sent back properly.
+        // The sessions are closed by the transport once their ongoing exchanges are complete.
+        //
+        // Note also that `remove_fabric` explicitly notifies that the fabrics need to be persisted,
+        // because if the fabric being removed is the one on which our session is running,
+        // the session will be closed and the regular persistence notification might not happen.
+        let status = if ctx.exchange().matter().remove_fabric(fab_idx).is_ok() {
             info!("Removed operational fabric with local index {}", fab_idx);
 
             NodeOperationalCertStatusEnum::OK
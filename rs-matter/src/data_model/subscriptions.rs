@@ -22,6 +22,7 @@ use embassy_time::Instant;
 
 use portable_atomic::{AtomicU32, Ordering};
 
+use crate::transport::session::{Session, SessionEvictionPolicy, SessionMgr};
 use crate::utils::cell::RefCell;
 use crate::utils::init::{init, Init};
 use crate::utils::sync::Notification;
@@ -161,6 +162,14 @@ impl<const N: usize> Subscriptions<N> {
         }
     }
 
+    /// Return `true` if there is at least one subscription reported over the session with the provided ID.
+    pub fn has_session(&self, session_id: u32) -> bool {
+        self.subscriptions
+            .borrow()
+            .iter()
+            .any(|sub| sub.session_id == Some(session_id))
+    }
+
     pub(crate) fn find_removed_session<F>(
         &self,
         session_removed: F,
@@ -214,3 +223,13 @@ impl<const N: usize> Default for Subscriptions<N> {
         Self::new()
     }
 }
+
+/// Using the subscriptions as a session eviction policy protects the sessions
+/// over which subscriptions are reported from being evicted.
+///
+/// The eviction order of the remaining sessions is the one of `DefaultSessionEvictionPolicy`.
+impl<const N: usize> SessionEvictionPolicy for Subscriptions<N> {
+    fn can_evict(&self, session: &Session, _sessions: &SessionMgr) -> bool {
+        !self.has_session(session.id())
+    }
+}
@@ -20,10 +20,10 @@
 use core::fmt::Debug;
 
 use crate::data_model::objects::{
-    ArrayAttributeRead, Cluster, Dataver, Endpoint, EndptId, ReadContext,
+    ArrayAttributeRead, Cluster, Dataver, Endpoint, EndptId, ReadContext, SemanticTag,
 };
 use crate::error::{Error, ErrorCode};
-use crate::tlv::{TLVBuilderParent, ToTLVArrayBuilder, ToTLVBuilder};
+use crate::tlv::{Nullable, TLVBuilderParent, ToTLVArrayBuilder, ToTLVBuilder};
 use crate::with;
 
 pub use crate::data_model::clusters::descriptor::*;
@@ -55,6 +55,112 @@ impl PartsMatcher for AggregatorPartsMatcher {
     }
 }
 
+/// The pattern according to which an endpoint of a composed device lists its parts
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CompositionPattern {
+    /// Only the direct children of the endpoint are its parts
+    Tree,
+    /// All descendants of the endpoint (children, grandchildren and so on) are its parts
+    FullFamily,
+}
+
+/// The position of an endpoint in the composition hierarchy of the node
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Composition {
+    /// The ID of the endpoint
+    pub id: EndptId,
+    /// The ID of the parent endpoint
+    pub parent: EndptId,
+    /// The pattern according to which the endpoint lists its own parts
+    pub pattern: CompositionPattern,
+}
+
+impl Composition {
+    /// Create a new `Composition` instance for an endpoint listing its parts
+    /// according to the full-family pattern
+    pub const fn new(id: EndptId, parent: EndptId) -> Self {
+        Self {
+            id,
+            parent,
+            pattern: CompositionPattern::FullFamily,
+        }
+    }
+
+    /// Return a copy of the composition with the provided pattern
+    pub const fn with_pattern(self, pattern: CompositionPattern) -> Self {
+        Self { pattern, ..self }
+    }
+}
+
+/// A parts matcher suitable for nodes containing composed devices, i.e. devices
+/// whose endpoints are nested under other endpoints (as in multi-gang devices or
+/// bridged devices with more than one endpoint).
+///
+/// The hierarchy of the endpoints is described with a list of `Composition` entries.
+/// Endpoints not present in the list are children of the root endpoint (0) and
+/// list their parts according to the full-family pattern.
+///
+/// The root endpoint always lists all other endpoints of the node as its parts.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ComposedPartsMatcher<'a>(&'a [Composition]);
+
+impl<'a> ComposedPartsMatcher<'a> {
+    /// Create a new instance of `ComposedPartsMatcher` with the given composition hierarchy
+    pub const fn new(compositions: &'a [Composition]) -> Self {
+        Self(compositions)
+    }
+
+    fn composition(&self, endpoint: EndptId) -> Option<&Composition> {
+        self.0.iter().find(|composition| composition.id == endpoint)
+    }
+
+    fn parent(&self, endpoint: EndptId) -> EndptId {
+        self.composition(endpoint)
+            .map(|composition| composition.parent)
+            .unwrap_or(0)
+    }
+}
+
+impl PartsMatcher for ComposedPartsMatcher<'_> {
+    fn matches(&self, our_endpoint: EndptId, endpoint: EndptId) -> bool {
+        if endpoint == our_endpoint || endpoint == 0 {
+            return false;
+        }
+
+        if our_endpoint == 0 {
+            return true;
+        }
+
+        let pattern = self
+            .composition(our_endpoint)
+            .map(|composition| composition.pattern)
+            .unwrap_or(CompositionPattern::FullFamily);
+
+        match pattern {
+            CompositionPattern::Tree => self.parent(endpoint) == our_endpoint,
+            CompositionPattern::FullFamily => {
+                let mut ancestor = self.parent(endpoint);
+
+                // Bound the walk by the hierarchy depth so that a malformed (cyclic) hierarchy cannot loop forever
+                for _ in 0..self.0.len() {
+                    if ancestor == our_endpoint {
+                        return true;
+                    } else if ancestor == 0 {
+                        break;
+                    }
+
+                    ancestor = self.parent(ancestor);
+                }
+
+                false
+            }
+        }
+    }
+}
+
 /// A trait for describing which endpoints (parts) should be returned
 /// from the POV of our endpoint
 ///
@@ -68,6 +174,17 @@ pub trait PartsMatcher: Debug {
     /// - `our_endpoint`: The endpoint ID of the endpoint that is being queried
     /// - `endpoint`: The endpoint ID of the endpoint that is being checked
     fn matches(&self, our_endpoint: EndptId, endpoint: EndptId) -> bool;
+
+    /// Return a version of the set of endpoints matched by this matcher
+    ///
+    /// Matchers working on top of a node whose endpoints change at runtime should
+    /// return a different value each time the endpoints change, so that the dataver
+    /// of the Descriptor cluster changes as well.
+    ///
+    /// The default implementation returns 0, i.e. the endpoints are assumed to never change.
+    fn version(&self) -> u32 {
+        0
+    }
 }
 
 impl<T> PartsMatcher for &T
@@ -77,6 +194,10 @@ where
     fn matches(&self, our_endpoint: EndptId, endpoint: EndptId) -> bool {
         (**self).matches(our_endpoint, endpoint)
     }
+
+    fn version(&self) -> u32 {
+        (**self).version()
+    }
 }
 
 impl<T> PartsMatcher for &mut T
@@ -86,6 +207,10 @@ where
     fn matches(&self, our_endpoint: EndptId, endpoint: EndptId) -> bool {
         (**self).matches(our_endpoint, endpoint)
     }
+
+    fn version(&self) -> u32 {
+        (**self).version()
+    }
 }
 
 /// The system implementation of a handler for the Descriptor Matter cluster.
@@ -110,6 +235,17 @@ impl DescHandler<'static> {
     }
 }
 
+impl DescHandler<'_> {
+    /// The metadata of the Descriptor cluster with the `TagList` feature enabled.
+    ///
+    /// Should be used instead of `DescHandler::CLUSTER` on endpoints having semantic tags.
+    pub const TAGGED_CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(2)
+        .with_features(Feature::TAG_LIST.bits())
+        .with_attrs(with!(required; AttributeId::TagList))
+        .with_cmds(with!());
+}
+
 impl<'a> DescHandler<'a> {
     /// Create a new instance of `DescHandler` with the given `Dataver`
     /// and a custom matcher
@@ -122,6 +258,18 @@ impl<'a> DescHandler<'a> {
         HandlerAdaptor(self)
     }
 
+    fn read_tag<P: TLVBuilderParent>(
+        tag: &SemanticTag<'_>,
+        builder: SemanticTagStructBuilder<P>,
+    ) -> Result<P, Error> {
+        builder
+            .mfg_code(Nullable::new(tag.mfg_code))?
+            .namespace_id(tag.namespace_id)?
+            .tag(tag.tag)?
+            .label(tag.label.map(Nullable::some))?
+            .end()
+    }
+
     fn endpoint<'b>(ctx: &'b ReadContext<'_>) -> Result<&'b Endpoint<'b>, Error> {
         ctx.attr()
             .node
@@ -137,7 +285,7 @@ impl ClusterHandler for DescHandler<'_> {
         .with_cmds(with!());
 
     fn dataver(&self) -> u32 {
-        self.dataver.get()
+        self.dataver.get().wrapping_add(self.matcher.version())
     }
 
     fn dataver_changed(&self) {
@@ -165,7 +313,7 @@ impl ClusterHandler for DescHandler<'_> {
             }
             ArrayAttributeRead::ReadOne(index, builder) => {
                 let Some(dev_type) = endpoint.device_types.get(index as usize) else {
-                    return Err(ErrorCode::InvalidAction.into()); // TODO
+                    return Err(ErrorCode::ConstraintError.into());
                 };
 
                 builder
@@ -193,7 +341,7 @@ impl ClusterHandler for DescHandler<'_> {
             }
             ArrayAttributeRead::ReadOne(index, builder) => {
                 let Some(cluster) = endpoint.clusters.get(index as usize) else {
-                    return Err(ErrorCode::InvalidAction.into()); // TODO
+                    return Err(ErrorCode::ConstraintError.into());
                 };
 
                 builder.set(&cluster.id)
@@ -208,10 +356,10 @@ impl ClusterHandler for DescHandler<'_> {
     ) -> Result<P, Error> {
         let _endpoint = Self::endpoint(ctx)?;
 
-        // Client clusters not support yet
+        // Client clusters not supported yet, hence the list is always empty
         match builder {
             ArrayAttributeRead::ReadAll(builder) => builder.end(),
-            ArrayAttributeRead::ReadOne(_, _) => Err(ErrorCode::InvalidAction.into()), // TODO
+            ArrayAttributeRead::ReadOne(_, _) => Err(ErrorCode::ConstraintError.into()),
         }
     }
 
@@ -238,11 +386,84 @@ impl ClusterHandler for DescHandler<'_> {
             }
             ArrayAttributeRead::ReadOne(index, builder) => {
                 let Some(ep_id) = ep_ids.nth(index as usize) else {
-                    return Err(ErrorCode::InvalidAction.into()); // TODO
+                    return Err(ErrorCode::ConstraintError.into());
                 };
 
                 builder.set(&ep_id)
             }
         }
     }
+
+    fn tag_list<P: TLVBuilderParent>(
+        &self,
+        ctx: &ReadContext<'_>,
+        builder: ArrayAttributeRead<SemanticTagStructArrayBuilder<P>, SemanticTagStructBuilder<P>>,
+    ) -> Result<P, Error> {
+        let endpoint = Self::endpoint(ctx)?;
+
+        match builder {
+            ArrayAttributeRead::ReadAll(mut builder) => {
+                for tag in endpoint.tags() {
+                    builder = Self::read_tag(tag, builder.push()?)?;
+                }
+
+                builder.end()
+            }
+            ArrayAttributeRead::ReadOne(index, builder) => {
+                let Some(tag) = endpoint.tags().get(index as usize) else {
+                    return Err(ErrorCode::ConstraintError.into());
+                };
+
+                Self::read_tag(tag, builder)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ComposedPartsMatcher, Composition, CompositionPattern, PartsMatcher};
+
+    // 1: a multi-gang device composed of 2 and 3, where 3 is composed of 4
+    // 5: a device composed of 6 and 7 using the tree pattern, where 6 is composed of 8
+    const COMPOSITIONS: &[Composition] = &[
+        Composition::new(2, 1),
+        Composition::new(3, 1),
+        Composition::new(4, 3),
+        Composition::new(5, 0).with_pattern(CompositionPattern::Tree),
+        Composition::new(6, 5),
+        Composition::new(7, 5),
+        Composition::new(8, 6),
+    ];
+
+    #[test]
+    fn test_composed_parts() {
+        let matcher = ComposedPartsMatcher::new(COMPOSITIONS);
+
+        let parts = |ep| {
+            (0..10)
+                .filter(|part| matcher.matches(ep, *part))
+                .collect::<heapless::Vec<_, 10>>()
+        };
+
+        assert_eq!(parts(0), [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(parts(1), [2, 3, 4]);
+        assert_eq!(parts(2), []);
+        assert_eq!(parts(3), [4]);
+        assert_eq!(parts(5), [6, 7]);
+        assert_eq!(parts(6), [8]);
+        assert_eq!(parts(9), []);
+    }
+
+    #[test]
+    fn test_cyclic_composition() {
+        const CYCLIC: &[Composition] = &[Composition::new(1, 2), Composition::new(2, 1)];
+
+        let matcher = ComposedPartsMatcher::new(CYCLIC);
+
+        assert!(matcher.matches(1, 2));
+        assert!(matcher.matches(2, 1));
+        assert!(!matcher.matches(1, 3));
+        assert!(!matcher.matches(3, 1));
+    }
 }
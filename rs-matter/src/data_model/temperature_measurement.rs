@@ -0,0 +1,28 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Temperature Measurement cluster and its handler.
+
+pub use crate::data_model::clusters::temperature_measurement::*;
+
+pub use super::measurement::{MeasurementHandler, Temperature};
+
+/// A handler for the Temperature Measurement Matter cluster.
+///
+/// The temperature is in units of 0.01 degrees Celsius, and is pushed by the sensor driver
+/// with `TemperatureMeasurementHandler::push_measurement`.
+pub type TemperatureMeasurementHandler = MeasurementHandler<Temperature>;
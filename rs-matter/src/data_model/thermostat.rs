@@ -0,0 +1,2109 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Thermostat cluster and its handler.
+//!
+//! The handler implements the Heating, Cooling, AutoMode, ScheduleConfiguration and Presets features of the cluster.
+//!
+//! The `Presets` attribute can only be written with an atomic write (see `ClusterHandler::commit_atomic_write`),
+//! hence the handler should be used with a `DataModel` created with `DataModel::new_with_atomic_writes`.
+//!
+//! Note that the MatterScheduleConfiguration feature (i.e. the `Schedules` attribute and the `SetActiveScheduleRequest`
+//! command) is not supported yet; the weekly schedule of the ScheduleConfiguration feature is supported instead.
+
+use embassy_futures::select::select;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::{Duration, Timer};
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{
+    FromTLV, Nullable, NullableBuilder, Octets, OctetsBuilder, OctetsOwned, TLVArray,
+    TLVBuilderParent, TLVElement, TLVTag, ToTLV,
+};
+use crate::utils::cell::RefCell;
+use crate::utils::storage::{Vec, WriteBuf};
+use crate::utils::sync::Notification;
+use crate::with;
+
+use super::objects::{
+    ArrayAttributeRead, ArrayAttributeWrite, AtomicWrite, AttrId, Cluster, Dataver, InvokeContext,
+    ReadContext, WriteContext,
+};
+
+pub use crate::data_model::clusters::thermostat::*;
+
+/// The absolute minimum heating setpoint, in 0.01°C.
+pub const ABS_MIN_HEAT_SETPOINT: i16 = 700;
+/// The absolute maximum heating setpoint, in 0.01°C.
+pub const ABS_MAX_HEAT_SETPOINT: i16 = 3000;
+/// The absolute minimum cooling setpoint, in 0.01°C.
+pub const ABS_MIN_COOL_SETPOINT: i16 = 1600;
+/// The absolute maximum cooling setpoint, in 0.01°C.
+pub const ABS_MAX_COOL_SETPOINT: i16 = 3200;
+
+/// The maximum number of transitions in the schedule of a single day (and mode).
+pub const MAX_DAILY_TRANSITIONS: usize = 10;
+/// The maximum number of daily schedules, i.e. one per day of the week (and the Away "day") and mode.
+const MAX_DAILY_SCHEDULES: usize = 16;
+
+/// The maximum number of presets.
+pub const MAX_PRESETS: usize = 6;
+/// The maximum length of the handle of a preset, in bytes.
+const MAX_PRESET_HANDLE_LEN: usize = 16;
+/// The maximum length of the name of a preset.
+const MAX_PRESET_NAME_LEN: usize = 64;
+
+/// The supported preset scenarios, with the maximum number of presets for each.
+const PRESET_TYPES: [(PresetScenarioEnum, u8); 7] = [
+    (PresetScenarioEnum::Occupied, 1),
+    (PresetScenarioEnum::Unoccupied, 1),
+    (PresetScenarioEnum::Sleep, 1),
+    (PresetScenarioEnum::Wake, 1),
+    (PresetScenarioEnum::Vacation, 1),
+    (PresetScenarioEnum::GoingToSleep, 1),
+    (PresetScenarioEnum::UserDefined, 2),
+];
+
+/// The hysteresis of the control loop, in 0.01°C.
+const HYSTERESIS: i16 = 50;
+/// The interval at which the control loop samples the local temperature.
+const CONTROL_INTERVAL_SECS: u64 = 10;
+/// The number of minutes in a day, i.e. the upper (exclusive) bound of a schedule transition time.
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// The days (and the away/vacation pseudo-day) of the weekly schedule, in the order of their bits
+const SCHEDULE_DAYS: [ScheduleDayOfWeekBitmap; 8] = [
+    ScheduleDayOfWeekBitmap::SUNDAY,
+    ScheduleDayOfWeekBitmap::MONDAY,
+    ScheduleDayOfWeekBitmap::TUESDAY,
+    ScheduleDayOfWeekBitmap::WEDNESDAY,
+    ScheduleDayOfWeekBitmap::THURSDAY,
+    ScheduleDayOfWeekBitmap::FRIDAY,
+    ScheduleDayOfWeekBitmap::SATURDAY,
+    ScheduleDayOfWeekBitmap::AWAY,
+];
+
+/// A trait for the hardware (or the driver) of a thermostat, i.e. its temperature sensor and its relays.
+pub trait ThermostatHooks {
+    /// Return the local temperature in 0.01°C, or `None` if it cannot be measured.
+    fn local_temperature(&self) -> Option<i16>;
+
+    /// Switch the heating, cooling and fan relays.
+    fn set_running_state(&self, state: RelayStateBitmap);
+
+    /// Return the current local day of the week and the minutes since local midnight,
+    /// or `None` if the local time is unknown.
+    ///
+    /// The weekly schedule is only applied when the local time is known.
+    ///
+    /// The default implementation returns `None`.
+    fn local_time(&self) -> Option<(ScheduleDayOfWeekBitmap, u16)> {
+        None
+    }
+}
+
+impl<T> ThermostatHooks for &T
+where
+    T: ThermostatHooks,
+{
+    fn local_temperature(&self) -> Option<i16> {
+        (*self).local_temperature()
+    }
+
+    fn set_running_state(&self, state: RelayStateBitmap) {
+        (*self).set_running_state(state)
+    }
+
+    fn local_time(&self) -> Option<(ScheduleDayOfWeekBitmap, u16)> {
+        (*self).local_time()
+    }
+}
+
+/// A no-op implementation for devices that only need to observe the state of the cluster
+/// with `ThermostatHandler::running_state`.
+///
+/// Since no local temperature is reported, the relays are never switched on (except for the fan).
+impl ThermostatHooks for () {
+    fn local_temperature(&self) -> Option<i16> {
+        None
+    }
+
+    fn set_running_state(&self, _state: RelayStateBitmap) {}
+}
+
+/// A transition of a daily schedule.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct ScheduleTransition {
+    /// The minutes since midnight
+    time: u16,
+    /// The setpoint, in 0.01°C
+    setpoint: i16,
+}
+
+/// The schedule of a single day of the week (or of the Away "day") for either heating or cooling.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct DailySchedule {
+    /// The day, as a single bit of `ScheduleDayOfWeekBitmap`
+    day: u8,
+    /// `true` for the cooling setpoint schedule, `false` for the heating one
+    cool: bool,
+    /// The transitions, sorted by time
+    transitions: Vec<ScheduleTransition, MAX_DAILY_TRANSITIONS>,
+}
+
+/// A preset, i.e. a pair of setpoints for a given scenario.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Preset {
+    /// The handle of the preset, assigned by the handler
+    handle: OctetsOwned<MAX_PRESET_HANDLE_LEN>,
+    scenario: PresetScenarioEnum,
+    name: Option<heapless::String<MAX_PRESET_NAME_LEN>>,
+    /// The heating setpoint, in 0.01°C
+    heating_setpoint: i16,
+    /// The cooling setpoint, in 0.01°C
+    cooling_setpoint: i16,
+}
+
+/// The persisted part of the state of the Thermostat cluster.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct ThermostatSettings {
+    occupied_heating_setpoint: i16,
+    occupied_cooling_setpoint: i16,
+    min_heat_setpoint_limit: i16,
+    max_heat_setpoint_limit: i16,
+    min_cool_setpoint_limit: i16,
+    max_cool_setpoint_limit: i16,
+    min_setpoint_dead_band: i8,
+    local_temperature_calibration: i8,
+    control_sequence: ControlSequenceOfOperationEnum,
+    system_mode: SystemModeEnum,
+    programming_operation_mode: ProgrammingOperationModeBitmap,
+    schedules: Vec<DailySchedule, MAX_DAILY_SCHEDULES>,
+    presets: Vec<Preset, MAX_PRESETS>,
+    active_preset: Option<OctetsOwned<MAX_PRESET_HANDLE_LEN>>,
+    /// The handle to be assigned to the next new preset
+    next_preset_handle: u8,
+}
+
+impl ThermostatSettings {
+    const fn new() -> Self {
+        Self {
+            occupied_heating_setpoint: 2000,
+            occupied_cooling_setpoint: 2600,
+            min_heat_setpoint_limit: ABS_MIN_HEAT_SETPOINT,
+            max_heat_setpoint_limit: ABS_MAX_HEAT_SETPOINT,
+            min_cool_setpoint_limit: ABS_MIN_COOL_SETPOINT,
+            max_cool_setpoint_limit: ABS_MAX_COOL_SETPOINT,
+            min_setpoint_dead_band: 25,
+            local_temperature_calibration: 0,
+            control_sequence: ControlSequenceOfOperationEnum::CoolingAndHeating,
+            system_mode: SystemModeEnum::Off,
+            programming_operation_mode: ProgrammingOperationModeBitmap::empty(),
+            schedules: Vec::new(),
+            presets: Vec::new(),
+            active_preset: None,
+            next_preset_handle: 0,
+        }
+    }
+
+    /// The `MinSetpointDeadBand` attribute in 0.01°C.
+    fn dead_band(&self) -> i16 {
+        self.min_setpoint_dead_band as i16 * 10
+    }
+
+    /// Set the heating setpoint, pushing the cooling setpoint up if necessary to preserve the deadband.
+    fn set_heating_setpoint(&mut self, setpoint: i16) -> Result<(), Error> {
+        if !(self.min_heat_setpoint_limit..=self.max_heat_setpoint_limit).contains(&setpoint) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let min_cool = setpoint.saturating_add(self.dead_band());
+
+        if self.occupied_cooling_setpoint < min_cool {
+            if min_cool > self.max_cool_setpoint_limit {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            self.occupied_cooling_setpoint = min_cool;
+        }
+
+        self.occupied_heating_setpoint = setpoint;
+
+        Ok(())
+    }
+
+    /// Set the cooling setpoint, pushing the heating setpoint down if necessary to preserve the deadband.
+    fn set_cooling_setpoint(&mut self, setpoint: i16) -> Result<(), Error> {
+        if !(self.min_cool_setpoint_limit..=self.max_cool_setpoint_limit).contains(&setpoint) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let max_heat = setpoint.saturating_sub(self.dead_band());
+
+        if self.occupied_heating_setpoint > max_heat {
+            if max_heat < self.min_heat_setpoint_limit {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            self.occupied_heating_setpoint = max_heat;
+        }
+
+        self.occupied_cooling_setpoint = setpoint;
+
+        Ok(())
+    }
+
+    /// Check the setpoint limits against the absolute limits and the deadband,
+    /// and move the setpoints within the limits.
+    fn check_limits(&mut self) -> Result<(), Error> {
+        let dead_band = self.dead_band();
+
+        if !(ABS_MIN_HEAT_SETPOINT <= self.min_heat_setpoint_limit
+            && self.min_heat_setpoint_limit <= self.max_heat_setpoint_limit
+            && self.max_heat_setpoint_limit <= ABS_MAX_HEAT_SETPOINT
+            && ABS_MIN_COOL_SETPOINT <= self.min_cool_setpoint_limit
+            && self.min_cool_setpoint_limit <= self.max_cool_setpoint_limit
+            && self.max_cool_setpoint_limit <= ABS_MAX_COOL_SETPOINT
+            && self.min_heat_setpoint_limit <= self.min_cool_setpoint_limit - dead_band
+            && self.max_heat_setpoint_limit <= self.max_cool_setpoint_limit - dead_band)
+        {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        self.occupied_heating_setpoint = self
+            .occupied_heating_setpoint
+            .clamp(self.min_heat_setpoint_limit, self.max_heat_setpoint_limit);
+        self.occupied_cooling_setpoint = self
+            .occupied_cooling_setpoint
+            .clamp(self.min_cool_setpoint_limit, self.max_cool_setpoint_limit);
+
+        if self.occupied_cooling_setpoint - self.occupied_heating_setpoint < dead_band {
+            // Always possible, as the limits preserve the deadband
+            self.occupied_heating_setpoint =
+                (self.occupied_cooling_setpoint - dead_band).max(self.min_heat_setpoint_limit);
+            self.occupied_cooling_setpoint = self
+                .occupied_cooling_setpoint
+                .max(self.occupied_heating_setpoint + dead_band);
+        }
+
+        Ok(())
+    }
+
+    /// Return `true` if the given system mode is supported with the current control sequence of operation.
+    fn system_mode_allowed(&self, mode: SystemModeEnum) -> bool {
+        let heat = !matches!(
+            self.control_sequence,
+            ControlSequenceOfOperationEnum::CoolingOnly
+                | ControlSequenceOfOperationEnum::CoolingWithReheat
+        );
+        let cool = !matches!(
+            self.control_sequence,
+            ControlSequenceOfOperationEnum::HeatingOnly
+                | ControlSequenceOfOperationEnum::HeatingWithReheat
+        );
+
+        match mode {
+            SystemModeEnum::Off | SystemModeEnum::FanOnly => true,
+            SystemModeEnum::Heat | SystemModeEnum::EmergencyHeat => heat,
+            SystemModeEnum::Cool | SystemModeEnum::Precooling => cool,
+            SystemModeEnum::Auto => heat && cool,
+            _ => false,
+        }
+    }
+
+    /// Replace the presets with the given ones, as per a committed atomic write of the `Presets` attribute.
+    ///
+    /// Presets with a null handle are new presets, and are assigned a handle, while presets with a handle
+    /// replace the existing presets with the same handle. Existing presets which are missing from the list are removed,
+    /// except for the active preset, whose removal is rejected.
+    fn set_presets<'a, I>(&mut self, presets: I) -> Result<(), Error>
+    where
+        I: Iterator<Item = Result<PresetStruct<'a>, Error>>,
+    {
+        let mut new_presets = Vec::<Preset, MAX_PRESETS>::new();
+
+        for preset in presets {
+            let preset = preset?;
+
+            // There are no built-in presets
+            if preset.built_in()?.into_option().unwrap_or(false) {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            let scenario = preset.preset_scenario()?;
+
+            let Some((_, max_presets)) = PRESET_TYPES.iter().find(|(s, _)| *s == scenario) else {
+                Err(ErrorCode::ConstraintError)?
+            };
+
+            if new_presets
+                .iter()
+                .filter(|preset| preset.scenario == scenario)
+                .count()
+                >= *max_presets as usize
+            {
+                Err(ErrorCode::ResourceExhausted)?;
+            }
+
+            let handle = match preset.preset_handle()?.into_option() {
+                Some(handle) => {
+                    if !self
+                        .presets
+                        .iter()
+                        .any(|preset| preset.handle.vec == handle.0)
+                    {
+                        Err(ErrorCode::NotFound)?;
+                    }
+
+                    if new_presets
+                        .iter()
+                        .any(|preset| preset.handle.vec == handle.0)
+                    {
+                        Err(ErrorCode::ConstraintError)?;
+                    }
+
+                    OctetsOwned {
+                        vec: unwrap!(handle.0.try_into()),
+                    }
+                }
+                None => self.new_preset_handle(&new_presets),
+            };
+
+            let name = preset
+                .name()?
+                .and_then(Nullable::into_option)
+                .map(|name| name.try_into().map_err(|_| ErrorCode::ConstraintError))
+                .transpose()?;
+
+            // Both setpoints are mandatory, as the handler supports both heating and cooling
+            let (Some(heating_setpoint), Some(cooling_setpoint)) =
+                (preset.heating_setpoint()?, preset.cooling_setpoint()?)
+            else {
+                Err(ErrorCode::ConstraintError)?
+            };
+
+            if !(self.min_heat_setpoint_limit..=self.max_heat_setpoint_limit)
+                .contains(&heating_setpoint)
+                || !(self.min_cool_setpoint_limit..=self.max_cool_setpoint_limit)
+                    .contains(&cooling_setpoint)
+                || cooling_setpoint - heating_setpoint < self.dead_band()
+            {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            new_presets
+                .push(Preset {
+                    handle,
+                    scenario,
+                    name,
+                    heating_setpoint,
+                    cooling_setpoint,
+                })
+                .map_err(|_| ErrorCode::ResourceExhausted)?;
+        }
+
+        if let Some(active_preset) = &self.active_preset {
+            if !new_presets
+                .iter()
+                .any(|preset| preset.handle == *active_preset)
+            {
+                Err(ErrorCode::InvalidState)?;
+            }
+        }
+
+        self.presets = new_presets;
+
+        Ok(())
+    }
+
+    /// Return a handle for a new preset, which is not used by any of the existing or the new presets.
+    fn new_preset_handle(&mut self, new_presets: &[Preset]) -> OctetsOwned<MAX_PRESET_HANDLE_LEN> {
+        loop {
+            let handle = self.next_preset_handle;
+            self.next_preset_handle = self.next_preset_handle.wrapping_add(1);
+
+            // Terminates, as there are much fewer presets than single-byte handles
+            if !self
+                .presets
+                .iter()
+                .chain(new_presets)
+                .any(|preset| preset.handle.vec == [handle])
+            {
+                return OctetsOwned {
+                    vec: unwrap!(Vec::from_slice(&[handle])),
+                };
+            }
+        }
+    }
+
+    /// Make the preset with the given handle active, applying its setpoints,
+    /// or - with a `None` handle - clear the active preset.
+    fn set_active_preset(&mut self, handle: Option<&[u8]>) -> Result<(), Error> {
+        let Some(handle) = handle else {
+            self.active_preset = None;
+            return Ok(());
+        };
+
+        let preset = self
+            .presets
+            .iter()
+            .find(|preset| preset.handle.vec == handle)
+            .cloned()
+            .ok_or(ErrorCode::InvalidCommand)?;
+
+        // Move the heating setpoint first, so that the cooling one is not held back by it
+        // (the deadband between the two setpoints of the preset was checked when the preset was written)
+        self.set_heating_setpoint(preset.heating_setpoint)?;
+        self.set_cooling_setpoint(preset.cooling_setpoint)?;
+        self.set_heating_setpoint(preset.heating_setpoint)?;
+
+        self.active_preset = Some(preset.handle);
+
+        Ok(())
+    }
+
+    /// Return the schedule of the given day and mode.
+    fn schedule(&self, day: u8, cool: bool) -> Option<&DailySchedule> {
+        self.schedules
+            .iter()
+            .find(|schedule| schedule.day == day && schedule.cool == cool)
+    }
+}
+
+/// The state of the Thermostat cluster.
+struct ThermostatState {
+    settings: ThermostatSettings,
+    local_temperature: Option<i16>,
+    running_state: RelayStateBitmap,
+    setpoint_change_source: SetpointChangeSourceEnum,
+    setpoint_change_amount: Option<i16>,
+    /// The day and the time of the last applied heating and cooling schedule transitions
+    applied_transitions: [Option<(u8, u16)>; 2],
+    changed: bool,
+}
+
+impl ThermostatState {
+    const fn new() -> Self {
+        Self {
+            settings: ThermostatSettings::new(),
+            local_temperature: None,
+            running_state: RelayStateBitmap::empty(),
+            setpoint_change_source: SetpointChangeSourceEnum::Manual,
+            setpoint_change_amount: None,
+            applied_transitions: [None, None],
+            changed: false,
+        }
+    }
+
+    fn running_mode(&self) -> ThermostatRunningModeEnum {
+        if self.running_state.contains(RelayStateBitmap::HEAT) {
+            ThermostatRunningModeEnum::Heat
+        } else if self.running_state.contains(RelayStateBitmap::COOL) {
+            ThermostatRunningModeEnum::Cool
+        } else {
+            ThermostatRunningModeEnum::Off
+        }
+    }
+
+    /// Compute the state of the relays for the current local temperature, system mode and setpoints.
+    fn next_running_state(&self) -> RelayStateBitmap {
+        let (heat, cool) = match self.settings.system_mode {
+            SystemModeEnum::Heat | SystemModeEnum::EmergencyHeat => (true, false),
+            SystemModeEnum::Cool | SystemModeEnum::Precooling => (false, true),
+            SystemModeEnum::Auto => (true, true),
+            SystemModeEnum::FanOnly => return RelayStateBitmap::FAN,
+            _ => return RelayStateBitmap::empty(),
+        };
+
+        let Some(temperature) = self.local_temperature else {
+            return RelayStateBitmap::empty();
+        };
+
+        let heating_setpoint = self.settings.occupied_heating_setpoint;
+        let cooling_setpoint = self.settings.occupied_cooling_setpoint;
+
+        let heat = heat
+            && if self.running_state.contains(RelayStateBitmap::HEAT) {
+                temperature < heating_setpoint
+            } else {
+                temperature < heating_setpoint - HYSTERESIS
+            };
+
+        let cool = cool
+            && if self.running_state.contains(RelayStateBitmap::COOL) {
+                temperature > cooling_setpoint
+            } else {
+                temperature > cooling_setpoint + HYSTERESIS
+            };
+
+        let mut state = RelayStateBitmap::empty();
+        state.set(RelayStateBitmap::HEAT, heat);
+        state.set(RelayStateBitmap::COOL, cool);
+
+        state
+    }
+
+    /// Apply the transitions of the weekly schedule which are due at the given local time.
+    ///
+    /// Return `true` if any setpoint was changed.
+    fn apply_schedule(&mut self, day: ScheduleDayOfWeekBitmap, minute: u16) -> bool {
+        if !self
+            .settings
+            .programming_operation_mode
+            .contains(ProgrammingOperationModeBitmap::SCHEDULE_ACTIVE)
+        {
+            return false;
+        }
+
+        let mut changed = false;
+
+        for (slot, cool) in [false, true].into_iter().enumerate() {
+            let Some(transition) = self
+                .settings
+                .schedule(day.bits(), cool)
+                .and_then(|schedule| {
+                    schedule
+                        .transitions
+                        .iter()
+                        .rev()
+                        .find(|transition| transition.time <= minute)
+                })
+                .cloned()
+            else {
+                continue;
+            };
+
+            let applied = Some((day.bits(), transition.time));
+
+            if self.applied_transitions[slot] == applied {
+                continue;
+            }
+
+            self.applied_transitions[slot] = applied;
+
+            let prev = if cool {
+                self.settings.occupied_cooling_setpoint
+            } else {
+                self.settings.occupied_heating_setpoint
+            };
+
+            // The setpoints of a transition were validated against the limits when the schedule was set,
+            // but the limits (or the deadband) might have changed since then
+            let result = if cool {
+                self.settings.set_cooling_setpoint(transition.setpoint)
+            } else {
+                self.settings.set_heating_setpoint(transition.setpoint)
+            };
+
+            if result.is_ok() {
+                self.setpoint_change_source = SetpointChangeSourceEnum::Schedule;
+                self.setpoint_change_amount = Some(transition.setpoint - prev);
+                self.changed = true;
+
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}
+
+/// A handler for the Thermostat Matter cluster.
+///
+/// The handler reads the local temperature and drives the heating and cooling relays via the `ThermostatHooks` trait,
+/// in a simple on/off control loop with a hysteresis of 0.5°C. The control loop is only running
+/// while the `ThermostatHandler::run` future is polled.
+///
+/// The setpoints, the setpoint limits, the system mode and the weekly schedule should be persisted
+/// (see `ThermostatHandler::load` and `ThermostatHandler::store`).
+pub struct ThermostatHandler<'a> {
+    dataver: Dataver,
+    state: RefCell<ThermostatState>,
+    hooks: &'a dyn ThermostatHooks,
+    control_changed: Notification<NoopRawMutex>,
+    persist_state_changed: Notification<NoopRawMutex>,
+}
+
+impl<'a> ThermostatHandler<'a> {
+    /// Creates a new instance of `ThermostatHandler` with the given `Dataver`.
+    ///
+    /// The thermostat is initially off, with a heating setpoint of 20°C and a cooling setpoint of 26°C.
+    pub const fn new(dataver: Dataver) -> Self {
+        Self {
+            dataver,
+            state: RefCell::new(ThermostatState::new()),
+            hooks: &(),
+            control_changed: Notification::new(),
+            persist_state_changed: Notification::new(),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Set the hooks which drive the hardware of the device.
+    pub fn set_hooks(&mut self, hooks: &'a dyn ThermostatHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Return the system mode.
+    pub fn system_mode(&self) -> SystemModeEnum {
+        self.state.borrow().settings.system_mode
+    }
+
+    /// Return the heating and the cooling setpoints, in 0.01°C.
+    pub fn setpoints(&self) -> (i16, i16) {
+        let state = self.state.borrow();
+
+        (
+            state.settings.occupied_heating_setpoint,
+            state.settings.occupied_cooling_setpoint,
+        )
+    }
+
+    /// Return the state of the heating, cooling and fan relays.
+    pub fn running_state(&self) -> RelayStateBitmap {
+        self.state.borrow().running_state
+    }
+
+    /// Return the local temperature (with the calibration applied), in 0.01°C.
+    pub fn local_temperature(&self) -> Option<i16> {
+        self.state.borrow().local_temperature
+    }
+
+    /// Run the control loop.
+    ///
+    /// # Arguments
+    /// - `notify`: A callback which is called whenever the control loop changes the state of the cluster,
+    ///   and which is expected to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`)
+    pub async fn run<F>(&self, notify: F) -> Result<(), Error>
+    where
+        F: Fn(),
+    {
+        loop {
+            if self.control() {
+                notify();
+            }
+
+            select(
+                Timer::after(Duration::from_secs(CONTROL_INTERVAL_SECS)),
+                self.control_changed.wait(),
+            )
+            .await;
+        }
+    }
+
+    /// Load the persisted settings and weekly schedule from a byte slice.
+    ///
+    /// # Arguments
+    /// - `data`: The byte slice to load the state from
+    pub fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let settings = ThermostatSettings::from_tlv(&TLVElement::new(data))?;
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.settings = settings;
+            state.changed = false;
+        }
+
+        self.dataver.changed();
+        self.control_changed.notify();
+
+        Ok(())
+    }
+
+    /// Store the settings and the weekly schedule into a byte slice.
+    ///
+    /// # Arguments
+    /// - `buf`: The byte slice to store the state into
+    ///
+    /// Returns `Ok(None)` if the state has not changed, `Ok(Some(data))` if the state has changed
+    /// where `data` is the sub-slice of the buffer that contains the data to be persisted
+    pub fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        let mut state = self.state.borrow_mut();
+
+        if !state.changed {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        state.settings.to_tlv(&TLVTag::Anonymous, &mut wb)?;
+
+        state.changed = false;
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    /// Return `true` if the state has changed and needs to be persisted.
+    pub fn changed(&self) -> bool {
+        self.state.borrow().changed
+    }
+
+    /// Wait for the state to be changed in a way that requires persisting.
+    pub async fn wait_persist(&self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+
+            self.persist_state_changed.wait().await;
+        }
+    }
+
+    /// Run one iteration of the control loop: sample the local temperature, apply the weekly schedule
+    /// and switch the relays.
+    ///
+    /// Return `true` if the state of the cluster changed.
+    fn control(&self) -> bool {
+        let temperature = self.hooks.local_temperature();
+        let local_time = self.hooks.local_time();
+
+        let (changed, persist, relays) = {
+            let mut state = self.state.borrow_mut();
+
+            let persist = local_time
+                .map(|(day, minute)| state.apply_schedule(day, minute))
+                .unwrap_or(false);
+
+            let temperature = temperature.map(|temperature| {
+                temperature.saturating_add(state.settings.local_temperature_calibration as i16 * 10)
+            });
+
+            let mut changed = persist;
+
+            if state.local_temperature != temperature {
+                state.local_temperature = temperature;
+                changed = true;
+            }
+
+            let running_state = state.next_running_state();
+
+            let relays = (state.running_state != running_state).then_some(running_state);
+
+            if relays.is_some() {
+                state.running_state = running_state;
+                changed = true;
+            }
+
+            (changed, persist, relays)
+        };
+
+        if let Some(relays) = relays {
+            self.hooks.set_running_state(relays);
+        }
+
+        if persist {
+            self.persist_state_changed.notify();
+        }
+
+        if changed {
+            self.dataver.changed();
+        }
+
+        changed
+    }
+
+    /// Update the persisted settings, as a result of a write or a command from a client.
+    fn update<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ThermostatState) -> Result<(), Error>,
+    {
+        {
+            let mut state = self.state.borrow_mut();
+
+            let prev = state.settings.clone();
+
+            if let Err(err) = f(&mut state) {
+                // Restore the settings, as the update might have been applied partially
+                state.settings = prev;
+
+                return Err(err);
+            }
+
+            state.changed = true;
+        }
+
+        self.persist_state_changed.notify();
+        self.control_changed.notify();
+        self.dataver.changed();
+
+        Ok(())
+    }
+
+    /// Update one of the setpoints, as a result of a write from a client.
+    fn update_setpoint(&self, ctx: &WriteContext, cool: bool, setpoint: i16) -> Result<(), Error> {
+        self.update(|state| {
+            let prev = if cool {
+                state.settings.set_cooling_setpoint(setpoint)?;
+                state.settings.occupied_cooling_setpoint
+            } else {
+                state.settings.set_heating_setpoint(setpoint)?;
+                state.settings.occupied_heating_setpoint
+            };
+
+            state.setpoint_change_source = SetpointChangeSourceEnum::Manual;
+            state.setpoint_change_amount = Some(setpoint - prev);
+
+            Ok(())
+        })?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    /// Update one of the setpoint limits, as a result of a write from a client.
+    fn update_limits<F>(&self, ctx: &WriteContext, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ThermostatSettings),
+    {
+        self.update(|state| {
+            f(&mut state.settings);
+            state.settings.check_limits()
+        })?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    /// Raise or lower the setpoints by the given amount (in 0.1°C), as per the `SetpointRaiseLower` command.
+    ///
+    /// The setpoints are clamped to their limits, and to the limits which preserve the deadband.
+    fn raise_lower(&self, mode: SetpointRaiseLowerModeEnum, amount: i8) -> Result<(), Error> {
+        let amount = amount as i16 * 10;
+
+        self.update(|state| {
+            let settings = &mut state.settings;
+            let dead_band = settings.dead_band();
+
+            let heat = matches!(
+                mode,
+                SetpointRaiseLowerModeEnum::Heat | SetpointRaiseLowerModeEnum::Both
+            );
+            let cool = matches!(
+                mode,
+                SetpointRaiseLowerModeEnum::Cool | SetpointRaiseLowerModeEnum::Both
+            );
+
+            // When raising, move the cooling setpoint first, so that the heating one is not held back by it
+            // (and vice versa when lowering)
+            let order = if amount > 0 {
+                [(cool, true), (heat, false)]
+            } else {
+                [(heat, false), (cool, true)]
+            };
+
+            for (_, cool) in order.into_iter().filter(|(enabled, _)| *enabled) {
+                if cool {
+                    let setpoint = settings
+                        .occupied_cooling_setpoint
+                        .saturating_add(amount)
+                        .clamp(
+                            settings
+                                .min_cool_setpoint_limit
+                                .max(settings.min_heat_setpoint_limit + dead_band),
+                            settings.max_cool_setpoint_limit,
+                        );
+
+                    settings.set_cooling_setpoint(setpoint)?;
+                } else {
+                    let setpoint = settings
+                        .occupied_heating_setpoint
+                        .saturating_add(amount)
+                        .clamp(
+                            settings.min_heat_setpoint_limit,
+                            settings
+                                .max_heat_setpoint_limit
+                                .min(settings.max_cool_setpoint_limit - dead_band),
+                        );
+
+                    settings.set_heating_setpoint(setpoint)?;
+                }
+            }
+
+            state.setpoint_change_source = SetpointChangeSourceEnum::Manual;
+            state.setpoint_change_amount = Some(amount);
+
+            Ok(())
+        })
+    }
+
+    /// Set the weekly schedule of the given days and modes, as per the `SetWeeklySchedule` command.
+    ///
+    /// An empty list of transitions clears the schedules of the given days and modes.
+    fn set_weekly_schedule<I>(
+        &self,
+        days: ScheduleDayOfWeekBitmap,
+        modes: ScheduleModeBitmap,
+        transitions: I,
+    ) -> Result<(), Error>
+    where
+        I: Iterator<Item = Result<(u16, Option<i16>, Option<i16>), Error>>,
+    {
+        if days.is_empty() || modes.is_empty() {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        self.update(|state| {
+            let settings = &mut state.settings;
+
+            let mut heat = Vec::<ScheduleTransition, MAX_DAILY_TRANSITIONS>::new();
+            let mut cool = Vec::<ScheduleTransition, MAX_DAILY_TRANSITIONS>::new();
+
+            for transition in transitions {
+                let (time, heat_setpoint, cool_setpoint) = transition?;
+
+                if time >= MINUTES_PER_DAY {
+                    Err(ErrorCode::ConstraintError)?;
+                }
+
+                for (mode, setpoint, min, max, transitions) in [
+                    (
+                        ScheduleModeBitmap::HEAT_SETPOINT_PRESENT,
+                        heat_setpoint,
+                        settings.min_heat_setpoint_limit,
+                        settings.max_heat_setpoint_limit,
+                        &mut heat,
+                    ),
+                    (
+                        ScheduleModeBitmap::COOL_SETPOINT_PRESENT,
+                        cool_setpoint,
+                        settings.min_cool_setpoint_limit,
+                        settings.max_cool_setpoint_limit,
+                        &mut cool,
+                    ),
+                ] {
+                    if !modes.contains(mode) {
+                        continue;
+                    }
+
+                    let Some(setpoint) = setpoint else {
+                        Err(ErrorCode::InvalidCommand)?
+                    };
+
+                    if !(min..=max).contains(&setpoint) {
+                        Err(ErrorCode::ConstraintError)?;
+                    }
+
+                    transitions
+                        .push(ScheduleTransition { time, setpoint })
+                        .map_err(|_| ErrorCode::ResourceExhausted)?;
+                }
+            }
+
+            heat.sort_unstable_by_key(|transition| transition.time);
+            cool.sort_unstable_by_key(|transition| transition.time);
+
+            for day in SCHEDULE_DAYS.into_iter().filter(|day| days.contains(*day)) {
+                for (mode, transitions) in [
+                    (ScheduleModeBitmap::HEAT_SETPOINT_PRESENT, &heat),
+                    (ScheduleModeBitmap::COOL_SETPOINT_PRESENT, &cool),
+                ] {
+                    if !modes.contains(mode) {
+                        continue;
+                    }
+
+                    let cool = mode == ScheduleModeBitmap::COOL_SETPOINT_PRESENT;
+
+                    settings
+                        .schedules
+                        .retain(|schedule| schedule.day != day.bits() || schedule.cool != cool);
+
+                    if !transitions.is_empty() {
+                        settings
+                            .schedules
+                            .push(DailySchedule {
+                                day: day.bits(),
+                                cool,
+                                transitions: transitions.clone(),
+                            })
+                            .map_err(|_| ErrorCode::ResourceExhausted)?;
+                    }
+                }
+            }
+
+            state.applied_transitions = [None, None];
+
+            Ok(())
+        })
+    }
+
+    /// Clear the weekly schedule, as per the `ClearWeeklySchedule` command.
+    fn clear_weekly_schedule(&self) -> Result<(), Error> {
+        self.update(|state| {
+            state.settings.schedules.clear();
+            state.applied_transitions = [None, None];
+
+            Ok(())
+        })
+    }
+}
+
+impl ClusterHandler for ThermostatHandler<'_> {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(7)
+        .with_features(
+            Feature::HEATING.bits()
+                | Feature::COOLING.bits()
+                | Feature::AUTO_MODE.bits()
+                | Feature::SCHEDULE_CONFIGURATION.bits()
+                | Feature::PRESETS.bits(),
+        )
+        .with_attrs(with!(
+            required;
+            AttributeId::AbsMinHeatSetpointLimit
+                | AttributeId::AbsMaxHeatSetpointLimit
+                | AttributeId::AbsMinCoolSetpointLimit
+                | AttributeId::AbsMaxCoolSetpointLimit
+                | AttributeId::LocalTemperatureCalibration
+                | AttributeId::OccupiedCoolingSetpoint
+                | AttributeId::OccupiedHeatingSetpoint
+                | AttributeId::MinHeatSetpointLimit
+                | AttributeId::MaxHeatSetpointLimit
+                | AttributeId::MinCoolSetpointLimit
+                | AttributeId::MaxCoolSetpointLimit
+                | AttributeId::MinSetpointDeadBand
+                | AttributeId::ThermostatRunningMode
+                | AttributeId::StartOfWeek
+                | AttributeId::NumberOfWeeklyTransitions
+                | AttributeId::NumberOfDailyTransitions
+                | AttributeId::ThermostatProgrammingOperationMode
+                | AttributeId::ThermostatRunningState
+                | AttributeId::SetpointChangeSource
+                | AttributeId::SetpointChangeAmount
+                | AttributeId::PresetTypes
+                | AttributeId::NumberOfPresets
+                | AttributeId::ActivePresetHandle
+                | AttributeId::Presets
+        ))
+        .with_cmds(with!(
+            CommandId::SetpointRaiseLower
+                | CommandId::SetWeeklySchedule
+                | CommandId::GetWeeklySchedule
+                | CommandId::ClearWeeklySchedule
+                | CommandId::SetActivePresetRequest
+                | CommandId::AtomicRequest
+        ));
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn commit_atomic_write(&self, ctx: &InvokeContext, write: &AtomicWrite) -> Result<(), Error> {
+        if write
+            .attrs()
+            .any(|attr| attr != AttributeId::Presets as AttrId)
+        {
+            Err(ErrorCode::InvalidAction)?;
+        }
+
+        let Some(value) = write.value(AttributeId::Presets as _) else {
+            return Ok(());
+        };
+
+        let presets = TLVArray::<PresetStruct>::new(value)?;
+
+        self.update(|state| state.settings.set_presets(presets.iter()))?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn local_temperature(&self, _ctx: &ReadContext) -> Result<Nullable<i16>, Error> {
+        Ok(Nullable::new(ThermostatHandler::local_temperature(self)))
+    }
+
+    fn abs_min_heat_setpoint_limit(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(ABS_MIN_HEAT_SETPOINT)
+    }
+
+    fn abs_max_heat_setpoint_limit(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(ABS_MAX_HEAT_SETPOINT)
+    }
+
+    fn abs_min_cool_setpoint_limit(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(ABS_MIN_COOL_SETPOINT)
+    }
+
+    fn abs_max_cool_setpoint_limit(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(ABS_MAX_COOL_SETPOINT)
+    }
+
+    fn local_temperature_calibration(&self, _ctx: &ReadContext) -> Result<i8, Error> {
+        Ok(self.state.borrow().settings.local_temperature_calibration)
+    }
+
+    fn occupied_cooling_setpoint(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(self.setpoints().1)
+    }
+
+    fn occupied_heating_setpoint(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(self.setpoints().0)
+    }
+
+    fn min_heat_setpoint_limit(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(self.state.borrow().settings.min_heat_setpoint_limit)
+    }
+
+    fn max_heat_setpoint_limit(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(self.state.borrow().settings.max_heat_setpoint_limit)
+    }
+
+    fn min_cool_setpoint_limit(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(self.state.borrow().settings.min_cool_setpoint_limit)
+    }
+
+    fn max_cool_setpoint_limit(&self, _ctx: &ReadContext) -> Result<i16, Error> {
+        Ok(self.state.borrow().settings.max_cool_setpoint_limit)
+    }
+
+    fn min_setpoint_dead_band(&self, _ctx: &ReadContext) -> Result<i8, Error> {
+        Ok(self.state.borrow().settings.min_setpoint_dead_band)
+    }
+
+    fn control_sequence_of_operation(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<ControlSequenceOfOperationEnum, Error> {
+        Ok(self.state.borrow().settings.control_sequence)
+    }
+
+    fn system_mode(&self, _ctx: &ReadContext) -> Result<SystemModeEnum, Error> {
+        Ok(ThermostatHandler::system_mode(self))
+    }
+
+    fn thermostat_running_mode(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<ThermostatRunningModeEnum, Error> {
+        Ok(self.state.borrow().running_mode())
+    }
+
+    fn start_of_week(&self, _ctx: &ReadContext) -> Result<StartOfWeekEnum, Error> {
+        Ok(StartOfWeekEnum::Monday)
+    }
+
+    fn number_of_weekly_transitions(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok((MAX_DAILY_SCHEDULES * MAX_DAILY_TRANSITIONS) as _)
+    }
+
+    fn number_of_daily_transitions(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(MAX_DAILY_TRANSITIONS as _)
+    }
+
+    fn thermostat_programming_operation_mode(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<ProgrammingOperationModeBitmap, Error> {
+        Ok(self.state.borrow().settings.programming_operation_mode)
+    }
+
+    fn thermostat_running_state(&self, _ctx: &ReadContext) -> Result<RelayStateBitmap, Error> {
+        Ok(self.running_state())
+    }
+
+    fn setpoint_change_source(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<SetpointChangeSourceEnum, Error> {
+        Ok(self.state.borrow().setpoint_change_source)
+    }
+
+    fn setpoint_change_amount(&self, _ctx: &ReadContext) -> Result<Nullable<i16>, Error> {
+        Ok(Nullable::new(self.state.borrow().setpoint_change_amount))
+    }
+
+    fn set_local_temperature_calibration(
+        &self,
+        ctx: &WriteContext,
+        value: i8,
+    ) -> Result<(), Error> {
+        if !(-25..=25).contains(&value) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        self.update(|state| {
+            state.settings.local_temperature_calibration = value;
+            Ok(())
+        })?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn set_occupied_cooling_setpoint(&self, ctx: &WriteContext, value: i16) -> Result<(), Error> {
+        self.update_setpoint(ctx, true, value)
+    }
+
+    fn set_occupied_heating_setpoint(&self, ctx: &WriteContext, value: i16) -> Result<(), Error> {
+        self.update_setpoint(ctx, false, value)
+    }
+
+    fn set_min_heat_setpoint_limit(&self, ctx: &WriteContext, value: i16) -> Result<(), Error> {
+        self.update_limits(ctx, |settings| settings.min_heat_setpoint_limit = value)
+    }
+
+    fn set_max_heat_setpoint_limit(&self, ctx: &WriteContext, value: i16) -> Result<(), Error> {
+        self.update_limits(ctx, |settings| settings.max_heat_setpoint_limit = value)
+    }
+
+    fn set_min_cool_setpoint_limit(&self, ctx: &WriteContext, value: i16) -> Result<(), Error> {
+        self.update_limits(ctx, |settings| settings.min_cool_setpoint_limit = value)
+    }
+
+    fn set_max_cool_setpoint_limit(&self, ctx: &WriteContext, value: i16) -> Result<(), Error> {
+        self.update_limits(ctx, |settings| settings.max_cool_setpoint_limit = value)
+    }
+
+    fn set_min_setpoint_dead_band(&self, ctx: &WriteContext, value: i8) -> Result<(), Error> {
+        if !(0..=25).contains(&value) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        self.update_limits(ctx, |settings| settings.min_setpoint_dead_band = value)
+    }
+
+    fn set_control_sequence_of_operation(
+        &self,
+        ctx: &WriteContext,
+        value: ControlSequenceOfOperationEnum,
+    ) -> Result<(), Error> {
+        self.update(|state| {
+            state.settings.control_sequence = value;
+
+            // A system mode which is not supported by the new control sequence turns the thermostat off
+            if !state
+                .settings
+                .system_mode_allowed(state.settings.system_mode)
+            {
+                state.settings.system_mode = SystemModeEnum::Off;
+            }
+
+            Ok(())
+        })?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn set_system_mode(&self, ctx: &WriteContext, value: SystemModeEnum) -> Result<(), Error> {
+        self.update(|state| {
+            if !state.settings.system_mode_allowed(value) {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            state.settings.system_mode = value;
+
+            Ok(())
+        })?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn set_thermostat_programming_operation_mode(
+        &self,
+        ctx: &WriteContext,
+        value: ProgrammingOperationModeBitmap,
+    ) -> Result<(), Error> {
+        self.update(|state| {
+            state.settings.programming_operation_mode = value;
+            state.applied_transitions = [None, None];
+
+            Ok(())
+        })?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn preset_types<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext,
+        builder: ArrayAttributeRead<PresetTypeStructArrayBuilder<P>, PresetTypeStructBuilder<P>>,
+    ) -> Result<P, Error> {
+        fn read<P: TLVBuilderParent>(
+            (scenario, max_presets): (PresetScenarioEnum, u8),
+            builder: PresetTypeStructBuilder<P>,
+        ) -> Result<P, Error> {
+            builder
+                .preset_scenario(scenario)?
+                .number_of_presets(max_presets)?
+                .preset_type_features(PresetTypeFeaturesBitmap::SUPPORTS_NAMES)?
+                .end()
+        }
+
+        match builder {
+            ArrayAttributeRead::ReadAll(mut builder) => {
+                for preset_type in PRESET_TYPES {
+                    builder = read(preset_type, builder.push()?)?;
+                }
+
+                builder.end()
+            }
+            ArrayAttributeRead::ReadOne(index, builder) => {
+                let Some(preset_type) = PRESET_TYPES.get(index as usize) else {
+                    return Err(ErrorCode::ConstraintError.into());
+                };
+
+                read(*preset_type, builder)
+            }
+        }
+    }
+
+    fn number_of_presets(&self, _ctx: &ReadContext) -> Result<u8, Error> {
+        Ok(MAX_PRESETS as _)
+    }
+
+    fn active_preset_handle<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext,
+        builder: NullableBuilder<P, OctetsBuilder<P>>,
+    ) -> Result<P, Error> {
+        if let Some(handle) = &self.state.borrow().settings.active_preset {
+            builder.non_null()?.set(Octets(&handle.vec))
+        } else {
+            builder.null()
+        }
+    }
+
+    fn presets<P: TLVBuilderParent>(
+        &self,
+        _ctx: &ReadContext,
+        builder: ArrayAttributeRead<PresetStructArrayBuilder<P>, PresetStructBuilder<P>>,
+    ) -> Result<P, Error> {
+        fn read<P: TLVBuilderParent>(
+            preset: &Preset,
+            builder: PresetStructBuilder<P>,
+        ) -> Result<P, Error> {
+            builder
+                .preset_handle(Nullable::some(Octets(&preset.handle.vec)))?
+                .preset_scenario(preset.scenario)?
+                .name(preset.name.as_deref().map(Nullable::some))?
+                .cooling_setpoint(Some(preset.cooling_setpoint))?
+                .heating_setpoint(Some(preset.heating_setpoint))?
+                .built_in(Nullable::some(false))?
+                .end()
+        }
+
+        let state = self.state.borrow();
+        let presets = &state.settings.presets;
+
+        match builder {
+            ArrayAttributeRead::ReadAll(mut builder) => {
+                for preset in presets {
+                    builder = read(preset, builder.push()?)?;
+                }
+
+                builder.end()
+            }
+            ArrayAttributeRead::ReadOne(index, builder) => {
+                let Some(preset) = presets.get(index as usize) else {
+                    return Err(ErrorCode::ConstraintError.into());
+                };
+
+                read(preset, builder)
+            }
+        }
+    }
+
+    fn set_presets(
+        &self,
+        _ctx: &WriteContext,
+        _value: ArrayAttributeWrite<TLVArray<'_, PresetStruct<'_>>, PresetStruct<'_>>,
+    ) -> Result<(), Error> {
+        // The presets can only be written as part of an atomic write, see `commit_atomic_write`
+        Err(ErrorCode::InvalidState.into())
+    }
+
+    fn handle_setpoint_raise_lower(
+        &self,
+        ctx: &InvokeContext,
+        request: SetpointRaiseLowerRequest,
+    ) -> Result<(), Error> {
+        self.raise_lower(request.mode()?, request.amount()?)?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_set_weekly_schedule(
+        &self,
+        ctx: &InvokeContext,
+        request: SetWeeklyScheduleRequest,
+    ) -> Result<(), Error> {
+        let transitions = request.transitions()?;
+
+        if transitions.iter().count() != request.number_of_transitions_for_sequence()? as usize {
+            Err(ErrorCode::InvalidCommand)?;
+        }
+
+        self.set_weekly_schedule(
+            request.day_of_week_for_sequence()?,
+            request.mode_for_sequence()?,
+            transitions.iter().map(|transition| {
+                let transition = transition?;
+
+                Ok((
+                    transition.transition_time()?,
+                    transition.heat_setpoint()?.into_option(),
+                    transition.cool_setpoint()?.into_option(),
+                ))
+            }),
+        )?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_get_weekly_schedule<P: TLVBuilderParent>(
+        &self,
+        _ctx: &InvokeContext,
+        request: GetWeeklyScheduleRequest,
+        response: GetWeeklyScheduleResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        let days = request.days_to_return()?;
+        let modes = request.mode_to_return()?;
+
+        let state = self.state.borrow();
+        let settings = &state.settings;
+
+        let schedules = |day: ScheduleDayOfWeekBitmap| {
+            let heat = modes
+                .contains(ScheduleModeBitmap::HEAT_SETPOINT_PRESENT)
+                .then(|| settings.schedule(day.bits(), false))
+                .flatten();
+            let cool = modes
+                .contains(ScheduleModeBitmap::COOL_SETPOINT_PRESENT)
+                .then(|| settings.schedule(day.bits(), true))
+                .flatten();
+
+            (heat, cool)
+        };
+
+        // Return the schedule of the first requested day which has one
+        let mut requested_days = SCHEDULE_DAYS.into_iter().filter(|day| days.contains(*day));
+
+        let day = requested_days
+            .clone()
+            .find(|day| {
+                let (heat, cool) = schedules(*day);
+                heat.is_some() || cool.is_some()
+            })
+            .or_else(|| requested_days.next())
+            .unwrap_or(ScheduleDayOfWeekBitmap::empty());
+
+        let (heat, cool) = schedules(day);
+
+        let heat = heat
+            .map(|schedule| schedule.transitions.as_slice())
+            .unwrap_or(&[]);
+        let cool = cool
+            .map(|schedule| schedule.transitions.as_slice())
+            .unwrap_or(&[]);
+
+        let mut mode = ScheduleModeBitmap::empty();
+        mode.set(ScheduleModeBitmap::HEAT_SETPOINT_PRESENT, !heat.is_empty());
+        mode.set(ScheduleModeBitmap::COOL_SETPOINT_PRESENT, !cool.is_empty());
+
+        // Merge the heating and cooling transitions by time
+        let merged = || {
+            let (mut h, mut c) = (0, 0);
+
+            core::iter::from_fn(move || {
+                let time = match (heat.get(h), cool.get(c)) {
+                    (Some(ht), Some(ct)) => ht.time.min(ct.time),
+                    (Some(ht), None) => ht.time,
+                    (None, Some(ct)) => ct.time,
+                    (None, None) => return None,
+                };
+
+                let heat_setpoint = heat
+                    .get(h)
+                    .filter(|transition| transition.time == time)
+                    .map(|transition| transition.setpoint);
+                let cool_setpoint = cool
+                    .get(c)
+                    .filter(|transition| transition.time == time)
+                    .map(|transition| transition.setpoint);
+
+                h += heat_setpoint.is_some() as usize;
+                c += cool_setpoint.is_some() as usize;
+
+                Some((time, heat_setpoint, cool_setpoint))
+            })
+        };
+
+        let mut transitions = response
+            .number_of_transitions_for_sequence(merged().count() as _)?
+            .day_of_week_for_sequence(day)?
+            .mode_for_sequence(mode)?
+            .transitions()?;
+
+        for (time, heat_setpoint, cool_setpoint) in merged() {
+            transitions = transitions
+                .push()?
+                .transition_time(time)?
+                .heat_setpoint(Nullable::new(heat_setpoint))?
+                .cool_setpoint(Nullable::new(cool_setpoint))?
+                .end()?;
+        }
+
+        transitions.end()?.end()
+    }
+
+    fn handle_clear_weekly_schedule(&self, ctx: &InvokeContext) -> Result<(), Error> {
+        self.clear_weekly_schedule()?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_set_active_schedule_request(
+        &self,
+        _ctx: &InvokeContext,
+        _request: SetActiveScheduleRequestRequest,
+    ) -> Result<(), Error> {
+        // The MatterScheduleConfiguration feature is not supported
+        Err(ErrorCode::CommandNotFound.into())
+    }
+
+    fn handle_set_active_preset_request(
+        &self,
+        ctx: &InvokeContext,
+        request: SetActivePresetRequestRequest,
+    ) -> Result<(), Error> {
+        let handle = request.preset_handle()?;
+
+        self.update(|state| {
+            state
+                .settings
+                .set_active_preset(handle.into_option().map(|handle| handle.0))
+        })?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_atomic_request<P: TLVBuilderParent>(
+        &self,
+        _ctx: &InvokeContext,
+        _request: AtomicRequestRequest,
+        _response: AtomicResponseBuilder<P>,
+    ) -> Result<P, Error> {
+        // Atomic requests are handled by the Data Model, which only
+        // calls us with the pending values when committing
+        Err(ErrorCode::InvalidAction.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::data_model::objects::Dataver;
+    use crate::tlv::OctetStr;
+    use crate::utils::storage::WriteBuf;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Hooks {
+        temperature: Cell<Option<i16>>,
+        running_state: Cell<Option<RelayStateBitmap>>,
+        local_time: Cell<Option<(ScheduleDayOfWeekBitmap, u16)>>,
+    }
+
+    impl ThermostatHooks for Hooks {
+        fn local_temperature(&self) -> Option<i16> {
+            self.temperature.get()
+        }
+
+        fn set_running_state(&self, state: RelayStateBitmap) {
+            self.running_state.set(Some(state));
+        }
+
+        fn local_time(&self) -> Option<(ScheduleDayOfWeekBitmap, u16)> {
+            self.local_time.get()
+        }
+    }
+
+    /// A preset, as written by a client.
+    #[derive(ToTLV)]
+    struct TestPreset<'a> {
+        handle: Nullable<OctetStr<'a>>,
+        scenario: PresetScenarioEnum,
+        name: Option<Nullable<&'a str>>,
+        cooling_setpoint: Option<i16>,
+        heating_setpoint: Option<i16>,
+        built_in: Nullable<bool>,
+    }
+
+    impl<'a> TestPreset<'a> {
+        const fn new(handle: Option<&'a [u8]>, scenario: PresetScenarioEnum) -> Self {
+            Self {
+                handle: match handle {
+                    Some(handle) => Nullable::some(Octets(handle)),
+                    None => Nullable::none(),
+                },
+                scenario,
+                name: None,
+                cooling_setpoint: Some(2600),
+                heating_setpoint: Some(2000),
+                built_in: Nullable::none(),
+            }
+        }
+    }
+
+    fn set_presets(handler: &ThermostatHandler, presets: &[TestPreset]) -> Result<(), Error> {
+        let mut buf = [0; 1024];
+        let mut wb = WriteBuf::new(&mut buf);
+        presets.to_tlv(&TLVTag::Anonymous, &mut wb)?;
+
+        let presets = TLVArray::<PresetStruct>::new(TLVElement::new(wb.as_slice()))?;
+
+        handler.update(|state| state.settings.set_presets(presets.iter()))
+    }
+
+    fn preset_handles(handler: &ThermostatHandler) -> Vec<u8, MAX_PRESETS> {
+        handler
+            .state
+            .borrow()
+            .settings
+            .presets
+            .iter()
+            .map(|preset| preset.handle.vec[0])
+            .collect()
+    }
+
+    fn set_system_mode(handler: &ThermostatHandler, mode: SystemModeEnum) -> Result<(), Error> {
+        handler.update(|state| {
+            if !state.settings.system_mode_allowed(mode) {
+                Err(ErrorCode::ConstraintError)?;
+            }
+
+            state.settings.system_mode = mode;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_dead_band() {
+        let mut settings = ThermostatSettings::new();
+
+        // Raising the heating setpoint pushes the cooling one up
+        unwrap!(settings.set_heating_setpoint(2500));
+        assert_eq!(settings.occupied_cooling_setpoint, 2750);
+
+        // Lowering the cooling setpoint pushes the heating one down
+        unwrap!(settings.set_cooling_setpoint(2000));
+        assert_eq!(settings.occupied_heating_setpoint, 1750);
+
+        // Out of the limits
+        assert!(settings
+            .set_heating_setpoint(ABS_MAX_HEAT_SETPOINT + 1)
+            .is_err());
+        assert!(settings
+            .set_cooling_setpoint(ABS_MIN_COOL_SETPOINT - 1)
+            .is_err());
+
+        // The pushed setpoint would be out of its limits
+        settings.max_cool_setpoint_limit = 2800;
+        assert!(settings.set_heating_setpoint(2600).is_err());
+        assert_eq!(settings.occupied_heating_setpoint, 1750);
+
+        // Limits violating the deadband
+        settings.max_heat_setpoint_limit = 2600;
+        assert!(settings.check_limits().is_err());
+
+        settings.max_heat_setpoint_limit = 2550;
+        unwrap!(settings.check_limits());
+    }
+
+    #[test]
+    fn test_raise_lower() {
+        let handler = ThermostatHandler::new(Dataver::new(0));
+
+        unwrap!(handler.raise_lower(SetpointRaiseLowerModeEnum::Heat, 10));
+        assert_eq!(handler.setpoints(), (2100, 2600));
+
+        unwrap!(handler.raise_lower(SetpointRaiseLowerModeEnum::Both, -10));
+        assert_eq!(handler.setpoints(), (2000, 2500));
+
+        unwrap!(handler.raise_lower(SetpointRaiseLowerModeEnum::Heat, 50));
+        assert_eq!(handler.setpoints(), (2500, 2750));
+
+        // Clamped to the limits which preserve the deadband
+        unwrap!(handler.raise_lower(SetpointRaiseLowerModeEnum::Heat, 127));
+        assert_eq!(
+            handler.setpoints(),
+            (ABS_MAX_COOL_SETPOINT - 250, ABS_MAX_COOL_SETPOINT)
+        );
+
+        unwrap!(handler.raise_lower(SetpointRaiseLowerModeEnum::Cool, -128));
+        assert_eq!(handler.setpoints(), (1670, 1920));
+
+        unwrap!(handler.raise_lower(SetpointRaiseLowerModeEnum::Cool, -128));
+        assert_eq!(
+            handler.setpoints(),
+            (ABS_MIN_COOL_SETPOINT - 250, ABS_MIN_COOL_SETPOINT)
+        );
+
+        assert!(handler.changed());
+        assert_eq!(
+            handler.state.borrow().setpoint_change_source,
+            SetpointChangeSourceEnum::Manual
+        );
+    }
+
+    #[test]
+    fn test_system_mode() {
+        let handler = ThermostatHandler::new(Dataver::new(0));
+
+        unwrap!(set_system_mode(&handler, SystemModeEnum::Auto));
+        assert!(set_system_mode(&handler, SystemModeEnum::Sleep).is_err());
+
+        unwrap!(handler.update(|state| {
+            state.settings.control_sequence = ControlSequenceOfOperationEnum::HeatingOnly;
+            Ok(())
+        }));
+
+        assert!(set_system_mode(&handler, SystemModeEnum::Cool).is_err());
+        assert!(set_system_mode(&handler, SystemModeEnum::Auto).is_err());
+        unwrap!(set_system_mode(&handler, SystemModeEnum::Heat));
+        assert_eq!(handler.system_mode(), SystemModeEnum::Heat);
+    }
+
+    #[test]
+    fn test_control() {
+        let hooks = Hooks::default();
+
+        let mut handler = ThermostatHandler::new(Dataver::new(0));
+        handler.set_hooks(&hooks);
+
+        // Off
+        hooks.temperature.set(Some(1500));
+        assert!(handler.control());
+        assert_eq!(handler.local_temperature(), Some(1500));
+        assert_eq!(hooks.running_state.take(), None);
+
+        unwrap!(set_system_mode(&handler, SystemModeEnum::Heat));
+        assert!(handler.control());
+        assert_eq!(hooks.running_state.take(), Some(RelayStateBitmap::HEAT));
+        assert_eq!(
+            handler.state.borrow().running_mode(),
+            ThermostatRunningModeEnum::Heat
+        );
+
+        // Heats until the setpoint is reached
+        hooks.temperature.set(Some(1990));
+        assert!(handler.control());
+        assert_eq!(hooks.running_state.take(), None);
+
+        hooks.temperature.set(Some(2000));
+        assert!(handler.control());
+        assert_eq!(hooks.running_state.take(), Some(RelayStateBitmap::empty()));
+
+        // ... and does not restart within the hysteresis
+        hooks.temperature.set(Some(1960));
+        handler.control();
+        assert_eq!(hooks.running_state.take(), None);
+
+        // Auto mode cools above the cooling setpoint
+        unwrap!(set_system_mode(&handler, SystemModeEnum::Auto));
+        hooks.temperature.set(Some(2700));
+        handler.control();
+        assert_eq!(hooks.running_state.take(), Some(RelayStateBitmap::COOL));
+
+        // The calibration is applied to the local temperature
+        unwrap!(handler.update(|state| {
+            state.settings.local_temperature_calibration = -10;
+            Ok(())
+        }));
+        handler.control();
+        assert_eq!(handler.local_temperature(), Some(2600));
+        assert_eq!(hooks.running_state.take(), Some(RelayStateBitmap::empty()));
+
+        // No temperature, no relays
+        hooks.temperature.set(None);
+        handler.control();
+        assert_eq!(handler.local_temperature(), None);
+        assert!(!handler.control());
+    }
+
+    #[test]
+    fn test_weekly_schedule() {
+        let hooks = Hooks::default();
+
+        let mut handler = ThermostatHandler::new(Dataver::new(0));
+        handler.set_hooks(&hooks);
+
+        let days = ScheduleDayOfWeekBitmap::MONDAY | ScheduleDayOfWeekBitmap::TUESDAY;
+
+        // A missing heating setpoint
+        assert!(handler
+            .set_weekly_schedule(
+                days,
+                ScheduleModeBitmap::HEAT_SETPOINT_PRESENT,
+                [Ok((360, None, Some(2600)))].into_iter(),
+            )
+            .is_err());
+
+        unwrap!(handler.set_weekly_schedule(
+            days,
+            ScheduleModeBitmap::HEAT_SETPOINT_PRESENT,
+            [Ok((1320, Some(1800), None)), Ok((360, Some(2100), None))].into_iter(),
+        ));
+
+        {
+            let state = handler.state.borrow();
+            assert_eq!(state.settings.schedules.len(), 2);
+
+            let schedule = unwrap!(state
+                .settings
+                .schedule(ScheduleDayOfWeekBitmap::TUESDAY.bits(), false));
+            assert_eq!(schedule.transitions[0].time, 360);
+            assert_eq!(schedule.transitions[1].time, 1320);
+        }
+
+        // The schedule is only applied when active
+        hooks
+            .local_time
+            .set(Some((ScheduleDayOfWeekBitmap::MONDAY, 400)));
+        handler.control();
+        assert_eq!(handler.setpoints().0, 2000);
+
+        unwrap!(handler.update(|state| {
+            state.settings.programming_operation_mode =
+                ProgrammingOperationModeBitmap::SCHEDULE_ACTIVE;
+            Ok(())
+        }));
+
+        assert!(handler.control());
+        assert_eq!(handler.setpoints().0, 2100);
+        assert_eq!(
+            handler.state.borrow().setpoint_change_source,
+            SetpointChangeSourceEnum::Schedule
+        );
+
+        // A manual change holds until the next transition
+        unwrap!(handler.raise_lower(SetpointRaiseLowerModeEnum::Heat, 5));
+        handler.control();
+        assert_eq!(handler.setpoints().0, 2150);
+
+        hooks
+            .local_time
+            .set(Some((ScheduleDayOfWeekBitmap::MONDAY, 1400)));
+        handler.control();
+        assert_eq!(handler.setpoints().0, 1800);
+
+        // No schedule on Wednesday
+        hooks
+            .local_time
+            .set(Some((ScheduleDayOfWeekBitmap::WEDNESDAY, 1400)));
+        unwrap!(handler.raise_lower(SetpointRaiseLowerModeEnum::Heat, 10));
+        handler.control();
+        assert_eq!(handler.setpoints().0, 1900);
+
+        unwrap!(handler.clear_weekly_schedule());
+        assert!(handler.state.borrow().settings.schedules.is_empty());
+    }
+
+    #[test]
+    fn test_presets() {
+        let handler = ThermostatHandler::new(Dataver::new(0));
+
+        unwrap!(set_presets(
+            &handler,
+            &[
+                TestPreset::new(None, PresetScenarioEnum::Occupied),
+                TestPreset {
+                    name: Some(Nullable::some("Night")),
+                    cooling_setpoint: Some(2400),
+                    heating_setpoint: Some(1800),
+                    ..TestPreset::new(None, PresetScenarioEnum::Sleep)
+                },
+            ],
+        ));
+        assert_eq!(preset_handles(&handler), [0, 1]);
+        assert!(handler.changed());
+
+        // Built-in presets and too many presets of a scenario are rejected
+        assert_eq!(
+            unwrap!(set_presets(
+                &handler,
+                &[TestPreset {
+                    built_in: Nullable::some(true),
+                    ..TestPreset::new(None, PresetScenarioEnum::Wake)
+                }],
+            )
+            .err())
+            .code(),
+            ErrorCode::ConstraintError
+        );
+        assert_eq!(
+            unwrap!(set_presets(
+                &handler,
+                &[
+                    TestPreset::new(None, PresetScenarioEnum::Wake),
+                    TestPreset::new(None, PresetScenarioEnum::Wake),
+                ],
+            )
+            .err())
+            .code(),
+            ErrorCode::ResourceExhausted
+        );
+
+        // Unknown or duplicate handles are rejected
+        assert_eq!(
+            unwrap!(set_presets(
+                &handler,
+                &[TestPreset::new(Some(&[7]), PresetScenarioEnum::Wake)],
+            )
+            .err())
+            .code(),
+            ErrorCode::NotFound
+        );
+        assert_eq!(
+            unwrap!(set_presets(
+                &handler,
+                &[
+                    TestPreset::new(Some(&[0]), PresetScenarioEnum::Occupied),
+                    TestPreset::new(Some(&[0]), PresetScenarioEnum::Wake),
+                ],
+            )
+            .err())
+            .code(),
+            ErrorCode::ConstraintError
+        );
+
+        // Missing setpoints and setpoints violating the deadband are rejected
+        assert!(set_presets(
+            &handler,
+            &[TestPreset {
+                cooling_setpoint: None,
+                ..TestPreset::new(None, PresetScenarioEnum::Wake)
+            }],
+        )
+        .is_err());
+        assert!(set_presets(
+            &handler,
+            &[TestPreset {
+                cooling_setpoint: Some(2100),
+                ..TestPreset::new(None, PresetScenarioEnum::Wake)
+            }],
+        )
+        .is_err());
+
+        // Nothing was applied by the rejected writes
+        assert_eq!(preset_handles(&handler), [0, 1]);
+
+        unwrap!(handler.update(|state| state.settings.set_active_preset(Some(&[1]))));
+        assert_eq!(handler.setpoints(), (1800, 2400));
+        assert_eq!(
+            unwrap!(handler
+                .update(|state| state.settings.set_active_preset(Some(&[7])))
+                .err())
+            .code(),
+            ErrorCode::InvalidCommand
+        );
+
+        // The active preset cannot be removed
+        assert_eq!(
+            unwrap!(set_presets(
+                &handler,
+                &[TestPreset::new(Some(&[0]), PresetScenarioEnum::Occupied)],
+            )
+            .err())
+            .code(),
+            ErrorCode::InvalidState
+        );
+
+        // Replace the presets, keeping the active one and adding a new one
+        unwrap!(set_presets(
+            &handler,
+            &[
+                TestPreset::new(Some(&[1]), PresetScenarioEnum::Sleep),
+                TestPreset::new(None, PresetScenarioEnum::UserDefined),
+            ],
+        ));
+        assert_eq!(preset_handles(&handler), [1, 2]);
+
+        unwrap!(handler.update(|state| state.settings.set_active_preset(None)));
+        assert!(handler.state.borrow().settings.active_preset.is_none());
+    }
+
+    #[test]
+    fn test_persist() {
+        let handler = ThermostatHandler::new(Dataver::new(0));
+
+        unwrap!(handler.raise_lower(SetpointRaiseLowerModeEnum::Both, 10));
+        unwrap!(set_system_mode(&handler, SystemModeEnum::Cool));
+        unwrap!(handler.set_weekly_schedule(
+            ScheduleDayOfWeekBitmap::SUNDAY,
+            ScheduleModeBitmap::COOL_SETPOINT_PRESENT,
+            [Ok((600, None, Some(2400)))].into_iter(),
+        ));
+        unwrap!(set_presets(
+            &handler,
+            &[TestPreset {
+                name: Some(Nullable::some("Away")),
+                ..TestPreset::new(None, PresetScenarioEnum::Vacation)
+            }],
+        ));
+        unwrap!(handler.update(|state| state.settings.set_active_preset(Some(&[0]))));
+
+        let mut buf = [0; 1024];
+        let data = unwrap!(unwrap!(handler.store(&mut buf)));
+        assert!(!handler.changed());
+
+        let handler2 = ThermostatHandler::new(Dataver::new(0));
+        unwrap!(handler2.load(data));
+
+        assert_eq!(
+            handler.state.borrow().settings,
+            handler2.state.borrow().settings
+        );
+        assert_eq!(handler2.setpoints(), (2000, 2600));
+        assert_eq!(handler2.system_mode(), SystemModeEnum::Cool);
+    }
+}
@@ -0,0 +1,330 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Thermostat User Interface Configuration cluster and its handler.
+
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+use crate::error::Error;
+use crate::tlv::{FromTLV, TLVElement, TLVTag, ToTLV};
+use crate::utils::cell::RefCell;
+use crate::utils::storage::WriteBuf;
+use crate::utils::sync::Notification;
+use crate::with;
+
+use super::objects::{Cluster, Dataver, ReadContext, WriteContext};
+
+pub use crate::data_model::clusters::thermostat_user_interface_configuration::*;
+
+/// The persisted state of the Thermostat User Interface Configuration cluster.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct UiConfig {
+    temperature_display_mode: TemperatureDisplayModeEnum,
+    keypad_lockout: KeypadLockoutEnum,
+    schedule_programming_visibility: ScheduleProgrammingVisibilityEnum,
+}
+
+struct UiConfigState {
+    config: UiConfig,
+    changed: bool,
+}
+
+/// A handler for the Thermostat User Interface Configuration Matter cluster.
+///
+/// The cluster only stores the preferences of the user; the local user interface of the thermostat
+/// is expected to observe them with `ThermostatUiConfigHandler::temperature_display_mode`,
+/// `ThermostatUiConfigHandler::keypad_lockout` and `ThermostatUiConfigHandler::schedule_programming_visibility`.
+///
+/// The preferences should be persisted (see `ThermostatUiConfigHandler::load` and `ThermostatUiConfigHandler::store`).
+pub struct ThermostatUiConfigHandler {
+    dataver: Dataver,
+    state: RefCell<UiConfigState>,
+    persist_state_changed: Notification<NoopRawMutex>,
+}
+
+impl ThermostatUiConfigHandler {
+    /// Creates a new instance of `ThermostatUiConfigHandler` with the given `Dataver`.
+    pub const fn new(dataver: Dataver) -> Self {
+        Self {
+            dataver,
+            state: RefCell::new(UiConfigState {
+                config: UiConfig {
+                    temperature_display_mode: TemperatureDisplayModeEnum::Celsius,
+                    keypad_lockout: KeypadLockoutEnum::NoLockout,
+                    schedule_programming_visibility:
+                        ScheduleProgrammingVisibilityEnum::ScheduleProgrammingPermitted,
+                },
+                changed: false,
+            }),
+            persist_state_changed: Notification::new(),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Return the units in which the temperature should be displayed.
+    pub fn temperature_display_mode(&self) -> TemperatureDisplayModeEnum {
+        self.state.borrow().config.temperature_display_mode
+    }
+
+    /// Return the level of functionality of the keypad which is locked out.
+    pub fn keypad_lockout(&self) -> KeypadLockoutEnum {
+        self.state.borrow().config.keypad_lockout
+    }
+
+    /// Return whether the local schedule programming functionality is available.
+    pub fn schedule_programming_visibility(&self) -> ScheduleProgrammingVisibilityEnum {
+        self.state.borrow().config.schedule_programming_visibility
+    }
+
+    /// Load the persisted preferences from a byte slice.
+    ///
+    /// # Arguments
+    /// - `data`: The byte slice to load the state from
+    pub fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let config = UiConfig::from_tlv(&TLVElement::new(data))?;
+
+        let mut state = self.state.borrow_mut();
+
+        state.config = config;
+        state.changed = false;
+
+        self.dataver.changed();
+
+        Ok(())
+    }
+
+    /// Store the preferences into a byte slice.
+    ///
+    /// # Arguments
+    /// - `buf`: The byte slice to store the state into
+    ///
+    /// Returns `Ok(None)` if the state has not changed, `Ok(Some(data))` if the state has changed
+    /// where `data` is the sub-slice of the buffer that contains the data to be persisted
+    pub fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        let mut state = self.state.borrow_mut();
+
+        if !state.changed {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        state.config.to_tlv(&TLVTag::Anonymous, &mut wb)?;
+
+        state.changed = false;
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    /// Return `true` if the state has changed and needs to be persisted.
+    pub fn changed(&self) -> bool {
+        self.state.borrow().changed
+    }
+
+    /// Wait for the state to be changed in a way that requires persisting.
+    pub async fn wait_persist(&self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+
+            self.persist_state_changed.wait().await;
+        }
+    }
+
+    fn update<F>(&self, ctx: &WriteContext, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut UiConfig),
+    {
+        self.set(f);
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn set<F>(&self, f: F)
+    where
+        F: FnOnce(&mut UiConfig),
+    {
+        {
+            let mut state = self.state.borrow_mut();
+
+            f(&mut state.config);
+            state.changed = true;
+        }
+
+        self.persist_state_changed.notify();
+        self.dataver.changed();
+    }
+}
+
+impl ClusterHandler for ThermostatUiConfigHandler {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(2)
+        .with_attrs(with!(required; AttributeId::ScheduleProgrammingVisibility))
+        .with_cmds(with!());
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn temperature_display_mode(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<TemperatureDisplayModeEnum, Error> {
+        Ok(ThermostatUiConfigHandler::temperature_display_mode(self))
+    }
+
+    fn keypad_lockout(&self, _ctx: &ReadContext) -> Result<KeypadLockoutEnum, Error> {
+        Ok(ThermostatUiConfigHandler::keypad_lockout(self))
+    }
+
+    fn schedule_programming_visibility(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<ScheduleProgrammingVisibilityEnum, Error> {
+        Ok(ThermostatUiConfigHandler::schedule_programming_visibility(
+            self,
+        ))
+    }
+
+    fn set_temperature_display_mode(
+        &self,
+        ctx: &WriteContext,
+        value: TemperatureDisplayModeEnum,
+    ) -> Result<(), Error> {
+        self.update(ctx, |config| config.temperature_display_mode = value)
+    }
+
+    fn set_keypad_lockout(
+        &self,
+        ctx: &WriteContext,
+        value: KeypadLockoutEnum,
+    ) -> Result<(), Error> {
+        self.update(ctx, |config| config.keypad_lockout = value)
+    }
+
+    fn set_schedule_programming_visibility(
+        &self,
+        ctx: &WriteContext,
+        value: ScheduleProgrammingVisibilityEnum,
+    ) -> Result<(), Error> {
+        self.update(ctx, |config| config.schedule_programming_visibility = value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_model::objects::Dataver;
+    use crate::tlv::{FromTLV, TLVElement};
+
+    use super::*;
+
+    /// Decode a written enum value the same way the generated handler adaptor does,
+    /// and apply it with `set` if it is valid.
+    fn write<T, F>(handler: &ThermostatUiConfigHandler, value: u8, f: F) -> Result<(), Error>
+    where
+        T: for<'a> FromTLV<'a>,
+        F: FnOnce(&mut UiConfig, T),
+    {
+        // A TLV-encoded anonymous unsigned 8-bit integer
+        let data = [0x04, value];
+        let value = T::from_tlv(&TLVElement::new(&data))?;
+
+        handler.set(|config| f(config, value));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_enum_write_validation() {
+        let handler = ThermostatUiConfigHandler::new(Dataver::new(0));
+
+        unwrap!(write(&handler, 1, |config, value| {
+            config.temperature_display_mode = value
+        }));
+        unwrap!(write(&handler, 5, |config, value| config.keypad_lockout = value));
+        unwrap!(write(&handler, 1, |config, value| {
+            config.schedule_programming_visibility = value
+        }));
+
+        assert_eq!(
+            handler.temperature_display_mode(),
+            TemperatureDisplayModeEnum::Fahrenheit
+        );
+        assert_eq!(handler.keypad_lockout(), KeypadLockoutEnum::Lockout5);
+        assert_eq!(
+            handler.schedule_programming_visibility(),
+            ScheduleProgrammingVisibilityEnum::ScheduleProgrammingDenied
+        );
+
+        let mut buf = [0; 64];
+        unwrap!(handler.store(&mut buf));
+
+        // Out-of-range values are rejected and do not change the preferences
+        assert!(write(&handler, 2, |config, value| {
+            config.temperature_display_mode = value
+        })
+        .is_err());
+        assert!(write(&handler, 6, |config, value| config.keypad_lockout = value).is_err());
+        assert!(write(&handler, 2, |config, value| {
+            config.schedule_programming_visibility = value
+        })
+        .is_err());
+
+        assert!(!handler.changed());
+        assert_eq!(
+            handler.temperature_display_mode(),
+            TemperatureDisplayModeEnum::Fahrenheit
+        );
+        assert_eq!(handler.keypad_lockout(), KeypadLockoutEnum::Lockout5);
+        assert_eq!(
+            handler.schedule_programming_visibility(),
+            ScheduleProgrammingVisibilityEnum::ScheduleProgrammingDenied
+        );
+    }
+
+    #[test]
+    fn test_persist() {
+        let handler = ThermostatUiConfigHandler::new(Dataver::new(0));
+
+        handler.set(|config| config.keypad_lockout = KeypadLockoutEnum::Lockout2);
+        assert!(handler.changed());
+
+        let mut buf = [0; 64];
+        let data = unwrap!(unwrap!(handler.store(&mut buf)));
+        assert!(!handler.changed());
+
+        let handler2 = ThermostatUiConfigHandler::new(Dataver::new(0));
+        unwrap!(handler2.load(data));
+
+        assert_eq!(handler2.keypad_lockout(), KeypadLockoutEnum::Lockout2);
+        assert!(!handler2.changed());
+    }
+}
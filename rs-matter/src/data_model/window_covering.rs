@@ -0,0 +1,1211 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! This module contains the implementation of the Window Covering cluster and its handler.
+//!
+//! The handler implements the Lift, Tilt, PositionAwareLift, PositionAwareTilt and AbsolutePosition
+//! features of the cluster.
+
+use core::cmp::Ordering;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::{Duration, Timer};
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{FromTLV, Nullable, TLVElement, TLVTag, ToTLV};
+use crate::utils::cell::RefCell;
+use crate::utils::storage::WriteBuf;
+use crate::utils::sync::Notification;
+use crate::with;
+
+use super::objects::{Cluster, Dataver, InvokeContext, ReadContext, WriteContext};
+use super::scenes::{SceneAttributeValue, SceneAttributeValues, SceneExtension};
+
+pub use crate::data_model::clusters::window_covering::*;
+
+/// The fully open position, in percent100ths.
+pub const OPEN: u16 = 0;
+/// The fully closed position, in percent100ths.
+pub const CLOSED: u16 = 10000;
+
+/// The resolution of the simulated movement.
+const TICK_MS: u32 = 100;
+
+/// How often - in ticks, i.e. every 5 seconds - the positions of the moving axes are reported
+/// to the subscribers. The start and the end of a movement are reported right away.
+const POSITION_REPORT_TICKS: u32 = 50;
+
+/// An axis of a window covering.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Axis {
+    Lift,
+    Tilt,
+}
+
+/// The installation-specific configuration of an axis of a window covering.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AxisConfig {
+    /// The installed open limit, in the (vendor-specific) units of the `GoToLiftValue` (`GoToTiltValue`) command
+    /// and the `CurrentPositionLift` (`CurrentPositionTilt`) attribute.
+    pub open_limit: u16,
+    /// The installed closed limit, in the same units as `open_limit`. Needs to be greater than `open_limit`.
+    pub closed_limit: u16,
+    /// The time it takes the motor to travel from fully open to fully closed, in milliseconds.
+    pub travel_time_ms: u32,
+}
+
+impl AxisConfig {
+    /// The default configuration of the lift.
+    pub const LIFT: Self = Self::new(0, 2000, 10000);
+    /// The default configuration of the tilt.
+    pub const TILT: Self = Self::new(0, 900, 2000);
+
+    /// Create a new axis configuration.
+    ///
+    /// # Panics
+    /// If `closed_limit` is not greater than `open_limit`, or if `travel_time_ms` is zero.
+    pub const fn new(open_limit: u16, closed_limit: u16, travel_time_ms: u32) -> Self {
+        let config = Self {
+            open_limit,
+            closed_limit,
+            travel_time_ms,
+        };
+
+        // `core::assert!`, as the `defmt` one cannot be used in `const` contexts
+        core::assert!(config.is_valid());
+
+        config
+    }
+
+    const fn is_valid(&self) -> bool {
+        self.closed_limit > self.open_limit && self.travel_time_ms > 0
+    }
+
+    /// Convert an absolute position within the installed limits to percent100ths.
+    fn to_percent100ths(self, value: u16) -> Result<u16, Error> {
+        if !(self.open_limit..=self.closed_limit).contains(&value) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        Ok(((value - self.open_limit) as u32 * CLOSED as u32
+            / (self.closed_limit - self.open_limit) as u32) as u16)
+    }
+
+    /// Convert a position in percent100ths to an absolute position within the installed limits.
+    fn to_value(self, percent100ths: u16) -> u16 {
+        self.open_limit
+            + (percent100ths as u32 * (self.closed_limit - self.open_limit) as u32 / CLOSED as u32)
+                as u16
+    }
+
+    /// The distance - in percent100ths - travelled by the motor in one tick of the simulated movement.
+    fn step(&self) -> u16 {
+        (CLOSED as u32 * TICK_MS / self.travel_time_ms).clamp(1, CLOSED as _) as u16
+    }
+}
+
+/// The motion of the motor of an axis.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Motion {
+    Stopped,
+    Opening,
+    Closing,
+}
+
+impl Motion {
+    /// The value of the 2-bit fields of the `OperationalStatus` attribute.
+    const fn status(&self) -> u8 {
+        match self {
+            Self::Stopped => 0,
+            Self::Opening => 1,
+            Self::Closing => 2,
+        }
+    }
+
+    const fn reversed(self) -> Self {
+        match self {
+            Self::Stopped => Self::Stopped,
+            Self::Opening => Self::Closing,
+            Self::Closing => Self::Opening,
+        }
+    }
+}
+
+/// A trait for the motor driver of a window covering.
+pub trait WindowCoveringHooks {
+    /// Start or stop the motor of the given axis.
+    ///
+    /// When the `MotorDirectionReversed` bit of the `Mode` attribute is set, the motion is reversed,
+    /// i.e. `Motion::Opening` is requested when the covering needs to close and vice versa.
+    fn set_motion(&self, axis: Axis, motion: Motion);
+
+    /// Calibrate the motor of the given axis, when the `CalibrationMode` bit of the `Mode` attribute is set.
+    ///
+    /// The implementation may update the provided configuration of the axis with the installed limits and
+    /// the travel time measured during the calibration.
+    ///
+    /// Return `true` if the calibration succeeded, in which case the updated configuration is applied,
+    /// and - once all axes are calibrated - the bit is cleared.
+    ///
+    /// The default implementation does nothing and returns `true`.
+    fn calibrate(&self, axis: Axis, config: &mut AxisConfig) -> bool {
+        let _ = (axis, config);
+
+        true
+    }
+}
+
+impl<T> WindowCoveringHooks for &T
+where
+    T: WindowCoveringHooks,
+{
+    fn set_motion(&self, axis: Axis, motion: Motion) {
+        (*self).set_motion(axis, motion)
+    }
+
+    fn calibrate(&self, axis: Axis, config: &mut AxisConfig) -> bool {
+        (*self).calibrate(axis, config)
+    }
+}
+
+/// A no-op implementation for devices that only need to observe the positions
+/// with `WindowCoveringHandler::current_position`.
+impl WindowCoveringHooks for () {
+    fn set_motion(&self, _axis: Axis, _motion: Motion) {}
+}
+
+/// The state of an axis.
+#[derive(Debug, Clone)]
+struct AxisState {
+    config: AxisConfig,
+    current: Option<u16>,
+    target: Option<u16>,
+    motion: Motion,
+}
+
+impl AxisState {
+    const fn new(config: AxisConfig) -> Self {
+        Self {
+            config,
+            current: None,
+            target: None,
+            motion: Motion::Stopped,
+        }
+    }
+}
+
+/// The persisted part of the state of the Window Covering cluster.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct WindowCoveringData {
+    mode: Mode,
+    lift: Option<u16>,
+    tilt: Option<u16>,
+    lift_config: AxisConfig,
+    tilt_config: AxisConfig,
+}
+
+/// The state of the Window Covering cluster.
+struct WindowCoveringState {
+    mode: Mode,
+    lift: AxisState,
+    tilt: AxisState,
+    changed: bool,
+}
+
+impl WindowCoveringState {
+    const fn new(lift: AxisConfig, tilt: AxisConfig) -> Self {
+        Self {
+            mode: Mode::empty(),
+            lift: AxisState::new(lift),
+            tilt: AxisState::new(tilt),
+            changed: false,
+        }
+    }
+
+    fn axis(&self, axis: Axis) -> &AxisState {
+        match axis {
+            Axis::Lift => &self.lift,
+            Axis::Tilt => &self.tilt,
+        }
+    }
+
+    fn axis_mut(&mut self, axis: Axis) -> &mut AxisState {
+        match axis {
+            Axis::Lift => &mut self.lift,
+            Axis::Tilt => &mut self.tilt,
+        }
+    }
+
+    /// Return `true` if the covering cannot be moved remotely, i.e. it is being calibrated or maintained.
+    fn busy(&self) -> bool {
+        self.mode
+            .intersects(Mode::CALIBRATION_MODE | Mode::MAINTENANCE_MODE)
+    }
+
+    fn moving(&self) -> bool {
+        self.lift.motion != Motion::Stopped || self.tilt.motion != Motion::Stopped
+    }
+
+    fn operational_status(&self) -> OperationalStatus {
+        let global = if self.lift.motion != Motion::Stopped {
+            self.lift.motion
+        } else {
+            self.tilt.motion
+        };
+
+        OperationalStatus::from_bits_truncate(
+            global.status() | (self.lift.motion.status() << 2) | (self.tilt.motion.status() << 4),
+        )
+    }
+
+    fn config_status(&self) -> ConfigStatus {
+        let mut status = ConfigStatus::LIFT_POSITION_AWARE | ConfigStatus::TILT_POSITION_AWARE;
+
+        status.set(ConfigStatus::OPERATIONAL, !self.busy());
+        status.set(
+            ConfigStatus::LIFT_MOVEMENT_REVERSED,
+            self.mode.contains(Mode::MOTOR_DIRECTION_REVERSED),
+        );
+
+        status
+    }
+
+    /// Return the motion to be requested from the motor driver.
+    fn motor_motion(&self, motion: Motion) -> Motion {
+        if self.mode.contains(Mode::MOTOR_DIRECTION_REVERSED) {
+            motion.reversed()
+        } else {
+            motion
+        }
+    }
+}
+
+/// A handler for the Window Covering Matter cluster.
+///
+/// The handler drives the motors via the `WindowCoveringHooks` trait, and simulates their movement
+/// towards the target positions at a constant speed. The simulated movement is only running
+/// while the `WindowCoveringHandler::run` future is polled.
+///
+/// The current positions, the `Mode` attribute and the (possibly calibrated) axis configurations should be persisted
+/// (see `WindowCoveringHandler::load` and `WindowCoveringHandler::store`), so that the positions are known after a reboot.
+pub struct WindowCoveringHandler<'a> {
+    dataver: Dataver,
+    covering_type: Type,
+    end_product_type: EndProductType,
+    state: RefCell<WindowCoveringState>,
+    hooks: &'a dyn WindowCoveringHooks,
+    motion_changed: Notification<NoopRawMutex>,
+    persist_state_changed: Notification<NoopRawMutex>,
+}
+
+impl<'a> WindowCoveringHandler<'a> {
+    /// Creates a new instance of `WindowCoveringHandler` with the given `Dataver` and covering types.
+    ///
+    /// The lift and the tilt are configured with `AxisConfig::LIFT` and `AxisConfig::TILT`.
+    ///
+    /// The positions are initially unknown (i.e. `null`), until they are loaded,
+    /// or until the first movement.
+    pub const fn new(
+        dataver: Dataver,
+        covering_type: Type,
+        end_product_type: EndProductType,
+    ) -> Self {
+        Self::new_with_config(
+            dataver,
+            covering_type,
+            end_product_type,
+            AxisConfig::LIFT,
+            AxisConfig::TILT,
+        )
+    }
+
+    /// Creates a new instance of `WindowCoveringHandler` with the given `Dataver`, covering types
+    /// and the given configurations of the lift and the tilt.
+    ///
+    /// The configurations might later be updated by a calibration (see `WindowCoveringHooks::calibrate`).
+    pub const fn new_with_config(
+        dataver: Dataver,
+        covering_type: Type,
+        end_product_type: EndProductType,
+        lift: AxisConfig,
+        tilt: AxisConfig,
+    ) -> Self {
+        Self {
+            dataver,
+            covering_type,
+            end_product_type,
+            state: RefCell::new(WindowCoveringState::new(lift, tilt)),
+            hooks: &(),
+            motion_changed: Notification::new(),
+            persist_state_changed: Notification::new(),
+        }
+    }
+
+    /// Adapt the handler instance to the generic `rs-matter` `Handler` trait
+    pub const fn adapt(self) -> HandlerAdaptor<Self> {
+        HandlerAdaptor(self)
+    }
+
+    /// Set the hooks which drive the motors of the device.
+    pub fn set_hooks(&mut self, hooks: &'a dyn WindowCoveringHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Return the current configuration of the given axis.
+    pub fn axis_config(&self, axis: Axis) -> AxisConfig {
+        self.state.borrow().axis(axis).config
+    }
+
+    /// Return the current position of the given axis, in percent100ths, or `None` if it is not known.
+    pub fn current_position(&self, axis: Axis) -> Option<u16> {
+        self.state.borrow().axis(axis).current
+    }
+
+    /// Return the target position of the given axis, in percent100ths, or `None` if it is not known.
+    pub fn target_position(&self, axis: Axis) -> Option<u16> {
+        self.state.borrow().axis(axis).target
+    }
+
+    /// Set the current position of the given axis, in percent100ths, as reported by the device
+    /// (i.e. after a manual operation), stopping any movement of the axis.
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn set_current_position(&self, axis: Axis, position: u16) {
+        let position = position.min(CLOSED);
+
+        {
+            let mut state = self.state.borrow_mut();
+            let axis_state = state.axis_mut(axis);
+
+            axis_state.current = Some(position);
+            axis_state.target = Some(position);
+        }
+
+        self.set_motion(axis, Motion::Stopped);
+        self.positions_changed();
+    }
+
+    /// Move the given axis to the given position, in percent100ths.
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn go_to(&self, axis: Axis, position: u16) -> Result<(), Error> {
+        if position > CLOSED {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        let motion = {
+            let mut state = self.state.borrow_mut();
+
+            if state.busy() {
+                Err(ErrorCode::Busy)?;
+            }
+
+            let axis_state = state.axis_mut(axis);
+
+            // An unknown position is assumed to be at the opposite end of the target
+            let current = *axis_state.current.get_or_insert(if position > CLOSED / 2 {
+                OPEN
+            } else {
+                CLOSED
+            });
+
+            axis_state.target = Some(position);
+
+            match position.cmp(&current) {
+                Ordering::Less => Motion::Opening,
+                Ordering::Greater => Motion::Closing,
+                Ordering::Equal => Motion::Stopped,
+            }
+        };
+
+        self.set_motion(axis, motion);
+        self.dataver.changed();
+
+        Ok(())
+    }
+
+    /// Stop the movement of both axes.
+    ///
+    /// The caller is expected to notify the subscriptions.
+    pub fn stop(&self) {
+        for axis in [Axis::Lift, Axis::Tilt] {
+            {
+                let mut state = self.state.borrow_mut();
+                let axis_state = state.axis_mut(axis);
+
+                axis_state.target = axis_state.current;
+            }
+
+            self.set_motion(axis, Motion::Stopped);
+        }
+
+        self.positions_changed();
+    }
+
+    /// Run the simulated movement of the motors.
+    ///
+    /// # Arguments
+    /// - `notify`: A callback which is called whenever the movement changes the state of the cluster,
+    ///   and which is expected to notify the subscriptions (i.e. by calling `Subscriptions::notify_changed`).
+    ///   While an axis is moving, the callback is called every few seconds, and then when the axis stops.
+    pub async fn run<F>(&self, notify: F) -> Result<(), Error>
+    where
+        F: Fn(),
+    {
+        let mut ticks = 0;
+
+        loop {
+            if self.state.borrow().moving() {
+                let tick = Timer::after(Duration::from_millis(TICK_MS as _));
+
+                if let Either::First(_) = select(tick, self.motion_changed.wait()).await {
+                    ticks += 1;
+
+                    if self.tick() || ticks >= POSITION_REPORT_TICKS {
+                        ticks = 0;
+                        notify();
+                    }
+                }
+            } else {
+                ticks = 0;
+
+                self.motion_changed.wait().await;
+            }
+        }
+    }
+
+    /// Load the persisted positions and `Mode` attribute from a byte slice.
+    ///
+    /// # Arguments
+    /// - `data`: The byte slice to load the state from
+    pub fn load(&self, data: &[u8]) -> Result<(), Error> {
+        let data = WindowCoveringData::from_tlv(&TLVElement::new(data))?;
+
+        if !data.lift_config.is_valid() || !data.tilt_config.is_valid() {
+            Err(ErrorCode::InvalidData)?;
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        state.mode = data.mode;
+        state.lift.config = data.lift_config;
+        state.tilt.config = data.tilt_config;
+        state.lift.current = data.lift;
+        state.lift.target = data.lift;
+        state.tilt.current = data.tilt;
+        state.tilt.target = data.tilt;
+        state.changed = false;
+
+        self.dataver.changed();
+
+        Ok(())
+    }
+
+    /// Store the positions and the `Mode` attribute into a byte slice.
+    ///
+    /// # Arguments
+    /// - `buf`: The byte slice to store the state into
+    ///
+    /// Returns `Ok(None)` if the state has not changed, `Ok(Some(data))` if the state has changed
+    /// where `data` is the sub-slice of the buffer that contains the data to be persisted
+    pub fn store<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Error> {
+        let mut state = self.state.borrow_mut();
+
+        if !state.changed {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        WindowCoveringData {
+            mode: state.mode,
+            lift: state.lift.current,
+            tilt: state.tilt.current,
+            lift_config: state.lift.config,
+            tilt_config: state.tilt.config,
+        }
+        .to_tlv(&TLVTag::Anonymous, &mut wb)?;
+
+        state.changed = false;
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    /// Return `true` if the state has changed and needs to be persisted.
+    pub fn changed(&self) -> bool {
+        self.state.borrow().changed
+    }
+
+    /// Wait for the state to be changed in a way that requires persisting.
+    pub async fn wait_persist(&self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+
+            self.persist_state_changed.wait().await;
+        }
+    }
+
+    /// Advance the simulated movement of the moving axes by one tick.
+    ///
+    /// Return `true` if an axis had reached its target position and stopped.
+    fn tick(&self) -> bool {
+        let mut stopped = false;
+
+        for axis in [Axis::Lift, Axis::Tilt] {
+            let reached = {
+                let mut state = self.state.borrow_mut();
+                let axis_state = state.axis_mut(axis);
+
+                let (Some(current), Some(target)) = (axis_state.current, axis_state.target) else {
+                    continue;
+                };
+
+                if axis_state.motion == Motion::Stopped {
+                    continue;
+                }
+
+                let step = axis_state.config.step();
+
+                let current = if current < target {
+                    current.saturating_add(step).min(target)
+                } else {
+                    current.saturating_sub(step).max(target)
+                };
+
+                axis_state.current = Some(current);
+
+                current == target
+            };
+
+            if reached {
+                self.set_motion(axis, Motion::Stopped);
+                self.positions_changed();
+
+                stopped = true;
+            }
+        }
+
+        self.dataver.changed();
+
+        stopped
+    }
+
+    /// Update the motion of the given axis, and drive its motor accordingly.
+    fn set_motion(&self, axis: Axis, motion: Motion) {
+        let motor_motion = {
+            let mut state = self.state.borrow_mut();
+
+            if state.axis(axis).motion == motion {
+                return;
+            }
+
+            state.axis_mut(axis).motion = motion;
+
+            state.motor_motion(motion)
+        };
+
+        self.hooks.set_motion(axis, motor_motion);
+
+        self.dataver.changed();
+        self.motion_changed.notify();
+    }
+
+    /// Mark the positions as changed, so that they are persisted.
+    fn positions_changed(&self) {
+        self.state.borrow_mut().changed = true;
+
+        self.persist_state_changed.notify();
+        self.dataver.changed();
+    }
+
+    /// Set the `Mode` attribute, calibrating the motors if requested.
+    fn set_mode(&self, mode: Mode) -> Result<(), Error> {
+        if !Mode::all().contains(mode) {
+            Err(ErrorCode::ConstraintError)?;
+        }
+
+        self.state.borrow_mut().mode = mode;
+
+        if mode.intersects(Mode::CALIBRATION_MODE | Mode::MAINTENANCE_MODE) {
+            for axis in [Axis::Lift, Axis::Tilt] {
+                {
+                    let mut state = self.state.borrow_mut();
+                    let axis_state = state.axis_mut(axis);
+
+                    axis_state.target = axis_state.current;
+                }
+
+                self.set_motion(axis, Motion::Stopped);
+            }
+        }
+
+        if mode.contains(Mode::CALIBRATION_MODE) {
+            let mut calibrated = true;
+
+            for axis in [Axis::Lift, Axis::Tilt] {
+                let mut config = self.axis_config(axis);
+
+                if self.hooks.calibrate(axis, &mut config) && config.is_valid() {
+                    self.state.borrow_mut().axis_mut(axis).config = config;
+                } else {
+                    calibrated = false;
+                }
+            }
+
+            if calibrated {
+                self.state.borrow_mut().mode.remove(Mode::CALIBRATION_MODE);
+            }
+        }
+
+        self.positions_changed();
+
+        Ok(())
+    }
+
+    fn go_to_both(&self, position: u16) -> Result<(), Error> {
+        self.go_to(Axis::Lift, position)?;
+        self.go_to(Axis::Tilt, position)
+    }
+}
+
+impl SceneExtension for WindowCoveringHandler<'_> {
+    fn cluster_id(&self) -> u32 {
+        Self::CLUSTER.id
+    }
+
+    fn store_scene(&self, values: &mut SceneAttributeValues) -> Result<(), Error> {
+        for (axis, attr) in [
+            (Axis::Lift, AttributeId::CurrentPositionLiftPercent100ths),
+            (Axis::Tilt, AttributeId::CurrentPositionTiltPercent100ths),
+        ] {
+            if let Some(position) = self.current_position(axis) {
+                values
+                    .push(SceneAttributeValue::new(attr as _, position as _))
+                    .map_err(|_| ErrorCode::NoSpace)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn recall_scene(
+        &self,
+        values: &[SceneAttributeValue],
+        _transition_time: Duration,
+    ) -> Result<(), Error> {
+        // The motors move at their own speed, so the transition time is ignored
+        for (axis, attr) in [
+            (Axis::Lift, AttributeId::CurrentPositionLiftPercent100ths),
+            (Axis::Tilt, AttributeId::CurrentPositionTiltPercent100ths),
+        ] {
+            if let Some(position) = SceneAttributeValue::find(values, attr as _) {
+                self.go_to(axis, position.min(CLOSED as _) as _)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ClusterHandler for WindowCoveringHandler<'_> {
+    const CLUSTER: Cluster<'static> = FULL_CLUSTER
+        .with_revision(5)
+        .with_features(
+            Feature::LIFT.bits()
+                | Feature::TILT.bits()
+                | Feature::POSITION_AWARE_LIFT.bits()
+                | Feature::POSITION_AWARE_TILT.bits()
+                | Feature::ABSOLUTE_POSITION.bits(),
+        )
+        .with_attrs(with!(
+            required;
+            AttributeId::CurrentPositionLift
+                | AttributeId::CurrentPositionTilt
+                | AttributeId::CurrentPositionLiftPercentage
+                | AttributeId::CurrentPositionTiltPercentage
+                | AttributeId::TargetPositionLiftPercent100ths
+                | AttributeId::TargetPositionTiltPercent100ths
+                | AttributeId::CurrentPositionLiftPercent100ths
+                | AttributeId::CurrentPositionTiltPercent100ths
+                | AttributeId::InstalledOpenLimitLift
+                | AttributeId::InstalledClosedLimitLift
+                | AttributeId::InstalledOpenLimitTilt
+                | AttributeId::InstalledClosedLimitTilt
+        ))
+        .with_cmds(with!(all));
+
+    fn dataver(&self) -> u32 {
+        self.dataver.get()
+    }
+
+    fn dataver_changed(&self) {
+        self.dataver.changed();
+    }
+
+    fn r#type(&self, _ctx: &ReadContext) -> Result<Type, Error> {
+        Ok(self.covering_type)
+    }
+
+    fn config_status(&self, _ctx: &ReadContext) -> Result<ConfigStatus, Error> {
+        Ok(self.state.borrow().config_status())
+    }
+
+    fn operational_status(&self, _ctx: &ReadContext) -> Result<OperationalStatus, Error> {
+        Ok(self.state.borrow().operational_status())
+    }
+
+    fn end_product_type(&self, _ctx: &ReadContext) -> Result<EndProductType, Error> {
+        Ok(self.end_product_type)
+    }
+
+    fn mode(&self, _ctx: &ReadContext) -> Result<Mode, Error> {
+        Ok(self.state.borrow().mode)
+    }
+
+    fn current_position_lift(&self, _ctx: &ReadContext) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::new(self.current_position(Axis::Lift).map(
+            |position| self.axis_config(Axis::Lift).to_value(position),
+        )))
+    }
+
+    fn current_position_tilt(&self, _ctx: &ReadContext) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::new(self.current_position(Axis::Tilt).map(
+            |position| self.axis_config(Axis::Tilt).to_value(position),
+        )))
+    }
+
+    fn current_position_lift_percentage(&self, _ctx: &ReadContext) -> Result<Nullable<u8>, Error> {
+        Ok(Nullable::new(
+            self.current_position(Axis::Lift)
+                .map(|position| (position / 100) as _),
+        ))
+    }
+
+    fn current_position_tilt_percentage(&self, _ctx: &ReadContext) -> Result<Nullable<u8>, Error> {
+        Ok(Nullable::new(
+            self.current_position(Axis::Tilt)
+                .map(|position| (position / 100) as _),
+        ))
+    }
+
+    fn target_position_lift_percent_100_ths(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::new(self.target_position(Axis::Lift)))
+    }
+
+    fn target_position_tilt_percent_100_ths(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::new(self.target_position(Axis::Tilt)))
+    }
+
+    fn current_position_lift_percent_100_ths(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::new(self.current_position(Axis::Lift)))
+    }
+
+    fn current_position_tilt_percent_100_ths(
+        &self,
+        _ctx: &ReadContext,
+    ) -> Result<Nullable<u16>, Error> {
+        Ok(Nullable::new(self.current_position(Axis::Tilt)))
+    }
+
+    fn installed_open_limit_lift(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.axis_config(Axis::Lift).open_limit)
+    }
+
+    fn installed_closed_limit_lift(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.axis_config(Axis::Lift).closed_limit)
+    }
+
+    fn installed_open_limit_tilt(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.axis_config(Axis::Tilt).open_limit)
+    }
+
+    fn installed_closed_limit_tilt(&self, _ctx: &ReadContext) -> Result<u16, Error> {
+        Ok(self.axis_config(Axis::Tilt).closed_limit)
+    }
+
+    fn set_mode(&self, ctx: &WriteContext, value: Mode) -> Result<(), Error> {
+        WindowCoveringHandler::set_mode(self, value)?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_up_or_open(&self, ctx: &InvokeContext) -> Result<(), Error> {
+        self.go_to_both(OPEN)?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_down_or_close(&self, ctx: &InvokeContext) -> Result<(), Error> {
+        self.go_to_both(CLOSED)?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_stop_motion(&self, ctx: &InvokeContext) -> Result<(), Error> {
+        if self.state.borrow().busy() {
+            Err(ErrorCode::Busy)?;
+        }
+
+        self.stop();
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_go_to_lift_value(
+        &self,
+        ctx: &InvokeContext,
+        request: GoToLiftValueRequest,
+    ) -> Result<(), Error> {
+        self.go_to(
+            Axis::Lift,
+            self.axis_config(Axis::Lift)
+                .to_percent100ths(request.lift_value()?)?,
+        )?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_go_to_lift_percentage(
+        &self,
+        ctx: &InvokeContext,
+        request: GoToLiftPercentageRequest,
+    ) -> Result<(), Error> {
+        self.go_to(Axis::Lift, request.lift_percent_100_ths_value()?)?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_go_to_tilt_value(
+        &self,
+        ctx: &InvokeContext,
+        request: GoToTiltValueRequest,
+    ) -> Result<(), Error> {
+        self.go_to(
+            Axis::Tilt,
+            self.axis_config(Axis::Tilt)
+                .to_percent100ths(request.tilt_value()?)?,
+        )?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+
+    fn handle_go_to_tilt_percentage(
+        &self,
+        ctx: &InvokeContext,
+        request: GoToTiltPercentageRequest,
+    ) -> Result<(), Error> {
+        self.go_to(Axis::Tilt, request.tilt_percent_100_ths_value()?)?;
+
+        ctx.notify_changed();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::data_model::objects::Dataver;
+    use crate::data_model::scenes::SceneAttributeValues;
+
+    use super::*;
+
+    struct Hooks {
+        lift: Cell<Motion>,
+        tilt: Cell<Motion>,
+        calibrated: Cell<bool>,
+    }
+
+    impl Hooks {
+        const fn new() -> Self {
+            Self {
+                lift: Cell::new(Motion::Stopped),
+                tilt: Cell::new(Motion::Stopped),
+                calibrated: Cell::new(false),
+            }
+        }
+    }
+
+    impl WindowCoveringHooks for Hooks {
+        fn set_motion(&self, axis: Axis, motion: Motion) {
+            match axis {
+                Axis::Lift => self.lift.set(motion),
+                Axis::Tilt => self.tilt.set(motion),
+            }
+        }
+
+        fn calibrate(&self, axis: Axis, config: &mut AxisConfig) -> bool {
+            self.calibrated.set(true);
+
+            // The lift turns out to be longer and slower than configured
+            if axis == Axis::Lift {
+                *config = CALIBRATED_LIFT;
+            }
+
+            true
+        }
+    }
+
+    const CALIBRATED_LIFT: AxisConfig = AxisConfig::new(100, 3100, 20000);
+
+    fn handler(hooks: &Hooks) -> WindowCoveringHandler<'_> {
+        let mut handler = WindowCoveringHandler::new(
+            Dataver::new(0),
+            Type::TiltBlindLiftAndTilt,
+            EndProductType::TiltOnlyInteriorBlind,
+        );
+        handler.set_hooks(hooks);
+
+        handler
+    }
+
+    fn run_to_completion(handler: &WindowCoveringHandler) {
+        while handler.state.borrow().moving() {
+            handler.tick();
+        }
+    }
+
+    #[test]
+    fn test_movement() {
+        let hooks = Hooks::new();
+        let handler = handler(&hooks);
+
+        assert_eq!(handler.current_position(Axis::Lift), None);
+
+        unwrap!(handler.go_to_both(CLOSED));
+        assert_eq!(hooks.lift.get(), Motion::Closing);
+        assert_eq!(hooks.tilt.get(), Motion::Closing);
+        assert_eq!(
+            handler.state.borrow().operational_status().bits(),
+            0b10_10_10
+        );
+
+        // Intermediate positions are not reported...
+        assert!(!handler.tick());
+        assert_eq!(handler.current_position(Axis::Lift), Some(100));
+        assert_eq!(handler.current_position(Axis::Tilt), Some(500));
+
+        // Stopping keeps the current positions
+        handler.stop();
+        assert_eq!(hooks.lift.get(), Motion::Stopped);
+        assert_eq!(handler.target_position(Axis::Lift), Some(100));
+        assert_eq!(handler.state.borrow().operational_status().bits(), 0);
+
+        unwrap!(handler.go_to(Axis::Lift, 200));
+        assert_eq!(hooks.tilt.get(), Motion::Stopped);
+        // ... unlike the end of the movement
+        assert!(handler.tick());
+        assert_eq!(handler.current_position(Axis::Lift), Some(200));
+
+        unwrap!(handler.go_to(Axis::Lift, 5000));
+        run_to_completion(&handler);
+        assert_eq!(hooks.lift.get(), Motion::Stopped);
+        assert_eq!(handler.current_position(Axis::Lift), Some(5000));
+
+        unwrap!(handler.go_to(Axis::Lift, OPEN));
+        assert_eq!(hooks.lift.get(), Motion::Opening);
+        assert_eq!(handler.state.borrow().operational_status().bits(), 0b01_01);
+
+        assert!(handler.go_to(Axis::Tilt, CLOSED + 1).is_err());
+    }
+
+    #[test]
+    fn test_absolute_position() {
+        assert_eq!(unwrap!(AxisConfig::LIFT.to_percent100ths(1000)), 5000);
+        assert_eq!(AxisConfig::LIFT.to_value(2500), 500);
+        assert_eq!(
+            unwrap!(AxisConfig::TILT.to_percent100ths(AxisConfig::TILT.closed_limit)),
+            CLOSED
+        );
+        assert!(AxisConfig::TILT
+            .to_percent100ths(AxisConfig::TILT.closed_limit + 1)
+            .is_err());
+
+        // The installed limits are configurable
+        let handler = WindowCoveringHandler::new_with_config(
+            Dataver::new(0),
+            Type::TiltBlindLiftAndTilt,
+            EndProductType::TiltOnlyInteriorBlind,
+            CALIBRATED_LIFT,
+            AxisConfig::TILT,
+        );
+
+        assert_eq!(unwrap!(CALIBRATED_LIFT.to_percent100ths(100)), OPEN);
+        assert_eq!(unwrap!(CALIBRATED_LIFT.to_percent100ths(1600)), 5000);
+        assert!(CALIBRATED_LIFT.to_percent100ths(99).is_err());
+
+        // ... and so is the travel time
+        unwrap!(handler.go_to(Axis::Lift, CLOSED));
+        handler.tick();
+        assert_eq!(handler.current_position(Axis::Lift), Some(50));
+    }
+
+    #[test]
+    fn test_mode() {
+        let hooks = Hooks::new();
+        let handler = handler(&hooks);
+
+        // Reversed motor direction
+        unwrap!(handler.set_mode(Mode::MOTOR_DIRECTION_REVERSED));
+        assert!(handler
+            .state
+            .borrow()
+            .config_status()
+            .contains(ConfigStatus::LIFT_MOVEMENT_REVERSED));
+
+        unwrap!(handler.go_to(Axis::Lift, CLOSED));
+        assert_eq!(hooks.lift.get(), Motion::Opening);
+        assert_eq!(handler.state.borrow().operational_status().bits(), 0b10_10);
+
+        // Maintenance stops the motors and rejects movement
+        unwrap!(handler.set_mode(Mode::MAINTENANCE_MODE));
+        assert_eq!(hooks.lift.get(), Motion::Stopped);
+        assert_eq!(
+            handler.target_position(Axis::Lift),
+            handler.current_position(Axis::Lift)
+        );
+        assert!(!handler
+            .state
+            .borrow()
+            .config_status()
+            .contains(ConfigStatus::OPERATIONAL));
+        assert_eq!(
+            handler.go_to(Axis::Lift, OPEN).map_err(|e| e.code()),
+            Err(ErrorCode::Busy)
+        );
+
+        // Calibration is cleared once done, and updates the axis configurations
+        unwrap!(handler.set_mode(Mode::CALIBRATION_MODE));
+        assert!(hooks.calibrated.get());
+        assert_eq!(handler.axis_config(Axis::Lift), CALIBRATED_LIFT);
+        assert_eq!(handler.axis_config(Axis::Tilt), AxisConfig::TILT);
+        assert_eq!(handler.state.borrow().mode, Mode::empty());
+        assert!(handler
+            .state
+            .borrow()
+            .config_status()
+            .contains(ConfigStatus::OPERATIONAL));
+        unwrap!(handler.go_to(Axis::Lift, OPEN));
+
+        assert!(handler.set_mode(Mode::from_bits_retain(0x10)).is_err());
+    }
+
+    #[test]
+    fn test_scene() {
+        let hooks = Hooks::new();
+        let handler = handler(&hooks);
+
+        handler.set_current_position(Axis::Lift, 3000);
+        handler.set_current_position(Axis::Tilt, 7000);
+
+        let mut values = SceneAttributeValues::new();
+        unwrap!(handler.store_scene(&mut values));
+        assert_eq!(values.len(), 2);
+
+        handler.set_current_position(Axis::Lift, OPEN);
+        handler.set_current_position(Axis::Tilt, OPEN);
+
+        unwrap!(handler.recall_scene(&values, Duration::from_secs(0)));
+        run_to_completion(&handler);
+        assert_eq!(handler.current_position(Axis::Lift), Some(3000));
+        assert_eq!(handler.current_position(Axis::Tilt), Some(7000));
+    }
+
+    #[test]
+    fn test_persist() {
+        let hooks = Hooks::new();
+        let handler = handler(&hooks);
+
+        let mut buf = [0; 64];
+        assert!(unwrap!(handler.store(&mut buf)).is_none());
+
+        unwrap!(handler.go_to(Axis::Lift, 1000));
+        run_to_completion(&handler);
+        unwrap!(handler.set_mode(Mode::CALIBRATION_MODE));
+        unwrap!(handler.set_mode(Mode::LED_FEEDBACK));
+        assert!(handler.changed());
+
+        let data = unwrap!(unwrap!(handler.store(&mut buf))).to_vec();
+        assert!(!handler.changed());
+
+        let other = WindowCoveringHandler::new(
+            Dataver::new(0),
+            Type::TiltBlindLiftAndTilt,
+            EndProductType::TiltOnlyInteriorBlind,
+        );
+        unwrap!(other.load(&data));
+        assert_eq!(other.current_position(Axis::Lift), Some(1000));
+        assert_eq!(other.target_position(Axis::Lift), Some(1000));
+        assert_eq!(other.current_position(Axis::Tilt), None);
+        assert_eq!(other.state.borrow().mode, Mode::LED_FEEDBACK);
+        assert_eq!(other.axis_config(Axis::Lift), CALIBRATED_LIFT);
+        assert_eq!(other.axis_config(Axis::Tilt), AxisConfig::TILT);
+
+        // Invalid axis configurations are rejected
+        let mut wb = WriteBuf::new(&mut buf);
+        unwrap!(WindowCoveringData {
+            mode: Mode::empty(),
+            lift: None,
+            tilt: None,
+            lift_config: AxisConfig {
+                travel_time_ms: 0,
+                ..AxisConfig::LIFT
+            },
+            tilt_config: AxisConfig::TILT,
+        }
+        .to_tlv(&TLVTag::Anonymous, &mut wb));
+        let len = wb.get_tail();
+        assert_eq!(
+            other.load(&buf[..len]).map_err(|e| e.code()),
+            Err(ErrorCode::InvalidData)
+        );
+        assert_eq!(other.axis_config(Axis::Lift), CALIBRATED_LIFT);
+    }
+}
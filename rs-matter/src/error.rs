@@ -44,6 +44,7 @@ pub enum ErrorCode {
     ResourceExhausted,
     Busy,
     DataVersionMismatch,
+    Failure,
     Crypto,
     TLSStack,
     BtpError,
@@ -212,6 +212,15 @@ impl Fabric {
         self.fabric_id
     }
 
+    /// Return the fabric's compressed fabric ID
+    ///
+    /// The ID is the first part of the fabric's operational mDNS service name (`<CompressedFabricId>-<NodeId>`).
+    pub fn compressed_fabric_id(&self) -> Option<u64> {
+        self.mdns_service_name
+            .split_once('-')
+            .and_then(|(id, _)| u64::from_str_radix(id, 16).ok())
+    }
+
     /// Return the fabric's local index
     pub fn fab_idx(&self) -> NonZeroU8 {
         self.fab_idx
@@ -398,6 +407,7 @@ pub const MAX_SUPPORTED_FABRICS: usize = 3;
 /// Fabric manager type
 pub struct FabricMgr {
     fabrics: Vec<Fabric, MAX_SUPPORTED_FABRICS>,
+    next_fab_idx: NonZeroU8,
     changed: bool,
 }
 
@@ -413,6 +423,7 @@ impl FabricMgr {
     pub const fn new() -> Self {
         Self {
             fabrics: Vec::new(),
+            next_fab_idx: NonZeroU8::MIN,
             changed: false,
         }
     }
@@ -421,6 +432,7 @@ impl FabricMgr {
     pub fn init() -> impl Init<Self> {
         init!(Self {
             fabrics <- Vec::init(),
+            next_fab_idx: NonZeroU8::MIN,
             changed: false,
         })
     }
@@ -428,6 +440,7 @@ impl FabricMgr {
     /// Removes all fabrics
     pub fn reset(&mut self) {
         self.fabrics.clear();
+        self.next_fab_idx = NonZeroU8::MIN;
         self.changed = false;
     }
 
@@ -439,13 +452,26 @@ impl FabricMgr {
 
         self.fabrics.clear();
 
-        for entry in TLVElement::new(data).array()?.iter() {
+        let root = TLVElement::new(data);
+
+        // Data stored before the next fabric index was persisted is just the array of fabrics
+        let (fabrics, next_fab_idx) = if let Ok(seq) = root.structure() {
+            (seq.ctx(0)?, Some(seq.ctx(1)?.u8()?))
+        } else {
+            (root, None)
+        };
+
+        for entry in fabrics.array()?.iter() {
             let entry = entry?;
 
             self.fabrics
                 .push_init(Fabric::init_from_tlv(entry), || ErrorCode::NoSpace.into())?;
         }
 
+        self.next_fab_idx = next_fab_idx
+            .and_then(NonZeroU8::new)
+            .unwrap_or_else(|| Self::fab_idx_after(self.max_fab_idx()));
+
         for fabric in &self.fabrics {
             mdns.add(&fabric.mdns_service_name, ServiceMode::Commissioned)?;
         }
@@ -466,7 +492,8 @@ impl FabricMgr {
 
         let mut wb = WriteBuf::new(buf);
 
-        wb.start_array(&TLVTag::Anonymous)?;
+        wb.start_struct(&TLVTag::Anonymous)?;
+        wb.start_array(&TLVTag::Context(0))?;
 
         for fabric in self.iter() {
             fabric
@@ -474,6 +501,8 @@ impl FabricMgr {
                 .map_err(|_| ErrorCode::NoSpace)?;
         }
 
+        wb.end_container()?;
+        wb.u8(&TLVTag::Context(1), self.next_fab_idx.get())?;
         wb.end_container()?;
 
         self.changed = false;
@@ -501,24 +530,17 @@ impl FabricMgr {
     where
         F: FnOnce(&mut Fabric) -> Result<(), Error>,
     {
-        let max_fab_idx = self
-            .iter()
-            .map(|fabric| fabric.fab_idx().get())
-            .max()
-            .unwrap_or(0);
-        let fab_idx = unwrap!(NonZeroU8::new(if max_fab_idx < u8::MAX - 1 {
-            // First try with the next available fabric index larger than all currently used
-            max_fab_idx + 1
-        } else {
-            // If there is already a fabric with index 254, try to find the first unused one
-            let Some(fab_idx) = (1..u8::MAX)
-                .find(|fab_idx| self.iter().all(|fabric| fabric.fab_idx().get() != *fab_idx))
-            else {
+        // Fabric indices are allocated in a round-robin fashion, so that the index of a removed fabric
+        // is not re-used right away by the next fabric, which might otherwise inherit fabric-scoped data
+        // (i.e. scenes) not yet cleaned up after the removed fabric
+        let mut fab_idx = self.next_fab_idx;
+        while self.get(fab_idx).is_some() {
+            fab_idx = Self::fab_idx_after(fab_idx.get());
+
+            if fab_idx == self.next_fab_idx {
                 return Err(ErrorCode::NoSpace.into());
-            };
-
-            fab_idx
-        })); // We never use 0 as a fabric index, nor u8::MAX
+            }
+        }
 
         self.fabrics.push_init(
             Fabric::init(fab_idx, key_pair)
@@ -527,6 +549,8 @@ impl FabricMgr {
             || ErrorCode::NoSpace.into(),
         )?;
 
+        self.next_fab_idx = Self::fab_idx_after(fab_idx.get());
+
         let fabric = unwrap!(self.fabrics.last_mut());
         self.changed = true;
 
@@ -629,6 +653,25 @@ impl FabricMgr {
         Ok(())
     }
 
+    /// Return the largest local index of the fabrics, or 0 if there are no fabrics
+    fn max_fab_idx(&self) -> u8 {
+        self.iter()
+            .map(|fabric| fabric.fab_idx().get())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Return the fabric index following the provided one.
+    ///
+    /// We never use 0 as a fabric index, nor u8::MAX.
+    fn fab_idx_after(fab_idx: u8) -> NonZeroU8 {
+        unwrap!(NonZeroU8::new(if fab_idx < u8::MAX - 1 {
+            fab_idx + 1
+        } else {
+            1
+        }))
+    }
+
     /// Get a fabric that matches the provided destination ID
     pub fn get_by_dest_id(&self, random: &[u8], target: &[u8]) -> Option<&Fabric> {
         self.iter()
@@ -774,3 +817,58 @@ impl FabricMgr {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU8;
+
+    use crate::crypto::KeyPair;
+    use crate::mdns::MdnsService;
+    use crate::test_device::{TEST_DEV_ATT, TEST_DEV_COMM, TEST_DEV_DET};
+    use crate::utils::epoch::dummy_epoch;
+    use crate::utils::rand::dummy_rand;
+    use crate::{Matter, MATTER_PORT};
+
+    fn matter() -> Matter<'static> {
+        Matter::new(
+            &TEST_DEV_DET,
+            TEST_DEV_COMM,
+            &TEST_DEV_ATT,
+            MdnsService::Disabled,
+            dummy_epoch,
+            dummy_rand,
+            MATTER_PORT,
+        )
+    }
+
+    fn add(matter: &Matter<'_>) -> u8 {
+        unwrap!(matter
+            .fabric_mgr
+            .borrow_mut()
+            .add_with_post_init(unwrap!(KeyPair::new(dummy_rand)), |_| Ok(())))
+        .fab_idx()
+        .get()
+    }
+
+    #[test]
+    fn test_fab_idx_not_reused() {
+        let matter = matter();
+
+        assert_eq!(add(&matter), 1);
+        assert_eq!(add(&matter), 2);
+
+        // The index of a removed fabric is not given to the next fabric
+        unwrap!(matter.remove_fabric(unwrap!(NonZeroU8::new(2))));
+        assert_eq!(add(&matter), 3);
+
+        // ... even after a reboot
+        let mut buf = [0; 4096];
+        let data = unwrap!(unwrap!(matter.fabric_mgr.borrow_mut().store(&mut buf)));
+
+        let matter = self::matter();
+        unwrap!(matter.load_fabrics(data));
+
+        unwrap!(matter.remove_fabric(unwrap!(NonZeroU8::new(3))));
+        assert_eq!(add(&matter), 4);
+    }
+}
@@ -77,8 +77,20 @@ impl From<IMStatusCode> for IMError {
     }
 }
 
+/// Information about a fail-safe timer which had expired, rather than being disarmed
+/// with `CommissioningComplete`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FailSafeExpiry {
+    /// The local index of the fabric added with `AddNOC` while the fail-safe was armed, if any.
+    ///
+    /// As per the Matter Core spec, this fabric should be removed.
+    pub added_fab_idx: Option<NonZeroU8>,
+}
+
 pub struct FailSafe {
     state: State,
+    expiry: Option<FailSafeExpiry>,
     key_pair: Option<KeyPair>,
     root_ca: Vec<u8, { MAX_CERT_TLV_LEN }>,
     epoch: Epoch,
@@ -90,6 +102,7 @@ impl FailSafe {
     pub const fn new(epoch: Epoch, rand: Rand) -> Self {
         Self {
             state: State::Idle,
+            expiry: None,
             key_pair: None,
             root_ca: Vec::new(),
             epoch,
@@ -100,6 +113,7 @@ impl FailSafe {
     pub fn init(epoch: Epoch, rand: Rand) -> impl Init<Self> {
         init!(Self {
             state: State::Idle,
+            expiry: None,
             key_pair: None,
             root_ca <- Vec::init(),
             epoch,
@@ -116,6 +130,7 @@ impl FailSafe {
                 return Err(ErrorCode::GennCommInvalidAuthentication)?;
             }
 
+            self.expiry = None;
             self.state = State::Armed(ArmedCtx {
                 armed_at: (self.epoch)(),
                 timeout_secs,
@@ -428,10 +443,47 @@ impl FailSafe {
         }
     }
 
+    /// Return `true` if the fail-safe is currently armed.
+    pub fn is_armed(&mut self) -> bool {
+        self.update_state_timeout();
+
+        matches!(self.state, State::Armed(_))
+    }
+
+    /// Return the time remaining until the fail-safe expires, or `None` if it is not armed.
+    pub fn expires_in(&mut self) -> Option<Duration> {
+        self.update_state_timeout();
+
+        if let State::Armed(ctx) = &self.state {
+            let deadline = ctx.armed_at + Duration::from_secs(ctx.timeout_secs as u64);
+
+            Some(deadline.saturating_sub((self.epoch)()))
+        } else {
+            None
+        }
+    }
+
+    /// Return and clear the information about the last fail-safe expiry, if the fail-safe
+    /// had expired since it was last armed.
+    pub fn take_expiry(&mut self) -> Option<FailSafeExpiry> {
+        self.update_state_timeout();
+
+        self.expiry.take()
+    }
+
     fn update_state_timeout(&mut self) {
         if let State::Armed(ctx) = &mut self.state {
             let now = (self.epoch)();
             if now >= ctx.armed_at + Duration::from_secs(ctx.timeout_secs as u64) {
+                warn!("Fail-safe expired");
+
+                self.expiry = Some(FailSafeExpiry {
+                    added_fab_idx: ctx
+                        .flags
+                        .contains(NocFlags::ADD_NOC_RECVD)
+                        .then(|| NonZeroU8::new(ctx.fab_idx))
+                        .flatten(),
+                });
                 self.state = State::Idle;
             }
         }
@@ -75,6 +75,7 @@ pub enum IMStatusCode {
     PathsExhausted = 0xc8,
     TimedRequestMisMatch = 0xc9,
     FailSafeRequired = 0xca,
+    InvalidInState = 0xcb,
 }
 
 impl From<ErrorCode> for IMStatusCode {
@@ -92,6 +93,9 @@ impl From<ErrorCode> for IMStatusCode {
             ErrorCode::ResourceExhausted => IMStatusCode::ResourceExhausted,
             ErrorCode::FailSafeRequired => IMStatusCode::FailSafeRequired,
             ErrorCode::ConstraintError => IMStatusCode::ConstraintError,
+            ErrorCode::NotFound => IMStatusCode::NotFound,
+            ErrorCode::Failure => IMStatusCode::Failure,
+            ErrorCode::InvalidState => IMStatusCode::InvalidInState,
             _ => IMStatusCode::Failure,
         }
     }
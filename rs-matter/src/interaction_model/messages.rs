@@ -280,6 +280,7 @@ pub mod msg {
     pub struct InvResp<'a> {
         pub suppress_response: Option<bool>,
         pub inv_responses: Option<TLVArray<'a, ib::CmdResp<'a>>>,
+        pub more_chunks: Option<bool>,
     }
 
     // This enum is helpful when we are constructing the response
@@ -290,6 +291,7 @@ pub mod msg {
     pub enum InvRespTag {
         SupressResponse = 0,
         InvokeResponses = 1,
+        MoreChunkedMsgs = 2,
     }
 
     #[derive(Debug, Default, Clone, ToTLV, FromTLV)]
@@ -516,10 +518,7 @@ pub mod ib {
 
     impl CmdResp<'_> {
         pub fn status_new(cmd_path: CmdPath, status: IMStatusCode, cluster_status: u16) -> Self {
-            Self::Status(CmdStatus {
-                path: cmd_path,
-                status: Status::new(status, cluster_status),
-            })
+            Self::Status(CmdStatus::new(cmd_path, status, cluster_status))
         }
     }
 
@@ -545,6 +544,7 @@ pub mod ib {
     pub struct CmdStatus {
         path: CmdPath,
         status: Status,
+        command_ref: Option<u16>,
     }
 
     impl CmdStatus {
@@ -555,8 +555,17 @@ pub mod ib {
                     status,
                     cluster_status,
                 },
+                command_ref: None,
             }
         }
+
+        /// Set the reference of the command request this status is a response to.
+        ///
+        /// Only necessary when responding to Invoke Requests with multiple commands.
+        pub fn with_command_ref(mut self, command_ref: Option<u16>) -> Self {
+            self.command_ref = command_ref;
+            self
+        }
     }
 
     #[derive(Debug, Clone, FromTLV, ToTLV)]
@@ -565,17 +574,23 @@ pub mod ib {
     pub struct CmdData<'a> {
         pub path: CmdPath,
         pub data: TLVElement<'a>,
+        pub command_ref: Option<u16>,
     }
 
     impl<'a> CmdData<'a> {
         pub const fn new(path: CmdPath, data: TLVElement<'a>) -> Self {
-            Self { path, data }
+            Self {
+                path,
+                data,
+                command_ref: None,
+            }
         }
     }
 
     pub enum CmdDataTag {
         Path = 0,
         Data = 1,
+        CommandRef = 2,
     }
 
     // Status
@@ -219,6 +219,9 @@ impl ServiceMode {
                 let mut sii_str = heapless::String::<5>::new();
                 write_unwrap!(sii_str, "{}", dev_det.sii.unwrap_or(5000));
 
+                let mut sat_str = heapless::String::<5>::new();
+                write_unwrap!(sat_str, "{}", dev_det.sat.unwrap_or(4000));
+
                 let txt_kvs = &[
                     ("D", discriminator_str.as_str()),
                     ("CM", "1"),
@@ -226,6 +229,7 @@ impl ServiceMode {
                     ("VP", &vp),
                     ("SAI", sai_str.as_str()), // Session Active Interval
                     ("SII", sii_str.as_str()), // Session Idle Interval
+                    ("SAT", sat_str.as_str()), // Session Active Threshold
                     ("PH", "33"),              // Pairing Hint
                     ("PI", ""),                // Pairing Instruction
                 ];
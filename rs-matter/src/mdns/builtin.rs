@@ -8,6 +8,7 @@ use embassy_time::{Duration, Timer};
 
 use crate::data_model::basic_info::BasicInfoConfig;
 use crate::error::{Error, ErrorCode};
+use crate::transport::mrp::MrpParameters;
 use crate::transport::network::{
     Address, Ipv4Addr, Ipv6Addr, NetworkReceive, NetworkSend, SocketAddr, SocketAddrV4,
     SocketAddrV6,
@@ -105,8 +106,12 @@ impl<'a> MdnsImpl<'a> {
         Ok(())
     }
 
+    /// Run the mDNS responder
+    ///
+    /// `peer_params` is called with the instance name and the advertised MRP parameters of every
+    /// operational Matter node whose TXT record is seen in the received mDNS responses.
     #[allow(clippy::too_many_arguments)]
-    pub async fn run<S, R, SB, RB>(
+    pub async fn run<S, R, SB, RB, P>(
         &self,
         send: S,
         recv: R,
@@ -116,12 +121,14 @@ impl<'a> MdnsImpl<'a> {
         ipv4_interface: Option<Ipv4Addr>,
         ipv6_interface: Option<u32>,
         rand: Rand,
+        peer_params: P,
     ) -> Result<(), Error>
     where
         S: NetworkSend,
         R: NetworkReceive,
         SB: BufferAccess<[u8]>,
         RB: BufferAccess<[u8]>,
+        P: FnMut(&str, MrpParameters),
     {
         let send = Mutex::<NoopRawMutex, _>::new(send);
 
@@ -135,7 +142,8 @@ impl<'a> MdnsImpl<'a> {
             host,
             ipv4_interface,
             ipv6_interface,
-            rand
+            rand,
+            peer_params
         ));
 
         select(&mut broadcast, &mut respond).coalesce().await
@@ -191,7 +199,7 @@ impl<'a> MdnsImpl<'a> {
     }
 
     #[allow(clippy::too_many_arguments)]
-    async fn respond<S, R, SB, RB>(
+    async fn respond<S, R, SB, RB, P>(
         &self,
         send: &Mutex<impl RawMutex, S>,
         mut recv: R,
@@ -201,12 +209,14 @@ impl<'a> MdnsImpl<'a> {
         ipv4_interface: Option<Ipv4Addr>,
         ipv6_interface: Option<u32>,
         rand: Rand,
+        mut peer_params: P,
     ) -> Result<(), Error>
     where
         S: NetworkSend,
         R: NetworkReceive,
         SB: BufferAccess<[u8]>,
         RB: BufferAccess<[u8]>,
+        P: FnMut(&str, MrpParameters),
     {
         loop {
             recv.wait_available().await?;
@@ -215,6 +225,13 @@ impl<'a> MdnsImpl<'a> {
                 let mut rx = rx_buf.get().await.ok_or(ErrorCode::NoSpace)?;
                 let (len, addr) = recv.recv_from(&mut rx).await?;
 
+                if let Err(err) = proto::peer_mrp_params(&rx[..len], &mut peer_params) {
+                    debug!(
+                        "mDNS protocol error {} while inspecting a packet from {}",
+                        err, addr
+                    );
+                }
+
                 let mut tx = tx_buf.get().await.ok_or(ErrorCode::NoSpace)?;
                 let mut send = send.lock().await;
 
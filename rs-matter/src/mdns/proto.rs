@@ -13,6 +13,7 @@ use domain::dep::octseq::{OctetsBuilder, ShortBuf};
 use domain::rdata::{Aaaa, Ptr, Srv, Txt, A};
 
 use crate::error::{Error, ErrorCode};
+use crate::transport::mrp::MrpParameters;
 use crate::utils::bitflags::bitflags;
 
 use super::Service;
@@ -96,6 +97,58 @@ bitflags! {
     }
 }
 
+/// Call `f` with the instance name and the MRP parameters advertised in the TXT record
+/// of each operational (`_matter._tcp`) service found in an mDNS response
+///
+/// Both the answers and the additional data of the response are inspected.
+/// Queries, as well as TXT records of other services are ignored.
+pub fn peer_mrp_params<F>(data: &[u8], mut f: F) -> Result<(), Error>
+where
+    F: FnMut(&str, MrpParameters),
+{
+    let message = Message::from_octets(data)?;
+
+    if !message.header().qr() {
+        return Ok(());
+    }
+
+    for section in [message.answer()?, message.additional()?] {
+        for record in section.limit_to::<Txt<_>>() {
+            let record = record?;
+
+            let mut labels = record.owner().iter();
+
+            let (Some(instance), Some(service), Some(protocol)) =
+                (labels.next(), labels.next(), labels.next())
+            else {
+                continue;
+            };
+
+            if !service.as_slice().eq_ignore_ascii_case(b"_matter")
+                || !protocol.as_slice().eq_ignore_ascii_case(b"_tcp")
+            {
+                continue;
+            }
+
+            let Ok(instance) = core::str::from_utf8(instance.as_slice()) else {
+                continue;
+            };
+
+            let params = MrpParameters::from_txt_kvs(
+                record
+                    .data()
+                    .iter()
+                    .filter_map(|kv| core::str::from_utf8(kv).ok())
+                    .filter_map(|kv| kv.split_once('=')),
+            );
+
+            f(instance, params);
+        }
+    }
+
+    Ok(())
+}
+
 pub struct Host<'a> {
     pub id: u16,
     pub hostname: &'a str,
@@ -750,7 +803,7 @@ mod tests {
     use crate::error::Error;
     use crate::mdns::Service;
 
-    use super::{Buf, Host, Services};
+    use super::{peer_mrp_params, Buf, Host, MrpParameters, Services};
 
     static TEST_HOST_ONLY: TestRun = TestRun {
         host: Host {
@@ -930,6 +983,74 @@ mod tests {
         TEST_SERVICES.run();
     }
 
+    #[test]
+    fn test_peer_mrp_params() {
+        let host = Host {
+            id: 0,
+            hostname: "foo",
+            ip: Ipv4Addr::new(192, 168, 0, 1),
+            ipv6: Ipv6Addr::UNSPECIFIED,
+        };
+
+        let services: &[Service] = &[
+            Service {
+                name: "87E1B004E235A130-8FC7772401CD0696",
+                service: "_matter",
+                protocol: "_tcp",
+                port: 5540,
+                service_subtypes: &[],
+                txt_kvs: &[("SII", "5300"), ("SAI", "320"), ("SAT", "bad")],
+            },
+            Service {
+                name: "bar",
+                service: "_matterc",
+                protocol: "_udp",
+                port: 5540,
+                service_subtypes: &[],
+                txt_kvs: &[("SII", "1000")],
+            },
+        ];
+
+        let mut buf1 = [0; 1500];
+        let mut buf2 = [0; 1500];
+
+        let query = Question::prep(
+            &mut buf1,
+            host.id,
+            &[
+                Question {
+                    name: "87E1B004E235A130-8FC7772401CD0696._matter._tcp.local",
+                    qtype: Rtype::TXT,
+                },
+                Question {
+                    name: "bar._matterc._udp.local",
+                    qtype: Rtype::TXT,
+                },
+            ],
+        );
+
+        // Queries are ignored
+        unwrap!(peer_mrp_params(query, |_, _| panic!()));
+
+        let (len, _) = unwrap!(host.respond(services, query, &mut buf2, 0));
+
+        let mut found = 0;
+
+        unwrap!(peer_mrp_params(&buf2[..len], |instance, params| {
+            assert_eq!(instance, "87E1B004E235A130-8FC7772401CD0696");
+            assert_eq!(params.idle_interval_ms(), 5300);
+            assert_eq!(params.active_interval_ms(), 320);
+            assert_eq!(
+                params.active_threshold_ms(),
+                MrpParameters::new().active_threshold_ms()
+            );
+
+            found += 1;
+        }));
+
+        assert_eq!(found, 1);
+    }
+
     struct TestRun<'a> {
         host: Host<'a>,
         services: &'a [Service<'a>],
@@ -28,14 +28,33 @@ pub mod fileio {
     use embassy_futures::select::{select, Either};
     use embassy_sync::blocking_mutex::raw::{NoopRawMutex, RawMutex};
 
+    use crate::data_model::bridge::Bridge;
+    use crate::data_model::color_control::ColorControlHandler;
+    use crate::data_model::door_lock::DoorLockHandler;
+    use crate::data_model::level_control::LevelControlHandler;
     use crate::data_model::networks::wireless::{Wifi, WirelessNetwork, WirelessNetworks};
+    use crate::data_model::on_off::OnOffHandler;
+    use crate::data_model::scenes::ScenesHandler;
+    use crate::data_model::thermostat::ThermostatHandler;
+    use crate::data_model::thermostat_ui_config::ThermostatUiConfigHandler;
+    use crate::data_model::window_covering::WindowCoveringHandler;
     use crate::error::{Error, ErrorCode};
     use crate::utils::init::{init, Init};
     use crate::Matter;
 
     const KEY_FABRICS: &str = "fabrics";
     const KEY_BASIC_INFO: &str = "basic_info";
+    const KEY_COUNTERS: &str = "counters";
     const KEY_WIRELESS_NETWORKS: &str = "wireless_networks";
+    const KEY_BRIDGE: &str = "bridge";
+    const KEY_ON_OFF: &str = "on_off";
+    const KEY_LEVEL_CONTROL: &str = "level_control";
+    const KEY_COLOR_CONTROL: &str = "color_control";
+    const KEY_SCENES: &str = "scenes";
+    const KEY_DOOR_LOCK: &str = "door_lock";
+    const KEY_THERMOSTAT: &str = "thermostat";
+    const KEY_THERMOSTAT_UI_CONFIG: &str = "thermostat_ui_config";
+    const KEY_WINDOW_COVERING: &str = "window_covering";
 
     pub struct Psm<const N: usize = 4096> {
         buf: MaybeUninit<[u8; N]>,
@@ -76,11 +95,18 @@ pub mod fileio {
                 matter.load_basic_info(data)?;
             }
 
+            if let Some(data) =
+                Self::load_key(dir, KEY_COUNTERS, unsafe { self.buf.assume_init_mut() })?
+            {
+                matter.load_counters(data)?;
+            }
+
             Ok(())
         }
 
         pub fn store(&mut self, dir: &Path, matter: &Matter) -> Result<(), Error> {
-            if matter.fabrics_changed() || matter.basic_info_changed() {
+            if matter.fabrics_changed() || matter.basic_info_changed() || matter.counters_changed()
+            {
                 fs::create_dir_all(dir)?;
             }
 
@@ -98,6 +124,13 @@ pub mod fileio {
                 }
             }
 
+            if matter.counters_changed() {
+                if let Some(data) = matter.store_counters(unsafe { self.buf.assume_init_mut() })? {
+                    Self::store_key(dir, KEY_COUNTERS, data)?;
+                    matter.counters_stored();
+                }
+            }
+
             Ok(())
         }
 
@@ -141,6 +174,286 @@ pub mod fileio {
             Ok(())
         }
 
+        pub fn load_bridge<const B: usize, M, H>(
+            &mut self,
+            dir: &Path,
+            bridge: &Bridge<'_, B, M, H>,
+        ) -> Result<(), Error>
+        where
+            M: RawMutex,
+        {
+            fs::create_dir_all(dir)?;
+
+            if let Some(data) =
+                Self::load_key(dir, KEY_BRIDGE, unsafe { self.buf.assume_init_mut() })?
+            {
+                bridge.load(data)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn store_bridge<const B: usize, M, H>(
+            &mut self,
+            dir: &Path,
+            bridge: &Bridge<'_, B, M, H>,
+        ) -> Result<(), Error>
+        where
+            M: RawMutex,
+        {
+            if bridge.changed() {
+                fs::create_dir_all(dir)?;
+
+                if let Some(data) = bridge.store(unsafe { self.buf.assume_init_mut() })? {
+                    Self::store_key(dir, KEY_BRIDGE, data)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn load_on_off(&mut self, dir: &Path, on_off: &OnOffHandler) -> Result<(), Error> {
+            fs::create_dir_all(dir)?;
+
+            if let Some(data) =
+                Self::load_key(dir, KEY_ON_OFF, unsafe { self.buf.assume_init_mut() })?
+            {
+                on_off.load(data)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn store_on_off(&mut self, dir: &Path, on_off: &OnOffHandler) -> Result<(), Error> {
+            if on_off.changed() {
+                fs::create_dir_all(dir)?;
+
+                if let Some(data) = on_off.store(unsafe { self.buf.assume_init_mut() })? {
+                    Self::store_key(dir, KEY_ON_OFF, data)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn load_level_control(
+            &mut self,
+            dir: &Path,
+            level_control: &LevelControlHandler,
+        ) -> Result<(), Error> {
+            fs::create_dir_all(dir)?;
+
+            if let Some(data) = Self::load_key(dir, KEY_LEVEL_CONTROL, unsafe {
+                self.buf.assume_init_mut()
+            })? {
+                level_control.load(data)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn store_level_control(
+            &mut self,
+            dir: &Path,
+            level_control: &LevelControlHandler,
+        ) -> Result<(), Error> {
+            if level_control.changed() {
+                fs::create_dir_all(dir)?;
+
+                if let Some(data) = level_control.store(unsafe { self.buf.assume_init_mut() })? {
+                    Self::store_key(dir, KEY_LEVEL_CONTROL, data)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn load_color_control(
+            &mut self,
+            dir: &Path,
+            color_control: &ColorControlHandler,
+        ) -> Result<(), Error> {
+            fs::create_dir_all(dir)?;
+
+            if let Some(data) = Self::load_key(dir, KEY_COLOR_CONTROL, unsafe {
+                self.buf.assume_init_mut()
+            })? {
+                color_control.load(data)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn store_color_control(
+            &mut self,
+            dir: &Path,
+            color_control: &ColorControlHandler,
+        ) -> Result<(), Error> {
+            if color_control.changed() {
+                fs::create_dir_all(dir)?;
+
+                if let Some(data) = color_control.store(unsafe { self.buf.assume_init_mut() })? {
+                    Self::store_key(dir, KEY_COLOR_CONTROL, data)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn load_scenes(&mut self, dir: &Path, scenes: &ScenesHandler) -> Result<(), Error> {
+            fs::create_dir_all(dir)?;
+
+            if let Some(data) =
+                Self::load_key(dir, KEY_SCENES, unsafe { self.buf.assume_init_mut() })?
+            {
+                scenes.load(data)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn store_scenes(&mut self, dir: &Path, scenes: &ScenesHandler) -> Result<(), Error> {
+            if scenes.changed() {
+                fs::create_dir_all(dir)?;
+
+                if let Some(data) = scenes.store(unsafe { self.buf.assume_init_mut() })? {
+                    Self::store_key(dir, KEY_SCENES, data)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn load_door_lock(
+            &mut self,
+            dir: &Path,
+            door_lock: &DoorLockHandler,
+        ) -> Result<(), Error> {
+            fs::create_dir_all(dir)?;
+
+            if let Some(data) =
+                Self::load_key(dir, KEY_DOOR_LOCK, unsafe { self.buf.assume_init_mut() })?
+            {
+                door_lock.load(data)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn store_door_lock(
+            &mut self,
+            dir: &Path,
+            door_lock: &DoorLockHandler,
+        ) -> Result<(), Error> {
+            if door_lock.changed() {
+                fs::create_dir_all(dir)?;
+
+                if let Some(data) = door_lock.store(unsafe { self.buf.assume_init_mut() })? {
+                    Self::store_key(dir, KEY_DOOR_LOCK, data)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn load_thermostat(
+            &mut self,
+            dir: &Path,
+            thermostat: &ThermostatHandler,
+        ) -> Result<(), Error> {
+            fs::create_dir_all(dir)?;
+
+            if let Some(data) =
+                Self::load_key(dir, KEY_THERMOSTAT, unsafe { self.buf.assume_init_mut() })?
+            {
+                thermostat.load(data)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn store_thermostat(
+            &mut self,
+            dir: &Path,
+            thermostat: &ThermostatHandler,
+        ) -> Result<(), Error> {
+            if thermostat.changed() {
+                fs::create_dir_all(dir)?;
+
+                if let Some(data) = thermostat.store(unsafe { self.buf.assume_init_mut() })? {
+                    Self::store_key(dir, KEY_THERMOSTAT, data)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn load_thermostat_ui_config(
+            &mut self,
+            dir: &Path,
+            thermostat_ui_config: &ThermostatUiConfigHandler,
+        ) -> Result<(), Error> {
+            fs::create_dir_all(dir)?;
+
+            if let Some(data) = Self::load_key(dir, KEY_THERMOSTAT_UI_CONFIG, unsafe {
+                self.buf.assume_init_mut()
+            })? {
+                thermostat_ui_config.load(data)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn store_thermostat_ui_config(
+            &mut self,
+            dir: &Path,
+            thermostat_ui_config: &ThermostatUiConfigHandler,
+        ) -> Result<(), Error> {
+            if thermostat_ui_config.changed() {
+                fs::create_dir_all(dir)?;
+
+                if let Some(data) =
+                    thermostat_ui_config.store(unsafe { self.buf.assume_init_mut() })?
+                {
+                    Self::store_key(dir, KEY_THERMOSTAT_UI_CONFIG, data)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn load_window_covering(
+            &mut self,
+            dir: &Path,
+            window_covering: &WindowCoveringHandler,
+        ) -> Result<(), Error> {
+            fs::create_dir_all(dir)?;
+
+            if let Some(data) = Self::load_key(dir, KEY_WINDOW_COVERING, unsafe {
+                self.buf.assume_init_mut()
+            })? {
+                window_covering.load(data)?;
+            }
+
+            Ok(())
+        }
+
+        pub fn store_window_covering(
+            &mut self,
+            dir: &Path,
+            window_covering: &WindowCoveringHandler,
+        ) -> Result<(), Error> {
+            if window_covering.changed() {
+                fs::create_dir_all(dir)?;
+
+                if let Some(data) = window_covering.store(unsafe { self.buf.assume_init_mut() })? {
+                    Self::store_key(dir, KEY_WINDOW_COVERING, data)?;
+                }
+            }
+
+            Ok(())
+        }
+
         pub async fn run<P: AsRef<Path>>(
             &mut self,
             dir: P,
@@ -194,6 +507,48 @@ pub mod fileio {
             }
         }
 
+        /// Same as `Psm::run`, but also persists the endpoint IDs allocated to the bridged devices of the provided bridge.
+        ///
+        /// The bridge should be loaded with `Psm::load_bridge` before calling this method.
+        pub async fn run_with_bridge<P: AsRef<Path>, const B: usize, M, H>(
+            &mut self,
+            dir: P,
+            matter: &Matter<'_>,
+            bridge: &Bridge<'_, B, M, H>,
+        ) -> Result<(), Error>
+        where
+            M: RawMutex,
+        {
+            let dir = dir.as_ref();
+
+            loop {
+                match select(matter.wait_persist(), bridge.wait_persist()).await {
+                    Either::First(_) => self.store(dir, matter)?,
+                    Either::Second(_) => self.store_bridge(dir, bridge)?,
+                }
+            }
+        }
+
+        /// Same as `Psm::run`, but also persists the state of the provided On/Off cluster handler,
+        /// as needed by its `StartUpOnOff` behavior.
+        ///
+        /// The handler should be loaded with `Psm::load_on_off` before calling this method.
+        pub async fn run_with_on_off<P: AsRef<Path>>(
+            &mut self,
+            dir: P,
+            matter: &Matter<'_>,
+            on_off: &OnOffHandler<'_>,
+        ) -> Result<(), Error> {
+            let dir = dir.as_ref();
+
+            loop {
+                match select(matter.wait_persist(), on_off.wait_persist()).await {
+                    Either::First(_) => self.store(dir, matter)?,
+                    Either::Second(_) => self.store_on_off(dir, on_off)?,
+                }
+            }
+        }
+
         fn load_key<'b>(
             dir: &Path,
             key: &str,
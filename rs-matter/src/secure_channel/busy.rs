@@ -36,6 +36,8 @@ use super::common::{sc_write, OpCode, SCStatusCodes, PROTO_ID_SECURE_CHANNEL};
 ///
 /// ... to respond with "I'm busy, please try later" status code to all incoming Secure Channel messages, which were
 /// not accepted in time by the actual Secure Channel responder, due to all its handlers being occupied with work.
+///
+/// The wait time reported to the peer grows with the number of session establishments currently in progress.
 pub struct BusySecureChannel(());
 
 impl Default for BusySecureChannel {
@@ -46,6 +48,8 @@ impl Default for BusySecureChannel {
 
 impl BusySecureChannel {
     const BUSY_RETRY_DELAY_MS: u16 = 500;
+    const BUSY_RETRY_DELAY_PER_ESTABLISHMENT_MS: u16 = 1000;
+    const BUSY_RETRY_MAX_DELAY_MS: u16 = 10000;
 
     #[inline(always)]
     pub const fn new() -> Self {
@@ -60,13 +64,19 @@ impl BusySecureChannel {
 
         match meta.opcode()? {
             OpCode::PBKDFParamRequest | OpCode::CASESigma1 => {
+                let pending = exchange
+                    .matter()
+                    .transport_mgr
+                    .session_mgr
+                    .borrow()
+                    .pending_establishments();
+
+                // Do not count the session establishment of the peer itself
+                let delay_ms = Self::retry_delay_ms(pending.saturating_sub(1));
+
                 exchange
                     .send_with(|_, wb| {
-                        sc_write(
-                            wb,
-                            SCStatusCodes::Busy,
-                            &u16::to_le_bytes(Self::BUSY_RETRY_DELAY_MS),
-                        )
+                        sc_write(wb, SCStatusCodes::Busy, &u16::to_le_bytes(delay_ms))
                     })
                     .await
             }
@@ -76,6 +86,15 @@ impl BusySecureChannel {
             }
         }
     }
+
+    /// Estimate how long the peer should wait before retrying, based on the number of
+    /// other session establishments currently in progress.
+    fn retry_delay_ms(pending: usize) -> u16 {
+        Self::BUSY_RETRY_DELAY_PER_ESTABLISHMENT_MS
+            .saturating_mul(pending.min(u16::MAX as usize) as u16)
+            .saturating_add(Self::BUSY_RETRY_DELAY_MS)
+            .min(Self::BUSY_RETRY_MAX_DELAY_MS)
+    }
 }
 
 impl ExchangeHandler for BusySecureChannel {
@@ -83,3 +102,18 @@ impl ExchangeHandler for BusySecureChannel {
         BusySecureChannel::handle(self, exchange).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::BusySecureChannel;
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(BusySecureChannel::retry_delay_ms(0), 500);
+        assert_eq!(BusySecureChannel::retry_delay_ms(1), 1500);
+        assert_eq!(BusySecureChannel::retry_delay_ms(3), 3500);
+        // Capped at the maximum delay
+        assert_eq!(BusySecureChannel::retry_delay_ms(10), 10000);
+        assert_eq!(BusySecureChannel::retry_delay_ms(usize::MAX), 10000);
+    }
+}
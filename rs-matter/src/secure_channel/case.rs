@@ -24,9 +24,10 @@ use crate::{
     error::{Error, ErrorCode},
     fabric::Fabric,
     secure_channel::common::{complete_with_status, sc_write, OpCode, SCStatusCodes},
-    tlv::{get_root_node_struct, FromTLV, OctetStr, TLVElement, TLVTag, TLVWrite},
+    tlv::{get_root_node_struct, FromTLV, OctetStr, TLVElement, TLVTag, TLVWrite, ToTLV},
     transport::{
         exchange::Exchange,
+        mrp::MrpParameters,
         session::{NocCatIds, ReservedSession, SessionMode},
     },
     utils::{
@@ -179,7 +180,9 @@ impl Case {
                         session_keys,
                     )?;
 
-                    let peer_addr = exchange.with_session(|sess| Ok(sess.get_peer_addr()))?;
+                    let (peer_addr, peer_mrp_params) = exchange.with_session(|sess| {
+                        Ok((sess.get_peer_addr(), sess.get_peer_mrp_params().clone()))
+                    })?;
 
                     session.update(
                         fabric.node_id(),
@@ -195,6 +198,7 @@ impl Case {
                         Some(&session_keys[0..16]),
                         Some(&session_keys[16..32]),
                         Some(&session_keys[32..48]),
+                        &peer_mrp_params,
                     )?;
 
                     // Complete the reserved session and thus make the `Session` instance
@@ -226,6 +230,14 @@ impl Case {
         let root = get_root_node_struct(exchange.rx()?.payload())?;
         let r = Sigma1Req::from_tlv(&root)?;
 
+        if let Some(session_params) = &r.session_params {
+            // Retransmit the handshake messages with the cadence requested by the peer
+            exchange.with_session(|sess| {
+                sess.set_peer_mrp_params(session_params.clone());
+                Ok(())
+            })?;
+        }
+
         let local_fabric_idx = exchange
             .matter()
             .fabric_mgr
@@ -319,6 +331,8 @@ impl Case {
                         buf,
                     )
                 })?;
+                MrpParameters::local(exchange.matter().dev_det())
+                    .to_tlv(&TLVTag::Context(5), &mut *tw)?;
                 tw.end_container()?;
 
                 if !hash_updated {
@@ -577,6 +591,7 @@ struct Sigma1Req<'a> {
     initiator_sessid: u16,
     dest_id: OctetStr<'a>,
     peer_pub_key: OctetStr<'a>,
+    session_params: Option<MrpParameters>,
 }
 
 #[derive(FromTLV, Debug)]
@@ -27,6 +27,7 @@ use crate::{
 
 use super::{
     case::{Case, CaseSession},
+    mcsp::Mcsp,
     spake2p::Spake2P,
 };
 
@@ -62,6 +63,7 @@ impl SecureChannel {
                 let case_session = case_session.init_with(CaseSession::init());
                 Case::new().handle(exchange, case_session).await
             }
+            OpCode::MsgCounterSyncReq => Mcsp::new().handle(exchange).await,
             opcode => {
                 error!("Invalid opcode: {:?}", opcode);
                 Err(ErrorCode::InvalidOpcode.into())
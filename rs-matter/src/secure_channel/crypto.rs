@@ -25,3 +25,207 @@ pub use super::crypto_mbedtls::CryptoSpake2;
 pub use super::crypto_openssl::CryptoSpake2;
 #[cfg(feature = "rustcrypto")]
 pub use super::crypto_rustcrypto::CryptoSpake2;
+
+use crate::crypto::{self, AEAD_MIC_LEN_BYTES, AEAD_NONCE_LEN_BYTES, SYMM_KEY_LEN_BYTES};
+use crate::error::{Error, ErrorCode};
+
+/// The info string used for deriving the privacy key from an encryption key
+const PRIVACY_KEY_INFO: &[u8] = b"PrivacyKey";
+
+/// The offset in the MIC of a message, where the part of the MIC used in the privacy nonce starts
+const PRIVACY_NONCE_MIC_OFFSET: usize = 5;
+
+/// The maximum length of the privacy-protected data of a message header,
+/// i.e. the message counter, the source node ID and the destination node ID
+pub const PRIVACY_MAX_DATA_LEN_BYTES: usize = 4 + 8 + 8;
+
+/// Derive the privacy key from an encryption key.
+///
+/// The encryption key is either the encryption key of a unicast session
+/// (for the direction the privacy key is used in), or an operational group key.
+///
+/// # Arguments
+/// - `enc_key`: The encryption key
+/// - `privacy_key`: The buffer where the derived privacy key is stored
+pub fn privacy_key(
+    enc_key: &[u8],
+    privacy_key: &mut [u8; SYMM_KEY_LEN_BYTES],
+) -> Result<(), Error> {
+    crypto::hkdf_sha256(&[], enc_key, PRIVACY_KEY_INFO, privacy_key)
+}
+
+/// Encrypt or decrypt - in place - the privacy-protected data of a message header.
+///
+/// Encryption and decryption are the same operation, as the data is encrypted with AES-CTR
+/// using a nonce composed of the session ID and a part of the MIC of the message.
+///
+/// # Arguments
+/// - `privacy_key`: The privacy key, as derived by `privacy_key`
+/// - `sess_id`: The session ID of the message
+/// - `mic`: The MIC of the message
+/// - `data`: The privacy-protected data to be encrypted or decrypted
+pub fn privacy_crypt_in_place(
+    privacy_key: &[u8],
+    sess_id: u16,
+    mic: &[u8],
+    data: &mut [u8],
+) -> Result<(), Error> {
+    if mic.len() != AEAD_MIC_LEN_BYTES || data.len() > PRIVACY_MAX_DATA_LEN_BYTES {
+        Err(ErrorCode::Invalid)?;
+    }
+
+    let mut nonce = [0; AEAD_NONCE_LEN_BYTES];
+    nonce[..2].copy_from_slice(&sess_id.to_be_bytes());
+    nonce[2..].copy_from_slice(&mic[PRIVACY_NONCE_MIC_OFFSET..]);
+
+    // AES-CCM encrypts its payload with AES-CTR, so AES-CTR is emulated
+    // with AES-CCM and an empty AAD, by discarding the resulting MIC
+    let mut buf = [0; PRIVACY_MAX_DATA_LEN_BYTES + AEAD_MIC_LEN_BYTES];
+    let buf = &mut buf[..data.len() + AEAD_MIC_LEN_BYTES];
+    buf[..data.len()].copy_from_slice(data);
+
+    crypto::encrypt_in_place(privacy_key, &nonce, &[], buf, data.len())?;
+
+    data.copy_from_slice(&buf[..data.len()]);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    //! The test vectors are computed independently with the OpenSSL 3 command line tool, as in:
+    //!
+    //! ```sh
+    //! # The privacy key
+    //! openssl kdf -keylen 16 -kdfopt digest:SHA256 -kdfopt hexkey:<key> -kdfopt info:PrivacyKey HKDF
+    //! # The Group Session ID of an operational group key
+    //! openssl kdf -keylen 2 -kdfopt digest:SHA256 -kdfopt hexkey:<key> -kdfopt info:GroupKeyHash HKDF
+    //! # The encrypted data; the AES-CTR counter block of AES-CCM is 0x01 || <sess id> || <mic[5..]> || 0x0001
+    //! openssl enc -aes-128-ctr -K <privacy key> -iv 01<sess id><mic[5..]>0001
+    //! ```
+
+    use super::{privacy_crypt_in_place, privacy_key};
+
+    /// The encryption key of a unicast session
+    const ENC_KEY: [u8; 16] = [
+        0x5e, 0xde, 0xd2, 0x44, 0xe5, 0x53, 0x2b, 0x3c, 0xdc, 0x23, 0x40, 0x9d, 0xba, 0xd0, 0x52,
+        0xd2,
+    ];
+
+    const PRIVACY_KEY: [u8; 16] = [
+        0x63, 0xd2, 0xde, 0x30, 0x88, 0x99, 0xe2, 0x4d, 0xb0, 0x53, 0xb0, 0x23, 0x6d, 0xf0, 0x77,
+        0x19,
+    ];
+
+    const SESS_ID: u16 = 0x002a;
+
+    const MIC: [u8; 16] = [
+        0xc5, 0xa0, 0x06, 0x3a, 0xd5, 0xd2, 0x51, 0x64, 0x91, 0xb4, 0xa2, 0xf4, 0xd1, 0xa3, 0xef,
+        0x3e,
+    ];
+
+    // Message counter, source node ID and destination node ID
+    const DATA: [u8; 20] = [
+        0x39, 0x30, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x11, 0x12, 0x13,
+        0x14, 0x15, 0x16, 0x17, 0x18,
+    ];
+
+    const ENCRYPTED_DATA: [u8; 20] = [
+        0xbd, 0x5e, 0xc6, 0xc9, 0x17, 0x6c, 0xa6, 0x70, 0xb0, 0x50, 0xe2, 0x02, 0xbd, 0xe8, 0x83,
+        0x94, 0x39, 0xe5, 0x49, 0xaf,
+    ];
+
+    /// An operational group key
+    const GROUP_OP_KEY: [u8; 16] = [
+        0xa6, 0xf5, 0x30, 0x6b, 0xaf, 0x6d, 0x05, 0x0a, 0xf2, 0x3b, 0xa4, 0xbd, 0x6b, 0x9d, 0xd9,
+        0x60,
+    ];
+
+    const GROUP_PRIVACY_KEY: [u8; 16] = [
+        0x01, 0xf8, 0xd1, 0x92, 0x71, 0x26, 0xf1, 0x94, 0x08, 0x25, 0x72, 0xd4, 0x9b, 0x1f, 0xdc,
+        0x73,
+    ];
+
+    /// The Group Session ID derived from `GROUP_OP_KEY`
+    const GROUP_SESS_ID: u16 = 0xb9f7;
+
+    const GROUP_MIC: [u8; 16] = [
+        0x1b, 0x7a, 0x4c, 0x6e, 0x02, 0xd8, 0xf3, 0xa5, 0xc9, 0xe1, 0xb0, 0x4d, 0x7f, 0x32, 0x68,
+        0xa1,
+    ];
+
+    // Message counter, source node ID and destination group ID
+    const GROUP_DATA: [u8; 14] = [
+        0x3a, 0x30, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x01, 0x01,
+    ];
+
+    const GROUP_ENCRYPTED_DATA: [u8; 14] = [
+        0xe0, 0x87, 0x18, 0x00, 0x23, 0x9d, 0xdf, 0x81, 0xd3, 0x3a, 0x07, 0xdd, 0x9b, 0xa8,
+    ];
+
+    #[test]
+    fn test_privacy_key() {
+        let mut key = [0; 16];
+        unwrap!(privacy_key(&ENC_KEY, &mut key));
+
+        assert_eq!(key, PRIVACY_KEY);
+
+        unwrap!(privacy_key(&GROUP_OP_KEY, &mut key));
+
+        assert_eq!(key, GROUP_PRIVACY_KEY);
+    }
+
+    #[test]
+    fn test_privacy_crypt() {
+        let mut data = DATA;
+
+        unwrap!(privacy_crypt_in_place(
+            &PRIVACY_KEY,
+            SESS_ID,
+            &MIC,
+            &mut data
+        ));
+        assert_eq!(data, ENCRYPTED_DATA);
+
+        unwrap!(privacy_crypt_in_place(
+            &PRIVACY_KEY,
+            SESS_ID,
+            &MIC,
+            &mut data
+        ));
+        assert_eq!(data, DATA);
+
+        // Only the message counter is protected when there are no node IDs in the header
+        let mut data = [0; 4];
+        data.copy_from_slice(&DATA[..4]);
+
+        unwrap!(privacy_crypt_in_place(
+            &PRIVACY_KEY,
+            SESS_ID,
+            &MIC,
+            &mut data
+        ));
+        assert_eq!(data, ENCRYPTED_DATA[..4]);
+    }
+
+    #[test]
+    fn test_group_privacy_crypt() {
+        let mut data = GROUP_DATA;
+
+        unwrap!(privacy_crypt_in_place(
+            &GROUP_PRIVACY_KEY,
+            GROUP_SESS_ID,
+            &GROUP_MIC,
+            &mut data
+        ));
+        assert_eq!(data, GROUP_ENCRYPTED_DATA);
+
+        unwrap!(privacy_crypt_in_place(
+            &GROUP_PRIVACY_KEY,
+            GROUP_SESS_ID,
+            &GROUP_MIC,
+            &mut data
+        ));
+        assert_eq!(data, GROUP_DATA);
+    }
+}
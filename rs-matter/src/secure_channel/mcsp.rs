@@ -0,0 +1,227 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Message Counter Synchronization Protocol (MCSP)
+//!
+//! Used to synchronize the Global Group Encrypted Control Message Counter of a peer,
+//! before accepting group control messages from it.
+//!
+//! The synchronization is triggered by `TransportMgr::decode_group_message` when a group control message
+//! is received from a peer whose group control message counter is not known yet.
+//!
+//! NOTE: As group sessions are not supported by the exchange layer yet, the MCSP messages are
+//! exchanged over an existing secure unicast session with the peer.
+
+use core::num::NonZeroU8;
+
+use crate::error::{Error, ErrorCode};
+use crate::transport::counters::MCSP_CHALLENGE_LEN;
+use crate::transport::exchange::Exchange;
+use crate::utils::storage::WriteBuf;
+
+use super::common::{OpCode, PROTO_ID_SECURE_CHANNEL};
+
+/// The payload of a `MsgCounterSyncReq` message
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MsgCounterSyncReq {
+    pub challenge: [u8; MCSP_CHALLENGE_LEN],
+}
+
+impl MsgCounterSyncReq {
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        let challenge = data
+            .get(..MCSP_CHALLENGE_LEN)
+            .ok_or(ErrorCode::InvalidData)?;
+
+        Ok(Self {
+            challenge: unwrap!(challenge.try_into()),
+        })
+    }
+
+    pub fn write(&self, wb: &mut WriteBuf) -> Result<(), Error> {
+        wb.append(&self.challenge)
+    }
+}
+
+/// The payload of a `MsgCounterSyncResp` message
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MsgCounterSyncResp {
+    pub synchronized_ctr: u32,
+    pub response: [u8; MCSP_CHALLENGE_LEN],
+}
+
+impl MsgCounterSyncResp {
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 4 + MCSP_CHALLENGE_LEN {
+            Err(ErrorCode::InvalidData)?;
+        }
+
+        Ok(Self {
+            synchronized_ctr: u32::from_le_bytes(unwrap!(data[..4].try_into())),
+            response: unwrap!(data[4..4 + MCSP_CHALLENGE_LEN].try_into()),
+        })
+    }
+
+    pub fn write(&self, wb: &mut WriteBuf) -> Result<(), Error> {
+        wb.le_u32(self.synchronized_ctr)?;
+        wb.append(&self.response)
+    }
+}
+
+/// The Message Counter Synchronization Protocol
+pub struct Mcsp(());
+
+impl Default for Mcsp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mcsp {
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self(())
+    }
+
+    /// Handle an incoming `MsgCounterSyncReq` message by responding with
+    /// the current value of our Global Group Encrypted Control Message Counter.
+    pub async fn handle(&self, exchange: &mut Exchange<'_>) -> Result<(), Error> {
+        let req = {
+            let rx = exchange.rx()?;
+            let meta = rx.meta();
+
+            if meta.proto_id != PROTO_ID_SECURE_CHANNEL
+                || meta.opcode::<OpCode>()? != OpCode::MsgCounterSyncReq
+            {
+                Err(ErrorCode::InvalidOpcode)?;
+            }
+
+            MsgCounterSyncReq::parse(rx.payload())?
+        };
+
+        let resp = MsgCounterSyncResp {
+            synchronized_ctr: exchange
+                .matter()
+                .transport_mgr
+                .counters
+                .borrow_mut()
+                .group_control(),
+            response: req.challenge,
+        };
+
+        exchange
+            .send_with(|_, wb| {
+                resp.write(wb)?;
+
+                Ok(Some(OpCode::MsgCounterSyncResp.into()))
+            })
+            .await
+    }
+
+    /// Synchronize the Global Group Encrypted Control Message Counter of the peer of the provided
+    /// initiator exchange, and record it in the group peer counters' table.
+    ///
+    /// Return the synchronized counter value.
+    pub async fn sync(&self, exchange: &mut Exchange<'_>) -> Result<u32, Error> {
+        let (fabric_idx, peer_node_id) = exchange.with_session(|sess| {
+            let fabric_idx =
+                NonZeroU8::new(sess.get_local_fabric_idx()).ok_or(ErrorCode::NoSession)?;
+            let peer_node_id = sess.get_peer_node_id().ok_or(ErrorCode::NoSession)?;
+
+            Ok((fabric_idx, peer_node_id))
+        })?;
+
+        let matter = exchange.matter();
+
+        let req = MsgCounterSyncReq {
+            challenge: matter.transport_mgr.group_peers.borrow_mut().start_sync(
+                fabric_idx,
+                peer_node_id,
+                matter.rand(),
+            ),
+        };
+
+        exchange
+            .send_with(|_, wb| {
+                req.write(wb)?;
+
+                Ok(Some(OpCode::MsgCounterSyncReq.into()))
+            })
+            .await?;
+
+        let resp = {
+            let rx = exchange.recv().await?;
+            let meta = rx.meta();
+
+            if meta.proto_id != PROTO_ID_SECURE_CHANNEL
+                || meta.opcode::<OpCode>()? != OpCode::MsgCounterSyncResp
+            {
+                Err(ErrorCode::InvalidOpcode)?;
+            }
+
+            MsgCounterSyncResp::parse(rx.payload())?
+        };
+
+        exchange.acknowledge().await?;
+
+        matter
+            .transport_mgr
+            .group_peers
+            .borrow_mut()
+            .complete_sync(
+                fabric_idx,
+                peer_node_id,
+                resp.synchronized_ctr,
+                &resp.response,
+            )?;
+
+        Ok(resp.synchronized_ctr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::storage::WriteBuf;
+
+    use super::{MsgCounterSyncReq, MsgCounterSyncResp};
+
+    #[test]
+    fn test_mcsp_codec() {
+        let mut buf = [0; 16];
+
+        let req = MsgCounterSyncReq {
+            challenge: [1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        let mut wb = WriteBuf::new(&mut buf);
+        req.write(&mut wb).unwrap();
+        assert_eq!(wb.as_slice(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(MsgCounterSyncReq::parse(wb.as_slice()).unwrap(), req);
+        assert!(MsgCounterSyncReq::parse(&[1, 2, 3]).is_err());
+
+        let resp = MsgCounterSyncResp {
+            synchronized_ctr: 0x01020304,
+            response: [1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        let mut wb = WriteBuf::new(&mut buf);
+        resp.write(&mut wb).unwrap();
+        assert_eq!(wb.as_slice(), &[4, 3, 2, 1, 1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(MsgCounterSyncResp::parse(wb.as_slice()).unwrap(), resp);
+        assert!(MsgCounterSyncResp::parse(&[1, 2, 3, 4, 5]).is_err());
+    }
+}
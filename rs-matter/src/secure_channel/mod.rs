@@ -31,6 +31,7 @@ pub mod crypto_rustcrypto;
 pub mod busy;
 pub mod core;
 pub mod crypto;
+pub mod mcsp;
 pub mod pake;
 pub mod spake2p;
 pub mod spake2p_test_vectors;
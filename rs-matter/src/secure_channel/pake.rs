@@ -23,6 +23,7 @@ use crate::mdns::{Mdns, ServiceMode};
 use crate::secure_channel::common::{complete_with_status, OpCode};
 use crate::tlv::{get_root_node_struct, FromTLV, OctetStr, TLVElement, TagType, ToTLV};
 use crate::transport::exchange::{Exchange, ExchangeId};
+use crate::transport::mrp::MrpParameters;
 use crate::transport::session::{ReservedSession, SessionMode};
 use crate::utils::epoch::Epoch;
 use crate::utils::init::{init, try_init, Init};
@@ -270,7 +271,9 @@ impl Pake {
             let data = spake2p.get_app_data();
             let peer_sessid: u16 = (data & 0xffff) as u16;
             let local_sessid: u16 = ((data >> 16) & 0xffff) as u16;
-            let peer_addr = exchange.with_session(|sess| Ok(sess.get_peer_addr()))?;
+            let (peer_addr, peer_mrp_params) = exchange.with_session(|sess| {
+                Ok((sess.get_peer_addr(), sess.get_peer_mrp_params().clone()))
+            })?;
 
             session.update(
                 0,
@@ -282,6 +285,7 @@ impl Pake {
                 Some(&session_keys[0..16]),
                 Some(&session_keys[16..32]),
                 Some(&session_keys[32..48]),
+                &peer_mrp_params,
             )?;
 
             Ok(())
@@ -363,6 +367,14 @@ impl Pake {
                 Err(ErrorCode::Invalid)?;
             }
 
+            if let Some(session_params) = &a.session_params {
+                // Retransmit the handshake messages with the cadence requested by the peer
+                exchange.with_session(|sess| {
+                    sess.set_peer_mrp_params(session_params.clone());
+                    Ok(())
+                })?;
+            }
+
             (exchange.matter().pase_mgr.borrow().rand)(&mut our_random);
 
             let local_sessid = exchange
@@ -385,6 +397,7 @@ impl Pake {
                 our_random: OctetStr::new(&our_random),
                 local_sessid,
                 params: None,
+                session_params: MrpParameters::local(exchange.matter().dev_det()),
             };
             if !a.has_params {
                 let params_resp = PBKDFParamRespParams {
@@ -511,6 +524,7 @@ struct PBKDFParamResp<'a> {
     our_random: OctetStr<'a>,
     local_sessid: u16,
     params: Option<PBKDFParamRespParams<'a>>,
+    session_params: MrpParameters,
 }
 
 #[allow(non_snake_case)]
@@ -528,4 +542,5 @@ struct PBKDFParamReq<'a> {
     initiator_ssid: u16,
     passcode_id: u16,
     has_params: bool,
+    session_params: Option<MrpParameters>,
 }
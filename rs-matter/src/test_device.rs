@@ -52,6 +52,9 @@ pub const TEST_DEV_DET: BasicInfoConfig = BasicInfoConfig {
     vendor_name: "ACME",
     sai: None,
     sii: None,
+    sat: None,
+    max_paths_per_invoke: None,
+    privacy: false,
 };
 
 #[derive(Debug, Clone)]
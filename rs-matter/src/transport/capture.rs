@@ -0,0 +1,819 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Packet capture
+//!
+//! When a `PacketCapture` hook is registered with `Matter::set_packet_capture`, the transport
+//! calls it with every Matter message it receives (after decryption) and sends (before encryption),
+//! along with the keys of the session the message belongs to.
+//!
+//! Received messages which are dropped by the transport (duplicates, messages for unknown sessions
+//! or exchanges, or messages which cannot be decoded) are captured as well, and are marked as such.
+//!
+//! With the `std` feature, `PcapngCapture` implements the hook by writing the messages into a pcapng file,
+//! and `PcapngReader` reads them back for offline decoding.
+
+use core::fmt;
+
+use crate::transport::network::Address;
+use crate::transport::packet::PacketHdr;
+use crate::transport::session::Session;
+
+#[cfg(feature = "std")]
+pub use pcapng::*;
+
+/// The length of the session encryption/decryption keys
+pub const SESSION_KEY_LEN: usize = 16;
+
+/// The direction of a captured message
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CaptureDirection {
+    /// A message received from the peer
+    Rx,
+    /// A message sent to the peer
+    Tx,
+}
+
+impl fmt::Display for CaptureDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rx => write!(f, ">>RCV"),
+            Self::Tx => write!(f, "<<SND"),
+        }
+    }
+}
+
+/// The identifiers and keys of the encrypted session a captured message belongs to
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CaptureSession {
+    /// The session ID assigned by us, i.e. the one used by the peer when sending messages to us
+    pub local_sess_id: u16,
+    /// The session ID assigned by the peer, i.e. the one used by us when sending messages to the peer
+    pub peer_sess_id: u16,
+    /// The local fabric index of the session (0 for PASE sessions)
+    pub fabric_idx: u8,
+    /// The node ID of the peer, if known
+    pub peer_node_id: Option<u64>,
+    /// The key used for encrypting the messages we send
+    pub enc_key: [u8; SESSION_KEY_LEN],
+    /// The key used for decrypting the messages we receive
+    pub dec_key: [u8; SESSION_KEY_LEN],
+}
+
+impl CaptureSession {
+    /// Return the capture details of the provided session, or `None` if the session is not encrypted
+    pub(crate) fn new(session: &Session) -> Option<Self> {
+        let (Some(enc_key), Some(dec_key)) = (session.get_enc_key(), session.get_dec_key()) else {
+            return None;
+        };
+
+        Some(Self {
+            local_sess_id: session.get_local_sess_id(),
+            peer_sess_id: session.get_peer_sess_id(),
+            fabric_idx: session.get_local_fabric_idx(),
+            peer_node_id: session.get_peer_node_id(),
+            enc_key: enc_key.try_into().ok()?,
+            dec_key: dec_key.try_into().ok()?,
+        })
+    }
+
+    /// Return `true` if a message with the provided session ID in its header and
+    /// travelling in the provided direction belongs to this session
+    pub fn matches(&self, direction: CaptureDirection, sess_id: u16) -> bool {
+        match direction {
+            CaptureDirection::Rx => self.local_sess_id == sess_id,
+            CaptureDirection::Tx => self.peer_sess_id == sess_id,
+        }
+    }
+}
+
+/// A Matter message passed to the `PacketCapture` hook
+#[derive(Debug)]
+pub struct CaptureRecord<'a> {
+    /// The direction of the message
+    pub direction: CaptureDirection,
+    /// The address of the peer which sent or is about to receive the message
+    pub peer: Address,
+    /// The decoded header of the message
+    pub header: &'a PacketHdr,
+    /// The unencrypted payload of the message
+    pub payload: &'a [u8],
+    /// The encrypted session the message belongs to, or `None` for unencrypted messages
+    pub session: Option<CaptureSession>,
+    /// `true` if the received message was dropped by the transport.
+    ///
+    /// The payload of dropped messages which could not be decrypted or decoded is empty.
+    pub dropped: bool,
+}
+
+/// A hook for capturing all Matter messages received and sent by the transport.
+///
+/// The hook is called synchronously from within the transport, so it should return quickly.
+pub trait PacketCapture {
+    /// Capture the provided message
+    fn capture(&self, record: &CaptureRecord<'_>);
+}
+
+impl<T> PacketCapture for &T
+where
+    T: PacketCapture,
+{
+    fn capture(&self, record: &CaptureRecord<'_>) {
+        (*self).capture(record)
+    }
+}
+
+#[cfg(feature = "std")]
+mod pcapng {
+    //! A simple pcapng format for captured Matter messages.
+    //!
+    //! The file contains a single interface with link type `LINKTYPE_MATTER` (`LINKTYPE_USER0`),
+    //! where every Enhanced Packet Block carries one Matter message, in the following (little-endian) format:
+    //! - `u8`: the record format version (`1`)
+    //! - `u8`: the direction (`0` - received, `1` - sent)
+    //! - `u8`: the peer address type (`0` - UDP, `1` - TCP, `2` - BTP)
+    //! - `u8`: flags (bit 0 - the received message was dropped)
+    //! - The peer address: 16 bytes IPv6 address (IPv4 addresses are IPv4-mapped) followed by a `u16` port for UDP and TCP,
+    //!   or 6 bytes BLE address for BTP
+    //! - The Matter message in the clear, i.e. the message header, followed by the protocol header
+    //!   and the payload, without privacy obfuscation, encryption and MIC
+    //!
+    //! The keys of each encrypted session are written once - before its first message - in a
+    //! Decryption Secrets Block of type `MATTER_SECRETS_TYPE`.
+    //!
+    //! Just like the TLS secrets stored by Wireshark, the secrets are in a text "key log" format, so that
+    //! they can be extracted with `editcap --extract-secrets` and fed to Matter dissectors expecting a key log file.
+    //! Each session is described by a single line with the following space-separated fields:
+    //! - `MATTER_SESSION_KEYS`
+    //! - The local session ID, as 4 hex digits
+    //! - The peer session ID, as 4 hex digits
+    //! - The local fabric index, as 2 hex digits
+    //! - The peer node ID, as 16 hex digits, or `-` if it is not known
+    //! - The encryption key, as 32 hex digits
+    //! - The decryption key, as 32 hex digits
+
+    use core::fmt::{self, Write as _};
+    use core::time::Duration;
+
+    use std::string::String;
+
+    use std::fs::File;
+    use std::io::{BufWriter, ErrorKind, Read, Write};
+    use std::path::Path;
+    use std::vec::Vec;
+
+    use crate::error::{Error, ErrorCode};
+    use crate::transport::core::Packet;
+    use crate::transport::network::{Address, BtAddr, IpAddr, Ipv6Addr, SocketAddr};
+    use crate::transport::packet::PacketHdr;
+    use crate::utils::cell::RefCell;
+    use crate::utils::epoch::Epoch;
+    use crate::utils::storage::{ParseBuf, WriteBuf};
+
+    use super::{CaptureDirection, CaptureRecord, CaptureSession, PacketCapture, SESSION_KEY_LEN};
+
+    /// The pcapng link type of the captured Matter messages (`LINKTYPE_USER0`)
+    pub const LINKTYPE_MATTER: u16 = 147;
+
+    /// The type of the Decryption Secrets Blocks carrying the Matter session keys
+    pub const MATTER_SECRETS_TYPE: u32 = 0x4d54_534b;
+
+    const RECORD_VERSION: u8 = 1;
+
+    const BLOCK_SHB: u32 = 0x0a0d_0d0a;
+    const BLOCK_IDB: u32 = 0x0000_0001;
+    const BLOCK_EPB: u32 = 0x0000_0006;
+    const BLOCK_DSB: u32 = 0x0000_000a;
+
+    const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+    const ADDR_UDP: u8 = 0;
+    const ADDR_TCP: u8 = 1;
+    const ADDR_BTP: u8 = 2;
+
+    const SECRETS_LABEL: &str = "MATTER_SESSION_KEYS";
+
+    const FLAG_DROPPED: u8 = 0x01;
+
+    /// A writer of captured Matter messages in the pcapng format
+    pub struct PcapngWriter<W> {
+        write: W,
+        epoch: Epoch,
+        sessions: Vec<(u16, u16)>,
+    }
+
+    impl<W> PcapngWriter<W>
+    where
+        W: Write,
+    {
+        /// Create a new writer, writing the pcapng section and interface headers into the provided output
+        pub fn new(mut write: W, epoch: Epoch) -> Result<Self, Error> {
+            let mut shb = Vec::new();
+            shb.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+            shb.extend_from_slice(&1_u16.to_le_bytes());
+            shb.extend_from_slice(&0_u16.to_le_bytes());
+            shb.extend_from_slice(&(-1_i64).to_le_bytes());
+
+            Self::write_block(&mut write, BLOCK_SHB, &shb)?;
+
+            let mut idb = Vec::new();
+            idb.extend_from_slice(&LINKTYPE_MATTER.to_le_bytes());
+            idb.extend_from_slice(&0_u16.to_le_bytes());
+            idb.extend_from_slice(&0_u32.to_le_bytes());
+
+            Self::write_block(&mut write, BLOCK_IDB, &idb)?;
+
+            Ok(Self {
+                write,
+                epoch,
+                sessions: Vec::new(),
+            })
+        }
+
+        /// Write the provided captured message, preceded by the keys of its session
+        /// if this is the first message of the session
+        pub fn write(&mut self, record: &CaptureRecord<'_>) -> Result<(), Error> {
+            if let Some(session) = &record.session {
+                let id = (session.local_sess_id, session.peer_sess_id);
+
+                if !self.sessions.contains(&id) {
+                    let secrets = Self::encode_secrets(session);
+
+                    let mut dsb = Vec::new();
+                    dsb.extend_from_slice(&MATTER_SECRETS_TYPE.to_le_bytes());
+                    dsb.extend_from_slice(&(secrets.len() as u32).to_le_bytes());
+                    dsb.extend_from_slice(secrets.as_bytes());
+
+                    Self::write_block(&mut self.write, BLOCK_DSB, &dsb)?;
+
+                    self.sessions.push(id);
+                }
+            }
+
+            let data = Self::encode_record(record)?;
+            let timestamp = (self.epoch)().as_micros() as u64;
+
+            let mut epb = Vec::new();
+            epb.extend_from_slice(&0_u32.to_le_bytes());
+            epb.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+            epb.extend_from_slice(&(timestamp as u32).to_le_bytes());
+            epb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            epb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            epb.extend_from_slice(&data);
+
+            Self::write_block(&mut self.write, BLOCK_EPB, &epb)
+        }
+
+        /// Flush the underlying output
+        pub fn flush(&mut self) -> Result<(), Error> {
+            Ok(self.write.flush()?)
+        }
+
+        /// Return the underlying output
+        pub fn into_inner(self) -> W {
+            self.write
+        }
+
+        fn encode_secrets(session: &CaptureSession) -> String {
+            let mut secrets = String::new();
+
+            // Writing into a `String` cannot fail
+            let _ = write!(
+                secrets,
+                "{} {:04x} {:04x} {:02x} ",
+                SECRETS_LABEL, session.local_sess_id, session.peer_sess_id, session.fabric_idx
+            );
+
+            if let Some(peer_node_id) = session.peer_node_id {
+                let _ = write!(secrets, "{:016x} ", peer_node_id);
+            } else {
+                secrets.push_str("- ");
+            }
+
+            for (index, key) in [&session.enc_key, &session.dec_key].iter().enumerate() {
+                if index > 0 {
+                    secrets.push(' ');
+                }
+
+                for byte in key.iter() {
+                    let _ = write!(secrets, "{:02x}", byte);
+                }
+            }
+
+            secrets.push('\n');
+
+            secrets
+        }
+
+        fn encode_record(record: &CaptureRecord<'_>) -> Result<Vec<u8>, Error> {
+            let mut data = Vec::new();
+
+            data.push(RECORD_VERSION);
+            data.push(match record.direction {
+                CaptureDirection::Rx => 0,
+                CaptureDirection::Tx => 1,
+            });
+
+            match record.peer {
+                Address::Udp(addr) | Address::Tcp(addr) => {
+                    data.push(if record.peer.is_udp() {
+                        ADDR_UDP
+                    } else {
+                        ADDR_TCP
+                    });
+                    data.push(Self::flags(record));
+
+                    let ip = match addr.ip() {
+                        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+                        IpAddr::V6(ip) => ip,
+                    };
+
+                    data.extend_from_slice(&ip.octets());
+                    data.extend_from_slice(&addr.port().to_le_bytes());
+                }
+                Address::Btp(addr) => {
+                    data.push(ADDR_BTP);
+                    data.push(Self::flags(record));
+                    data.extend_from_slice(&addr.0);
+                }
+            }
+
+            // Privacy obfuscation is applied on top of encryption, and the message is stored in the clear
+            let mut header = record.header.clone();
+            header.plain.set_privacy(false);
+
+            let mut buf = std::vec![0; PacketHdr::HDR_RESERVE + record.payload.len()];
+            let mut wb = WriteBuf::new(&mut buf);
+            wb.reserve(PacketHdr::HDR_RESERVE)?;
+            wb.append(record.payload)?;
+
+            header.encode(&mut wb, 0, None, None)?;
+
+            data.extend_from_slice(wb.as_slice());
+
+            Ok(data)
+        }
+
+        fn flags(record: &CaptureRecord<'_>) -> u8 {
+            if record.dropped {
+                FLAG_DROPPED
+            } else {
+                0
+            }
+        }
+
+        fn write_block(write: &mut W, block_type: u32, body: &[u8]) -> Result<(), Error> {
+            let padding = (4 - body.len() % 4) % 4;
+            let len = (body.len() + padding + 12) as u32;
+
+            write.write_all(&block_type.to_le_bytes())?;
+            write.write_all(&len.to_le_bytes())?;
+            write.write_all(body)?;
+            write.write_all(&[0; 3][..padding])?;
+            write.write_all(&len.to_le_bytes())?;
+
+            Ok(())
+        }
+    }
+
+    /// A `PacketCapture` hook writing the captured messages into a pcapng output
+    pub struct PcapngCapture<W>(RefCell<PcapngWriter<W>>);
+
+    impl<W> PcapngCapture<W>
+    where
+        W: Write,
+    {
+        /// Create a new capture hook writing into the provided output
+        pub fn new(write: W, epoch: Epoch) -> Result<Self, Error> {
+            Ok(Self(RefCell::new(PcapngWriter::new(write, epoch)?)))
+        }
+
+        /// Flush the underlying output
+        pub fn flush(&self) -> Result<(), Error> {
+            self.0.borrow_mut().flush()
+        }
+
+        /// Return the underlying writer
+        pub fn into_inner(self) -> PcapngWriter<W> {
+            self.0.into_inner()
+        }
+    }
+
+    impl PcapngCapture<BufWriter<File>> {
+        /// Create a new capture hook writing into the provided file
+        pub fn create<P: AsRef<Path>>(path: P, epoch: Epoch) -> Result<Self, Error> {
+            Self::new(BufWriter::new(File::create(path)?), epoch)
+        }
+    }
+
+    impl<W> PacketCapture for PcapngCapture<W>
+    where
+        W: Write,
+    {
+        fn capture(&self, record: &CaptureRecord<'_>) {
+            if let Err(e) = self.0.borrow_mut().write(record) {
+                warn!("Failed to capture packet: {:?}", e);
+            }
+        }
+    }
+
+    /// A Matter message read from a pcapng capture
+    #[derive(Debug, Clone)]
+    pub struct CapturedPacket {
+        /// The time the message was captured at, as a duration since the epoch used by the capture
+        pub timestamp: Duration,
+        /// The direction of the message
+        pub direction: CaptureDirection,
+        /// The address of the peer
+        pub peer: Address,
+        /// The decoded header of the message
+        pub header: PacketHdr,
+        /// The unencrypted payload of the message
+        pub payload: Vec<u8>,
+        /// The encrypted session the message belongs to, if its keys were captured
+        pub session: Option<CaptureSession>,
+        /// `true` if the received message was dropped by the transport
+        pub dropped: bool,
+    }
+
+    impl fmt::Display for CapturedPacket {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(
+                f,
+                "[{}.{:06}] {} {}{}",
+                self.timestamp.as_secs(),
+                self.timestamp.subsec_micros(),
+                self.direction,
+                Packet::<0>::display(&self.peer, &self.header),
+                if self.dropped { " (dropped)" } else { "" }
+            )?;
+
+            write!(
+                f,
+                "{}",
+                Packet::<0>::display_payload(&self.header.proto, &self.payload)
+            )
+        }
+    }
+
+    /// A reader of Matter messages captured in the pcapng format by `PcapngWriter`
+    pub struct PcapngReader<R> {
+        read: R,
+        sessions: Vec<CaptureSession>,
+    }
+
+    impl<R> PcapngReader<R>
+    where
+        R: Read,
+    {
+        /// Create a new reader, validating the pcapng section header of the provided input
+        pub fn new(mut read: R) -> Result<Self, Error> {
+            let (block_type, body) = Self::read_block(&mut read)?.ok_or(ErrorCode::InvalidData)?;
+
+            Self::check_shb(block_type, &body)?;
+
+            Ok(Self {
+                read,
+                sessions: Vec::new(),
+            })
+        }
+
+        /// Return the sessions whose keys were read so far
+        pub fn sessions(&self) -> &[CaptureSession] {
+            &self.sessions
+        }
+
+        /// Read the next captured message, or return `None` if the end of the input is reached
+        pub fn next_packet(&mut self) -> Result<Option<CapturedPacket>, Error> {
+            while let Some((block_type, body)) = Self::read_block(&mut self.read)? {
+                match block_type {
+                    BLOCK_SHB => Self::check_shb(block_type, &body)?,
+                    BLOCK_IDB
+                        if body.len() < 2
+                            || u16::from_le_bytes([body[0], body[1]]) != LINKTYPE_MATTER =>
+                    {
+                        Err(ErrorCode::InvalidData)?
+                    }
+                    BLOCK_DSB => self.read_secrets(&body)?,
+                    BLOCK_EPB => return self.read_packet(&body).map(Some),
+                    _ => (),
+                }
+            }
+
+            Ok(None)
+        }
+
+        fn read_secrets(&mut self, body: &[u8]) -> Result<(), Error> {
+            let secrets_type = le_u32(body, 0)?;
+            let secrets_len = le_u32(body, 4)? as usize;
+
+            if secrets_type != MATTER_SECRETS_TYPE {
+                return Ok(());
+            }
+
+            let secrets = body.get(8..8 + secrets_len).ok_or(ErrorCode::InvalidData)?;
+            let secrets = core::str::from_utf8(secrets).map_err(|_| ErrorCode::InvalidData)?;
+
+            for line in secrets.lines().filter(|line| !line.trim().is_empty()) {
+                let session = Self::parse_secrets(line).ok_or(ErrorCode::InvalidData)?;
+
+                self.sessions.push(session);
+            }
+
+            Ok(())
+        }
+
+        fn parse_secrets(line: &str) -> Option<CaptureSession> {
+            let mut fields = line.split_ascii_whitespace();
+
+            if fields.next()? != SECRETS_LABEL {
+                return None;
+            }
+
+            let local_sess_id = u16::from_str_radix(fields.next()?, 16).ok()?;
+            let peer_sess_id = u16::from_str_radix(fields.next()?, 16).ok()?;
+            let fabric_idx = u8::from_str_radix(fields.next()?, 16).ok()?;
+            let peer_node_id = match fields.next()? {
+                "-" => None,
+                id => Some(u64::from_str_radix(id, 16).ok()?),
+            };
+            let enc_key = parse_key(fields.next()?)?;
+            let dec_key = parse_key(fields.next()?)?;
+
+            fields.next().is_none().then_some(CaptureSession {
+                local_sess_id,
+                peer_sess_id,
+                fabric_idx,
+                peer_node_id,
+                enc_key,
+                dec_key,
+            })
+        }
+
+        fn read_packet(&self, body: &[u8]) -> Result<CapturedPacket, Error> {
+            let timestamp = ((le_u32(body, 4)? as u64) << 32) | le_u32(body, 8)? as u64;
+            let captured_len = le_u32(body, 12)? as usize;
+
+            let data = body
+                .get(20..20 + captured_len)
+                .ok_or(ErrorCode::InvalidData)?;
+
+            if data.len() < 4 || data[0] != RECORD_VERSION {
+                Err(ErrorCode::InvalidData)?;
+            }
+
+            let dropped = data[3] & FLAG_DROPPED != 0;
+
+            let direction = match data[1] {
+                0 => CaptureDirection::Rx,
+                1 => CaptureDirection::Tx,
+                _ => Err(ErrorCode::InvalidData)?,
+            };
+
+            let (peer, msg) = match data[2] {
+                ADDR_UDP | ADDR_TCP => {
+                    let addr = data.get(4..22).ok_or(ErrorCode::InvalidData)?;
+
+                    let ip = Ipv6Addr::from(unwrap!(<[u8; 16]>::try_from(&addr[..16])));
+                    let ip = ip
+                        .to_ipv4_mapped()
+                        .map(IpAddr::V4)
+                        .unwrap_or(IpAddr::V6(ip));
+                    let addr = SocketAddr::new(ip, u16::from_le_bytes([addr[16], addr[17]]));
+
+                    let peer = if data[2] == ADDR_UDP {
+                        Address::Udp(addr)
+                    } else {
+                        Address::Tcp(addr)
+                    };
+
+                    (peer, &data[22..])
+                }
+                ADDR_BTP => {
+                    let addr = data.get(4..10).ok_or(ErrorCode::InvalidData)?;
+
+                    (Address::Btp(BtAddr(unwrap!(addr.try_into()))), &data[10..])
+                }
+                _ => Err(ErrorCode::InvalidData)?,
+            };
+
+            let mut msg = msg.to_vec();
+
+            let mut header = PacketHdr::new();
+            let mut pb = ParseBuf::new(&mut msg);
+            header.decode_plain_hdr(&mut pb)?;
+            header.decode_remaining(&mut pb, 0, None)?;
+
+            let payload = pb.as_slice().to_vec();
+
+            let session = header
+                .plain
+                .is_encrypted()
+                .then(|| {
+                    self.sessions
+                        .iter()
+                        .rev()
+                        .find(|session| session.matches(direction, header.plain.sess_id))
+                        .cloned()
+                })
+                .flatten();
+
+            Ok(CapturedPacket {
+                timestamp: Duration::from_micros(timestamp),
+                direction,
+                peer,
+                header,
+                payload,
+                session,
+                dropped,
+            })
+        }
+
+        fn check_shb(block_type: u32, body: &[u8]) -> Result<(), Error> {
+            // Only little-endian captures (as written by `PcapngWriter`) are supported
+            if block_type != BLOCK_SHB || le_u32(body, 0)? != BYTE_ORDER_MAGIC {
+                Err(ErrorCode::InvalidData)?;
+            }
+
+            Ok(())
+        }
+
+        fn read_block(read: &mut R) -> Result<Option<(u32, Vec<u8>)>, Error> {
+            let mut hdr = [0; 8];
+
+            match read.read_exact(&mut hdr) {
+                Ok(()) => (),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => Err(e)?,
+            }
+
+            let block_type = le_u32(&hdr, 0)?;
+            let len = le_u32(&hdr, 4)? as usize;
+
+            if len < 12 || len % 4 != 0 {
+                Err(ErrorCode::InvalidData)?;
+            }
+
+            let mut body = std::vec![0; len - 8];
+            read.read_exact(&mut body)?;
+
+            // Strip the trailing block length
+            body.truncate(len - 12);
+
+            Ok(Some((block_type, body)))
+        }
+    }
+
+    fn parse_key(hex: &str) -> Option<[u8; SESSION_KEY_LEN]> {
+        if hex.len() != SESSION_KEY_LEN * 2 || !hex.is_ascii() {
+            return None;
+        }
+
+        let mut key = [0; SESSION_KEY_LEN];
+
+        for (index, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+        }
+
+        Some(key)
+    }
+
+    fn le_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+        let bytes = data.get(offset..offset + 4).ok_or(ErrorCode::InvalidData)?;
+
+        Ok(u32::from_le_bytes(unwrap!(bytes.try_into())))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::time::Duration;
+
+    use crate::transport::network::{Address, BtAddr, SocketAddr};
+    use crate::transport::packet::PacketHdr;
+
+    use super::{CaptureDirection, CaptureRecord, CaptureSession, PcapngReader, PcapngWriter};
+
+    fn epoch() -> Duration {
+        Duration::from_micros(0x1_0000_0002)
+    }
+
+    #[test]
+    fn test_pcapng_roundtrip() {
+        let session = CaptureSession {
+            local_sess_id: 0x1234,
+            peer_sess_id: 0x5678,
+            fabric_idx: 1,
+            peer_node_id: Some(0x1122334455667788),
+            enc_key: [1; 16],
+            dec_key: [2; 16],
+        };
+
+        let mut secure = PacketHdr::new();
+        secure.plain.sess_id = session.peer_sess_id;
+        secure.plain.ctr = 0x3039;
+        secure.plain.set_privacy(true);
+        secure.proto.exch_id = 0x0102;
+        secure.proto.proto_id = 0x0001;
+        secure.proto.proto_opcode = 0x02;
+        secure.proto.set_initiator();
+        secure.proto.set_reliable();
+
+        let mut plain = PacketHdr::new();
+        plain.plain.ctr = 1;
+        plain.plain.set_src_nodeid(Some(0xaabb));
+        plain.proto.exch_id = 0x0304;
+        plain.proto.proto_id = 0x0000;
+        plain.proto.proto_opcode = 0x20;
+
+        let udp: SocketAddr = "192.168.1.2:5540".parse().unwrap();
+        let btp = BtAddr([1, 2, 3, 4, 5, 6]);
+
+        let mut writer = PcapngWriter::new(std::vec::Vec::new(), epoch).unwrap();
+
+        for _ in 0..2 {
+            writer
+                .write(&CaptureRecord {
+                    direction: CaptureDirection::Tx,
+                    peer: Address::Udp(udp),
+                    header: &secure,
+                    payload: &[0x15, 0x18],
+                    session: Some(session.clone()),
+                    dropped: false,
+                })
+                .unwrap();
+        }
+
+        writer
+            .write(&CaptureRecord {
+                direction: CaptureDirection::Rx,
+                peer: Address::Btp(btp),
+                header: &plain,
+                payload: &[1, 2, 3],
+                session: None,
+                dropped: true,
+            })
+            .unwrap();
+
+        let data = writer.into_inner();
+
+        // The session keys are stored in the key log text format
+        let secrets = "MATTER_SESSION_KEYS 1234 5678 01 1122334455667788 \
+            01010101010101010101010101010101 02020202020202020202020202020202\n";
+        assert!(data
+            .windows(secrets.len())
+            .any(|window| window == secrets.as_bytes()));
+
+        let mut reader = PcapngReader::new(data.as_slice()).unwrap();
+
+        for _ in 0..2 {
+            let packet = reader.next_packet().unwrap().unwrap();
+            assert_eq!(packet.timestamp, epoch());
+            assert_eq!(packet.direction, CaptureDirection::Tx);
+            assert_eq!(packet.peer, Address::Udp(udp));
+            assert!(!packet.header.plain.is_privacy());
+            assert_eq!(packet.header.plain.sess_id, 0x5678);
+            assert_eq!(packet.header.plain.ctr, 0x3039);
+            assert_eq!(packet.header.proto.exch_id, 0x0102);
+            assert_eq!(packet.header.proto.proto_opcode, 0x02);
+            assert!(packet.header.proto.is_initiator());
+            assert!(packet.header.proto.is_reliable());
+            assert_eq!(packet.payload, &[0x15, 0x18]);
+            assert_eq!(packet.session.as_ref(), Some(&session));
+            assert!(!packet.dropped);
+        }
+
+        // The session keys are written only once
+        assert_eq!(reader.sessions(), &[session]);
+
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.direction, CaptureDirection::Rx);
+        assert_eq!(packet.peer, Address::Btp(btp));
+        assert_eq!(packet.header.plain.get_src_nodeid(), Some(0xaabb));
+        assert_eq!(packet.header.proto.exch_id, 0x0304);
+        assert_eq!(packet.header.proto.proto_opcode, 0x20);
+        assert_eq!(packet.payload, &[1, 2, 3]);
+        assert!(packet.session.is_none());
+        assert!(packet.dropped);
+
+        assert!(reader.next_packet().unwrap().is_none());
+
+        assert!(PcapngReader::new(&[0_u8; 12][..]).is_err());
+    }
+}
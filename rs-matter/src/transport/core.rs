@@ -15,11 +15,13 @@
  *    limitations under the License.
  */
 
+use core::cell::Cell;
 use core::fmt::{self, Display};
+use core::num::NonZeroU8;
 use core::ops::{Deref, DerefMut};
 use core::pin::pin;
 
-use embassy_futures::select::{select, select3};
+use embassy_futures::select::{select, select3, select4};
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_time::Timer;
 
@@ -39,13 +41,20 @@ use crate::utils::storage::{pooled::BufferAccess, ParseBuf, WriteBuf};
 use crate::utils::sync::{IfMutex, IfMutexGuard, Notification};
 use crate::{Matter, MATTER_PORT};
 
+use super::capture::{CaptureDirection, CaptureRecord, CaptureSession, PacketCapture};
+use super::counters::{GroupPeerCounters, MsgCounters, MAX_GROUP_PEERS};
 use super::exchange::{Exchange, ExchangeId, ExchangeState, MessageMeta, ResponderState, Role};
+use super::mrp::{AckStats, MrpParameters};
 use super::network::{
     self, Address, Ipv6Addr, NetworkReceive, NetworkSend, SocketAddr, SocketAddrV6,
 };
 use super::packet::PacketHdr;
+use super::plain_hdr::PlainHdr;
 use super::proto_hdr::ProtoHdr;
-use super::session::{Session, SessionMgr};
+use super::session::{
+    DefaultSessionEvictionPolicy, Session, SessionEvictionPolicy, SessionMgr, SessionRemoval,
+    SessionRemovalReason,
+};
 
 #[cfg(all(feature = "large-buffers", feature = "alloc"))]
 extern crate alloc;
@@ -55,6 +64,9 @@ pub const MATTER_SOCKET_BIND_ADDR: SocketAddr =
 
 const ACCEPT_TIMEOUT_MS: u64 = 1000;
 
+/// How many session removals are remembered for `TransportMgr::wait_session_removed`
+pub const MAX_SESSION_REMOVALS: usize = 4;
+
 #[cfg(all(feature = "large-buffers", feature = "alloc"))]
 pub(crate) const MAX_RX_BUF_SIZE: usize = network::MAX_RX_LARGE_PACKET_SIZE;
 #[cfg(all(feature = "large-buffers", feature = "alloc"))]
@@ -74,12 +86,23 @@ pub struct TransportMgr<'m> {
     pub(crate) tx: IfMutex<NoopRawMutex, Packet<MAX_TX_BUF_SIZE>>,
     pub(crate) dropped: Notification<NoopRawMutex>,
     pub(crate) session_removed: Notification<NoopRawMutex>,
+    session_removals: Notification<NoopRawMutex>,
+    removed_sessions: RefCell<heapless::Deque<SessionRemoval, MAX_SESSION_REMOVALS>>,
+    pub(crate) ack_pending: Notification<NoopRawMutex>,
+    // The earliest time (in ms since the epoch) when a standalone ACK might become due,
+    // so that the ACK scheduler does not have to scan all exchanges before that
+    ack_due_at_ms: Cell<Option<u64>>,
+    pub(crate) ack_stats: RefCell<AckStats>,
+    pub(crate) counters: RefCell<MsgCounters>,
+    pub(crate) group_peers: RefCell<GroupPeerCounters<MAX_GROUP_PEERS>>,
+    eviction_policy: &'m dyn SessionEvictionPolicy,
+    capture: Option<&'m dyn PacketCapture>,
     pub session_mgr: RefCell<SessionMgr>, // For testing
     pub(crate) mdns: MdnsImpl<'m>,
     #[allow(dead_code)]
     rand: Rand,
-    device_sai: Option<u16>,
-    device_sii: Option<u16>,
+    epoch: Epoch,
+    pub(crate) device_privacy: bool,
 }
 
 impl<'m> TransportMgr<'m> {
@@ -96,11 +119,20 @@ impl<'m> TransportMgr<'m> {
             tx: IfMutex::new(Packet::new()),
             dropped: Notification::new(),
             session_removed: Notification::new(),
+            session_removals: Notification::new(),
+            removed_sessions: RefCell::new(heapless::Deque::new()),
+            ack_pending: Notification::new(),
+            ack_due_at_ms: Cell::new(None),
+            ack_stats: RefCell::new(AckStats::new()),
+            counters: RefCell::new(MsgCounters::new(rand)),
+            group_peers: RefCell::new(GroupPeerCounters::new()),
+            eviction_policy: &DefaultSessionEvictionPolicy,
+            capture: None,
             session_mgr: RefCell::new(SessionMgr::new(epoch, rand)),
             mdns: MdnsImpl::new(service, dev_det, matter_port),
             rand,
-            device_sai: dev_det.sai,
-            device_sii: dev_det.sii,
+            epoch,
+            device_privacy: dev_det.privacy,
         }
     }
 
@@ -116,11 +148,20 @@ impl<'m> TransportMgr<'m> {
             tx <- IfMutex::init(Packet::init()),
             dropped: Notification::new(),
             session_removed: Notification::new(),
+            session_removals: Notification::new(),
+            removed_sessions: RefCell::new(heapless::Deque::new()),
+            ack_pending: Notification::new(),
+            ack_due_at_ms: Cell::new(None),
+            ack_stats: RefCell::new(AckStats::new()),
+            counters: RefCell::new(MsgCounters::new(rand)),
+            group_peers <- RefCell::init(GroupPeerCounters::init()),
+            eviction_policy: &DefaultSessionEvictionPolicy,
+            capture: None,
             session_mgr <- RefCell::init(SessionMgr::init(epoch, rand)),
             mdns <- MdnsImpl::init(service, dev_det, matter_port),
             rand,
-            device_sai: dev_det.sai,
-            device_sii: dev_det.sii,
+            epoch,
+            device_privacy: dev_det.privacy,
         })
     }
 
@@ -151,6 +192,116 @@ impl<'m> TransportMgr<'m> {
         Ok(())
     }
 
+    /// Return the counters of the MRP acknowledgements sent so far,
+    /// i.e. how many were piggybacked on outgoing messages and how many were sent as standalone ACKs
+    pub fn ack_stats(&self) -> AckStats {
+        self.ack_stats.borrow().clone()
+    }
+
+    /// Set the policy used for selecting the session to be evicted when the session table is full.
+    ///
+    /// By default, `DefaultSessionEvictionPolicy` is used.
+    pub fn set_eviction_policy(&mut self, policy: &'m dyn SessionEvictionPolicy) {
+        self.eviction_policy = policy;
+    }
+
+    /// Set a hook which is called with every Matter message received (after decryption) and sent (before encryption).
+    ///
+    /// By default, no messages are captured.
+    pub fn set_packet_capture(&mut self, capture: &'m dyn PacketCapture) {
+        self.capture = Some(capture);
+    }
+
+    /// Pass the provided message to the packet capture hook, if one is set
+    pub(crate) fn capture_packet(
+        &self,
+        direction: CaptureDirection,
+        peer: Address,
+        header: &PacketHdr,
+        payload: &[u8],
+        session: Option<&Session>,
+        dropped: bool,
+    ) {
+        if let Some(capture) = self.capture {
+            capture.capture(&CaptureRecord {
+                direction,
+                peer,
+                header,
+                payload,
+                session: session.and_then(CaptureSession::new),
+                dropped,
+            });
+        }
+    }
+
+    /// Close all sessions matching the provided predicate, for the provided reason.
+    ///
+    /// The sessions do not accept new exchanges from now on, and are closed
+    /// (by sending a `CloseSession` status report to the peer) as soon as their ongoing exchanges are complete.
+    ///
+    /// Return the number of sessions scheduled for closing.
+    pub fn close_sessions<F>(&self, f: F, reason: SessionRemovalReason) -> usize
+    where
+        F: Fn(&Session) -> bool,
+    {
+        let count = self.session_mgr.borrow_mut().expire(f, reason);
+
+        self.sessions_expired(count)
+    }
+
+    /// Close all sessions of the fabric with the provided local index, for the provided reason.
+    ///
+    /// Return the number of sessions scheduled for closing.
+    pub fn close_sessions_for_fabric(
+        &self,
+        fab_idx: NonZeroU8,
+        reason: SessionRemovalReason,
+    ) -> usize {
+        let count = self
+            .session_mgr
+            .borrow_mut()
+            .expire_for_fabric(fab_idx, reason);
+
+        self.sessions_expired(count)
+    }
+
+    /// Set the MRP parameters of all sessions with the provided peer node on the fabric with the provided local index.
+    ///
+    /// Return the number of updated sessions.
+    pub fn set_peer_mrp_params(
+        &self,
+        fab_idx: NonZeroU8,
+        peer_node_id: u64,
+        params: &MrpParameters,
+    ) -> usize {
+        self.session_mgr
+            .borrow_mut()
+            .set_peer_mrp_params(fab_idx, peer_node_id, params)
+    }
+
+    fn sessions_expired(&self, count: usize) -> usize {
+        if count > 0 {
+            // Wake up the dropped exchanges' processing, which also takes care of closing expired sessions
+            self.dropped.notify();
+        }
+
+        count
+    }
+
+    /// Wait until a session is removed, and return information about the removed session.
+    ///
+    /// Only the last `MAX_SESSION_REMOVALS` removals are remembered, so if
+    /// the removals are not consumed timely, the oldest ones are lost.
+    pub async fn wait_session_removed(&self) -> SessionRemoval {
+        loop {
+            if let Some(removal) = self.removed_sessions.borrow_mut().pop_front() {
+                break removal;
+            }
+
+            self.session_removals.wait().await;
+        }
+    }
+
     /// Resets the transport layer by clearing all sessions, exchanges, the RX buffer and the TX buffer
     /// NOTE: User should be careful _not_ to call this method while the transport layer and/or the built-in mDNS is running.
     pub fn reset(&self) -> Result<(), Error> {
@@ -291,17 +442,19 @@ impl<'m> TransportMgr<'m> {
         feature = "std",
         any(target_os = "macos", all(feature = "zeroconf", target_os = "linux"))
     )))]
-    pub async fn run_builtin_mdns<S, R>(
+    pub async fn run_builtin_mdns<S, R, P>(
         &self,
         send: S,
         recv: R,
         host: &crate::mdns::Host<'_>,
         ipv4_interface: Option<core::net::Ipv4Addr>,
         ipv6_interface: Option<u32>,
+        peer_params: P,
     ) -> Result<(), Error>
     where
         S: NetworkSend,
         R: NetworkReceive,
+        P: FnMut(&str, MrpParameters),
     {
         info!("Running Matter built-in mDNS service");
 
@@ -315,6 +468,7 @@ impl<'m> TransportMgr<'m> {
                 ipv4_interface,
                 ipv6_interface,
                 self.rand,
+                peer_params,
             )
             .await
         } else {
@@ -421,10 +575,16 @@ impl<'m> TransportMgr<'m> {
         let mut rx_accept_timeout = pin!(self.process_accept_timeout_rx());
         let mut rx_orphaned = pin!(self.process_orphaned_rx());
         let mut exch_dropped = pin!(self.process_dropped_exchanges());
+        let mut acks = pin!(self.process_pending_acks());
 
-        select3(&mut rx_accept_timeout, &mut rx_orphaned, &mut exch_dropped)
-            .coalesce()
-            .await
+        select4(
+            &mut rx_accept_timeout,
+            &mut rx_orphaned,
+            &mut exch_dropped,
+            &mut acks,
+        )
+        .coalesce()
+        .await
     }
 
     async fn process_accept_timeout_rx(&self) -> Result<(), Error> {
@@ -459,7 +619,13 @@ impl<'m> TransportMgr<'m> {
             let mut tx = self.get_if(&self.tx, |packet| packet.buf.is_empty()).await;
             tx.clear_on_drop(true); // In case of error, or if the future is dropped
 
-            let wait = match self.handle_dropped_exchange(&mut tx) {
+            let wait = match self.handle_dropped_exchange(&mut tx).and_then(|wait| {
+                if wait && tx.buf.is_empty() {
+                    self.handle_expired_session(&mut tx)
+                } else {
+                    Ok(false)
+                }
+            }) {
                 Ok(wait) => {
                     tx.clear_on_drop(false);
                     wait
@@ -481,6 +647,39 @@ impl<'m> TransportMgr<'m> {
         }
     }
 
+    async fn process_pending_acks(&self) -> Result<(), Error> {
+        loop {
+            let now_ms = (self.epoch)().as_millis() as u64;
+
+            match self.ack_due_at_ms.get() {
+                None => {
+                    trace!("Waiting for pending ACKs");
+
+                    self.ack_pending.wait().await;
+                    continue;
+                }
+                Some(due_at_ms) if due_at_ms > now_ms => {
+                    let mut timeout = pin!(Timer::after(embassy_time::Duration::from_millis(
+                        due_at_ms - now_ms
+                    )));
+                    let mut pending = pin!(self.ack_pending.wait());
+
+                    select(&mut timeout, &mut pending).await;
+                    continue;
+                }
+                _ => (),
+            }
+
+            let mut tx = self.get_if(&self.tx, |packet| packet.buf.is_empty()).await;
+            tx.clear_on_drop(true); // In case of error, or if the future is dropped
+
+            match self.handle_pending_ack(&mut tx) {
+                Ok(()) => tx.clear_on_drop(false),
+                Err(e) => error!("UNEXPECTED ACK ERROR: {:?}", e),
+            }
+        }
+    }
+
     async fn handle_rx_packet<const N: usize, S>(
         &self,
         packet: &mut Packet<N>,
@@ -490,6 +689,41 @@ impl<'m> TransportMgr<'m> {
         S: NetworkSend,
     {
         let result = self.decode_packet(packet);
+
+        if self.capture.is_some() {
+            // Messages which could not be decoded are captured without payload
+            let decoded = match &result {
+                Ok(_) => true,
+                Err(e) => matches!(
+                    e.code(),
+                    ErrorCode::Duplicate
+                        | ErrorCode::NoExchange
+                        | ErrorCode::NoSpaceExchanges
+                        | ErrorCode::NoSpaceSessions
+                        | ErrorCode::NoSession
+                ),
+            };
+
+            let payload = if decoded {
+                &packet.buf[core::cmp::min(packet.payload_start, packet.buf.len())..]
+            } else {
+                &[]
+            };
+
+            // Use a lookup which does not update the session, so that capturing does not affect the eviction order
+            let session_mgr = self.session_mgr.borrow();
+            let session = session_mgr.find_for_rx(&packet.peer, &packet.header.plain);
+
+            self.capture_packet(
+                CaptureDirection::Rx,
+                packet.peer,
+                &packet.header,
+                payload,
+                session,
+                result.is_err(),
+            );
+        }
+
         match result {
             Err(e) if matches!(e.code(), ErrorCode::Duplicate) => {
                 if !packet.peer.is_reliable()
@@ -589,7 +823,7 @@ impl<'m> TransportMgr<'m> {
 
                     // See above why `unwrap` is safe
                     let mut session = unwrap!(session_mgr.remove(session_id));
-                    self.session_removed.notify();
+                    self.notify_session_removed(&session, SessionRemovalReason::Failed);
 
                     self.encode_packet(packet, Some(&mut session), None, |wb| {
                         sc_write(wb, SCStatusCodes::CloseSession, &[])
@@ -636,8 +870,9 @@ impl<'m> TransportMgr<'m> {
                         .get_for_rx(&packet.peer, &packet.header.plain)
                         .map(|sess| sess.id)
                     {
-                        session_mgr.remove(session_id);
-                        self.session_removed.notify();
+                        // `unwrap` is safe because we just found the session
+                        let session = unwrap!(session_mgr.remove(session_id));
+                        self.notify_session_removed(&session, SessionRemovalReason::ClosedByPeer);
                     }
                 } else {
                     debug!(
@@ -771,7 +1006,12 @@ impl<'m> TransportMgr<'m> {
                 exchange_id.display(unwrap!(session_mgr.get(session_id))) // Session exists or else we wouldn't be here
             );
 
-            self.encode_evict_session(packet, &mut session_mgr, session_id)?;
+            self.encode_evict_session(
+                packet,
+                &mut session_mgr,
+                session_id,
+                SessionRemovalReason::Failed,
+            )?;
         } else {
             // Found a dropped exchange which has no outstanding (re)transmission
             // Send a standalone ACK if necessary and then close it
@@ -787,6 +1027,17 @@ impl<'m> TransportMgr<'m> {
                 })?;
             }
 
+            // Ditto
+            let session = unwrap!(session_mgr.get(session_id));
+
+            if unwrap!(session.exchanges[exch_index].as_ref())
+                .mrp
+                .is_ack_pending()
+            {
+                // Displaced ACKs are sent one by one, so only close the exchange once all of them are sent
+                return Ok(false);
+            }
+
             warn!("Dropped exchange {}: Closed", exchange_id.display(session));
             session.exchanges[exch_index] = None;
         }
@@ -794,6 +1045,100 @@ impl<'m> TransportMgr<'m> {
         Ok(exch.is_none())
     }
 
+    /// Update the time when the ACK scheduler should look for due standalone ACKs
+    /// with the state of the exchange which just received the provided message, if any.
+    ///
+    /// The ACK scheduler is woken up only if the exchange has an ACK due earlier than that time.
+    fn schedule_ack(&self, session: &Session, rx_proto: &ProtoHdr, epoch: Epoch) {
+        let due_ms = session
+            .get_exch_for_rx(rx_proto)
+            .and_then(|exch_index| session.exchanges[exch_index].as_ref())
+            .and_then(|exch| exch.mrp.standalone_ack_due_ms(epoch));
+
+        let Some(due_ms) = due_ms else {
+            return;
+        };
+
+        if self.schedule_ack_at(epoch().as_millis() as u64 + due_ms) {
+            self.ack_pending.notify();
+        }
+    }
+
+    /// Make the ACK scheduler look for due standalone ACKs at the provided time (in ms since the epoch),
+    /// unless it is already scheduled to do so earlier.
+    ///
+    /// Return `true` if the time was updated.
+    fn schedule_ack_at(&self, due_at_ms: u64) -> bool {
+        let earlier = self
+            .ack_due_at_ms
+            .get()
+            .map(|scheduled_at_ms| due_at_ms < scheduled_at_ms)
+            .unwrap_or(true);
+
+        if earlier {
+            self.ack_due_at_ms.set(Some(due_at_ms));
+        }
+
+        earlier
+    }
+
+    /// Encode a standalone ACK for the first exchange whose pending ACK could not be piggybacked
+    /// on time (or was displaced by a newer ACK), if any.
+    ///
+    /// Also re-compute when the ACK scheduler should look for due standalone ACKs next, so that
+    /// the exchanges are only scanned once per due ACK.
+    fn handle_pending_ack<const N: usize>(&self, packet: &mut Packet<N>) -> Result<(), Error> {
+        let mut session_mgr = self.session_mgr.borrow_mut();
+        let epoch = session_mgr.epoch;
+        let now_ms = epoch().as_millis() as u64;
+
+        let mut due = None;
+        let mut next_due_ms = None;
+
+        for session in session_mgr.iter() {
+            for (exch_index, exch) in session.exchanges.iter().enumerate() {
+                let Some(due_ms) = exch
+                    .as_ref()
+                    .and_then(|exch| exch.mrp.standalone_ack_due_ms(epoch))
+                else {
+                    continue;
+                };
+
+                if due_ms == 0 && due.is_none() {
+                    due = Some((session.id, exch_index));
+                } else {
+                    next_due_ms = Some(next_due_ms.map_or(due_ms, |next: u64| next.min(due_ms)));
+                }
+            }
+        }
+
+        // Do this before encoding, so that an encoding error does not result in a busy loop
+        self.ack_due_at_ms
+            .set(next_due_ms.map(|due_ms| now_ms + due_ms));
+
+        let Some((session_id, exch_index)) = due else {
+            return Ok(());
+        };
+
+        // `unwrap` is safe because we just found the session
+        let session = unwrap!(session_mgr.get(session_id));
+
+        self.encode_packet(packet, Some(session), Some(exch_index), |_| {
+            Ok(Some(OpCode::MRPStandAloneAck.into()))
+        })?;
+
+        // The exchange might still have more displaced ACKs, or a newer ACK which is not due yet
+        let session = unwrap!(session_mgr.get(session_id));
+        if let Some(due_ms) = session.exchanges[exch_index]
+            .as_ref()
+            .and_then(|exch| exch.mrp.standalone_ack_due_ms(epoch))
+        {
+            self.schedule_ack_at(now_ms + due_ms);
+        }
+
+        Ok(())
+    }
+
     pub(crate) async fn evict_some_session(&self) -> Result<(), Error> {
         let mut tx = self.get_if(&self.tx, |packet| packet.buf.is_empty()).await;
         tx.clear_on_drop(true); // By default, if an error occurs
@@ -824,13 +1169,37 @@ impl<'m> TransportMgr<'m> {
             packet.buf.truncate(end);
         };
 
+        if packet.header.plain.is_privacy() {
+            // The message counter and the node IDs are obfuscated, so de-obfuscate them
+            // with the privacy key of the session and decode the plain header again
+            let hdr_len = pb.parsed_as_slice().len();
+
+            let Some(privacy_key) = session_mgr.get_privacy_key_for_rx(packet.header.plain.sess_id)
+            else {
+                set_payload(packet, (0, 0));
+                Err(ErrorCode::NoSession)?
+            };
+
+            PlainHdr::privacy_crypt_in_place(
+                &mut packet.buf[packet.payload_start..],
+                hdr_len,
+                &privacy_key,
+            )?;
+
+            pb = ParseBuf::new(&mut packet.buf[packet.payload_start..]);
+            packet.header.plain.decode(&mut pb)?;
+        }
+
         if let Some(session) = session_mgr.get_for_rx(&packet.peer, &packet.header.plain) {
             // Found existing session: decode, indicate packet payload slice and process further
 
             let payload_range = session.decode_remaining(&mut packet.header, pb)?;
             set_payload(packet, payload_range);
 
-            return session.post_recv(&packet.header, epoch);
+            let new_exchange = session.post_recv(&packet.header, epoch)?;
+            self.schedule_ack(session, &packet.header.proto, epoch);
+
+            return Ok(new_exchange);
         }
 
         // No existing session: we either have to create one, or return an error
@@ -852,7 +1221,10 @@ impl<'m> TransportMgr<'m> {
                     session_mgr.add(false, packet.peer, packet.header.plain.get_src_nodeid())?;
 
                 // Session created successfully: decode, indicate packet payload slice and process further
-                return session.post_recv(&packet.header, epoch);
+                let new_exchange = session.post_recv(&packet.header, epoch)?;
+                self.schedule_ack(session, &packet.header.proto, epoch);
+
+                return Ok(new_exchange);
             }
         } else {
             // Packet cannot be decoded, set packet payload to empty
@@ -893,12 +1265,14 @@ impl<'m> TransportMgr<'m> {
             let (peer, retransmission) = session.pre_send(
                 exchange_index,
                 &mut packet.header,
-                self.device_sai,
-                self.device_sii,
+                self.device_privacy,
+                self.epoch,
             )?;
 
             packet.peer = peer;
 
+            self.ack_stats.borrow_mut().update(&packet.header.proto);
+
             retransmission
         } else {
             if packet.header.plain.is_encrypted()
@@ -914,7 +1288,7 @@ impl<'m> TransportMgr<'m> {
             packet.header.plain = Default::default();
 
             packet.header.plain.sess_id = 0;
-            packet.header.plain.ctr = 1;
+            packet.header.plain.ctr = self.counters.borrow_mut().next_unencrypted();
             packet.header.plain.set_src_nodeid(None);
             packet.header.plain.set_dst_unicast_nodeid(src_nodeid);
 
@@ -939,10 +1313,19 @@ impl<'m> TransportMgr<'m> {
             Packet::<0>::display_payload(&packet.header.proto, wb.as_slice())
         );
 
+        self.capture_packet(
+            CaptureDirection::Tx,
+            packet.peer,
+            &packet.header,
+            wb.as_slice(),
+            session.as_deref(),
+            false,
+        );
+
         if let Some(session) = session {
             session.encode(&packet.header, &mut wb)?;
         } else {
-            packet.header.encode(&mut wb, 0, None)?;
+            packet.header.encode(&mut wb, 0, None, None)?;
         }
 
         let range = (wb.get_start(), wb.get_tail());
@@ -958,9 +1341,11 @@ impl<'m> TransportMgr<'m> {
         packet: &mut Packet<N>,
     ) -> Result<bool, Error> {
         let mut session_mgr = self.session_mgr.borrow_mut();
-        let id = session_mgr.get_session_for_eviction().map(|sess| sess.id);
+        let id = session_mgr
+            .get_session_for_eviction(self.eviction_policy)
+            .map(|sess| sess.id);
         if let Some(id) = id {
-            self.encode_evict_session(packet, &mut session_mgr, id)?;
+            self.encode_evict_session(packet, &mut session_mgr, id, SessionRemovalReason::Evicted)?;
 
             Ok(true)
         } else {
@@ -975,21 +1360,28 @@ impl<'m> TransportMgr<'m> {
         packet: &mut Packet<N>,
         session_mgr: &mut SessionMgr,
         id: u32,
+        reason: SessionRemovalReason,
     ) -> Result<(), Error> {
         packet.header.proto.exch_id = session_mgr.get_next_exch_id();
         packet.header.proto.set_initiator();
 
         // It is a responsibility of the caller to ensure that this method is called with a valid session ID
         let mut session = unwrap!(session_mgr.remove(id));
-        self.session_removed.notify();
+        self.notify_session_removed(&session, reason);
 
         debug!(
-            "Evicting session {} [SID:{:x},RSID:{:x}]",
+            "Closing session {} [SID:{:x},RSID:{:x}], reason: {:?}",
             session.id,
             session.get_local_sess_id(),
-            session.get_peer_sess_id()
+            session.get_peer_sess_id(),
+            reason
         );
 
+        if !session.is_encrypted() {
+            // No point in sending `CloseSession` over an unencrypted session
+            return Ok(());
+        }
+
         self.encode_packet(packet, Some(&mut session), None, |wb| {
             sc_write(wb, SCStatusCodes::CloseSession, &[])
         })?;
@@ -997,6 +1389,42 @@ impl<'m> TransportMgr<'m> {
         Ok(())
     }
 
+    /// Close the first expired session which does not have any ongoing exchanges.
+    ///
+    /// Return `true` if there are no more such sessions.
+    fn handle_expired_session<const N: usize>(
+        &self,
+        packet: &mut Packet<N>,
+    ) -> Result<bool, Error> {
+        let mut session_mgr = self.session_mgr.borrow_mut();
+
+        let expired = session_mgr
+            .iter()
+            .find(|sess| sess.is_expired() && !sess.has_exchanges())
+            .map(|sess| (sess.id, unwrap!(sess.expiry_reason())));
+
+        if let Some((id, reason)) = expired {
+            self.encode_evict_session(packet, &mut session_mgr, id, reason)?;
+        }
+
+        Ok(expired.is_none())
+    }
+
+    fn notify_session_removed(&self, session: &Session, reason: SessionRemovalReason) {
+        let mut removed_sessions = self.removed_sessions.borrow_mut();
+
+        if removed_sessions.is_full() {
+            removed_sessions.pop_front();
+        }
+
+        unwrap!(removed_sessions
+            .push_back(SessionRemoval::new(session, reason))
+            .map_err(|_| ()));
+
+        self.session_removed.notify();
+        self.session_removals.notify();
+    }
+
     fn is_close_session(payload: &mut [u8]) -> Result<bool, Error> {
         let mut pb = ParseBuf::new(payload);
         let report = StatusReport::read(&mut pb)?;
@@ -1399,3 +1827,110 @@ impl<const N: usize> Drop for ExternalPacketBuffer<'_, N> {
         self.0.buf.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU8;
+
+    use crate::mdns::MdnsService;
+    use crate::secure_channel::common::{OpCode, PROTO_ID_SECURE_CHANNEL};
+    use crate::test_device::{TEST_DEV_ATT, TEST_DEV_COMM, TEST_DEV_DET};
+    use crate::transport::exchange::Role;
+    use crate::transport::mrp::MrpParameters;
+    use crate::transport::network::{Address, MAX_TX_PACKET_SIZE};
+    use crate::transport::session::{ReservedSession, SessionMode, SessionRemovalReason};
+    use crate::utils::epoch::dummy_epoch;
+    use crate::utils::rand::dummy_rand;
+    use crate::{Matter, MATTER_PORT};
+
+    use super::Packet;
+
+    fn add_case_session(matter: &Matter<'_>, fab_idx: u8, local_sess_id: u16) -> u32 {
+        let mut session = unwrap!(ReservedSession::reserve_now(matter));
+
+        unwrap!(session.update(
+            1,
+            2,
+            local_sess_id,
+            local_sess_id,
+            Address::default(),
+            SessionMode::Case {
+                fab_idx: unwrap!(NonZeroU8::new(fab_idx)),
+                cat_ids: Default::default(),
+            },
+            Some(&[1; 16]),
+            Some(&[2; 16]),
+            None,
+            &MrpParameters::new(),
+        ));
+
+        session.complete();
+
+        unwrap!(matter
+            .transport_mgr
+            .session_mgr
+            .borrow()
+            .iter()
+            .find(|sess| sess.get_local_sess_id() == local_sess_id)
+            .map(|sess| sess.id()))
+    }
+
+    #[test]
+    fn test_close_expired_sessions() {
+        let matter = Matter::new(
+            &TEST_DEV_DET,
+            TEST_DEV_COMM,
+            &TEST_DEV_ATT,
+            MdnsService::Disabled,
+            dummy_epoch,
+            dummy_rand,
+            MATTER_PORT,
+        );
+
+        let transport = &matter.transport_mgr;
+
+        let s1 = add_case_session(&matter, 1, 10);
+        let s2 = add_case_session(&matter, 2, 20);
+
+        // An expired session with an ongoing exchange is only closed once the exchange is complete
+        let exch_index = unwrap!(unwrap!(transport.session_mgr.borrow_mut().get(s1))
+            .add_exch(1, Role::Responder(Default::default())));
+
+        assert_eq!(
+            transport.close_sessions_for_fabric(
+                unwrap!(NonZeroU8::new(1)),
+                SessionRemovalReason::FabricRemoved
+            ),
+            1
+        );
+
+        let mut packet = Packet::<MAX_TX_PACKET_SIZE>::new();
+
+        assert!(unwrap!(transport.handle_expired_session(&mut packet)));
+        assert!(packet.buf.is_empty());
+        assert!(transport.session_mgr.borrow_mut().get(s1).is_some());
+
+        assert!(unwrap!(transport.session_mgr.borrow_mut().get(s1)).remove_exch(exch_index));
+
+        // The session is removed and a `CloseSession` status report is sent to the peer
+        assert!(!unwrap!(transport.handle_expired_session(&mut packet)));
+        assert!(!packet.buf.is_empty());
+        assert_eq!(packet.header.plain.sess_id, 10);
+        assert_eq!(packet.header.proto.proto_id, PROTO_ID_SECURE_CHANNEL);
+        assert_eq!(packet.header.proto.proto_opcode, OpCode::StatusReport as u8);
+        assert!(packet.header.proto.is_initiator());
+
+        assert!(transport.session_mgr.borrow_mut().get(s1).is_none());
+        assert!(transport.session_mgr.borrow_mut().get(s2).is_some());
+
+        let removal = unwrap!(transport.removed_sessions.borrow_mut().pop_front());
+        assert_eq!(removal.session_id, s1);
+        assert_eq!(removal.fabric_idx, 1);
+        assert_eq!(removal.reason, SessionRemovalReason::FabricRemoved);
+
+        // No more expired sessions
+        packet.buf.clear();
+        assert!(unwrap!(transport.handle_expired_session(&mut packet)));
+        assert!(packet.buf.is_empty());
+    }
+}
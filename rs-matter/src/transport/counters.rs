@@ -0,0 +1,599 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Global message counters (as opposed to the per-session ones) and the
+//! per-peer group message counter tables.
+//!
+//! The global group counters are persisted with a lookahead, so that no counter value
+//! is ever reused after a reboot, while not having to persist the counters on each message.
+//! A counter never goes past its last limit which is known to be persisted.
+
+use core::num::NonZeroU8;
+
+use crate::error::{Error, ErrorCode};
+use crate::tlv::{FromTLV, TLVElement, TLVTag, ToTLV};
+use crate::utils::init::{init, Init};
+use crate::utils::rand::Rand;
+use crate::utils::storage::{Vec, WriteBuf};
+
+use super::dedup::RxCtrState;
+
+/// The number of counter values reserved each time a persistent counter is persisted
+pub const MSG_COUNTER_PERSIST_LOOKAHEAD: u32 = 1000;
+
+/// The maximum number of peers for which group message counters are tracked
+pub const MAX_GROUP_PEERS: usize = 8;
+
+/// The length of the challenge of a Message Counter Synchronization Protocol (MCSP) request
+pub const MCSP_CHALLENGE_LEN: usize = 8;
+
+const MSG_CTR_RANGE: u32 = 0x0fffffff;
+
+/// A message counter which is persisted with a lookahead of `MSG_COUNTER_PERSIST_LOOKAHEAD`.
+///
+/// Only the upper limit of the counter is persisted. Once the counter gets within half of the lookahead
+/// from the limit, a new limit is reserved and the counter is marked as changed. This leaves
+/// the other half of the lookahead to be used while the new limit is being persisted.
+///
+/// The counter never goes past the last limit confirmed as persisted with `PersistentMsgCounter::stored`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PersistentMsgCounter {
+    value: u32,
+    limit: u32,
+    stored_limit: u32,
+}
+
+impl PersistentMsgCounter {
+    /// Create a new counter, starting from the provided value
+    ///
+    /// The counter cannot be used until a limit is reserved and persisted.
+    pub const fn new(value: u32) -> Self {
+        Self {
+            value,
+            limit: value,
+            stored_limit: value,
+        }
+    }
+
+    /// Return the current value of the counter, without incrementing it
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// Return the current value of the counter and increment it, or `None` if the counter
+    /// had reached the persisted limit.
+    pub fn next_value(&mut self) -> Option<u32> {
+        if self.stored_limit.wrapping_sub(self.value) == 0
+            || self.stored_limit.wrapping_sub(self.value) > MSG_COUNTER_PERSIST_LOOKAHEAD
+        {
+            return None;
+        }
+
+        let value = self.value;
+
+        self.value = self.value.wrapping_add(1);
+
+        if self.needs_reservation() {
+            self.reserve();
+        }
+
+        Some(value)
+    }
+
+    /// Return the limit which should be persisted
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Reserve a new limit, a full lookahead away from the current value of the counter
+    pub fn reserve(&mut self) {
+        self.limit = self.value.wrapping_add(MSG_COUNTER_PERSIST_LOOKAHEAD);
+    }
+
+    /// Confirm that the provided limit (as returned by `PersistentMsgCounter::limit`) is persisted,
+    /// so that the counter can go up to it.
+    pub fn stored(&mut self, limit: u32) {
+        self.stored_limit = limit;
+    }
+
+    /// Restore the counter from a persisted limit.
+    ///
+    /// As all counter values below the limit might have been used before the reboot,
+    /// the counter continues from the limit itself.
+    pub fn load(&mut self, limit: u32) {
+        self.value = limit;
+        self.limit = limit;
+        self.stored_limit = limit;
+    }
+
+    fn needs_reservation(&self) -> bool {
+        self.limit.wrapping_sub(self.value) < MSG_COUNTER_PERSIST_LOOKAHEAD / 2
+            || self.limit.wrapping_sub(self.value) > MSG_COUNTER_PERSIST_LOOKAHEAD
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[tlvargs(start = 1)]
+struct PersistedMsgCounters {
+    group_data_limit: u32,
+    group_control_limit: u32,
+}
+
+/// The global message counters of the node, as per the Matter spec:
+/// - The Global Unencrypted Message Counter, which is random on each boot and is not persisted
+/// - The Global Group Encrypted Data Message Counter and the Global Group Encrypted Control Message Counter,
+///   which are persisted with a lookahead
+///
+/// The counters are lazily initialized to random values on first use, unless they are loaded
+/// from persistent storage before that.
+#[derive(Debug, Clone)]
+pub struct MsgCounters {
+    unencrypted: u32,
+    group_data: PersistentMsgCounter,
+    group_control: PersistentMsgCounter,
+    initialized: bool,
+    changed: bool,
+    storing: Option<PersistedMsgCounters>,
+    rand: Rand,
+}
+
+impl MsgCounters {
+    /// Create a new instance
+    #[inline(always)]
+    pub const fn new(rand: Rand) -> Self {
+        Self {
+            unencrypted: 0,
+            group_data: PersistentMsgCounter::new(0),
+            group_control: PersistentMsgCounter::new(0),
+            initialized: false,
+            changed: false,
+            storing: None,
+            rand,
+        }
+    }
+
+    /// Re-initialize all counters to random values
+    pub fn reset(&mut self) {
+        self.unencrypted = self.rand_ctr();
+        self.group_data = PersistentMsgCounter::new(self.rand_ctr());
+        self.group_control = PersistentMsgCounter::new(self.rand_ctr());
+
+        self.reserve();
+    }
+
+    /// Return the next value of the Global Unencrypted Message Counter
+    pub fn next_unencrypted(&mut self) -> u32 {
+        self.ensure_initialized();
+
+        let value = self.unencrypted;
+        self.unencrypted = self.unencrypted.wrapping_add(1);
+
+        value
+    }
+
+    /// Return the next value of the Global Group Encrypted Data Message Counter
+    ///
+    /// Return an error with code `ErrorCode::Busy` if the counter had reached its persisted limit,
+    /// in which case no group data message can be sent until the new limit is persisted.
+    /// When the method returns, `MsgCounters::is_changed` indicates if a new limit needs to be persisted.
+    pub fn next_group_data(&mut self) -> Result<u32, Error> {
+        self.ensure_initialized();
+
+        Self::next_persistent(&mut self.group_data, &mut self.changed)
+    }
+
+    /// Return the next value of the Global Group Encrypted Control Message Counter
+    ///
+    /// Same as `MsgCounters::next_group_data`, but for group control messages.
+    pub fn next_group_control(&mut self) -> Result<u32, Error> {
+        self.ensure_initialized();
+
+        Self::next_persistent(&mut self.group_control, &mut self.changed)
+    }
+
+    /// Return the current value of the Global Group Encrypted Control Message Counter,
+    /// without incrementing it
+    pub fn group_control(&mut self) -> u32 {
+        self.ensure_initialized();
+
+        self.group_control.value()
+    }
+
+    /// Return `true` if the persisted counter limits had changed since the last store operation
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Load the persisted counter limits from the provided TLV data
+    pub fn load(&mut self, data: &[u8]) -> Result<(), Error> {
+        let persisted = PersistedMsgCounters::from_tlv(&TLVElement::new(data))?;
+
+        self.unencrypted = self.rand_ctr();
+        self.group_data.load(persisted.group_data_limit);
+        self.group_control.load(persisted.group_control_limit);
+
+        self.reserve();
+
+        Ok(())
+    }
+
+    /// Store the counter limits into the provided buffer as TLV data
+    ///
+    /// If the limits have not changed since the last store operation, the
+    /// function returns `None` and does not store the limits.
+    ///
+    /// Once the returned data is persisted, `MsgCounters::stored` must be called, as the counters
+    /// do not go past the limits before that.
+    pub fn store<'a>(&mut self, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if !self.changed {
+            return Ok(None);
+        }
+
+        let mut wb = WriteBuf::new(buf);
+
+        let persisted = PersistedMsgCounters {
+            group_data_limit: self.group_data.limit(),
+            group_control_limit: self.group_control.limit(),
+        };
+
+        persisted
+            .to_tlv(&TLVTag::Anonymous, &mut wb)
+            .map_err(|_| ErrorCode::NoSpace)?;
+
+        self.changed = false;
+        self.storing = Some(persisted);
+
+        let len = wb.get_tail();
+
+        Ok(Some(&buf[..len]))
+    }
+
+    /// Confirm that the counter limits returned by the last `MsgCounters::store` call are persisted
+    pub fn stored(&mut self) {
+        if let Some(persisted) = self.storing.take() {
+            self.group_data.stored(persisted.group_data_limit);
+            self.group_control.stored(persisted.group_control_limit);
+        }
+    }
+
+    fn ensure_initialized(&mut self) {
+        if !self.initialized {
+            self.reset();
+        }
+    }
+
+    // Reserve new lookahead windows right away, so that the counter values
+    // used until the next store operation are not reused after a reboot
+    fn reserve(&mut self) {
+        self.group_data.reserve();
+        self.group_control.reserve();
+
+        self.initialized = true;
+        self.changed = true;
+        self.storing = None;
+    }
+
+    fn next_persistent(
+        counter: &mut PersistentMsgCounter,
+        changed: &mut bool,
+    ) -> Result<u32, Error> {
+        let limit = counter.limit();
+        let value = counter.next_value();
+
+        if counter.limit() != limit {
+            *changed = true;
+        }
+
+        value.ok_or_else(|| {
+            warn!("Group message counter reached its persisted limit");
+            ErrorCode::Busy.into()
+        })
+    }
+
+    fn rand_ctr(&self) -> u32 {
+        let mut buf = [0; 4];
+        (self.rand)(&mut buf);
+        u32::from_be_bytes(buf) & MSG_CTR_RANGE
+    }
+}
+
+/// The outcome of processing the counter of a received group message
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GroupRxOutcome {
+    /// The message is not a duplicate and should be processed
+    Accept,
+    /// The message is a duplicate and should be dropped
+    Duplicate,
+    /// The counter of the peer is not known yet; the message should be held (or dropped)
+    /// until the counter is synchronized with the Message Counter Synchronization Protocol (MCSP)
+    SyncRequired,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct GroupPeer {
+    fabric_idx: NonZeroU8,
+    peer_node_id: u64,
+    data: Option<RxCtrState>,
+    control: Option<RxCtrState>,
+    challenge: Option<[u8; MCSP_CHALLENGE_LEN]>,
+}
+
+/// A table tracking the group message counters of up to `N` peers.
+///
+/// As per the spec, data messages from unknown peers are accepted with a "trust-first" policy,
+/// while control messages from unknown peers require synchronizing the peer counter
+/// with the Message Counter Synchronization Protocol first.
+///
+/// When the table is full, the least recently added peer is forgotten.
+pub struct GroupPeerCounters<const N: usize> {
+    peers: Vec<GroupPeer, N>,
+}
+
+impl<const N: usize> Default for GroupPeerCounters<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> GroupPeerCounters<N> {
+    /// Create a new, empty table
+    pub const fn new() -> Self {
+        Self { peers: Vec::new() }
+    }
+
+    /// Return an in-place initializer for a new, empty table
+    pub fn init() -> impl Init<Self> {
+        init!(Self {
+            peers <- Vec::init(),
+        })
+    }
+
+    /// Process the counter of a group message received from the provided peer.
+    pub fn post_recv(
+        &mut self,
+        fabric_idx: NonZeroU8,
+        peer_node_id: u64,
+        msg_ctr: u32,
+        control: bool,
+    ) -> GroupRxOutcome {
+        let peer = self.get_or_add(fabric_idx, peer_node_id);
+
+        let state = if control {
+            &mut peer.control
+        } else {
+            &mut peer.data
+        };
+
+        match state {
+            Some(state) => {
+                if state.post_recv(msg_ctr, true) {
+                    GroupRxOutcome::Accept
+                } else {
+                    GroupRxOutcome::Duplicate
+                }
+            }
+            None if control => GroupRxOutcome::SyncRequired,
+            None => {
+                // Trust-first
+                *state = Some(RxCtrState::new(msg_ctr));
+
+                GroupRxOutcome::Accept
+            }
+        }
+    }
+
+    /// Start a counter synchronization with the provided peer.
+    ///
+    /// Return the challenge which should be sent to the peer in a `MsgCounterSyncReq` message.
+    pub fn start_sync(
+        &mut self,
+        fabric_idx: NonZeroU8,
+        peer_node_id: u64,
+        rand: Rand,
+    ) -> [u8; MCSP_CHALLENGE_LEN] {
+        let mut challenge = [0; MCSP_CHALLENGE_LEN];
+        rand(&mut challenge);
+
+        self.get_or_add(fabric_idx, peer_node_id).challenge = Some(challenge);
+
+        challenge
+    }
+
+    /// Complete the counter synchronization with the provided peer, using the data from
+    /// the `MsgCounterSyncResp` message sent by the peer.
+    ///
+    /// Return an error if there is no synchronization in progress with the peer,
+    /// or if the response does not match the challenge sent to the peer.
+    pub fn complete_sync(
+        &mut self,
+        fabric_idx: NonZeroU8,
+        peer_node_id: u64,
+        synchronized_ctr: u32,
+        response: &[u8],
+    ) -> Result<(), Error> {
+        let peer = self
+            .peers
+            .iter_mut()
+            .find(|peer| peer.fabric_idx == fabric_idx && peer.peer_node_id == peer_node_id)
+            .ok_or(ErrorCode::NotFound)?;
+
+        if peer.challenge.as_ref().map(|challenge| &challenge[..]) != Some(response) {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        peer.challenge = None;
+        peer.control = Some(RxCtrState::new_synchronized(synchronized_ctr));
+
+        Ok(())
+    }
+
+    /// Forget all peers of the provided fabric (i.e. when the fabric is removed)
+    pub fn remove_for_fabric(&mut self, fabric_idx: NonZeroU8) {
+        self.peers.retain(|peer| peer.fabric_idx != fabric_idx);
+    }
+
+    fn get_or_add(&mut self, fabric_idx: NonZeroU8, peer_node_id: u64) -> &mut GroupPeer {
+        let index = self
+            .peers
+            .iter()
+            .position(|peer| peer.fabric_idx == fabric_idx && peer.peer_node_id == peer_node_id);
+
+        let index = if let Some(index) = index {
+            index
+        } else {
+            if self.peers.is_full() {
+                self.peers.remove(0);
+            }
+
+            unwrap!(self
+                .peers
+                .push(GroupPeer {
+                    fabric_idx,
+                    peer_node_id,
+                    data: None,
+                    control: None,
+                    challenge: None,
+                })
+                .map_err(|_| ()));
+
+            self.peers.len() - 1
+        };
+
+        &mut self.peers[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU8;
+
+    use crate::utils::rand::dummy_rand;
+
+    use super::{
+        GroupPeerCounters, GroupRxOutcome, MsgCounters, PersistentMsgCounter,
+        MSG_COUNTER_PERSIST_LOOKAHEAD,
+    };
+
+    #[test]
+    fn test_persistent_counter() {
+        const LOOKAHEAD: u32 = MSG_COUNTER_PERSIST_LOOKAHEAD;
+
+        let mut ctr = PersistentMsgCounter::new(100);
+
+        // Nothing is persisted yet
+        assert_eq!(ctr.next_value(), None);
+
+        ctr.reserve();
+        assert_eq!(ctr.limit(), 100 + LOOKAHEAD);
+        ctr.stored(ctr.limit());
+
+        assert_eq!(ctr.next_value(), Some(100));
+
+        // No new reservation until we get within half of the lookahead
+        for _ in 0..LOOKAHEAD / 2 - 1 {
+            assert!(ctr.next_value().is_some());
+        }
+        assert_eq!(ctr.value(), 100 + LOOKAHEAD / 2);
+        assert_eq!(ctr.limit(), 100 + LOOKAHEAD);
+
+        assert!(ctr.next_value().is_some());
+        assert_eq!(ctr.value(), 101 + LOOKAHEAD / 2);
+        assert_eq!(ctr.limit(), 101 + LOOKAHEAD / 2 + LOOKAHEAD);
+
+        // The new limit is not persisted yet, so the counter stops at the old one
+        while ctr.next_value().is_some() {}
+        assert_eq!(ctr.value(), 100 + LOOKAHEAD);
+
+        ctr.stored(ctr.limit());
+        assert_eq!(ctr.next_value(), Some(100 + LOOKAHEAD));
+    }
+
+    #[test]
+    fn test_counters_persistence() {
+        let mut counters = MsgCounters::new(dummy_rand);
+        let mut buf = [0; 32];
+        assert!(counters.store(&mut buf).unwrap().is_none());
+
+        // The initial limits are not persisted yet
+        assert!(counters.next_group_control().is_err());
+        assert!(counters.is_changed());
+
+        let stored = counters.store(&mut buf).unwrap().unwrap().to_vec();
+        assert!(!counters.is_changed());
+        assert!(counters.store(&mut buf).unwrap().is_none());
+        assert!(counters.next_group_control().is_err());
+
+        counters.stored();
+        let used = counters.next_group_control().unwrap();
+        assert!(counters.next_group_data().is_ok());
+
+        // After a "reboot", the counter never goes back
+        let mut counters = MsgCounters::new(dummy_rand);
+        counters.load(&stored).unwrap();
+        assert!(counters.is_changed());
+        assert!(counters.next_group_control().is_err());
+
+        counters.store(&mut buf).unwrap().unwrap();
+        counters.stored();
+        assert!(counters.next_group_control().unwrap() > used + 1);
+    }
+
+    #[test]
+    fn test_group_peers() {
+        let fab1 = NonZeroU8::new(1).unwrap();
+        let mut peers = GroupPeerCounters::<2>::new();
+
+        // Trust-first for data messages
+        assert_eq!(peers.post_recv(fab1, 1, 10, false), GroupRxOutcome::Accept);
+        assert_eq!(
+            peers.post_recv(fab1, 1, 10, false),
+            GroupRxOutcome::Duplicate
+        );
+        assert_eq!(peers.post_recv(fab1, 1, 11, false), GroupRxOutcome::Accept);
+
+        // Synchronization for control messages
+        assert_eq!(
+            peers.post_recv(fab1, 1, 500, true),
+            GroupRxOutcome::SyncRequired
+        );
+
+        let challenge = peers.start_sync(fab1, 1, dummy_rand);
+        let mut wrong = challenge;
+        wrong[0] ^= 0xff;
+        assert!(peers.complete_sync(fab1, 1, 499, &wrong).is_err());
+        peers.complete_sync(fab1, 1, 499, &challenge).unwrap();
+        assert!(peers.complete_sync(fab1, 1, 499, &challenge).is_err());
+
+        assert_eq!(peers.post_recv(fab1, 1, 500, true), GroupRxOutcome::Accept);
+        assert_eq!(
+            peers.post_recv(fab1, 1, 499, true),
+            GroupRxOutcome::Duplicate
+        );
+
+        // Oldest peers are forgotten when the table is full
+        assert_eq!(peers.post_recv(fab1, 2, 1, false), GroupRxOutcome::Accept);
+        assert_eq!(peers.post_recv(fab1, 3, 1, false), GroupRxOutcome::Accept);
+        assert_eq!(
+            peers.post_recv(fab1, 1, 600, true),
+            GroupRxOutcome::SyncRequired
+        );
+    }
+}
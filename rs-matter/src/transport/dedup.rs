@@ -32,6 +32,15 @@ impl RxCtrState {
         }
     }
 
+    /// Create a state synchronized with the next message counter of the peer (i.e. with MCSP)
+    ///
+    /// Unlike with `RxCtrState::new`, the counters within the window behind the synchronized counter
+    /// are not considered as received yet, so that the messages which the peer had sent
+    /// while the synchronization was in progress are still accepted.
+    pub fn new_synchronized(next_ctr: u32) -> Self {
+        Self::new(next_ctr.wrapping_sub(MSG_RX_STATE_BITMAP_LEN + 1))
+    }
+
     fn contains(&self, bit_number: u32) -> bool {
         (self.ctr_bitmap & (1 << bit_number)) != 0
     }
@@ -205,4 +214,18 @@ mod tests {
         assert_ndup(s.post_recv(20011, NOT_ENCRYPTED));
         assert_ndup(s.post_recv(0, NOT_ENCRYPTED));
     }
+
+    #[test]
+    fn encrypted_synchronized() {
+        let mut s = RxCtrState::new_synchronized(100);
+
+        assert_dup(s.post_recv(83, ENCRYPTED));
+        assert_ndup(s.post_recv(90, ENCRYPTED));
+        assert_ndup(s.post_recv(99, ENCRYPTED));
+        assert_dup(s.post_recv(99, ENCRYPTED));
+        assert_ndup(s.post_recv(95, ENCRYPTED));
+        assert_dup(s.post_recv(95, ENCRYPTED));
+        assert_ndup(s.post_recv(100, ENCRYPTED));
+        assert_dup(s.post_recv(100, ENCRYPTED));
+    }
 }
@@ -29,8 +29,9 @@ use crate::utils::epoch::Epoch;
 use crate::utils::storage::WriteBuf;
 use crate::Matter;
 
+use super::capture::CaptureDirection;
 use super::core::{Packet, PacketAccess, MAX_RX_BUF_SIZE, MAX_TX_BUF_SIZE};
-use super::mrp::{ReliableMessage, RetransEntry};
+use super::mrp::{MrpParameters, ReliableMessage, RetransEntry};
 use super::network;
 use super::packet::PacketHdr;
 use super::plain_hdr::PlainHdr;
@@ -102,7 +103,14 @@ impl ExchangeId {
             let mut session_removed = pin!(transport_mgr.session_removed.wait());
 
             let mut timeout = pin!(Timer::after(Duration::from_millis(
-                RetransEntry::new(matter.dev_det().sai, 0).max_delay_ms() * 3 / 2
+                // The peer retransmits towards us using our own MRP parameters
+                RetransEntry::new(
+                    MrpParameters::local(matter.dev_det()).active_interval_ms(),
+                    0
+                )
+                .max_delay_ms()
+                    * 3
+                    / 2
             )));
 
             match select3(&mut recv, &mut session_removed, &mut timeout).await {
@@ -417,8 +425,7 @@ impl ExchangeState {
         &mut self,
         tx_plain: &PlainHdr,
         tx_proto: &mut ProtoHdr,
-        session_active_interval_ms: Option<u16>,
-        session_idle_interval_ms: Option<u16>,
+        retrans_interval_ms: u32,
     ) -> Result<(), Error> {
         if matches!(self.role, Role::Initiator(_)) {
             tx_proto.set_initiator();
@@ -428,12 +435,7 @@ impl ExchangeState {
 
         tx_proto.exch_id = self.exch_id;
 
-        self.mrp.pre_send(
-            tx_plain,
-            tx_proto,
-            session_active_interval_ms,
-            session_idle_interval_ms,
-        )
+        self.mrp.pre_send(tx_plain, tx_proto, retrans_interval_ms)
     }
 
     pub fn retrans_delay_ms(&mut self, jitter_rand: u8) -> Option<u64> {
@@ -651,6 +653,8 @@ impl TxMessage<'_> {
 
         let mut session_mgr = self.matter.transport_mgr.session_mgr.borrow_mut();
 
+        let epoch = session_mgr.epoch;
+
         let session = session_mgr
             .get(self.exchange_id.session_id())
             .ok_or(ErrorCode::NoSession)?;
@@ -658,18 +662,18 @@ impl TxMessage<'_> {
         let (peer, retransmission) = session.pre_send(
             Some(self.exchange_id.exchange_index()),
             &mut self.packet.header,
-            // NOTE: It is not entirely correct to use our own SAI/SII when sending to a peer,
-            // as the peer might be slower than us
-            //
-            // However, given that for now `rs-matter` would be in the role of the device rather
-            // than a controller, that's a good-enough approximation (i.e. if we are running on Thread,
-            // the controller would either be running on Thread as well, or on a network faster than ours)
-            self.matter.dev_det().sai,
-            self.matter.dev_det().sii,
+            self.matter.dev_det().privacy,
+            epoch,
         )?;
 
         self.packet.peer = peer;
 
+        self.matter
+            .transport_mgr
+            .ack_stats
+            .borrow_mut()
+            .update(&self.packet.header.proto);
+
         debug!(
             "\n<<SND {}\n      => {}",
             Packet::<0>::display(&self.packet.peer, &self.packet.header),
@@ -689,6 +693,16 @@ impl TxMessage<'_> {
             )
         );
 
+        self.matter.transport_mgr.capture_packet(
+            CaptureDirection::Tx,
+            self.packet.peer,
+            &self.packet.header,
+            &self.packet.buf
+                [PacketHdr::HDR_RESERVE + payload_start..PacketHdr::HDR_RESERVE + payload_end],
+            Some(session),
+            false,
+        );
+
         let packet = &mut *self.packet;
 
         let mut writebuf = WriteBuf::new_with(
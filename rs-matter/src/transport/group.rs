@@ -0,0 +1,542 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Group messages
+//!
+//! Group messages are encrypted with an operational group key, and carry the Group Session ID
+//! derived from that key. They are not tied to a unicast session or to an exchange, and are never acknowledged.
+//!
+//! Outgoing group messages use the Global Group Encrypted Data (or Control) Message Counter of the node.
+//! Incoming group messages are checked against the group message counters of their sender, which - for
+//! control messages - are synchronized with the Message Counter Synchronization Protocol (MCSP) first.
+//!
+//! If message privacy is enabled for the device, the headers of outgoing group messages are obfuscated with the
+//! privacy key derived from the operational group key. Incoming group messages using message privacy are always accepted.
+//!
+//! NOTE: The group key sets are not managed by the Group Key Management cluster yet, so the operational group keys
+//! are provided by the user, and group messages are encoded and decoded with `TransportMgr::encode_group_message`
+//! and `TransportMgr::decode_group_message` rather than by the exchange layer.
+
+use core::num::NonZeroU8;
+
+use crate::crypto::{self, SYMM_KEY_LEN_BYTES};
+use crate::error::{Error, ErrorCode};
+use crate::fabric::Fabric;
+use crate::group_keys::KeySet;
+use crate::secure_channel::crypto::privacy_key;
+use crate::secure_channel::mcsp::Mcsp;
+use crate::utils::storage::{ParseBuf, WriteBuf};
+use crate::Matter;
+
+use super::core::TransportMgr;
+use super::counters::GroupRxOutcome;
+use super::exchange::{Exchange, MessageMeta};
+use super::packet::PacketHdr;
+use super::plain_hdr::PlainHdr;
+
+/// The info string used for deriving the Group Session ID from an operational group key
+const GROUP_KEY_HASH_INFO: &[u8] = b"GroupKeyHash";
+
+/// An operational group key of a fabric, along with the Group Session ID and the privacy key derived from it
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GroupKey {
+    fab_idx: NonZeroU8,
+    session_id: u16,
+    op_key: [u8; SYMM_KEY_LEN_BYTES],
+    privacy_key: [u8; SYMM_KEY_LEN_BYTES],
+}
+
+impl GroupKey {
+    /// Create a group key of the fabric with the provided local index from an operational group key
+    pub fn new(fab_idx: NonZeroU8, op_key: &[u8]) -> Result<Self, Error> {
+        let op_key: [u8; SYMM_KEY_LEN_BYTES] =
+            op_key.try_into().map_err(|_| ErrorCode::InvalidData)?;
+
+        let mut hash = [0; 2];
+        crypto::hkdf_sha256(&[], &op_key, GROUP_KEY_HASH_INFO, &mut hash)?;
+
+        let mut privacy = [0; SYMM_KEY_LEN_BYTES];
+        privacy_key(&op_key, &mut privacy)?;
+
+        Ok(Self {
+            fab_idx,
+            session_id: u16::from_be_bytes(hash),
+            op_key,
+            privacy_key: privacy,
+        })
+    }
+
+    /// Create a group key of the provided fabric from an epoch key of one of its group key sets
+    pub fn from_epoch_key(fabric: &Fabric, epoch_key: &[u8]) -> Result<Self, Error> {
+        let compressed_id = fabric
+            .compressed_fabric_id()
+            .ok_or(ErrorCode::InvalidState)?;
+
+        let key_set = KeySet::new(epoch_key, &compressed_id.to_be_bytes())?;
+
+        Self::new(fabric.fab_idx(), key_set.op_key())
+    }
+
+    /// Return the local index of the fabric of the key
+    pub fn fab_idx(&self) -> NonZeroU8 {
+        self.fab_idx
+    }
+
+    /// Return the Group Session ID derived from the key
+    pub fn session_id(&self) -> u16 {
+        self.session_id
+    }
+
+    /// Return the operational group key
+    pub fn op_key(&self) -> &[u8] {
+        &self.op_key
+    }
+
+    /// Return the privacy key derived from the operational group key
+    pub fn privacy_key(&self) -> &[u8] {
+        &self.privacy_key
+    }
+}
+
+/// A decoded group message
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GroupMessage<'a> {
+    /// The local index of the fabric of the group
+    pub fab_idx: NonZeroU8,
+    /// The node ID of the sender
+    pub src_node_id: u64,
+    /// The ID of the group the message is sent to
+    pub group_id: u16,
+    /// `true` if the message is a control message, as opposed to a data message
+    pub control: bool,
+    /// The exchange ID of the message
+    pub exch_id: u16,
+    /// The protocol ID and opcode of the message
+    pub meta: MessageMeta,
+    /// The payload of the message
+    pub payload: &'a [u8],
+}
+
+impl TransportMgr<'_> {
+    /// Encode a group message to the group with the provided ID, encrypting it with the provided group key.
+    ///
+    /// The message counter is taken from the Global Group Encrypted Data (or Control) Message Counter.
+    /// Return an error with code `ErrorCode::Busy` if the counter had reached its persisted limit,
+    /// in which case the message should be re-tried once the new limit is persisted.
+    ///
+    /// If message privacy is enabled for the device, the header of the message is obfuscated
+    /// with the privacy key of the group key.
+    ///
+    /// # Arguments
+    /// - `matter`: The Matter stack
+    /// - `key`: The operational group key
+    /// - `group_id`: The ID of the destination group
+    /// - `control`: `true` for control messages, `false` for data messages
+    /// - `buf`: The buffer where the message is encoded
+    /// - `payload_writer`: A closure writing the payload of the message and returning its protocol ID and opcode
+    ///
+    /// Return the encoded message, which is a slice of `buf`.
+    pub fn encode_group_message<'b, F>(
+        &self,
+        matter: &Matter<'_>,
+        key: &GroupKey,
+        group_id: u16,
+        control: bool,
+        buf: &'b mut [u8],
+        payload_writer: F,
+    ) -> Result<&'b [u8], Error>
+    where
+        F: FnOnce(&mut WriteBuf) -> Result<MessageMeta, Error>,
+    {
+        let src_node_id = matter
+            .fabric_mgr
+            .borrow()
+            .get(key.fab_idx())
+            .ok_or(ErrorCode::NotFound)?
+            .node_id();
+
+        let mut wb = WriteBuf::new(buf);
+        wb.reserve(PacketHdr::HDR_RESERVE)?;
+
+        let meta = payload_writer(&mut wb)?;
+
+        let mut header = PacketHdr::new();
+
+        meta.set_into(&mut header.proto);
+        header.proto.unset_reliable();
+        header.proto.set_initiator();
+        header.proto.exch_id = self.session_mgr.borrow_mut().get_next_exch_id();
+
+        header.plain.sess_id = key.session_id();
+        header.plain.set_group_session(true);
+        header.plain.set_control(control);
+        header.plain.set_src_nodeid(Some(src_node_id));
+        header.plain.set_dst_groupcast_nodeid(Some(group_id));
+        header.plain.set_privacy(self.device_privacy);
+
+        let ctr = {
+            let mut counters = self.counters.borrow_mut();
+
+            let ctr = if control {
+                counters.next_group_control()
+            } else {
+                counters.next_group_data()
+            };
+
+            (ctr, counters.is_changed())
+        };
+
+        if ctr.1 {
+            // A new counter limit needs to be persisted
+            matter.notify_persist();
+        }
+
+        header.plain.ctr = ctr.0?;
+
+        header.encode(
+            &mut wb,
+            src_node_id,
+            Some(key.op_key()),
+            Some(key.privacy_key()),
+        )?;
+
+        let (start, end) = (wb.get_start(), wb.get_tail());
+
+        Ok(&buf[start..end])
+    }
+
+    /// Decode a group message, decrypting it with the one of the provided group keys
+    /// which matches the Group Session ID of the message.
+    ///
+    /// If the message uses message privacy, its header is first de-obfuscated with the privacy key of that group key.
+    ///
+    /// The message counter is checked against the group message counters of the sender.
+    /// If the sender of a control message is not known yet, its group control message counter is first synchronized
+    /// with MCSP, over the secure unicast session with the sender, which therefore needs to exist.
+    ///
+    /// Return an error with code `ErrorCode::Duplicate` if the message is a duplicate.
+    pub async fn decode_group_message<'a, 'b>(
+        &self,
+        matter: &'a Matter<'a>,
+        keys: &[GroupKey],
+        buf: &'b mut [u8],
+    ) -> Result<GroupMessage<'b>, Error> {
+        let mut header = PacketHdr::new();
+
+        let (key, hdr_len) = {
+            let mut pb = ParseBuf::new(buf);
+            header.plain.decode(&mut pb)?;
+
+            if !header.plain.is_group_session() {
+                Err(ErrorCode::Invalid)?;
+            }
+
+            let key = keys
+                .iter()
+                .find(|key| key.session_id() == header.plain.sess_id)
+                .ok_or(ErrorCode::NoSession)?;
+
+            (key, pb.parsed_as_slice().len())
+        };
+
+        let payload_range = {
+            if header.plain.is_privacy() {
+                // The message counter and the node IDs are obfuscated, so de-obfuscate them
+                // with the privacy key of the group key
+                PlainHdr::privacy_crypt_in_place(buf, hdr_len, key.privacy_key())?;
+            }
+
+            let mut pb = ParseBuf::new(buf);
+            header.plain.decode(&mut pb)?;
+
+            let src_node_id = header
+                .plain
+                .get_src_nodeid()
+                .ok_or(ErrorCode::InvalidData)?;
+
+            header.decode_remaining(&mut pb, src_node_id, Some(key.op_key()))?;
+
+            pb.slice_range()
+        };
+
+        let src_node_id = unwrap!(header.plain.get_src_nodeid());
+        let group_id = header
+            .plain
+            .get_dst_groupcast_nodeid()
+            .ok_or(ErrorCode::InvalidData)?;
+        let control = header.plain.is_control();
+
+        let outcome = self.group_peers.borrow_mut().post_recv(
+            key.fab_idx(),
+            src_node_id,
+            header.plain.ctr,
+            control,
+        );
+
+        let outcome = if matches!(outcome, GroupRxOutcome::SyncRequired) {
+            let mut exchange =
+                Exchange::initiate(matter, key.fab_idx().get(), src_node_id, true).await?;
+
+            Mcsp::new().sync(&mut exchange).await?;
+
+            self.group_peers.borrow_mut().post_recv(
+                key.fab_idx(),
+                src_node_id,
+                header.plain.ctr,
+                control,
+            )
+        } else {
+            outcome
+        };
+
+        if !matches!(outcome, GroupRxOutcome::Accept) {
+            Err(ErrorCode::Duplicate)?;
+        }
+
+        Ok(GroupMessage {
+            fab_idx: key.fab_idx(),
+            src_node_id,
+            group_id,
+            control,
+            exch_id: header.proto.exch_id,
+            meta: MessageMeta::from(&header.proto),
+            payload: &buf[payload_range.0..payload_range.1],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU8;
+
+    use embassy_futures::block_on;
+
+    use crate::crypto::KeyPair;
+    use crate::data_model::basic_info::BasicInfoConfig;
+    use crate::error::ErrorCode;
+    use crate::mdns::MdnsService;
+    use crate::test_device::{TEST_DEV_ATT, TEST_DEV_COMM, TEST_DEV_DET};
+    use crate::transport::exchange::MessageMeta;
+    use crate::utils::epoch::dummy_epoch;
+    use crate::utils::rand::dummy_rand;
+    use crate::{Matter, MATTER_PORT};
+
+    use super::GroupKey;
+
+    const OP_KEY: [u8; 16] = [
+        0xa6, 0xf5, 0x30, 0x6b, 0xaf, 0x6d, 0x05, 0x0a, 0xf2, 0x3b, 0xa4, 0xbd, 0x6b, 0x9d, 0xd9,
+        0x60,
+    ];
+
+    const PAYLOAD: &[u8] = &[0x15, 0x18];
+
+    const TEST_DEV_DET_PRIVACY: BasicInfoConfig = BasicInfoConfig {
+        privacy: true,
+        ..TEST_DEV_DET
+    };
+
+    fn matter() -> Matter<'static> {
+        matter_with(&TEST_DEV_DET)
+    }
+
+    fn matter_with(dev_det: &'static BasicInfoConfig<'static>) -> Matter<'static> {
+        let matter = Matter::new(
+            dev_det,
+            TEST_DEV_COMM,
+            &TEST_DEV_ATT,
+            MdnsService::Disabled,
+            dummy_epoch,
+            dummy_rand,
+            MATTER_PORT,
+        );
+
+        unwrap!(matter
+            .fabric_mgr
+            .borrow_mut()
+            .add_with_post_init(unwrap!(KeyPair::new(dummy_rand)), |_| Ok(())));
+
+        matter
+    }
+
+    fn encode<'b>(
+        matter: &Matter<'_>,
+        key: &GroupKey,
+        control: bool,
+        buf: &'b mut [u8],
+    ) -> Result<&'b [u8], crate::error::Error> {
+        matter
+            .transport_mgr
+            .encode_group_message(matter, key, 0x0101, control, buf, |wb| {
+                wb.append(PAYLOAD)?;
+
+                Ok(MessageMeta::new(0x0001, 0x08, false))
+            })
+    }
+
+    #[test]
+    fn test_group_message() {
+        let sender = matter();
+        let receiver = matter();
+
+        let key = unwrap!(GroupKey::new(unwrap!(NonZeroU8::new(1)), &OP_KEY));
+
+        let mut buf = [0; 128];
+
+        // The group counters are not persisted yet
+        assert_eq!(
+            encode(&sender, &key, false, &mut buf).map_err(|e| e.code()),
+            Err(ErrorCode::Busy)
+        );
+        assert!(sender.counters_changed());
+
+        let mut store_buf = [0; 32];
+        unwrap!(sender.store_counters(&mut store_buf));
+        sender.counters_stored();
+
+        // Data messages from unknown peers are trusted first
+        let mut msg = [0; 128];
+        let len = {
+            let encoded = unwrap!(encode(&sender, &key, false, &mut buf));
+            msg[..encoded.len()].copy_from_slice(encoded);
+            encoded.len()
+        };
+
+        let mut rx = msg;
+        let message = unwrap!(block_on(receiver.transport_mgr.decode_group_message(
+            &receiver,
+            core::slice::from_ref(&key),
+            &mut rx[..len]
+        )));
+        assert_eq!(message.fab_idx, key.fab_idx());
+        assert_eq!(message.group_id, 0x0101);
+        assert!(!message.control);
+        assert_eq!(message.meta, MessageMeta::new(0x0001, 0x08, false));
+        assert_eq!(message.payload, PAYLOAD);
+
+        // Duplicates are dropped
+        let mut rx = msg;
+        assert_eq!(
+            block_on(receiver.transport_mgr.decode_group_message(
+                &receiver,
+                core::slice::from_ref(&key),
+                &mut rx[..len]
+            ))
+            .map(|_| ())
+            .map_err(|e| e.code()),
+            Err(ErrorCode::Duplicate)
+        );
+
+        // Messages with an unknown key cannot be decoded
+        let other = unwrap!(GroupKey::new(unwrap!(NonZeroU8::new(1)), &[0; 16]));
+        let mut rx = msg;
+        assert!(block_on(receiver.transport_mgr.decode_group_message(
+            &receiver,
+            core::slice::from_ref(&other),
+            &mut rx[..len]
+        ))
+        .is_err());
+
+        // Control messages are accepted once the control counter of the sender is synchronized
+        let len = {
+            let encoded = unwrap!(encode(&sender, &key, true, &mut buf));
+            msg[..encoded.len()].copy_from_slice(encoded);
+            encoded.len()
+        };
+
+        let mut peers = receiver.transport_mgr.group_peers.borrow_mut();
+        let challenge = peers.start_sync(key.fab_idx(), 0, dummy_rand);
+        let synchronized_ctr = sender.transport_mgr.counters.borrow_mut().group_control();
+        unwrap!(peers.complete_sync(key.fab_idx(), 0, synchronized_ctr, &challenge));
+        drop(peers);
+
+        let mut rx = msg;
+        let message = unwrap!(block_on(receiver.transport_mgr.decode_group_message(
+            &receiver,
+            core::slice::from_ref(&key),
+            &mut rx[..len]
+        )));
+        assert!(message.control);
+        assert_eq!(message.payload, PAYLOAD);
+    }
+
+    #[test]
+    fn test_group_message_privacy() {
+        let sender = matter_with(&TEST_DEV_DET_PRIVACY);
+        let plain_sender = matter();
+        let receiver = matter();
+
+        let key = unwrap!(GroupKey::new(unwrap!(NonZeroU8::new(1)), &OP_KEY));
+
+        // As per the test vectors in `secure_channel::crypto`
+        assert_eq!(key.session_id(), 0xb9f7);
+
+        let mut buf = [0; 128];
+
+        // Persist the group counters first
+        let mut store_buf = [0; 32];
+        for matter in [&sender, &plain_sender] {
+            assert!(encode(matter, &key, false, &mut buf).is_err());
+            unwrap!(matter.store_counters(&mut store_buf));
+            matter.counters_stored();
+        }
+
+        let mut msg = [0; 128];
+        let len = {
+            let encoded = unwrap!(encode(&sender, &key, false, &mut buf));
+            msg[..encoded.len()].copy_from_slice(encoded);
+            encoded.len()
+        };
+
+        // Only the privacy-protected fields (message counter and node IDs) differ
+        // from the same message without message privacy
+        let mut plain_buf = [0; 128];
+        let plain = unwrap!(encode(&plain_sender, &key, false, &mut plain_buf));
+        assert_eq!(plain.len(), len);
+        assert_eq!(msg[..3], plain[..3]);
+        assert_ne!(msg[3..4], plain[3..4]);
+        assert_ne!(msg[4..8], plain[4..8]);
+        assert_ne!(msg[8..18], plain[8..18]);
+
+        let mut rx = msg;
+        let message = unwrap!(block_on(receiver.transport_mgr.decode_group_message(
+            &receiver,
+            core::slice::from_ref(&key),
+            &mut rx[..len]
+        )));
+        assert_eq!(message.fab_idx, key.fab_idx());
+        assert_eq!(
+            message.src_node_id,
+            unwrap!(sender.fabric_mgr.borrow().get(key.fab_idx())).node_id()
+        );
+        assert_eq!(message.group_id, 0x0101);
+        assert!(!message.control);
+        assert_eq!(message.meta, MessageMeta::new(0x0001, 0x08, false));
+        assert_eq!(message.payload, PAYLOAD);
+
+        // Messages with an unknown key cannot be de-obfuscated
+        let other = unwrap!(GroupKey::new(unwrap!(NonZeroU8::new(1)), &[0; 16]));
+        let mut rx = msg;
+        assert!(block_on(receiver.transport_mgr.decode_group_message(
+            &receiver,
+            core::slice::from_ref(&other),
+            &mut rx[..len]
+        ))
+        .is_err());
+    }
+}
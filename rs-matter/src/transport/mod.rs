@@ -15,9 +15,12 @@
  *    limitations under the License.
  */
 
+pub mod capture;
 pub mod core;
+pub mod counters;
 mod dedup;
 pub mod exchange;
+pub mod group;
 pub mod mrp;
 pub mod network;
 pub mod packet;
@@ -15,13 +15,22 @@
  *    limitations under the License.
  */
 
+use core::time::Duration;
+
+use crate::data_model::basic_info::BasicInfoConfig;
 use crate::error::*;
+use crate::tlv::{FromTLV, ToTLV};
 use crate::utils::epoch::Epoch;
 
-use super::{plain_hdr::PlainHdr, proto_hdr::ProtoHdr};
+use super::{exchange::MessageMeta, plain_hdr::PlainHdr, proto_hdr::ProtoHdr};
 
-//const MRP_STANDALONE_ACK_TIMEOUT_MS: u64 = 200;   // TODO: Use to pro-actively send ACKs
-const MRP_BASE_RETRY_INTERVAL_MS: u16 = 300;
+const MRP_STANDALONE_ACK_TIMEOUT_MS: u64 = 200;
+const MRP_MAX_DISPLACED_ACKS: usize = 4;
+const MRP_DEFAULT_IDLE_INTERVAL_MS: u32 = 500;
+const MRP_DEFAULT_ACTIVE_INTERVAL_MS: u32 = 300;
+const MRP_DEFAULT_ACTIVE_THRESHOLD_MS: u16 = 4000;
+const MRP_MAX_INTERVAL_MS: u32 = 3_600_000; // 1 hour
+const MRP_LOCAL_IDLE_INTERVAL_MS: u16 = 5000;
 const MRP_MAX_TRANSMISSIONS: u16 = 10;
 const MRP_BACKOFF_THRESHOLD: u16 = 1;
 const MRP_BACKOFF_BASE: (u64, u64) = (16, 10); // 1.6
@@ -29,11 +38,115 @@ const MRP_BACKOFF_JITTER: (u64, u64) = (25, 100); // 0.25
 const MRP_BACKOFF_MARGIN: (u64, u64) = (11, 10); // 1.1
 const MRP_JITTER_RAND_MAX: u8 = u8::MAX;
 
+/// The MRP parameters of a node, as carried in the Session Parameters TLV structure
+/// (`PBKDFParamRequest`, `PBKDFParamResponse`, `Sigma1` and `Sigma2`) and in the
+/// `SII`, `SAI` and `SAT` DNS-SD TXT records.
+///
+/// Parameters which are not specified by the node fall back to the defaults mandated by the Matter spec.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash, FromTLV, ToTLV)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[tlvargs(start = 1)]
+pub struct MrpParameters {
+    /// Session Idle Interval in ms
+    pub idle_interval_ms: Option<u32>,
+    /// Session Active Interval in ms
+    pub active_interval_ms: Option<u32>,
+    /// Session Active Threshold in ms
+    pub active_threshold_ms: Option<u16>,
+}
+
+impl MrpParameters {
+    /// Create a new instance with all parameters unspecified
+    pub const fn new() -> Self {
+        Self {
+            idle_interval_ms: None,
+            active_interval_ms: None,
+            active_threshold_ms: None,
+        }
+    }
+
+    /// Return the MRP parameters of our own node, as configured in the provided device details
+    pub fn local(dev_det: &BasicInfoConfig) -> Self {
+        Self {
+            idle_interval_ms: Some(dev_det.sii.unwrap_or(MRP_LOCAL_IDLE_INTERVAL_MS) as _),
+            active_interval_ms: Some(
+                dev_det
+                    .sai
+                    .map(|sai| sai as _)
+                    .unwrap_or(MRP_DEFAULT_ACTIVE_INTERVAL_MS),
+            ),
+            active_threshold_ms: Some(dev_det.sat.unwrap_or(MRP_DEFAULT_ACTIVE_THRESHOLD_MS)),
+        }
+    }
+
+    /// Create a new instance from the `SII`, `SAI` and `SAT` keys of a DNS-SD TXT record
+    ///
+    /// Other keys, as well as keys with malformed values are ignored.
+    pub fn from_txt_kvs<'a, I>(txt_kvs: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut params = Self::new();
+
+        for (key, value) in txt_kvs {
+            match key {
+                "SII" => params.idle_interval_ms = value.parse().ok(),
+                "SAI" => params.active_interval_ms = value.parse().ok(),
+                "SAT" => params.active_threshold_ms = value.parse().ok(),
+                _ => (),
+            }
+        }
+
+        params
+    }
+
+    /// Return the Session Idle Interval in ms, or the default one, if not specified
+    pub fn idle_interval_ms(&self) -> u32 {
+        self.idle_interval_ms
+            .unwrap_or(MRP_DEFAULT_IDLE_INTERVAL_MS)
+            .min(MRP_MAX_INTERVAL_MS)
+    }
+
+    /// Return the Session Active Interval in ms, or the default one, if not specified
+    pub fn active_interval_ms(&self) -> u32 {
+        self.active_interval_ms
+            .unwrap_or(MRP_DEFAULT_ACTIVE_INTERVAL_MS)
+            .min(MRP_MAX_INTERVAL_MS)
+    }
+
+    /// Return the Session Active Threshold in ms, or the default one, if not specified
+    pub fn active_threshold_ms(&self) -> u16 {
+        self.active_threshold_ms
+            .unwrap_or(MRP_DEFAULT_ACTIVE_THRESHOLD_MS)
+    }
+
+    /// Return the base retransmission interval to use when sending to the node
+    /// which is described by these parameters.
+    ///
+    /// As per the spec, the node is considered active if we have heard from it within its
+    /// Session Active Threshold (i.e. `last_heard` is not older than the threshold),
+    /// in which case its Session Active Interval is used. Otherwise, its Session Idle Interval is used.
+    pub fn retrans_interval_ms(&self, last_heard: Option<Duration>, now: Duration) -> u32 {
+        let active = last_heard
+            .map(|last_heard| {
+                now.saturating_sub(last_heard)
+                    < Duration::from_millis(self.active_threshold_ms() as _)
+            })
+            .unwrap_or(false);
+
+        if active {
+            self.active_interval_ms()
+        } else {
+            self.idle_interval_ms()
+        }
+    }
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct RetransEntry {
     /// The retransmission delay interval in milliseconds
-    base_delay_interval_ms: u16,
+    base_delay_interval_ms: u32,
     // The msg counter that we are waiting to be acknowledged
     msg_ctr: u32,
     // The retransmission counter
@@ -41,9 +154,9 @@ pub struct RetransEntry {
 }
 
 impl RetransEntry {
-    pub fn new(base_delay_interval_ms: Option<u16>, msg_ctr: u32) -> Self {
+    pub fn new(base_delay_interval_ms: u32, msg_ctr: u32) -> Self {
         Self {
-            base_delay_interval_ms: base_delay_interval_ms.unwrap_or(MRP_BASE_RETRY_INTERVAL_MS),
+            base_delay_interval_ms,
             msg_ctr,
             counter: 0,
         }
@@ -101,13 +214,16 @@ pub struct AckEntry {
     pub(crate) msg_ctr: u32,
     // Whether the message was acknowledged at least once
     pub(crate) acknowledged: bool,
+    // When was the message received
+    pub(crate) received_at_ms: u64,
 }
 
 impl AckEntry {
-    pub fn new(msg_ctr: u32) -> Result<Self, Error> {
+    pub fn new(msg_ctr: u32, received_at_ms: u64) -> Result<Self, Error> {
         Ok(Self {
             msg_ctr,
             acknowledged: false,
+            received_at_ms,
         })
     }
 
@@ -116,11 +232,45 @@ impl AckEntry {
     }
 }
 
+/// Counters of the MRP acknowledgements sent by the transport
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AckStats {
+    /// The number of ACKs piggybacked on outgoing messages
+    pub piggybacked: u32,
+    /// The number of ACKs sent as standalone ACK messages
+    pub standalone: u32,
+}
+
+impl AckStats {
+    /// Create a new instance with all counters set to 0
+    pub const fn new() -> Self {
+        Self {
+            piggybacked: 0,
+            standalone: 0,
+        }
+    }
+
+    /// Account for the ACK (if any) carried by the provided outgoing message
+    pub(crate) fn update(&mut self, tx_proto: &ProtoHdr) {
+        if tx_proto.get_ack().is_some() {
+            if MessageMeta::from(tx_proto).is_standalone_ack() {
+                self.standalone = self.standalone.wrapping_add(1);
+            } else {
+                self.piggybacked = self.piggybacked.wrapping_add(1);
+            }
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ReliableMessage {
     pub(crate) retrans: Option<RetransEntry>,
     pub(crate) ack: Option<AckEntry>,
+    // The msg counters of pending ACKs which were replaced by newer ones,
+    // and which should therefore be sent out immediately as standalone ACKs (oldest first)
+    pub(crate) displaced_acks: heapless::Vec<u32, MRP_MAX_DISPLACED_ACKS>,
     pub(crate) received_at_ms: Option<u64>,
 }
 
@@ -134,10 +284,39 @@ impl ReliableMessage {
     }
 
     pub fn is_ack_pending(&self) -> bool {
+        !self.displaced_acks.is_empty()
+            || self
+                .ack
+                .as_ref()
+                .map(|ack| !ack.acknowledged)
+                .unwrap_or(false)
+    }
+
+    /// Return in how many milliseconds a standalone ACK should be sent for this exchange,
+    /// or `None` if there is no ACK pending.
+    ///
+    /// A pending ACK is given `MRP_STANDALONE_ACK_TIMEOUT_MS` to be piggybacked on an outgoing
+    /// message of the exchange, while displaced ACKs are due immediately.
+    ///
+    /// Displaced ACKs are due even while a retransmission is pending, as the retransmission
+    /// can only carry the latest ACK. The latest ACK, however, is not due while a retransmission is pending,
+    /// as the retransmission will carry it anyway.
+    pub fn standalone_ack_due_ms(&self, epoch: Epoch) -> Option<u64> {
+        if !self.displaced_acks.is_empty() {
+            return Some(0);
+        }
+
+        if self.is_retrans_pending() {
+            return None;
+        }
+
         self.ack
             .as_ref()
-            .map(|ack| !ack.acknowledged)
-            .unwrap_or(false)
+            .filter(|ack| !ack.acknowledged)
+            .map(|ack| {
+                (ack.received_at_ms + MRP_STANDALONE_ACK_TIMEOUT_MS)
+                    .saturating_sub(epoch().as_millis() as u64)
+            })
     }
 
     pub fn has_rx_timed_out(&self, timeout_ms: u64, epoch: Epoch) -> bool {
@@ -154,13 +333,22 @@ impl ReliableMessage {
         &mut self,
         tx_plain: &PlainHdr,
         tx_proto: &mut ProtoHdr,
-        session_active_interval_ms: Option<u16>,
-        // TODO: Need to make use of it in future,
-        // once we detect idle vs active devices
-        _session_idle_interval_ms: Option<u16>,
+        retrans_interval_ms: u32,
     ) -> Result<(), Error> {
-        // Check if any acknowledgements are pending for this exchange,
-        if let Some(ack) = &mut self.ack {
+        let standalone_ack = MessageMeta::from(&*tx_proto).is_standalone_ack();
+
+        // A displaced ACK can only be sent with a standalone ACK, as
+        // any other message needs to acknowledge the latest received one
+        let displaced_ack = if standalone_ack && !self.displaced_acks.is_empty() {
+            Some(self.displaced_acks.remove(0))
+        } else {
+            None
+        };
+
+        if let Some(displaced_ack) = displaced_ack {
+            tx_proto.set_ack(Some(displaced_ack));
+        } else if let Some(ack) = &mut self.ack {
+            // Check if any acknowledgements are pending for this exchange,
             // if so, piggy back in the encoded header here
             tx_proto.set_ack(Some(ack.get_msg_ctr()));
             ack.acknowledged = true;
@@ -177,13 +365,18 @@ impl ReliableMessage {
 
                     self.retrans = None;
                     self.ack = None;
+                    self.displaced_acks.clear();
                 }
             } else {
-                self.retrans = Some(RetransEntry::new(session_active_interval_ms, tx_plain.ctr));
+                self.retrans = Some(RetransEntry::new(retrans_interval_ms, tx_plain.ctr));
             }
         }
 
-        self.received_at_ms = None;
+        if !standalone_ack {
+            // Standalone ACKs are not a reply to the peer, so keep tracking
+            // the time of the last received message
+            self.received_at_ms = None;
+        }
 
         Ok(())
     }
@@ -224,21 +417,250 @@ impl ReliableMessage {
             }
         }
 
+        let now_ms = epoch().as_millis() as u64;
+
         if rx_proto.is_reliable() {
-            if let Some(ack) = &self.ack {
-                // This indicates there was some existing entry for same sess-id/exch-id, which shouldnt happen
-                // TODO: As per the spec if this happens, we need to send out the previous ACK and note this new ACK
-                error!(
-                    "Previous ACK entry {:x} for this exchange already exists",
+            if let Some(ack) = self.ack.as_ref().filter(|ack| !ack.acknowledged) {
+                // As per the spec, if there is already a pending ACK for this exchange,
+                // it should be sent out immediately as a standalone ACK, and the new one should be noted
+                warn!(
+                    "Previous ACK entry {:x} for this exchange still pending, sending it as a standalone ACK",
                     ack.get_msg_ctr()
                 );
+
+                if self.displaced_acks.is_full() {
+                    // The peer will retransmit the message of the oldest ACK, and we'll ACK the duplicate then
+                    let dropped_ack = self.displaced_acks.remove(0);
+                    warn!(
+                        "Too many displaced ACK entries, dropping the oldest one {:x}",
+                        dropped_ack
+                    );
+                }
+
+                // `unwrap` is safe because we just made room for the entry
+                unwrap!(self.displaced_acks.push(ack.get_msg_ctr()));
             }
 
-            self.ack = Some(AckEntry::new(rx_plain.ctr)?);
+            self.ack = Some(AckEntry::new(rx_plain.ctr, now_ms)?);
         }
 
-        self.received_at_ms = Some(epoch().as_millis() as u64);
+        self.received_at_ms = Some(now_ms);
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::tlv::{FromTLV, TLVElement, TLVTag, ToTLV};
+    use crate::utils::storage::WriteBuf;
+
+    use crate::secure_channel::common::{OpCode, PROTO_ID_SECURE_CHANNEL};
+    use crate::transport::plain_hdr::PlainHdr;
+    use crate::transport::proto_hdr::ProtoHdr;
+
+    use super::{MrpParameters, ReliableMessage, RetransEntry};
+
+    #[test]
+    fn test_params_from_txt() {
+        let params = MrpParameters::from_txt_kvs([
+            ("D", "3840"),
+            ("SII", "5000"),
+            ("SAI", "300"),
+            ("SAT", "bogus"),
+        ]);
+
+        assert_eq!(params.idle_interval_ms, Some(5000));
+        assert_eq!(params.active_interval_ms, Some(300));
+        assert_eq!(params.active_threshold_ms, None);
+        assert_eq!(params.active_threshold_ms(), 4000);
+    }
+
+    #[test]
+    fn test_params_tlv() {
+        let params = MrpParameters {
+            idle_interval_ms: Some(60_000),
+            active_interval_ms: None,
+            active_threshold_ms: Some(1000),
+        };
+
+        let mut buf = [0; 32];
+        let mut wb = WriteBuf::new(&mut buf);
+        params.to_tlv(&TLVTag::Anonymous, &mut wb).unwrap();
+
+        assert_eq!(
+            wb.as_slice(),
+            &[0x15, 0x25, 0x01, 0x60, 0xea, 0x25, 0x03, 0xe8, 0x03, 0x18]
+        );
+        assert_eq!(
+            MrpParameters::from_tlv(&TLVElement::new(wb.as_slice())).unwrap(),
+            params
+        );
+    }
+
+    #[test]
+    fn test_retrans_interval() {
+        let params = MrpParameters {
+            idle_interval_ms: Some(60_000),
+            active_interval_ms: Some(200),
+            active_threshold_ms: Some(1000),
+        };
+
+        let now = Duration::from_secs(100);
+
+        // Never heard from the peer: assume it is idle
+        assert_eq!(params.retrans_interval_ms(None, now), 60_000);
+        // Heard from the peer within its active threshold
+        assert_eq!(
+            params.retrans_interval_ms(Some(now - Duration::from_millis(500)), now),
+            200
+        );
+        // Heard from the peer long ago
+        assert_eq!(
+            params.retrans_interval_ms(Some(now - Duration::from_millis(1000)), now),
+            60_000
+        );
+
+        // Defaults as per the spec
+        assert_eq!(MrpParameters::new().retrans_interval_ms(None, now), 500);
+
+        // Backoff and jitter are applied on top of the peer interval
+        let entry = RetransEntry::new(60_000, 0);
+        assert_eq!(entry.delay_ms_counter(0, 0), 66_000);
+        assert_eq!(entry.delay_ms_counter(2, 0), 105_600);
+        assert_eq!(entry.delay_ms_counter(0, 255), 82_500);
+    }
+
+    fn epoch_1000() -> Duration {
+        Duration::from_millis(1000)
+    }
+
+    fn epoch_1150() -> Duration {
+        Duration::from_millis(1150)
+    }
+
+    fn epoch_1200() -> Duration {
+        Duration::from_millis(1200)
+    }
+
+    fn rx(mrp: &mut ReliableMessage, ctr: u32) {
+        let mut plain = PlainHdr::default();
+        plain.ctr = ctr;
+
+        let mut proto = ProtoHdr::default();
+        proto.set_reliable();
+
+        mrp.post_recv(&plain, &proto, epoch_1000).unwrap();
+    }
+
+    fn rx_ack(mrp: &mut ReliableMessage, ctr: u32, ack: u32) {
+        let mut plain = PlainHdr::default();
+        plain.ctr = ctr;
+
+        let mut proto = ProtoHdr::default();
+        proto.set_ack(Some(ack));
+
+        mrp.post_recv(&plain, &proto, epoch_1000).unwrap();
+    }
+
+    fn tx_standalone_ack(mrp: &mut ReliableMessage) -> Option<u32> {
+        let mut proto = ProtoHdr::default();
+        proto.proto_id = PROTO_ID_SECURE_CHANNEL;
+        proto.proto_opcode = OpCode::MRPStandAloneAck as _;
+
+        mrp.pre_send(&PlainHdr::default(), &mut proto, 300).unwrap();
+
+        proto.get_ack()
+    }
+
+    #[test]
+    fn test_standalone_ack_timeout() {
+        let mut mrp = ReliableMessage::new();
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1000), None);
+
+        rx(&mut mrp, 10);
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1000), Some(200));
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1150), Some(50));
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1200), Some(0));
+
+        assert_eq!(tx_standalone_ack(&mut mrp), Some(10));
+        assert!(!mrp.is_ack_pending());
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1200), None);
+
+        // Sending the standalone ACK does not count as a reply to the peer
+        assert!(mrp.has_rx_timed_out(200, epoch_1200));
+    }
+
+    #[test]
+    fn test_displaced_ack() {
+        let mut mrp = ReliableMessage::new();
+
+        rx(&mut mrp, 10);
+        rx(&mut mrp, 11);
+
+        // The displaced ACK is due immediately
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1000), Some(0));
+        assert_eq!(tx_standalone_ack(&mut mrp), Some(10));
+
+        // The new ACK is still waiting for a piggyback opportunity
+        assert!(mrp.is_ack_pending());
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1000), Some(200));
+        assert_eq!(tx_standalone_ack(&mut mrp), Some(11));
+        assert!(!mrp.is_ack_pending());
+    }
+
+    #[test]
+    fn test_multiple_displaced_acks() {
+        let mut mrp = ReliableMessage::new();
+
+        for ctr in 10..16 {
+            rx(&mut mrp, ctr);
+        }
+
+        // Only the oldest displaced ACK (10) is dropped, as the displaced ACKs queue holds 4 entries
+        for ctr in 11..15 {
+            assert_eq!(mrp.standalone_ack_due_ms(epoch_1000), Some(0));
+            assert_eq!(tx_standalone_ack(&mut mrp), Some(ctr));
+        }
+
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1000), Some(200));
+        assert_eq!(tx_standalone_ack(&mut mrp), Some(15));
+        assert!(!mrp.is_ack_pending());
+    }
+
+    #[test]
+    fn test_displaced_ack_while_retrans_pending() {
+        let mut mrp = ReliableMessage::new();
+
+        let mut plain = PlainHdr::default();
+        plain.ctr = 100;
+
+        let mut proto = ProtoHdr::default();
+        proto.set_reliable();
+
+        mrp.pre_send(&plain, &mut proto, 300).unwrap();
+        assert!(mrp.is_retrans_pending());
+
+        rx(&mut mrp, 10);
+        rx(&mut mrp, 11);
+
+        // The displaced ACK is due immediately, even while retransmitting
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1000), Some(0));
+        assert_eq!(tx_standalone_ack(&mut mrp), Some(10));
+        assert!(mrp.is_retrans_pending());
+
+        // ... while the pending one is not, as the retransmission carries it
+        assert!(mrp.is_ack_pending());
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1000), None);
+        assert_eq!(mrp.standalone_ack_due_ms(epoch_1200), None);
+
+        mrp.pre_send(&plain, &mut proto, 300).unwrap();
+        assert_eq!(proto.get_ack(), Some(11));
+        assert!(!mrp.is_ack_pending());
+
+        rx_ack(&mut mrp, 12, 100);
+        assert!(!mrp.is_retrans_pending());
+    }
+}
@@ -120,6 +120,21 @@ impl Address {
             _ => None,
         }
     }
+
+    /// Return the scope ID (i.e. the index of the network interface) of a scoped IPv6 UDP or TCP address,
+    /// like e.g. a link-local address.
+    ///
+    /// Return `None` if the address is not an IPv6 address or if it is not scoped.
+    pub const fn scope_id(&self) -> Option<u32> {
+        match self {
+            Self::Udp(SocketAddr::V6(addr)) | Self::Tcp(SocketAddr::V6(addr))
+                if addr.scope_id() != 0 =>
+            {
+                Some(addr.scope_id())
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Default for Address {
@@ -20,13 +20,13 @@ use core::future::Future;
 use core::marker::PhantomData;
 use core::ops::DerefMut;
 
-use embassy_futures::select::select4;
+use embassy_futures::select::{select, select4};
 use embassy_sync::blocking_mutex::raw::{NoopRawMutex, RawMutex};
 use embassy_time::{Duration, Instant, Timer};
 
 use context::LockError;
 
-use session::{BTP_ACK_TIMEOUT_SECS, BTP_CONN_IDLE_TIMEOUT_SECS};
+use session::{BTP_ACK_TIMEOUT_SECS, BTP_CONN_HANDSHAKE_TIMEOUT_SECS, BTP_CONN_IDLE_TIMEOUT_SECS};
 
 use crate::data_model::basic_info::BasicInfoConfig;
 use crate::error::{Error, ErrorCode};
@@ -36,11 +36,13 @@ use crate::utils::init::{init, Init};
 use crate::utils::select::Coalesce;
 use crate::utils::sync::IfMutex;
 
+pub use commissioning::BtpCommissioning;
 pub use context::{BtpContext, MAX_BTP_SESSIONS};
 pub use gatt::*;
 
 use self::context::SessionSendLock;
 
+mod commissioning;
 mod context;
 mod gatt;
 mod session;
@@ -62,19 +64,20 @@ pub(crate) const MAX_MTU: u16 = (MAX_BTP_SEGMENT_SIZE + GATT_HEADER_SIZE) as u16
 ///
 /// The implementation needs a `Gatt` trait implementation which is OS/platform-specific.
 /// All aspects of the BTP protocol however are implemented in platform-neutral way.
-pub struct Btp<C, M, T> {
+pub struct Btp<C, M, T, const N: usize = MAX_BTP_SESSIONS> {
     gatt: T,
     context: C,
     send_buf: IfMutex<NoopRawMutex, crate::utils::storage::Vec<u8, MAX_BTP_SEGMENT_SIZE>>,
     ack_timeout_secs: u16,
+    handshake_timeout_secs: u16,
     conn_idle_timeout_secs: u16,
     _mutex: PhantomData<M>,
 }
 
 #[cfg(all(feature = "std", target_os = "linux"))]
-impl<C, M> Btp<C, M, BuiltinGattPeripheral>
+impl<C, M, const N: usize> Btp<C, M, BuiltinGattPeripheral, N>
 where
-    C: Borrow<BtpContext<M>> + Clone + Send + Sync + 'static,
+    C: Borrow<BtpContext<M, N>> + Clone + Send + Sync + 'static,
     M: RawMutex + Send + Sync,
 {
     #[inline(always)]
@@ -87,9 +90,9 @@ where
     }
 }
 
-impl<C, M, T> Btp<C, M, T>
+impl<C, M, T, const N: usize> Btp<C, M, T, N>
 where
-    C: Borrow<BtpContext<M>> + Clone + Send + Sync + 'static,
+    C: Borrow<BtpContext<M, N>> + Clone + Send + Sync + 'static,
     M: RawMutex + Send + Sync,
     T: GattPeripheral,
 {
@@ -101,6 +104,7 @@ where
             gatt,
             context,
             BTP_ACK_TIMEOUT_SECS,
+            BTP_CONN_HANDSHAKE_TIMEOUT_SECS,
             BTP_CONN_IDLE_TIMEOUT_SECS,
         )
     }
@@ -110,6 +114,7 @@ where
         gatt: T,
         context: C,
         ack_timeout_secs: u16,
+        handshake_timeout_secs: u16,
         conn_idle_timeout_secs: u16,
     ) -> Self {
         Self {
@@ -117,6 +122,7 @@ where
             context,
             send_buf: IfMutex::new(crate::utils::storage::Vec::new()),
             ack_timeout_secs,
+            handshake_timeout_secs,
             conn_idle_timeout_secs,
             _mutex: PhantomData,
         }
@@ -131,6 +137,7 @@ where
             context <- context,
             send_buf <- IfMutex::init(crate::utils::storage::Vec::init()),
             ack_timeout_secs: BTP_ACK_TIMEOUT_SECS,
+            handshake_timeout_secs: BTP_CONN_HANDSHAKE_TIMEOUT_SECS,
             conn_idle_timeout_secs: BTP_CONN_IDLE_TIMEOUT_SECS,
             _mutex: PhantomData,
         })
@@ -140,7 +147,8 @@ where
     ///
     /// While all sending and receiving of Matter packets (a.k.a. BTP SDUs) is done via the `recv` and `send` methods
     /// on the `Btp` struct, this method is responsible for managing internal implementation aspects of
-    /// the BTP protocol implementation, like e.g. the sessions' keepalive logic.
+    /// the BTP protocol implementation, like e.g. the sessions' keepalive logic and turning the advertising
+    /// of the GATT service on and off.
     ///
     /// Therefore, user is expected to call this method in order to run the BTP protocol.
     pub fn run<'a>(
@@ -154,27 +162,56 @@ where
         let context = self.context.clone();
 
         async move {
-            select4(
-                self.gatt.run(service_name, &adv_data, move |event| {
-                    context.borrow().on_event(event)
-                }),
-                self.handshake(),
-                self.ack(),
-                self.remove_expired(),
+            select(
+                select4(
+                    self.gatt.run(service_name, &adv_data, move |event| {
+                        context.borrow().on_event(event)
+                    }),
+                    self.handshake(),
+                    self.ack(),
+                    self.remove_expired(),
+                )
+                .coalesce(),
+                self.advertise(),
             )
             .coalesce()
             .await
         }
     }
 
+    /// Return the number of GATT centrals currently connected to the peripheral.
     pub fn conn_ct(&self) -> usize {
         self.context.borrow().conn_ct()
     }
 
+    /// Return the number of BTP sessions, including the ones for which the handshake is not complete yet.
+    pub fn session_ct(&self) -> usize {
+        self.context.borrow().session_ct()
+    }
+
+    /// Wait until the connections or the BTP sessions change.
     pub async fn wait_changed(&self) {
         self.context.borrow().wait_changed().await
     }
 
+    /// Enable or disable advertising of the Matter BLE service.
+    ///
+    /// Advertising is enabled by default. As per the Matter Core spec, the device should only
+    /// advertise while its commissioning window is open, so users are expected to disable it when
+    /// the commissioning window is closed, and to enable it again once the window is re-opened
+    /// (`BtpCommissioning` does this automatically).
+    ///
+    /// Note that even when enabled, the service is not advertised while the maximum number of
+    /// BTP sessions (`N`) is established.
+    pub fn set_advertising(&self, enabled: bool) {
+        self.context.borrow().set_advertising(enabled)
+    }
+
+    /// Return `true` if advertising of the Matter BLE service is enabled.
+    pub fn is_advertising_enabled(&self) -> bool {
+        self.context.borrow().is_advertising_enabled()
+    }
+
     /// Wait until there is at least one Matter (a.k.a. BTP SDU) packet available for consumption.
     pub async fn wait_available(&self) -> Result<(), Error> {
         self.context.borrow().wait_available().await
@@ -219,7 +256,7 @@ where
     /// The `data` parameter is the data to be sent as part of the BTP SDU packet.
     async fn do_send(
         &self,
-        session_lock: &SessionSendLock<'_, M>,
+        session_lock: &SessionSendLock<'_, M, N>,
         data: &[u8],
     ) -> Result<(), Error> {
         let mut offset = 0;
@@ -254,7 +291,7 @@ where
     }
 
     /// A job that is responsible for removing all sessions, which are considered expired due to
-    /// the remote peers not sending an ACK packet on time.
+    /// the remote peers not completing the handshake or not sending an ACK packet on time.
     async fn remove_expired(&self) -> Result<(), Error> {
         let context = self.context.borrow();
 
@@ -263,7 +300,11 @@ where
 
             // Remove all timed-out sessions
             context.remove(|session| {
-                session.is_timed_out(Instant::now(), self.conn_idle_timeout_secs)
+                session.is_timed_out(
+                    Instant::now(),
+                    self.handshake_timeout_secs,
+                    self.conn_idle_timeout_secs,
+                )
             })?;
 
             // Notify ack() below that maybe it is time to send an ACK packet
@@ -287,6 +328,29 @@ where
         }
     }
 
+    /// A job that is responsible for turning the advertising of the GATT service on and off.
+    ///
+    /// The service is advertised while advertising is enabled and there are less than `N` established BTP sessions,
+    /// so that other centrals do not try to connect to us once we cannot accept any more sessions.
+    async fn advertise(&self) -> Result<(), Error> {
+        let context = self.context.borrow();
+
+        let mut advertising = None;
+
+        loop {
+            let advertise = context.should_advertise();
+
+            if advertising != Some(advertise) {
+                debug!("BTP advertising: {}", advertise);
+
+                self.gatt.set_advertising(advertise).await?;
+                advertising = Some(advertise);
+            }
+
+            context.adv_notif.wait().await;
+        }
+    }
+
     /// A job that is resposible for sending the Handshake Response packet to all remote peers that
     /// in the meantime have connected to the peripheral, subscribed to chracteristic `C2` and had
     /// written the Handshake Request packet to characteristic `C1`.
@@ -329,9 +393,9 @@ where
     }
 }
 
-impl<C, M, T> NetworkSend for &Btp<C, M, T>
+impl<C, M, T, const N: usize> NetworkSend for &Btp<C, M, T, N>
 where
-    C: Borrow<BtpContext<M>> + Clone + Send + Sync + 'static,
+    C: Borrow<BtpContext<M, N>> + Clone + Send + Sync + 'static,
     M: RawMutex + Send + Sync,
     T: GattPeripheral,
 {
@@ -342,9 +406,9 @@ where
     }
 }
 
-impl<C, M, T> NetworkReceive for &Btp<C, M, T>
+impl<C, M, T, const N: usize> NetworkReceive for &Btp<C, M, T, N>
 where
-    C: Borrow<BtpContext<M>> + Clone + Send + Sync + 'static,
+    C: Borrow<BtpContext<M, N>> + Clone + Send + Sync + 'static,
     M: RawMutex + Send + Sync,
     T: GattPeripheral,
 {
@@ -360,9 +424,9 @@ where
     }
 }
 
-impl<C, M, T> NetworkSend for Btp<C, M, T>
+impl<C, M, T, const N: usize> NetworkSend for Btp<C, M, T, N>
 where
-    C: Borrow<BtpContext<M>> + Clone + Send + Sync + 'static,
+    C: Borrow<BtpContext<M, N>> + Clone + Send + Sync + 'static,
     M: RawMutex + Send + Sync,
     T: GattPeripheral,
 {
@@ -371,9 +435,9 @@ where
     }
 }
 
-impl<C, M, T> NetworkReceive for Btp<C, M, T>
+impl<C, M, T, const N: usize> NetworkReceive for Btp<C, M, T, N>
 where
-    C: Borrow<BtpContext<M>> + Clone + Send + Sync + 'static,
+    C: Borrow<BtpContext<M, N>> + Clone + Send + Sync + 'static,
     M: RawMutex + Send + Sync,
     T: GattPeripheral,
 {
@@ -0,0 +1,326 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! A module containing a utility which orchestrates the commissioning of a device over BLE (BTP),
+//! and the handover to the operational (Wifi or Thread) network.
+
+use core::borrow::Borrow;
+use core::future::pending;
+use core::pin::pin;
+
+use embassy_futures::select::{select, select3, Either};
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_time::{Duration, Timer};
+
+use crate::core::Matter;
+use crate::data_model::networks::wireless::{NetCtlState, NetCtlStateMutex};
+use crate::data_model::sdm::gen_comm::CommPolicy;
+use crate::error::Error;
+use crate::failsafe::FailSafeExpiry;
+use crate::pairing::DiscoveryCapabilities;
+use crate::transport::network::{Address, ChainedNetwork, NetworkReceive, NetworkSend};
+use crate::transport::session::SessionRemovalReason;
+use crate::utils::select::Coalesce;
+
+use super::{Btp, BtpContext, GattPeripheral, MAX_BTP_SESSIONS};
+
+/// How long to wait for the BTP sessions to be closed gracefully, before shutting down BTP.
+const BTP_SHUTDOWN_GRACE_SECS: u64 = 5;
+
+/// A utility which orchestrates the commissioning of a device over BLE (BTP) and the handover to the
+/// operational (Wifi or Thread) network.
+///
+/// Depending on whether the device can keep the BLE and the operational network connections up at the
+/// same time (as reported by `CommPolicy::concurrent_connection_supported`), the commissioning is done
+/// in one of two ways:
+///
+/// - Concurrent: The Matter transport runs over both BTP and the operational network at the same time,
+///   so that the commissioner can complete the commissioning over the operational network, while still
+///   being connected over BLE. BTP is shut down once `CommissioningComplete` is received.
+///
+/// - Non-concurrent: The Matter transport runs over BTP only, until the commissioner had provided the
+///   operational network credentials (i.e. `ConnectNetwork` was called) and had disconnected. Thereafter,
+///   BTP is shut down and the Matter transport runs over the operational network only, where the commissioner
+///   is expected to complete the commissioning. If the fail-safe expires before that, i.e. because the
+///   device could not connect to the operational network, the device falls back to commissioning over BTP.
+///
+///   In this mode, the `NetCtl` implementation is expected to only record the network credentials
+///   (see `NoopWirelessNetCtl` and `NetCtlWithStatusImpl`) and the user is expected to connect to the
+///   operational network once BTP is shut down, e.g. by running `WirelessMgr`.
+///
+/// In both cases, when the fail-safe expires, the fabric added during the fail-safe (if any) is removed
+/// and the commissioning window is re-opened.
+///
+/// If the device is already commissioned, BTP is not used at all.
+pub struct BtpCommissioning<'a, 'm, C, M, T, const N: usize = MAX_BTP_SESSIONS> {
+    matter: &'a Matter<'m>,
+    btp: &'a Btp<C, M, T, N>,
+    comm_policy: &'a dyn CommPolicy,
+}
+
+impl<'a, 'm, C, M, T, const N: usize> BtpCommissioning<'a, 'm, C, M, T, N>
+where
+    C: Borrow<BtpContext<M, N>> + Clone + Send + Sync + 'static,
+    M: RawMutex + Send + Sync,
+    T: GattPeripheral,
+{
+    /// Create a new instance.
+    ///
+    /// # Arguments
+    /// - `matter`: The Matter stack
+    /// - `btp`: The BTP protocol implementation to use for commissioning
+    /// - `comm_policy`: The commissioning policy of the device; should be the same one
+    ///   as the one used by the General Commissioning cluster handler
+    pub const fn new(
+        matter: &'a Matter<'m>,
+        btp: &'a Btp<C, M, T, N>,
+        comm_policy: &'a dyn CommPolicy,
+    ) -> Self {
+        Self {
+            matter,
+            btp,
+            comm_policy,
+        }
+    }
+
+    /// Run the Matter transport, commissioning the device over BTP first, if the device is not commissioned yet.
+    ///
+    /// # Arguments
+    /// - `send`, `recv`: The operational network
+    /// - `net_ctl_state`: The state of the network controller, used to detect when the operational network
+    ///   credentials had been provided by the commissioner in non-concurrent mode
+    /// - `service_name`: The name of the advertised BLE service
+    ///
+    /// The method never returns, unless the Matter transport or BTP fails.
+    /// The user is expected to run the mDNS responder and the data model responder concurrently with this method.
+    pub async fn run<S, R, M2>(
+        &self,
+        send: S,
+        recv: R,
+        net_ctl_state: &NetCtlStateMutex<M2>,
+        service_name: &str,
+    ) -> Result<(), Error>
+    where
+        S: NetworkSend,
+        R: NetworkReceive,
+        M2: RawMutex,
+    {
+        if self.matter.is_commissioned() {
+            info!("Device is commissioned, running over the operational network only");
+
+            self.matter.run_transport(send, recv).await
+        } else if self.comm_policy.concurrent_connection_supported() {
+            self.run_concurrent(send, recv, service_name).await
+        } else {
+            self.run_non_concurrent(send, recv, net_ctl_state, service_name)
+                .await
+        }
+    }
+
+    async fn run_concurrent<S, R>(&self, send: S, recv: R, service_name: &str) -> Result<(), Error>
+    where
+        S: NetworkSend,
+        R: NetworkReceive,
+    {
+        info!("Commissioning over BTP and the operational network concurrently");
+
+        self.enable_commissioning(DiscoveryCapabilities::BLE | DiscoveryCapabilities::IP)
+            .await?;
+
+        let send = ChainedNetwork::new(Address::is_btp, self.btp, send);
+        let recv = ChainedNetwork::new(Address::is_btp, self.btp, recv);
+
+        let mut transport = pin!(self.matter.run_transport(send, recv));
+
+        let mut bluetooth = pin!(async {
+            select(self.run_btp(service_name), async {
+                while let Some(expiry) = self.wait_failsafe().await {
+                    warn!("Commissioning did not complete in time");
+
+                    self.rollback(expiry)?;
+                    self.enable_commissioning(
+                        DiscoveryCapabilities::BLE | DiscoveryCapabilities::IP,
+                    )
+                    .await?;
+                }
+
+                info!("Commissioning complete, shutting down BTP");
+
+                self.btp.set_advertising(false);
+
+                // Give the BTP sessions a chance to be closed gracefully
+                self.matter.transport_mgr.close_sessions(
+                    |sess| sess.get_peer_addr().is_btp(),
+                    SessionRemovalReason::Closed,
+                );
+
+                Timer::after(Duration::from_secs(BTP_SHUTDOWN_GRACE_SECS)).await;
+
+                Ok(())
+            })
+            .coalesce()
+            .await?;
+
+            self.btp.context.borrow().remove(|_| true)?;
+
+            // The transport keeps running over the operational network
+            pending().await
+        });
+
+        select(&mut transport, &mut bluetooth).coalesce().await
+    }
+
+    async fn run_non_concurrent<S, R, M2>(
+        &self,
+        mut send: S,
+        mut recv: R,
+        net_ctl_state: &NetCtlStateMutex<M2>,
+        service_name: &str,
+    ) -> Result<(), Error>
+    where
+        S: NetworkSend,
+        R: NetworkReceive,
+        M2: RawMutex,
+    {
+        loop {
+            info!("Commissioning over BTP");
+
+            self.enable_commissioning(DiscoveryCapabilities::BLE)
+                .await?;
+
+            {
+                let mut transport = pin!(self.matter.run_transport(self.btp, self.btp));
+                let mut bluetooth = pin!(self.run_btp(service_name));
+                let mut prov = pin!(async {
+                    NetCtlState::wait_prov_ready(net_ctl_state, self.btp).await;
+                    Ok(())
+                });
+
+                select3(&mut transport, &mut bluetooth, &mut prov)
+                    .coalesce()
+                    .await?;
+            }
+
+            info!("Operational network provisioned, shutting down BTP");
+
+            self.btp.context.borrow().remove(|_| true)?;
+            self.matter.reset_transport()?;
+
+            let mut transport = pin!(self.matter.run_transport(&mut send, &mut recv));
+            let mut expired = pin!(self.wait_failsafe_expired());
+
+            match select(&mut transport, &mut expired).await {
+                Either::First(result) => break result,
+                Either::Second(expiry) => {
+                    warn!("Commissioning over the operational network did not complete in time, falling back to BTP");
+
+                    self.rollback(expiry)?;
+                    self.matter.reset_transport()?;
+
+                    net_ctl_state.lock(|state| *state.borrow_mut() = NetCtlState::new());
+                }
+            }
+        }
+    }
+
+    /// Run BTP, advertising only while a commissioning window is open.
+    async fn run_btp(&self, service_name: &str) -> Result<(), Error> {
+        let mut btp = pin!(self.btp.run(
+            service_name,
+            self.matter.dev_det(),
+            self.matter.dev_comm().discriminator,
+        ));
+
+        let mut advertising = pin!(async {
+            loop {
+                self.btp
+                    .set_advertising(self.matter.is_commissioning_open());
+
+                self.matter.wait_commissioning_changed().await;
+            }
+        });
+
+        select(&mut btp, &mut advertising).coalesce().await
+    }
+
+    /// (Re-)open the basic commissioning window.
+    async fn enable_commissioning(
+        &self,
+        discovery_capabilities: DiscoveryCapabilities,
+    ) -> Result<(), Error> {
+        self.matter.disable_commissioning()?;
+        self.matter
+            .enable_basic_commissioning(discovery_capabilities, 0)
+            .await
+    }
+
+    /// Wait until the fail-safe expires.
+    ///
+    /// Never completes, if `CommissioningComplete` is received before that.
+    async fn wait_failsafe_expired(&self) -> FailSafeExpiry {
+        if let Some(expiry) = self.wait_failsafe().await {
+            expiry
+        } else {
+            info!("Commissioning complete");
+
+            pending().await
+        }
+    }
+
+    /// Wait until the fail-safe either expires, or is disarmed with `CommissioningComplete`.
+    ///
+    /// Return the expiry information in the former case, and `None` in the latter.
+    async fn wait_failsafe(&self) -> Option<FailSafeExpiry> {
+        loop {
+            let (expiry, expires_in) = {
+                let mut failsafe = self.matter.failsafe.borrow_mut();
+
+                (failsafe.take_expiry(), failsafe.expires_in())
+            };
+
+            if expiry.is_some() {
+                break expiry;
+            }
+
+            if let Some(expires_in) = expires_in {
+                select(
+                    self.matter.wait_failsafe_changed(),
+                    Timer::after(Duration::from_micros(expires_in.as_micros() as u64)),
+                )
+                .await;
+            } else if self.matter.is_commissioned() {
+                break None;
+            } else {
+                self.matter.wait_failsafe_changed().await;
+            }
+        }
+    }
+
+    /// Remove the fabric which was added while the (now expired) fail-safe was armed.
+    pub(super) fn rollback(&self, expiry: FailSafeExpiry) -> Result<(), Error> {
+        if let Some(fab_idx) = expiry.added_fab_idx {
+            info!(
+                "Removing fabric {} added during the expired fail-safe",
+                fab_idx
+            );
+
+            self.matter.remove_fabric(fab_idx)?;
+        }
+
+        Ok(())
+    }
+}
@@ -18,7 +18,7 @@
 use core::sync::atomic::Ordering;
 
 use embassy_sync::blocking_mutex::raw::RawMutex;
-use portable_atomic::AtomicUsize;
+use portable_atomic::{AtomicBool, AtomicUsize};
 
 use crate::error::{Error, ErrorCode};
 use crate::fmt::Bytes;
@@ -30,11 +30,10 @@ use crate::utils::sync::Notification;
 
 use super::{session::Session, GattPeripheralEvent};
 
-/// The maximum number of BTP sessions that can be active at any given time.
-/// This is an `rs-matter` specific limit, and is not a requirement of the Matter BTP spec, and which in future should be configurable.
+/// The default maximum number of BTP sessions that can be active at any given time.
+/// This is an `rs-matter` specific limit, and is not a requirement of the Matter BTP spec.
 ///
-/// The `GattPeripheral` implementation is expected to enforce this limit as well,
-/// i.e. it should not allow more than `MAX_BTP_SESSIONS` active subscriptions to characteristic `C2`.
+/// A different limit can be used by instantiating `BtpContext` with a different `N` const generic parameter.
 pub const MAX_BTP_SESSIONS: usize = 2;
 
 /// Represents an error that occurred while trying to lock a session for sending.
@@ -53,15 +52,15 @@ pub(crate) enum LockError {
 /// or is sending the BTP PDUs of a single BTP SDU, which is a requirement of the Matter BTP spec.
 ///
 /// The send lock is removed once this object is dropped.
-pub(crate) struct SessionSendLock<'a, M>
+pub(crate) struct SessionSendLock<'a, M, const N: usize>
 where
     M: RawMutex,
 {
-    context: &'a BtpContext<M>,
+    context: &'a BtpContext<M, N>,
     address: BtAddr,
 }
 
-impl<'a, M> SessionSendLock<'a, M>
+impl<'a, M, const N: usize> SessionSendLock<'a, M, N>
 where
     M: RawMutex,
 {
@@ -72,7 +71,7 @@ where
     ///
     /// Due to the above semantics, the condition is expected to uniquely identify a session, by - say - matching on
     /// the session peer BLE address.
-    pub fn try_lock<F>(context: &'a BtpContext<M>, condition: F) -> Result<Self, LockError>
+    pub fn try_lock<F>(context: &'a BtpContext<M, N>, condition: F) -> Result<Self, LockError>
     where
         F: Fn(&Session) -> bool,
     {
@@ -98,7 +97,7 @@ where
     ///
     /// If all sessions matching the provided condition are already locked for sending, or if there is no
     /// session matching the provided condition, the method will return `None`.
-    pub fn lock_any<F>(context: &'a BtpContext<M>, condition: F) -> Option<Self>
+    pub fn lock_any<F>(context: &'a BtpContext<M, N>, condition: F) -> Option<Self>
     where
         F: Fn(&Session) -> bool,
     {
@@ -140,7 +139,7 @@ where
     }
 }
 
-impl<M> Drop for SessionSendLock<'_, M>
+impl<M, const N: usize> Drop for SessionSendLock<'_, M, N>
 where
     M: RawMutex,
 {
@@ -151,6 +150,8 @@ where
                 .iter_mut()
                 .find(|session| session.address() == self.address)
             {
+                let established = session.is_established();
+
                 if !session.set_sending(false) || !session.set_running() {
                     // If we reach here this is a bug, because
                     // - a `SessionSendLock` cannot be acqired unless the session is
@@ -160,6 +161,11 @@ where
                     //   and is unset when the lock is dropped.
                     unreachable!("Should not happen")
                 }
+
+                if !established {
+                    // The session just got established, so advertising might need to stop
+                    self.context.adv_notif.notify();
+                }
             }
         });
 
@@ -183,21 +189,27 @@ where
 /// While this simplifies the implementation of the `GattPeripheral` trait (as MCU-based Gatt peripheral stacks often expect a closure with these
 /// precise restrictions), it complicates the implementation of the BTP protocol and necessiates the isolation of the shared state in the
 /// `BtpContext` structure.
-pub struct BtpContext<M>
+///
+/// The `N` const generic parameter is the maximum number of BTP sessions that can be active at any given time.
+/// The `GattPeripheral` implementation is expected to enforce this limit as well,
+/// i.e. it should not allow more than `N` active subscriptions to characteristic `C2`.
+pub struct BtpContext<M, const N: usize = MAX_BTP_SESSIONS>
 where
     M: RawMutex,
 {
-    pub(crate) sessions: Mutex<M, RefCell<crate::utils::storage::Vec<Session, MAX_BTP_SESSIONS>>>,
+    pub(crate) sessions: Mutex<M, RefCell<crate::utils::storage::Vec<Session, N>>>,
     pub(crate) handshake_notif: Notification<M>,
     pub(crate) available_notif: Notification<M>,
     pub(crate) recv_notif: Notification<M>,
     pub(crate) ack_notif: Notification<M>,
     pub(crate) send_notif: Notification<M>,
     pub(crate) changed_notif: Notification<M>,
+    pub(crate) adv_notif: Notification<M>,
     pub(crate) conn_ct: AtomicUsize,
+    pub(crate) advertising: AtomicBool,
 }
 
-impl<M> Default for BtpContext<M>
+impl<M, const N: usize> Default for BtpContext<M, N>
 where
     M: RawMutex,
 {
@@ -206,7 +218,7 @@ where
     }
 }
 
-impl<M> BtpContext<M>
+impl<M, const N: usize> BtpContext<M, N>
 where
     M: RawMutex,
 {
@@ -221,7 +233,9 @@ where
             ack_notif: Notification::new(),
             send_notif: Notification::new(),
             changed_notif: Notification::new(),
+            adv_notif: Notification::new(),
             conn_ct: AtomicUsize::new(0),
+            advertising: AtomicBool::new(true),
         }
     }
 
@@ -235,12 +249,14 @@ where
             ack_notif: Notification::new(),
             send_notif: Notification::new(),
             changed_notif: Notification::new(),
+            adv_notif: Notification::new(),
             conn_ct: AtomicUsize::new(0),
+            advertising: AtomicBool::new(true),
         })
     }
 }
 
-impl<M> BtpContext<M>
+impl<M, const N: usize> BtpContext<M, N>
 where
     M: RawMutex,
 {
@@ -272,7 +288,24 @@ where
             let mut sessions = sessions.borrow_mut();
 
             if Session::is_handshake(data)? {
-                if sessions.len() >= MAX_BTP_SESSIONS {
+                if let Some(index) = sessions
+                    .iter()
+                    .position(|session| session.address() == address)
+                {
+                    // The remote peer had reconnected (or restarted the handshake) without us noticing
+                    // the end of the previous session, so the previous session is stale by now
+                    warn!(
+                        "Got a handshake request from address {} which already has a session, replacing it",
+                        address
+                    );
+
+                    sessions.swap_remove(index);
+
+                    self.send_notif.notify();
+                    self.adv_notif.notify();
+                }
+
+                if sessions.len() >= N {
                     warn!(
                         "Too many BTP sessions, dropping a handshake request from address {}",
                         address
@@ -320,7 +353,7 @@ where
         })
     }
 
-    /// Handles a subscribe event to characteristic `C2` from the GATT peripheral.
+    /// Handles a connect event from the GATT peripheral.
     fn on_connect(&self, address: BtAddr) -> Result<(), Error> {
         debug!("Connect request from {}", address);
 
@@ -330,11 +363,18 @@ where
         Ok(())
     }
 
-    /// Handles an unsubscribe event to characteristic `C2` from the GATT peripheral.
+    /// Handles a disconnect event from the GATT peripheral.
+    ///
+    /// Removes the session of the remote peer (if any), as it cannot be used anymore.
     fn on_disconnect(&self, address: BtAddr) -> Result<(), Error> {
         debug!("Disconnect request from {}", address);
 
-        self.conn_ct.fetch_sub(1, Ordering::SeqCst);
+        self.remove(|session| session.address() == address)?;
+
+        // Saturate, as a GATT peripheral might report a disconnect without a prior connect
+        let _ = self
+            .conn_ct
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |ct| ct.checked_sub(1));
         self.changed_notif.notify();
 
         Ok(())
@@ -377,10 +417,50 @@ where
         Ok(())
     }
 
+    /// Return the number of GATT centrals currently connected to the peripheral.
     pub fn conn_ct(&self) -> usize {
         self.conn_ct.load(Ordering::SeqCst)
     }
 
+    /// Return the number of BTP sessions, including the ones for which the handshake is not complete yet.
+    pub fn session_ct(&self) -> usize {
+        self.sessions.lock(|sessions| sessions.borrow().len())
+    }
+
+    /// Enable or disable advertising of the Matter BLE service.
+    ///
+    /// Advertising is enabled by default. Users are expected to disable it when the commissioning window
+    /// is closed, and to enable it again once the commissioning window is re-opened
+    /// (`BtpCommissioning` does this automatically).
+    ///
+    /// Note that even when enabled, the service is not advertised while the maximum number of
+    /// BTP sessions (`N`) is established.
+    pub fn set_advertising(&self, enabled: bool) {
+        if self.advertising.swap(enabled, Ordering::SeqCst) != enabled {
+            debug!("BTP advertising enabled: {}", enabled);
+            self.adv_notif.notify();
+        }
+    }
+
+    /// Return `true` if advertising of the Matter BLE service is enabled.
+    pub fn is_advertising_enabled(&self) -> bool {
+        self.advertising.load(Ordering::SeqCst)
+    }
+
+    /// Return `true` if the Matter BLE service should be advertised right now, i.e.
+    /// advertising is enabled and there are less than `N` established BTP sessions.
+    pub(crate) fn should_advertise(&self) -> bool {
+        self.is_advertising_enabled()
+            && self.sessions.lock(|sessions| {
+                sessions
+                    .borrow()
+                    .iter()
+                    .filter(|session| session.is_established())
+                    .count()
+                    < N
+            })
+    }
+
     pub async fn wait_changed(&self) {
         self.changed_notif.wait().await;
     }
@@ -397,6 +477,7 @@ where
                 debug!("Session {} removed", session.address());
 
                 self.send_notif.notify();
+                self.adv_notif.notify();
             }
 
             Ok(())
@@ -185,7 +185,7 @@ pub trait GattPeripheral {
     /// - GATT peripheral lifecycle:
     ///   - Start avertising a GATT service with UUID `MATTER_BLE_SERVICE_UUID16`, by utilizing
     ///     the provided `service_name` and `adv_data` parameters.
-    ///   - Possibly stop advertising the GATT service when the first notification subscription is received.
+    ///   - Stop and resume advertising the GATT service, as instructed by the BTP protocol via `set_advertising`.
     ///   - Stop advertising and tear down the GATT service when the future of this method is dropped.
     /// - Gatt peripheral incoming data:
     ///   - Handle incoming GATT events, and call the provided `callback` function for each event.
@@ -209,6 +209,22 @@ pub trait GattPeripheral {
     ///
     /// See the Matter Core spec w.r.t. details on characteristic C2.
     async fn indicate(&self, data: &[u8], address: BtAddr) -> Result<(), Error>;
+
+    /// Start (`advertise = true`) or stop (`advertise = false`) advertising the GATT service.
+    ///
+    /// The BTP protocol calls this method to stop advertising while a BTP session is established,
+    /// or when the user had disabled advertising (i.e. because the commissioning window is closed),
+    /// and to resume advertising afterwards. The method might be called before `run` is called.
+    ///
+    /// Stopping the advertising should not affect the already connected GATT centrals.
+    ///
+    /// The default implementation does nothing, i.e. the GATT service is advertised for as long as
+    /// `run` is running.
+    async fn set_advertising(&self, advertise: bool) -> Result<(), Error> {
+        let _ = advertise;
+
+        Ok(())
+    }
 }
 
 impl<T> GattPeripheral for &T
@@ -230,6 +246,10 @@ where
     async fn indicate(&self, data: &[u8], address: BtAddr) -> Result<(), Error> {
         (*self).indicate(data, address).await
     }
+
+    async fn set_advertising(&self, advertise: bool) -> Result<(), Error> {
+        (*self).set_advertising(advertise).await
+    }
 }
 
 impl<T> GattPeripheral for &mut T
@@ -251,4 +271,8 @@ where
     async fn indicate(&self, data: &[u8], address: BtAddr) -> Result<(), Error> {
         (**self).indicate(data, address).await
     }
+
+    async fn set_advertising(&self, advertise: bool) -> Result<(), Error> {
+        (**self).set_advertising(advertise).await
+    }
 }
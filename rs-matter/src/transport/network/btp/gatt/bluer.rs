@@ -28,9 +28,10 @@ use bluer::gatt::local::{
     CharacteristicWrite, CharacteristicWriteMethod, Service,
 };
 use bluer::gatt::CharacteristicWriter;
+use bluer::Adapter;
 use bluer::Uuid;
 
-use embassy_futures::select::{select, select_slice, Either};
+use embassy_futures::select::{select, select3, select_slice, Either};
 
 use tokio::io::AsyncWriteExt;
 use tokio_stream::StreamExt;
@@ -38,7 +39,7 @@ use tokio_stream::StreamExt;
 use crate::error::{Error, ErrorCode};
 use crate::fmt::Bytes;
 use crate::transport::network::btp::MIN_MTU;
-use crate::transport::network::{btp::context::MAX_BTP_SESSIONS, BtAddr};
+use crate::transport::network::BtAddr;
 use crate::utils::init::{init_from_closure, Init};
 use crate::utils::select::Coalesce;
 use crate::utils::sync::blocking::raw::StdRawMutex;
@@ -48,27 +49,30 @@ use crate::utils::sync::Signal;
 use super::{AdvData, GattPeripheral, GattPeripheralEvent};
 use super::{C1_CHARACTERISTIC_UUID, C2_CHARACTERISTIC_UUID, MATTER_BLE_SERVICE_UUID};
 
-const MAX_CONNECTIONS: usize = MAX_BTP_SESSIONS;
-
 /// The internal state of the peripheral.
 /// Arc-ed so as to be thread-safe and to have `'static` interior, as demanded by the BlueR bindings.
 struct GattState {
     /// The name of the bluetooth adapter to use. If `None`, the default adapter is used.
     adapter_name: Option<String>,
     /// The list of active notifiers on characteristic `C2`.
-    notifiers: IfMutex<StdRawMutex, heapless::Vec<CharacteristicWriter, MAX_CONNECTIONS>>,
+    ///
+    /// Not bounded, as the maximum number of BTP sessions is enforced by the `BtpContext` instance in use.
+    notifiers: IfMutex<StdRawMutex, Vec<CharacteristicWriter>>,
     /// A signal necessary so that we can switch between two states:
     /// - Indicating data to a notifier
     /// - Listening all notifiers for a closed one (i.e. a remote peer had unsubscribed from characteristic `C2`)
     notifiers_listen_allowed: Signal<StdRawMutex, bool>,
+    /// Whether the service should currently be advertised.
+    advertise: Signal<StdRawMutex, bool>,
 }
 
 impl GattState {
     pub fn new(adapter_name: Option<&str>) -> Self {
         Self {
             adapter_name: adapter_name.map(|name| name.into()),
-            notifiers: IfMutex::new(heapless::Vec::new()),
+            notifiers: IfMutex::new(Vec::new()),
             notifiers_listen_allowed: Signal::new(true),
+            advertise: Signal::new(true),
         }
     }
 }
@@ -108,7 +112,8 @@ impl BluerGattPeripheral {
     /// Runs the GATT peripheral service.
     /// What this means in details:
     /// - Advertises the service with the provided name and advertising data, where the advertising data
-    ///   contains the elements specified in the Matter Core spec.
+    ///   contains the elements specified in the Matter Core spec, for as long as advertising is enabled
+    ///   with `set_advertising`.
     /// - Serves a GATT peripheral service with the `C1`, `C2` and `C3` characteristics, as specified
     ///   in the Matter Core spec.
     /// - Calls the provided callback with the events that occur during the service lifetime, on the `C1`
@@ -136,7 +141,7 @@ impl BluerGattPeripheral {
         adapter.set_powered(true).await?;
 
         info!(
-            "Advertising on Bluetooth adapter {} with address {}",
+            "Using Bluetooth adapter {} with address {}",
             adapter.name(),
             BtAddr(adapter.address().await?.0)
         );
@@ -152,9 +157,6 @@ impl BluerGattPeripheral {
             ..Default::default()
         };
 
-        // TODO: Stop advertizing after the first connection?
-        let _adv_handle = adapter.advertise(le_advertisement).await?;
-
         info!(
             "Serving GATT echo service on Bluetooth adapter {}",
             adapter.name()
@@ -232,14 +234,56 @@ impl BluerGattPeripheral {
 
         let _app_handle = adapter.serve_gatt_application(app).await?;
 
-        select(
+        select3(
             self.closed(callback_s),
             self.pull_notify(notify, callback_n),
+            self.advertise(&adapter, &le_advertisement),
         )
         .coalesce()
         .await
     }
 
+    /// Start (`advertise = true`) or stop (`advertise = false`) advertising the service.
+    pub fn set_advertising(&self, advertise: bool) {
+        self.0.advertise.modify(|state| {
+            let changed = *state != advertise;
+            *state = advertise;
+
+            (changed, ())
+        });
+    }
+
+    /// Advertise the service while advertising is enabled.
+    async fn advertise(
+        &self,
+        adapter: &Adapter,
+        advertisement: &Advertisement,
+    ) -> Result<(), Error> {
+        loop {
+            self.0
+                .advertise
+                .wait(|advertise| (*advertise).then_some(()))
+                .await;
+
+            let adv_handle = adapter.advertise(advertisement.clone()).await?;
+
+            info!("Advertising on Bluetooth adapter {}", adapter.name());
+
+            self.0
+                .advertise
+                .wait(|advertise| (!*advertise).then_some(()))
+                .await;
+
+            // Dropping the handle stops the advertising
+            drop(adv_handle);
+
+            info!(
+                "Stopped advertising on Bluetooth adapter {}",
+                adapter.name()
+            );
+        }
+    }
+
     /// Indicate new data on characteristic `C2` to a remote peer.
     pub async fn indicate(&self, data: &[u8], address: BtAddr) -> Result<(), Error> {
         self.0.notifiers_listen_allowed.modify(|listen| {
@@ -286,16 +330,8 @@ impl BluerGattPeripheral {
 
         let address = BtAddr(notifier.device_address().0);
 
-        if notifiers.len() < MAX_CONNECTIONS {
-            // Unwraping is safe because we just checked the length
-            unwrap!(notifiers.push(notifier).map_err(|_| ()));
-            trace!("Notify connection from address {} started", address);
-        } else {
-            warn!(
-                "Notifiers limit reached; ignoring notifier from address {}",
-                address
-            );
-        }
+        notifiers.push(notifier);
+        trace!("Notify connection from address {} started", address);
 
         drop(notifiers);
 
@@ -357,7 +393,7 @@ impl BluerGattPeripheral {
                 let mut closed = notifiers
                     .iter()
                     .map(|notifier| notifier.closed())
-                    .collect::<heapless::Vec<_, MAX_CONNECTIONS>>();
+                    .collect::<Vec<_>>();
 
                 // Await until we are no longer allowed to await (future notifiers_listen_allowed)
                 // or until we have a closed notifier
@@ -397,4 +433,10 @@ impl GattPeripheral for BluerGattPeripheral {
     async fn indicate(&self, data: &[u8], address: BtAddr) -> Result<(), Error> {
         BluerGattPeripheral::indicate(self, data, address).await
     }
+
+    async fn set_advertising(&self, advertise: bool) -> Result<(), Error> {
+        BluerGattPeripheral::set_advertising(self, advertise);
+
+        Ok(())
+    }
 }
@@ -38,6 +38,10 @@ pub(crate) const BTP_ACK_TIMEOUT_SECS: u16 = BTP_CONN_IDLE_TIMEOUT_SECS / 2;
 /// The maximum amount of time no unique data has been sent over a BTP session before the
 /// Central Device must close the BTP session.
 pub(crate) const BTP_CONN_IDLE_TIMEOUT_SECS: u16 = 30;
+/// Matter Core spec constant:
+/// The maximum amount of time after the BTP Handshake Request is received before the
+/// BTP session must be established, or else it is closed.
+pub(crate) const BTP_CONN_HANDSHAKE_TIMEOUT_SECS: u16 = 30;
 
 /// Represents the three possible states of each BTP session
 #[derive(Debug)]
@@ -372,6 +376,10 @@ pub struct Session {
     version: u8,
     mtu: u16,
     window_size: u8,
+    /// The instant when the BTP Handshake Request was received, i.e. when the session was created.
+    created_at: Instant,
+    /// The instant when the last BTP segment (data or standalone ACK) was received.
+    last_rx: Instant,
     recv_window: RecvWindow,
     send_window: SendWindow,
 }
@@ -383,12 +391,16 @@ impl Session {
     /// Initializing a session is done based on the data that had arrived in the Handshake Request message,
     /// written by a remote peer on the `C1` characteristic.
     fn init(address: BtAddr, version: u8, mtu: u16, window_size: u8) -> impl Init<Self> {
+        let now = Instant::now();
+
         init!(Self {
             address,
             state: SessionState::New,
             version,
             mtu,
             window_size,
+            created_at: now,
+            last_rx: now,
             recv_window <- RecvWindow::init(window_size),
             send_window: SendWindow::new(window_size),
         })
@@ -399,6 +411,12 @@ impl Session {
         self.address
     }
 
+    /// Return true if the session is fully established, i.e. the BTP Handshake Response message
+    /// had been sent to the remote peer.
+    pub fn is_established(&self) -> bool {
+        matches!(self.state, SessionState::Running)
+    }
+
     /// Return true if this session is in a state where we need to send a BTP Handshake Response message
     /// to the remote peer (i.e. the remote peer did subscribe to characteristic `C2`).
     pub fn is_handshake_resp_due(&self) -> bool {
@@ -420,13 +438,29 @@ impl Session {
     }
 
     /// Return true if this session needs to be removed due to inactivity.
-    /// (I.e. the remote peer did not sent an ACK in due time.)
-    pub fn is_timed_out(&self, now: Instant, conn_idle_timeout_secs: u16) -> bool {
-        self.send_window
-            .sent_at
-            .checked_add(Duration::from_secs(conn_idle_timeout_secs as _))
-            .map(|expires| expires < now)
-            .unwrap_or(false)
+    /// I.e.:
+    /// - The session is not established yet, and the remote peer did not complete the handshake in due time, or
+    /// - The remote peer did not sent an ACK in due time, or
+    /// - The remote peer did not send anything at all in due time.
+    pub fn is_timed_out(
+        &self,
+        now: Instant,
+        handshake_timeout_secs: u16,
+        conn_idle_timeout_secs: u16,
+    ) -> bool {
+        let expired = |since: Instant, timeout_secs: u16| {
+            since
+                .checked_add(Duration::from_secs(timeout_secs as _))
+                .map(|expires| expires < now)
+                .unwrap_or(false)
+        };
+
+        if self.is_established() {
+            expired(self.send_window.sent_at, conn_idle_timeout_secs)
+                || expired(self.last_rx, conn_idle_timeout_secs)
+        } else {
+            expired(self.created_at, handshake_timeout_secs)
+        }
     }
 
     /// Set the session in subscribed state.
@@ -510,38 +544,59 @@ impl Session {
 
         let version = req.versions().min().unwrap_or(4);
 
-        let mtu = if gatt_mtu.map(|gatt_mtu| gatt_mtu != req.mtu).unwrap_or(true) {
-            if let Some(gatt_mtu) = gatt_mtu {
-                warn!(
-                    "MTU mismatch: GATT MTU: {}, BTP MTU: {}, will use MTU: {}",
-                    gatt_mtu, req.mtu, MIN_MTU
-                );
-            }
-
-            // We don't know our MTU or what we know is not what the other peer reports
-            // => use the minimum MTU
-            MIN_MTU
-        } else {
-            // Used MTU should not be bigger than the maximum allowed
-            min(req.mtu, MAX_MTU)
-        };
+        let mtu = Self::negotiate_mtu(req.mtu, gatt_mtu);
 
         // Remove the header as we need to report back the payload MTU
         // and we'll use the payload MTU anyway for all operations
         let mtu = mtu - GATT_HEADER_SIZE as u16;
 
-        // Make sure we are using a window size that would allow us to receive at least one full BTP SDU
-        // TODO: Revisit the mtu and window_size computations
-        let window_size = min(
-            req.window_size,
-            min(MAX_MESSAGE_SIZE as u16 / mtu / 2, 255) as u8,
-        );
+        let window_size = Self::negotiate_window_size(req.window_size, mtu);
 
         debug!("\n>>RCV (BTP IO) {} [{}]\n      HANDSHAKE REQ {:?}\nSelected version: {}, MTU: {}, window size: {}", address, hdr, req, version, mtu, window_size);
 
         Ok(Self::init(address, version, mtu, window_size))
     }
 
+    /// Select the MTU (including the GATT header) to be used by the session.
+    ///
+    /// As per the Matter Core spec, the selected MTU is the smaller one of the ATT MTU negotiated
+    /// by the GATT stack and the MTU requested by the remote peer in its handshake request, where a
+    /// requested MTU of 0 means that the remote peer has no preference.
+    ///
+    /// If the GATT stack does not report the ATT MTU, we cannot know how large the GATT packets might
+    /// be, so the minimum MTU is used.
+    fn negotiate_mtu(req_mtu: u16, gatt_mtu: Option<u16>) -> u16 {
+        let mtu = match gatt_mtu {
+            Some(gatt_mtu) if req_mtu == 0 => gatt_mtu,
+            Some(gatt_mtu) => {
+                if gatt_mtu != req_mtu {
+                    debug!(
+                        "MTU mismatch: GATT MTU: {}, BTP MTU: {}, will use the smaller one",
+                        gatt_mtu, req_mtu
+                    );
+                }
+
+                min(gatt_mtu, req_mtu)
+            }
+            None => MIN_MTU,
+        };
+
+        mtu.clamp(MIN_MTU, MAX_MTU)
+    }
+
+    /// Select the window size to be used by the session, given the window size requested by the
+    /// remote peer and the selected (payload) MTU.
+    ///
+    /// The receiving ring-buffer has room for two full Matter messages, and we do not ACK segments
+    /// while a complete message is waiting in the buffer for consumption. Hence, a full window of
+    /// segments must fit into the space of a single message, or the remote peer might legitimately
+    /// overflow our buffer.
+    fn negotiate_window_size(req_window_size: u8, mtu: u16) -> u8 {
+        let max_window_size = min(MAX_RX_PACKET_SIZE / mtu as usize, u8::MAX as usize) as u8;
+
+        min(req_window_size, max_window_size).max(1)
+    }
+
     /// Process an incoming BTP segment of a regular data or ACK type, updating the state of the session accordingly.
     pub fn process_rx_data(&mut self, data: &[u8]) -> Result<(), Error> {
         let mut iter = data.iter();
@@ -558,6 +613,7 @@ impl Session {
 
         self.recv_window.accept_incoming(&hdr, payload, self.mtu)?;
         self.send_window.accept_incoming(&hdr);
+        self.last_rx = Instant::now();
 
         Ok(())
     }
@@ -32,6 +32,8 @@ use super::*;
 extern crate alloc;
 
 const PEER_ADDR: BtAddr = BtAddr([1, 2, 3, 4, 5, 6]);
+const PEER_ADDR2: BtAddr = BtAddr([2, 3, 4, 5, 6, 7]);
+const PEER_ADDR3: BtAddr = BtAddr([3, 4, 5, 6, 7, 8]);
 
 const BASIC_INFO: BasicInfoConfig<'static> = BasicInfoConfig {
     vid: 10,
@@ -46,11 +48,16 @@ const BASIC_INFO: BasicInfoConfig<'static> = BasicInfoConfig {
     vendor_name: "TestVendor",
     sai: None,
     sii: None,
+    sat: None,
+    max_paths_per_invoke: None,
+    privacy: false,
 };
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 enum PeripheralIncoming {
+    Connected(BtAddr),
+    Disconnected(BtAddr),
     Subscribed(BtAddr),
     Unsubscribed(BtAddr),
     Write {
@@ -71,9 +78,26 @@ struct PeripheralOutgoing {
 struct Peripheral {
     peer_sender: async_channel::Sender<PeripheralIncoming>,
     peer_receiver: async_channel::Receiver<PeripheralOutgoing>,
+    advertising: async_channel::Receiver<bool>,
 }
 
 impl Peripheral {
+    /// Generate `GattPeripheralEvent::NotifyConnected` event for the peer
+    async fn connect(&self, addr: BtAddr) {
+        self.peer_sender
+            .send(PeripheralIncoming::Connected(addr))
+            .await
+            .unwrap();
+    }
+
+    /// Generate `GattPeripheralEvent::NotifyDisconnected` event for the peer
+    async fn disconnect(&self, addr: BtAddr) {
+        self.peer_sender
+            .send(PeripheralIncoming::Disconnected(addr))
+            .await
+            .unwrap();
+    }
+
     /// Generate `GattPeripheralEvent::NotifySubscribed` event for the peer
     async fn subscribe(&self, addr: BtAddr) {
         self.peer_sender
@@ -110,6 +134,13 @@ impl Peripheral {
         ::core::assert_eq!(received.data, data);
         ::core::assert_eq!(received.address, addr);
     }
+
+    /// Expect the BTP protocol to turn the advertising on or off
+    async fn expect_advertising(&self, advertising: bool) {
+        let received = self.advertising.recv().await.unwrap();
+
+        ::core::assert_eq!(received, advertising);
+    }
 }
 
 #[derive(Debug)]
@@ -156,9 +187,32 @@ impl Io {
 struct GattPeriheralMock {
     sender: async_channel::Sender<PeripheralOutgoing>,
     receiver: async_channel::Receiver<PeripheralIncoming>,
+    advertising: async_channel::Sender<bool>,
 }
 
 impl GattPeriheralMock {
+    /// Create a new mock peripheral, along with the `Peripheral` instance used to send and receive data
+    /// on behalf of the peer ("peripheral")
+    fn new() -> (Self, Peripheral) {
+        let (sender, peer_receiver) = async_channel::unbounded();
+        let (peer_sender, receiver) = async_channel::unbounded();
+
+        let (advertising, peer_advertising) = async_channel::unbounded();
+
+        (
+            Self {
+                sender,
+                receiver,
+                advertising,
+            },
+            Peripheral {
+                peer_sender,
+                peer_receiver,
+                advertising: peer_advertising,
+            },
+        )
+    }
+
     /// Run the provided test closure using the mock peripheral
     ///
     /// The test closure may use the provided `Peripheral` instance
@@ -171,12 +225,21 @@ impl GattPeriheralMock {
         T: FnOnce(Peripheral, Io) -> F,
         F: Future<Output = ()> + Send + 'static,
     {
-        Self::run_with_custom_timeouts(BTP_ACK_TIMEOUT_SECS, BTP_CONN_IDLE_TIMEOUT_SECS, test)
+        Self::run_with_custom_timeouts(
+            BTP_ACK_TIMEOUT_SECS,
+            BTP_CONN_HANDSHAKE_TIMEOUT_SECS,
+            BTP_CONN_IDLE_TIMEOUT_SECS,
+            test,
+        )
     }
 
-    /// Same as run but provides the opportunity for custom ACK timeout.
-    fn run_with_custom_timeouts<T, F>(ack_timeout_secs: u16, conn_idle_timeout_secs: u16, test: T)
-    where
+    /// Same as run but provides the opportunity for custom ACK, handshake and idle timeouts.
+    fn run_with_custom_timeouts<T, F>(
+        ack_timeout_secs: u16,
+        handshake_timeout_secs: u16,
+        conn_idle_timeout_secs: u16,
+        test: T,
+    ) where
         T: FnOnce(Peripheral, Io) -> F,
         F: Future<Output = ()> + Send + 'static,
     {
@@ -189,16 +252,14 @@ impl GattPeriheralMock {
 
         // Pipe send/receive data between the mocked peripheral and the BTP protocol using channels.
 
-        let (sender, peer_receiver) = async_channel::unbounded();
-        let (peer_sender, receiver) = async_channel::unbounded();
-
-        let mock = GattPeriheralMock { sender, receiver };
+        let (mock, peripheral) = GattPeriheralMock::new();
 
         let context = Arc::new(BtpContext::<StdRawMutex>::new());
         let btp = Arc::new(Btp::new_internal(
             mock,
             context.clone(),
             ack_timeout_secs,
+            handshake_timeout_secs,
             conn_idle_timeout_secs,
         ));
 
@@ -206,10 +267,7 @@ impl GattPeriheralMock {
         let (io_btp_sender, io_receiver) = async_channel::unbounded();
 
         let test_fut = Box::pin(test(
-            Peripheral {
-                peer_sender,
-                peer_receiver,
-            },
+            peripheral,
             Io {
                 send: io_sender.clone(),
                 recv: io_receiver.clone(),
@@ -273,6 +331,12 @@ impl GattPeripheral for GattPeriheralMock {
         Ok(())
     }
 
+    async fn set_advertising(&self, advertise: bool) -> Result<(), Error> {
+        self.advertising.send(advertise).await.unwrap();
+
+        Ok(())
+    }
+
     async fn run<F>(
         &self,
         _service_name: &str,
@@ -284,6 +348,12 @@ impl GattPeripheral for GattPeriheralMock {
     {
         while let Ok(msg) = self.receiver.recv().await {
             match msg {
+                PeripheralIncoming::Connected(addr) => {
+                    callback(GattPeripheralEvent::NotifyConnected(addr));
+                }
+                PeripheralIncoming::Disconnected(addr) => {
+                    callback(GattPeripheralEvent::NotifyDisconnected(addr));
+                }
                 PeripheralIncoming::Subscribed(addr) => {
                     callback(GattPeripheralEvent::NotifySubscribed(addr));
                 }
@@ -363,15 +433,20 @@ fn test_mtu() {
 
 // Utility to do the negotiation phase with a minumum MTU
 async fn nego_min_mtu(peripheral: &Peripheral) {
+    nego_min_mtu_with(peripheral, PEER_ADDR).await;
+}
+
+// Utility to do the negotiation phase with a minumum MTU for a concrete peer
+async fn nego_min_mtu_with(peripheral: &Peripheral, addr: BtAddr) {
     peripheral
         .send(
             &[0x65, 0x6c, 0x54, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x05],
-            PEER_ADDR,
+            addr,
             None,
         )
         .await;
 
-    peripheral.subscribe(PEER_ADDR).await;
+    peripheral.subscribe(addr).await;
 
     // io.context.sessions.lock(|sessions| {
     //     assert!(sessions.borrow().len() == 1);
@@ -379,7 +454,7 @@ async fn nego_min_mtu(peripheral: &Peripheral) {
 
     // Peer window = 1 because of this handshake resp
     peripheral
-        .expect(&[0x65, 0x6c, 0x05, 0x14, 0x00, 0x05], PEER_ADDR)
+        .expect(&[0x65, 0x6c, 0x05, 0x14, 0x00, 0x05], addr)
         .await;
 }
 
@@ -577,6 +652,7 @@ fn test_long_write_ack() {
 fn test_idle_ping_pong() {
     GattPeriheralMock::run_with_custom_timeouts(
         0,
+        BTP_CONN_HANDSHAKE_TIMEOUT_SECS,
         BTP_CONN_IDLE_TIMEOUT_SECS,
         |peripheral, _io| async move {
             nego_min_mtu(&peripheral).await;
@@ -602,6 +678,7 @@ fn test_idle_ping_pong() {
 fn test_idle_timeout() {
     GattPeriheralMock::run_with_custom_timeouts(
         BTP_ACK_TIMEOUT_SECS,
+        BTP_CONN_HANDSHAKE_TIMEOUT_SECS,
         1,
         |peripheral, io| async move {
             nego_min_mtu(&peripheral).await;
@@ -615,3 +692,373 @@ fn test_idle_timeout() {
         },
     );
 }
+
+#[test]
+fn test_mtu_negotiation() {
+    GattPeriheralMock::run(|peripheral, _io| async move {
+        // The peer asks for a smaller MTU (0x64) than the GATT MTU (0xc8)
+        peripheral
+            .send(
+                &[0x65, 0x6c, 0x54, 0x00, 0x00, 0x00, 0x64, 0x00, 0x05],
+                PEER_ADDR,
+                Some(0xc8),
+            )
+            .await;
+
+        peripheral.subscribe(PEER_ADDR).await;
+
+        // Expected MTU in response is the smaller one: 0x64 - 3 = 0x61
+        peripheral
+            .expect(&[0x65, 0x6c, 0x05, 0x61, 0x00, 0x05], PEER_ADDR)
+            .await;
+
+        // The peer has no MTU preference (0)
+        peripheral
+            .send(
+                &[0x65, 0x6c, 0x54, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05],
+                PEER_ADDR2,
+                Some(0xc8),
+            )
+            .await;
+
+        peripheral.subscribe(PEER_ADDR2).await;
+
+        // Expected MTU in response is the GATT one: 0xc8 - 3 = 0xc5
+        peripheral
+            .expect(&[0x65, 0x6c, 0x05, 0xc5, 0x00, 0x05], PEER_ADDR2)
+            .await;
+    });
+}
+
+#[test]
+fn test_multiple_connections() {
+    GattPeriheralMock::run(|peripheral, io| async move {
+        peripheral.connect(PEER_ADDR).await;
+        peripheral.connect(PEER_ADDR2).await;
+
+        nego_min_mtu_with(&peripheral, PEER_ADDR).await;
+        nego_min_mtu_with(&peripheral, PEER_ADDR2).await;
+
+        assert_eq!(io.context.conn_ct(), 2);
+        assert_eq!(io.context.session_ct(), 2);
+
+        // Both sessions are usable at the same time
+        peripheral
+            .send(&[5, 0, 3, 0, 1, 2, 3], PEER_ADDR2, None)
+            .await;
+        io.expect(&[1, 2, 3], PEER_ADDR2).await;
+
+        peripheral
+            .send(&[5, 0, 3, 0, 4, 5, 6], PEER_ADDR, None)
+            .await;
+        io.expect(&[4, 5, 6], PEER_ADDR).await;
+
+        // No room for a third session
+        peripheral
+            .send(
+                &[0x65, 0x6c, 0x54, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x05],
+                PEER_ADDR3,
+                None,
+            )
+            .await;
+
+        Timer::after(Duration::from_millis(100)).await;
+
+        assert_eq!(io.context.session_ct(), 2);
+
+        // Once a peer disconnects, its session is gone
+        peripheral.disconnect(PEER_ADDR).await;
+
+        Timer::after(Duration::from_millis(100)).await;
+
+        assert_eq!(io.context.conn_ct(), 1);
+        io.context.sessions.lock(|sessions| {
+            let sessions = sessions.borrow();
+            assert_eq!(sessions.len(), 1);
+            assert_eq!(sessions[0].address(), PEER_ADDR2);
+        });
+
+        // ... and the third peer can connect now
+        nego_min_mtu_with(&peripheral, PEER_ADDR3).await;
+    });
+}
+
+#[test]
+fn test_reconnect() {
+    GattPeriheralMock::run(|peripheral, io| async move {
+        nego_min_mtu(&peripheral).await;
+
+        peripheral
+            .send(&[5, 0, 3, 0, 1, 2, 3], PEER_ADDR, None)
+            .await;
+        io.expect(&[1, 2, 3], PEER_ADDR).await;
+
+        // The peer reconnects without us noticing that the previous connection is gone;
+        // the new handshake should replace the stale session
+        nego_min_mtu(&peripheral).await;
+
+        assert_eq!(io.context.session_ct(), 1);
+
+        // Sequence numbers start from scratch
+        peripheral
+            .send(&[5, 0, 3, 0, 4, 5, 6], PEER_ADDR, None)
+            .await;
+        io.expect(&[4, 5, 6], PEER_ADDR).await;
+    });
+}
+
+#[test]
+fn test_handshake_timeout() {
+    GattPeriheralMock::run_with_custom_timeouts(
+        BTP_ACK_TIMEOUT_SECS,
+        1,
+        BTP_CONN_IDLE_TIMEOUT_SECS,
+        |peripheral, io| async move {
+            // Handshake request, but the peer never subscribes
+            peripheral
+                .send(
+                    &[0x65, 0x6c, 0x54, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x05],
+                    PEER_ADDR,
+                    None,
+                )
+                .await;
+
+            Timer::after(Duration::from_millis(100)).await;
+
+            assert_eq!(io.context.session_ct(), 1);
+
+            Timer::after(Duration::from_secs(3)).await;
+
+            // Session should be closed by now
+            assert_eq!(io.context.session_ct(), 0);
+        },
+    );
+}
+
+#[test]
+fn test_advertising() {
+    GattPeriheralMock::run(|peripheral, io| async move {
+        peripheral.expect_advertising(true).await;
+
+        // Advertising continues while less than `MAX_BTP_SESSIONS` sessions are established...
+        nego_min_mtu(&peripheral).await;
+
+        Timer::after(Duration::from_millis(100)).await;
+
+        assert!(peripheral.advertising.is_empty());
+
+        // ... stops once the limit is reached...
+        nego_min_mtu_with(&peripheral, PEER_ADDR2).await;
+        peripheral.expect_advertising(false).await;
+
+        // ... and resumes once a session is gone
+        peripheral.unsubscribe(PEER_ADDR).await;
+        peripheral.expect_advertising(true).await;
+
+        // Advertising stops when disabled by the user (i.e. commissioning window closed)...
+        io.context.set_advertising(false);
+        peripheral.expect_advertising(false).await;
+
+        // ... and stays off even if re-enabled, while the session limit is reached
+        nego_min_mtu(&peripheral).await;
+        io.context.set_advertising(true);
+
+        Timer::after(Duration::from_millis(100)).await;
+
+        assert!(peripheral.advertising.is_empty());
+
+        peripheral.unsubscribe(PEER_ADDR2).await;
+        peripheral.expect_advertising(true).await;
+    });
+}
+
+std::thread_local! {
+    /// The current time, as returned by `test_epoch`, so that the fail-safe expiry can be simulated
+    static EPOCH: core::cell::Cell<core::time::Duration> =
+        const { core::cell::Cell::new(core::time::Duration::ZERO) };
+}
+
+fn test_epoch() -> core::time::Duration {
+    EPOCH.with(|epoch| epoch.get())
+}
+
+fn advance_epoch(secs: u64) {
+    EPOCH.with(|epoch| epoch.set(epoch.get() + core::time::Duration::from_secs(secs)));
+}
+
+fn commissioning_matter() -> crate::Matter<'static> {
+    let matter = crate::Matter::new(
+        &crate::test_device::TEST_DEV_DET,
+        crate::test_device::TEST_DEV_COMM,
+        &crate::test_device::TEST_DEV_ATT,
+        crate::mdns::MdnsService::Disabled,
+        test_epoch,
+        crate::utils::rand::dummy_rand,
+        crate::MATTER_PORT,
+    );
+
+    matter.initialize_transport_buffers().unwrap();
+
+    matter
+}
+
+/// Arm the fail-safe as if `ArmFailSafe` was received over a PASE session
+fn arm_failsafe(matter: &crate::Matter<'_>, timeout_secs: u16) {
+    matter
+        .failsafe
+        .borrow_mut()
+        .arm(
+            timeout_secs,
+            &crate::transport::session::SessionMode::Pase { fab_idx: 0 },
+        )
+        .unwrap();
+    matter.notify_failsafe_changed();
+}
+
+/// Run `BtpCommissioning` with the provided commissioning policy, and over no operational network,
+/// concurrently with the provided test closure
+fn run_commissioning<T, F>(concurrent: bool, test: T)
+where
+    T: FnOnce(
+        &'static crate::Matter<'static>,
+        Peripheral,
+        &'static crate::data_model::networks::wireless::NetCtlStateMutex<NoopRawMutex>,
+    ) -> F,
+    F: Future<Output = ()>,
+{
+    use crate::data_model::networks::wireless::NetCtlState;
+    use crate::transport::network::NoNetwork;
+
+    let (mock, peripheral) = GattPeriheralMock::new();
+
+    let matter = Box::leak(Box::new(commissioning_matter()));
+    let net_ctl_state = Box::leak(Box::new(NetCtlState::new_with_mutex::<NoopRawMutex>()));
+
+    let btp = Btp::new_internal(
+        mock,
+        Arc::new(BtpContext::<StdRawMutex>::new()),
+        BTP_ACK_TIMEOUT_SECS,
+        BTP_CONN_HANDSHAKE_TIMEOUT_SECS,
+        BTP_CONN_IDLE_TIMEOUT_SECS,
+    );
+
+    let commissioning = BtpCommissioning::new(matter, &btp, &concurrent);
+
+    let result = block_on(select(
+        commissioning.run(NoNetwork, NoNetwork, net_ctl_state, "test"),
+        test(matter, peripheral, net_ctl_state),
+    ));
+
+    if let embassy_futures::select::Either::First(result) = result {
+        panic!("Commissioning stopped unexpectedly: {:?}", result);
+    }
+}
+
+#[test]
+fn test_commissioning_non_concurrent() {
+    use crate::data_model::networks::wireless::NetCtlState;
+    use crate::data_model::sdm::net_comm::NetCtlError;
+
+    run_commissioning(false, |matter, peripheral, net_ctl_state| async move {
+        // Commissioning starts over BTP
+        peripheral.expect_advertising(true).await;
+        assert!(matter.is_commissioning_open());
+
+        arm_failsafe(matter, 60);
+
+        // Once the network credentials are provided and the commissioner disconnects,
+        // BTP is shut down and the commissioning continues over the operational network
+        nego_min_mtu(&peripheral).await;
+        unwrap!(NetCtlState::update_with_mutex(
+            net_ctl_state,
+            Some(b"ssid"),
+            Ok::<_, NetCtlError>(())
+        ));
+        peripheral.unsubscribe(PEER_ADDR).await;
+
+        Timer::after(Duration::from_millis(100)).await;
+
+        // BTP no longer processes the events of the peripheral
+        peripheral.connect(PEER_ADDR).await;
+
+        Timer::after(Duration::from_millis(100)).await;
+
+        assert_eq!(peripheral.peer_sender.len(), 1);
+
+        // When the fail-safe expires, the device falls back to commissioning over BTP
+        advance_epoch(61);
+        matter.notify_failsafe_changed();
+
+        peripheral.expect_advertising(true).await;
+        assert!(matter.is_commissioning_open());
+        assert!(peripheral.peer_sender.is_empty());
+        assert!(!net_ctl_state.lock(|state| state.borrow().is_prov_ready()));
+        assert!(matter.failsafe.borrow_mut().take_expiry().is_none());
+    });
+}
+
+#[test]
+fn test_commissioning_concurrent() {
+    run_commissioning(true, |matter, peripheral, _| async move {
+        // Commissioning starts over BTP and the operational network
+        peripheral.expect_advertising(true).await;
+
+        arm_failsafe(matter, 60);
+
+        // Advertising stops while the commissioning window is closed...
+        unwrap!(matter.disable_commissioning());
+        peripheral.expect_advertising(false).await;
+
+        // ... and the window is re-opened once the fail-safe expires
+        advance_epoch(61);
+        matter.notify_failsafe_changed();
+
+        peripheral.expect_advertising(true).await;
+        assert!(matter.is_commissioning_open());
+        assert!(matter.failsafe.borrow_mut().take_expiry().is_none());
+
+        // BTP is shut down once the commissioning is complete,
+        // i.e. the fail-safe is disarmed with a fabric being added
+        unwrap!(matter.fabric_mgr.borrow_mut().add_with_post_init(
+            unwrap!(crate::crypto::KeyPair::new(crate::utils::rand::dummy_rand)),
+            |_| Ok(())
+        ));
+        matter.notify_failsafe_changed();
+
+        peripheral.expect_advertising(false).await;
+    });
+}
+
+#[test]
+fn test_commissioning_rollback() {
+    let matter = commissioning_matter();
+
+    let (mock, _peripheral) = GattPeriheralMock::new();
+    let btp = Btp::new_internal(
+        mock,
+        Arc::new(BtpContext::<StdRawMutex>::new()),
+        BTP_ACK_TIMEOUT_SECS,
+        BTP_CONN_HANDSHAKE_TIMEOUT_SECS,
+        BTP_CONN_IDLE_TIMEOUT_SECS,
+    );
+
+    let commissioning = BtpCommissioning::new(&matter, &btp, &false);
+
+    let fab_idx = unwrap!(matter.fabric_mgr.borrow_mut().add_with_post_init(
+        unwrap!(crate::crypto::KeyPair::new(crate::utils::rand::dummy_rand)),
+        |_| Ok(())
+    ))
+    .fab_idx();
+
+    // Nothing to remove if no fabric was added during the fail-safe
+    unwrap!(commissioning.rollback(crate::failsafe::FailSafeExpiry {
+        added_fab_idx: None
+    }));
+    assert!(matter.is_commissioned());
+
+    // The fabric added during the fail-safe is removed
+    unwrap!(commissioning.rollback(crate::failsafe::FailSafeExpiry {
+        added_fab_idx: Some(fab_idx)
+    }));
+    assert!(!matter.is_commissioned());
+}
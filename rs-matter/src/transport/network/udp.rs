@@ -51,3 +51,415 @@ impl NetworkReceive for &Async<UdpSocket> {
         Ok((len, Address::Udp(addr)))
     }
 }
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+))]
+pub use pktinfo::*;
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+))]
+mod pktinfo {
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+    use std::io::{self, IoSlice, IoSliceMut};
+    use std::net::UdpSocket;
+    use std::os::fd::AsRawFd;
+
+    use async_io::Async;
+
+    use nix::libc;
+    use nix::sys::socket::{
+        recvmsg, sendmsg, setsockopt, sockopt, ControlMessage, ControlMessageOwned, MsgFlags,
+        SockaddrStorage,
+    };
+
+    use crate::error::{Error, ErrorCode};
+    use crate::transport::network::{Address, NetworkReceive, NetworkSend};
+    use crate::utils::cell::RefCell;
+
+    /// The local side of a received UDP packet: the network interface it arrived on
+    /// and the local (destination) IP address it was sent to.
+    #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    pub struct PacketInfo {
+        /// The index of the network interface on which the packet was received
+        pub if_index: u32,
+        /// The local IP address to which the packet was sent
+        pub local_ip: IpAddr,
+    }
+
+    /// A UDP socket which is aware of the network interfaces of a multi-homed host.
+    ///
+    /// The socket records the arrival interface and the destination address of each received packet
+    /// (via `IPV6_PKTINFO` / `IP_PKTINFO`), and sends the replies to a peer from the same interface and
+    /// with the same local address as the last packet received from that peer.
+    ///
+    /// This way, replies do not leave on the wrong interface (or with the wrong link-local source address),
+    /// as might happen with a plain wildcard-bound socket on e.g. hosts with Ethernet, Wi-Fi, Thread and VPN interfaces.
+    ///
+    /// The routes of the last `N` peers are remembered.
+    pub struct PktInfoUdpSocket<const N: usize = 16> {
+        socket: Async<UdpSocket>,
+        routes: RefCell<heapless::Vec<(SocketAddr, PacketInfo), N>>,
+    }
+
+    impl<const N: usize> PktInfoUdpSocket<N> {
+        /// Bind a new socket to the provided address (typically `MATTER_SOCKET_BIND_ADDR`).
+        pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+            Self::new(Async::<UdpSocket>::bind(addr)?)
+        }
+
+        /// Wrap an existing socket, enabling the reception of the packet info on it.
+        pub fn new(socket: Async<UdpSocket>) -> io::Result<Self> {
+            if socket.get_ref().local_addr()?.is_ipv6() {
+                setsockopt(socket.get_ref(), sockopt::Ipv6RecvPacketInfo, &true)?;
+            }
+
+            // For IPv6 sockets, this is only necessary for dual-stack operation, hence errors are ignored
+            let ipv4 = setsockopt(socket.get_ref(), sockopt::Ipv4PacketInfo, &true);
+            if socket.get_ref().local_addr()?.is_ipv4() {
+                ipv4?;
+            }
+
+            Ok(Self {
+                socket,
+                routes: RefCell::new(heapless::Vec::new()),
+            })
+        }
+
+        /// Return a reference to the wrapped socket
+        pub fn socket(&self) -> &Async<UdpSocket> {
+            &self.socket
+        }
+
+        /// Return the packet info of the last packet received from the provided peer, if known
+        pub fn packet_info(&self, peer: &SocketAddr) -> Option<PacketInfo> {
+            self.routes
+                .borrow()
+                .iter()
+                .find(|(addr, _)| addr == peer)
+                .map(|(_, info)| *info)
+        }
+
+        /// Receive a packet, returning its length, the address of the peer and - if available -
+        /// the interface and the local address on which the packet was received.
+        ///
+        /// The packet info is also recorded, so that subsequent replies to the peer are routed accordingly.
+        pub async fn recv_from_with_info(
+            &self,
+            buf: &mut [u8],
+        ) -> io::Result<(usize, SocketAddr, Option<PacketInfo>)> {
+            let (len, peer, info) = self
+                .socket
+                .read_with(|socket| Self::recvmsg(socket, buf))
+                .await?;
+
+            if let Some(info) = info {
+                self.record(peer, info);
+            }
+
+            Ok((len, peer, info))
+        }
+
+        /// Send a packet to the provided peer, from the provided interface and local address.
+        ///
+        /// If `info` is `None`, the packet info recorded for the peer (if any) is used.
+        pub async fn send_to_with_info(
+            &self,
+            data: &[u8],
+            peer: SocketAddr,
+            info: Option<PacketInfo>,
+        ) -> io::Result<()> {
+            let info = info.or_else(|| self.packet_info(&peer));
+
+            self.socket
+                .write_with(|socket| Self::sendmsg(socket, data, peer, info.as_ref()))
+                .await
+        }
+
+        fn record(&self, peer: SocketAddr, info: PacketInfo) {
+            let mut routes = self.routes.borrow_mut();
+
+            if let Some(index) = routes.iter().position(|(addr, _)| *addr == peer) {
+                routes.remove(index);
+            } else if routes.is_full() {
+                routes.remove(0);
+            }
+
+            unwrap!(routes.push((peer, info)).map_err(|_| ()));
+        }
+
+        fn recvmsg(
+            socket: &UdpSocket,
+            buf: &mut [u8],
+        ) -> io::Result<(usize, SocketAddr, Option<PacketInfo>)> {
+            let mut iov = [IoSliceMut::new(buf)];
+            let mut cmsg = nix::cmsg_space!(libc::in6_pktinfo, libc::in_pktinfo);
+
+            let msg = recvmsg::<SockaddrStorage>(
+                socket.as_raw_fd(),
+                &mut iov,
+                Some(&mut cmsg),
+                MsgFlags::empty(),
+            )?;
+
+            let peer = msg
+                .address
+                .as_ref()
+                .and_then(|addr| {
+                    addr.as_sockaddr_in6()
+                        .map(|addr| SocketAddr::V6(SocketAddrV6::from(*addr)))
+                        .or_else(|| {
+                            addr.as_sockaddr_in()
+                                .map(|addr| SocketAddr::V4(SocketAddrV4::from(*addr)))
+                        })
+                })
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+
+            let info = msg.cmsgs().find_map(|cmsg| match cmsg {
+                ControlMessageOwned::Ipv6PacketInfo(info) => Some(PacketInfo {
+                    if_index: info.ipi6_ifindex as _,
+                    local_ip: IpAddr::V6(Ipv6Addr::from(info.ipi6_addr.s6_addr)),
+                }),
+                ControlMessageOwned::Ipv4PacketInfo(info) => Some(PacketInfo {
+                    if_index: info.ipi_ifindex as _,
+                    local_ip: IpAddr::V4(Ipv4Addr::from(u32::from_be(info.ipi_addr.s_addr))),
+                }),
+                _ => None,
+            });
+
+            Ok((msg.bytes, peer, info))
+        }
+
+        fn sendmsg(
+            socket: &UdpSocket,
+            data: &[u8],
+            peer: SocketAddr,
+            info: Option<&PacketInfo>,
+        ) -> io::Result<()> {
+            let iov = [IoSlice::new(data)];
+            let addr = SockaddrStorage::from(peer);
+
+            // Replies to multicast packets are sent from a unicast address chosen by the OS
+            let local_ip = info.map(|info| {
+                if info.local_ip.is_multicast() {
+                    match info.local_ip {
+                        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                    }
+                } else {
+                    info.local_ip
+                }
+            });
+
+            let info6 = info
+                .zip(local_ip)
+                .and_then(|(info, local_ip)| match local_ip {
+                    IpAddr::V6(ip) if peer.is_ipv6() => Some(libc::in6_pktinfo {
+                        ipi6_addr: libc::in6_addr {
+                            s6_addr: ip.octets(),
+                        },
+                        ipi6_ifindex: info.if_index as _,
+                    }),
+                    _ => None,
+                });
+
+            let info4 = info
+                .zip(local_ip)
+                .and_then(|(info, local_ip)| match local_ip {
+                    IpAddr::V4(ip) if peer.is_ipv4() => Some(libc::in_pktinfo {
+                        ipi_ifindex: info.if_index as _,
+                        ipi_spec_dst: libc::in_addr {
+                            s_addr: u32::from(ip).to_be(),
+                        },
+                        ipi_addr: libc::in_addr { s_addr: 0 },
+                    }),
+                    _ => None,
+                });
+
+            let mut cmsgs = heapless::Vec::<_, 1>::new();
+            if let Some(info6) = info6.as_ref() {
+                unwrap!(cmsgs
+                    .push(ControlMessage::Ipv6PacketInfo(info6))
+                    .map_err(|_| ()));
+            } else if let Some(info4) = info4.as_ref() {
+                unwrap!(cmsgs
+                    .push(ControlMessage::Ipv4PacketInfo(info4))
+                    .map_err(|_| ()));
+            }
+
+            sendmsg(
+                socket.as_raw_fd(),
+                &iov,
+                &cmsgs,
+                MsgFlags::empty(),
+                Some(&addr),
+            )?;
+
+            Ok(())
+        }
+    }
+
+    impl<const N: usize> NetworkSend for &PktInfoUdpSocket<N> {
+        async fn send_to(&mut self, data: &[u8], addr: Address) -> Result<(), Error> {
+            PktInfoUdpSocket::send_to_with_info(
+                self,
+                data,
+                addr.udp().ok_or(ErrorCode::NoNetworkInterface)?,
+                None,
+            )
+            .await?;
+
+            Ok(())
+        }
+    }
+
+    impl<const N: usize> NetworkReceive for &PktInfoUdpSocket<N> {
+        async fn wait_available(&mut self) -> Result<(), Error> {
+            self.socket.readable().await?;
+
+            Ok(())
+        }
+
+        async fn recv_from(&mut self, buffer: &mut [u8]) -> Result<(usize, Address), Error> {
+            let (len, addr, _) = PktInfoUdpSocket::recv_from_with_info(self, buffer).await?;
+
+            Ok((len, Address::Udp(addr)))
+        }
+    }
+
+    /// Bind a UDP socket to the provided address, and restrict it to the network interface with the provided name.
+    ///
+    /// Useful for binding e.g. the mDNS socket (or the Matter socket) only to the interface(s) on which
+    /// the device should be operational, so that i.e. VPN interfaces are not used.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_to_interface(addr: SocketAddr, if_name: &str) -> io::Result<Async<UdpSocket>> {
+        let socket = UdpSocket::bind(addr)?;
+
+        setsockopt(
+            &socket,
+            sockopt::BindToDevice,
+            &std::ffi::OsString::from(if_name),
+        )?;
+
+        Async::new(socket)
+    }
+
+    /// Bind an mDNS socket to the network interface with the provided name, and join the mDNS
+    /// multicast groups on that interface.
+    ///
+    /// - `ipv4_addr` is the IPv4 address of the interface, if IPv4 mDNS should be enabled
+    /// - `ipv6_if_index` is the index of the interface, if IPv6 mDNS should be enabled
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        not(feature = "zeroconf")
+    ))]
+    pub fn bind_mdns_to_interface(
+        if_name: &str,
+        ipv4_addr: Option<Ipv4Addr>,
+        ipv6_if_index: Option<u32>,
+    ) -> io::Result<Async<UdpSocket>> {
+        use crate::mdns::{
+            MDNS_IPV4_BROADCAST_ADDR, MDNS_IPV6_BROADCAST_ADDR, MDNS_SOCKET_BIND_ADDR,
+        };
+
+        let socket = bind_to_interface(MDNS_SOCKET_BIND_ADDR, if_name)?;
+
+        if let Some(ipv6_if_index) = ipv6_if_index {
+            socket
+                .get_ref()
+                .join_multicast_v6(&MDNS_IPV6_BROADCAST_ADDR, ipv6_if_index)?;
+        }
+
+        if let Some(ipv4_addr) = ipv4_addr {
+            socket
+                .get_ref()
+                .join_multicast_v4(&MDNS_IPV4_BROADCAST_ADDR, &ipv4_addr)?;
+        }
+
+        Ok(socket)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+
+        use crate::transport::network::Address;
+
+        use super::{PacketInfo, PktInfoUdpSocket};
+
+        /// Exchange a ping and a pong between two sockets bound to the provided address,
+        /// returning the address of the client and the packet info of the ping, as seen by the server.
+        fn ping_pong(addr: SocketAddr) -> (SocketAddr, PacketInfo) {
+            futures_lite::future::block_on(async {
+                let server = PktInfoUdpSocket::<4>::bind(addr).unwrap();
+                let client = PktInfoUdpSocket::<4>::bind(addr).unwrap();
+
+                let server_addr = server.socket().get_ref().local_addr().unwrap();
+                let client_addr = client.socket().get_ref().local_addr().unwrap();
+
+                client
+                    .send_to_with_info(b"ping", server_addr, None)
+                    .await
+                    .unwrap();
+
+                let mut buf = [0; 16];
+                let (len, peer, info) = server.recv_from_with_info(&mut buf).await.unwrap();
+                assert_eq!(&buf[..len], b"ping");
+                assert_eq!(peer, client_addr);
+
+                let info = info.unwrap();
+                assert_ne!(info.if_index, 0);
+                assert_eq!(server.packet_info(&peer), Some(info));
+
+                // The reply is routed using the recorded packet info
+                server.send_to_with_info(b"pong", peer, None).await.unwrap();
+
+                let (len, peer, _) = client.recv_from_with_info(&mut buf).await.unwrap();
+                assert_eq!(&buf[..len], b"pong");
+                assert_eq!(peer, server_addr);
+
+                (client_addr, info)
+            })
+        }
+
+        #[test]
+        fn test_pktinfo_loopback() {
+            let (_, info) = ping_pong(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0));
+            assert_eq!(info.local_ip, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        }
+
+        #[test]
+        fn test_pktinfo_loopback_ipv6() {
+            let (peer, info) = ping_pong(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 0));
+            assert_eq!(info.local_ip, IpAddr::V6(Ipv6Addr::LOCALHOST));
+            assert_eq!(Address::Udp(peer).scope_id(), None);
+        }
+
+        #[test]
+        fn test_pktinfo_link_local() {
+            // Skipped on hosts without an interface with an IPv6 link-local address
+            let Some(addr) = nix::ifaddrs::getifaddrs().unwrap().find_map(|ifaddr| {
+                let addr = ifaddr.address?;
+                let addr = addr.as_sockaddr_in6()?;
+
+                ((addr.ip().segments()[0] & 0xffc0) == 0xfe80)
+                    .then(|| SocketAddrV6::new(addr.ip(), 0, 0, addr.scope_id()))
+            }) else {
+                return;
+            };
+
+            let (peer, info) = ping_pong(SocketAddr::V6(addr));
+            assert_eq!(info.local_ip, IpAddr::V6(*addr.ip()));
+            assert_eq!(info.if_index, addr.scope_id());
+            assert_eq!(Address::Udp(peer).scope_id(), Some(addr.scope_id()));
+        }
+    }
+}
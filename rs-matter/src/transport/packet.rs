@@ -18,7 +18,7 @@
 use core::fmt;
 
 use crate::crypto::AEAD_MIC_LEN_BYTES;
-use crate::error::Error;
+use crate::error::{Error, ErrorCode};
 use crate::fmt::Bytes;
 use crate::utils::storage::{ParseBuf, WriteBuf};
 
@@ -70,11 +70,17 @@ impl PacketHdr {
             .decrypt_and_decode(&self.plain, pb, peer_nodeid, dec_key)
     }
 
+    /// Encode the packet header into the provided `WriteBuf` which already contains the payload,
+    /// encrypting the packet if an encryption key is provided.
+    ///
+    /// If the plain header has message privacy enabled, the privacy key derived from
+    /// the encryption key must be provided as well.
     pub fn encode(
         &self,
         wb: &mut WriteBuf,
         local_nodeid: u64,
         enc_key: Option<&[u8]>,
+        privacy_key: Option<&[u8]>,
     ) -> Result<(), Error> {
         // Generate encrypted header
         let mut tmp_buf = [0_u8; proto_hdr::max_proto_hdr_len()];
@@ -94,6 +100,20 @@ impl PacketHdr {
         }
 
         wb.prepend(plain_hdr_bytes)?;
+
+        if self.plain.is_privacy() {
+            let (Some(_), Some(privacy_key)) = (enc_key, privacy_key) else {
+                // Privacy is only possible for encrypted messages
+                Err(ErrorCode::InvalidState)?
+            };
+
+            PlainHdr::privacy_crypt_in_place(
+                wb.as_mut_slice(),
+                plain_hdr_bytes.len(),
+                privacy_key,
+            )?;
+        }
+
         trace!("Full encrypted packet: {}", Bytes(wb.as_slice()));
 
         Ok(())
@@ -112,3 +132,91 @@ impl defmt::Format for PacketHdr {
         defmt::write!(f, "[{}][{}]", self.plain, self.proto)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::secure_channel::crypto::privacy_key;
+    use crate::transport::plain_hdr::PlainHdr;
+    use crate::utils::storage::{ParseBuf, WriteBuf};
+
+    use super::PacketHdr;
+
+    const KEY: [u8; 16] = [
+        0x5e, 0xde, 0xd2, 0x44, 0xe5, 0x53, 0x2b, 0x3c, 0xdc, 0x23, 0x40, 0x9d, 0xba, 0xd0, 0x52,
+        0xd2,
+    ];
+
+    const NODE_ID: u64 = 0x1122334455667788;
+
+    const PAYLOAD: [u8; 2] = [0x15, 0x18];
+
+    // Session ID 0x2a, message counter 0x3039 (obfuscated), followed by the encrypted payload and the MIC
+    const MSG: [u8; 32] = [
+        0x00, 0x2a, 0x00, 0x80, 0x88, 0x9f, 0x51, 0x34, 0x9b, 0xff, 0xcc, 0x63, 0xde, 0x42, 0xd1,
+        0x5f, 0xa6, 0x30, 0xef, 0xef, 0xf5, 0x7b, 0x8a, 0x73, 0x57, 0x54, 0x43, 0x4e, 0xcc, 0x10,
+        0x76, 0x73,
+    ];
+
+    #[test]
+    fn test_privacy_encode() {
+        let mut privacy = [0; 16];
+        unwrap!(privacy_key(&KEY, &mut privacy));
+
+        let mut header = PacketHdr::new();
+        header.plain.sess_id = 0x2a;
+        header.plain.ctr = 0x3039;
+        header.plain.set_privacy(true);
+        header.proto.exch_id = 0x1234;
+        header.proto.proto_id = 0x0001;
+        header.proto.proto_opcode = 0x02;
+        header.proto.set_initiator();
+        header.proto.set_reliable();
+
+        let mut buf = [0; 64];
+        let mut wb = WriteBuf::new(&mut buf);
+        unwrap!(wb.reserve(PacketHdr::HDR_RESERVE));
+        unwrap!(wb.append(&PAYLOAD));
+
+        unwrap!(header.encode(&mut wb, NODE_ID, Some(&KEY), Some(&privacy)));
+        assert_eq!(wb.as_slice(), MSG);
+
+        // Privacy is not possible without a privacy key
+        let mut wb = WriteBuf::new(&mut buf);
+        unwrap!(wb.reserve(PacketHdr::HDR_RESERVE));
+        unwrap!(wb.append(&PAYLOAD));
+        assert!(header.encode(&mut wb, NODE_ID, Some(&KEY), None).is_err());
+    }
+
+    #[test]
+    fn test_privacy_decode() {
+        let mut privacy = [0; 16];
+        unwrap!(privacy_key(&KEY, &mut privacy));
+
+        let mut msg = MSG;
+
+        let mut header = PacketHdr::new();
+
+        let mut pb = ParseBuf::new(&mut msg);
+        unwrap!(header.decode_plain_hdr(&mut pb));
+        assert!(header.plain.is_privacy());
+        assert_eq!(header.plain.sess_id, 0x2a);
+        assert_ne!(header.plain.ctr, 0x3039);
+
+        let hdr_len = pb.parsed_as_slice().len();
+        unwrap!(PlainHdr::privacy_crypt_in_place(
+            &mut msg, hdr_len, &privacy
+        ));
+
+        let mut pb = ParseBuf::new(&mut msg);
+        unwrap!(header.decode_plain_hdr(&mut pb));
+        assert_eq!(header.plain.ctr, 0x3039);
+
+        unwrap!(header.decode_remaining(&mut pb, NODE_ID, Some(&KEY)));
+        assert_eq!(header.proto.exch_id, 0x1234);
+        assert_eq!(header.proto.proto_id, 0x0001);
+        assert_eq!(header.proto.proto_opcode, 0x02);
+        assert!(header.proto.is_initiator());
+        assert!(header.proto.is_reliable());
+        assert_eq!(pb.as_slice(), PAYLOAD);
+    }
+}
@@ -17,7 +17,9 @@
 
 use core::fmt;
 
+use crate::crypto::AEAD_MIC_LEN_BYTES;
 use crate::error::*;
+use crate::secure_channel::crypto::privacy_crypt_in_place;
 use crate::utils::storage::{ParseBuf, WriteBuf};
 
 bitflags::bitflags! {
@@ -87,11 +89,27 @@ impl defmt::Format for MsgFlags {
     }
 }
 
+bitflags::bitflags! {
+    #[repr(transparent)]
+    #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+    pub struct SecFlags: u8 {
+        const PRIVACY = 0x80;
+        const CONTROL = 0x40;
+        const MSG_EXTENSIONS = 0x20;
+        const GROUP_SESSION = 0x01;
+    }
+}
+
+/// The offset of the privacy-protected fields (message counter, source and destination node IDs)
+/// in an encoded message header
+const PRIVACY_HDR_OFFSET: usize = 4;
+
 // This is the unencrypted message
 #[derive(Debug, Default, Clone)]
 pub struct PlainHdr {
     flags: MsgFlags,
     pub sess_id: u16,
+    sec_flags: SecFlags,
     pub ctr: u32,
     src_nodeid: u64,
     dst_nodeid: u64,
@@ -103,6 +121,7 @@ impl PlainHdr {
         Self {
             flags: MsgFlags::empty(),
             sess_id: 0,
+            sec_flags: SecFlags::empty(),
             ctr: 0,
             src_nodeid: 0,
             dst_nodeid: 0,
@@ -167,11 +186,39 @@ impl PlainHdr {
         }
     }
 
+    /// Return `true` if the privacy-protected fields of the header are obfuscated
+    /// (message privacy is in use).
+    pub fn is_privacy(&self) -> bool {
+        self.sec_flags.contains(SecFlags::PRIVACY)
+    }
+
+    pub fn set_privacy(&mut self, privacy: bool) {
+        self.sec_flags.set(SecFlags::PRIVACY, privacy);
+    }
+
+    /// Return `true` if the message is sent over a group session
+    pub fn is_group_session(&self) -> bool {
+        self.sec_flags.contains(SecFlags::GROUP_SESSION)
+    }
+
+    pub fn set_group_session(&mut self, group: bool) {
+        self.sec_flags.set(SecFlags::GROUP_SESSION, group);
+    }
+
+    /// Return `true` if the message is a control message, as opposed to a data message
+    pub fn is_control(&self) -> bool {
+        self.sec_flags.contains(SecFlags::CONTROL)
+    }
+
+    pub fn set_control(&mut self, control: bool) {
+        self.sec_flags.set(SecFlags::CONTROL, control);
+    }
+
     // it will have an additional 'message length' field first
     pub fn decode(&mut self, msg: &mut ParseBuf) -> Result<(), Error> {
         self.flags = MsgFlags::from_bits(msg.le_u8()?).ok_or(ErrorCode::Invalid)?;
         self.sess_id = msg.le_u16()?;
-        let _sec_flags = msg.le_u8()?;
+        self.sec_flags = SecFlags::from_bits_retain(msg.le_u8()?);
         self.ctr = msg.le_u32()?;
 
         if self.flags.contains(MsgFlags::SRC_ADDR_PRESENT) {
@@ -197,7 +244,7 @@ impl PlainHdr {
         trace!("[encode] {}", self);
         resp_buf.le_u8(self.flags.bits())?;
         resp_buf.le_u16(self.sess_id)?;
-        resp_buf.le_u8(0)?;
+        resp_buf.le_u8(self.sec_flags.bits())?;
         resp_buf.le_u32(self.ctr)?;
 
         if self.flags.contains(MsgFlags::SRC_ADDR_PRESENT) {
@@ -221,6 +268,33 @@ impl PlainHdr {
     pub fn is_encrypted(&self) -> bool {
         self.sess_id != 0
     }
+
+    /// Obfuscate - or de-obfuscate - in place the privacy-protected fields of an encoded message.
+    ///
+    /// The operation is symmetric, so the same call obfuscates the header of an outgoing message
+    /// after it had been encrypted, and de-obfuscates the header of an incoming message
+    /// before it can be decoded and decrypted.
+    ///
+    /// # Arguments
+    /// - `msg`: The encoded message, i.e. the message header, followed by the encrypted payload and its MIC
+    /// - `hdr_len`: The length of the message header
+    /// - `privacy_key`: The privacy key derived from the encryption key of the message
+    pub fn privacy_crypt_in_place(
+        msg: &mut [u8],
+        hdr_len: usize,
+        privacy_key: &[u8],
+    ) -> Result<(), Error> {
+        if hdr_len < PRIVACY_HDR_OFFSET || msg.len() < hdr_len + AEAD_MIC_LEN_BYTES {
+            Err(ErrorCode::Invalid)?;
+        }
+
+        let sess_id = u16::from_le_bytes([msg[1], msg[2]]);
+
+        let (hdr, rest) = msg.split_at_mut(hdr_len);
+        let mic = &rest[rest.len() - AEAD_MIC_LEN_BYTES..];
+
+        privacy_crypt_in_place(privacy_key, sess_id, mic, &mut hdr[PRIVACY_HDR_OFFSET..])
+    }
 }
 
 impl fmt::Display for PlainHdr {
@@ -229,6 +303,10 @@ impl fmt::Display for PlainHdr {
             write!(f, "{},", self.flags)?;
         }
 
+        if self.is_privacy() {
+            write!(f, "P,")?;
+        }
+
         write!(f, "SID:{:x},CTR:{:x}", self.sess_id, self.ctr)?;
 
         if let Some(src_nodeid) = self.get_src_nodeid() {
@@ -254,6 +332,10 @@ impl defmt::Format for PlainHdr {
             defmt::write!(f, "{},", self.flags);
         }
 
+        if self.is_privacy() {
+            defmt::write!(f, "P,");
+        }
+
         defmt::write!(f, "SID:{:x},CTR:{:x}", self.sess_id, self.ctr);
 
         if let Some(src_nodeid) = self.get_src_nodeid() {
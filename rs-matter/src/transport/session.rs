@@ -15,13 +15,15 @@
  *    limitations under the License.
  */
 
+use core::cmp::{Ordering, Reverse};
 use core::fmt;
 use core::num::NonZeroU8;
 use core::time::Duration;
 
 use crate::error::*;
+use crate::secure_channel::crypto::privacy_key;
 use crate::transport::exchange::ExchangeId;
-use crate::transport::mrp::ReliableMessage;
+use crate::transport::mrp::{MrpParameters, ReliableMessage};
 use crate::utils::cell::RefCell;
 use crate::utils::epoch::Epoch;
 use crate::utils::init::{init, zeroed, Init, IntoFallibleInit};
@@ -42,6 +44,49 @@ pub type NocCatIds = [u32; MAX_CAT_IDS_PER_NOC];
 
 const MATTER_AES128_KEY_SIZE: usize = 16;
 
+/// The reason why a session was removed (or is about to be removed)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SessionRemovalReason {
+    /// The session was evicted to make room for a new one
+    Evicted,
+    /// The peer sent a `CloseSession` status report
+    ClosedByPeer,
+    /// The session was closed locally, via the session lifecycle API of `Matter`
+    Closed,
+    /// The fabric of the session was removed
+    FabricRemoved,
+    /// The session was closed because of an unrecoverable error on one of its exchanges
+    /// (i.e. no space for new exchanges, or an exchange which could not be closed cleanly)
+    Failed,
+}
+
+/// Information about a removed session
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SessionRemoval {
+    /// The internal ID of the removed session
+    pub session_id: u32,
+    /// The local fabric index of the removed session (0 if the session had no fabric)
+    pub fabric_idx: u8,
+    /// The peer node ID of the removed session, if known
+    pub peer_node_id: Option<u64>,
+    /// Why the session was removed
+    pub reason: SessionRemovalReason,
+}
+
+impl SessionRemoval {
+    /// Create a new removal record for the provided session
+    pub fn new(session: &Session, reason: SessionRemovalReason) -> Self {
+        Self {
+            session_id: session.id(),
+            fabric_idx: session.get_local_fabric_idx(),
+            peer_node_id: session.get_peer_node_id(),
+            reason,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SessionMode {
@@ -81,6 +126,9 @@ pub struct Session {
     // So, we might keep this as enc_key and dec_key for now
     dec_key: [u8; MATTER_AES128_KEY_SIZE],
     enc_key: [u8; MATTER_AES128_KEY_SIZE],
+    // The privacy keys, derived from the decryption and encryption keys respectively
+    dec_privacy_key: [u8; MATTER_AES128_KEY_SIZE],
+    enc_privacy_key: [u8; MATTER_AES128_KEY_SIZE],
     att_challenge: [u8; MATTER_AES128_KEY_SIZE],
     local_sess_id: u16,
     peer_sess_id: u16,
@@ -88,13 +136,21 @@ pub struct Session {
     rx_ctr_state: RxCtrState,
     mode: SessionMode,
     pub(crate) exchanges: crate::utils::storage::Vec<Option<ExchangeState>, MAX_EXCHANGES>,
+    /// The maximum number of exchanges which can be active at the same time, as configured in the session manager
+    max_exchanges: usize,
     last_use: Duration,
-    /// If `true` then the session is considered "expired". Session expiration happens
-    /// for the session on behalf of which a fabric is removed.
+    /// The MRP parameters of the peer, as negotiated during session establishment
+    /// or as learned from the peer's DNS-SD TXT records
+    peer_mrp_params: MrpParameters,
+    /// When did we last receive a message from the peer
+    peer_last_heard: Option<Duration>,
+    /// If `Some` then the session is considered "expired", for the contained reason.
+    /// Session expiration happens when the session is closed locally, or when its fabric is removed.
     ///
     /// Expired sessions can still process their ongoing exchanges, but do not accept any new ones.
+    /// Once all of their exchanges are complete, expired sessions are closed by the transport.
     /// Furthermore, expired sessions are the prime candidates for eviction.
-    expired: bool,
+    expired: Option<SessionRemovalReason>,
     reserved: bool,
 }
 
@@ -115,6 +171,8 @@ impl Session {
             peer_nodeid,
             dec_key: [0; MATTER_AES128_KEY_SIZE],
             enc_key: [0; MATTER_AES128_KEY_SIZE],
+            dec_privacy_key: [0; MATTER_AES128_KEY_SIZE],
+            enc_privacy_key: [0; MATTER_AES128_KEY_SIZE],
             att_challenge: [0; MATTER_AES128_KEY_SIZE],
             peer_sess_id: 0,
             local_sess_id: 0,
@@ -122,8 +180,11 @@ impl Session {
             rx_ctr_state: RxCtrState::new(0),
             mode: SessionMode::PlainText,
             exchanges: crate::utils::storage::Vec::new(),
+            max_exchanges: MAX_EXCHANGES,
             last_use: epoch(),
-            expired: false,
+            peer_mrp_params: MrpParameters::new(),
+            peer_last_heard: None,
+            expired: None,
         }
     }
 
@@ -143,6 +204,8 @@ impl Session {
             peer_nodeid,
             dec_key <- zeroed(),
             enc_key <- zeroed(),
+            dec_privacy_key <- zeroed(),
+            enc_privacy_key <- zeroed(),
             att_challenge <- zeroed(),
             peer_sess_id: 0,
             local_sess_id: 0,
@@ -150,8 +213,11 @@ impl Session {
             rx_ctr_state: RxCtrState::new(0),
             mode: SessionMode::PlainText,
             exchanges: crate::utils::storage::Vec::new(),
+            max_exchanges: MAX_EXCHANGES,
             last_use: epoch(),
-            expired: false,
+            peer_mrp_params: MrpParameters::new(),
+            peer_last_heard: None,
+            expired: None,
         })
     }
 
@@ -217,10 +283,31 @@ impl Session {
         }
     }
 
+    fn get_dec_privacy_key(&self) -> Option<&[u8]> {
+        self.get_dec_key().map(|_| &self.dec_privacy_key[..])
+    }
+
+    fn get_enc_privacy_key(&self) -> Option<&[u8]> {
+        self.get_enc_key().map(|_| &self.enc_privacy_key[..])
+    }
+
     pub fn get_att_challenge(&self) -> &[u8] {
         &self.att_challenge
     }
 
+    /// Return the MRP parameters of the peer
+    pub fn get_peer_mrp_params(&self) -> &MrpParameters {
+        &self.peer_mrp_params
+    }
+
+    /// Set the MRP parameters of the peer
+    ///
+    /// Useful when the parameters are learned out of band, i.e. from the `SII`, `SAI` and `SAT`
+    /// DNS-SD TXT records of the peer (see `MrpParameters::from_txt_kvs`).
+    pub fn set_peer_mrp_params(&mut self, params: MrpParameters) {
+        self.peer_mrp_params = params;
+    }
+
     pub(crate) fn is_for_node(&self, fabric_idx: u8, peer_node_id: u64, secure: bool) -> bool {
         self.get_local_fabric_idx() == fabric_idx
             && self.peer_nodeid == Some(peer_node_id)
@@ -241,10 +328,40 @@ impl Session {
     }
 
     /// Return `true` if the session is expired.
-    pub(crate) fn is_expired(&self) -> bool {
+    pub fn is_expired(&self) -> bool {
+        self.expired.is_some()
+    }
+
+    /// Return the reason why the session was expired, if it is expired.
+    pub fn expiry_reason(&self) -> Option<SessionRemovalReason> {
         self.expired
     }
 
+    /// Mark the session as expired for the provided reason.
+    ///
+    /// The session will not accept new exchanges, and will be closed
+    /// once its ongoing exchanges are complete.
+    pub fn expire(&mut self, reason: SessionRemovalReason) {
+        if self.expired.is_none() {
+            self.expired = Some(reason);
+        }
+    }
+
+    /// Return `true` if the session is reserved (i.e. still being established).
+    pub fn is_reserved(&self) -> bool {
+        self.reserved
+    }
+
+    /// Return `true` if the session has any active exchanges.
+    pub fn has_exchanges(&self) -> bool {
+        self.exchanges.iter().any(Option::is_some)
+    }
+
+    /// Return when was the session last used.
+    pub fn last_used(&self) -> Duration {
+        self.last_use
+    }
+
     pub fn upgrade_fabric_idx(&mut self, fabric_idx: NonZeroU8) -> Result<(), Error> {
         if let SessionMode::Pase { fab_idx } = &mut self.mode {
             if *fab_idx == 0 {
@@ -266,6 +383,9 @@ impl Session {
     ///
     /// Return `true` if a new exchange was created, and `false` otherwise.
     pub(crate) fn post_recv(&mut self, rx_header: &PacketHdr, epoch: Epoch) -> Result<bool, Error> {
+        // Even duplicates indicate that the peer is active
+        self.peer_last_heard = Some(epoch());
+
         if !self
             .rx_ctr_state
             .post_recv(rx_header.plain.ctr, self.is_encrypted())
@@ -283,10 +403,12 @@ impl Session {
         } else {
             if !rx_header.proto.is_initiator()
                 || !MessageMeta::from(&rx_header.proto).is_new_exchange()
+                || self.is_expired()
             {
                 // Do not create a new exchange if the peer is not an initiator, or if
                 // the packet is NOT a candidate for a new exchange
-                // (i.e. it is a standalone ACK or a SC status response)
+                // (i.e. it is a standalone ACK or a SC status response),
+                // or if the session is expired
                 Err(ErrorCode::NoExchange)?;
             }
 
@@ -309,8 +431,8 @@ impl Session {
         &mut self,
         exch_index: Option<usize>,
         tx_header: &mut PacketHdr,
-        session_active_interval_ms: Option<u16>,
-        session_idle_interval_ms: Option<u16>,
+        privacy: bool,
+        epoch: Epoch,
     ) -> Result<(Address, bool), Error> {
         let ctr = if let Some(exchange_index) = exch_index {
             let exchange = unwrap!(self.exchanges[exchange_index].as_mut());
@@ -329,18 +451,18 @@ impl Session {
                 .then_some(self.peer_nodeid)
                 .flatten(),
         );
+        tx_header.plain.set_privacy(privacy && self.is_encrypted());
 
         tx_header.proto.adjust_reliability(false, &self.peer_addr);
 
         if let Some(exchange_index) = exch_index {
+            let retrans_interval_ms = self
+                .peer_mrp_params
+                .retrans_interval_ms(self.peer_last_heard, epoch());
+
             let exchange = unwrap!(self.exchanges[exchange_index].as_mut());
 
-            exchange.pre_send(
-                &tx_header.plain,
-                &mut tx_header.proto,
-                session_active_interval_ms,
-                session_idle_interval_ms,
-            )?;
+            exchange.pre_send(&tx_header.plain, &mut tx_header.proto, retrans_interval_ms)?;
         }
 
         Ok((self.peer_addr, retransmission))
@@ -367,7 +489,12 @@ impl Session {
     }
 
     pub(crate) fn encode(&self, tx: &PacketHdr, wb: &mut WriteBuf) -> Result<(), Error> {
-        tx.encode(wb, self.local_nodeid, self.get_enc_key())
+        tx.encode(
+            wb,
+            self.local_nodeid,
+            self.get_enc_key(),
+            self.get_enc_privacy_key(),
+        )
     }
 
     fn update_last_used(&mut self, epoch: Epoch) {
@@ -400,7 +527,7 @@ impl Session {
             mrp: ReliableMessage::new(),
         });
 
-        let exch_index = if self.exchanges.len() < MAX_EXCHANGES {
+        let exch_index = if self.exchanges.len() < self.max_exchanges {
             let _ = self.exchanges.push(exch_state);
 
             self.exchanges.len() - 1
@@ -460,7 +587,7 @@ impl fmt::Display for Session {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "peer: {:?}, peer_nodeid: {:?}, local: {}, remote: {}, msg_ctr: {}, mode: {:?}, ts: {:?}, expired: {}",
+            "peer: {:?}, peer_nodeid: {:?}, local: {}, remote: {}, msg_ctr: {}, mode: {:?}, ts: {:?}, expired: {:?}",
             self.peer_addr,
             self.peer_nodeid,
             self.local_sess_id,
@@ -516,6 +643,7 @@ impl<'a> ReservedSession<'a> {
         dec_key: Option<&[u8]>,
         enc_key: Option<&[u8]>,
         att_challenge: Option<&[u8]>,
+        peer_mrp_params: &MrpParameters,
     ) -> Result<(), Error> {
         let mut mgr = self.session_mgr.borrow_mut();
         let session = mgr.get(self.id).ok_or(ErrorCode::NoSession)?;
@@ -526,13 +654,16 @@ impl<'a> ReservedSession<'a> {
         session.local_sess_id = local_sessid;
         session.peer_addr = peer_addr;
         session.mode = mode;
+        session.peer_mrp_params = peer_mrp_params.clone();
 
         if let Some(dec_key) = dec_key {
             session.dec_key.copy_from_slice(dec_key);
+            privacy_key(dec_key, &mut session.dec_privacy_key)?;
         }
 
         if let Some(enc_key) = enc_key {
             session.enc_key.copy_from_slice(enc_key);
+            privacy_key(enc_key, &mut session.enc_privacy_key)?;
         }
 
         if let Some(att_challenge) = att_challenge {
@@ -559,8 +690,99 @@ impl Drop for ReservedSession<'_> {
     }
 }
 
-const MAX_SESSIONS: usize = 16;
-const MAX_EXCHANGES: usize = 5;
+/// The capacity of the session table, i.e. the maximum number of sessions which can be tracked at the same time.
+///
+/// The number of sessions actually allowed can be lowered with `SessionMgr::set_limits`.
+pub const MAX_SESSIONS: usize = 16;
+
+/// The capacity of the exchange table of each session, i.e. the maximum number of exchanges per session
+/// which can be active at the same time.
+///
+/// The number of exchanges actually allowed can be lowered with `SessionMgr::set_limits`.
+/// Cannot be larger than 16, as the exchange index is encoded in the upper 4 bits of the exchange ID.
+pub const MAX_EXCHANGES: usize = 5;
+
+const _: () = core::assert!(MAX_EXCHANGES <= 16);
+
+/// The minimum number of CASE sessions per fabric, which the spec requires to be supported
+/// and which the default eviction policy therefore tries to keep.
+pub const MIN_CASE_SESSIONS_PER_FABRIC: usize = 3;
+
+/// A policy for selecting the session to be evicted when the session table is full
+/// and a new session needs to be established.
+///
+/// Regardless of the policy, sessions which are still being established or which have
+/// active exchanges are never evicted.
+pub trait SessionEvictionPolicy {
+    /// Return `true` if the provided session can be evicted.
+    ///
+    /// Useful for protecting certain sessions (i.e. sessions with active subscriptions) from eviction.
+    fn can_evict(&self, session: &Session, sessions: &SessionMgr) -> bool {
+        let _ = (session, sessions);
+
+        true
+    }
+
+    /// Compare two eviction candidates.
+    ///
+    /// Return `Ordering::Less` if session `a` should be evicted before session `b`.
+    fn compare(&self, a: &Session, b: &Session, sessions: &SessionMgr) -> Ordering {
+        DefaultSessionEvictionPolicy.compare(a, b, sessions)
+    }
+}
+
+/// The default session eviction policy.
+///
+/// Sessions are evicted in the following order:
+/// - Expired sessions (i.e. sessions of removed fabrics)
+/// - Unauthenticated sessions
+/// - PASE sessions
+/// - CASE sessions of the fabric with the most sessions, as long as it has more than
+///   `MIN_CASE_SESSIONS_PER_FABRIC` sessions
+/// - All other CASE sessions
+///
+/// Sessions in the same group are evicted in a least-recently-used order.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DefaultSessionEvictionPolicy;
+
+impl DefaultSessionEvictionPolicy {
+    fn key(session: &Session, sessions: &SessionMgr) -> impl Ord {
+        let fabric_sessions = match session.get_session_mode() {
+            SessionMode::Case { fab_idx, .. } => sessions
+                .iter()
+                .filter(|sess| {
+                    matches!(sess.get_session_mode(), SessionMode::Case { .. })
+                        && sess.get_local_fabric_idx() == fab_idx.get()
+                })
+                .count(),
+            _ => 0,
+        };
+
+        let mode: u8 = match session.get_session_mode() {
+            SessionMode::PlainText => 0,
+            SessionMode::Pase { .. } => 1,
+            SessionMode::Case { .. } if fabric_sessions > MIN_CASE_SESSIONS_PER_FABRIC => 2,
+            SessionMode::Case { .. } => 3,
+        };
+
+        // Among the CASE sessions over the per-fabric minimum, the fabric with the most sessions goes first
+        let excess_fabric_sessions = if mode == 2 { fabric_sessions } else { 0 };
+
+        (
+            !session.is_expired(),
+            mode,
+            Reverse(excess_fabric_sessions),
+            session.last_used(),
+        )
+    }
+}
+
+impl SessionEvictionPolicy for DefaultSessionEvictionPolicy {
+    fn compare(&self, a: &Session, b: &Session, sessions: &SessionMgr) -> Ordering {
+        Self::key(a, sessions).cmp(&Self::key(b, sessions))
+    }
+}
 
 const MATTER_MSG_CTR_RANGE: u32 = 0x0fffffff;
 
@@ -569,6 +791,8 @@ pub struct SessionMgr {
     next_sess_id: u16,
     next_exch_id: u16,
     sessions: crate::utils::storage::Vec<Session, MAX_SESSIONS>,
+    max_sessions: usize,
+    max_exchanges: usize,
     pub(crate) epoch: Epoch,
     pub(crate) rand: Rand,
 }
@@ -582,6 +806,8 @@ impl SessionMgr {
             next_sess_unique_id: 0,
             next_sess_id: 1,
             next_exch_id: 1,
+            max_sessions: MAX_SESSIONS,
+            max_exchanges: MAX_EXCHANGES,
             epoch,
             rand,
         }
@@ -594,11 +820,41 @@ impl SessionMgr {
             next_sess_unique_id: 0,
             next_sess_id: 1,
             next_exch_id: 1,
+            max_sessions: MAX_SESSIONS,
+            max_exchanges: MAX_EXCHANGES,
             epoch,
             rand,
         })
     }
 
+    /// Set the maximum number of sessions which can be tracked at the same time,
+    /// and the maximum number of exchanges per session which can be active at the same time.
+    ///
+    /// The limits cannot be zero or larger than the `MAX_SESSIONS` and `MAX_EXCHANGES` capacities.
+    /// Sessions which already exist keep their exchange limit.
+    pub fn set_limits(&mut self, max_sessions: usize, max_exchanges: usize) -> Result<(), Error> {
+        if !(1..=MAX_SESSIONS).contains(&max_sessions)
+            || !(1..=MAX_EXCHANGES).contains(&max_exchanges)
+        {
+            return Err(ErrorCode::InvalidData.into());
+        }
+
+        self.max_sessions = max_sessions;
+        self.max_exchanges = max_exchanges;
+
+        Ok(())
+    }
+
+    /// Return the maximum number of sessions which can be tracked at the same time
+    pub fn max_sessions(&self) -> usize {
+        self.max_sessions
+    }
+
+    /// Return the maximum number of exchanges per session which can be active at the same time
+    pub fn max_exchanges(&self) -> usize {
+        self.max_exchanges
+    }
+
     pub fn reset(&mut self) {
         self.sessions.clear();
         self.next_sess_id = 1;
@@ -655,26 +911,23 @@ impl SessionMgr {
         next_exch_id
     }
 
-    pub fn get_session_for_eviction(&mut self) -> Option<&mut Session> {
-        let mut lru_index = None;
-        let mut lru_ts = (self.epoch)();
-        for (i, s) in self.sessions.iter().enumerate() {
-            if (s.expired || s.last_use < lru_ts)
-                && !s.reserved
-                && s.exchanges.iter().all(Option::is_none)
-            {
-                lru_ts = s.last_use;
-                lru_index = Some(i);
-
-                if s.expired {
-                    // Expired sessons are the prime candidates for eviction,
-                    // so we can break early
-                    break;
-                }
-            }
-        }
+    /// Return the session which should be evicted according to the provided eviction policy,
+    /// or `None` if no session can be evicted.
+    pub fn get_session_for_eviction(
+        &mut self,
+        policy: &dyn SessionEvictionPolicy,
+    ) -> Option<&mut Session> {
+        let index = self
+            .sessions
+            .iter()
+            .enumerate()
+            .filter(|(_, sess)| {
+                !sess.reserved && !sess.has_exchanges() && policy.can_evict(sess, self)
+            })
+            .min_by(|(_, a), (_, b)| policy.compare(a, b, self))
+            .map(|(index, _)| index);
 
-        lru_index.map(|index| &mut self.sessions[index])
+        index.map(|index| &mut self.sessions[index])
     }
 
     pub fn add(
@@ -683,6 +936,10 @@ impl SessionMgr {
         peer_addr: Address,
         peer_nodeid: Option<u64>,
     ) -> Result<&mut Session, Error> {
+        if self.sessions.len() >= self.max_sessions {
+            return Err(ErrorCode::NoSpaceSessions.into());
+        }
+
         let session_id = self.next_sess_unique_id;
 
         self.next_sess_unique_id += 1;
@@ -705,7 +962,10 @@ impl SessionMgr {
                 ErrorCode::NoSpaceSessions.into()
             })?;
 
-        Ok(unwrap!(self.sessions.last_mut()))
+        let session = unwrap!(self.sessions.last_mut());
+        session.max_exchanges = self.max_exchanges;
+
+        Ok(session)
     }
 
     /// This assumes that the higher layer has taken care of doing anything required
@@ -718,43 +978,76 @@ impl SessionMgr {
         }
     }
 
-    /// This assumes that the higher layer has taken care of doing anything required
-    /// as per the spec before the sessions are removed or expired
-    pub fn remove_for_fabric(&mut self, fabric_idx: NonZeroU8, expire_sess_id: Option<u32>) {
-        loop {
-            let Some(index) = self.sessions.iter().position(|sess| {
-                sess.get_local_fabric_idx() == fabric_idx.get() && Some(sess.id) != expire_sess_id
-            }) else {
-                break;
-            };
+    /// Expire all sessions matching the provided predicate, for the provided reason.
+    ///
+    /// Expired sessions are closed by the transport (by sending a `CloseSession` status report
+    /// to the peer) once their ongoing exchanges are complete.
+    ///
+    /// Return the number of sessions which were expired.
+    pub fn expire<F>(&mut self, f: F, reason: SessionRemovalReason) -> usize
+    where
+        F: Fn(&Session) -> bool,
+    {
+        let mut count = 0;
 
+        for sess in self
+            .sessions
+            .iter_mut()
+            .filter(|sess| !sess.is_expired() && f(sess))
+        {
             info!(
-                "Dropping session with ID {} for fabric index {} immediately",
-                self.sessions[index].id, fabric_idx
+                "Marking session with ID {} as expired, reason: {:?}",
+                sess.id, reason
             );
-            self.sessions.swap_remove(index);
+
+            sess.expire(reason);
+            count += 1;
         }
 
-        if let Some(expire_sess_id) = expire_sess_id {
-            let expire_sess = self
-                .sessions
-                .iter_mut()
-                .find(|sess| sess.id == expire_sess_id);
-            if let Some(expire_sess) = expire_sess {
-                expire_sess.expired = true;
-                info!(
-                    "Marking session with ID {} as expired for fabric index {}",
-                    expire_sess_id,
-                    fabric_idx.get()
-                );
-            } else {
-                warn!(
-                    "No session with ID {} found for fabric index {} to mark as expired",
-                    expire_sess_id,
-                    fabric_idx.get()
-                );
-            }
+        count
+    }
+
+    /// Expire all sessions of the provided fabric
+    pub fn expire_for_fabric(
+        &mut self,
+        fabric_idx: NonZeroU8,
+        reason: SessionRemovalReason,
+    ) -> usize {
+        self.expire(
+            |sess| sess.get_local_fabric_idx() == fabric_idx.get(),
+            reason,
+        )
+    }
+
+    /// Set the MRP parameters of all secure sessions with the provided peer node on the provided fabric
+    ///
+    /// Return the number of updated sessions.
+    pub fn set_peer_mrp_params(
+        &mut self,
+        fabric_idx: NonZeroU8,
+        peer_node_id: u64,
+        params: &MrpParameters,
+    ) -> usize {
+        let mut count = 0;
+
+        for sess in self
+            .sessions
+            .iter_mut()
+            .filter(|sess| sess.is_for_node(fabric_idx.get(), peer_node_id, true))
+        {
+            sess.set_peer_mrp_params(params.clone());
+            count += 1;
         }
+
+        count
+    }
+
+    /// Return the number of session establishments (PASE or CASE) currently in progress
+    pub fn pending_establishments(&self) -> usize {
+        self.sessions
+            .iter()
+            .filter(|sess| !sess.is_encrypted() && sess.has_exchanges())
+            .count()
     }
 
     pub fn get(&mut self, id: u32) -> Option<&mut Session> {
@@ -777,7 +1070,7 @@ impl SessionMgr {
             .sessions
             .iter_mut()
             // Expired sessions are not allowed to initiate new exchanges
-            .find(|sess| !sess.expired && sess.is_for_node(fabric_idx, peer_node_id, secure));
+            .find(|sess| !sess.is_expired() && sess.is_for_node(fabric_idx, peer_node_id, secure));
 
         if let Some(session) = session.as_mut() {
             session.update_last_used(self.epoch);
@@ -803,6 +1096,30 @@ impl SessionMgr {
         session
     }
 
+    /// Same as `get_for_rx`, but does not update the last used timestamp of the found session,
+    /// and therefore does not affect the eviction order of the sessions.
+    pub(crate) fn find_for_rx(&self, rx_peer: &Address, rx_plain: &PlainHdr) -> Option<&Session> {
+        self.sessions
+            .iter()
+            .find(|sess| sess.is_for_rx(rx_peer, rx_plain))
+    }
+
+    /// Return the privacy key for de-obfuscating the header of an incoming message
+    /// received with message privacy on the session with the given local session ID.
+    ///
+    /// The session can only be looked up by its ID, as the other fields of the header
+    /// used for the lookup are obfuscated.
+    pub(crate) fn get_privacy_key_for_rx(
+        &self,
+        local_sess_id: u16,
+    ) -> Option<[u8; MATTER_AES128_KEY_SIZE]> {
+        self.sessions
+            .iter()
+            .filter(|sess| !sess.reserved && sess.local_sess_id == local_sess_id)
+            .find_map(|sess| sess.get_dec_privacy_key())
+            .map(|key| unwrap!(key.try_into()))
+    }
+
     pub(crate) fn get_exch<F>(&mut self, f: F) -> Option<(&mut Session, usize)>
     where
         F: Fn(&Session, &ExchangeState) -> bool,
@@ -858,7 +1175,16 @@ mod tests {
         utils::{epoch::dummy_epoch, rand::dummy_rand},
     };
 
-    use super::SessionMgr;
+    use core::num::NonZeroU8;
+    use core::time::Duration;
+
+    use crate::error::ErrorCode;
+    use crate::transport::exchange::Role;
+
+    use super::{
+        DefaultSessionEvictionPolicy, Session, SessionEvictionPolicy, SessionMgr, SessionMode,
+        SessionRemovalReason, MAX_EXCHANGES,
+    };
 
     #[test]
     fn test_next_sess_id_doesnt_reuse() {
@@ -883,4 +1209,135 @@ mod tests {
         assert_eq!(sm.get_next_sess_id(), 65535);
         assert_eq!(sm.get_next_sess_id(), 2);
     }
+
+    fn add_session(sm: &mut SessionMgr, mode: SessionMode, last_use_secs: u64) -> u32 {
+        let sess = unwrap!(sm.add(false, Address::default(), None));
+        sess.mode = mode;
+        sess.last_use = Duration::from_secs(last_use_secs);
+
+        sess.id
+    }
+
+    fn case(fab_idx: u8) -> SessionMode {
+        SessionMode::Case {
+            fab_idx: unwrap!(NonZeroU8::new(fab_idx)),
+            cat_ids: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_expire_for_fabric() {
+        let mut sm = SessionMgr::new(dummy_epoch, dummy_rand);
+
+        let f1 = add_session(&mut sm, case(1), 1);
+        let f2 = add_session(&mut sm, case(2), 2);
+
+        assert_eq!(
+            sm.expire_for_fabric(
+                unwrap!(NonZeroU8::new(1)),
+                SessionRemovalReason::FabricRemoved
+            ),
+            1
+        );
+        // Already expired sessions are not expired again
+        assert_eq!(
+            sm.expire_for_fabric(unwrap!(NonZeroU8::new(1)), SessionRemovalReason::Closed),
+            0
+        );
+
+        assert_eq!(
+            unwrap!(sm.get(f1)).expiry_reason(),
+            Some(SessionRemovalReason::FabricRemoved)
+        );
+        assert!(!unwrap!(sm.get(f2)).is_expired());
+    }
+
+    fn evict(sm: &mut SessionMgr, policy: &dyn SessionEvictionPolicy) -> Option<u32> {
+        let id = sm.get_session_for_eviction(policy).map(|sess| sess.id);
+        if let Some(id) = id {
+            sm.remove(id);
+        }
+
+        id
+    }
+
+    #[test]
+    fn test_default_eviction_policy() {
+        let mut sm = SessionMgr::new(dummy_epoch, dummy_rand);
+
+        let f1: [u32; 5] = core::array::from_fn(|i| add_session(&mut sm, case(1), 10 + i as u64));
+        let f2: [u32; 2] = core::array::from_fn(|i| add_session(&mut sm, case(2), 20 + i as u64));
+        let f3: [u32; 4] = core::array::from_fn(|i| add_session(&mut sm, case(3), 1 + i as u64));
+        let pase = add_session(&mut sm, SessionMode::Pase { fab_idx: 0 }, 50);
+        let plain = add_session(&mut sm, SessionMode::PlainText, 60);
+
+        unwrap!(sm.sessions.iter_mut().find(|sess| sess.id == f2[1]))
+            .expire(SessionRemovalReason::Closed);
+
+        let policy = DefaultSessionEvictionPolicy;
+
+        // Expired sessions first
+        assert_eq!(evict(&mut sm, &policy), Some(f2[1]));
+        // Then unauthenticated and PASE sessions
+        assert_eq!(evict(&mut sm, &policy), Some(plain));
+        assert_eq!(evict(&mut sm, &policy), Some(pase));
+        // Then sessions of fabrics having more than the minimum number of sessions,
+        // starting with the fabric having the most sessions, even if its sessions were used more recently
+        assert_eq!(evict(&mut sm, &policy), Some(f1[0]));
+        // Same number of sessions in both fabrics - LRU order
+        assert_eq!(evict(&mut sm, &policy), Some(f3[0]));
+        assert_eq!(evict(&mut sm, &policy), Some(f1[1]));
+        // Then all other sessions in LRU order
+        assert_eq!(evict(&mut sm, &policy), Some(f3[1]));
+        assert_eq!(evict(&mut sm, &policy), Some(f3[2]));
+        assert_eq!(evict(&mut sm, &policy), Some(f3[3]));
+        assert_eq!(evict(&mut sm, &policy), Some(f1[2]));
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut sm = SessionMgr::new(dummy_epoch, dummy_rand);
+
+        assert!(sm.set_limits(0, 1).is_err());
+        assert!(sm.set_limits(1, MAX_EXCHANGES + 1).is_err());
+        unwrap!(sm.set_limits(2, 1));
+
+        add_session(&mut sm, case(1), 1);
+        let sess = unwrap!(sm.add(false, Address::default(), None));
+
+        assert!(sess
+            .add_exch(1, Role::Initiator(Default::default()))
+            .is_some());
+        assert!(sess
+            .add_exch(2, Role::Initiator(Default::default()))
+            .is_none());
+
+        assert_eq!(
+            sm.add(false, Address::default(), None)
+                .err()
+                .map(|e| e.code()),
+            Some(ErrorCode::NoSpaceSessions)
+        );
+    }
+
+    #[test]
+    fn test_custom_eviction_policy() {
+        struct Protected(u32);
+
+        impl SessionEvictionPolicy for Protected {
+            fn can_evict(&self, session: &Session, _sessions: &SessionMgr) -> bool {
+                session.id != self.0
+            }
+        }
+
+        let mut sm = SessionMgr::new(dummy_epoch, dummy_rand);
+
+        let s1 = add_session(&mut sm, case(1), 1);
+        let s2 = add_session(&mut sm, case(1), 2);
+
+        unwrap!(sm.get(s2)).reserved = true;
+
+        assert_eq!(evict(&mut sm, &Protected(s1)), None);
+        assert_eq!(evict(&mut sm, &Protected(s2)), Some(s1));
+    }
 }
@@ -28,13 +28,14 @@ use rs_matter::acl::{AclEntry, AuthMode};
 use rs_matter::crypto::KeyPair;
 use rs_matter::data_model::basic_info::BasicInfoConfig;
 use rs_matter::data_model::core::{DataModel, IMBuffer};
-use rs_matter::data_model::objects::{AsyncHandler, AsyncMetadata, Privilege};
+use rs_matter::data_model::objects::{AsyncHandler, AsyncMetadata, AtomicWrites, Privilege};
 use rs_matter::data_model::sdm::dev_att::{DataType, DevAttDataFetcher};
 use rs_matter::data_model::subscriptions::Subscriptions;
 use rs_matter::error::Error;
 use rs_matter::mdns::MdnsService;
 use rs_matter::respond::Responder;
 use rs_matter::transport::exchange::Exchange;
+use rs_matter::transport::mrp::MrpParameters;
 use rs_matter::transport::network::{
     Address, NetworkReceive, NetworkSend, MAX_RX_PACKET_SIZE, MAX_TX_PACKET_SIZE,
 };
@@ -86,6 +87,9 @@ impl E2eRunner {
         vendor_name: "E2E",
         sai: None,
         sii: None,
+        sat: None,
+        max_paths_per_invoke: Some(4),
+        privacy: false,
     };
 
     const BASIC_COMM: BasicCommData = BasicCommData {
@@ -185,9 +189,17 @@ impl E2eRunner {
 
         let matter_client = &self.matter_client;
 
+        // A fresh storage for each run, so that atomic writes do not outlive the Data Model instance
+        let atomic_writes = AtomicWrites::new();
+
         let responder = Responder::new(
             "Default",
-            DataModel::new(&self.buffers, &self.subscriptions, handler),
+            DataModel::new_with_atomic_writes(
+                &self.buffers,
+                &self.subscriptions,
+                &atomic_writes,
+                handler,
+            ),
             &self.matter,
             0,
         );
@@ -263,6 +275,7 @@ impl E2eRunner {
             None,
             None,
             None,
+            &MrpParameters::new(),
         )?;
 
         session.complete();
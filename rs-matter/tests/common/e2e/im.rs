@@ -339,6 +339,7 @@ impl TestToTLV for TestInvReq<'_> {
 pub struct TestInvResp<'a> {
     pub suppress_response: Option<bool>,
     pub inv_responses: Option<&'a [TestCmdResp<'a>]>,
+    pub more_chunks: Option<bool>,
 }
 
 impl<'a> TestInvResp<'a> {
@@ -347,6 +348,7 @@ impl<'a> TestInvResp<'a> {
         Self {
             suppress_response: Some(false),
             inv_responses: Some(inv_responses),
+            more_chunks: None,
         }
     }
 }
@@ -367,6 +369,10 @@ impl TestToTLV for TestInvResp<'_> {
             tw.end_container()?;
         }
 
+        if let Some(more_chunks) = self.more_chunks {
+            tw.bool(&TLVTag::Context(2), more_chunks)?;
+        }
+
         tw.end_container()?;
 
         Ok(())
@@ -594,6 +600,48 @@ where
         }
     }
 
+    /// Create a new TLV test instance with input payload being the IM `StatusResponse` message
+    /// and the expected payload being the IM `InvokeResponse` message.
+    pub const fn continue_invoke(input_payload: I, expected_payload: E, process_reply: F) -> Self {
+        Self {
+            input_meta: MessageMeta::new(
+                PROTO_ID_INTERACTION_MODEL,
+                OpCode::StatusResponse as _,
+                true,
+            ),
+            input_payload,
+            expected_meta: MessageMeta::new(
+                PROTO_ID_INTERACTION_MODEL,
+                OpCode::InvokeResponse as _,
+                true,
+            ),
+            expected_payload,
+            process_reply,
+            delay_ms: None,
+        }
+    }
+
+    /// Create a new TLV test instance with input payload being the IM `InvokeRequest` message
+    /// and the expected payload being the IM `StatusResponse` message.
+    pub const fn invoke_status(input_payload: I, expected_payload: E, process_reply: F) -> Self {
+        Self {
+            input_meta: MessageMeta::new(
+                PROTO_ID_INTERACTION_MODEL,
+                OpCode::InvokeRequest as _,
+                true,
+            ),
+            input_payload,
+            expected_meta: MessageMeta::new(
+                PROTO_ID_INTERACTION_MODEL,
+                OpCode::StatusResponse as _,
+                true,
+            ),
+            expected_payload,
+            process_reply,
+            delay_ms: None,
+        }
+    }
+
     /// Create a new TLV test instance with input payload being the IM `TimedRequest` message
     /// and the expected payload being the IM `StatusResponse` message.
     pub const fn timed(input_payload: I, expected_payload: E, process_reply: F) -> Self {
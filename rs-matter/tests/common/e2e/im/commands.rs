@@ -43,6 +43,9 @@ macro_rules! echo_req {
             &($data as u32),
         )
     };
+    ($endpoint:literal, $data:literal, $command_ref:literal) => {
+        $crate::echo_req!($endpoint, $data).with_command_ref($command_ref)
+    };
 }
 
 #[macro_export]
@@ -59,6 +62,19 @@ macro_rules! echo_resp {
             ),
         )
     };
+    ($endpoint:literal, $data:literal, $command_ref:literal) => {
+        $crate::common::e2e::im::commands::TestCmdResp::Cmd(
+            $crate::common::e2e::im::commands::TestCmdData::new(
+                rs_matter::interaction_model::messages::ib::CmdPath::new(
+                    Some($endpoint),
+                    Some($crate::common::e2e::im::echo_cluster::ID),
+                    Some($crate::common::e2e::im::echo_cluster::RespCommands::EchoResp as u32),
+                ),
+                &($data as u32),
+            )
+            .with_command_ref($command_ref),
+        )
+    };
 }
 
 /// A `TestCmdData` alternative more suitable for testing.
@@ -69,12 +85,23 @@ macro_rules! echo_resp {
 pub struct TestCmdData<'a> {
     pub path: CmdPath,
     pub data: &'a dyn TestToTLV,
+    pub command_ref: Option<u16>,
 }
 
 impl<'a> TestCmdData<'a> {
     /// Create a new `TestCmdData` instance.
     pub const fn new(path: CmdPath, data: &'a dyn TestToTLV) -> Self {
-        Self { path, data }
+        Self {
+            path,
+            data,
+            command_ref: None,
+        }
+    }
+
+    /// Set the command reference of the `TestCmdData` instance.
+    pub const fn with_command_ref(mut self, command_ref: u16) -> Self {
+        self.command_ref = Some(command_ref);
+        self
     }
 }
 
@@ -86,6 +113,10 @@ impl TestToTLV for TestCmdData<'_> {
 
         self.data.test_to_tlv(&TLVTag::Context(1), tw)?;
 
+        if let Some(command_ref) = self.command_ref {
+            tw.u16(&TLVTag::Context(2), command_ref)?;
+        }
+
         tw.end_container()?;
 
         Ok(())
@@ -25,9 +25,9 @@ use num_derive::FromPrimitive;
 use strum::{EnumDiscriminants, FromRepr};
 
 use rs_matter::data_model::objects::{
-    Access, AttrDataEncoder, AttrDataWriter, AttrType, Attribute, Cluster, CmdDataEncoder,
-    CmdDataWriter, Command, Dataver, Handler, InvokeContext, NonBlockingHandler, Quality,
-    ReadContext, WriteContext,
+    Access, AtomicWrite, AttrDataEncoder, AttrDataWriter, AttrType, Attribute, Cluster,
+    CmdDataEncoder, CmdDataWriter, Command, Dataver, Handler, InvokeContext, NonBlockingHandler,
+    Quality, ReadContext, WriteContext,
 };
 use rs_matter::error::{Error, ErrorCode};
 use rs_matter::interaction_model::messages::ib::{attr_list_write, ListOperation};
@@ -55,15 +55,22 @@ attribute_enum!(Attributes);
 
 #[derive(FromRepr)]
 #[repr(u32)]
+#[allow(clippy::enum_variant_names)]
 pub enum Commands {
     EchoReq = 0x00,
+    BigEchoReq = 0x02,
+    TimedEchoReq = 0x04,
+    AtomicReq = 0xfe,
 }
 
 command_enum!(Commands);
 
 #[derive(FromPrimitive)]
+#[allow(clippy::enum_variant_names)]
 pub enum RespCommands {
     EchoResp = 0x01,
+    BigEchoResp = 0x03,
+    AtomicResp = 0xfd,
 }
 
 pub const CLUSTER: Cluster<'static> = Cluster {
@@ -97,11 +104,28 @@ pub const CLUSTER: Cluster<'static> = Cluster {
             Quality::NONE,
         ),
     ),
-    commands: commands!(Command::new(
-        Commands::EchoReq as _,
-        Some(RespCommands::EchoResp as _),
-        Access::WA,
-    ),),
+    commands: commands!(
+        Command::new(
+            Commands::EchoReq as _,
+            Some(RespCommands::EchoResp as _),
+            Access::WA,
+        ),
+        Command::new(
+            Commands::BigEchoReq as _,
+            Some(RespCommands::BigEchoResp as _),
+            Access::WA,
+        ),
+        Command::new(
+            Commands::TimedEchoReq as _,
+            None,
+            Access::WA.union(Access::TIMED_ONLY),
+        ),
+        Command::new(
+            Commands::AtomicReq as _,
+            Some(RespCommands::AtomicResp as _),
+            Access::WA,
+        ),
+    ),
     with_attrs: with!(all),
     with_cmds: with!(all),
 };
@@ -220,6 +244,10 @@ impl EchoHandler {
         let cmd = ctx.cmd();
         let data = ctx.data();
 
+        if let Some(write) = ctx.atomic_write() {
+            return self.commit_atomic_write(write);
+        }
+
         match cmd.cmd_id.try_into()? {
             // This will generate an echo response on the same endpoint
             // with data multiplied by the multiplier
@@ -233,7 +261,47 @@ impl EchoHandler {
 
                 writer.complete()
             }
+            // This will generate an echo response on the same endpoint
+            // with an octet string of the requested length, filled with the multiplier
+            Commands::BigEchoReq => {
+                let len = data.u16()? as usize;
+
+                let mut writer = encoder.with_command(RespCommands::BigEchoResp as _)?;
+
+                writer.stri(
+                    &CmdDataWriter::TAG,
+                    len,
+                    core::iter::repeat_n(self.multiplier, len),
+                )?;
+
+                writer.complete()
+            }
+            // Atomic requests are handled by the Data Model, which only
+            // calls us with the pending values when committing
+            // This will only succeed if invoked as part of a timed interaction
+            Commands::TimedEchoReq => Ok(()),
+            Commands::AtomicReq => Err(ErrorCode::InvalidAction.into()),
+        }
+    }
+
+    fn commit_atomic_write(&self, write: &AtomicWrite<'_>) -> Result<(), Error> {
+        let att_write = write
+            .value(AttributesDiscriminants::AttWrite as _)
+            .map(|value| value.u16())
+            .transpose()?;
+
+        // Validate the whole set first, so that nothing is applied if it is invalid
+        if att_write == Some(0) {
+            return Err(ErrorCode::ConstraintError.into());
         }
+
+        if let Some(att_write) = att_write {
+            self.att_write.set(att_write);
+        }
+
+        self.data_ver.changed();
+
+        Ok(())
     }
 
     fn write_attr_list(&self, op: &ListOperation, data: &TLVElement) -> Result<(), Error> {
@@ -36,7 +36,7 @@ use super::echo_cluster::{self, EchoHandler};
 /// A sample handler for E2E IM tests.
 pub struct E2eTestHandler<'a>(
     handler_chain_type!(
-        EpClMatcher => Async<on_off::HandlerAdaptor<OnOffHandler>>, 
+        EpClMatcher => Async<on_off::HandlerAdaptor<OnOffHandler<'static>>>, 
         EpClMatcher => Async<EchoHandler>, 
         EpClMatcher => Async<desc::HandlerAdaptor<DescHandler<'static>>>, 
         EpClMatcher => Async<EchoHandler>
@@ -47,20 +47,20 @@ impl<'a> E2eTestHandler<'a> {
     pub const NODE: Node<'static> = Node {
         id: 0,
         endpoints: &[
-            Endpoint {
-                id: 0,
-                clusters: clusters!(eth; echo_cluster::CLUSTER),
-                device_types: &[DEV_TYPE_ROOT_NODE],
-            },
-            Endpoint {
-                id: 1,
-                clusters: clusters!(
+            Endpoint::new(
+                0,
+                &[DEV_TYPE_ROOT_NODE],
+                clusters!(eth; echo_cluster::CLUSTER),
+            ),
+            Endpoint::new(
+                1,
+                &[DEV_TYPE_ON_OFF_LIGHT],
+                clusters!(
                     DescHandler::CLUSTER,
                     OnOffHandler::CLUSTER,
                     echo_cluster::CLUSTER,
                 ),
-                device_types: &[DEV_TYPE_ON_OFF_LIGHT],
-            },
+            ),
         ],
     };
 
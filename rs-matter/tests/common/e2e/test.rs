@@ -92,6 +92,34 @@ impl E2eRunner {
         .unwrap()
     }
 
+    /// Run the provided tests with the given handler, each one in its own exchange,
+    /// and wait with blocking until all tests complete or the first one fails.
+    ///
+    /// Unlike `test_all`, this allows testing a sequence of separate IM transactions
+    /// against the same Data Model instance.
+    pub fn test_each<H, I, T>(&self, handler: H, tests: I)
+    where
+        H: AsyncHandler + AsyncMetadata,
+        I: IntoIterator<Item = T>,
+        T: E2eTest,
+    {
+        block_on(
+            select(self.run(handler), async move {
+                for test in tests {
+                    let mut exchange = self.initiate_exchange().await?;
+
+                    Self::execute_test(&mut exchange, test).await?;
+
+                    exchange.acknowledge().await?;
+                }
+
+                Ok(())
+            })
+            .coalesce(),
+        )
+        .unwrap()
+    }
+
     /// Execute the test via the provided exchange.
     pub async fn execute_test<T>(exchange: &mut Exchange<'_>, test: T) -> Result<(), Error>
     where
@@ -25,10 +25,14 @@ use rs_matter::interaction_model::messages::ib::{
     AttrPath, AttrStatus, ClusterPath, DataVersionFilter,
 };
 use rs_matter::interaction_model::messages::GenericPath;
+use rs_matter::tlv::Nullable;
 
 use crate::common::e2e::im::attributes::{TestAttrData, TestAttrResp};
 use crate::common::e2e::im::echo_cluster::ATTR_WRITE_DEFAULT_VALUE;
-use crate::common::e2e::im::{echo_cluster, ReplyProcessor, TestReadReq, TestReportDataMsg};
+use crate::common::e2e::im::{
+    echo_cluster, ReplyProcessor, TestReadReq, TestReportDataMsg, TestWriteReq, TestWriteResp,
+};
+use crate::common::e2e::test::E2eTest;
 use crate::common::e2e::tlv::TLVTest;
 use crate::common::e2e::{ImEngine, IM_ENGINE_PEER_ID};
 use crate::common::init_env_logger;
@@ -439,6 +443,71 @@ fn write_with_runtime_acl_add() {
     assert_eq!(val0, handler.echo_cluster(0).att_write.get());
 }
 
+#[test]
+/// Ensure that a chunked write to the ACL attribute - which starts with replacing the ACL
+/// with an empty list - is not denied in its subsequent chunks, even though the first chunk
+/// revokes all privileges of our peer
+fn chunked_acl_write() {
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+    im.add_default_acl();
+
+    let val0 = 10;
+    let ep0_att = GenericPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::AttributesDiscriminants::AttWrite as u32),
+    );
+    let input0 = TestAttrData::new(None, AttrPath::new(&ep0_att), &val0 as _);
+
+    // Create ACL to allow our peer ADMIN on everything
+    let mut allow_acl = AclEntry::new(None, Privilege::ADMIN, AuthMode::Case);
+    allow_acl.add_subject(IM_ENGINE_PEER_ID).unwrap();
+
+    let acl_att = GenericPath::new(
+        Some(0),
+        Some(acl::AclHandler::CLUSTER.id),
+        Some(acl::AttributeId::Acl as u32),
+    );
+    let mut acl_append_path = AttrPath::new(&acl_att);
+    acl_append_path.list_index = Some(Nullable::none());
+
+    let empty_acl: &[u32] = &[];
+
+    // Test: replace the ACL with an empty one in the first chunk, then append to it in the second
+    im.test_all(
+        &handler,
+        [
+            &TLVTest::write(
+                TestWriteReq {
+                    more_chunked: Some(true),
+                    ..TestWriteReq::reqs(&[TestAttrData::new(
+                        None,
+                        AttrPath::new(&acl_att),
+                        &empty_acl,
+                    )])
+                },
+                TestWriteResp::resp(&[AttrStatus::new(&acl_att, IMStatusCode::Success, 0)]),
+                ReplyProcessor::none,
+            ) as &dyn E2eTest,
+            &TLVTest::write_attrs(
+                &[TestAttrData::new(None, acl_append_path, &allow_acl)],
+                &[AttrStatus::new(&acl_att, IMStatusCode::Success, 0)],
+            ),
+        ],
+    );
+
+    // Test: the peer is still allowed to write
+    im.handle_write_reqs(
+        &handler,
+        &[input0],
+        &[AttrStatus::new(&ep0_att, IMStatusCode::Success, 0)],
+    );
+    assert_eq!(val0, handler.echo_cluster(0).att_write.get());
+}
+
 #[test]
 /// Data Version filtering should ignore the attributes that are filtered
 /// - in case of wildcard reads
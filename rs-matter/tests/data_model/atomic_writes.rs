@@ -0,0 +1,322 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use rs_matter::data_model::objects::AtomicRequestType;
+use rs_matter::interaction_model::core::IMStatusCode;
+use rs_matter::interaction_model::messages::ib::{AttrPath, AttrStatus, CmdPath, CmdStatus};
+use rs_matter::interaction_model::messages::GenericPath;
+use rs_matter::tlv::ToTLV;
+
+use crate::common::e2e::im::attributes::TestAttrData;
+use crate::common::e2e::im::commands::{TestCmdData, TestCmdResp};
+use crate::common::e2e::im::echo_cluster::{self, ATTR_WRITE_DEFAULT_VALUE};
+use crate::common::e2e::test::E2eTest;
+use crate::common::e2e::tlv::TLVTest;
+use crate::common::e2e::ImEngine;
+use crate::common::init_env_logger;
+
+/// An `AtomicRequest` alternative more suitable for testing.
+#[derive(Debug, Clone, ToTLV)]
+struct TestAtomicRequest<'a> {
+    request_type: AtomicRequestType,
+    attribute_requests: &'a [u32],
+    timeout: Option<u16>,
+}
+
+#[derive(Debug, Clone, ToTLV)]
+struct TestAtomicAttributeStatus {
+    attribute_id: u32,
+    status_code: IMStatusCode,
+}
+
+/// An `AtomicResponse` alternative more suitable for testing.
+#[derive(Debug, Clone, ToTLV)]
+struct TestAtomicResponse<'a> {
+    status_code: IMStatusCode,
+    attribute_status: &'a [TestAtomicAttributeStatus],
+    timeout: Option<u16>,
+}
+
+const ATT_WRITE: u32 = echo_cluster::AttributesDiscriminants::AttWrite as _;
+
+const ATT1: u32 = echo_cluster::AttributesDiscriminants::Att1 as _;
+
+const ATT_WRITE_SUCCESS: &[TestAtomicAttributeStatus] = &[TestAtomicAttributeStatus {
+    attribute_id: ATT_WRITE,
+    status_code: IMStatusCode::Success,
+}];
+
+fn req_path() -> CmdPath {
+    CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::AtomicReq as _),
+    )
+}
+
+fn resp_path() -> CmdPath {
+    CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::RespCommands::AtomicResp as _),
+    )
+}
+
+fn atomic_req(
+    request_type: AtomicRequestType,
+    attrs: &[u32],
+    timeout: Option<u16>,
+) -> TestAtomicRequest<'_> {
+    TestAtomicRequest {
+        request_type,
+        attribute_requests: attrs,
+        timeout,
+    }
+}
+
+fn atomic_resp<'a>(
+    status_code: IMStatusCode,
+    attribute_status: &'a [TestAtomicAttributeStatus],
+    timeout: Option<u16>,
+) -> TestAtomicResponse<'a> {
+    TestAtomicResponse {
+        status_code,
+        attribute_status,
+        timeout,
+    }
+}
+
+#[test]
+fn atomic_write_commit() {
+    // Begin an atomic write of AttWrite, write it, and then commit
+    // - the written value should only be applied once committed
+    init_env_logger();
+
+    let att_write = GenericPath::new(Some(0), Some(echo_cluster::ID), Some(ATT_WRITE));
+    let val = 25u16;
+
+    // Begin, with the timeout capped to the maximum one
+    let begin_req_data = atomic_req(AtomicRequestType::BeginWrite, &[ATT_WRITE], Some(60000));
+    let begin_req = &[TestCmdData::new(req_path(), &begin_req_data)];
+    let begin_resp_data = atomic_resp(IMStatusCode::Success, ATT_WRITE_SUCCESS, Some(9000));
+    let begin_resp = &[TestCmdResp::Cmd(TestCmdData::new(
+        resp_path(),
+        &begin_resp_data,
+    ))];
+
+    // Beginning again by the same client should fail
+    let begin_again_resp = &[TestCmdResp::Status(CmdStatus::new(
+        req_path(),
+        IMStatusCode::InvalidInState,
+        0,
+    ))];
+
+    let write_req = &[TestAttrData::new(
+        None,
+        AttrPath::new(&att_write),
+        &val as _,
+    )];
+    let write_resp = &[AttrStatus::new(&att_write, IMStatusCode::Success, 0)];
+
+    let commit_req_data = atomic_req(AtomicRequestType::CommitWrite, &[ATT_WRITE], None);
+    let commit_req = &[TestCmdData::new(req_path(), &commit_req_data)];
+    let commit_resp_data = atomic_resp(IMStatusCode::Success, ATT_WRITE_SUCCESS, None);
+    let commit_resp = &[TestCmdResp::Cmd(TestCmdData::new(
+        resp_path(),
+        &commit_resp_data,
+    ))];
+
+    // Committing again should fail, as there is no atomic write in progress
+    let commit_again_resp = begin_again_resp;
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+
+    // The write is only stored as pending
+    im.test_each(
+        &handler,
+        [
+            &TLVTest::inv_cmds(begin_req, begin_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(begin_req, begin_again_resp) as &dyn E2eTest,
+            &TLVTest::write_attrs(write_req, write_resp) as &dyn E2eTest,
+        ],
+    );
+    assert_eq!(
+        ATTR_WRITE_DEFAULT_VALUE,
+        handler.echo_cluster(0).att_write.get()
+    );
+
+    // Commit applies the pending value
+    im.test_each(
+        &handler,
+        [
+            &TLVTest::inv_cmds(begin_req, begin_resp) as &dyn E2eTest,
+            &TLVTest::write_attrs(write_req, write_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(commit_req, commit_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(commit_req, commit_again_resp) as &dyn E2eTest,
+        ],
+    );
+    assert_eq!(val, handler.echo_cluster(0).att_write.get());
+}
+
+#[test]
+fn atomic_write_rollback_and_failed_commit() {
+    init_env_logger();
+
+    let att_write = GenericPath::new(Some(0), Some(echo_cluster::ID), Some(ATT_WRITE));
+    let val = 30u16;
+    let invalid = 0u16;
+
+    let begin_req_data = atomic_req(AtomicRequestType::BeginWrite, &[ATT_WRITE], Some(5000));
+    let begin_req = &[TestCmdData::new(req_path(), &begin_req_data)];
+    let begin_resp_data = atomic_resp(IMStatusCode::Success, ATT_WRITE_SUCCESS, Some(5000));
+    let begin_resp = &[TestCmdResp::Cmd(TestCmdData::new(
+        resp_path(),
+        &begin_resp_data,
+    ))];
+
+    let write_req = &[TestAttrData::new(
+        None,
+        AttrPath::new(&att_write),
+        &val as _,
+    )];
+    let write_invalid_req = &[TestAttrData::new(
+        None,
+        AttrPath::new(&att_write),
+        &invalid as _,
+    )];
+    let write_resp = &[AttrStatus::new(&att_write, IMStatusCode::Success, 0)];
+
+    let rollback_req_data = atomic_req(AtomicRequestType::RollbackWrite, &[ATT_WRITE], None);
+    let rollback_req = &[TestCmdData::new(req_path(), &rollback_req_data)];
+    let rollback_resp_data = atomic_resp(IMStatusCode::Success, ATT_WRITE_SUCCESS, None);
+    let rollback_resp = &[TestCmdResp::Cmd(TestCmdData::new(
+        resp_path(),
+        &rollback_resp_data,
+    ))];
+
+    let commit_req_data = atomic_req(AtomicRequestType::CommitWrite, &[ATT_WRITE], None);
+    let commit_req = &[TestCmdData::new(req_path(), &commit_req_data)];
+    let commit_failed_resp_data = atomic_resp(
+        IMStatusCode::Failure,
+        &[TestAtomicAttributeStatus {
+            attribute_id: ATT_WRITE,
+            status_code: IMStatusCode::ConstraintError,
+        }],
+        None,
+    );
+    let commit_failed_resp = &[TestCmdResp::Cmd(TestCmdData::new(
+        resp_path(),
+        &commit_failed_resp_data,
+    ))];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+
+    // Begin, write and then rollback - the pending value should be discarded
+    im.test_each(
+        &handler,
+        [
+            &TLVTest::inv_cmds(begin_req, begin_resp) as &dyn E2eTest,
+            &TLVTest::write_attrs(write_req, write_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(rollback_req, rollback_resp) as &dyn E2eTest,
+        ],
+    );
+    assert_eq!(
+        ATTR_WRITE_DEFAULT_VALUE,
+        handler.echo_cluster(0).att_write.get()
+    );
+
+    // Once rolled back, writes are applied immediately again
+    im.test_each(
+        &handler,
+        [
+            &TLVTest::inv_cmds(begin_req, begin_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(rollback_req, rollback_resp) as &dyn E2eTest,
+            &TLVTest::write_attrs(write_req, write_resp) as &dyn E2eTest,
+        ],
+    );
+    assert_eq!(val, handler.echo_cluster(0).att_write.get());
+
+    // Begin, write an invalid value and commit - the handler rejects the whole set
+    im.test_each(
+        &handler,
+        [
+            &TLVTest::inv_cmds(begin_req, begin_resp) as &dyn E2eTest,
+            &TLVTest::write_attrs(write_invalid_req, write_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(commit_req, commit_failed_resp) as &dyn E2eTest,
+        ],
+    );
+    assert_eq!(val, handler.echo_cluster(0).att_write.get());
+}
+
+#[test]
+fn atomic_write_begin_invalid() {
+    // Beginning an atomic write of attributes which are not writable or do not exist
+    // should fail, reporting the status of each attribute
+    init_env_logger();
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+
+    im.add_default_acl();
+
+    let req = atomic_req(
+        AtomicRequestType::BeginWrite,
+        &[ATT_WRITE, ATT1, 0x99],
+        Some(5000),
+    );
+    let resp = atomic_resp(
+        IMStatusCode::Failure,
+        &[
+            TestAtomicAttributeStatus {
+                attribute_id: ATT_WRITE,
+                status_code: IMStatusCode::Success,
+            },
+            TestAtomicAttributeStatus {
+                attribute_id: ATT1,
+                status_code: IMStatusCode::UnsupportedWrite,
+            },
+            TestAtomicAttributeStatus {
+                attribute_id: 0x99,
+                status_code: IMStatusCode::UnsupportedAttribute,
+            },
+        ],
+        None,
+    );
+    im.handle_commands(
+        &handler,
+        &[TestCmdData::new(req_path(), &req)],
+        &[TestCmdResp::Cmd(TestCmdData::new(resp_path(), &resp))],
+    );
+
+    // Begin without a timeout is invalid
+    let req = atomic_req(AtomicRequestType::BeginWrite, &[ATT_WRITE], None);
+    im.handle_commands(
+        &handler,
+        &[TestCmdData::new(req_path(), &req)],
+        &[TestCmdResp::Status(CmdStatus::new(
+            req_path(),
+            IMStatusCode::InvalidCommand,
+            0,
+        ))],
+    );
+}
@@ -22,6 +22,9 @@ use rs_matter::tlv::{Nullable, TLVValue};
 
 use crate::common::e2e::im::attributes::TestAttrData;
 use crate::common::e2e::im::echo_cluster::{self, TestChecker};
+use crate::common::e2e::im::{ReplyProcessor, TestWriteReq, TestWriteResp};
+use crate::common::e2e::test::E2eTest;
+use crate::common::e2e::tlv::TLVTest;
 use crate::common::e2e::ImEngine;
 use crate::common::init_env_logger;
 
@@ -102,6 +105,129 @@ fn attr_list_ops() {
 
     // Test 6: Overwrite Operation - delete whole list
     att_path.list_index = None;
+    let input = &[TestAttrData::new(None, att_path.clone(), &delete_all)];
+    let expected = &[AttrStatus::new(&att_data, IMStatusCode::Success, 0)];
+
+    ImEngine::write_reqs(input, expected);
+    {
+        let tc = tc_handle.lock().unwrap();
+        assert_eq!([None, None, None, None, None], tc.write_list);
+    }
+
+    let mut append_path = att_path.clone();
+    append_path.list_index = Some(Nullable::none());
+
+    let success = AttrStatus::new(&att_data, IMStatusCode::Success, 0);
+    let exhausted = AttrStatus::new(&att_data, IMStatusCode::ResourceExhausted, 0);
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+    im.add_default_acl();
+
+    // Test 7: Chunked Overwrite Operation - an empty list followed by appends, spanning two chunks
+    let (val2, val3, val4): (u16, u16, u16) = (30, 31, 32);
+    let chunk0 = &[
+        TestAttrData::new(None, att_path.clone(), &delete_all),
+        TestAttrData::new(None, append_path.clone(), &val2),
+        TestAttrData::new(None, append_path.clone(), &val3),
+    ];
+    let chunk1 = &[TestAttrData::new(None, append_path.clone(), &val4)];
+
+    im.test_all(
+        &handler,
+        [
+            &TLVTest::write(
+                TestWriteReq {
+                    more_chunked: Some(true),
+                    ..TestWriteReq::reqs(chunk0)
+                },
+                TestWriteResp::resp(&[success.clone(), success.clone(), success.clone()]),
+                ReplyProcessor::none,
+            ) as &dyn E2eTest,
+            &TLVTest::write_attrs(chunk1, core::slice::from_ref(&success)),
+        ],
+    );
+    {
+        let tc = tc_handle.lock().unwrap();
+        assert_eq!(
+            [Some(val2), Some(val3), Some(val4), None, None],
+            tc.write_list
+        );
+    }
+
+    // Test 8: Chunked Overwrite Operation - an append in the second chunk fails, which
+    // rolls back the whole list write, and skips the remaining appends
+    let vals: [u16; 7] = [40, 41, 42, 43, 44, 45, 46];
+    let chunk0 = &[
+        TestAttrData::new(None, att_path.clone(), &delete_all),
+        TestAttrData::new(None, append_path.clone(), &vals[0]),
+        TestAttrData::new(None, append_path.clone(), &vals[1]),
+        TestAttrData::new(None, append_path.clone(), &vals[2]),
+    ];
+    let chunk1 = &[
+        TestAttrData::new(None, append_path.clone(), &vals[3]),
+        TestAttrData::new(None, append_path.clone(), &vals[4]),
+        TestAttrData::new(None, append_path.clone(), &vals[5]),
+        TestAttrData::new(None, append_path.clone(), &vals[6]),
+    ];
+
+    im.test_all(
+        &handler,
+        [
+            &TLVTest::write(
+                TestWriteReq {
+                    more_chunked: Some(true),
+                    ..TestWriteReq::reqs(chunk0)
+                },
+                TestWriteResp::resp(&[
+                    success.clone(),
+                    success.clone(),
+                    success.clone(),
+                    success.clone(),
+                ]),
+                ReplyProcessor::none,
+            ) as &dyn E2eTest,
+            &TLVTest::write_attrs(
+                chunk1,
+                &[
+                    success.clone(),
+                    success.clone(),
+                    exhausted.clone(),
+                    exhausted.clone(),
+                ],
+            ),
+        ],
+    );
+    {
+        let tc = tc_handle.lock().unwrap();
+        assert_eq!(
+            [Some(val2), Some(val3), Some(val4), None, None],
+            tc.write_list
+        );
+    }
+
+    // Test 9: Many Add Operations - more than what fits in a single batch of expanded writes;
+    // the third one fails, which rolls back the first two, and skips the remaining ones
+    let many_vals: [u16; 9] = [50, 51, 52, 53, 54, 55, 56, 57, 58];
+    let input = many_vals
+        .iter()
+        .map(|val| TestAttrData::new(None, append_path.clone(), val))
+        .collect::<Vec<_>>();
+    let expected = [success.clone(), success]
+        .into_iter()
+        .chain(core::iter::repeat_n(exhausted, 7))
+        .collect::<Vec<_>>();
+
+    ImEngine::write_reqs(&input, &expected);
+    {
+        let tc = tc_handle.lock().unwrap();
+        assert_eq!(
+            [Some(val2), Some(val3), Some(val4), None, None],
+            tc.write_list
+        );
+    }
+
+    // Test 10: Overwrite Operation - delete whole list
     let input = &[TestAttrData::new(None, att_path, &delete_all)];
     let expected = &[AttrStatus::new(&att_data, IMStatusCode::Success, 0)];
 
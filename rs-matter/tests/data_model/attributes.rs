@@ -186,9 +186,18 @@ fn test_read_wc_endpoint_wc_attribute() {
         GlobalElements::ClusterRevision as _,
     ];
 
-    let gen_cmd_list: &[u32] = &[echo_cluster::RespCommands::EchoResp as _];
+    let gen_cmd_list: &[u32] = &[
+        echo_cluster::RespCommands::EchoResp as _,
+        echo_cluster::RespCommands::BigEchoResp as _,
+        echo_cluster::RespCommands::AtomicResp as _,
+    ];
 
-    let acc_cmd_list: &[u32] = &[echo_cluster::Commands::EchoReq as _];
+    let acc_cmd_list: &[u32] = &[
+        echo_cluster::Commands::EchoReq as _,
+        echo_cluster::Commands::BigEchoReq as _,
+        echo_cluster::Commands::TimedEchoReq as _,
+        echo_cluster::Commands::AtomicReq as _,
+    ];
 
     let event_list: &[u32] = &[];
 
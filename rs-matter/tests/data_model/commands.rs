@@ -18,9 +18,13 @@
 use rs_matter::data_model::on_off::{self, ClusterHandler as _};
 use rs_matter::interaction_model::core::IMStatusCode;
 use rs_matter::interaction_model::messages::ib::{CmdPath, CmdStatus};
+use rs_matter::interaction_model::messages::msg::StatusResp;
+use rs_matter::tlv::Octets;
 
-use crate::common::e2e::im::commands::TestCmdResp;
-use crate::common::e2e::im::echo_cluster;
+use crate::common::e2e::im::commands::{TestCmdData, TestCmdResp};
+use crate::common::e2e::im::{echo_cluster, ReplyProcessor, TestInvReq, TestInvResp};
+use crate::common::e2e::test::E2eTest;
+use crate::common::e2e::tlv::TLVTest;
 use crate::common::e2e::ImEngine;
 use crate::common::init_env_logger;
 use crate::{cmd_data, echo_req, echo_resp};
@@ -32,19 +36,17 @@ fn test_invoke_cmds_success() {
     // - another on endpoint 1 with data 10
     init_env_logger();
 
-    let input = &[echo_req!(0, 5), echo_req!(1, 10)];
-    let expected = &[echo_resp!(0, 10), echo_resp!(1, 30)];
+    let input = &[echo_req!(0, 5, 1), echo_req!(1, 10, 2)];
+    let expected = &[echo_resp!(0, 10, 1), echo_resp!(1, 30, 2)];
     ImEngine::commands(input, expected);
 }
 
 #[test]
 fn test_invoke_cmds_unsupported_fields() {
-    // 5 commands
+    // 3 commands
     // - endpoint doesn't exist - UnsupportedEndpoint
     // - cluster doesn't exist - UnsupportedCluster
-    // - cluster doesn't exist and endpoint is wildcard - UnsupportedCluster
     // - command doesn't exist - UnsupportedCommand
-    // - command doesn't exist and endpoint is wildcard - UnsupportedCommand
     init_env_logger();
 
     let invalid_endpoint = CmdPath::new(
@@ -57,39 +59,178 @@ fn test_invoke_cmds_unsupported_fields() {
         Some(0x1234),
         Some(echo_cluster::Commands::EchoReq as u32),
     );
+    let invalid_command = CmdPath::new(Some(0), Some(echo_cluster::ID), Some(0x1234));
+    let input = &[
+        cmd_data!(invalid_endpoint.clone(), 5).with_command_ref(1),
+        cmd_data!(invalid_cluster.clone(), 5).with_command_ref(2),
+        cmd_data!(invalid_command.clone(), 5).with_command_ref(3),
+    ];
+
+    let expected = &[
+        TestCmdResp::Status(
+            CmdStatus::new(invalid_endpoint, IMStatusCode::UnsupportedEndpoint, 0)
+                .with_command_ref(Some(1)),
+        ),
+        TestCmdResp::Status(
+            CmdStatus::new(invalid_cluster, IMStatusCode::UnsupportedCluster, 0)
+                .with_command_ref(Some(2)),
+        ),
+        TestCmdResp::Status(
+            CmdStatus::new(invalid_command, IMStatusCode::UnsupportedCommand, 0)
+                .with_command_ref(Some(3)),
+        ),
+    ];
+    ImEngine::commands(input, expected);
+}
+
+#[test]
+fn test_invoke_cmd_wc_endpoint_unsupported_fields() {
+    // 2 commands with wildcard endpoints, each in its own request
+    // - cluster doesn't exist - no response
+    // - command doesn't exist - no response
+    init_env_logger();
+
     let invalid_cluster_wc_endpoint = CmdPath::new(
         None,
         Some(0x1234),
         Some(echo_cluster::Commands::EchoReq as u32),
     );
-    let invalid_command = CmdPath::new(Some(0), Some(echo_cluster::ID), Some(0x1234));
     let invalid_command_wc_endpoint = CmdPath::new(None, Some(echo_cluster::ID), Some(0x1234));
-    let input = &[
-        cmd_data!(invalid_endpoint.clone(), 5),
-        cmd_data!(invalid_cluster.clone(), 5),
-        cmd_data!(invalid_cluster_wc_endpoint, 5),
-        cmd_data!(invalid_command.clone(), 5),
-        cmd_data!(invalid_command_wc_endpoint, 5),
+
+    ImEngine::commands(&[cmd_data!(invalid_cluster_wc_endpoint, 5)], &[]);
+    ImEngine::commands(&[cmd_data!(invalid_command_wc_endpoint, 5)], &[]);
+}
+
+#[test]
+fn test_invoke_cmds_batched_invalid() {
+    // Batched requests are rejected as a whole with an InvalidAction status when
+    // - a command does not have a command reference
+    // - two commands have the same command reference
+    // - two commands have the same path
+    // - a command has a wildcard path
+    // - there are more commands than the supported maximum
+    init_env_logger();
+
+    let wc_endpoint = CmdPath::new(
+        None,
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::EchoReq as u32),
+    );
+    let no_ref = &[echo_req!(0, 5, 1), echo_req!(1, 10)];
+    let dup_ref = &[echo_req!(0, 5, 1), echo_req!(1, 10, 1)];
+    let dup_path = &[echo_req!(0, 5, 1), echo_req!(0, 10, 2)];
+    let wildcard = &[
+        echo_req!(0, 5, 1),
+        cmd_data!(wc_endpoint, 10).with_command_ref(2),
+    ];
+    let too_many = &[
+        echo_req!(0, 5, 1),
+        echo_req!(1, 5, 2),
+        cmd_data!(
+            CmdPath::new(Some(0), Some(echo_cluster::ID), Some(0x1234)),
+            5
+        )
+        .with_command_ref(3),
+        cmd_data!(
+            CmdPath::new(Some(1), Some(echo_cluster::ID), Some(0x1234)),
+            5
+        )
+        .with_command_ref(4),
+        cmd_data!(CmdPath::new(Some(0), Some(0x1234), Some(0x1234)), 5).with_command_ref(5),
     ];
 
-    let expected = &[
-        TestCmdResp::Status(CmdStatus::new(
-            invalid_endpoint,
-            IMStatusCode::UnsupportedEndpoint,
-            0,
-        )),
-        TestCmdResp::Status(CmdStatus::new(
-            invalid_cluster,
-            IMStatusCode::UnsupportedCluster,
-            0,
-        )),
-        TestCmdResp::Status(CmdStatus::new(
-            invalid_command,
-            IMStatusCode::UnsupportedCommand,
-            0,
-        )),
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+    im.add_default_acl();
+
+    for input in [
+        &no_ref[..],
+        &dup_ref[..],
+        &dup_path[..],
+        &wildcard[..],
+        &too_many[..],
+    ] {
+        im.test_one(
+            &handler,
+            TLVTest::invoke_status(
+                TestInvReq::reqs(input),
+                StatusResp {
+                    status: IMStatusCode::InvalidAction,
+                },
+                ReplyProcessor::none,
+            ),
+        );
+    }
+}
+
+#[test]
+fn test_invoke_cmds_chunked() {
+    // 2 big echo requests which do not fit in a single response
+    // - the response to the first one is sent in a chunk with MoreChunkedMsgs set
+    // - the response to the second one is sent once the client acknowledges the first chunk
+    init_env_logger();
+
+    const LEN: u16 = 700;
+
+    let req0_path = CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::BigEchoReq as u32),
+    );
+    let req1_path = CmdPath::new(
+        Some(1),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::BigEchoReq as u32),
+    );
+    let resp0_path = CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::RespCommands::BigEchoResp as u32),
+    );
+    let resp1_path = CmdPath::new(
+        Some(1),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::RespCommands::BigEchoResp as u32),
+    );
+
+    let input = &[
+        TestCmdData::new(req0_path, &LEN).with_command_ref(1),
+        TestCmdData::new(req1_path, &LEN).with_command_ref(2),
     ];
-    ImEngine::commands(input, expected);
+
+    let data0 = Octets(&[2; LEN as usize]);
+    let data1 = Octets(&[3; LEN as usize]);
+    let chunk0 = &[TestCmdResp::Cmd(
+        TestCmdData::new(resp0_path, &data0).with_command_ref(1),
+    )];
+    let chunk1 = &[TestCmdResp::Cmd(
+        TestCmdData::new(resp1_path, &data1).with_command_ref(2),
+    )];
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+    im.add_default_acl();
+
+    im.test_all(
+        &handler,
+        [
+            &TLVTest::invoke(
+                TestInvReq::reqs(input),
+                TestInvResp {
+                    more_chunks: Some(true),
+                    ..TestInvResp::resp(chunk0)
+                },
+                ReplyProcessor::none,
+            ) as &dyn E2eTest,
+            &TLVTest::continue_invoke(
+                StatusResp {
+                    status: IMStatusCode::Success,
+                },
+                TestInvResp::resp(chunk1),
+                ReplyProcessor::none,
+            ) as &dyn E2eTest,
+        ],
+    );
 }
 
 #[test]
@@ -174,6 +174,10 @@ static ATTR_RESPS: &[TestAttrResp<'static>] = &[
     attr_data!(1, 29, GlobalElements::FeatureMap, None),
     attr_data!(1, 29, GlobalElements::ClusterRevision, None),
     attr_data!(1, 6, on_off::AttributeId::OnOff, None),
+    attr_data!(1, 6, on_off::AttributeId::GlobalSceneControl, None),
+    attr_data!(1, 6, on_off::AttributeId::OnTime, None),
+    attr_data!(1, 6, on_off::AttributeId::OffWaitTime, None),
+    attr_data!(1, 6, on_off::AttributeId::StartUpOnOff, None),
     attr_data!(1, 6, GlobalElements::GeneratedCmdList, None),
     attr_data!(1, 6, GlobalElements::AcceptedCmdList, None),
     attr_data!(1, 6, GlobalElements::EventList, None),
@@ -196,8 +200,9 @@ fn test_long_read_success() {
     const PART_1: usize = 38;
     const PART_2: usize = 37;
     const PART_3: usize = 37;
+    const PART_4: usize = 35;
 
-    // Read the entire attribute database, which requires 3 reads to complete
+    // Read the entire attribute database, which requires 5 reads to complete
     init_env_logger();
 
     let im = ImEngine::new_default();
@@ -244,7 +249,18 @@ fn test_long_read_success() {
                     status: IMStatusCode::Success,
                 },
                 TestReportDataMsg {
-                    attr_reports: Some(&ATTR_RESPS[PART_1..][PART_2..][PART_3..]),
+                    attr_reports: Some(&ATTR_RESPS[PART_1..][PART_2..][PART_3..][..PART_4]),
+                    more_chunks: Some(true),
+                    ..Default::default()
+                },
+                ReplyProcessor::remove_attr_data,
+            ),
+            &TLVTest::continue_report(
+                StatusResp {
+                    status: IMStatusCode::Success,
+                },
+                TestReportDataMsg {
+                    attr_reports: Some(&ATTR_RESPS[PART_1..][PART_2..][PART_3..][PART_4..]),
                     suppress_response: Some(true),
                     ..Default::default()
                 },
@@ -259,8 +275,9 @@ fn test_long_read_subscription_success() {
     const PART_1: usize = 38;
     const PART_2: usize = 37;
     const PART_3: usize = 37;
+    const PART_4: usize = 35;
 
-    // Subscribe to the entire attribute database, which requires 3 reads to complete
+    // Subscribe to the entire attribute database, which requires 5 reads to complete
     init_env_logger();
 
     let im = ImEngine::new_default();
@@ -315,7 +332,19 @@ fn test_long_read_subscription_success() {
                 },
                 TestReportDataMsg {
                     subscription_id: Some(1),
-                    attr_reports: Some(&ATTR_RESPS[PART_1..][PART_2..][PART_3..]),
+                    attr_reports: Some(&ATTR_RESPS[PART_1..][PART_2..][PART_3..][..PART_4]),
+                    more_chunks: Some(true),
+                    ..Default::default()
+                },
+                ReplyProcessor::remove_attr_data,
+            ),
+            &TLVTest::continue_report(
+                StatusResp {
+                    status: IMStatusCode::Success,
+                },
+                TestReportDataMsg {
+                    subscription_id: Some(1),
+                    attr_reports: Some(&ATTR_RESPS[PART_1..][PART_2..][PART_3..][PART_4..]),
                     ..Default::default()
                 },
                 ReplyProcessor::remove_attr_data,
@@ -16,8 +16,11 @@
  */
 
 mod acl_and_dataver;
+mod atomic_writes;
 mod attribute_lists;
 mod attributes;
 mod commands;
 mod long_reads;
+mod scenes;
+mod thermostat;
 mod timed_requests;
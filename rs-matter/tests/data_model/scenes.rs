@@ -0,0 +1,112 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use rs_matter::data_model::device_types::DEV_TYPE_ON_OFF_LIGHT;
+use rs_matter::data_model::objects::{Async, Dataver, Endpoint, Node};
+use rs_matter::data_model::scenes::{self, ClusterHandler as _, ScenesHandler};
+use rs_matter::interaction_model::core::IMStatusCode;
+use rs_matter::interaction_model::messages::ib::{CmdPath, CmdStatus};
+use rs_matter::tlv::ToTLV;
+
+use crate::common::e2e::im::commands::{TestCmdData, TestCmdResp};
+use crate::common::e2e::test::E2eTest;
+use crate::common::e2e::tlv::TLVTest;
+use crate::common::e2e::ImEngine;
+use crate::common::init_env_logger;
+
+/// A `StoreSceneRequest` / `RecallSceneRequest` alternative more suitable for testing.
+#[derive(Debug, Clone, ToTLV)]
+struct TestSceneRequest {
+    group_id: u16,
+    scene_id: u8,
+}
+
+/// A `StoreSceneResponse` alternative more suitable for testing.
+#[derive(Debug, Clone, ToTLV)]
+struct TestSceneResponse {
+    status: IMStatusCode,
+    group_id: u16,
+    scene_id: u8,
+}
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[Endpoint::new(
+        1,
+        &[DEV_TYPE_ON_OFF_LIGHT],
+        &[ScenesHandler::CLUSTER],
+    )],
+};
+
+fn cmd_path(cmd: u32) -> CmdPath {
+    CmdPath::new(Some(1), Some(ScenesHandler::CLUSTER.id), Some(cmd))
+}
+
+#[test]
+fn test_recall_scene() {
+    // Recall a scene, store it, and then recall it again
+    // - recalling a scene which does not exist should fail with NotFound
+    init_env_logger();
+
+    let recall_path = cmd_path(scenes::CommandId::RecallScene as _);
+    let store_path = cmd_path(scenes::CommandId::StoreScene as _);
+
+    let scene = TestSceneRequest {
+        group_id: 0,
+        scene_id: 1,
+    };
+
+    let recall_req = &[TestCmdData::new(recall_path.clone(), &scene)];
+    let recall_not_found_resp = &[TestCmdResp::Status(CmdStatus::new(
+        recall_path.clone(),
+        IMStatusCode::NotFound,
+        0,
+    ))];
+    let recall_resp = &[TestCmdResp::Status(CmdStatus::new(
+        recall_path,
+        IMStatusCode::Success,
+        0,
+    ))];
+
+    let store_req = &[TestCmdData::new(store_path, &scene)];
+    let store_resp_data = TestSceneResponse {
+        status: IMStatusCode::Success,
+        group_id: 0,
+        scene_id: 1,
+    };
+    let store_resp = &[TestCmdResp::Cmd(TestCmdData::new(
+        cmd_path(scenes::CommandResponseId::StoreSceneResponse as _),
+        &store_resp_data,
+    ))];
+
+    let im = ImEngine::new_default();
+    let handler = (
+        NODE,
+        Async(ScenesHandler::new(Dataver::new_rand(im.matter.rand())).adapt()),
+    );
+
+    im.add_default_acl();
+
+    im.test_each(
+        &handler,
+        [
+            &TLVTest::inv_cmds(recall_req, recall_not_found_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(store_req, store_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(recall_req, recall_resp) as &dyn E2eTest,
+        ],
+    );
+}
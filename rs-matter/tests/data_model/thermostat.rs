@@ -0,0 +1,191 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use rs_matter::data_model::device_types::DEV_TYPE_THERMOSTAT;
+use rs_matter::data_model::objects::{Async, AtomicRequestType, Dataver, Endpoint, Node};
+use rs_matter::data_model::thermostat::{
+    self, ClusterHandler as _, PresetScenarioEnum, ThermostatHandler,
+};
+use rs_matter::interaction_model::core::IMStatusCode;
+use rs_matter::interaction_model::messages::ib::{AttrPath, AttrStatus, CmdPath, CmdStatus};
+use rs_matter::interaction_model::messages::GenericPath;
+use rs_matter::tlv::{Nullable, OctetStr, Octets, ToTLV};
+
+use crate::common::e2e::im::attributes::TestAttrData;
+use crate::common::e2e::im::commands::{TestCmdData, TestCmdResp};
+use crate::common::e2e::test::E2eTest;
+use crate::common::e2e::tlv::TLVTest;
+use crate::common::e2e::ImEngine;
+use crate::common::init_env_logger;
+
+/// An `AtomicRequest` alternative more suitable for testing.
+#[derive(Debug, Clone, ToTLV)]
+struct TestAtomicRequest<'a> {
+    request_type: AtomicRequestType,
+    attribute_requests: &'a [u32],
+    timeout: Option<u16>,
+}
+
+#[derive(Debug, Clone, ToTLV)]
+struct TestAtomicAttributeStatus {
+    attribute_id: u32,
+    status_code: IMStatusCode,
+}
+
+/// An `AtomicResponse` alternative more suitable for testing.
+#[derive(Debug, Clone, ToTLV)]
+struct TestAtomicResponse<'a> {
+    status_code: IMStatusCode,
+    attribute_status: &'a [TestAtomicAttributeStatus],
+    timeout: Option<u16>,
+}
+
+/// A `PresetStruct` alternative more suitable for testing.
+#[derive(Debug, Clone, ToTLV)]
+struct TestPreset<'a> {
+    preset_handle: Nullable<OctetStr<'a>>,
+    preset_scenario: PresetScenarioEnum,
+    name: Option<Nullable<&'a str>>,
+    cooling_setpoint: Option<i16>,
+    heating_setpoint: Option<i16>,
+    built_in: Nullable<bool>,
+}
+
+/// A `SetActivePresetRequestRequest` alternative more suitable for testing.
+#[derive(Debug, Clone, ToTLV)]
+struct TestSetActivePresetRequest<'a> {
+    preset_handle: Nullable<OctetStr<'a>>,
+}
+
+const PRESETS: u32 = thermostat::AttributeId::Presets as _;
+
+const PRESETS_SUCCESS: &[TestAtomicAttributeStatus] = &[TestAtomicAttributeStatus {
+    attribute_id: PRESETS,
+    status_code: IMStatusCode::Success,
+}];
+
+const NODE: Node<'static> = Node {
+    id: 0,
+    endpoints: &[Endpoint::new(
+        1,
+        &[DEV_TYPE_THERMOSTAT],
+        &[ThermostatHandler::CLUSTER],
+    )],
+};
+
+fn cmd_path(cmd: u32) -> CmdPath {
+    CmdPath::new(Some(1), Some(ThermostatHandler::CLUSTER.id), Some(cmd))
+}
+
+#[test]
+fn test_presets_atomic_write() {
+    // Write the presets in an atomic write, and then make one of them active
+    // - the written presets should only be applied once committed
+    init_env_logger();
+
+    let presets = GenericPath::new(Some(1), Some(ThermostatHandler::CLUSTER.id), Some(PRESETS));
+
+    let atomic_path = cmd_path(thermostat::CommandId::AtomicRequest as _);
+    let atomic_resp_path = cmd_path(thermostat::CommandResponseId::AtomicResponse as _);
+    let set_active_path = cmd_path(thermostat::CommandId::SetActivePresetRequest as _);
+
+    let begin_req_data = TestAtomicRequest {
+        request_type: AtomicRequestType::BeginWrite,
+        attribute_requests: &[PRESETS],
+        timeout: Some(5000),
+    };
+    let begin_req = &[TestCmdData::new(atomic_path.clone(), &begin_req_data)];
+    let begin_resp_data = TestAtomicResponse {
+        status_code: IMStatusCode::Success,
+        attribute_status: PRESETS_SUCCESS,
+        timeout: Some(5000),
+    };
+    let begin_resp = &[TestCmdResp::Cmd(TestCmdData::new(
+        atomic_resp_path.clone(),
+        &begin_resp_data,
+    ))];
+
+    // A new preset, which is assigned a handle on commit
+    let presets_val: &[TestPreset] = &[TestPreset {
+        preset_handle: Nullable::none(),
+        preset_scenario: PresetScenarioEnum::Occupied,
+        name: Some(Nullable::some("Home")),
+        cooling_setpoint: Some(2500),
+        heating_setpoint: Some(2100),
+        built_in: Nullable::none(),
+    }];
+    let write_req = &[TestAttrData::new(
+        None,
+        AttrPath::new(&presets),
+        &presets_val as _,
+    )];
+    let write_resp = &[AttrStatus::new(&presets, IMStatusCode::Success, 0)];
+
+    let commit_req_data = TestAtomicRequest {
+        request_type: AtomicRequestType::CommitWrite,
+        attribute_requests: &[PRESETS],
+        timeout: None,
+    };
+    let commit_req = &[TestCmdData::new(atomic_path, &commit_req_data)];
+    let commit_resp_data = TestAtomicResponse {
+        status_code: IMStatusCode::Success,
+        attribute_status: PRESETS_SUCCESS,
+        timeout: None,
+    };
+    let commit_resp = &[TestCmdResp::Cmd(TestCmdData::new(
+        atomic_resp_path,
+        &commit_resp_data,
+    ))];
+
+    let set_active_req_data = TestSetActivePresetRequest {
+        preset_handle: Nullable::some(Octets(&[0])),
+    };
+    let set_active_req = &[TestCmdData::new(
+        set_active_path.clone(),
+        &set_active_req_data,
+    )];
+    let set_active_unknown_resp = &[TestCmdResp::Status(CmdStatus::new(
+        set_active_path.clone(),
+        IMStatusCode::InvalidCommand,
+        0,
+    ))];
+    let set_active_resp = &[TestCmdResp::Status(CmdStatus::new(
+        set_active_path,
+        IMStatusCode::Success,
+        0,
+    ))];
+
+    let im = ImEngine::new_default();
+    let handler = (
+        NODE,
+        Async(ThermostatHandler::new(Dataver::new_rand(im.matter.rand())).adapt()),
+    );
+
+    im.add_default_acl();
+
+    im.test_each(
+        &handler,
+        [
+            &TLVTest::inv_cmds(begin_req, begin_resp) as &dyn E2eTest,
+            &TLVTest::write_attrs(write_req, write_resp) as &dyn E2eTest,
+            // The preset does not exist until the write is committed
+            &TLVTest::inv_cmds(set_active_req, set_active_unknown_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(commit_req, commit_resp) as &dyn E2eTest,
+            &TLVTest::inv_cmds(set_active_req, set_active_resp) as &dyn E2eTest,
+        ],
+    );
+}
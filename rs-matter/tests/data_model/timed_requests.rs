@@ -16,12 +16,13 @@
  */
 
 use rs_matter::interaction_model::core::{IMStatusCode, OpCode, PROTO_ID_INTERACTION_MODEL};
-use rs_matter::interaction_model::messages::ib::{AttrPath, AttrStatus};
+use rs_matter::interaction_model::messages::ib::{AttrPath, AttrStatus, CmdPath, CmdStatus};
 use rs_matter::interaction_model::messages::msg::{StatusResp, TimedReq};
 use rs_matter::interaction_model::messages::GenericPath;
 use rs_matter::transport::exchange::MessageMeta;
 
 use crate::common::e2e::im::attributes::TestAttrData;
+use crate::common::e2e::im::commands::TestCmdResp;
 use crate::common::e2e::im::{
     echo_cluster, ReplyProcessor, TestInvReq, TestInvResp, TestWriteReq, TestWriteResp,
 };
@@ -29,7 +30,7 @@ use crate::common::e2e::test::E2eTest;
 use crate::common::e2e::tlv::TLVTest;
 use crate::common::e2e::ImEngine;
 use crate::common::init_env_logger;
-use crate::{echo_req, echo_resp};
+use crate::{cmd_data, echo_req, echo_resp};
 
 #[test]
 fn test_timed_write_fail_and_success() {
@@ -198,8 +199,8 @@ fn test_timed_cmd_success() {
     // A timed request that works
     init_env_logger();
 
-    let input = &[echo_req!(0, 5), echo_req!(1, 10)];
-    let expected = &[echo_resp!(0, 10), echo_resp!(1, 30)];
+    let input = &[echo_req!(0, 5, 1), echo_req!(1, 10, 2)];
+    let expected = &[echo_resp!(0, 10, 1), echo_resp!(1, 30, 2)];
 
     let im = ImEngine::new_default();
     let handler = im.handler();
@@ -236,7 +237,7 @@ fn test_timed_cmd_timeout() {
     // A timed request that is executed after a timeout
     init_env_logger();
 
-    let input = &[echo_req!(0, 5), echo_req!(1, 10)];
+    let input = &[echo_req!(0, 5, 1), echo_req!(1, 10, 2)];
 
     let im = ImEngine::new_default();
     let handler = im.handler();
@@ -285,7 +286,7 @@ fn test_timed_cmd_timeout_mismatch() {
     // A timed request with timeout mismatch
     init_env_logger();
 
-    let input = &[echo_req!(0, 5), echo_req!(1, 10)];
+    let input = &[echo_req!(0, 5, 1), echo_req!(1, 10, 2)];
 
     let im = ImEngine::new_default();
     let handler = im.handler();
@@ -352,3 +353,59 @@ fn test_timed_cmd_timeout_mismatch() {
         ],
     );
 }
+
+#[test]
+fn test_timed_only_cmd() {
+    // A command which can only be invoked as part of a timed interaction
+    // - fails with NeedsTimedInteraction when invoked outside of one
+    // - succeeds when invoked as part of one
+    init_env_logger();
+
+    let path = CmdPath::new(
+        Some(0),
+        Some(echo_cluster::ID),
+        Some(echo_cluster::Commands::TimedEchoReq as u32),
+    );
+    let input = &[cmd_data!(path.clone(), 5)];
+
+    ImEngine::commands(
+        input,
+        &[TestCmdResp::Status(CmdStatus::new(
+            path.clone(),
+            IMStatusCode::NeedsTimedInteraction,
+            0,
+        ))],
+    );
+
+    let im = ImEngine::new_default();
+    let handler = im.handler();
+    im.add_default_acl();
+
+    im.test_all(
+        &handler,
+        [
+            &TLVTest {
+                delay_ms: None,
+                ..TLVTest::timed(
+                    TimedReq { timeout: 2000 },
+                    StatusResp {
+                        status: IMStatusCode::Success,
+                    },
+                    ReplyProcessor::none,
+                )
+            } as &dyn E2eTest,
+            &TLVTest::invoke(
+                TestInvReq {
+                    timed_request: Some(true),
+                    ..TestInvReq::reqs(input)
+                },
+                TestInvResp::resp(&[TestCmdResp::Status(CmdStatus::new(
+                    path,
+                    IMStatusCode::Success,
+                    0,
+                ))]),
+                ReplyProcessor::none,
+            ),
+        ],
+    );
+}
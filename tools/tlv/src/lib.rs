@@ -1,3 +1,5 @@
+pub mod pcap;
+
 #[derive(Clone, Copy, Debug)]
 pub enum InputBase {
     Hex,
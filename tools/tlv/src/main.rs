@@ -15,11 +15,16 @@
  *    limitations under the License.
  */
 
+use std::fs::File;
+use std::io::BufReader;
+
 use clap::{App, Arg};
 use rs_matter::cert;
 use rs_matter::tlv;
+use rs_matter::transport::capture::PcapngReader;
 use simple_logger::SimpleLogger;
 
+use parser::pcap::{ClusterNames, DecodedPacket};
 use parser::InputBase;
 
 fn main() {
@@ -57,9 +62,31 @@ fn main() {
                 .long("as-asn1")
                 .help("Decode a Matter-encoded Certificate and encode as ASN1"),
         )
-        .arg(Arg::with_name("tlvs").help("List of TLVs").required(true))
+        .arg(
+            Arg::with_name("pcapng")
+                .long("pcapng")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Decode the Matter messages captured in a pcapng file"),
+        )
+        .arg(
+            Arg::with_name("tlvs")
+                .help("List of TLVs")
+                .required_unless("pcapng"),
+        )
         .get_matches();
 
+    if let Some(path) = m.value_of("pcapng") {
+        let names = ClusterNames::standard();
+
+        let mut reader = PcapngReader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            println!("{}", DecodedPacket::new(&names, &packet));
+        }
+
+        return;
+    }
+
     if m.is_present("dec") {
         base = InputBase::Dec;
     }
@@ -0,0 +1,388 @@
+/*
+ *
+ *    Copyright (c) 2020-2022 Project CHIP Authors
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Offline decoding of Matter messages captured with `rs_matter::transport::capture::PcapngCapture`.
+//!
+//! The Interaction Model paths in the captured messages are annotated with the names of
+//! their clusters, attributes, commands and events, as found in the standard clusters' IDL
+//! (the one used by the `import!` macro).
+
+use std::collections::HashMap;
+use std::fmt;
+
+use rs_matter::interaction_model::core::PROTO_ID_INTERACTION_MODEL;
+use rs_matter::tlv::TLVElement;
+use rs_matter::transport::capture::CapturedPacket;
+use rs_matter_data_model::idl::Idl;
+use rs_matter_data_model::{StructType, CSA_STANDARD_CLUSTERS_IDL};
+
+const GLOBAL_ATTRIBUTES: &[(u32, &str)] = &[
+    (0xfff8, "GeneratedCommandList"),
+    (0xfff9, "AcceptedCommandList"),
+    (0xfffa, "EventList"),
+    (0xfffb, "AttributeList"),
+    (0xfffc, "FeatureMap"),
+    (0xfffd, "ClusterRevision"),
+];
+
+#[derive(Default)]
+struct ClusterInfo {
+    name: String,
+    attributes: HashMap<u32, String>,
+    commands: HashMap<u32, String>,
+    responses: HashMap<u32, String>,
+    events: HashMap<u32, String>,
+}
+
+/// The names of the clusters and their attributes, commands and events, by ID
+pub struct ClusterNames(HashMap<u32, ClusterInfo>);
+
+impl ClusterNames {
+    /// Create the names of all standard clusters
+    pub fn standard() -> Self {
+        Self::new(&Idl::parse(CSA_STANDARD_CLUSTERS_IDL.into()).unwrap())
+    }
+
+    /// Create the names of all clusters in the provided IDL
+    pub fn new(idl: &Idl) -> Self {
+        let clusters = idl
+            .clusters
+            .iter()
+            .map(|cluster| {
+                let info = ClusterInfo {
+                    name: cluster.id.clone(),
+                    attributes: cluster
+                        .attributes
+                        .iter()
+                        .map(|attr| (attr.field.field.code as u32, attr.field.field.id.clone()))
+                        .collect(),
+                    commands: cluster
+                        .commands
+                        .iter()
+                        .map(|cmd| (cmd.code as u32, cmd.id.clone()))
+                        .collect(),
+                    responses: cluster
+                        .structs
+                        .iter()
+                        .filter_map(|s| match s.struct_type {
+                            StructType::Response(code) => Some((code as u32, s.id.clone())),
+                            _ => None,
+                        })
+                        .collect(),
+                    events: cluster
+                        .events
+                        .iter()
+                        .map(|event| (event.code as u32, event.id.clone()))
+                        .collect(),
+                };
+
+                (cluster.code as u32, info)
+            })
+            .collect();
+
+        Self(clusters)
+    }
+
+    /// Return the name of the provided cluster
+    pub fn cluster(&self, cluster: u32) -> Option<&str> {
+        self.0.get(&cluster).map(|info| info.name.as_str())
+    }
+
+    /// Return the name of the provided attribute, including the global ones
+    pub fn attribute(&self, cluster: u32, attr: u32) -> Option<&str> {
+        GLOBAL_ATTRIBUTES
+            .iter()
+            .find(|(id, _)| *id == attr)
+            .map(|(_, name)| *name)
+            .or_else(|| {
+                self.0
+                    .get(&cluster)
+                    .and_then(|info| info.attributes.get(&attr))
+                    .map(String::as_str)
+            })
+    }
+
+    /// Return the name of the provided command
+    pub fn command(&self, cluster: u32, cmd: u32) -> Option<&str> {
+        self.0
+            .get(&cluster)
+            .and_then(|info| info.commands.get(&cmd))
+            .map(String::as_str)
+    }
+
+    /// Return the name of the provided command response
+    pub fn response(&self, cluster: u32, cmd: u32) -> Option<&str> {
+        self.0
+            .get(&cluster)
+            .and_then(|info| info.responses.get(&cmd))
+            .map(String::as_str)
+    }
+
+    /// Return the name of the provided event
+    pub fn event(&self, cluster: u32, event: u32) -> Option<&str> {
+        self.0
+            .get(&cluster)
+            .and_then(|info| info.events.get(&event))
+            .map(String::as_str)
+    }
+
+    fn leaf(&self, kind: ImPathKind, cluster: u32, leaf: u32) -> Option<&str> {
+        match kind {
+            ImPathKind::Attribute => self.attribute(cluster, leaf),
+            ImPathKind::Command => self.command(cluster, leaf),
+            ImPathKind::CommandResponse => self.response(cluster, leaf),
+            ImPathKind::Event => self.event(cluster, leaf),
+            ImPathKind::Cluster => None,
+        }
+    }
+}
+
+/// The type of an Interaction Model path
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImPathKind {
+    Attribute,
+    Command,
+    CommandResponse,
+    Event,
+    Cluster,
+}
+
+impl ImPathKind {
+    /// Return the type of the paths in the field with the provided context tag
+    /// of an Interaction Model message with the provided opcode
+    fn new(opcode: u8, field: u8) -> Option<Self> {
+        match (opcode, field) {
+            // ReadRequest
+            (2, 0) => Some(Self::Attribute),
+            (2, 1) => Some(Self::Event),
+            (2, 4) => Some(Self::Cluster),
+            // SubscribeRequest
+            (3, 3) => Some(Self::Attribute),
+            (3, 4) => Some(Self::Event),
+            (3, 7) => Some(Self::Cluster),
+            // ReportData
+            (5, 1) => Some(Self::Attribute),
+            (5, 2) => Some(Self::Event),
+            // WriteRequest
+            (6, 2) => Some(Self::Attribute),
+            // WriteResponse
+            (7, 0) => Some(Self::Attribute),
+            // InvokeRequest
+            (8, 2) => Some(Self::Command),
+            // InvokeResponse
+            (9, 1) => Some(Self::Command),
+            _ => None,
+        }
+    }
+
+    /// Return the context tags of the endpoint, cluster and leaf in a path of this type
+    fn tags(&self) -> (u8, u8, Option<u8>) {
+        match self {
+            Self::Attribute => (2, 3, Some(4)),
+            Self::Command | Self::CommandResponse => (0, 1, Some(2)),
+            Self::Event => (1, 2, Some(3)),
+            Self::Cluster => (1, 2, None),
+        }
+    }
+}
+
+/// An Interaction Model path found in a captured message
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ImPath {
+    pub kind: ImPathKind,
+    pub endpoint: Option<u16>,
+    pub cluster: Option<u32>,
+    pub leaf: Option<u32>,
+}
+
+impl ImPath {
+    /// Return all paths in the provided Interaction Model message
+    pub fn collect(opcode: u8, payload: &[u8]) -> Vec<ImPath> {
+        let mut paths = Vec::new();
+
+        if let Ok(fields) = TLVElement::new(payload).structure() {
+            for field in fields.iter().flatten() {
+                let kind = field
+                    .try_ctx()
+                    .ok()
+                    .flatten()
+                    .and_then(|tag| ImPathKind::new(opcode, tag));
+
+                if let Some(kind) = kind {
+                    Self::collect_nested(kind, &field, None, &mut paths);
+                }
+            }
+        }
+
+        paths
+    }
+
+    fn collect_nested(
+        kind: ImPathKind,
+        element: &TLVElement,
+        parent_tag: Option<u8>,
+        paths: &mut Vec<ImPath>,
+    ) {
+        if let Ok(list) = element.list() {
+            // Paths are the only TLV lists in the Interaction Model messages
+            let kind = if kind == ImPathKind::Command && parent_tag == Some(0) {
+                // A `CommandDataIB` in an `InvokeResponseIB`
+                ImPathKind::CommandResponse
+            } else {
+                kind
+            };
+
+            let (endpoint, cluster, leaf) = kind.tags();
+
+            paths.push(ImPath {
+                kind,
+                endpoint: list.find_ctx(endpoint).and_then(|e| e.u16()).ok(),
+                cluster: list.find_ctx(cluster).and_then(|e| e.u32()).ok(),
+                leaf: leaf.and_then(|leaf| list.find_ctx(leaf).and_then(|e| e.u32()).ok()),
+            });
+        } else if let Ok(container) = element.container() {
+            let tag = element.try_ctx().ok().flatten();
+
+            for child in container.iter().flatten() {
+                Self::collect_nested(kind, &child, tag, paths);
+            }
+        }
+    }
+
+    /// Return a displayable representation of the path, with names resolved using the provided cluster names
+    pub fn display<'a>(&'a self, names: &'a ClusterNames) -> impl fmt::Display + 'a {
+        ImPathDisplay(self, names)
+    }
+}
+
+struct ImPathDisplay<'a>(&'a ImPath, &'a ClusterNames);
+
+impl fmt::Display for ImPathDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ImPathDisplay(path, names) = self;
+
+        write!(f, "{:?} ", path.kind)?;
+
+        match path.endpoint {
+            Some(endpoint) => write!(f, "Endpoint {endpoint}")?,
+            None => write!(f, "Endpoint *")?,
+        }
+
+        match path.cluster {
+            Some(cluster) => match names.cluster(cluster) {
+                Some(name) => write!(f, " / {name} (0x{cluster:04x})")?,
+                None => write!(f, " / 0x{cluster:04x}")?,
+            },
+            None => write!(f, " / *")?,
+        }
+
+        if path.kind != ImPathKind::Cluster {
+            match path.leaf {
+                Some(leaf) => match path
+                    .cluster
+                    .and_then(|cluster| names.leaf(path.kind, cluster, leaf))
+                {
+                    Some(name) => write!(f, " / {name} (0x{leaf:04x})")?,
+                    None => write!(f, " / 0x{leaf:04x}")?,
+                },
+                None => write!(f, " / *")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A captured packet, displayed with its Interaction Model paths annotated with names
+pub struct DecodedPacket<'a> {
+    names: &'a ClusterNames,
+    packet: &'a CapturedPacket,
+}
+
+impl<'a> DecodedPacket<'a> {
+    pub const fn new(names: &'a ClusterNames, packet: &'a CapturedPacket) -> Self {
+        Self { names, packet }
+    }
+}
+
+impl fmt::Display for DecodedPacket<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.packet)?;
+
+        if self.packet.header.proto.proto_id == PROTO_ID_INTERACTION_MODEL {
+            let paths =
+                ImPath::collect(self.packet.header.proto.proto_opcode, &self.packet.payload);
+
+            if !paths.is_empty() {
+                writeln!(f, "Paths:")?;
+
+                for path in &paths {
+                    writeln!(f, "  {}", path.display(self.names))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rs_matter::tlv::{TLVTag, TLVWrite};
+    use rs_matter::utils::storage::WriteBuf;
+
+    use super::{ClusterNames, ImPath, ImPathKind};
+
+    #[test]
+    fn test_invoke_request_paths() {
+        let mut buf = [0; 64];
+        let mut wb = WriteBuf::new(&mut buf);
+
+        // InvokeRequest { InvokeRequests: [ { CommandPath: [1, 6, 2], CommandFields: {} } ] }
+        wb.start_struct(&TLVTag::Anonymous).unwrap();
+        wb.start_array(&TLVTag::Context(2)).unwrap();
+        wb.start_struct(&TLVTag::Anonymous).unwrap();
+        wb.start_list(&TLVTag::Context(0)).unwrap();
+        wb.u16(&TLVTag::Context(0), 1).unwrap();
+        wb.u32(&TLVTag::Context(1), 6).unwrap();
+        wb.u32(&TLVTag::Context(2), 2).unwrap();
+        wb.end_container().unwrap();
+        wb.start_struct(&TLVTag::Context(1)).unwrap();
+        wb.end_container().unwrap();
+        wb.end_container().unwrap();
+        wb.end_container().unwrap();
+        wb.end_container().unwrap();
+
+        let paths = ImPath::collect(8, wb.as_slice());
+        assert_eq!(
+            paths,
+            vec![ImPath {
+                kind: ImPathKind::Command,
+                endpoint: Some(1),
+                cluster: Some(6),
+                leaf: Some(2),
+            }]
+        );
+
+        let names = ClusterNames::standard();
+        assert_eq!(
+            paths[0].display(&names).to_string(),
+            "Command Endpoint 1 / OnOff (0x0006) / Toggle (0x0002)"
+        );
+        assert_eq!(names.attribute(6, 0xfffd), Some("ClusterRevision"));
+    }
+}